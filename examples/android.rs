@@ -0,0 +1,41 @@
+//! The app on Android phones and tablets, packaged with [cargo-apk](https://github.com/rust-mobile/cargo-apk):
+//!
+//! ```bash
+//! cargo apk run --example android --features android
+//! ```
+#![cfg(target_os = "android")]
+
+use clap::Parser;
+use show_gpu_compute_image::{Options, run_app};
+use winit::{
+    event_loop::EventLoop,
+    platform::android::{EventLoopBuilderExtAndroid, activity::AndroidApp},
+    window::Window,
+};
+
+/// Called by the `NativeActivity` instead of `main`
+#[unsafe(no_mangle)]
+fn android_main(app: AndroidApp) {
+    // Standard error goes nowhere on Android, see `adb logcat -s gpgpu`
+    android_logger::init_once(
+        android_logger::Config::default()
+            .with_max_level(log::LevelFilter::Info)
+            .with_tag("gpgpu"),
+    );
+
+    let event_loop = EventLoop::with_user_event()
+        .with_android_app(app)
+        .build()
+        .unwrap();
+    // There is no command line, the activity always covers the whole screen
+    let options = Options::parse_from([env!("CARGO_PKG_NAME")]);
+    run_app(
+        event_loop,
+        options,
+        Box::new(|event_loop| {
+            event_loop
+                .create_window(Window::default_attributes())
+                .unwrap()
+        }),
+    );
+}
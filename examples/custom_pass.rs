@@ -0,0 +1,51 @@
+//! Add a post-effect pass of your own to the app, here one posterizing the image
+//! into `params.x` levels per channel.
+//!
+//! ```bash
+//! cargo run --example custom_pass
+//! ```
+
+use std::fs;
+
+use clap::Parser;
+use show_gpu_compute_image::{Options, run_app};
+use winit::{event_loop::EventLoop, window::Window};
+
+/// See `src/shaders/post_effect.wgsl` for what effects can use
+const POSTERIZE: &str = "
+fn effect(pixel: vec2<u32>) -> vec4<f32> {
+    let color = load_input(vec2<i32>(pixel));
+    return vec4<f32>(floor(color.rgb * params.x) / params.x, color.a);
+}
+";
+
+const MANIFEST: &str = "
+[[pass]]
+shader = \"posterize.wgsl\"
+params = [4.0]
+";
+
+fn main() -> std::io::Result<()> {
+    let dir = std::env::temp_dir().join("custom_pass");
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("posterize.wgsl"), POSTERIZE)?;
+    fs::write(dir.join("effects.toml"), MANIFEST)?;
+
+    let options = Options::parse_from([
+        "custom_pass".as_ref(),
+        "--example".as_ref(),
+        "plasma".as_ref(),
+        "--post-effects".as_ref(),
+        dir.as_os_str(),
+    ]);
+    let event_loop = EventLoop::with_user_event().build().unwrap();
+    run_app(
+        event_loop,
+        options,
+        Box::new(|event_loop| {
+            let attributes = Window::default_attributes().with_title("Custom post effect");
+            event_loop.create_window(attributes).unwrap()
+        }),
+    );
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! Run the app in a window created by the embedding program, with its own title and size.
+//!
+//! ```bash
+//! cargo run --example embed_in_winit
+//! ```
+
+use clap::Parser;
+use show_gpu_compute_image::{Options, run_app};
+use winit::{dpi::LogicalSize, event_loop::EventLoop, window::Window};
+
+fn main() {
+    let event_loop = EventLoop::with_user_event().build().unwrap();
+
+    // The app draws at this resolution whatever the window size, and fits it in
+    let options = Options::parse_from([
+        "embed_in_winit",
+        "--example",
+        "raymarch",
+        "--resolution",
+        "1280x720",
+    ]);
+    // The window is created once the event loop runs
+    run_app(
+        event_loop,
+        options,
+        Box::new(|event_loop| {
+            let attributes = Window::default_attributes()
+                .with_title("Embedded compute image")
+                .with_inner_size(LogicalSize::new(800, 450));
+            event_loop.create_window(attributes).unwrap()
+        }),
+    );
+}
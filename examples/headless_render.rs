@@ -0,0 +1,27 @@
+//! Draw a bundled example without a window and write it to a PNG.
+//!
+//! ```bash
+//! cargo run --example headless_render -- plasma.png
+//! ```
+
+use std::{env, path::PathBuf};
+
+use clap::Parser;
+use show_gpu_compute_image::{Headless, Options, write_png};
+
+fn main() -> Result<(), String> {
+    let output = env::args()
+        .nth(1)
+        .map_or_else(|| PathBuf::from("headless.png"), PathBuf::from);
+    // Any command line option of the app works here, e.g. `--shader draw.wgsl` or `--backend gl`
+    let options = Options::parse_from(["headless_render", "--example", "plasma"]);
+
+    let (width, height) = (640, 360);
+    let mut headless = Headless::new(&options, width, height)?;
+    headless.globals.globals.time = 2.5;
+    let pixels = headless.draw()?;
+
+    write_png(&output, width, height, &pixels).map_err(|err| err.to_string())?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
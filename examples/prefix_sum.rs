@@ -0,0 +1,64 @@
+//! Sort pseudo-random values and take their prefix sums with the GPU algorithms of the crate,
+//! with no image involved, and check the result on the CPU.
+//!
+//! ```bash
+//! cargo run --example prefix_sum
+//! WGPU_BACKEND=gl cargo run --example prefix_sum
+//! ```
+
+use show_gpu_compute_image::{GpuScan, GpuSort, ScanKind, counters};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+/// More than one block of the scan, so the block totals are scanned a level up
+const LENGTH: u32 = 100_000;
+
+fn main() -> Result<(), String> {
+    let instance = Instance::new(InstanceDescriptor {
+        backends: util::backend_bits_from_env().unwrap_or_default(),
+        ..Default::default()
+    });
+    let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions::default()))
+        .ok_or("No adapter found")?;
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &DeviceDescriptor {
+            required_limits: Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            ..Default::default()
+        },
+        None,
+    ))
+    .map_err(|err| err.to_string())?;
+
+    // Pseudo-random values from 0 to 999
+    let input: Vec<u32> = (0..LENGTH)
+        .map(|index| index.wrapping_mul(2_654_435_761) >> 22)
+        .collect();
+    let values = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Values Buffer"),
+        contents: bytemuck::cast_slice(&input),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+    });
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+    GpuSort::new(&device).sort_keys(&device, &mut encoder, &values, LENGTH);
+    GpuScan::new(&device).scan(&device, &mut encoder, &values, LENGTH, ScanKind::Inclusive);
+    queue.submit(Some(encoder.finish()));
+    let sums: Vec<u32> = counters::read_buffer(&device, &queue, &values);
+
+    let mut sorted = input.clone();
+    sorted.sort_unstable();
+    let expected: Vec<u32> = sorted
+        .iter()
+        .scan(0, |sum, value| {
+            *sum += value;
+            Some(*sum)
+        })
+        .collect();
+    if sums != expected {
+        return Err("The prefix sums differ from the ones on the CPU".to_owned());
+    }
+    println!(
+        "Prefix sums of {LENGTH} sorted values check out, the last is {}",
+        sums[sums.len() - 1]
+    );
+    Ok(())
+}
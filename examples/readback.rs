@@ -0,0 +1,42 @@
+//! Draw a shader at several `globals.params` and analyze the images on the CPU,
+//! printing the average brightness and hash of each.
+//!
+//! ```bash
+//! cargo run --example readback
+//! ```
+
+use clap::Parser;
+use show_gpu_compute_image::{Headless, Options};
+
+/// Written by the example instead of loaded from a file, `--shader` takes a path
+const SHADER: &str = "
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let uv = vec2<f32>(pixel) / vec2<f32>(globals.resolution);
+    return vec4<f32>(vec3<f32>(step(globals.params.x, uv.x)), 1.0);
+}
+";
+
+fn main() -> Result<(), String> {
+    let shader = std::env::temp_dir().join("readback.wgsl");
+    std::fs::write(&shader, SHADER).map_err(|err| err.to_string())?;
+    let options =
+        Options::parse_from(["readback".as_ref(), "--shader".as_ref(), shader.as_os_str()]);
+
+    let mut headless = Headless::new(&options, 256, 256)?;
+    for step in 0..=4 {
+        let x = step as f32 / 4.0;
+        headless.globals.globals.params = [x, 0.0];
+        // 8-bit sRGB RGBA, rows tightly packed
+        let pixels = headless.draw()?;
+        let brightness = pixels
+            .chunks_exact(4)
+            .map(|pixel| f64::from(pixel[0]) / 255.0)
+            .sum::<f64>()
+            / (pixels.len() / 4) as f64;
+        println!(
+            "params.x {x:.2}: brightness {brightness:.3}, {}",
+            blake3::hash(&pixels)
+        );
+    }
+    Ok(())
+}
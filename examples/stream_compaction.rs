@@ -0,0 +1,87 @@
+//! Compact the indices of the values above a threshold into an append list in `scratch`,
+//! then read it back with the helpers of `counters` and check it on the CPU.
+//!
+//! ```bash
+//! cargo run --example stream_compaction
+//! ```
+
+use std::env;
+
+use clap::Parser;
+use show_gpu_compute_image::{Headless, Options, counters};
+
+/// Every invocation tests one value of the output buffer and appends its index if it passes,
+/// the list and a counter of the rejected values live in `scratch`
+const KERNEL: &str = "
+#include <counters.wgsl>
+
+const THRESHOLD: u32 = 700u;
+const REJECTED: u32 = 0u;
+const LIST: u32 = 1u;
+
+@group(0) @binding(0)
+var<storage, read_write> values: array<u32>;
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn compact(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if gid.x >= arrayLength(&values) {
+        return;
+    }
+    if values[gid.x] > THRESHOLD {
+        append(LIST, SCRATCH_LENGTH - 2u, gid.x);
+    } else {
+        counter_add(REJECTED, 1u);
+    }
+}
+";
+
+const LENGTH: u32 = 4096;
+const THRESHOLD: u32 = 700;
+
+fn main() -> Result<(), String> {
+    let kernel = env::temp_dir().join("stream_compaction.wgsl");
+    std::fs::write(&kernel, KERNEL).map_err(|err| err.to_string())?;
+    // Room for the counter, the length of the list and every value
+    let scratch_size = (u64::from(LENGTH) + 2) * 4;
+    // Further arguments are passed on, e.g. `--backend gl`
+    let options = Options::parse_from(
+        [
+            "stream_compaction".into(),
+            "--shader".into(),
+            kernel.into_os_string(),
+        ]
+        .into_iter()
+        .chain(["--workgroup-size".into(), "64x1".into()])
+        .chain(["--scratch-size".into(), scratch_size.to_string().into()])
+        .chain(env::args_os().skip(1)),
+    );
+
+    let headless = Headless::kernel(&options, LENGTH, 1, u64::from(LENGTH) * 4)?;
+    // Pseudo-random values from 0 to 999
+    let values: Vec<u32> = (0..LENGTH)
+        .map(|i| i.wrapping_mul(2_654_435_761) % 1000)
+        .collect();
+    headless.write_output(0, &values);
+    headless.run_to_vec::<u32>()?;
+    let scratch = headless.read_scratch()?;
+
+    // Appends land in any order
+    let mut kept = counters::list_items(&scratch, 1, LENGTH).to_vec();
+    kept.sort_unstable();
+    let expected: Vec<u32> = (0..LENGTH)
+        .filter(|&i| values[i as usize] > THRESHOLD)
+        .collect();
+    let rejected = counters::counter(&scratch, 0);
+    if kept != expected || rejected as usize != values.len() - expected.len() {
+        return Err(format!(
+            "Kept {} values and rejected {rejected}, expected to keep {}",
+            kept.len(),
+            expected.len()
+        ));
+    }
+    println!(
+        "{} of {LENGTH} values are above {THRESHOLD}, their indices check out",
+        kept.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,10 @@
+// Splits the colors like a cheap lens, red outwards and blue inwards, growing towards the edges.
+// params.x: shift in pixels at the edges
+
+fn effect(pixel: vec2<u32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(input));
+    let uv = (vec2<f32>(pixel) + 0.5) / size;
+    let shift = (uv - 0.5) * 2.0 * params.x / size;
+    let center = sample_input(uv);
+    return vec4<f32>(sample_input(uv + shift).r, center.g, sample_input(uv - shift).b, center.a);
+}
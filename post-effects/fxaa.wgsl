@@ -0,0 +1,40 @@
+// Fast approximate antialiasing after Timothy Lottes' FXAA: finds edges by the contrast of luma
+// and blurs along them. It runs before tonemapping, so colors brighter than 1 count as very bright.
+// params.x: contrast below which nothing is blurred, 1/32 if 0
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn effect(pixel: vec2<u32>) -> vec4<f32> {
+    let p = vec2<i32>(pixel);
+    let texel = 1.0 / vec2<f32>(textureDimensions(input));
+    let uv = (vec2<f32>(pixel) + 0.5) * texel;
+
+    let center = load_input(p);
+    let luma_center = luma(center.rgb);
+    let luma_nw = luma(load_input(p + vec2<i32>(-1, -1)).rgb);
+    let luma_ne = luma(load_input(p + vec2<i32>(1, -1)).rgb);
+    let luma_sw = luma(load_input(p + vec2<i32>(-1, 1)).rgb);
+    let luma_se = luma(load_input(p + vec2<i32>(1, 1)).rgb);
+    let luma_min = min(luma_center, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_center, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    let threshold = select(params.x, 1.0 / 32.0, params.x == 0.0);
+    if luma_max - luma_min < threshold {
+        return center;
+    }
+
+    // Perpendicular to the gradient, along the edge
+    var direction = vec2<f32>(luma_sw + luma_se - luma_nw - luma_ne, luma_nw + luma_sw - luma_ne - luma_se);
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) / 32.0, 1.0 / 128.0);
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2<f32>(-8.0), vec2<f32>(8.0)) * texel;
+
+    let near = 0.5 * (sample_input(uv - direction / 6.0).rgb + sample_input(uv + direction / 6.0).rgb);
+    let far = 0.5 * near + 0.25 * (sample_input(uv - direction / 2.0).rgb + sample_input(uv + direction / 2.0).rgb);
+    let luma_far = luma(far);
+    if luma_far < luma_min || luma_far > luma_max {
+        return vec4<f32>(near, center.a);
+    }
+    return vec4<f32>(far, center.a);
+}
@@ -0,0 +1,11 @@
+// Darkens towards the corners.
+// params.x: how much the corners are darkened, params.y: distance from the center where darkening starts
+
+fn effect(pixel: vec2<u32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(input));
+    let uv = (vec2<f32>(pixel) + 0.5) / size;
+    let distance = length((uv - 0.5) * vec2<f32>(size.x / size.y, 1.0));
+    let darkening = params.x * smoothstep(params.y, params.y + 0.5, distance);
+    let color = load_input(vec2<i32>(pixel));
+    return vec4<f32>(color.rgb * (1.0 - darkening), color.a);
+}
@@ -0,0 +1,259 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{compute::OUTPUT_FORMAT, globals::frame_random, shaders::Shaders};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AccumulationParams {
+    sample_count: u32,
+    _padding: [u32; 3],
+}
+
+/// Sub-frame samples of `--motion-blur`, which the accumulation pass averages into every recorded frame
+pub struct MotionBlur {
+    samples: u32,
+    /// Part of the frame interval the samples are spread over
+    shutter: f32,
+}
+
+impl MotionBlur {
+    pub fn new(samples: u32, shutter: f32) -> Self {
+        Self { samples, shutter }
+    }
+
+    /// Times of the samples of `frame` in timesteps past the frame,
+    /// each at a random point of its even share of the shutter, the same on every run
+    pub fn offsets(&self, frame: u32) -> impl Iterator<Item = f32> + '_ {
+        (0..self.samples).map(move |sample| {
+            let jitter = (frame_random(sample, frame) >> 8) as f32 / 16_777_216.0;
+            self.shutter * (sample as f32 + jitter) / self.samples as f32
+        })
+    }
+}
+
+/// Progressive accumulation of the drawn image.
+///
+/// Every frame the latest sample is added to an `Rgba32Float` accumulation buffer,
+/// and the average is written to `output_view` for presentation.
+/// Meant for noisy shaders like path tracers, which converge over successive frames.
+pub struct AccumulationState {
+    pub pipeline: ComputePipeline,
+    /// `bind_groups[i]` reads from accumulation texture `i` and writes to the other one
+    pub bind_groups: [BindGroup; 2],
+    pub params_buffer: Buffer,
+    pub output_texture: Texture,
+    pub output_view: TextureView,
+    /// Number of samples accumulated since the last reset
+    sample_count: u32,
+    /// Index of the accumulation texture holding the latest sum
+    current: usize,
+    workgroup_size: [u32; 2],
+    width: u32,
+    height: u32,
+}
+
+impl AccumulationState {
+    pub fn new(
+        device: &Device,
+        shaders: &Shaders,
+        sample_view: &TextureView,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let module = shaders
+            .accumulate
+            .as_ref()
+            .expect("Accumulation shader was not created");
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let accumulation_views = [0, 1].map(|_| {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: Some("Accumulation Texture"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba32Float,
+                    usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor::default())
+        });
+
+        let output_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Accumulation Output Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::STORAGE_BINDING
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Accumulation Params Buffer"),
+            contents: bytemuck::bytes_of(&AccumulationParams {
+                sample_count: 0,
+                _padding: [0; 3],
+            }),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Accumulation Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: TextureFormat::Rgba32Float,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: OUTPUT_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Accumulation Bind Group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(sample_view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(&accumulation_views[i]),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::TextureView(&accumulation_views[1 - i]),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(&output_view),
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            compilation_options: Default::default(),
+            label: Some("Accumulation Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Accumulation Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            })),
+            module,
+            entry_point: "main",
+            cache: shaders.pipeline_cache.as_deref(),
+        });
+
+        Self {
+            pipeline,
+            bind_groups,
+            params_buffer,
+            output_texture,
+            output_view,
+            sample_count: 0,
+            current: 0,
+            workgroup_size: shaders.workgroup_size,
+            width,
+            height,
+        }
+    }
+
+    /// Discard everything accumulated so far,
+    /// needed whenever parameters or the camera change
+    pub fn reset(&mut self) {
+        self.sample_count = 0;
+    }
+
+    /// Add the latest sample to the accumulation buffer and resolve the average
+    pub fn dispatch(&mut self, queue: &Queue, encoder: &mut wgpu::CommandEncoder) {
+        self.sample_count += 1;
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::bytes_of(&AccumulationParams {
+                sample_count: self.sample_count,
+                _padding: [0; 3],
+            }),
+        );
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Accumulation Pass"),
+        });
+
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_groups[self.current], &[]);
+        let [workgroup_width, workgroup_height] = self.workgroup_size;
+        compute_pass.dispatch_workgroups(
+            self.width.div_ceil(workgroup_width),
+            self.height.div_ceil(workgroup_height),
+            1,
+        );
+
+        self.current = 1 - self.current;
+    }
+}
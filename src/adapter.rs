@@ -0,0 +1,137 @@
+use clap::ValueEnum;
+use wgpu::{Adapter, Backends, Instance, Surface};
+
+use crate::cli::Options;
+
+/// Graphics API to run on, overriding the choice of the [`Profile`](crate::profile::Profile)
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+impl Backend {
+    fn backends(self) -> Backends {
+        match self {
+            Backend::Vulkan => Backends::VULKAN,
+            Backend::Metal => Backends::METAL,
+            Backend::Dx12 => Backends::DX12,
+            Backend::Gl => Backends::GL,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerPreference {
+    /// Integrated GPUs, easier on the battery
+    Low,
+    /// Discrete GPUs
+    High,
+}
+
+/// Which GPU to draw on, for machines with more than one
+pub struct AdapterSelection {
+    pub backends: Backends,
+    pub power_preference: wgpu::PowerPreference,
+    /// Index into the `--list-adapters` output, or a case insensitive substring of the adapter name
+    pub adapter: Option<String>,
+}
+
+impl AdapterSelection {
+    pub fn new(options: &Options) -> Self {
+        Self {
+            backends: options
+                .backend
+                .map_or(options.profile.backends(), Backend::backends),
+            power_preference: match options.power_preference {
+                Some(PowerPreference::Low) => wgpu::PowerPreference::LowPower,
+                Some(PowerPreference::High) => wgpu::PowerPreference::HighPerformance,
+                None => wgpu::PowerPreference::default(),
+            },
+            adapter: options.adapter.clone(),
+        }
+    }
+
+    pub fn create_instance(&self) -> Instance {
+        Instance::new(wgpu::InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        })
+    }
+
+    /// The requested adapter, or whatever wgpu picks for the power preference.
+    /// Without a surface, as for `--self-test`, the adapter doesn't need to present
+    pub async fn select(
+        &self,
+        instance: &Instance,
+        surface: Option<&Surface<'_>>,
+    ) -> Option<Adapter> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(requested) = &self.adapter {
+            return self.find(instance, requested, surface);
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some(requested) = &self.adapter {
+            log::warn!("The browser picks the adapter, ignoring --adapter {requested}");
+        }
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: self.power_preference,
+                compatible_surface: surface,
+                force_fallback_adapter: false,
+            })
+            .await
+    }
+
+    /// The adapter `requested` by index or name, browsers don't list their adapters
+    #[cfg(not(target_arch = "wasm32"))]
+    fn find(
+        &self,
+        instance: &Instance,
+        requested: &str,
+        surface: Option<&Surface<'_>>,
+    ) -> Option<Adapter> {
+        let adapters = instance.enumerate_adapters(self.backends);
+        let adapter = match requested.parse::<usize>() {
+            Ok(index) => adapters.into_iter().nth(index),
+            Err(_) => {
+                let requested = requested.to_lowercase();
+                adapters
+                    .into_iter()
+                    .find(|adapter| adapter.get_info().name.to_lowercase().contains(&requested))
+            }
+        };
+
+        match adapter {
+            Some(adapter)
+                if surface.is_some_and(|surface| !adapter.is_surface_supported(surface)) =>
+            {
+                log::error!(
+                    "Adapter {:?} can't present to the window",
+                    adapter.get_info().name
+                );
+                None
+            }
+            adapter => adapter,
+        }
+    }
+
+    /// Print every adapter on the selected backends, numbered for `--adapter`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn list(&self) {
+        let adapters = self.create_instance().enumerate_adapters(self.backends);
+        if adapters.is_empty() {
+            println!("No adapters found");
+        }
+
+        for (index, adapter) in adapters.iter().enumerate() {
+            let info = adapter.get_info();
+            println!(
+                "{index}: {} ({:?}, {:?}, driver {} {})",
+                info.name, info.backend, info.device_type, info.driver, info.driver_info
+            );
+        }
+    }
+}
@@ -1,99 +1,2717 @@
-use std::{process, sync::Arc};
-use winit::{event::*, event_loop::EventLoop, window::Window};
+use std::{mem, path::PathBuf, process, sync::Arc, time::Duration};
 
-use crate::{compute::ComputeState, gpu::GpuState, render::RenderState, shaders::Shaders};
+use clap::ValueEnum;
+use web_time::Instant;
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalPosition,
+    event::*,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey},
+    window::{Window, WindowId},
+};
+
+use crate::{
+    accumulation::MotionBlur,
+    attract::{AttractMode, PlaylistEntry},
+    audio::AudioInput,
+    bloom::BloomParams,
+    camera::CameraControls,
+    cli::{Options, Preset},
+    compute::{ComputeState, VolumeView},
+    config_file::ConfigFile,
+    crash,
+    crop::{Crop, CropControls, CropExport},
+    daemon::Signals,
+    dirty::DirtyRegions,
+    examples::Example,
+    exposure::AutoExposure,
+    feedback::{FeedbackLog, TriggerAction, Triggers},
+    fractal::FractalControls,
+    frame_hash::FrameHasher,
+    gamepad::{self, GamepadInput},
+    globals::{self, Camera, Globals, GlobalsState, Pointer},
+    gpu::{self, DrawMode, GpuError, GpuState},
+    histogram::Histogram,
+    live::{LiveCoding, StatusStrip},
+    ndi::NdiOutput,
+    osc::{self, OscArg},
+    output_window::OutputWindow,
+    pacing::FramePacer,
+    paint::PaintControls,
+    pipeline_cache,
+    playlist::{Crossfade, Playlist},
+    power::PowerSaving,
+    project::{self, ProjectWatcher},
+    reaction_diffusion::ReactionDiffusionParams,
+    readback::ReadbackRing,
+    recording::Recorder,
+    recovery::SurfaceRecovery,
+    render::Quad,
+    sandbox::Watchdog,
+    scene::{Drawing, Scene, SceneConfig},
+    screensaver::{self, Screensaver},
+    sidecar::ShaderSettings,
+    soundtrack::Soundtrack,
+    telemetry::{FrameTelemetry, Telemetry},
+    tonemap::{ColorSpace, TonemapParams, Tonemapper, Transfer},
+    touch::{TouchAction, TouchControls, TouchInput},
+    wall::{SyncFollower, SyncLeader, SyncMessage},
+};
 
 pub const WIDTH: u32 = 512;
 pub const HEIGHT: u32 = 512;
+pub const TITLE: &str = "wgpu compute image";
+
+/// Pause between attempts to recreate a lost device, the browser's thread can't sleep
+#[cfg(not(target_arch = "wasm32"))]
+const DEVICE_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Crops read back at once, exporting more fails until they are written
+const MAX_CROP_EXPORTS: usize = 4;
+/// Pixels inspected with Alt and a click read back at once, further clicks are ignored until they arrive
+const MAX_PIXEL_READBACKS: usize = 4;
+/// Intensity of the bloom turned on with B when `--bloom` wasn't given
+const DEFAULT_BLOOM: f32 = 0.5;
+/// Centers of the quarter sized quads dropped images are shown in, filled in this order
+const DROP_SLOTS: [[f32; 2]; 4] = [[0.25, 0.25], [0.75, 0.25], [0.25, 0.75], [0.75, 0.75]];
 
-/// Initilize GPU, Shaders and Pipelines
-/// and run the event loop
-pub async fn run_app(event_loop: EventLoop<()>, window: Window) {
-    let window = Arc::new(window);
-    let gpu_state = GpuState::new(&window, WIDTH, HEIGHT).await;
-    let shaders = Shaders::new(&gpu_state.device);
-    let compute_state = ComputeState::new(&gpu_state.device, &shaders, WIDTH, HEIGHT);
-    let render_state = RenderState::new(
-        &gpu_state.device,
-        &shaders,
-        &compute_state,
-        gpu_state.surface_format,
-    );
-
-    let app = App {
-        gpu_state,
-        compute_state,
-        render_state,
+/// Creates the main window once the event loop runs, see [`run_app`]
+pub type WindowFactory = Box<dyn FnOnce(&ActiveEventLoop) -> Window>;
+
+/// Events the app sends its own event loop
+pub enum AppEvent {
+    /// The device was requested asynchronously, as the browser's thread can't block
+    DeviceCreated(Result<GpuState, GpuError>),
+}
+
+/// Run the event loop, which creates the window with `create_window` once it resumes
+/// and then initializes GPU, shaders and pipelines.
+/// The browser runs the event loop, so there this returns right away
+pub fn run_app(event_loop: EventLoop<AppEvent>, options: Options, create_window: WindowFactory) {
+    let launcher = Launcher {
+        #[cfg(target_arch = "wasm32")]
+        proxy: event_loop.create_proxy(),
+        create_window: Some(create_window),
+        setup: Some(Setup::new(options)),
+        app: None,
     };
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut launcher = launcher;
+        event_loop
+            .run_app(&mut launcher)
+            .expect("Failed to run event loop");
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn_app(launcher);
+    }
+}
+
+/// What is known before the window and the device exist
+struct Setup {
+    options: Options,
+    project_watcher: Option<ProjectWatcher>,
+    playlist: Option<Playlist>,
+    window: Option<Arc<Window>>,
+}
+
+impl Setup {
+    fn new(options: Options) -> Self {
+        let project_watcher = options
+            .project
+            .as_deref()
+            .map(|dir| ProjectWatcher::new(dir, options.clone()));
+        let mut options = match project::apply(&options) {
+            Ok(options) => options,
+            Err(err) => {
+                log::error!("Failed to load project: {err}");
+                process::exit(1);
+            }
+        };
+        let interval = Duration::try_from_secs_f32(options.playlist_interval)
+            .ok()
+            .filter(|interval| !interval.is_zero());
+        let playlist = match options
+            .playlist
+            .as_deref()
+            .map(|directory| Playlist::load(directory, interval))
+        {
+            Some(Ok(playlist)) => {
+                options.shader = Some(playlist.shaders()[playlist.index()].clone());
+                Some(playlist)
+            }
+            Some(Err(err)) => {
+                log::error!("Failed to read playlist: {err}");
+                process::exit(1);
+            }
+            None => None,
+        };
+        Self {
+            options,
+            project_watcher,
+            playlist,
+            window: None,
+        }
+    }
+}
+
+/// Runs the event loop: creates the window and the device once it first resumes,
+/// then hands the events to the [`App`]
+struct Launcher {
+    /// Hands over the device requested asynchronously in the browser
+    #[cfg(target_arch = "wasm32")]
+    proxy: winit::event_loop::EventLoopProxy<AppEvent>,
+    create_window: Option<WindowFactory>,
+    /// Taken once the app is built
+    setup: Option<Setup>,
+    app: Option<App>,
+}
+
+impl Launcher {
+    fn start(&mut self, event_loop: &ActiveEventLoop, gpu_state: Result<GpuState, GpuError>) {
+        let gpu_state = match gpu_state {
+            Ok(gpu_state) => gpu_state,
+            Err(err) => {
+                log::error!("{err}");
+                process::exit(1);
+            }
+        };
+        let Some(setup) = self.setup.take() else {
+            return;
+        };
+        let mut app = App::new(event_loop, setup, gpu_state);
+        app.build_playlist();
+        self.app = Some(app);
+    }
+}
+
+impl ApplicationHandler<AppEvent> for Launcher {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(app) = &mut self.app {
+            app.resumed(event_loop);
+            return;
+        }
+        let (Some(create_window), Some(setup)) = (self.create_window.take(), &mut self.setup)
+        else {
+            return;
+        };
+        let window = Arc::new(create_window(event_loop));
+        setup.window = Some(Arc::clone(&window));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let gpu_state =
+                pollster::block_on(GpuState::new(&window, WIDTH, HEIGHT, &setup.options));
+            self.start(event_loop, gpu_state);
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let proxy = self.proxy.clone();
+            let options = setup.options.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let gpu_state = GpuState::new(&window, WIDTH, HEIGHT, &options).await;
+                // Fails only once the event loop is gone
+                let _ = proxy.send_event(AppEvent::DeviceCreated(gpu_state));
+            });
+        }
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: AppEvent) {
+        match event {
+            AppEvent::DeviceCreated(gpu_state) => self.start(event_loop, gpu_state),
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        if let Some(app) = &mut self.app {
+            app.window_event(event_loop, window_id, event);
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(app) = &mut self.app {
+            app.about_to_wait(event_loop);
+        }
+    }
+
+    fn suspended(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(app) = &mut self.app {
+            app.suspended(event_loop);
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(app) = &mut self.app {
+            app.exiting(event_loop);
+        }
+    }
+
+    fn memory_warning(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(app) = &mut self.app {
+            app.memory_warning(event_loop);
+        }
+    }
+}
+
+impl App {
+    /// Build everything else for the window and device of `setup`
+    fn new(event_loop: &ActiveEventLoop, setup: Setup, gpu_state: GpuState) -> Self {
+        let Setup {
+            options,
+            project_watcher,
+            playlist,
+            window,
+        } = setup;
+        let window = window.unwrap();
+        crash::set_device(&gpu_state);
+        let mut config = SceneConfig::new(&options, &gpu_state);
+        // Values given on the command line win over those saved
+        let settings = config
+            .shader_path
+            .as_deref()
+            .filter(|_| !options.fresh)
+            .and_then(ShaderSettings::load);
+        if let Some(settings) = &settings
+            && options.params.is_none()
+        {
+            config.initial_params = Some(settings.params);
+        }
+
+        let mut globals = GlobalsState::new(
+            &gpu_state.device,
+            &gpu_state.queue,
+            config.width,
+            config.height,
+        );
+        globals.globals.resolution = config.resolution();
+        if let Some(params) = config.start_params() {
+            globals.globals.params = params;
+        }
+        globals.globals.seed = options.seed;
+        // Frames only match across runs if time doesn't depend on the frame rate
+        globals.fixed_timestep = options.fixed_fps().map(|fps| 1.0 / fps as f32);
+        // Ignored once time advances by a fixed timestep per frame
+        if let Some(start) = settings.as_ref().and_then(|settings| {
+            Instant::now().checked_sub(Duration::from_secs_f32(settings.time.max(0.0)))
+        }) {
+            globals.start = start;
+        }
+
+        let config_file = match options.config.as_deref().map(ConfigFile::load) {
+            Some(Ok(config_file)) => config_file,
+            Some(Err(err)) => {
+                log::error!("Failed to read config file: {err}");
+                process::exit(1);
+            }
+            None => ConfigFile::default(),
+        };
+        config.quads = config_file.quads;
+        // `--convolve` overrides the config file
+        if config.convolution.is_none() && gpu_state.draw_mode == DrawMode::Compute {
+            config.convolution = config_file.convolution;
+        }
+        let output_windows = config_file
+            .windows
+            .iter()
+            .filter_map(|window_config| {
+                OutputWindow::open(event_loop, &gpu_state, options.color_space, window_config)
+                    .inspect_err(|err| {
+                        log::error!("Failed to open window {:?}: {err}", window_config.title)
+                    })
+                    .ok()
+            })
+            .collect();
+
+        let frame_hasher = match options.frame_hash.as_deref().map(FrameHasher::new) {
+            Some(Ok(frame_hasher)) => {
+                if gpu_state.draw_mode == DrawMode::Direct {
+                    log::warn!("Frames drawn straight to the window can't be hashed");
+                }
+                Some(frame_hasher)
+            }
+            Some(Err(err)) => {
+                log::error!("Failed to create frame hash file: {err}");
+                process::exit(1);
+            }
+            None => None,
+        };
+
+        let feedback_log = match options.feedback.as_deref().map(FeedbackLog::new) {
+            Some(Ok(feedback_log)) => Some(feedback_log),
+            Some(Err(err)) => {
+                log::error!("Failed to create feedback file: {err}");
+                process::exit(1);
+            }
+            None => None,
+        };
+
+        let triggers = match Triggers::new(config_file.triggers) {
+            Ok(triggers) => triggers,
+            Err(err) => {
+                log::error!("Invalid trigger in config file: {err}");
+                process::exit(1);
+            }
+        };
+
+        if let Err(err) = options.wall() {
+            log::error!("Invalid video wall: {err}");
+            process::exit(1);
+        }
+        let sync_leader = match options.sync_lead.map(SyncLeader::new).transpose() {
+            Ok(sync_leader) => sync_leader,
+            Err(err) => {
+                log::error!("Failed to open sync socket: {err}");
+                process::exit(1);
+            }
+        };
+        let sync_follower = match options.sync_follow.map(SyncFollower::new).transpose() {
+            Ok(sync_follower) => sync_follower,
+            Err(err) => {
+                log::error!("Failed to listen for sync messages: {err}");
+                process::exit(1);
+            }
+        };
+
+        let recorder = match options.record.as_deref().map(|directory| {
+            Recorder::new(
+                directory,
+                options.record_format,
+                options.format,
+                options.color_space == ColorSpace::Srgb,
+            )
+        }) {
+            Some(Ok(recorder)) => {
+                if gpu_state.draw_mode == DrawMode::Direct {
+                    log::warn!("Frames drawn straight to the window can't be recorded");
+                }
+                Some(recorder)
+            }
+            Some(Err(err)) => {
+                log::error!("Failed to create recording directory: {err}");
+                process::exit(1);
+            }
+            None => None,
+        };
+
+        let ndi_output = match options
+            .ndi
+            .as_deref()
+            .map(|name| NdiOutput::new(name, options.color_space == ColorSpace::Srgb))
+        {
+            Some(Ok(ndi_output)) => {
+                if gpu_state.draw_mode == DrawMode::Direct {
+                    log::warn!("Frames drawn straight to the window can't be published over NDI");
+                }
+                Some(ndi_output)
+            }
+            Some(Err(err)) => {
+                log::error!("Failed to publish over NDI: {err}");
+                process::exit(1);
+            }
+            None => None,
+        };
+
+        let mut audio = match options
+            .audio
+            .then(|| AudioInput::open(options.audio_device.as_deref()))
+        {
+            Some(Ok(audio)) => Some(audio),
+            Some(Err(err)) => {
+                log::error!("Failed to record audio, the audio texture stays black: {err}");
+                None
+            }
+            None => None,
+        };
+        let gamepad_bindings = config_file
+            .gamepad
+            .unwrap_or_else(gamepad::default_bindings);
+        let gamepad = match options
+            .gamepad
+            .then(|| GamepadInput::open(gamepad_bindings))
+        {
+            Some(Ok(gamepad)) => Some(gamepad),
+            Some(Err(err)) => {
+                log::error!("Failed to open gamepads, `gamepads` stay zero: {err}");
+                None
+            }
+            None => None,
+        };
+        let soundtrack = match (&recorder, audio.as_mut()) {
+            (Some(_), Some(audio)) => {
+                audio.start_recording();
+                let directory = options.record.as_deref().unwrap();
+                let frame_rate = options.fixed_fps().unwrap();
+                match Soundtrack::new(directory, audio.sample_rate(), frame_rate) {
+                    Ok(soundtrack) => Some(soundtrack),
+                    Err(err) => {
+                        log::error!("Failed to record the audio input: {err}");
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        let telemetry = match options
+            .record
+            .as_deref()
+            .filter(|_| options.telemetry)
+            .map(Telemetry::new)
+        {
+            Some(Ok(telemetry)) => Some(telemetry),
+            Some(Err(err)) => {
+                log::error!("Failed to create the telemetry file: {err}");
+                process::exit(1);
+            }
+            None => None,
+        };
+
+        let tonemapper = options
+            .tonemap
+            .or_else(|| settings.as_ref()?.tonemapper())
+            .unwrap_or(match config.preset {
+                Preset::PathTracer | Preset::Sdf => Tonemapper::Aces,
+                Preset::Drawing | Preset::ReactionDiffusion | Preset::Fractal | Preset::Paint => {
+                    Tonemapper::Clamp
+                }
+            });
+        let exposure = options
+            .exposure
+            .or(settings.as_ref().map(|settings| settings.exposure))
+            .unwrap_or(options.exposure());
+
+        let transfer = options.color_space.transfer(gpu_state.surface_format);
+        let scene = match Scene::new(
+            &gpu_state,
+            &globals,
+            &config,
+            TonemapParams::new(tonemapper, exposure, transfer),
+        ) {
+            Ok(scene) => scene,
+            Err(err) => {
+                log::error!("Failed to read shader: {err}");
+                process::exit(1);
+            }
+        };
+
+        let signals = match options.daemon.then(Signals::register).transpose() {
+            Ok(signals) => signals,
+            Err(err) => {
+                log::error!("Failed to register signal handlers: {err}");
+                process::exit(1);
+            }
+        };
+        if signals.is_some() {
+            log::info!(
+                "Running as daemon on {:?}",
+                gpu_state.adapter.get_info().name
+            );
+        }
+
+        if let Some(mode) = options.fullscreen {
+            window.set_fullscreen(Some(mode.fullscreen(&window)));
+        }
+
+        let camera_controls = camera_controlled(&options, &config, &scene).then(|| {
+            let mut controls = CameraControls::new();
+            if let Some(view) = settings.as_ref().and_then(|settings| settings.camera) {
+                controls.set_view(view);
+            }
+            controls
+        });
+
+        let analyzed = gpu_state.draw_mode == DrawMode::Compute;
+        if (options.histogram || options.auto_exposure) && !analyzed {
+            log::warn!(
+                "The histogram and auto exposure require compute shaders, presenting without them"
+            );
+        }
+        let histogram = (options.histogram && analyzed)
+            .then(|| Histogram::new(&gpu_state.device, gpu_state.pipeline_cache.as_deref(), true));
+        let auto_exposure = (options.auto_exposure && analyzed)
+            .then(|| AutoExposure::new(&gpu_state.device, gpu_state.pipeline_cache.as_deref()));
+
+        let dirty_regions = options.dirty_radius.and_then(|radius| {
+            if gpu_state.draw_mode != DrawMode::Compute {
+                log::warn!("--dirty-radius requires compute shaders, drawing every pixel");
+                return None;
+            }
+            Some(DirtyRegions::new(radius))
+        });
+
+        App {
+            tonemapper,
+            exposure,
+            transfer,
+            touch_controls: TouchControls::new(globals.globals.params),
+            touch_input: TouchInput::new(),
+            modifiers: ModifiersState::empty(),
+            pointer: Pointer {
+                focused: u32::from(window.has_focus()),
+                ..Default::default()
+            },
+            camera_controls,
+            fractal_controls: (config.preset == Preset::Fractal).then(FractalControls::new),
+            paint_controls: (config.preset == Preset::Paint).then(PaintControls::new),
+            dirty_regions,
+            histogram,
+            auto_exposure,
+            suspended: false,
+            signals,
+            status: Status::new(),
+            frame_hasher,
+            feedback_log,
+            triggers,
+            sync_leader,
+            sync_follower,
+            failed_sync_scene: None,
+            synced_frame: None,
+            recorder,
+            motion_blur: options
+                .motion_blur
+                .map(|samples| MotionBlur::new(samples, options.shutter)),
+            ndi_output,
+            audio,
+            gamepad,
+            paused_at: None,
+            soundtrack,
+            telemetry,
+            power_saving: if options.wallpaper {
+                Some(PowerSaving::wallpaper())
+            } else if options.screensaver {
+                Some(PowerSaving::screensaver(screensaver::FPS))
+            } else if options.screensaver_preview.is_some() {
+                Some(PowerSaving::screensaver(screensaver::PREVIEW_FPS))
+            } else {
+                options.power_saving.then(PowerSaving::new)
+            },
+            frame_pacer: FramePacer::new(options.max_fps, options.on_demand),
+            screensaver: (options.screensaver || options.screensaver_preview.is_some())
+                .then(|| Screensaver::new(options.screensaver_preview.is_some())),
+            watchdog: config.sandboxed.then(Watchdog::new),
+            project_watcher,
+            live: config
+                .shader_path
+                .as_deref()
+                .filter(|_| options.live)
+                .map(|shader| {
+                    LiveCoding::new(
+                        shader,
+                        gpu_state.draw_mode,
+                        globals::push_constants_enabled(&gpu_state.device),
+                        &config,
+                    )
+                }),
+            status_strip: StatusStrip::new(),
+            playlist,
+            playlist_scenes: Vec::new(),
+            fading_out: None,
+            attract: config_file.attract.as_ref().map(AttractMode::new),
+            crop_controls: CropControls::new(config_file.crops),
+            output_windows,
+            crop_exports: ReadbackRing::new(MAX_CROP_EXPORTS),
+            pixel_readbacks: ReadbackRing::new(MAX_PIXEL_READBACKS),
+            cubemap_preview: false,
+            equirect: options.equirect.clone(),
+            gpu_state,
+            globals,
+            config,
+            scene,
+            shown_options: options.clone(),
+            options,
+            window,
+        }
+    }
+}
+
+/// A scene with the config and globals it was built for, see [`App::build_scene`]
+struct BuiltScene {
+    config: SceneConfig,
+    globals: GlobalsState,
+    scene: Scene,
+}
 
-    app.run(event_loop, Arc::clone(&window));
+/// Whether the drawing is viewed through the [`CameraControls`]
+fn camera_controlled(options: &Options, config: &SceneConfig, scene: &Scene) -> bool {
+    options.camera
+        || config.preset == Preset::Sdf
+        || config.volume_view == VolumeView::Volume
+            && scene.compute_state().is_some_and(ComputeState::has_volume)
+        || scene.compute_state().is_some_and(ComputeState::has_cubemap)
+}
+
+fn is_user_input(event: &WindowEvent) -> bool {
+    matches!(
+        event,
+        WindowEvent::KeyboardInput { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. }
+            | WindowEvent::CursorMoved { .. }
+            | WindowEvent::Touch(_)
+    )
+}
+
+/// Frame counter for the periodic status line of daemons
+struct Status {
+    frames: u64,
+    since: Instant,
+}
+
+impl Status {
+    const INTERVAL: Duration = Duration::from_secs(60);
+
+    fn new() -> Self {
+        Self {
+            frames: 0,
+            since: Instant::now(),
+        }
+    }
+
+    fn frame_rendered(&mut self) {
+        self.frames += 1;
+        let elapsed = self.since.elapsed();
+        if elapsed >= Self::INTERVAL {
+            log::info!(
+                "Rendered {} frames in the last {}s, {:.1} fps",
+                self.frames,
+                elapsed.as_secs(),
+                self.frames as f64 / elapsed.as_secs_f64()
+            );
+            *self = Self::new();
+        }
+    }
 }
 
 /// Responsible for running the event loop and holding the state required to do so.
 pub struct App {
+    window: Arc<Window>,
     gpu_state: GpuState,
-    compute_state: ComputeState,
-    render_state: RenderState,
+    globals: GlobalsState,
+    config: SceneConfig,
+    scene: Scene,
+    tonemapper: Tonemapper,
+    exposure: f32,
+    /// Conversion between the drawing's color space and the surface
+    transfer: Transfer,
+    touch_controls: TouchControls,
+    touch_input: TouchInput,
+    /// Held modifier keys, for Alt+Enter
+    modifiers: ModifiersState,
+    /// Handed to the `overlay` of the drawing shader every frame
+    pointer: Pointer,
+    /// Only with `--camera` or the sdf preset
+    camera_controls: Option<CameraControls>,
+    /// Only with the fractal preset
+    fractal_controls: Option<FractalControls>,
+    /// Only with the paint preset
+    paint_controls: Option<PaintControls>,
+    /// Only with `--dirty-radius`
+    dirty_regions: Option<DirtyRegions>,
+    /// Only with `--histogram`, or once H was pressed
+    histogram: Option<Histogram>,
+    /// Only with `--auto-exposure`
+    auto_exposure: Option<AutoExposure>,
+    /// Set while the app is in the background, where mobile platforms forbid rendering
+    suspended: bool,
+    /// Only registered in daemon mode, which also disables all interactive controls
+    signals: Option<Signals>,
+    status: Status,
+    frame_hasher: Option<FrameHasher>,
+    /// Only with `--feedback`
+    feedback_log: Option<FeedbackLog>,
+    /// From `[[triggers]]` in the config file
+    triggers: Triggers,
+    /// Only with `--sync-lead`
+    sync_leader: Option<SyncLeader>,
+    /// Only with `--sync-follow`
+    sync_follower: Option<SyncFollower>,
+    /// Scene of the sync leader that failed to load here, not retried until the leader moves on
+    failed_sync_scene: Option<String>,
+    /// Frame number and time received from the sync leader, drawn next
+    synced_frame: Option<(u32, f32)>,
+    /// Only with `--record`
+    recorder: Option<Recorder>,
+    /// Only with `--motion-blur`
+    motion_blur: Option<MotionBlur>,
+    /// Only with `--ndi`
+    ndi_output: Option<NdiOutput>,
+    audio: Option<AudioInput>,
+    /// Only with `--gamepad`
+    gamepad: Option<GamepadInput>,
+    /// Since when the clock and the frames stand still, toggled with Space
+    paused_at: Option<Instant>,
+    /// Only with `--record` and `--audio`
+    soundtrack: Option<Soundtrack>,
+    /// Only with `--record` and `--telemetry`
+    telemetry: Option<Telemetry>,
+    /// Only with `--power-saving`
+    power_saving: Option<PowerSaving>,
+    frame_pacer: FramePacer,
+    screensaver: Option<Screensaver>,
+    /// Only for sandboxed shaders
+    watchdog: Option<Watchdog>,
+    /// Only with `--project`
+    project_watcher: Option<ProjectWatcher>,
+    /// Only with `--live`
+    live: Option<LiveCoding>,
+    /// Confirms live coding swaps and dropped files
+    status_strip: StatusStrip,
+    /// Only with `--playlist`
+    playlist: Option<Playlist>,
+    /// Scenes of the playlist built up front, by index. `None` for the one shown
+    playlist_scenes: Vec<Option<BuiltScene>>,
+    /// Index and scene of the playlist entry drawn under the current one while crossfading
+    fading_out: Option<(usize, BuiltScene, Crossfade)>,
+    /// Only with an `[attract]` table in the config file
+    attract: Option<AttractMode>,
+    crop_controls: CropControls,
+    /// From `[[windows]]` in the config file
+    output_windows: Vec<OutputWindow>,
+    crop_exports: ReadbackRing<CropExport>,
+    /// Texels of the drawing inspected with Alt and a click, tagged with their position
+    pixel_readbacks: ReadbackRing<[u32; 2]>,
+    /// Shows the cubemap through the camera instead of its equirectangular projection, toggled with P
+    cubemap_preview: bool,
+    /// Where `--equirect` writes the first frame, taken once it is exported
+    equirect: Option<PathBuf>,
+    /// Kept to recreate the device after it was lost
+    options: Options,
+    /// What the scene shown was built from, like a playlist entry or a dropped shader,
+    /// to build it again after the device was lost
+    shown_options: Options,
 }
 
-impl App {
-    fn run(mut self, event_loop: EventLoop<()>, window: Arc<Window>) {
-        event_loop
-            .run(|event, _control_flow| match event {
-                Event::AboutToWait => {
+impl ApplicationHandler<AppEvent> for App {
+    /// Called when the app comes back after being suspended. Mobile systems take the surface away meanwhile,
+    /// so it is created again
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {
+        if !self.suspended {
+            return;
+        }
+        self.suspended = false;
+        if let Err(err) = self.gpu_state.recreate_surface(&self.window) {
+            log::error!("{err}");
+            self.gpu_state.health.mark_lost();
+        }
+    }
+
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        self.suspended = true;
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.finish_recording();
+        self.save_settings();
+        if let Some(cache) = &self.gpu_state.pipeline_cache {
+            pipeline_cache::save(cache, &self.gpu_state.adapter);
+        }
+    }
+
+    fn memory_warning(&mut self, _event_loop: &ActiveEventLoop) {
+        log::warn!("Received a memory warning from the system");
+    }
+
+    fn about_to_wait(&mut self, elwt: &ActiveEventLoop) {
+        let window = &Arc::clone(&self.window);
+        self.handle_signals(elwt);
+        self.update_gamepad(elwt);
+        self.update_power_saving(window);
+        self.update_attract_mode();
+        if self.gpu_state.health.is_lost() {
+            self.recover_device(window);
+        } else if self.suspended {
+            elwt.set_control_flow(ControlFlow::Wait);
+        } else {
+            let ready = self.poll_readbacks() && self.poll_sync();
+            let due = self
+                .frame_pacer
+                .next_frame_at(!self.draws_while_hidden())
+                .or_else(|| {
+                    self.power_saving
+                        .as_mut()
+                        .and_then(PowerSaving::next_frame_at)
+                });
+            match due {
+                Some(due) => elwt.set_control_flow(ControlFlow::WaitUntil(due)),
+                None if ready => {
+                    elwt.set_control_flow(ControlFlow::Poll);
                     self.render_frame();
                 }
-                Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::CloseRequested => process::exit(0),
-                    WindowEvent::Resized(size) => {
-                        self.handle_resize(size.width, size.height, &window);
-                    }
-                    _ => {}
-                },
-                _ => {}
+                // Nothing wakes the event loop once the readbacks arrive
+                None => window.request_redraw(),
+            }
+            self.handle_feedback(elwt);
+            self.check_watchdog();
+            self.check_project();
+            self.update_live();
+            self.update_playlist();
+        }
+        if self
+            .options
+            .frames
+            .is_some_and(|frames| self.globals.globals.frame >= frames)
+        {
+            elwt.exit();
+        }
+    }
+
+    fn window_event(&mut self, elwt: &ActiveEventLoop, window_id: WindowId, event: WindowEvent) {
+        let window = &Arc::clone(&self.window);
+        if window_id != window.id() {
+            self.handle_output_window_event(window_id, event, elwt);
+            return;
+        }
+        if self
+            .screensaver
+            .as_mut()
+            .is_some_and(|screensaver| screensaver.ends_on(&event))
+        {
+            elwt.exit();
+            return;
+        }
+        if is_user_input(&event) {
+            self.handle_input();
+        }
+        if !matches!(event, WindowEvent::RedrawRequested) {
+            self.frame_pacer.invalidate();
+        }
+        match event {
+            // Recordings and settings are saved when exiting
+            WindowEvent::CloseRequested => elwt.exit(),
+            WindowEvent::Resized(size) => {
+                self.frame_pacer.resized(size.width, size.height);
+                self.handle_resize(size.width, size.height, window);
+            }
+            // The image keeps its resolution, only the surface follows the window's new size in pixels
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                log::info!("Scale factor changed to {scale_factor}");
+                let size = window.inner_size();
+                self.handle_resize(size.width, size.height, window);
+            }
+            WindowEvent::Occluded(occluded) => self.frame_pacer.set_occluded(occluded),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state,
+                        ..
+                    },
+                ..
+            } if self.signals.is_none() && self.handle_camera_key(code, state) => {}
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Character(key),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } if self.signals.is_none() => self.handle_key(&key),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(key),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if self.signals.is_none() => self.handle_named_key(key, window),
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::MouseInput { state, button, .. } if self.signals.is_none() => {
+                self.handle_mouse_button(state, button)
+            }
+            WindowEvent::MouseWheel { delta, .. } if self.signals.is_none() => {
+                if let Some(camera_controls) = &mut self.camera_controls {
+                    camera_controls.handle_wheel(delta);
+                }
+                if let Some(fractal_controls) = &mut self.fractal_controls {
+                    fractal_controls.handle_wheel(delta, self.globals.globals.resolution);
+                }
+                if let Some(paint_controls) = &mut self.paint_controls {
+                    paint_controls.handle_wheel(delta, self.modifiers.shift_key());
+                }
+            }
+            WindowEvent::Focused(focused) => self.pointer.focused = u32::from(focused),
+            WindowEvent::Touch(touch) if self.signals.is_none() => {
+                self.handle_touch(&touch, window)
+            }
+            WindowEvent::CursorMoved { position, .. } if self.signals.is_none() => {
+                self.handle_cursor(position, window)
+            }
+            WindowEvent::DroppedFile(path) if self.signals.is_none() => {
+                self.handle_dropped_file(path)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl App {
+    /// Close and resize the `[[windows]]` of the config file, keys pressed in them go to the main window
+    fn handle_output_window_event(
+        &mut self,
+        window_id: WindowId,
+        event: WindowEvent,
+        elwt: &ActiveEventLoop,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => self
+                .output_windows
+                .retain(|output_window| output_window.id() != window_id),
+            WindowEvent::Resized(size) => {
+                if let Some(output_window) = self
+                    .output_windows
+                    .iter_mut()
+                    .find(|output_window| output_window.id() == window_id)
+                {
+                    output_window.resize(&self.gpu_state, size.width, size.height);
+                }
+            }
+            WindowEvent::KeyboardInput { .. } | WindowEvent::ModifiersChanged(_) => {
+                self.window_event(elwt, self.window.id(), event);
+            }
+            _ => {}
+        }
+    }
+
+    /// Write out the frames and crops still being read back or encoded
+    fn finish_recording(&mut self) {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.finish(&self.gpu_state.device);
+        }
+        if let Some(soundtrack) = self.soundtrack.take()
+            && let Err(err) = soundtrack.finish()
+        {
+            log::error!("Failed to write the recorded audio: {err}");
+        }
+        if let Some(mut telemetry) = self.telemetry.take() {
+            telemetry.finish(&self.gpu_state.device);
+        }
+        self.crop_exports
+            .collect(&self.gpu_state.device, true, |export, layout, data| {
+                export.write(layout, data);
+                true
+            });
+    }
+
+    /// Remember where the shader was left off, see [`ShaderSettings`]
+    fn save_settings(&self) {
+        let Some(shader) = &self.config.shader_path else {
+            return;
+        };
+        let settings = ShaderSettings::new(
+            self.globals.globals.params,
+            self.globals.globals.time,
+            self.tonemapper,
+            self.exposure,
+            self.camera_controls.as_ref().map(CameraControls::view),
+        );
+        if let Err(err) = settings.save(shader) {
+            log::error!("Failed to save the settings of {}: {err}", shader.display());
+        }
+    }
+
+    /// Write out the crops, inspected pixels and telemetry that arrived and hand finished frames to the recorder.
+    /// Returns whether the next frame can be drawn, it waits while the recorder couldn't capture it
+    fn poll_readbacks(&mut self) -> bool {
+        let device = &self.gpu_state.device;
+        self.crop_exports
+            .collect(device, false, |export, layout, data| {
+                export.write(layout, data);
+                true
+            });
+        self.pixel_readbacks
+            .collect(device, false, |&[x, y], layout, data| {
+                if let Some([r, g, b, a]) = layout.texels(data).first() {
+                    log::info!("Pixel {x}, {y} of {:?}: {r} {g} {b} {a}", layout.format);
+                }
+                true
+            });
+        if let Some(telemetry) = &mut self.telemetry {
+            telemetry.poll(device);
+        }
+        if let Some(ndi_output) = &mut self.ndi_output {
+            ndi_output.poll(device);
+        }
+        let histogram_arrived = self
+            .histogram
+            .as_mut()
+            .is_some_and(|histogram| histogram.poll(device));
+        let exposure_adapted = self
+            .auto_exposure
+            .as_mut()
+            .is_some_and(|auto_exposure| auto_exposure.poll(device));
+        if histogram_arrived || exposure_adapted {
+            self.scene
+                .render_state
+                .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+        }
+        self.recorder
+            .as_mut()
+            .is_none_or(|recorder| recorder.poll(device))
+    }
+
+    /// Followers only draw once the leader sent a frame, taking over its scene, seed and parameters.
+    /// Returns whether to draw
+    fn poll_sync(&mut self) -> bool {
+        let Some(sync_follower) = &mut self.sync_follower else {
+            return true;
+        };
+        let Some(message) = sync_follower.receive() else {
+            return false;
+        };
+        if message.scene == self.config.scene_name() {
+            self.failed_sync_scene = None;
+        } else if self.failed_sync_scene.as_deref() != Some(message.scene.as_str()) {
+            log::info!("Following the sync leader to {}", message.scene);
+            let options = PlaylistEntry::parse(&message.scene).options(&self.options);
+            self.failed_sync_scene = (!self.load_scene(&options)).then(|| message.scene.clone());
+        }
+        self.globals.globals.seed = message.seed;
+        if message.params != self.globals.globals.params {
+            self.set_params(message.params);
+        }
+        self.synced_frame = Some((message.frame, message.time));
+        true
+    }
+
+    /// Any input ends the attract mode
+    fn handle_input(&mut self) {
+        if self.attract.as_mut().is_some_and(AttractMode::input) {
+            log::info!("Input received, leaving attract mode");
+            self.load_scene(&self.options.clone());
+            self.update_tonemapping();
+        }
+    }
+
+    fn update_attract_mode(&mut self) {
+        let Some(attract) = &mut self.attract else {
+            return;
+        };
+        if attract.poll() {
+            self.show_attract_entry();
+        }
+    }
+
+    /// Load the playlist entry the attract mode is at, or the interactive scene
+    fn show_attract_entry(&mut self) {
+        if let Some(entry) = self.attract.as_ref().and_then(AttractMode::current) {
+            log::info!("Attract mode showing {entry:?}");
+            let options = entry.options(&self.options);
+            self.load_scene(&options);
+        }
+        self.update_tonemapping();
+    }
+
+    /// Log what the drawing shader reported and take the actions of the triggers it fired
+    fn handle_feedback(&mut self, elwt: &ActiveEventLoop) {
+        let Some(compute_state) = self.scene.compute_state_mut() else {
+            return;
+        };
+        let Some(feedback) = compute_state.poll_feedback(&self.gpu_state.device) else {
+            return;
+        };
+        if let Some(feedback_log) = &mut self.feedback_log {
+            feedback_log.write(&feedback);
+        }
+
+        for action in self.triggers.check(&feedback) {
+            log::info!("Feedback of frame {} triggered {action:?}", feedback.frame);
+            self.take_action(action, elwt, &feedback.osc_args());
+        }
+    }
+
+    /// Take in gamepad input and the actions of the buttons pressed, which sends OSC messages with the frame number
+    fn update_gamepad(&mut self, elwt: &ActiveEventLoop) {
+        let Some(gamepad) = &mut self.gamepad else {
+            return;
+        };
+        if !gamepad.poll() {
+            return;
+        }
+        let actions = gamepad.take_actions();
+        self.handle_input();
+        self.frame_pacer.invalidate();
+        for action in actions {
+            log::info!("Gamepad triggered {action:?}");
+            let frame = OscArg::Int(self.globals.globals.frame as i32);
+            self.take_action(action, elwt, &[frame]);
+        }
+    }
+
+    /// Act on a trigger or gamepad button, `osc_args` are sent with OSC messages
+    fn take_action(&mut self, action: TriggerAction, elwt: &ActiveEventLoop, osc_args: &[OscArg]) {
+        match action {
+            TriggerAction::Reset => self.reset(),
+            TriggerAction::NextEntryPoint => self.next_entry_point(),
+            TriggerAction::NextScene => {
+                if self.playlist.is_some() {
+                    self.step_playlist(1);
+                } else if self.attract.as_mut().is_some_and(AttractMode::skip) {
+                    self.show_attract_entry();
+                } else {
+                    log::warn!("Switching scenes needs a --playlist or an [attract] playlist");
+                }
+            }
+            TriggerAction::Pause => self.toggle_pause(),
+            TriggerAction::NextPreset => self.next_preset(),
+            TriggerAction::Exit => elwt.exit(),
+            TriggerAction::Screenshot => {
+                if let Err(err) = self.read_back_crop("screenshot", Crop::FULL) {
+                    log::error!("Failed to take a screenshot: {err}");
+                }
+            }
+            TriggerAction::Scene(name) => {
+                let options = PlaylistEntry::parse(&name).options(&self.options);
+                self.load_scene(&options);
+            }
+            TriggerAction::Osc { target, address } => {
+                if let Err(err) = osc::send(target, &address, osc_args) {
+                    log::error!("Failed to send OSC message to {target}: {err}");
+                }
+            }
+        }
+    }
+
+    /// Stop the clock and the frames, showing the last one, or continue where they stopped
+    fn toggle_pause(&mut self) {
+        match self.paused_at.take() {
+            Some(paused_at) => {
+                self.globals.start += paused_at.elapsed();
+                log::info!("Continuing");
+            }
+            None => {
+                self.paused_at = Some(Instant::now());
+                log::info!("Paused at frame {}", self.globals.globals.frame);
+            }
+        }
+        self.frame_pacer.set_paused(self.paused_at.is_some());
+    }
+
+    /// Switch to the scene described by `options`, starting over with its initial parameters,
+    /// returns whether that succeeded. The current scene keeps running if the new one fails to build
+    fn load_scene(&mut self, options: &Options) -> bool {
+        match self.build_scene(options) {
+            Some(built) => {
+                self.install_scene(built, options);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Build the scene described by `options` next to the current one, with its initial parameters.
+    /// Errors are logged
+    fn build_scene(&self, options: &Options) -> Option<BuiltScene> {
+        let mut config = SceneConfig::new(options, &self.gpu_state);
+        config.set_image_size(self.image_size(), &self.gpu_state.device);
+        // Part of the config file rather than the options
+        config.quads = self.config.quads.clone();
+        if config.convolution.is_none() && self.gpu_state.draw_mode == DrawMode::Compute {
+            config.convolution = self.config.convolution.clone();
+        }
+        let mut globals = self.globals.recreate(
+            &self.gpu_state.device,
+            &self.gpu_state.queue,
+            config.width,
+            config.height,
+        );
+        globals.globals.resolution = config.resolution();
+        if let Some(params) = config.start_params() {
+            globals.globals.params = params;
+        }
+
+        let device = &self.gpu_state.device;
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let scene = Scene::new(&self.gpu_state, &globals, &config, self.tonemap_params());
+        let validation_error = gpu::pop_error_scope(device);
+
+        match (scene, validation_error) {
+            (Err(err), _) => {
+                log::error!("Failed to read shader: {err}");
+                None
+            }
+            (_, Some(err)) => {
+                log::error!("Invalid shader: {err}");
+                None
+            }
+            (Ok(scene), None) => Some(BuiltScene {
+                config,
+                globals,
+                scene,
+            }),
+        }
+    }
+
+    /// Show `built`, which was built from `options`, instead of the current scene and return that
+    fn install_scene(&mut self, built: BuiltScene, options: &Options) -> BuiltScene {
+        let previous = BuiltScene {
+            config: mem::replace(&mut self.config, built.config),
+            globals: mem::replace(&mut self.globals, built.globals),
+            scene: mem::replace(&mut self.scene, built.scene),
+        };
+        self.shown_options = options.clone();
+        self.touch_controls = TouchControls::new(self.globals.globals.params);
+        self.set_params(self.globals.globals.params);
+        let camera = camera_controlled(options, &self.config, &self.scene);
+        if camera != self.camera_controls.is_some() {
+            self.camera_controls = camera.then(CameraControls::new);
+        }
+        let fractal = self.config.preset == Preset::Fractal;
+        if fractal != self.fractal_controls.is_some() {
+            self.fractal_controls = fractal.then(FractalControls::new);
+        }
+        let paint = self.config.preset == Preset::Paint;
+        if paint != self.paint_controls.is_some() {
+            self.paint_controls = paint.then(PaintControls::new);
+        }
+        previous
+    }
+
+    /// Build the scenes of the `--playlist` up front, shaders that fail are left out
+    fn build_playlist(&mut self) {
+        let Some(playlist) = &self.playlist else {
+            return;
+        };
+        let shaders = playlist.shaders().to_vec();
+        let shown = playlist.index();
+        let mut broken = Vec::new();
+        for (index, shader) in shaders.into_iter().enumerate() {
+            if index == shown {
+                self.playlist_scenes.push(None);
+                continue;
+            }
+            let options = Options {
+                shader: Some(shader),
+                ..self.options.clone()
+            };
+            match self.build_scene(&options) {
+                Some(built) => self.playlist_scenes.push(Some(built)),
+                None => broken.push(index),
+            }
+        }
+        let playlist = self.playlist.as_mut().unwrap();
+        for &index in broken.iter().rev() {
+            log::warn!(
+                "Leaving {} out of the playlist",
+                playlist.shaders()[index].display()
+            );
+            playlist.remove(index);
+        }
+        log::info!("Playlist of {} shaders", playlist.shaders().len());
+    }
+
+    /// Show the playlist entry `steps` away from the current one, crossfading with `--crossfade`
+    fn step_playlist(&mut self, steps: isize) {
+        let Some(playlist) = &mut self.playlist else {
+            return;
+        };
+        let previous = playlist.index();
+        let next = playlist.step(steps);
+        if next == previous {
+            return;
+        }
+        let options = Options {
+            shader: Some(playlist.shaders()[next].clone()),
+            ..self.options.clone()
+        };
+        // Scenes built for another image size, e.g. before power saving kicked in, are built again
+        let image_size = (self.config.width, self.config.height);
+        let built = self.playlist_scenes[next]
+            .take()
+            .filter(|built| (built.config.width, built.config.height) == image_size)
+            .or_else(|| self.build_scene(&options));
+        let Some(mut built) = built else {
+            self.playlist
+                .as_mut()
+                .unwrap()
+                .step(previous as isize - next as isize);
+            return;
+        };
+
+        if let Some((index, fading, _)) = self.fading_out.take() {
+            self.playlist_scenes[index] = Some(fading);
+        }
+        // Carry on with the frame count and clock, but start from the initial parameters
+        built.globals.globals = Globals {
+            resolution: built.globals.globals.resolution,
+            params: built.globals.globals.params,
+            ..self.globals.globals
+        };
+        let scene = self.install_scene(built, &options);
+        self.options = options;
+        log::info!("Drawing {}", self.config.shader_name());
+
+        let crossfade = Duration::try_from_secs_f32(self.options.crossfade)
+            .ok()
+            .filter(|crossfade| !crossfade.is_zero());
+        if let Some(duration) = crossfade
+            && self.config.msaa_samples == 1
+        {
+            scene
+                .scene
+                .render_state
+                .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+            self.fading_out = Some((previous, scene, Crossfade::new(duration)));
+        } else {
+            self.playlist_scenes[previous] = Some(scene);
+        }
+        self.scene
+            .render_state
+            .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+    }
+
+    /// Move on once the playlist entry was shown for the interval, and fade in the current one
+    fn update_playlist(&mut self) {
+        if let Some((_, _, crossfade)) = &self.fading_out {
+            if crossfade.progress() >= 1.0 {
+                let (index, fading, _) = self.fading_out.take().unwrap();
+                self.playlist_scenes[index] = Some(fading);
+            }
+            self.scene
+                .render_state
+                .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+        }
+        if self.playlist.as_ref().is_some_and(Playlist::due) {
+            self.step_playlist(1);
+        }
+    }
+
+    /// Draw a dropped `.wgsl` shader, or show a dropped PNG in the next free of the [`DROP_SLOTS`],
+    /// confirmed by the status strip
+    fn handle_dropped_file(&mut self, path: PathBuf) {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let loaded = match extension.as_deref() {
+            Some("wgsl" | "glsl" | "frag") => self.load_dropped_shader(path),
+            Some("png") => self.load_dropped_image(path),
+            _ => {
+                log::error!(
+                    "Can't open {}, drop a .wgsl or .glsl shader or a .png image",
+                    path.display()
+                );
+                false
+            }
+        };
+        if loaded {
+            self.status_strip.succeeded();
+        } else {
+            self.status_strip.failed();
+        }
+        self.scene
+            .render_state
+            .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+    }
+
+    fn load_dropped_shader(&mut self, path: PathBuf) -> bool {
+        let mut options = self.options.clone();
+        options.shader = Some(path);
+        options.example = None;
+        if !self.load_scene(&options) {
+            return false;
+        }
+        log::info!("Drawing {}", self.config.shader_name());
+        self.options = options;
+        // Watch the dropped shader instead of the previous one
+        if let Some(live) = &mut self.live
+            && let Some(shader) = &self.config.shader_path
+        {
+            *live = LiveCoding::new(
+                shader,
+                self.gpu_state.draw_mode,
+                globals::push_constants_enabled(&self.gpu_state.device),
+                &self.config,
+            );
+        }
+        true
+    }
+
+    fn load_dropped_image(&mut self, path: PathBuf) -> bool {
+        let slot_name = |slot: usize| format!("dropped {}", slot + 1);
+        let Some(slot) = (0..DROP_SLOTS.len()).find(|&slot| {
+            !self
+                .config
+                .quads
+                .iter()
+                .any(|quad| quad.name == slot_name(slot))
+        }) else {
+            log::error!(
+                "All {} slots for dropped images are taken",
+                DROP_SLOTS.len()
+            );
+            return false;
+        };
+
+        let name = slot_name(slot);
+        self.config.quads.push(Quad {
+            name: name.clone(),
+            source: None,
+            image: Some(path),
+            position: DROP_SLOTS[slot],
+            scale: [0.5, 0.5],
+            rotation: 0.0,
+        });
+        if self.reload() && self.scene.render_state.has_quad(&name) {
+            log::info!("Showing the dropped image in slot {}", slot + 1);
+            true
+        } else {
+            self.config.quads.pop();
+            false
+        }
+    }
+
+    fn handle_signals(&mut self, elwt: &ActiveEventLoop) {
+        let Some(signals) = &self.signals else {
+            return;
+        };
+
+        if signals.terminate_requested() {
+            log::info!("Shutting down");
+            elwt.exit();
+        } else if signals.take_reload() {
+            self.reload_config_file(elwt);
+            self.reload();
+        }
+    }
+
+    /// Throttle or stop throttling when the power situation changed
+    fn update_power_saving(&mut self, window: &Window) {
+        let Some(throttle) = self.power_saving.as_mut().and_then(PowerSaving::poll) else {
+            return;
+        };
+
+        if throttle {
+            window.set_title(&format!("{TITLE} (power saving)"));
+        } else {
+            window.set_title(TITLE);
+        }
+        self.resize_image();
+    }
+
+    /// Whether frames are still needed while the window can't be seen,
+    /// by recordings, other outputs or followers, or to reach `--frames`
+    fn draws_while_hidden(&self) -> bool {
+        self.recorder.is_some()
+            || self.ndi_output.is_some()
+            || !self.output_windows.is_empty()
+            || self.frame_hasher.is_some()
+            || self.sync_leader.is_some()
+            || self.options.frames.is_some()
+    }
+
+    /// Size of the drawn image, that of the profile unless saving power
+    fn image_size(&self) -> (u32, u32) {
+        let size = self.options.resolution();
+        self.power_saving
+            .as_ref()
+            .map_or(size, |power_saving| power_saving.image_size(size))
+    }
+
+    /// Rebuild the scene at [`App::image_size`]
+    fn resize_image(&mut self) {
+        let previous_config = self.config.clone();
+        self.config
+            .set_image_size(self.image_size(), &self.gpu_state.device);
+        let mut globals = self.globals.recreate(
+            &self.gpu_state.device,
+            &self.gpu_state.queue,
+            self.config.width,
+            self.config.height,
+        );
+        globals.globals.resolution = self.config.resolution();
+
+        match Scene::new(
+            &self.gpu_state,
+            &globals,
+            &self.config,
+            self.tonemap_params(),
+        ) {
+            Ok(scene) => {
+                self.globals = globals;
+                self.scene = scene;
+                self.set_params(self.globals.globals.params);
+            }
+            Err(err) => {
+                log::error!("Failed to read shader, keeping the previous size: {err}");
+                self.config = previous_config;
+            }
+        }
+    }
+
+    /// Apply the project again once its file was saved
+    fn check_project(&mut self) {
+        if self
+            .project_watcher
+            .as_mut()
+            .is_some_and(ProjectWatcher::poll)
+        {
+            self.reload_project();
+        }
+    }
+
+    /// Start over with the project applied to the command line options.
+    /// The old scene keeps running if the project is broken
+    fn reload_project(&mut self) {
+        let Some(project_watcher) = &self.project_watcher else {
+            return;
+        };
+        log::info!("Reloading the project");
+        match project::apply(&project_watcher.command_line) {
+            Ok(options) => {
+                self.options = options;
+                self.load_scene(&self.options.clone());
+                self.exposure = self.options.exposure();
+                if let Some(tonemapper) = self.options.tonemap {
+                    self.tonemapper = tonemapper;
+                }
+                self.update_tonemapping();
+            }
+            Err(err) => log::error!("Failed to load project, keeping the previous one: {err}"),
+        }
+    }
+
+    /// Read the `--config` file again and apply it, keeping the previous settings if it fails to load.
+    /// The quads and the convolution take effect with the next scene built
+    fn reload_config_file(&mut self, elwt: &ActiveEventLoop) {
+        let Some(path) = self.options.config.as_deref() else {
+            return;
+        };
+        log::info!("Reloading the config file");
+        let config_file = match ConfigFile::load(path) {
+            Ok(config_file) => config_file,
+            Err(err) => {
+                log::error!("Failed to read config file, keeping the previous one: {err}");
+                return;
+            }
+        };
+        let triggers = match Triggers::new(config_file.triggers) {
+            Ok(triggers) => triggers,
+            Err(err) => {
+                log::error!("Invalid trigger in config file, keeping the previous one: {err}");
+                return;
+            }
+        };
+
+        self.triggers = triggers;
+        self.config.quads = config_file.quads;
+        // `--convolve` overrides the config file
+        if self.options.convolve.is_none() {
+            self.config.convolution = config_file
+                .convolution
+                .filter(|_| self.gpu_state.draw_mode == DrawMode::Compute);
+        }
+        self.crop_controls = CropControls::new(config_file.crops);
+        self.output_windows = config_file
+            .windows
+            .iter()
+            .filter_map(|window_config| {
+                OutputWindow::open(
+                    elwt,
+                    &self.gpu_state,
+                    self.options.color_space,
+                    window_config,
+                )
+                .inspect_err(|err| {
+                    log::error!("Failed to open window {:?}: {err}", window_config.title)
+                })
+                .ok()
             })
-            .expect("Failed to run event loop");
+            .collect();
+        if self.gamepad.is_some() {
+            let bindings = config_file
+                .gamepad
+                .unwrap_or_else(gamepad::default_bindings);
+            match GamepadInput::open(bindings) {
+                Ok(gamepad) => self.gamepad = Some(gamepad),
+                Err(err) => {
+                    log::error!("Failed to reopen gamepads, keeping the previous bindings: {err}")
+                }
+            }
+        }
+        let attracting = self
+            .attract
+            .as_ref()
+            .is_some_and(|attract| attract.current().is_some());
+        self.attract = config_file.attract.as_ref().map(AttractMode::new);
+        // The new attract mode starts out waiting for the installation to go idle
+        if attracting {
+            self.load_scene(&self.options.clone());
+        }
+    }
+
+    /// Swap in the latest edit once it validated, between two frames, and hide the status strip after a while
+    fn update_live(&mut self) {
+        let mut strip_changed = self.status_strip.expire();
+        match self.live.as_mut().and_then(|live| live.poll(&self.config)) {
+            Some(Ok(())) => {
+                if self.reload() {
+                    self.status_strip.succeeded();
+                } else {
+                    self.status_strip.failed();
+                }
+                strip_changed = true;
+            }
+            Some(Err(err)) => {
+                log::error!("Keeping the previous shader: {err}");
+                self.status_strip.failed();
+                strip_changed = true;
+            }
+            None => {}
+        }
+        if strip_changed {
+            self.scene
+                .render_state
+                .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+        }
+    }
+
+    /// Rebuild the scene from the shader on disk, returns whether that succeeded.
+    /// The old scene keeps running if the new one fails to build
+    fn reload(&mut self) -> bool {
+        if self.project_watcher.is_some() {
+            self.reload_project();
+            return true;
+        }
+        log::info!("Reloading");
+
+        let device = &self.gpu_state.device;
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let scene = Scene::new(
+            &self.gpu_state,
+            &self.globals,
+            &self.config,
+            self.tonemap_params(),
+        );
+        let validation_error = gpu::pop_error_scope(device);
+
+        match (scene, validation_error) {
+            (Err(err), _) => {
+                log::error!("Failed to read shader, keeping the previous one: {err}");
+                false
+            }
+            (_, Some(err)) => {
+                log::error!("Invalid shader, keeping the previous one: {err}");
+                false
+            }
+            (Ok(scene), None) => {
+                self.scene = scene;
+                // The simulation restarts with the rates from the command line otherwise
+                self.set_params(self.globals.globals.params);
+                log::info!("Reloaded");
+                true
+            }
+        }
+    }
+
+    /// Replace a sandboxed shader that keeps exceeding the GPU time budget with the built-in drawing
+    fn check_watchdog(&mut self) {
+        if !self
+            .watchdog
+            .as_mut()
+            .is_some_and(|watchdog| watchdog.poll(&self.gpu_state.device))
+        {
+            return;
+        }
+        log::error!(
+            "{} keeps exceeding the GPU time budget of the sandbox, stopping it",
+            self.config.shader_name()
+        );
+        self.config.shader_path = None;
+        self.config.example = None;
+        // Kept when the scene is rebuilt from the options
+        self.options.shader = None;
+        self.watchdog = None;
+        self.reload();
+    }
+
+    /// Rebuild the device and everything created from it, keeping the current parameters.
+    /// Retried every frame until it succeeds
+    fn recover_device(&mut self, window: &Arc<Window>) {
+        log::warn!("Recreating the lost device");
+
+        let size = window.inner_size();
+        let gpu_state = match gpu::block_on(GpuState::new(
+            window,
+            size.width,
+            size.height,
+            &self.options,
+        )) {
+            Some(Ok(gpu_state)) => gpu_state,
+            Some(Err(err)) => {
+                log::error!("{err}");
+                #[cfg(not(target_arch = "wasm32"))]
+                std::thread::sleep(DEVICE_RETRY_DELAY);
+                return;
+            }
+            None => {
+                log::error!("WebGPU devices can't be recreated, reload the page");
+                process::exit(1);
+            }
+        };
+        crash::set_device(&gpu_state);
+
+        // The adapter may differ from the lost one, so resolve the fallbacks again
+        let mut config = SceneConfig::new(&self.shown_options, &gpu_state);
+        config.set_image_size(self.image_size(), &gpu_state.device);
+        config.quads = self.config.quads.clone();
+        if config.convolution.is_none() && gpu_state.draw_mode == DrawMode::Compute {
+            config.convolution = self.config.convolution.clone();
+        }
+        let mut globals = self.globals.recreate(
+            &gpu_state.device,
+            &gpu_state.queue,
+            config.width,
+            config.height,
+        );
+        globals.globals.resolution = config.resolution();
+        self.transfer = self.options.color_space.transfer(gpu_state.surface_format);
+
+        let scene = match Scene::new(&gpu_state, &globals, &config, self.tonemap_params()) {
+            Ok(scene) => scene,
+            Err(err) => {
+                log::error!("Failed to read shader: {err}");
+                process::exit(1);
+            }
+        };
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.device_lost();
+        }
+        if let Some(ndi_output) = &mut self.ndi_output {
+            ndi_output.device_lost();
+        }
+        self.crop_exports.clear();
+        self.pixel_readbacks.clear();
+        if let Some(histogram) = &self.histogram {
+            self.histogram = Some(Histogram::new(
+                &gpu_state.device,
+                gpu_state.pipeline_cache.as_deref(),
+                histogram.shown,
+            ));
+        }
+        if self.auto_exposure.is_some() {
+            self.auto_exposure = Some(AutoExposure::new(
+                &gpu_state.device,
+                gpu_state.pipeline_cache.as_deref(),
+            ));
+        }
+        self.gpu_state = gpu_state;
+        self.config = config;
+        self.globals = globals;
+        self.scene = scene;
+        self.set_params(self.globals.globals.params);
+        // The other playlist scenes belong to the lost device
+        self.playlist_scenes.clear();
+        self.fading_out = None;
+        self.build_playlist();
+        self.output_windows.retain_mut(|output_window| {
+            output_window
+                .reconnect(&self.gpu_state)
+                .inspect_err(|err| log::error!("Closing window: {err}"))
+                .is_ok()
+        });
+        log::info!("Recovered from device loss");
+    }
+
+    /// Draw all but the last `--motion-blur` sample of the frame into the accumulation,
+    /// the frame itself draws the last one at the time this leaves behind
+    fn draw_subframes(&mut self) {
+        let (Some(motion_blur), Some(accumulation)) =
+            (&self.motion_blur, self.scene.accumulation_mut())
+        else {
+            return;
+        };
+        accumulation.reset();
+        let queue = &self.gpu_state.queue;
+        let offsets: Vec<f32> = motion_blur.offsets(self.globals.globals.frame).collect();
+        let Some((&last, samples)) = offsets.split_last() else {
+            return;
+        };
+        for &offset in samples {
+            self.globals.set_subframe(queue, offset);
+            let mut encoder =
+                self.gpu_state
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("Motion Blur Encoder"),
+                    });
+            self.scene.encode(queue, &mut encoder, &self.globals);
+            queue.submit(Some(encoder.finish()));
+        }
+        self.globals.set_subframe(queue, last);
     }
 
     fn render_frame(&mut self) {
-        // 1. Dispatch compute shader
+        self.frame_pacer.frame_drawn();
+        if self.paused_at.is_some() {
+            self.present_paused();
+            return;
+        }
+        if let Some(dirty_regions) = &mut self.dirty_regions {
+            self.globals.set_region(Some(dirty_regions.take()));
+        }
+        match self.synced_frame.take() {
+            Some((frame, time)) => self.globals.set_frame(&self.gpu_state.queue, frame, time),
+            None => self.globals.next_frame(&self.gpu_state.queue),
+        }
+        if let Some(sync_leader) = &mut self.sync_leader {
+            let globals = &self.globals.globals;
+            sync_leader.send(&SyncMessage {
+                frame: globals.frame,
+                time: globals.time,
+                seed: globals.seed,
+                params: globals.params,
+                scene: self.config.scene_name(),
+            });
+        }
+        if let Some(audio) = &mut self.audio {
+            audio.update(&self.gpu_state.queue, &self.globals.audio);
+        }
+        if let Some(compute_state) = self.scene.compute_state() {
+            compute_state.set_pointer(&self.gpu_state.queue, self.pointer);
+        }
+        self.globals
+            .set_touches(&self.gpu_state.queue, self.touch_input.next_frame());
+        if let Some(gamepad) = &self.gamepad {
+            self.globals
+                .set_gamepads(&self.gpu_state.queue, gamepad.gamepads());
+        }
+        if let Some(camera_controls) = &mut self.camera_controls
+            && camera_controls.update()
+        {
+            self.globals
+                .set_camera(&self.gpu_state.queue, camera_controls.camera());
+            // Samples seen from elsewhere would smear
+            if let Some(accumulation) = self.scene.accumulation_mut() {
+                accumulation.reset();
+            }
+        }
+        if let Some(fractal_controls) = &self.fractal_controls {
+            let view = fractal_controls.view(self.globals.globals.resolution);
+            if view != self.globals.fractal_view {
+                self.globals.set_fractal_view(&self.gpu_state.queue, view);
+                if let Some(accumulation) = self.scene.accumulation_mut() {
+                    accumulation.reset();
+                }
+            }
+        }
+        if let Some(paint_controls) = &mut self.paint_controls
+            && let Some(Drawing::Paint(paint)) = self.scene.drawing_mut()
+        {
+            paint.collect_history(&self.gpu_state.device);
+            if paint_controls.take_stroke_start() {
+                paint.snapshot(&self.gpu_state.device, &self.gpu_state.queue);
+            }
+            paint.add_segments(paint_controls.take_segments());
+        }
+        let has_cubemap = self
+            .scene
+            .compute_state()
+            .is_some_and(ComputeState::has_cubemap);
+        let preview = (self.cubemap_preview && has_cubemap).then(|| {
+            self.camera_controls
+                .as_ref()
+                .map_or_else(Camera::default, CameraControls::camera)
+        });
+        self.scene
+            .render_state
+            .set_preview(&self.gpu_state.queue, preview);
+
+        self.draw_subframes();
+
+        // Drawing the image and rendering it to the window are recorded into one encoder
+        // and submitted at once
         let mut encoder =
             self.gpu_state
                 .device
                 .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("Compute Encoder"),
+                    label: Some("Frame Encoder"),
                 });
 
-        self.compute_state.dispatch(&mut encoder, WIDTH, HEIGHT);
-        self.gpu_state.queue.submit(Some(encoder.finish()));
+        // 1. Draw the image, either by compute dispatch or by fragment pass
+        let drawn = self
+            .scene
+            .encode(&self.gpu_state.queue, &mut encoder, &self.globals);
+        if drawn && let Some((_, fading, _)) = &mut self.fading_out {
+            fading.globals.next_frame(&self.gpu_state.queue);
+            fading
+                .scene
+                .encode(&self.gpu_state.queue, &mut encoder, &fading.globals);
+        }
 
-        // 2. Render to window
-        let frame = match self.gpu_state.surface.get_current_texture() {
-            Ok(frame) => frame,
-            Err(_) => {
-                self.gpu_state.reconfigure_surface();
-                self.gpu_state
-                    .surface
-                    .get_current_texture()
-                    .expect("Failed to acquire next swap chain texture")
+        // 2. Render to window, the image is still drawn and captured when the surface isn't available
+        let frame = self.acquire_frame();
+        if let Some(frame) = &frame {
+            self.render_to_window(&mut encoder, &frame.texture);
+        }
+
+        self.gpu_state.queue.submit(Some(encoder.finish()));
+        if drawn {
+            if let Some(watchdog) = &self.watchdog {
+                watchdog.submitted(&self.gpu_state.queue);
+            }
+            if let Some(telemetry) = &mut self.telemetry {
+                let globals = &self.globals.globals;
+                telemetry.submitted(
+                    &self.gpu_state.queue,
+                    FrameTelemetry {
+                        frame: globals.frame,
+                        time: globals.time,
+                        seed: globals.seed,
+                        params: globals.params,
+                        camera: self.camera_controls.as_ref().map(CameraControls::camera),
+                        gpu_ms: None,
+                    },
+                );
             }
+        }
+        self.read_back_frame();
+
+        let Some(frame) = frame else {
+            return;
         };
+        frame.present();
+
+        let tonemap_params = self.tonemap_params();
+        for output_window in &mut self.output_windows {
+            output_window.render(
+                &self.gpu_state,
+                &self.scene,
+                &self.config,
+                &self.globals,
+                tonemap_params,
+            );
+        }
 
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        if self.signals.is_some() {
+            self.status.frame_rendered();
+        }
+    }
 
-        let mut render_encoder =
+    /// Show the last drawn image again while paused, e.g. after a resize or with another exposure
+    fn present_paused(&mut self) {
+        let Some(frame) = self.acquire_frame() else {
+            return;
+        };
+        let mut encoder =
             self.gpu_state
                 .device
                 .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("Render Encoder"),
+                    label: Some("Paused Frame Encoder"),
                 });
+        self.render_to_window(&mut encoder, &frame.texture);
+        self.gpu_state.queue.submit(Some(encoder.finish()));
+        frame.present();
+    }
 
-        self.render_state.render(&mut render_encoder, &view);
+    /// The surface texture to render into, `None` if it isn't available this frame
+    fn acquire_frame(&mut self) -> Option<wgpu::SurfaceTexture> {
+        match self.gpu_state.surface.get_current_texture() {
+            Ok(frame) => Some(frame),
+            Err(err) => {
+                match SurfaceRecovery::for_error(&err) {
+                    SurfaceRecovery::Reconfigure => self.gpu_state.reconfigure_surface(),
+                    SurfaceRecovery::SkipFrame => log::warn!("Skipping frame: {err}"),
+                    SurfaceRecovery::RecreateDevice => {
+                        log::error!("{err}");
+                        self.gpu_state.health.mark_lost();
+                    }
+                }
+                None
+            }
+        }
+    }
 
-        self.gpu_state.queue.submit(Some(render_encoder.finish()));
-        frame.present();
+    /// Record the render passes of the scene and the one fading out into `encoder`
+    fn render_to_window(&mut self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::Texture) {
+        self.scene
+            .render_state
+            .fit(&self.gpu_state.queue, (target.width(), target.height()));
+
+        if let Some((_, fading, _)) = &mut self.fading_out {
+            fading
+                .scene
+                .render_state
+                .fit(&self.gpu_state.queue, (target.width(), target.height()));
+            fading.scene.render_state.render(
+                &self.gpu_state.device,
+                encoder,
+                target,
+                &fading.globals,
+                true,
+            );
+        }
+        self.scene.render_state.render(
+            &self.gpu_state.device,
+            encoder,
+            target,
+            &self.globals,
+            self.fading_out.is_none(),
+        );
+    }
+
+    /// Start the readbacks and analyses of the drawn image, after the frame was submitted
+    fn read_back_frame(&mut self) {
+        if let Some(compute_state) = self.scene.compute_state_mut() {
+            compute_state.read_back_feedback(
+                &self.gpu_state.device,
+                &self.gpu_state.queue,
+                self.globals.globals.frame,
+            );
+        }
+        if let Some(frame_hasher) = &mut self.frame_hasher
+            && let Some(texture) = self.scene.presented_texture()
+        {
+            frame_hasher.hash_frame(
+                &self.gpu_state.device,
+                &self.gpu_state.queue,
+                texture,
+                self.globals.globals.frame,
+            );
+        }
+        if let Some(recorder) = &mut self.recorder
+            && let Some(texture) = self.scene.presented_texture()
+        {
+            recorder.capture(
+                &self.gpu_state.device,
+                &self.gpu_state.queue,
+                texture,
+                self.globals.globals.frame,
+            );
+            if let Some(soundtrack) = &mut self.soundtrack
+                && let Some(audio) = &mut self.audio
+                && let Err(err) = soundtrack.push_frame(&audio.take_recorded())
+            {
+                log::error!("Failed to record the audio input: {err}");
+                self.soundtrack = None;
+            }
+        }
+        if let Some(ndi_output) = &mut self.ndi_output
+            && let Some(texture) = self.scene.presented_texture()
+        {
+            ndi_output.capture(&self.gpu_state.device, &self.gpu_state.queue, texture);
+        }
+        if let Some(histogram) = &mut self.histogram
+            && let Some(texture) = self.scene.presented_texture()
+        {
+            histogram.analyze(
+                &self.gpu_state.device,
+                &self.gpu_state.queue,
+                texture,
+                self.globals.globals.frame,
+            );
+        }
+        if let Some(auto_exposure) = &mut self.auto_exposure
+            && let Some(texture) = self.scene.presented_texture()
+        {
+            auto_exposure.analyze(&self.gpu_state.device, &self.gpu_state.queue, texture);
+        }
+        if let Some(path) = self.equirect.take() {
+            self.export_equirect(path);
+        }
+    }
+
+    fn handle_key(&mut self, key: &str) {
+        match key {
+            "r" | "R" => self.reset(),
+            "c" | "C" => {
+                self.crop_controls.select_next();
+                self.update_tonemapping();
+            }
+            "e" | "E" => self.export_crop(),
+            "f" | "F" => {
+                if let Some(filter) = self.scene.render_state.toggle_filter() {
+                    log::info!("Filtering: {filter:?}");
+                    // Kept when the scene is rebuilt
+                    self.config.filter = filter;
+                }
+            }
+            "s" | "S" => self.cycle_supersampling(),
+            "v" | "V" => {
+                if let Some(camera_controls) = &mut self.camera_controls {
+                    camera_controls.toggle_mode();
+                }
+            }
+            "p" | "P"
+                if self
+                    .scene
+                    .compute_state()
+                    .is_some_and(ComputeState::has_cubemap) =>
+            {
+                self.cubemap_preview = !self.cubemap_preview;
+                log::info!("Cubemap preview: {}", self.cubemap_preview);
+            }
+            "j" | "J" => {
+                if let Some(fractal_controls) = &mut self.fractal_controls {
+                    fractal_controls.toggle_julia();
+                }
+            }
+            "x" | "X" => {
+                if let Some(paint_controls) = &mut self.paint_controls {
+                    paint_controls.next_color();
+                }
+            }
+            "z" | "Z" if self.modifiers.control_key() || self.modifiers.super_key() => {
+                if self.modifiers.shift_key() {
+                    self.redo_paint();
+                } else if let Some(Drawing::Paint(paint)) = self.scene.drawing_mut()
+                    && !paint.undo(&self.gpu_state.device, &self.gpu_state.queue)
+                {
+                    log::info!("Nothing to undo");
+                }
+            }
+            "y" | "Y" if self.modifiers.control_key() => self.redo_paint(),
+            "b" | "B" => self.toggle_bloom(),
+            "h" | "H" => self.toggle_histogram(),
+            "[" => self.tune_bloom(|params| params.intensity /= 1.25),
+            "]" => self.tune_bloom(|params| params.intensity *= 1.25),
+            "{" => self.tune_bloom(|params| params.threshold -= 0.25),
+            "}" => self.tune_bloom(|params| params.threshold += 0.25),
+            "t" | "T" => {
+                self.tonemapper = self.tonemapper.next();
+                self.update_tonemapping();
+            }
+            "+" | "=" => {
+                self.exposure *= 1.25;
+                self.update_tonemapping();
+            }
+            "-" => {
+                self.exposure /= 1.25;
+                self.update_tonemapping();
+            }
+            _ => {
+                if let Some(example) = Example::for_key(key) {
+                    self.select_example(example);
+                }
+            }
+        }
+    }
+
+    fn redo_paint(&mut self) {
+        if let Some(Drawing::Paint(paint)) = self.scene.drawing_mut()
+            && !paint.redo(&self.gpu_state.device, &self.gpu_state.queue)
+        {
+            log::info!("Nothing to redo");
+        }
+    }
+
+    /// Draw a bundled example with its parameters instead of the shader or example shown
+    fn select_example(&mut self, example: Example) {
+        let mut options = self.options.clone();
+        options.preset = Preset::Drawing;
+        options.shader = None;
+        options.example = Some(example);
+        options.params = None;
+        self.select(options);
+    }
+
+    /// Draw the preset after the current one, with its own shader
+    fn next_preset(&mut self) {
+        let presets = Preset::value_variants();
+        let index = presets
+            .iter()
+            .position(|preset| *preset == self.options.preset)
+            .map_or(0, |index| (index + 1) % presets.len());
+        let mut options = self.options.clone();
+        options.preset = presets[index];
+        options.shader = None;
+        options.example = None;
+        options.params = None;
+        self.select(options);
+    }
+
+    /// Switch to the scene of `options` for good, leaving the files, playlist and crossfade of the one shown
+    fn select(&mut self, options: Options) {
+        if !self.load_scene(&options) {
+            return;
+        }
+        log::info!("Drawing {}", self.config.shader_name());
+        self.options = options;
+        // There is no file left to watch or cycle through
+        self.live = None;
+        self.playlist = None;
+        self.playlist_scenes.clear();
+        self.fading_out = None;
+        self.scene
+            .render_state
+            .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+    }
+
+    fn handle_named_key(&mut self, key: NamedKey, window: &Window) {
+        match key {
+            NamedKey::F11 => self.toggle_fullscreen(window),
+            NamedKey::Enter if self.modifiers.alt_key() => self.toggle_fullscreen(window),
+            NamedKey::Tab => self.next_entry_point(),
+            NamedKey::Space => self.toggle_pause(),
+            NamedKey::PageDown => self.step_playlist(1),
+            NamedKey::PageUp => self.step_playlist(-1),
+            NamedKey::Home => {
+                if let Some(camera_controls) = &mut self.camera_controls {
+                    camera_controls.reset();
+                }
+                if let Some(fractal_controls) = &mut self.fractal_controls {
+                    fractal_controls.reset();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Hand W, A, S and D to the camera, returns whether they were consumed.
+    /// Shift+S still cycles the supersampling
+    fn handle_camera_key(&mut self, code: KeyCode, state: ElementState) -> bool {
+        let Some(camera_controls) = &mut self.camera_controls else {
+            return false;
+        };
+        let pressed = state == ElementState::Pressed;
+        if pressed && self.modifiers.shift_key() {
+            return false;
+        }
+        camera_controls.handle_key(code, pressed)
+    }
+
+    /// Switch between drawing at 1, 2 and 4 times the resolution
+    fn cycle_supersampling(&mut self) {
+        if self.gpu_state.draw_mode != DrawMode::Compute {
+            log::info!("Supersampling requires compute shaders");
+            return;
+        }
+        self.config.supersample = match self.config.supersample {
+            1 => 2,
+            2 | 3 => 4,
+            _ => 1,
+        };
+        // Kept when the scene is rebuilt from the options
+        self.options.supersample = self.config.supersample;
+        self.resize_image();
+        log::info!("Supersampling: {}x", self.config.supersample);
+    }
+
+    fn toggle_histogram(&mut self) {
+        if self.gpu_state.draw_mode != DrawMode::Compute {
+            log::info!("The histogram requires compute shaders");
+            return;
+        }
+        match &mut self.histogram {
+            Some(histogram) => histogram.shown = !histogram.shown,
+            None => {
+                self.histogram = Some(Histogram::new(
+                    &self.gpu_state.device,
+                    self.gpu_state.pipeline_cache.as_deref(),
+                    true,
+                ))
+            }
+        }
+        self.scene
+            .render_state
+            .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+    }
+
+    fn toggle_bloom(&mut self) {
+        if self.gpu_state.draw_mode != DrawMode::Compute {
+            log::info!("Bloom requires compute shaders");
+            return;
+        }
+        self.config.bloom = match self.config.bloom {
+            Some(_) => None,
+            None => Some(BloomParams::new(
+                self.options.bloom_threshold,
+                if self.options.bloom > 0.0 {
+                    self.options.bloom
+                } else {
+                    DEFAULT_BLOOM
+                },
+            )),
+        };
+        // Kept when the scene is rebuilt from the options
+        self.options.bloom = self.config.bloom.map_or(0.0, |params| params.intensity);
+        self.resize_image();
+        log::info!(
+            "Bloom: {}",
+            if self.config.bloom.is_some() {
+                "on"
+            } else {
+                "off"
+            }
+        );
+    }
+
+    /// Adjust the bloom without rebuilding the scene
+    fn tune_bloom(&mut self, tune: impl FnOnce(&mut BloomParams)) {
+        let (Some(params), Some(bloom)) = (&mut self.config.bloom, self.scene.bloom()) else {
+            log::info!("Bloom is off, press B to turn it on");
+            return;
+        };
+        tune(params);
+        params.threshold = params.threshold.max(0.0);
+        bloom.set_params(&self.gpu_state.queue, *params);
+        // Kept when the scene is rebuilt from the options
+        self.options.bloom = params.intensity;
+        self.options.bloom_threshold = params.threshold;
+        log::info!(
+            "Bloom: intensity {:.2}, threshold {:.2}",
+            params.intensity,
+            params.threshold
+        );
+    }
+
+    /// Dispatch the next `@compute` entry point of the drawing shader
+    fn next_entry_point(&mut self) {
+        let Some(compute_state) = self.scene.compute_state_mut() else {
+            log::info!("Only compute drawings have entry points to switch between");
+            return;
+        };
+        match compute_state.next_entry_point(&self.gpu_state.device) {
+            Some(entry_point) => {
+                log::info!("Entry point: {entry_point}");
+                // Kept when the scene is rebuilt
+                self.config.entry_point = Some(entry_point.to_owned());
+                self.reset();
+            }
+            None => log::info!("The drawing shader has no other entry point"),
+        }
+    }
+
+    /// The window resizes, and with it the surface, the image keeps its resolution
+    fn toggle_fullscreen(&self, window: &Window) {
+        self.options.fullscreen.unwrap_or_default().toggle(window);
+    }
+
+    fn update_tonemapping(&self) {
+        log::info!(
+            "Tonemapping: {:?}, exposure {:.2}",
+            self.tonemapper,
+            self.exposure
+        );
+        self.scene
+            .render_state
+            .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+    }
+
+    fn tonemap_params(&self) -> TonemapParams {
+        let brightness = self.attract.as_ref().map_or(1.0, AttractMode::brightness);
+        let auto_exposure = self
+            .auto_exposure
+            .as_ref()
+            .map_or(1.0, AutoExposure::exposure);
+        let mut params = TonemapParams::new(
+            self.tonemapper,
+            self.exposure * brightness * auto_exposure,
+            self.transfer,
+        );
+        if let Some(histogram) = self.histogram.as_ref().filter(|histogram| histogram.shown) {
+            params.histogram_shown = 1;
+            params.histogram = histogram.bars();
+        }
+        if let Some((_, crop)) = self.crop_controls.selected() {
+            params.selection = crop.corners();
+        }
+        params.status = self.status_strip.color();
+        if let Some((_, _, crossfade)) = &self.fading_out {
+            params.progress = crossfade.progress();
+            params.transition = self.options.transition as u32;
+        }
+        params
+    }
+
+    fn handle_touch(&mut self, touch: &Touch, window: &Window) {
+        let position = self.image_pixel(self.image_fraction(touch.location, window));
+        self.touch_input.handle(touch, position);
+        // Shaders following `globals.mouse` follow the first finger, pressed like the left button
+        if self.touch_controls.primary(touch) {
+            self.handle_cursor(touch.location, window);
+            match touch.phase {
+                TouchPhase::Started => {
+                    self.handle_mouse_button(ElementState::Pressed, MouseButton::Left)
+                }
+                TouchPhase::Ended | TouchPhase::Cancelled => {
+                    self.handle_mouse_button(ElementState::Released, MouseButton::Left)
+                }
+                TouchPhase::Moved => {}
+            }
+        }
+        match self.touch_controls.handle(touch, window.inner_size()) {
+            Some(TouchAction::SetParams(params)) => self.set_params(params),
+            Some(TouchAction::Reset) => {
+                self.touch_input.reset_view();
+                self.reset();
+            }
+            None => {}
+        }
+    }
+
+    /// Where `position` in the window is on the drawn image, in `0..1` across it
+    fn image_fraction(&self, position: PhysicalPosition<f64>, window: &Window) -> [f32; 2] {
+        let window_size = window.inner_size();
+        self.scene.render_state.image_position([
+            (position.x / window_size.width.max(1) as f64) as f32,
+            (position.y / window_size.height.max(1) as f64) as f32,
+        ])
+    }
+
+    /// Pixel of the whole image at `fraction` of the drawn part
+    fn image_pixel(&self, fraction: [f32; 2]) -> [f32; 2] {
+        let [left, top] = self.config.image_offset().map(|v| v as f32);
+        [
+            left + fraction[0] * self.config.width as f32,
+            top + fraction[1] * self.config.height as f32,
+        ]
+    }
+
+    /// Track the cursor in pixels of the drawn image, as placed in the window by the [`Scaling`](crate::render::Scaling)
+    fn handle_cursor(&mut self, position: PhysicalPosition<f64>, window: &Window) {
+        let fraction = self.image_fraction(position, window);
+        let [width, height] = [self.config.width as f32, self.config.height as f32];
+        self.globals.globals.mouse = self.image_pixel(fraction);
+        self.pointer.position = self.globals.globals.mouse;
+        if let Some(camera_controls) = &mut self.camera_controls {
+            camera_controls.handle_cursor(position);
+        }
+        if let Some(dirty_regions) = &mut self.dirty_regions {
+            dirty_regions.cursor_moved([fraction[0] * width, fraction[1] * height]);
+        }
+        if let Some(fractal_controls) = &mut self.fractal_controls {
+            fractal_controls
+                .handle_cursor(self.globals.globals.mouse, self.globals.globals.resolution);
+        }
+        if let Some(paint_controls) = &mut self.paint_controls {
+            paint_controls.handle_cursor([fraction[0] * width, fraction[1] * height]);
+        }
+
+        self.crop_controls.cursor_moved(fraction);
+        if self.crop_controls.is_dragging() {
+            self.scene
+                .render_state
+                .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+        }
+    }
+
+    /// Track the held buttons for the overlay, dragging with the left one turns the camera
+    /// or pans the fractal or paints and with the right one selects a crop. Alt and a click inspects a pixel
+    fn handle_mouse_button(&mut self, state: ElementState, button: MouseButton) {
+        let bit = match button {
+            MouseButton::Left => 1,
+            MouseButton::Right => 2,
+            MouseButton::Middle => 4,
+            _ => 0,
+        };
+        if button == MouseButton::Left && self.modifiers.alt_key() {
+            if state == ElementState::Pressed {
+                self.inspect_pixel();
+            }
+            return;
+        }
+        match state {
+            ElementState::Pressed => self.pointer.buttons |= bit,
+            ElementState::Released => self.pointer.buttons &= !bit,
+        }
+        match button {
+            MouseButton::Left => {
+                if let Some(camera_controls) = &mut self.camera_controls {
+                    camera_controls.handle_button(state == ElementState::Pressed);
+                }
+                if let Some(fractal_controls) = &mut self.fractal_controls {
+                    fractal_controls.handle_button(state == ElementState::Pressed);
+                }
+                if let Some(paint_controls) = &mut self.paint_controls {
+                    paint_controls.handle_button(state == ElementState::Pressed);
+                }
+            }
+            MouseButton::Right => self.handle_crop_drag(state),
+            _ => {}
+        }
+    }
+
+    /// Start reading back the texel of the drawing under the cursor, as it was drawn before tonemapping
+    fn inspect_pixel(&mut self) {
+        let Some(texture) = self.scene.drawing().map(Drawing::output_texture) else {
+            return;
+        };
+        if self.pixel_readbacks.is_full() {
+            log::warn!("Still reading back the pixels inspected before");
+            return;
+        }
+        let [left, top] = self.config.image_offset().map(|v| v as f32);
+        let [x, y] = self.globals.globals.mouse;
+        let fraction = [
+            (x - left) / self.config.width as f32,
+            (y - top) / self.config.height as f32,
+        ];
+        if !fraction.iter().all(|v| (0.0..1.0).contains(v)) {
+            return;
+        }
+        let texel = [
+            (fraction[0] * texture.width() as f32) as u32,
+            (fraction[1] * texture.height() as f32) as u32,
+        ];
+        self.pixel_readbacks.start_region(
+            &self.gpu_state.device,
+            &self.gpu_state.queue,
+            texture,
+            texel,
+            [1, 1],
+            texel,
+        );
+    }
+
+    fn handle_crop_drag(&mut self, state: ElementState) {
+        match state {
+            ElementState::Pressed => self.crop_controls.drag_started(),
+            ElementState::Released => self.crop_controls.drag_ended(),
+        }
+        self.scene
+            .render_state
+            .set_tonemapping(&self.gpu_state.queue, self.tonemap_params());
+    }
+
+    /// Write the selected crop as PNG to the working directory
+    fn export_crop(&mut self) {
+        let Some((name, crop)) = self.crop_controls.selected() else {
+            log::warn!(
+                "No crop selected, press C to pick one or drag one with the right mouse button"
+            );
+            return;
+        };
+        let name = name.to_owned();
+        if let Err(err) = self.read_back_crop(&name, crop) {
+            log::error!("Failed to export crop {name:?}: {err}");
+        }
+    }
+
+    /// Start reading back `crop`, it is written by [`App::poll_readbacks`] once it arrives.
+    /// With a `scale` the drawing is redrawn that much larger,
+    /// which only works for drawings that don't build on previous frames
+    fn read_back_crop(&mut self, name: &str, crop: Crop) -> Result<(), String> {
+        if self.crop_exports.is_full() {
+            return Err("too many crops are being exported".to_owned());
+        }
+        let device = &self.gpu_state.device;
+        let queue = &self.gpu_state.queue;
+        let scale = if crop.scale > 1
+            && (!matches!(self.config.preset, Preset::Drawing | Preset::Sdf)
+                || self.config.accumulate)
+        {
+            log::warn!("Only stateless drawings can be redrawn larger, exporting at scale 1");
+            1
+        } else {
+            crop.scale.max(1)
+        };
+
+        let scaled_scene;
+        let texture = if scale == 1 {
+            self.scene.presented_texture()
+        } else {
+            let (width, height) = (self.config.width * scale, self.config.height * scale);
+            let max_size = device.limits().max_texture_dimension_2d;
+            if width > max_size || height > max_size {
+                return Err(format!(
+                    "{width}x{height} exceeds the maximum texture size of {max_size}"
+                ));
+            }
+            let config = SceneConfig {
+                width,
+                height,
+                ..self.config.clone()
+            };
+            let mut globals = self.globals.recreate(device, queue, width, height);
+            globals.globals.resolution = config.resolution();
+            globals.globals.mouse = globals.globals.mouse.map(|v| v * scale as f32);
+            globals.write(queue);
+
+            let mut scene = Scene::new(&self.gpu_state, &globals, &config, self.tonemap_params())
+                .map_err(|err| err.to_string())?;
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Crop Encoder"),
+            });
+            if scene.encode(queue, &mut encoder, &globals) {
+                queue.submit(Some(encoder.finish()));
+            }
+            scaled_scene = scene;
+            scaled_scene.presented_texture()
+        }
+        .ok_or("frames drawn straight to the window can't be exported")?;
+
+        let export = CropExport {
+            name: name.to_owned(),
+            path: PathBuf::from(format!(
+                "{name}-{}.{}",
+                self.globals.globals.frame,
+                self.options.format.extension()
+            )),
+            rect: crop.pixels((texture.width(), texture.height())),
+            srgb_output: self.options.color_space == ColorSpace::Srgb,
+            format: self.options.format,
+        };
+        self.crop_exports.start(device, queue, texture, export);
+        Ok(())
+    }
+
+    /// Start reading back the equirectangular projection drawn for shaders defining `cubemap`
+    fn export_equirect(&mut self, path: PathBuf) {
+        if !self
+            .scene
+            .compute_state()
+            .is_some_and(ComputeState::has_cubemap)
+        {
+            log::error!("Only drawing shaders defining `cubemap` can be exported with --equirect");
+            return;
+        }
+        let Some(texture) = self.scene.presented_texture() else {
+            return;
+        };
+        let export = CropExport {
+            name: "equirect".to_owned(),
+            path,
+            rect: Crop::FULL.pixels((texture.width(), texture.height())),
+            srgb_output: self.options.color_space == ColorSpace::Srgb,
+            format: self.options.format,
+        };
+        self.crop_exports.start(
+            &self.gpu_state.device,
+            &self.gpu_state.queue,
+            texture,
+            export,
+        );
+    }
+
+    fn set_params(&mut self, params: [f32; 2]) {
+        self.globals.globals.params = params;
+        if let Some(Drawing::ReactionDiffusion(simulation)) = self.scene.drawing() {
+            simulation.set_params(
+                &self.gpu_state.queue,
+                ReactionDiffusionParams::from_controls(params),
+            );
+        }
+        // Samples drawn with the old parameters would linger otherwise
+        self.reset();
+    }
+
+    /// Start accumulating from scratch, and clear the state stateful shaders keep
+    fn reset(&mut self) {
+        self.frame_pacer.invalidate();
+        if let Some(dirty_regions) = &mut self.dirty_regions {
+            dirty_regions.mark_all();
+        }
+        if let Some(accumulation) = self.scene.accumulation_mut() {
+            accumulation.reset();
+        }
+        if let Some(compute_state) = self.scene.compute_state() {
+            compute_state.clear_scratch(&self.gpu_state.device, &self.gpu_state.queue);
+        }
+        if let Some(Drawing::Paint(paint)) = self.scene.drawing_mut() {
+            paint.clear();
+        }
     }
 
     fn handle_resize(&mut self, width: u32, height: u32, window: &Window) {
@@ -0,0 +1,177 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use web_time::Instant;
+
+use crate::{
+    cli::{Options, Preset},
+    examples::Example,
+};
+
+/// `[attract]` table of the config file
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AttractSettings {
+    /// Minutes without input before the attract mode starts
+    pub idle_minutes: f32,
+    /// Exposure factor while attracting, below 1 dims the image
+    pub brightness: f32,
+    /// Seconds each entry of the playlist is shown
+    pub interval_seconds: f32,
+    /// Presets, examples, shader files or library bundles to cycle through.
+    /// When empty, the interactive scene keeps running dimmed
+    pub playlist: Vec<String>,
+}
+
+impl Default for AttractSettings {
+    fn default() -> Self {
+        Self {
+            idle_minutes: 5.0,
+            brightness: 0.5,
+            interval_seconds: 60.0,
+            playlist: Vec::new(),
+        }
+    }
+}
+
+impl AttractSettings {
+    /// Fails for durations the attract mode can't wait for, or one rebuilding the scene every frame
+    pub fn validate(&self) -> Result<(), String> {
+        if Duration::try_from_secs_f32(self.idle_minutes * 60.0).is_err() {
+            return Err(format!(
+                "attract.idle_minutes has to be 0 or more, not {}",
+                self.idle_minutes
+            ));
+        }
+        if Duration::try_from_secs_f32(self.interval_seconds).is_err()
+            || self.interval_seconds <= 0.0
+        {
+            return Err(format!(
+                "attract.interval_seconds has to be above 0, not {}",
+                self.interval_seconds
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Something to show while attracting
+#[derive(Debug)]
+pub enum PlaylistEntry {
+    Preset(Preset),
+    Example(Example),
+    /// Path or library bundle, like `--shader`
+    Shader(PathBuf),
+}
+
+impl PlaylistEntry {
+    /// Preset and example names take precedence over shader files
+    pub fn parse(name: &str) -> Self {
+        if let Ok(preset) = Preset::from_str(name, true) {
+            PlaylistEntry::Preset(preset)
+        } else if let Ok(example) = Example::from_str(name, true) {
+            PlaylistEntry::Example(example)
+        } else {
+            PlaylistEntry::Shader(PathBuf::from(name))
+        }
+    }
+
+    /// `interactive` with the drawing replaced by this entry
+    pub fn options(&self, interactive: &Options) -> Options {
+        let mut options = interactive.clone();
+        options.example = None;
+        options.shader = None;
+        match self {
+            PlaylistEntry::Preset(preset) => options.preset = *preset,
+            PlaylistEntry::Example(example) => {
+                options.preset = Preset::Drawing;
+                options.example = Some(*example);
+            }
+            PlaylistEntry::Shader(path) => {
+                options.preset = Preset::Drawing;
+                options.shader = Some(path.clone());
+            }
+        }
+        options
+    }
+}
+
+/// Cycles through a playlist at reduced brightness while nobody interacts, for installations.
+/// Any input ends it
+pub struct AttractMode {
+    idle_time: Duration,
+    brightness: f32,
+    interval: Duration,
+    playlist: Vec<PlaylistEntry>,
+    last_input: Instant,
+    /// Index into the playlist and when that entry was shown, while attracting
+    showing: Option<(usize, Instant)>,
+}
+
+impl AttractMode {
+    pub fn new(settings: &AttractSettings) -> Self {
+        Self {
+            idle_time: Duration::from_secs_f32(settings.idle_minutes * 60.0),
+            brightness: settings.brightness,
+            interval: Duration::from_secs_f32(settings.interval_seconds),
+            playlist: settings
+                .playlist
+                .iter()
+                .map(|name| PlaylistEntry::parse(name))
+                .collect(),
+            last_input: Instant::now(),
+            showing: None,
+        }
+    }
+
+    /// Exposure factor, 1 unless attracting
+    pub fn brightness(&self) -> f32 {
+        match self.showing {
+            Some(_) => self.brightness,
+            None => 1.0,
+        }
+    }
+
+    /// The playlist entry to show, `None` if the interactive scene should be shown
+    pub fn current(&self) -> Option<&PlaylistEntry> {
+        let (index, _) = self.showing?;
+        self.playlist.get(index)
+    }
+
+    /// Register user input, returns true if this ended the attract mode
+    pub fn input(&mut self) -> bool {
+        self.last_input = Instant::now();
+        self.showing.take().is_some()
+    }
+
+    /// Show the next playlist entry right away, starting the attract mode if needed.
+    /// Returns false if the playlist is empty
+    pub fn skip(&mut self) -> bool {
+        if self.playlist.is_empty() {
+            return false;
+        }
+        let next = self
+            .showing
+            .map_or(0, |(index, _)| (index + 1) % self.playlist.len());
+        self.showing = Some((next, Instant::now()));
+        true
+    }
+
+    /// Start attracting once idle for long enough and move through the playlist,
+    /// returns true when [`AttractMode::current`] or the brightness changed
+    pub fn poll(&mut self) -> bool {
+        match self.showing {
+            None if self.last_input.elapsed() >= self.idle_time => {
+                log::info!("No input for a while, starting attract mode");
+                self.showing = Some((0, Instant::now()));
+                true
+            }
+            Some((index, since)) if self.playlist.len() > 1 && since.elapsed() >= self.interval => {
+                self.showing = Some(((index + 1) % self.playlist.len(), Instant::now()));
+                true
+            }
+            _ => false,
+        }
+    }
+}
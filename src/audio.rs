@@ -0,0 +1,307 @@
+use wgpu::*;
+
+/// Number of spectrum bins and waveform samples in the [`AudioTexture`]
+pub const AUDIO_TEXTURE_WIDTH: u32 = 512;
+
+/// Spectrum and waveform of the audio input, available to drawing shaders as `audio`.
+///
+/// Laid out like the audio channels of Shadertoy, 512×2 texels in `0..1`:
+/// row 0 holds the spectrum from low to high frequencies, row 1 the latest waveform.
+/// Stays black without `--audio`
+pub struct AudioTexture {
+    #[cfg_attr(not(feature = "audio"), allow(dead_code))]
+    pub texture: Texture,
+    pub view: TextureView,
+}
+
+impl AudioTexture {
+    pub fn new(device: &Device) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Audio Texture"),
+            size: Extent3d {
+                width: AUDIO_TEXTURE_WIDTH,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Self { texture, view }
+    }
+
+    /// Upload both rows, each [`AUDIO_TEXTURE_WIDTH`] values long
+    #[cfg_attr(not(feature = "audio"), allow(dead_code))]
+    pub fn write(&self, queue: &Queue, spectrum: &[f32], waveform: &[f32]) {
+        for (row, values) in [spectrum, waveform].into_iter().enumerate() {
+            queue.write_texture(
+                ImageCopyTexture {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: 0,
+                        y: row as u32,
+                        z: 0,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                bytemuck::cast_slice(values),
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(AUDIO_TEXTURE_WIDTH * 4),
+                    rows_per_image: None,
+                },
+                Extent3d {
+                    width: AUDIO_TEXTURE_WIDTH,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+pub use capture::AudioInput;
+
+/// Stand-in for builds without the `audio` feature, which never opens
+#[cfg(not(feature = "audio"))]
+pub struct AudioInput;
+
+#[cfg(not(feature = "audio"))]
+impl AudioInput {
+    pub fn open(_device_name: Option<&str>) -> Result<Self, String> {
+        Err("built without the `audio` feature".to_owned())
+    }
+
+    pub fn update(&mut self, _queue: &Queue, _texture: &AudioTexture) {}
+
+    pub fn sample_rate(&self) -> u32 {
+        0
+    }
+
+    pub fn start_recording(&mut self) {}
+
+    pub fn take_recorded(&mut self) -> Vec<f32> {
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "audio")]
+mod capture {
+    use std::{
+        collections::VecDeque,
+        f32::consts::PI,
+        sync::{Arc, Mutex},
+    };
+
+    use cpal::{
+        FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+    };
+
+    use super::{AUDIO_TEXTURE_WIDTH, AudioTexture};
+
+    /// Samples per spectrum, yielding one bin per texel
+    const FFT_SIZE: usize = 2 * AUDIO_TEXTURE_WIDTH as usize;
+    /// Weight of the previous spectrum, so bars fall off smoothly
+    const SMOOTHING: f32 = 0.8;
+    /// Range mapped to `0..1`, the defaults of the Web Audio analyser Shadertoy uses
+    const MIN_DECIBELS: f32 = -100.0;
+    const MAX_DECIBELS: f32 = -30.0;
+
+    /// Records the default or a named input device and analyses the latest samples once per frame
+    pub struct AudioInput {
+        /// The latest [`FFT_SIZE`] samples, mixed down to mono
+        samples: Arc<Mutex<VecDeque<f32>>>,
+        /// All samples since the last [`AudioInput::take_recorded`], once recording started
+        recorded: Arc<Mutex<Option<Vec<f32>>>>,
+        sample_rate: u32,
+        /// Recording stops when this is dropped
+        _stream: Stream,
+        spectrum: Vec<f32>,
+    }
+
+    impl AudioInput {
+        /// Start recording from the input device whose name contains `device_name`,
+        /// or from the default one
+        pub fn open(device_name: Option<&str>) -> Result<Self, String> {
+            let host = cpal::default_host();
+            let device = match device_name {
+                Some(name) => {
+                    let name = name.to_lowercase();
+                    host.input_devices()
+                        .map_err(|err| err.to_string())?
+                        .find(|device| {
+                            device
+                                .name()
+                                .is_ok_and(|device_name| device_name.to_lowercase().contains(&name))
+                        })
+                }
+                None => host.default_input_device(),
+            }
+            .ok_or("No matching input device")?;
+            let supported_config = device
+                .default_input_config()
+                .map_err(|err| err.to_string())?;
+            log::info!(
+                "Recording audio from {:?}",
+                device.name().unwrap_or_default()
+            );
+
+            let samples = Arc::new(Mutex::new(VecDeque::from(vec![0.0; FFT_SIZE])));
+            let recorded = Arc::new(Mutex::new(None));
+            let config = supported_config.config();
+            let stream = match supported_config.sample_format() {
+                SampleFormat::F32 => build_stream::<f32>(&device, &config, &samples, &recorded),
+                SampleFormat::I16 => build_stream::<i16>(&device, &config, &samples, &recorded),
+                SampleFormat::U16 => build_stream::<u16>(&device, &config, &samples, &recorded),
+                SampleFormat::I32 => build_stream::<i32>(&device, &config, &samples, &recorded),
+                format => return Err(format!("Unsupported sample format {format}")),
+            }
+            .map_err(|err| err.to_string())?;
+            stream.play().map_err(|err| err.to_string())?;
+
+            Ok(Self {
+                samples,
+                recorded,
+                sample_rate: config.sample_rate.0,
+                _stream: stream,
+                spectrum: vec![0.0; AUDIO_TEXTURE_WIDTH as usize],
+            })
+        }
+
+        pub fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        /// Keep all samples from now on, for [`AudioInput::take_recorded`]
+        pub fn start_recording(&mut self) {
+            *self.recorded.lock().unwrap() = Some(Vec::new());
+        }
+
+        /// The mono samples recorded since the previous call
+        pub fn take_recorded(&mut self) -> Vec<f32> {
+            self.recorded
+                .lock()
+                .unwrap()
+                .as_mut()
+                .map(std::mem::take)
+                .unwrap_or_default()
+        }
+
+        /// Analyse the latest samples and upload them
+        pub fn update(&mut self, queue: &wgpu::Queue, texture: &AudioTexture) {
+            let samples: Vec<f32> = self.samples.lock().unwrap().iter().copied().collect();
+
+            // Hann window against leakage between bins
+            let mut real: Vec<f32> = samples
+                .iter()
+                .enumerate()
+                .map(|(i, sample)| {
+                    let window = 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos();
+                    sample * window
+                })
+                .collect();
+            let mut imaginary = vec![0.0; FFT_SIZE];
+            fft(&mut real, &mut imaginary);
+
+            for (bin, smoothed) in self.spectrum.iter_mut().enumerate() {
+                let magnitude = real[bin].hypot(imaginary[bin]) / FFT_SIZE as f32;
+                *smoothed = SMOOTHING * *smoothed + (1.0 - SMOOTHING) * magnitude;
+            }
+            let spectrum: Vec<f32> = self
+                .spectrum
+                .iter()
+                .map(|magnitude| {
+                    let decibels = 20.0 * magnitude.max(1e-10).log10();
+                    ((decibels - MIN_DECIBELS) / (MAX_DECIBELS - MIN_DECIBELS)).clamp(0.0, 1.0)
+                })
+                .collect();
+            let waveform: Vec<f32> = samples[FFT_SIZE - AUDIO_TEXTURE_WIDTH as usize..]
+                .iter()
+                .map(|sample| (0.5 + 0.5 * sample).clamp(0.0, 1.0))
+                .collect();
+
+            texture.write(queue, &spectrum, &waveform);
+        }
+    }
+
+    fn build_stream<T>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        samples: &Arc<Mutex<VecDeque<f32>>>,
+        recorded: &Arc<Mutex<Option<Vec<f32>>>>,
+    ) -> Result<Stream, cpal::BuildStreamError>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
+        let channels = config.channels as usize;
+        let samples = Arc::clone(samples);
+        let recorded = Arc::clone(recorded);
+        device.build_input_stream(
+            config,
+            move |data: &[T], _| {
+                let mut samples = samples.lock().unwrap();
+                let mut recorded = recorded.lock().unwrap();
+                for frame in data.chunks_exact(channels) {
+                    let mono = frame
+                        .iter()
+                        .map(|sample| sample.to_sample::<f32>())
+                        .sum::<f32>()
+                        / channels as f32;
+                    samples.pop_front();
+                    samples.push_back(mono);
+                    if let Some(recorded) = recorded.as_mut() {
+                        recorded.push(mono);
+                    }
+                }
+            },
+            |err| log::error!("Audio input failed: {err}"),
+            None,
+        )
+    }
+
+    /// In-place radix-2 Cooley-Tukey FFT, the length has to be a power of two
+    fn fft(real: &mut [f32], imaginary: &mut [f32]) {
+        let n = real.len();
+
+        // Bit reversal permutation
+        let mut j = 0;
+        for i in 1..n {
+            let mut bit = n >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j |= bit;
+            if i < j {
+                real.swap(i, j);
+                imaginary.swap(i, j);
+            }
+        }
+
+        let mut length = 2;
+        while length <= n {
+            let angle = -2.0 * PI / length as f32;
+            for start in (0..n).step_by(length) {
+                for k in 0..length / 2 {
+                    let (sin, cos) = (angle * k as f32).sin_cos();
+                    let (a, b) = (start + k, start + k + length / 2);
+                    let twiddled_real = real[b] * cos - imaginary[b] * sin;
+                    let twiddled_imaginary = real[b] * sin + imaginary[b] * cos;
+                    real[b] = real[a] - twiddled_real;
+                    imaginary[b] = imaginary[a] - twiddled_imaginary;
+                    real[a] += twiddled_real;
+                    imaginary[a] += twiddled_imaginary;
+                }
+            }
+            length *= 2;
+        }
+    }
+}
@@ -0,0 +1,492 @@
+use std::{collections::BTreeMap, path::Path, sync::Arc, time::Instant};
+
+use wgpu::*;
+
+use crate::{
+    adapter::AdapterSelection,
+    cli::{Options, Preset},
+    compute::{ComputeState, OUTPUT_FORMAT, OutputStorage},
+    frame_hash,
+    globals::{self, GlobalsState},
+    gpu::DrawMode,
+    library, pipeline_cache,
+    preprocessor::ShaderSource,
+    reaction_diffusion::{ReactionDiffusionParams, STATE_FORMATS},
+    scene::SceneConfig,
+    shaders::Shaders,
+};
+
+/// Workgroup sizes worth trying, sizes beyond the limits of the device are skipped
+const CANDIDATES: &[[u32; 2]] = &[
+    [4, 4],
+    [8, 4],
+    [8, 8],
+    [16, 8],
+    [8, 16],
+    [16, 16],
+    [32, 8],
+    [8, 32],
+    [32, 32],
+    [64, 4],
+];
+
+/// Dispatches before measuring, to let clocks ramp up and caches warm
+const WARMUP_DISPATCHES: u32 = 10;
+const TIMED_DISPATCHES: u32 = 50;
+
+/// Time the drawing shader compiled with each candidate workgroup size
+/// and report the fastest one for this GPU. Returns false if nothing could be measured
+pub fn run(options: &Options) -> bool {
+    let Some(bench) = pollster::block_on(Bench::new(options)) else {
+        eprintln!("No adapter with compute shader support found, see --list-adapters");
+        return false;
+    };
+
+    bench.print_adapter();
+    println!(
+        "{}x{} pixels, timed with {}",
+        bench.width,
+        bench.height,
+        bench.timer()
+    );
+
+    let draw_src = match bench.config(options, [8, 8]).draw_source() {
+        Ok(draw_src) => draw_src,
+        Err(err) => {
+            eprintln!("Failed to read shader: {err}");
+            return false;
+        }
+    };
+
+    let limits = bench.device.limits();
+    let mut fastest: Option<([u32; 2], f64)> = None;
+    for &workgroup_size in CANDIDATES {
+        let [x, y] = workgroup_size;
+        let label = format!("{x}x{y}");
+        if x > limits.max_compute_workgroup_size_x
+            || y > limits.max_compute_workgroup_size_y
+            || x * y > limits.max_compute_invocations_per_workgroup
+        {
+            println!("{label:>7}  exceeds device limits");
+            continue;
+        }
+
+        let config = bench.config(options, workgroup_size);
+        let storage_buffers = match config.create_storage_buffers(&bench.device) {
+            Ok(storage_buffers) => storage_buffers,
+            Err(err) => {
+                eprintln!("Failed to read storage buffer: {err}");
+                return false;
+            }
+        };
+
+        bench.device.push_error_scope(ErrorFilter::Validation);
+        let globals = GlobalsState::new(&bench.device, &bench.queue, config.width, config.height);
+        let measured = bench
+            .compute_state(&config, &globals, &draw_src, &storage_buffers)
+            .map(|mut compute_state| {
+                let milliseconds = bench.measure(&compute_state, &globals);
+                // What tile classification saves, compared to drawing every tile
+                let every_tile = compute_state.classifies_tiles().then(|| {
+                    compute_state.set_tile_classification(false);
+                    bench.measure(&compute_state, &globals)
+                });
+                (milliseconds, every_tile)
+            });
+        let (milliseconds, every_tile) =
+            match (measured, pollster::block_on(bench.device.pop_error_scope())) {
+                (Err(err), _) => {
+                    eprintln!("{label:>7}  failed: {err}");
+                    continue;
+                }
+                (_, Some(error)) => {
+                    eprintln!("{label:>7}  failed: {error}");
+                    continue;
+                }
+                (Ok(measured), None) => measured,
+            };
+
+        match every_tile {
+            Some(every_tile) => println!(
+                "{label:>7}  {milliseconds:.3} ms, {every_tile:.3} ms drawing every tile ({:.2}x)",
+                every_tile / milliseconds
+            ),
+            None => println!("{label:>7}  {milliseconds:.3} ms"),
+        }
+        if fastest.is_none_or(|(_, best)| milliseconds < best) {
+            fastest = Some((workgroup_size, milliseconds));
+        }
+    }
+
+    match fastest {
+        Some(([x, y], _)) => {
+            println!("Fastest: {x}x{y}, use it with --workgroup-size {x}x{y}");
+            true
+        }
+        None => false,
+    }
+}
+
+/// Resolutions `bench-compare` runs the shaders at
+const TIERS: &[(&str, u32, u32)] = &[
+    ("360p", 640, 360),
+    ("720p", 1280, 720),
+    ("1080p", 1920, 1080),
+];
+
+/// Time the drawing passes of two shaders at every resolution tier and compare their output,
+/// for checking that an optimization is faster and still draws the same image.
+/// Returns false if a shader failed or the images differ
+pub fn compare(options: &Options, old: &Path, new: &Path) -> bool {
+    let Some(bench) = pollster::block_on(Bench::new(options)) else {
+        eprintln!("No adapter with compute shader support found, see --list-adapters");
+        return false;
+    };
+
+    bench.print_adapter();
+    println!("Drawing pass timed with {}", bench.timer());
+    println!("old: {}", old.display());
+    println!("new: {}", new.display());
+    println!(
+        "{:>6}  {:>10}  {:>10}  {:>8}  output",
+        "tier", "old", "new", "delta"
+    );
+
+    let workgroup_size = options
+        .workgroup_size
+        .unwrap_or(options.profile.workgroup_size());
+    let format = bench
+        .config(options, workgroup_size)
+        .output_storage
+        .format();
+    let mut same = true;
+    for &(tier, width, height) in TIERS {
+        let [old_run, new_run] = [old, new].map(|shader| {
+            let config = SceneConfig {
+                shader_path: Some(library::resolve_shader(shader)),
+                width,
+                height,
+                ..bench.config(options, workgroup_size)
+            };
+            bench.run_shader(&config)
+        });
+
+        let ((old_milliseconds, old_image), (new_milliseconds, new_image)) =
+            match (old_run, new_run) {
+                (Ok(old_run), Ok(new_run)) => (old_run, new_run),
+                (Err(err), _) | (_, Err(err)) => {
+                    eprintln!("{tier:>6}  failed: {err}");
+                    same = false;
+                    continue;
+                }
+            };
+
+        let delta = (new_milliseconds / old_milliseconds - 1.0) * 100.0;
+        let output = match ImageDifference::new(format, &old_image, &new_image) {
+            None => "identical".to_owned(),
+            Some(difference) => {
+                same = false;
+                format!(
+                    "DIFFERS in {:.2}% of pixels, by up to {:.4}",
+                    difference.pixels as f64 * 100.0 / (width * height) as f64,
+                    difference.max
+                )
+            }
+        };
+        println!(
+            "{tier:>6}  {old_milliseconds:>7.3} ms  {new_milliseconds:>7.3} ms  {delta:>+7.1}%  {output}"
+        );
+    }
+
+    same
+}
+
+/// How far the outputs of two shaders are apart
+struct ImageDifference {
+    /// Number of pixels with any channel differing
+    pixels: usize,
+    /// Largest difference of a single channel
+    max: f32,
+}
+
+impl ImageDifference {
+    /// Compares images in `format`, one of the [`OUTPUT_FORMATS`](crate::compute::OUTPUT_FORMATS),
+    /// `None` if they are identical
+    fn new(format: TextureFormat, old: &[u8], new: &[u8]) -> Option<Self> {
+        let pixel_size = format
+            .block_copy_size(None)
+            .expect("output formats have a fixed texel size") as usize;
+
+        let mut difference = Self {
+            pixels: 0,
+            max: 0.0,
+        };
+        for (old, new) in old
+            .chunks_exact(pixel_size)
+            .zip(new.chunks_exact(pixel_size))
+        {
+            if old == new {
+                continue;
+            }
+            difference.pixels += 1;
+            for (old, new) in channels(format, old).into_iter().zip(channels(format, new)) {
+                difference.max = difference.max.max((old - new).abs());
+            }
+        }
+        (difference.pixels > 0).then_some(difference)
+    }
+}
+
+/// The channels of a `pixel` in `format`, one of the [`OUTPUT_FORMATS`](crate::compute::OUTPUT_FORMATS)
+fn channels(format: TextureFormat, pixel: &[u8]) -> [f32; 4] {
+    std::array::from_fn(|channel| {
+        if format == OUTPUT_FORMAT {
+            let half = u16::from_le_bytes([pixel[2 * channel], pixel[2 * channel + 1]]);
+            frame_hash::f16_to_f32(half)
+        } else {
+            f32::from(pixel[channel]) / 255.0
+        }
+    })
+}
+
+struct Bench {
+    adapter: Adapter,
+    device: Device,
+    queue: Queue,
+    /// `None` when the device can't write timestamps, then the CPU clock is used instead
+    timestamps: Option<QuerySet>,
+    /// Shared by the pipelines of every workgroup size and shader, saved when dropped
+    pipeline_cache: Option<Arc<PipelineCache>>,
+    width: u32,
+    height: u32,
+}
+
+impl Drop for Bench {
+    fn drop(&mut self) {
+        if let Some(cache) = &self.pipeline_cache {
+            pipeline_cache::save(cache, &self.adapter);
+        }
+    }
+}
+
+impl Bench {
+    async fn new(options: &Options) -> Option<Self> {
+        let adapter_selection = AdapterSelection::new(options);
+        let instance = adapter_selection.create_instance();
+        let adapter = adapter_selection.select(&instance, None).await?;
+
+        let compute_supported = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::COMPUTE_SHADERS);
+        if !compute_supported {
+            return None;
+        }
+
+        // Everything the adapter offers, so large workgroups can be tried
+        let mut required_features =
+            adapter.features() & Features::TIMESTAMP_QUERY | pipeline_cache::features(&adapter);
+        if globals::push_constants_available(&adapter) {
+            required_features |= Features::PUSH_CONSTANTS;
+        }
+        let (device, queue) = adapter
+            .request_device(
+                &DeviceDescriptor {
+                    required_features,
+                    required_limits: adapter.limits(),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .ok()?;
+
+        let timestamps = required_features
+            .contains(Features::TIMESTAMP_QUERY)
+            .then(|| {
+                device.create_query_set(&QuerySetDescriptor {
+                    label: Some("Benchmark Timestamps"),
+                    ty: QueryType::Timestamp,
+                    count: 2 * TIMED_DISPATCHES,
+                })
+            });
+
+        let (width, height) = options.resolution();
+        let pipeline_cache = pipeline_cache::load(&device, &adapter);
+        Some(Self {
+            adapter,
+            device,
+            queue,
+            timestamps,
+            pipeline_cache,
+            width,
+            height,
+        })
+    }
+
+    fn config(&self, options: &Options, workgroup_size: [u32; 2]) -> SceneConfig {
+        SceneConfig {
+            preset: Preset::Drawing,
+            accumulate: false,
+            shader_path: options.shader.as_deref().map(library::resolve_shader),
+            initial_params: None,
+            example: options.example,
+            storage_buffers: options.storage_buffers.clone(),
+            scratch_size: options.scratch_size(),
+            history: options.history,
+            volume_size: options.volume_size,
+            volume_view: options.volume_view,
+            cubemap_size: options.cubemap_size,
+            reaction_diffusion_params: ReactionDiffusionParams::new(options.feed, options.kill),
+            state_format: STATE_FORMATS[0],
+            output_storage: ComputeState::output_storage(&self.adapter)
+                .unwrap_or(OutputStorage::Float),
+            pipeline_cache: self.pipeline_cache.clone(),
+            steps_per_frame: options.steps_per_frame,
+            undo_history: options.undo_history,
+            undo_memory: 0,
+            workgroup_size,
+            width: self.width,
+            height: self.height,
+            presented_size: [self.width, self.height],
+            supersample: 1,
+            scales: BTreeMap::new(),
+            wall: None,
+            msaa_samples: 1,
+            mipmaps: false,
+            bloom: None,
+            convolution: None,
+            post_effects: None,
+            scaling: options.scaling,
+            filter: options.filter,
+            entry_point: options.entry_point.clone(),
+            quads: Vec::new(),
+            sandboxed: false,
+        }
+    }
+
+    fn print_adapter(&self) {
+        let info = self.adapter.get_info();
+        println!("{} ({:?}, {})", info.name, info.backend, info.driver);
+    }
+
+    fn timer(&self) -> &'static str {
+        match self.timestamps {
+            Some(_) => "timestamp queries",
+            None => "the CPU clock, timestamp queries are unsupported",
+        }
+    }
+
+    /// Time the drawing pass of `config` and read back the image it draws
+    fn run_shader(&self, config: &SceneConfig) -> Result<(f64, Vec<u8>), String> {
+        let draw_src = config
+            .draw_source()
+            .map_err(|err| format!("Failed to read shader: {err}"))?;
+        let storage_buffers = config
+            .create_storage_buffers(&self.device)
+            .map_err(|err| format!("Failed to read storage buffer: {err}"))?;
+
+        self.device.push_error_scope(ErrorFilter::Validation);
+        let globals = GlobalsState::new(&self.device, &self.queue, config.width, config.height);
+        let run = self
+            .compute_state(config, &globals, &draw_src, &storage_buffers)
+            .map(|compute_state| {
+                let milliseconds = self.measure(&compute_state, &globals);
+                let image = frame_hash::read_texture(
+                    &self.device,
+                    &self.queue,
+                    compute_state
+                        .output_texture()
+                        .expect("benchmarks draw into textures"),
+                );
+                (milliseconds, image)
+            });
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(error) => Err(error.to_string()),
+            None => run,
+        }
+    }
+
+    /// The drawing pass of `config`
+    fn compute_state(
+        &self,
+        config: &SceneConfig,
+        globals: &GlobalsState,
+        draw_src: &ShaderSource,
+        storage_buffers: &[Buffer],
+    ) -> Result<ComputeState, String> {
+        let shaders = Shaders::new(&self.device, DrawMode::Compute, draw_src, config)?;
+        let mut builder =
+            ComputeState::builder(&self.device, &shaders, globals, config.width, config.height);
+        for buffer in storage_buffers {
+            builder = builder.storage_buffer(buffer, true);
+        }
+        if let Some(entry_point) = &config.entry_point {
+            builder = builder.entry_point(entry_point);
+        }
+        Ok(builder.build())
+    }
+
+    /// Average duration of one dispatch of the drawing shader in milliseconds
+    fn measure(&self, compute_state: &ComputeState, globals: &GlobalsState) -> f64 {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        for _ in 0..WARMUP_DISPATCHES {
+            compute_state.dispatch(&mut encoder, globals);
+        }
+        self.queue.submit(Some(encoder.finish()));
+        self.device.poll(Maintain::Wait);
+
+        let start = Instant::now();
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        for i in 0..TIMED_DISPATCHES {
+            let timestamp_writes =
+                self.timestamps
+                    .as_ref()
+                    .map(|query_set| ComputePassTimestampWrites {
+                        query_set,
+                        beginning_of_pass_write_index: Some(2 * i),
+                        end_of_pass_write_index: Some(2 * i + 1),
+                    });
+            compute_state.dispatch_timed(&mut encoder, globals, timestamp_writes);
+        }
+
+        let Some(query_set) = &self.timestamps else {
+            self.queue.submit(Some(encoder.finish()));
+            self.device.poll(Maintain::Wait);
+            return start.elapsed().as_secs_f64() * 1000.0 / TIMED_DISPATCHES as f64;
+        };
+
+        let size = (2 * TIMED_DISPATCHES as usize * std::mem::size_of::<u64>()) as BufferAddress;
+        let resolve_buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Benchmark Resolve Buffer"),
+            size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Benchmark Readback Buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.resolve_query_set(query_set, 0..2 * TIMED_DISPATCHES, &resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&resolve_buffer, 0, &readback_buffer, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        readback_buffer.slice(..).map_async(MapMode::Read, |_| {});
+        self.device.poll(Maintain::Wait);
+        let timestamps: Vec<u64> =
+            bytemuck::cast_slice(&readback_buffer.slice(..).get_mapped_range()).to_vec();
+
+        let ticks: u64 = timestamps
+            .chunks_exact(2)
+            .map(|pass| pass[1].saturating_sub(pass[0]))
+            .sum();
+        let nanoseconds = ticks as f64 * self.queue.get_timestamp_period() as f64;
+        nanoseconds / 1_000_000.0 / TIMED_DISPATCHES as f64
+    }
+}
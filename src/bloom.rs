@@ -0,0 +1,289 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{compute::OUTPUT_FORMAT, shaders::Shaders};
+
+/// Most levels the bright parts are blurred into, each half the size of the previous one
+const MAX_LEVELS: u32 = 6;
+
+/// Uniforms of `bloom.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BloomParams {
+    /// Brightness above which colors glow
+    pub threshold: f32,
+    /// The blurred excess gets multiplied by this before it is added
+    pub intensity: f32,
+    /// Set by [`BloomState`]
+    levels: u32,
+    _padding: u32,
+}
+
+impl BloomParams {
+    pub fn new(threshold: f32, intensity: f32) -> Self {
+        Self {
+            threshold,
+            intensity,
+            levels: 0,
+            _padding: 0,
+        }
+    }
+}
+
+/// One dispatch of the bloom chain
+struct Pass {
+    /// Index into [`BloomState::pipelines`]
+    pipeline: usize,
+    bind_group: BindGroup,
+    size: Extent3d,
+}
+
+const PREFILTER: usize = 0;
+const DOWNSAMPLE: usize = 1;
+const UPSAMPLE: usize = 2;
+const COMPOSITE: usize = 3;
+
+/// Glow around bright colors, added by compute passes between the drawing and the render pass:
+/// the excess over the threshold is blurred into a chain of ever smaller levels,
+/// which are added back up and onto the image
+pub struct BloomState {
+    pipelines: [ComputePipeline; 4],
+    passes: Vec<Pass>,
+    params_buffer: Buffer,
+    levels: u32,
+    pub output_texture: Texture,
+    pub output_view: TextureView,
+    workgroup_size: [u32; 2],
+}
+
+impl BloomState {
+    /// `width` × `height` is the size of the image behind `source_view`
+    pub fn new(
+        device: &Device,
+        shaders: &Shaders,
+        source_view: &TextureView,
+        width: u32,
+        height: u32,
+        params: BloomParams,
+    ) -> Self {
+        let module = shaders
+            .bloom
+            .as_ref()
+            .expect("Bloom shader was not created");
+
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let chain_size = Extent3d {
+            width: width.div_ceil(2),
+            height: height.div_ceil(2),
+            depth_or_array_layers: 1,
+        };
+        let levels = chain_size.max_mips(TextureDimension::D2).min(MAX_LEVELS);
+        let create_texture = |label, size| {
+            device.create_texture(&TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: OUTPUT_FORMAT,
+                usage: TextureUsages::STORAGE_BINDING
+                    | TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        };
+        let level_size = |level| chain_size.mip_level_size(level, TextureDimension::D2);
+        // A texture per level rather than mip levels of one, which GL can't bind to several bindings at once
+        let create_view = |label, size| {
+            create_texture(label, size).create_view(&TextureViewDescriptor::default())
+        };
+        // The bright parts blurred into ever smaller levels
+        let down: Vec<_> = (0..levels)
+            .map(|level| create_view("Bloom Downsample Texture", level_size(level)))
+            .collect();
+        // Those levels added back up, the smallest has nothing below it to add
+        let up: Vec<_> = (0..levels - 1)
+            .map(|level| create_view("Bloom Upsample Texture", level_size(level)))
+            .collect();
+        let output_texture = create_texture("Bloom Output Texture", size);
+        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Bloom Params Buffer"),
+            contents: bytemuck::bytes_of(&BloomParams { levels, ..params }),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Bloom Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let texture_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Bloom Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: OUTPUT_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        // `base` is only read by some passes, the others bind `input` twice
+        let pass =
+            |pipeline, input: &TextureView, base: &TextureView, output: &TextureView, size| {
+                let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("Bloom Bind Group"),
+                    layout: &bind_group_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(input),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(base),
+                        },
+                        BindGroupEntry {
+                            binding: 2,
+                            resource: BindingResource::TextureView(output),
+                        },
+                        BindGroupEntry {
+                            binding: 3,
+                            resource: BindingResource::Sampler(&sampler),
+                        },
+                        BindGroupEntry {
+                            binding: 4,
+                            resource: params_buffer.as_entire_binding(),
+                        },
+                    ],
+                });
+                Pass {
+                    pipeline,
+                    bind_group,
+                    size,
+                }
+            };
+        let mut passes = vec![pass(
+            PREFILTER,
+            source_view,
+            source_view,
+            &down[0],
+            level_size(0),
+        )];
+        for level in 1..levels {
+            let input = &down[level as usize - 1];
+            passes.push(pass(
+                DOWNSAMPLE,
+                input,
+                input,
+                &down[level as usize],
+                level_size(level),
+            ));
+        }
+        let mut blurred = &down[levels as usize - 1];
+        for level in (0..levels - 1).rev() {
+            let output = &up[level as usize];
+            passes.push(pass(
+                UPSAMPLE,
+                blurred,
+                &down[level as usize],
+                output,
+                level_size(level),
+            ));
+            blurred = output;
+        }
+        passes.push(pass(COMPOSITE, blurred, source_view, &output_view, size));
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipelines = ["prefilter", "downsample", "upsample", "composite"].map(|entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                compilation_options: Default::default(),
+                label: Some("Bloom Pipeline"),
+                layout: Some(&pipeline_layout),
+                module,
+                entry_point,
+                cache: shaders.pipeline_cache.as_deref(),
+            })
+        });
+
+        Self {
+            pipelines,
+            passes,
+            params_buffer,
+            levels,
+            output_texture,
+            output_view,
+            workgroup_size: shaders.workgroup_size,
+        }
+    }
+
+    pub fn set_params(&self, queue: &Queue, params: BloomParams) {
+        let params = BloomParams {
+            levels: self.levels,
+            ..params
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Bloom Pass"),
+        });
+
+        let [workgroup_width, workgroup_height] = self.workgroup_size;
+        for pass in &self.passes {
+            compute_pass.set_pipeline(&self.pipelines[pass.pipeline]);
+            compute_pass.set_bind_group(0, &pass.bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                pass.size.width.div_ceil(workgroup_width),
+                pass.size.height.div_ceil(workgroup_height),
+                1,
+            );
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::OnceLock;
+
+use wgpu::util::{DeviceExt, TextureDataOrder};
+use wgpu::*;
+
+use crate::globals::frame_random;
+
+/// Edge length of the [`BlueNoiseTexture`], which tiles the image
+pub const BLUE_NOISE_SIZE: u32 = 64;
+
+/// Standard deviation in texels of the Gaussian the void-and-cluster method measures clustering with
+const SIGMA: f32 = 1.5;
+
+/// Tileable blue noise, available to drawing shaders through `noise_blue` of `src/shaders/noise.wgsl`.
+///
+/// Every texel holds its rank in `0..1`, thresholding at any level leaves evenly spread points
+/// without the clumps of white noise, which makes dithering and sampling patterns look smooth
+pub struct BlueNoiseTexture {
+    pub view: TextureView,
+}
+
+impl BlueNoiseTexture {
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let texture = device.create_texture_with_data(
+            queue,
+            &TextureDescriptor {
+                label: Some("Blue Noise Texture"),
+                size: Extent3d {
+                    width: BLUE_NOISE_SIZE,
+                    height: BLUE_NOISE_SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::R32Float,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(ranks()),
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Self { view }
+    }
+}
+
+/// Rank of every texel in `0..1`, row by row, generated once per process
+fn ranks() -> &'static [f32] {
+    static RANKS: OnceLock<Vec<f32>> = OnceLock::new();
+    RANKS.get_or_init(void_and_cluster)
+}
+
+/// Robert Ulichney's void-and-cluster method: points are added where they are farthest from the others
+/// and ranked in the order they were added, starting from an evenly spread set of a tenth of them
+fn void_and_cluster() -> Vec<f32> {
+    let size = BLUE_NOISE_SIZE as usize;
+    let mut pattern = Pattern::new(size);
+    for index in 0..size * size {
+        if frame_random(index as u32, 0).is_multiple_of(10) {
+            pattern.toggle(index);
+        }
+    }
+    // Move the point in the tightest cluster to the largest void until that is where it came from
+    for _ in 0..size * size {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; size * size];
+    let initial = pattern.len;
+    let mut thinned = pattern.clone();
+    for rank in (0..initial).rev() {
+        let cluster = thinned.tightest_cluster();
+        thinned.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+    // Once more than half are set, the largest void of the points is the tightest cluster of the gaps
+    for rank in initial..size * size {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank;
+    }
+    let count = (size * size) as f32;
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / count)
+        .collect()
+}
+
+/// Points set on a torus, with how crowded every texel is by them
+#[derive(Clone)]
+struct Pattern {
+    size: usize,
+    set: Vec<bool>,
+    /// Sum of the Gaussian of the distance to every point
+    energy: Vec<f32>,
+    /// Gaussian by offset, wrapping around
+    kernel: Vec<f32>,
+    len: usize,
+}
+
+impl Pattern {
+    fn new(size: usize) -> Self {
+        let kernel = (0..size * size)
+            .map(|index| {
+                let (x, y) = (index % size, index / size);
+                let dx = x.min(size - x) as f32;
+                let dy = y.min(size - y) as f32;
+                (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+            })
+            .collect();
+        Self {
+            size,
+            set: vec![false; size * size],
+            energy: vec![0.0; size * size],
+            kernel,
+            len: 0,
+        }
+    }
+
+    fn toggle(&mut self, index: usize) {
+        let size = self.size;
+        let sign = if self.set[index] { -1.0 } else { 1.0 };
+        self.set[index] = !self.set[index];
+        self.len = if self.set[index] {
+            self.len + 1
+        } else {
+            self.len - 1
+        };
+        let (x, y) = (index % size, index / size);
+        for (other, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (other % size + size - x) % size;
+            let dy = (other / size + size - y) % size;
+            *energy += sign * self.kernel[dy * size + dx];
+        }
+    }
+
+    /// The most crowded point
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// The least crowded gap
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, set: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best: Option<usize> = None;
+        for index in (0..self.set.len()).filter(|&index| self.set[index] == set) {
+            if best.is_none_or(|best| better(self.energy[index], self.energy[best])) {
+                best = Some(index);
+            }
+        }
+        best.expect("patterns are neither empty nor full while ranking")
+    }
+}
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, f32::consts::FRAC_PI_2};
+use web_time::Instant;
+use winit::{dpi::PhysicalPosition, event::MouseScrollDelta, keyboard::KeyCode};
+
+use crate::globals::Camera;
+
+/// Radians turned per pixel dragged
+const ROTATE_SPEED: f32 = 0.005;
+/// Keeps the camera from flipping over the poles
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+/// Factor the orbit distance or fly speed changes by per line scrolled
+const WHEEL_FACTOR: f32 = 1.1;
+/// Pixels scrolled by touchpads that count as one line
+pub(crate) const PIXELS_PER_LINE: f32 = 40.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CameraMode {
+    /// Circle around `target`, the wheel zooms
+    Orbit,
+    /// Move freely, the wheel changes the speed
+    Fly,
+}
+
+/// Where the [`CameraControls`] are, saved with the [`ShaderSettings`](crate::sidecar::ShaderSettings)
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct CameraView {
+    pub mode: CameraMode,
+    /// Point orbited around
+    pub target: [f32; 3],
+    pub distance: f32,
+    /// Position while flying
+    pub position: [f32; 3],
+    /// Units per second moved with W, A, S and D
+    pub speed: f32,
+    /// Radians turned right from looking along -z
+    pub yaw: f32,
+    /// Radians looked up
+    pub pitch: f32,
+}
+
+/// Mouse and keyboard controls of the [`Camera`] handed to drawing shaders.
+///
+/// Dragging with the left mouse button looks around, W, A, S and D move,
+/// V switches between orbiting and flying and Home goes back to the start.
+pub struct CameraControls {
+    view: CameraView,
+    fov: f32,
+    held: HashSet<KeyCode>,
+    dragging: bool,
+    cursor: Option<PhysicalPosition<f64>>,
+    last_update: Instant,
+    changed: bool,
+}
+
+impl CameraControls {
+    pub fn new() -> Self {
+        let mut controls = Self {
+            view: CameraView {
+                mode: CameraMode::Orbit,
+                target: [0.0; 3],
+                distance: 0.0,
+                position: [0.0; 3],
+                speed: 0.0,
+                yaw: 0.0,
+                pitch: 0.0,
+            },
+            fov: 0.0,
+            held: HashSet::new(),
+            dragging: false,
+            cursor: None,
+            last_update: Instant::now(),
+            changed: true,
+        };
+        controls.reset();
+        controls
+    }
+
+    /// Back to the view of [`Camera::default`]
+    pub fn reset(&mut self) {
+        let camera = Camera::default();
+        let [x, y, z] = camera.forward;
+        self.view.mode = CameraMode::Orbit;
+        self.view.yaw = x.atan2(-z);
+        self.view.pitch = y.asin();
+        self.view.distance = 4.0;
+        self.view.target = add(camera.position, scale(camera.forward, self.view.distance));
+        self.view.position = camera.position;
+        self.view.speed = 2.0;
+        self.fov = camera.fov;
+        self.changed = true;
+    }
+
+    pub fn view(&self) -> CameraView {
+        self.view
+    }
+
+    /// Continue from a saved view
+    pub fn set_view(&mut self, view: CameraView) {
+        self.view = view;
+        self.changed = true;
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.view.mode = match self.view.mode {
+            CameraMode::Orbit => {
+                self.view.position = self.eye();
+                CameraMode::Fly
+            }
+            CameraMode::Fly => {
+                self.view.target = add(
+                    self.view.position,
+                    scale(self.forward(), self.view.distance),
+                );
+                CameraMode::Orbit
+            }
+        };
+        log::info!("Camera: {:?}", self.view.mode);
+    }
+
+    /// Returns whether the key moves the camera, so it isn't handled as shortcut as well
+    pub fn handle_key(&mut self, key: KeyCode, pressed: bool) -> bool {
+        if !matches!(
+            key,
+            KeyCode::KeyW | KeyCode::KeyA | KeyCode::KeyS | KeyCode::KeyD
+        ) {
+            return false;
+        }
+        if pressed {
+            self.held.insert(key);
+        } else {
+            self.held.remove(&key);
+        }
+        true
+    }
+
+    pub fn handle_button(&mut self, pressed: bool) {
+        self.dragging = pressed;
+    }
+
+    pub fn handle_cursor(&mut self, position: PhysicalPosition<f64>) {
+        if let Some(last) = self.cursor.replace(position)
+            && self.dragging
+        {
+            self.view.yaw += (position.x - last.x) as f32 * ROTATE_SPEED;
+            self.view.pitch = (self.view.pitch - (position.y - last.y) as f32 * ROTATE_SPEED)
+                .clamp(-MAX_PITCH, MAX_PITCH);
+            self.changed = true;
+        }
+    }
+
+    pub fn handle_wheel(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+        };
+        let factor = WHEEL_FACTOR.powf(lines);
+        match self.view.mode {
+            CameraMode::Orbit => self.view.distance = (self.view.distance / factor).max(0.01),
+            CameraMode::Fly => self.view.speed *= factor,
+        }
+        self.changed = true;
+    }
+
+    /// Move with the held keys, returns whether the camera changed since the last update
+    pub fn update(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f32();
+        self.last_update = now;
+
+        let forward = self.forward();
+        let right = Camera::new([0.0; 3], forward, self.fov).right;
+        let mut direction = [0.0; 3];
+        for key in &self.held {
+            let (axis, sign) = match key {
+                KeyCode::KeyW => (forward, 1.0),
+                KeyCode::KeyS => (forward, -1.0),
+                KeyCode::KeyD => (right, 1.0),
+                KeyCode::KeyA => (right, -1.0),
+                _ => continue,
+            };
+            direction = add(direction, scale(axis, sign));
+        }
+        if direction != [0.0; 3] {
+            let step = scale(direction, self.view.speed * elapsed);
+            match self.view.mode {
+                CameraMode::Orbit => self.view.target = add(self.view.target, step),
+                CameraMode::Fly => self.view.position = add(self.view.position, step),
+            }
+            self.changed = true;
+        }
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn camera(&self) -> Camera {
+        Camera::new(self.eye(), self.forward(), self.fov)
+    }
+
+    fn eye(&self) -> [f32; 3] {
+        match self.view.mode {
+            CameraMode::Orbit => add(self.view.target, scale(self.forward(), -self.view.distance)),
+            CameraMode::Fly => self.view.position,
+        }
+    }
+
+    fn forward(&self) -> [f32; 3] {
+        let (sin_yaw, cos_yaw) = self.view.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.view.pitch.sin_cos();
+        [sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch]
+    }
+}
+
+impl Default for CameraControls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn scale(v: [f32; 3], factor: f32) -> [f32; 3] {
+    v.map(|c| c * factor)
+}
@@ -0,0 +1,777 @@
+use std::{collections::BTreeSet, net::SocketAddr, path::PathBuf, time::Duration};
+
+use clap::{ArgMatches, Args, FromArgMatches, Parser, Subcommand, ValueEnum, parser::ValueSource};
+
+use crate::{
+    adapter::{Backend, PowerPreference},
+    compute::{MAX_HISTORY, MAX_VOLUME_SIZE, VolumeView},
+    convolution::Kernel,
+    examples::Example,
+    fullscreen::FullscreenMode,
+    graph::MAX_SCALE,
+    paint::UndoHistory,
+    playlist::Transition,
+    profile::Profile,
+    recording::{ImageFormat, RecordFormat},
+    render::{Filter, Scaling},
+    scene::SCALED_NODES,
+    tonemap::{ColorSpace, Tonemapper},
+    wall::WallTile,
+};
+
+/// Ids of the arguments given on the command line rather than left at their defaults,
+/// filled by clap along with the other options however they are parsed
+#[derive(Clone, Default, Debug)]
+pub(crate) struct GivenArgs(BTreeSet<String>);
+
+impl FromArgMatches for GivenArgs {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self(
+            matches
+                .ids()
+                .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+                .map(|id| id.to_string())
+                .collect(),
+        ))
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        self.0.extend(Self::from_arg_matches(matches)?.0);
+        Ok(())
+    }
+}
+
+impl Args for GivenArgs {
+    fn augment_args(command: clap::Command) -> clap::Command {
+        command
+    }
+
+    fn augment_args_for_update(command: clap::Command) -> clap::Command {
+        command
+    }
+}
+
+/// Command line options
+#[derive(Parser, Clone, Debug)]
+#[command(version, about = "Draw an image with a wgpu compute shader")]
+pub struct Options {
+    /// Ids of the arguments given on the command line. First, so it is read before clap takes the values out
+    #[command(flatten)]
+    pub(crate) given: GivenArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Stay within WebGL2/GLES downlevel limits.
+    /// The drawing shader is then evaluated by a fragment pass into an offscreen target
+    #[arg(long)]
+    pub downlevel: bool,
+
+    /// Defaults tuned for a class of hardware, e.g. `pi` for Raspberry Pi
+    #[arg(long, value_enum, default_value_t = Profile::Default)]
+    pub profile: Profile,
+
+    /// Resolution of the drawn image, instead of the one of the profile.
+    /// It stays the same when the window is resized
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    pub resolution: Option<[u32; 2]>,
+
+    /// Draw at this many times the resolution in each direction and average it down,
+    /// to antialias hard edges. Press S to cycle through 1, 2 and 4
+    #[arg(long, value_name = "FACTOR", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=4))]
+    pub supersample: u32,
+
+    /// Run a node of the render graph at FACTOR times its usual resolution, e.g. `drawing=0.5`
+    /// for a cheaper simulation under full-resolution post effects. Nodes are `drawing`, `accumulation`,
+    /// `downsampling`, `bloom` and `convolution`, their inputs and the presented image are resampled to fit.
+    /// Can be given once per node
+    #[arg(long = "scale", value_name = "NODE=FACTOR", value_parser = parse_scale)]
+    pub scales: Vec<(String, f32)>,
+
+    /// Multisample the render pass with this many samples per pixel, antialiasing the edges of the image,
+    /// lowered to what the adapter supports
+    #[arg(long, value_name = "SAMPLES", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=16))]
+    pub msaa: u32,
+
+    /// Generate a mip chain of the presented image every frame, so it doesn't shimmer when shown smaller
+    #[arg(long)]
+    pub mipmaps: bool,
+
+    /// Add a glow around colors brighter than `--bloom-threshold`, this many times their blurred excess.
+    /// Press B to toggle it and \[ or \] to adjust it
+    #[arg(long, value_name = "STRENGTH", default_value_t = 0.0)]
+    pub bloom: f32,
+
+    /// Brightness above which colors glow with `--bloom`, adjust with { and }
+    #[arg(long, value_name = "BRIGHTNESS", default_value_t = 1.0)]
+    pub bloom_threshold: f32,
+
+    /// Convolve the image after bloom with a kernel: `blur`, `sharpen`, `edges`, `emboss`
+    /// or an odd square number of comma-separated weights, row by row, e.g. 0,-1,0,-1,5,-1,0,-1,0.
+    /// Weights starting with a minus sign need `--convolve=-1,...`.
+    /// Overrides the `[convolution]` of the config file
+    #[arg(long, value_name = "KERNEL")]
+    pub convolve: Option<Kernel>,
+
+    /// Convolve this PNG instead of the drawing, stretched to the size of the image
+    #[arg(long, value_name = "PNG", requires = "convolve")]
+    pub convolve_input: Option<PathBuf>,
+
+    /// Directory with an `effects.toml` listing WGSL post-effect passes applied to the image in order,
+    /// reloaded along with the shader
+    #[arg(long, value_name = "DIR")]
+    pub post_effects: Option<PathBuf>,
+
+    /// How the image is fitted into a window of another aspect ratio
+    #[arg(long, value_enum, default_value_t = Scaling::Fit)]
+    pub scaling: Scaling,
+
+    /// How the image is sampled when scaled to the window, press F to toggle
+    #[arg(long, value_enum, default_value_t = Filter::Nearest)]
+    pub filter: Filter,
+
+    /// Workgroup size of the compute shaders as `WIDTHxHEIGHT`, instead of the one of the profile
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    pub workgroup_size: Option<[u32; 2]>,
+
+    /// Graphics API to use, instead of the ones of the profile
+    #[arg(long, value_enum)]
+    pub backend: Option<Backend>,
+
+    /// Which kind of GPU to prefer when no adapter is given
+    #[arg(long, value_enum)]
+    pub power_preference: Option<PowerPreference>,
+
+    /// GPU to draw on, either its index in `--list-adapters` or part of its name
+    #[arg(long, value_name = "INDEX|NAME")]
+    pub adapter: Option<String>,
+
+    /// Print the available GPUs and exit
+    #[arg(long)]
+    pub list_adapters: bool,
+
+    /// Run a few known-good compute and readback operations on the GPU,
+    /// print which of them work and exit
+    #[arg(long)]
+    pub self_test: bool,
+
+    /// Time the drawing shader with several workgroup sizes,
+    /// report the fastest one for this GPU and exit
+    #[arg(long)]
+    pub bench_workgroups: bool,
+
+    /// What to show
+    #[arg(long, value_enum, default_value_t = Preset::Drawing)]
+    pub preset: Preset,
+
+    /// Feed rate of the reaction-diffusion preset
+    #[arg(long, default_value_t = 0.0545)]
+    pub feed: f32,
+
+    /// Kill rate of the reaction-diffusion preset
+    #[arg(long, default_value_t = 0.062)]
+    pub kill: f32,
+
+    /// Simulation steps dispatched per frame, all recorded into the frame's single submission.
+    /// 12 for the reaction-diffusion preset and 1 for compute drawings by default
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub steps_per_frame: Option<u32>,
+
+    /// What the paint preset keeps for undo and redo
+    #[arg(long, value_enum, default_value_t = UndoHistory::Tiles)]
+    pub undo_history: UndoHistory,
+
+    /// Memory on the GPU the paint preset keeps its undo history in.
+    /// Each snapshot takes 8 bytes per pixel of the drawn image, the tiles of a stroke take the less the fewer pixels it changed
+    #[arg(long, value_name = "MEGABYTES", default_value_t = 128)]
+    pub undo_memory: u64,
+
+    /// Average successive frames, for noisy shaders like path tracers.
+    /// Always on for the path tracer preset, press R to restart accumulating
+    #[arg(long)]
+    pub accumulate: bool,
+
+    /// Curve mapping HDR output to the display, press T to cycle through them.
+    /// Defaults to aces for the path tracer and clamp otherwise
+    #[arg(long, value_enum)]
+    pub tonemap: Option<Tonemapper>,
+
+    /// Multiplier applied before tonemapping, adjust with + and -. Defaults to 1
+    #[arg(long)]
+    pub exposure: Option<f32>,
+
+    /// Show a histogram of the luminance of the image over its bottom left corner, toggle it with H.
+    /// Requires compute shaders
+    #[arg(long)]
+    pub histogram: bool,
+
+    /// Adapt the exposure smoothly to the average luminance of the image, so it ends up middle gray.
+    /// `--exposure`, + and - brighten or darken on top of it. Requires compute shaders
+    #[arg(long)]
+    pub auto_exposure: bool,
+
+    /// Whether the drawing shader outputs linear or sRGB encoded colors
+    #[arg(long, value_enum, default_value_t = ColorSpace::Linear)]
+    pub color_space: ColorSpace,
+
+    /// WGSL file providing the `draw` function, instead of the one built into the preset.
+    /// `.glsl` and `.frag` files are GLSL, translated to WGSL.
+    /// Also takes the name of a bundle downloaded with `fetch`
+    #[arg(long, value_name = "PATH|NAME")]
+    pub shader: Option<PathBuf>,
+
+    /// Live coding on stage: edits to the files next to the `--shader` are validated in the background
+    /// and swapped in between frames, broken ones never interrupt the image.
+    /// A strip along the bottom turns red while the latest edit fails and flashes green on a swap
+    #[arg(long, requires = "shader")]
+    pub live: bool,
+
+    /// Limit what the `--shader` can do, as is done for bundles from the library:
+    /// the resolution, loop counts, scratch buffer and GPU time per frame are capped
+    #[arg(long)]
+    pub sandbox: bool,
+
+    /// Don't limit bundles from the library, for ones you wrote or reviewed
+    #[arg(long, conflicts_with = "sandbox")]
+    pub trust: bool,
+
+    /// File bound as a read-only storage buffer of the compute drawing, may be repeated.
+    /// The first one is bound to `@group(0) @binding(1)`, the next to binding 2 and so on
+    #[arg(long = "storage-buffer", value_name = "PATH")]
+    pub storage_buffers: Vec<PathBuf>,
+
+    /// Size in bytes of the `scratch` buffer compute drawing shaders can keep state in across frames,
+    /// such as hash grids or caches. It is zeroed at startup and when accumulation restarts.
+    /// Post effects read and write the same buffer, drawings evaluated by fragment shaders don't get it
+    #[arg(long, value_name = "BYTES")]
+    pub scratch_size: Option<u64>,
+
+    /// Keep the last FRAMES images of the compute drawing for shaders to read with `history(pixel, frames_ago)`,
+    /// for echoes, trails and temporal filters. Each frame kept takes as much memory as the drawn image
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(1..=MAX_HISTORY as i64))]
+    pub history: Option<u32>,
+
+    /// Edge length in voxels of the 3D texture filled by `fn volume(voxel: vec3<u32>) -> vec4<f32>`,
+    /// if the compute drawing shader defines one
+    #[arg(long, value_name = "VOXELS", default_value_t = 64, value_parser = clap::value_parser!(u32).range(1..=MAX_VOLUME_SIZE as i64))]
+    pub volume_size: u32,
+
+    /// How shaders defining `volume` but no `draw` are shown
+    #[arg(long, value_enum, default_value_t = VolumeView::Volume)]
+    pub volume_view: VolumeView,
+
+    /// Edge length in pixels of the faces drawn by `fn cubemap(direction: vec3<f32>) -> vec4<f32>`,
+    /// if the compute drawing shader defines one
+    #[arg(long, value_name = "PIXELS", default_value_t = 512, value_parser = clap::value_parser!(u32).range(1..))]
+    pub cubemap_size: u32,
+
+    /// Write the first frame of a shader defining `cubemap` to this PNG, the equirectangular
+    /// projection unless it has a `draw` of its own, e.g. with `--frames 1 --resolution 4096x2048`
+    #[arg(long, value_name = "PATH")]
+    pub equirect: Option<PathBuf>,
+
+    /// `@compute` entry point of the `--shader` to dispatch instead of the one calling `draw`,
+    /// press Tab to cycle through them
+    #[arg(long, value_name = "NAME")]
+    pub entry_point: Option<String>,
+
+    /// Record audio and hand its spectrum and waveform to the drawing shader as `audio`.
+    /// Requires building with the `audio` feature
+    #[arg(long)]
+    pub audio: bool,
+
+    /// Input device to record with `--audio`, as case insensitive part of its name.
+    /// Defaults to the default input device of the system
+    #[arg(long, value_name = "NAME", requires = "audio")]
+    pub audio_device: Option<String>,
+
+    /// Hand the sticks, triggers and buttons of up to 4 gamepads to the drawing shader as `gamepads`,
+    /// and take the actions bound to the buttons under `[gamepad]` in the `--config` file.
+    /// Requires building with the `gamepad` feature
+    #[arg(long)]
+    pub gamepad: bool,
+
+    /// Initial `globals.params` as `X,Y`, instead of those of the bundle
+    #[arg(long, value_name = "X,Y", value_parser = parse_params, allow_negative_numbers = true)]
+    pub params: Option<[f32; 2]>,
+
+    /// Start the shader with the settings of the command line,
+    /// instead of where it was left off when the app was last closed
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// Move the `camera` of the drawing shader with the mouse and W, A, S and D,
+    /// always on for the sdf preset
+    #[arg(long)]
+    pub camera: bool,
+
+    /// Only draw the pixels within PIXELS of where the cursor moved again, for paint-style shaders
+    /// whose image changes under the cursor alone. Everything is drawn when the scene or its parameters change
+    /// and when R is pressed. Requires compute shaders
+    #[arg(long, value_name = "PIXELS")]
+    pub dirty_radius: Option<u32>,
+
+    /// Directory with a `project.toml` describing the shader, its resources, settings and post effects,
+    /// applied over the command line and again whenever it is saved
+    #[arg(long, value_name = "DIR")]
+    pub project: Option<PathBuf>,
+
+    /// Directory of `.wgsl` shaders to cycle through, all built up front so switching doesn't stall.
+    /// Page Down and Page Up switch to the next and previous one
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["shader", "example", "project", "live"])]
+    pub playlist: Option<PathBuf>,
+
+    /// Seconds each shader of the `--playlist` is shown, 0 to only switch with the keys
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 30.0,
+        value_parser = parse_seconds,
+        requires = "playlist"
+    )]
+    pub playlist_interval: f32,
+
+    /// Seconds the `--playlist` crossfades between shaders, 0 to cut
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 0.0,
+        value_parser = parse_seconds,
+        requires = "playlist"
+    )]
+    pub crossfade: f32,
+
+    /// How the `--crossfade` replaces one shader with the next
+    #[arg(long, value_enum, default_value_t = Transition::Fade, requires = "crossfade")]
+    pub transition: Transition,
+
+    /// Draw one of the bundled example shaders instead of the one of the preset.
+    /// Number keys 1 to 9 switch between them
+    #[arg(long, value_enum, conflicts_with = "shader")]
+    pub example: Option<Example>,
+
+    /// Print the bundled example shaders and exit
+    #[arg(long)]
+    pub list_examples: bool,
+
+    /// Start in fullscreen, borderless unless `exclusive` is given.
+    /// F11 and Alt+Enter toggle fullscreen in the same mode
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "borderless")]
+    pub fullscreen: Option<FullscreenMode>,
+
+    /// Run unattended, e.g. as a systemd service on a kiosk.
+    /// Hotkeys and touch controls are disabled, SIGHUP reloads the shader and the `--config` file
+    /// and SIGTERM shuts down cleanly
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Draw one screen of a video wall of `COLSxROWS` screens, which share one `globals.resolution`
+    #[arg(long, value_name = "COLSxROWS", value_parser = parse_size, requires = "wall_tile")]
+    pub wall_grid: Option<[u32; 2]>,
+
+    /// The screen of the `--wall-grid` this machine shows, `1x1` being the top left one
+    #[arg(long, value_name = "COLxROW", value_parser = parse_size, requires = "wall_grid")]
+    pub wall_tile: Option<[u32; 2]>,
+
+    /// Broadcast the clock, scene, seed and parameters of every frame to ADDR, e.g. `192.168.1.255:7700`,
+    /// for `--sync-follow`ers to show in lockstep
+    #[arg(long, value_name = "ADDR", conflicts_with = "sync_follow")]
+    pub sync_lead: Option<SocketAddr>,
+
+    /// Only draw the frames a `--sync-lead`er broadcasts to this UDP port,
+    /// with its clock, scene, seed and parameters
+    #[arg(long, value_name = "PORT")]
+    pub sync_follow: Option<u16>,
+
+    /// Print a BLAKE3 hash of every drawn frame, or write them to PATH.
+    /// Time advances by 1/60 s per frame, or 1/`--fps`, for reproducible results
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "-")]
+    pub frame_hash: Option<PathBuf>,
+
+    /// Print what a compute drawing reports through its `feedback` buffer, or write it to PATH.
+    /// One line per frame with the frame number, the counters and the values
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "-")]
+    pub feedback: Option<PathBuf>,
+
+    /// Write every drawn frame as numbered image to this directory.
+    /// Time advances by 1/60 s per frame, or 1/`--fps`, so the frames make up a video of that rate
+    #[arg(long, value_name = "DIR")]
+    pub record: Option<PathBuf>,
+
+    /// Publish every drawn frame as NDI source of this name, for VJ software like Resolume or OBS.
+    /// Requires building with the `ndi` feature and the NDI runtime
+    #[arg(long, value_name = "NAME")]
+    pub ndi: Option<String>,
+
+    /// Blur motion by averaging this many samples at times spread over each recorded frame.
+    /// Requires compute shaders, like `--accumulate`
+    #[arg(long, value_name = "SAMPLES", value_parser = clap::value_parser!(u32).range(2..), requires = "record")]
+    pub motion_blur: Option<u32>,
+
+    /// Part of the frame interval `--motion-blur` samples are spread over, 0.5 for a film camera's 180° shutter
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        default_value_t = 0.5,
+        value_parser = parse_fraction,
+        requires = "motion_blur"
+    )]
+    pub shutter: f32,
+
+    /// How `--record` stores frames
+    #[arg(long, value_enum, default_value_t = RecordFormat::Png, requires = "record")]
+    pub record_format: RecordFormat,
+
+    /// File format of screenshots, crops, `--equirect` and frames recorded as images.
+    /// exr and png16 keep the precision of float drawings, exr also values outside of 0..1
+    #[arg(long, value_enum, default_value_t = ImageFormat::Png8)]
+    pub format: ImageFormat,
+
+    /// Also write `telemetry.jsonl` to the `--record` directory, one JSON object per frame
+    /// with its time, seed, params, camera and GPU time
+    #[arg(long, requires = "record")]
+    pub telemetry: bool,
+
+    /// Value of `globals.seed`, e.g. to look at one piece of a `--generate`d edition
+    #[arg(long, default_value_t = 0, conflicts_with = "generate")]
+    pub seed: u32,
+
+    /// Write an edition of this many images, each drawn with the next `globals.seed`,
+    /// along with a manifest of their seeds, params and hashes, and exit
+    #[arg(long, value_name = "COUNT")]
+    pub generate: Option<u32>,
+
+    /// `globals.seed` of the first image of `--generate`
+    #[arg(long, value_name = "SEED", default_value_t = 1, requires = "generate")]
+    pub seed_start: u32,
+
+    /// Directory `--generate` writes the edition to
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "edition",
+        requires = "generate"
+    )]
+    pub edition_dir: PathBuf,
+
+    /// Advance `globals.time` by exactly 1/FPS s per drawn frame instead of following the clock,
+    /// so every run on every machine draws the same frames however long they take
+    #[arg(long, value_name = "FPS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub fps: Option<u32>,
+
+    /// Cap the frame rate at 30 fps and halve the resolution while on battery or when the system runs hot
+    #[arg(long, conflicts_with_all = ["frame_hash", "record", "fps"])]
+    pub power_saving: bool,
+
+    /// Draw at most this many frames per second, sleeping in between
+    #[arg(long, value_name = "FPS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_fps: Option<u32>,
+
+    /// Only draw when something changed, like the window size, the parameters or the scene, or on input.
+    /// For static shaders, which don't animate with `globals.time`
+    #[arg(long, conflicts_with_all = ["frame_hash", "record", "fps"])]
+    pub on_demand: bool,
+
+    /// Draw as animated desktop wallpaper: a window covering the primary monitor behind the desktop icons
+    /// on Windows and X11, or below all other windows elsewhere, at 30 fps and with `--power-saving`
+    #[arg(long, conflicts_with_all = ["frame_hash", "record", "fps", "fullscreen"])]
+    pub wallpaper: bool,
+
+    /// Run as screensaver: fullscreen with a hidden cursor until a key is pressed or the mouse moves,
+    /// at 30 fps, with `--power-saving`, and at half the resolution after ten minutes
+    #[arg(long, conflicts_with_all = ["frame_hash", "record", "fps", "fullscreen", "wallpaper"])]
+    pub screensaver: bool,
+
+    /// Draw into the window with this handle, like screensavers do in the preview of their settings, at 15 fps.
+    /// Only Windows window handles are supported
+    #[arg(long, value_name = "HANDLE", conflicts_with_all = ["frame_hash", "record", "fps", "fullscreen", "wallpaper", "screensaver"])]
+    pub screensaver_preview: Option<isize>,
+
+    /// Exit after drawing this many frames
+    #[arg(long, value_name = "COUNT")]
+    pub frames: Option<u32>,
+
+    /// TOML file with settings for installations, like the idle attract mode
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Append log output to this file instead of writing it to stderr
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+}
+
+impl Options {
+    /// Whether the argument with this id was given on the command line
+    pub fn given(&self, id: &str) -> bool {
+        self.given.0.contains(id)
+    }
+
+    /// Frame rate time advances at regardless of the clock, from `--fps`,
+    /// or 60 while frames are recorded or hashed. `None` when time follows the clock
+    pub fn fixed_fps(&self) -> Option<u32> {
+        self.fps
+            .or((self.record.is_some() || self.frame_hash.is_some()).then_some(60))
+    }
+
+    /// Resolution of the drawn image, from `--resolution` or the profile
+    pub fn resolution(&self) -> (u32, u32) {
+        self.resolution
+            .map_or(self.profile.resolution(), |[width, height]| (width, height))
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure.unwrap_or(1.0)
+    }
+
+    /// `--scratch-size` rounded up to whole `u32`s
+    pub fn scratch_size(&self) -> Option<u64> {
+        self.scratch_size
+            .map(|size| size.max(4).next_multiple_of(4))
+    }
+
+    /// The video wall screen from `--wall-grid` and `--wall-tile`
+    pub fn wall(&self) -> Result<Option<WallTile>, String> {
+        match (self.wall_grid, self.wall_tile) {
+            (Some(grid), Some(tile)) => WallTile::new(grid, tile).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum Command {
+    /// Download a shader bundle from a git repository into the local library,
+    /// afterwards it can be loaded with `--shader <NAME>`.
+    /// A bundle is a `draw.wgsl`, the files it `#include`s and optionally a `params` file
+    /// with the initial values of `globals.params`
+    Fetch {
+        /// URL of a git repository containing a `draw.wgsl`
+        source: String,
+
+        /// Name to load the bundle by, defaults to the last part of the URL
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Time two drawing shaders headlessly at several resolutions and compare their output.
+    /// Both get the same globals. Exits with an error if their images differ
+    BenchCompare {
+        /// WGSL file or library bundle to compare against
+        old: PathBuf,
+
+        /// WGSL file or library bundle, e.g. an optimized version of `old`
+        new: PathBuf,
+    },
+
+    /// Convert frames recorded with `--record-format raw` to PNGs or, using ffmpeg, a video
+    Transcode {
+        /// Directory the frames were recorded to
+        input: PathBuf,
+
+        /// Video file like `out.mp4`, or a directory for numbered PNGs
+        output: PathBuf,
+
+        /// Frame rate of the video
+        #[arg(long, default_value_t = 60)]
+        framerate: u32,
+
+        /// Audio file to mux into the video, cut to its length.
+        /// Defaults to the `audio.wav` recorded along with `--audio`, if there is one
+        #[arg(long, value_name = "PATH")]
+        audio: Option<PathBuf>,
+
+        /// Seconds into the video the audio starts at, negative to skip its beginning
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 0.0,
+            allow_negative_numbers = true
+        )]
+        audio_offset: f64,
+    },
+
+    /// Split an image sequence of the `--shader` or `--example` into chunks of frames,
+    /// hand them to `render-worker`s and write the frames they draw as PNGs.
+    /// The `--resolution` of the coordinator is used by all workers
+    RenderCoordinator {
+        /// Directory for the frames and `manifest.csv`.
+        /// Frames an earlier run left there are checked against the manifest and kept
+        output: PathBuf,
+
+        /// Number of frames to draw
+        #[arg(long)]
+        frames: u32,
+
+        /// Frames handed to a worker at once
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+        chunk_frames: u32,
+
+        /// Frame rate `globals.time` advances at
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+        framerate: u32,
+
+        /// Address workers connect to
+        #[arg(long, default_value = "0.0.0.0:7800")]
+        listen: SocketAddr,
+    },
+
+    /// Draw chunks of frames handed out by a `render-coordinator`.
+    /// Start one per GPU with `--adapter` to use several on one machine
+    RenderWorker {
+        /// Address of the coordinator, e.g. `192.168.1.10:7800`
+        coordinator: String,
+    },
+
+    /// Draw the `--shader` or `--example` in a grid, sweeping `globals.params.x` across columns
+    /// and optionally `globals.params.y` across rows, and write the labeled images into one PNG
+    ContactSheet {
+        /// PNG file to write
+        output: PathBuf,
+
+        /// Number of columns
+        #[arg(long, default_value_t = 5)]
+        columns: u32,
+
+        /// Number of rows, defaults to 5 with `--y` and 1 without
+        #[arg(long)]
+        rows: Option<u32>,
+
+        /// Range of `params.x` from the first to the last column
+        #[arg(long, value_name = "FROM..TO", value_parser = parse_range, default_value = "0..1")]
+        x: [f32; 2],
+
+        /// Range of `params.y` from the first to the last row,
+        /// it keeps its initial value if not given
+        #[arg(long, value_name = "FROM..TO", value_parser = parse_range)]
+        y: Option<[f32; 2]>,
+
+        /// Size of each image
+        #[arg(long, value_name = "WxH", value_parser = parse_size, default_value = "256x256")]
+        cell: [u32; 2],
+
+        /// Value of `globals.time` in seconds
+        #[arg(long, default_value_t = 0.0)]
+        time: f32,
+    },
+
+    /// Run WGSL effects over PNGs and videos on the GPU and write the results, like a batch image editor with custom filters:
+    /// the `--effect`s in order, then the passes of `--post-effects`
+    Process {
+        /// PNG files, or videos decoded and encoded again with ffmpeg
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Directory the results are written to, under the file names of the inputs
+        #[arg(long, short)]
+        output: PathBuf,
+
+        /// WGSL file providing `fn effect(pixel: vec2<u32>) -> vec4<f32>` like a post effect,
+        /// with `--params` as its first two params. Repeat to run several
+        #[arg(long = "effect", value_name = "WGSL")]
+        effects: Vec<PathBuf>,
+    },
+
+    /// Draw the `--shader` or `--example` at a size beyond the texture limits of the device,
+    /// e.g. a 16384x16384 poster, in tiles stitched into one PNG
+    Poster {
+        /// PNG file to write
+        output: PathBuf,
+
+        /// Size of the whole image
+        #[arg(long, value_name = "WxH", value_parser = parse_size)]
+        size: [u32; 2],
+
+        /// Size of the tiles drawn at once
+        #[arg(long, value_name = "WxH", value_parser = parse_size, default_value = "2048x2048")]
+        tile: [u32; 2],
+
+        /// Value of `globals.time` in seconds
+        #[arg(long, default_value_t = 0.0)]
+        time: f32,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// The drawing shader in `src/shaders/drawing.wgsl`
+    Drawing,
+    /// Gray-Scott reaction-diffusion simulation
+    ReactionDiffusion,
+    /// Progressive path tracer, in `src/shaders/path_tracer.wgsl`
+    PathTracer,
+    /// Signed distance field raymarcher viewed through the `camera`, in `src/shaders/sdf.wgsl`
+    Sdf,
+    /// Mandelbrot and Julia set explorer, drag to pan, scroll to zoom and J for the Julia set,
+    /// in `src/shaders/fractal.wgsl`
+    Fractal,
+    /// Paint with the mouse, the wheel sizes the brush, X picks the next color and Ctrl+Z takes back a stroke
+    Paint,
+}
+
+fn parse_size(arg: &str) -> Result<[u32; 2], String> {
+    let (x, y) = arg
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, e.g. 8x8, got {arg:?}"))?;
+    let parse = |n: &str| match n.trim().parse::<u32>() {
+        Ok(0) | Err(_) => Err(format!("{n:?} is not a positive number")),
+        Ok(n) => Ok(n),
+    };
+    Ok([parse(x)?, parse(y)?])
+}
+
+fn parse_scale(arg: &str) -> Result<(String, f32), String> {
+    let (node, factor) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected NODE=FACTOR, e.g. drawing=0.5, got {arg:?}"))?;
+    if !SCALED_NODES.contains(&node) {
+        return Err(format!(
+            "{node:?} is not a node of the render graph, expected one of {}",
+            SCALED_NODES.join(", ")
+        ));
+    }
+    match factor.trim().parse::<f32>() {
+        Ok(factor) if factor > 0.0 && factor <= MAX_SCALE => Ok((node.to_owned(), factor)),
+        _ => Err(format!(
+            "{factor:?} is not a number above 0 and up to {MAX_SCALE}"
+        )),
+    }
+}
+
+/// A duration in seconds, 0 or more and short enough for a `Duration`
+fn parse_seconds(arg: &str) -> Result<f32, String> {
+    match arg.trim().parse::<f32>() {
+        Ok(seconds) if Duration::try_from_secs_f32(seconds).is_ok() => Ok(seconds),
+        _ => Err(format!("{arg:?} is not a number of seconds, 0 or more")),
+    }
+}
+
+fn parse_fraction(arg: &str) -> Result<f32, String> {
+    match arg.trim().parse::<f32>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("{arg:?} is not a number from 0 to 1")),
+    }
+}
+
+fn parse_params(arg: &str) -> Result<[f32; 2], String> {
+    let (x, y) = arg
+        .split_once(',')
+        .ok_or_else(|| format!("expected X,Y, e.g. 0.5,0.5, got {arg:?}"))?;
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<f32>()
+            .map_err(|_| format!("{n:?} is not a number"))
+    };
+    Ok([parse(x)?, parse(y)?])
+}
+
+fn parse_range(arg: &str) -> Result<[f32; 2], String> {
+    let (from, to) = arg
+        .split_once("..")
+        .ok_or_else(|| format!("expected FROM..TO, e.g. 0..1, got {arg:?}"))?;
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<f32>()
+            .map_err(|_| format!("{n:?} is not a number"))
+    };
+    Ok([parse(from)?, parse(to)?])
+}
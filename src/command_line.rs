@@ -0,0 +1,225 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    adapter, app, bench, cli, contact_sheet, crash, edition, examples, farm, library, logger,
+    process, project, screensaver, self_test, tiled, transcode, wallpaper,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use clap::Parser;
+#[cfg(not(target_arch = "wasm32"))]
+use winit::{event_loop::EventLoop, window::Window};
+
+/// What the `show-gpu-compute-image` binary does: run the command or app the command line asks for
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run() {
+    let command_line = match screensaver::Invocation::detect() {
+        None => cli::Options::parse(),
+        Some(Ok(screensaver::Invocation::Configure)) => {
+            screensaver::configure();
+            return;
+        }
+        Some(invocation) => match invocation.and_then(screensaver::Invocation::options) {
+            Ok(options) => options,
+            Err(err) => {
+                screensaver::show_message(&format!("Failed to start the screensaver: {err}"));
+                std::process::exit(1);
+            }
+        },
+    };
+    if let Err(err) = logger::Logger::init(command_line.log_file.as_deref(), command_line.daemon) {
+        eprintln!("Failed to open log file: {err}");
+        std::process::exit(1);
+    }
+    crash::install(command_line.log_file.as_deref());
+    let options = match project::apply(&command_line) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("Failed to load project: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    match &options.command {
+        Some(cli::Command::Fetch { source, name }) => {
+            match library::fetch(source, name.as_deref()) {
+                Ok(name) => println!("Fetched, load it with --shader {name}"),
+                Err(err) => {
+                    eprintln!("Failed to fetch {source}: {err}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(cli::Command::BenchCompare { old, new }) => {
+            let same = bench::compare(&options, old, new);
+            std::process::exit(if same { 0 } else { 1 });
+        }
+        Some(cli::Command::ContactSheet {
+            output,
+            columns,
+            rows,
+            x,
+            y,
+            cell,
+            time,
+        }) => {
+            let sweep = contact_sheet::Sweep {
+                columns: (*columns).max(1),
+                rows: rows.unwrap_or(if y.is_some() { 5 } else { 1 }).max(1),
+                x: *x,
+                y: *y,
+                cell: *cell,
+                time: *time,
+            };
+            match contact_sheet::run(&options, &sweep, output) {
+                Ok(()) => println!("Wrote {}", output.display()),
+                Err(err) => {
+                    eprintln!("Failed to draw contact sheet: {err}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(cli::Command::Poster {
+            output,
+            size,
+            tile,
+            time,
+        }) => {
+            let poster = tiled::Poster {
+                size: *size,
+                tile: *tile,
+                time: *time,
+            };
+            match tiled::run(&options, &poster, output) {
+                Ok(()) => println!("Wrote {}", output.display()),
+                Err(err) => {
+                    eprintln!("Failed to draw poster: {err}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(cli::Command::Process {
+            inputs,
+            output,
+            effects,
+        }) => {
+            match process::run(&options, inputs, effects, output) {
+                Ok(count) => println!("Wrote {count} files to {}", output.display()),
+                Err(err) => {
+                    eprintln!("Failed to process images: {err}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(cli::Command::Transcode {
+            input,
+            output,
+            framerate,
+            audio,
+            audio_offset,
+        }) => {
+            match transcode::run(input, output, *framerate, audio.as_deref(), *audio_offset) {
+                Ok(frames) => println!("Transcoded {frames} frames to {}", output.display()),
+                Err(err) => {
+                    eprintln!("Failed to transcode {}: {err}", input.display());
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(cli::Command::RenderCoordinator {
+            output,
+            frames,
+            chunk_frames,
+            framerate,
+            listen,
+        }) => {
+            let (width, height) = options.resolution();
+            let sequence = farm::Sequence {
+                frames: *frames,
+                chunk_frames: *chunk_frames,
+                framerate: *framerate,
+                width,
+                height,
+            };
+            match farm::coordinate(output, sequence, *listen) {
+                Ok(drawn) => println!(
+                    "All {frames} frames are in {}, {drawn} drawn by workers",
+                    output.display()
+                ),
+                Err(err) => {
+                    eprintln!("Failed to render: {err}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(cli::Command::RenderWorker { coordinator }) => {
+            match farm::work(&options, coordinator) {
+                Ok(drawn) => println!("Drew {drawn} frames"),
+                Err(err) => {
+                    eprintln!("Failed to render: {err}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        None => {}
+    }
+    if options.list_adapters {
+        adapter::AdapterSelection::new(&options).list();
+        return;
+    }
+    if options.list_examples {
+        examples::Example::list();
+        return;
+    }
+    if options.bench_workgroups {
+        let measured = bench::run(&options);
+        std::process::exit(if measured { 0 } else { 1 });
+    }
+    if let Some(count) = options.generate {
+        match edition::generate(&options, count) {
+            Ok(()) => println!("Wrote {count} images to {}", options.edition_dir.display()),
+            Err(err) => {
+                eprintln!("Failed to generate edition: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if options.self_test {
+        let passed = self_test::run(&options);
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
+    // Run main loop, which creates the window and applies the project again whenever it changes
+    let event_loop = EventLoop::with_user_event().build().unwrap();
+    app::run_app(
+        event_loop,
+        command_line,
+        Box::new(move |event_loop| {
+            let mut attributes = Window::default_attributes()
+                .with_title(app::TITLE)
+                .with_inner_size(winit::dpi::LogicalSize::new(app::WIDTH, app::HEIGHT));
+            if options.wallpaper {
+                attributes = wallpaper::window_attributes(attributes, event_loop);
+            }
+            attributes = screensaver::window_attributes(attributes, &options);
+            let window = event_loop.create_window(attributes).unwrap();
+            if options.wallpaper {
+                wallpaper::attach(&window);
+            }
+            screensaver::set_up(&window, &options);
+            window
+        }),
+    );
+}
+
+/// What the `show-gpu-compute-image` binary does: start the app in the page
+#[cfg(target_arch = "wasm32")]
+pub fn run() {
+    crate::web::main();
+}
@@ -1,81 +1,1660 @@
+use std::{rc::Rc, sync::Arc};
+
+use clap::ValueEnum;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::*;
 
-use crate::shaders::Shaders;
+use crate::{
+    counters,
+    feedback::{Feedback, FeedbackBuffer},
+    globals::{GlobalsState, Pointer},
+    gpu,
+    shaders::Shaders,
+};
+
+/// Format of the images handed to the render pass.
+/// Floating point, so shaders can output HDR values which get tone-mapped on presentation
+pub const OUTPUT_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Formats the compute drawing may write to a storage texture, the first the adapter supports is used.
+/// Some mobile GPUs can't write [`OUTPUT_FORMAT`] from shaders and draw with 8 bits per channel instead,
+/// as `rgba8uint` since `rgba8unorm` storage isn't reliable, see [`OutputStorage`]
+pub const OUTPUT_FORMATS: [TextureFormat; 2] = [OUTPUT_FORMAT, TextureFormat::Rgba8Uint];
+
+/// Format of the image drawings with 8 bits per channel are copied into for the render pass.
+/// The passes after the drawing all write `OUTPUT_FORMAT` and are left out
+pub const PACKED_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// Rows of [`OutputStorage::Buffer`] pixels are padded to a multiple of this many, for copying them into a texture
+const PACKED_ROW_ALIGNMENT: u32 = COPY_BYTES_PER_ROW_ALIGNMENT / 4;
+
+/// Where the compute drawing writes its image, negotiated with the adapter by [`ComputeState::output_storage`].
+/// Drawing shaders call `textureStore(out_image, ...)` with `vec4<f32>` colors and `vec2<i32>` pixels either way
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputStorage {
+    /// A storage texture of [`OUTPUT_FORMAT`], read as it is
+    Float,
+    /// An `rgba8uint` storage texture, copied into a [`PACKED_FORMAT`] image after drawing
+    Uint,
+    /// `pack4x8unorm`ed pixels in a storage buffer, copied into a [`PACKED_FORMAT`] image after drawing,
+    /// for adapters that can't write storage textures
+    Buffer,
+}
+
+impl OutputStorage {
+    /// Format of the image the passes after the drawing and the render pass read
+    pub fn format(self) -> TextureFormat {
+        match self {
+            Self::Float => OUTPUT_FORMAT,
+            Self::Uint | Self::Buffer => PACKED_FORMAT,
+        }
+    }
+
+    /// Declares `out_image` at `@group(0) @binding(0)`, and `store_out_image` for the formats
+    /// `textureStore` can't write `vec4<f32>` colors to
+    pub fn wgsl_declaration(self) -> String {
+        match self {
+            Self::Float => format!(
+                "@group(0) @binding(0)\nvar out_image: texture_storage_2d<{}, write>;\n",
+                gpu::wgsl_storage_format(OUTPUT_FORMAT)
+                    .expect("output formats are storage formats")
+            ),
+            Self::Uint => include_str!("./shaders/out_image_uint.wgsl").to_owned(),
+            Self::Buffer => format!(
+                "const OUT_ROW_ALIGNMENT: u32 = {PACKED_ROW_ALIGNMENT}u;\n{}",
+                include_str!("./shaders/out_image_buffer.wgsl")
+            ),
+        }
+    }
+}
+
+/// Most previous frames `--history` keeps
+pub const MAX_HISTORY: u32 = 64;
+
+/// Items a drawing can [emit](Launches) per frame, `MAX_LAUNCHES` in `src/shaders/launch.wgsl`
+pub const MAX_LAUNCHES: u32 = 1 << 20;
+
+/// Largest `--volume-size`, the most WebGL supports
+pub const MAX_VOLUME_SIZE: u32 = 256;
+
+/// How the built-in `draw` shows a volume, see `src/shaders/volume_view.wgsl`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VolumeView {
+    /// A slice through the cube, at the depth of `globals.params.y`
+    Slice,
+    /// Raymarched through the `camera`
+    Volume,
+}
 
 pub struct ComputeState {
-    pub pipeline: ComputePipeline,
+    /// One per entry point of the drawing shader, created when it is first selected
+    pipelines: Vec<Option<ComputePipeline>>,
+    entry_points: Vec<String>,
+    /// Index of the dispatched entry point
+    active: usize,
+    module: Rc<ShaderModule>,
+    /// For the pipelines of entry points selected later, see [`Shaders::pipeline_cache`]
+    pipeline_cache: Option<Arc<PipelineCache>>,
+    pipeline_layout: PipelineLayout,
     pub bind_group: BindGroup,
-    pub output_view: TextureView,
+    pub globals_bind_group: BindGroup,
+    output: ComputeOutput,
+    /// Decides on the GPU whether the drawing is dispatched
+    predicate: Option<Box<Predicate>>,
+    /// Restricts `draw` to the tiles the shader accepts
+    tiles: Option<Box<TileClassification>>,
+    /// Whether `tiles` are used, switched off to compare
+    classify_tiles: bool,
+    /// Bound after the storage buffers if the drawing shader declares `scratch`
+    scratch: Option<Box<Buffer>>,
+    /// Bound after `scratch` if the drawing shader uses `feedback`
+    feedback: Option<Box<FeedbackBuffer>>,
+    /// Bound after `feedback` if the drawing shader defines `launch`
+    launches: Option<Box<Launches>>,
+    /// Bound after `launches` if previous frames are kept
+    history: Option<Box<History>>,
+    /// Drawn after the image if the drawing shader defines `overlay`
+    overlay: Option<Box<Overlay>>,
+    /// Filled before the image if the drawing shader defines `volume`, and bound after `history`
+    volume: Option<Box<Volume>>,
+    /// Drawn before the image if the drawing shader defines `cubemap`, and bound after `volume`
+    cubemap: Option<Box<Cubemap>>,
+    /// Dispatches of the drawing shader per frame
+    steps_per_frame: u32,
+    workgroup_size: [u32; 2],
+    width: u32,
+    height: u32,
+}
+
+/// What the drawing shader writes at `@group(0) @binding(0)`
+enum ComputeOutput {
+    Texture(Texture, TextureView),
+    /// See [`OutputStorage::Uint`] and [`OutputStorage::Buffer`]
+    Packed(Box<PackedOutput>),
+    /// See [`ComputeStateBuilder::output_buffer`]
+    Buffer(Buffer),
 }
 
 impl ComputeState {
-    pub fn new(device: &Device, shaders: &Shaders, width: u32, height: u32) -> Self {
-        let output_texture = device.create_texture(&TextureDescriptor {
-            label: Some("Compute Output Texture"),
+    /// Storage textures in the first of [`OUTPUT_FORMATS`] shaders on `adapter` can write, or else a storage buffer.
+    /// `None` if the drawing has to be rendered with fragment shaders
+    pub fn output_storage(adapter: &Adapter) -> Option<OutputStorage> {
+        let limits = adapter.limits();
+        let format = (limits.max_storage_textures_per_shader_stage > 0)
+            .then(|| gpu::storage_format(adapter, &OUTPUT_FORMATS))
+            .flatten();
+        match format {
+            Some(OUTPUT_FORMAT) => Some(OutputStorage::Float),
+            Some(_) => Some(OutputStorage::Uint),
+            None => {
+                (limits.max_storage_buffers_per_shader_stage > 0).then_some(OutputStorage::Buffer)
+            }
+        }
+    }
+
+    /// Bind group 0 holds the output image, or [buffer](ComputeStateBuilder::output_buffer), at binding 0,
+    /// followed by the buffers added with [`ComputeStateBuilder::storage_buffer`],
+    /// the scratch buffer, the feedback buffer, the launched items, the history, the volume and the cubemap
+    pub fn builder<'a>(
+        device: &'a Device,
+        shaders: &'a Shaders,
+        globals: &'a GlobalsState,
+        width: u32,
+        height: u32,
+    ) -> ComputeStateBuilder<'a> {
+        ComputeStateBuilder {
+            device,
+            shaders,
+            globals,
+            width,
+            height,
+            storage_buffers: Vec::new(),
+            entry_point: None,
+            output_buffer: None,
+            steps_per_frame: 1,
+        }
+    }
+
+    /// The image drawn into, `None` if the state writes a buffer
+    pub fn output_texture(&self) -> Option<&Texture> {
+        match &self.output {
+            ComputeOutput::Texture(texture, _) => Some(texture),
+            ComputeOutput::Packed(packed) => Some(&packed.texture),
+            ComputeOutput::Buffer(_) => None,
+        }
+    }
+
+    pub fn output_view(&self) -> Option<&TextureView> {
+        match &self.output {
+            ComputeOutput::Texture(_, view) => Some(view),
+            ComputeOutput::Packed(packed) => Some(&packed.view),
+            ComputeOutput::Buffer(_) => None,
+        }
+    }
+
+    /// The buffer written instead of an image, see [`ComputeStateBuilder::output_buffer`].
+    /// Fill it with `Queue::write_buffer` to hand the kernel its initial data
+    pub fn output_buffer(&self) -> Option<&Buffer> {
+        match &self.output {
+            ComputeOutput::Texture(..) | ComputeOutput::Packed(_) => None,
+            ComputeOutput::Buffer(buffer) => Some(buffer),
+        }
+    }
+
+    /// Affordances drawn by the shader's `overlay`, composited on screen only
+    pub fn overlay_view(&self) -> Option<&TextureView> {
+        self.overlay.as_ref().map(|overlay| &overlay.view)
+    }
+
+    /// Whether the drawing shader fills a [volume](Volume)
+    pub fn has_volume(&self) -> bool {
+        self.volume.is_some()
+    }
+
+    /// The cube texture of drawing shaders defining `cubemap`, previewed by the render pass
+    pub fn cubemap_view(&self) -> Option<&TextureView> {
+        self.cubemap.as_ref().map(|cubemap| &cubemap.view)
+    }
+
+    pub fn has_cubemap(&self) -> bool {
+        self.cubemap.is_some()
+    }
+
+    /// Hand the `pointer` to the overlay, takes effect with the next dispatch
+    pub fn set_pointer(&self, queue: &Queue, pointer: Pointer) {
+        if let Some(overlay) = &self.overlay {
+            queue.write_buffer(&overlay.pointer, 0, bytemuck::bytes_of(&pointer));
+        }
+    }
+
+    /// Number of invocations dispatched along x and y, the image size unless writing a buffer
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Dispatch once and read back the output buffer as `T`s, waiting for the GPU.
+    /// `None` if the state draws an image
+    pub fn run_to_vec<T: bytemuck::Pod>(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        globals: &GlobalsState,
+    ) -> Option<Vec<T>> {
+        let output = self.output_buffer()?;
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Compute Output Encoder"),
+        });
+        self.dispatch(&mut encoder, globals);
+        queue.submit(Some(encoder.finish()));
+        Some(counters::read_buffer(device, queue, output))
+    }
+
+    /// Read back the scratch buffer, waiting for the GPU. Pick the counters and lists of
+    /// `src/shaders/counters.wgsl` out of it with the helpers in [`counters`]. `None` without scratch
+    pub fn read_scratch(&self, device: &Device, queue: &Queue) -> Option<Vec<u32>> {
+        let scratch = self.scratch.as_deref()?;
+        Some(counters::read_buffer(device, queue, scratch))
+    }
+
+    /// The scratch buffer, shared with the post effects. `None` without scratch
+    pub fn scratch(&self) -> Option<&Buffer> {
+        self.scratch.as_deref()
+    }
+
+    /// Zero the scratch buffer, so stateful shaders start over
+    pub fn clear_scratch(&self, device: &Device, queue: &Queue) {
+        if let Some(scratch) = &self.scratch {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Clear Scratch Encoder"),
+            });
+            encoder.clear_buffer(scratch, 0, None);
+            queue.submit(Some(encoder.finish()));
+        }
+    }
+
+    /// Start reading back what the drawing shader reported about `frame`, after it was submitted
+    pub fn read_back_feedback(&mut self, device: &Device, queue: &Queue, frame: u32) {
+        if let Some(feedback) = &mut self.feedback {
+            feedback.read_back(device, queue, frame);
+        }
+    }
+
+    /// The latest feedback that arrived, see [`FeedbackBuffer::poll`]
+    pub fn poll_feedback(&mut self, device: &Device) -> Option<Feedback> {
+        self.feedback.as_mut()?.poll(device)
+    }
+
+    /// Name of the dispatched entry point
+    pub fn entry_point(&self) -> &str {
+        &self.entry_points[self.active]
+    }
+
+    /// Dispatch the entry point `name` of the drawing shader from now on,
+    /// creating its pipeline on first use
+    pub fn select_entry_point(&mut self, device: &Device, name: &str) -> Result<(), String> {
+        let index = self
+            .entry_points
+            .iter()
+            .position(|entry_point| entry_point == name)
+            .ok_or_else(|| format!("The drawing shader has no entry point {name:?}"))?;
+        if self.pipelines[index].is_none() {
+            device.push_error_scope(ErrorFilter::Validation);
+            let pipeline = create_pipeline(
+                device,
+                &self.pipeline_layout,
+                &self.module,
+                name,
+                self.pipeline_cache.as_deref(),
+            );
+            if let Some(error) = gpu::pop_error_scope(device) {
+                return Err(format!("Failed to create pipeline for {name:?}: {error}"));
+            }
+            self.pipelines[index] = Some(pipeline);
+        }
+        self.active = index;
+        Ok(())
+    }
+
+    /// Select the entry point after the current one, skipping those failing to compile.
+    /// Returns its name, or `None` if there is no other one
+    pub fn next_entry_point(&mut self, device: &Device) -> Option<&str> {
+        let count = self.entry_points.len();
+        for offset in 1..count {
+            let name = self.entry_points[(self.active + offset) % count].clone();
+            match self.select_entry_point(device, &name) {
+                Ok(()) => return Some(self.entry_point()),
+                Err(err) => log::error!("{err}"),
+            }
+        }
+        None
+    }
+
+    /// Whether the drawing shader restricts `draw` to the tiles it accepts, see `src/shaders/tiles.wgsl`
+    pub fn classifies_tiles(&self) -> bool {
+        self.tiles.is_some()
+    }
+
+    /// Draw every tile regardless of what the shader's `classify` says, for measuring what it saves
+    pub fn set_tile_classification(&mut self, enabled: bool) {
+        self.classify_tiles = enabled;
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, globals: &GlobalsState) {
+        self.dispatch_timed(encoder, globals, None);
+    }
+
+    /// [`ComputeState::dispatch`], recording when the pass starts and ends
+    pub fn dispatch_timed(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        globals: &GlobalsState,
+        timestamp_writes: Option<ComputePassTimestampWrites>,
+    ) {
+        if let Some(volume) = &self.volume {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                timestamp_writes: None,
+                label: Some("Volume Pass"),
+            });
+            compute_pass.set_pipeline(&volume.pipeline);
+            compute_pass.set_bind_group(0, &volume.empty_bind_group, &[]);
+            compute_pass.set_bind_group(1, &self.globals_bind_group, &[]);
+            compute_pass.set_bind_group(2, &volume.bind_group, &[]);
+            globals.set_compute_push_constants(&mut compute_pass);
+            let workgroups = volume.size.div_ceil(VOLUME_WORKGROUP_SIZE);
+            compute_pass.dispatch_workgroups(workgroups, workgroups, workgroups);
+        }
+        if let Some(cubemap) = &self.cubemap {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                timestamp_writes: None,
+                label: Some("Cubemap Pass"),
+            });
+            compute_pass.set_pipeline(&cubemap.pipeline);
+            compute_pass.set_bind_group(0, &cubemap.empty_bind_group, &[]);
+            compute_pass.set_bind_group(1, &self.globals_bind_group, &[]);
+            compute_pass.set_bind_group(2, &cubemap.bind_group, &[]);
+            globals.set_compute_push_constants(&mut compute_pass);
+            let [workgroup_width, workgroup_height] = self.workgroup_size;
+            compute_pass.dispatch_workgroups(
+                cubemap.size.div_ceil(workgroup_width),
+                cubemap.size.div_ceil(workgroup_height),
+                6,
+            );
+            drop(compute_pass);
+            cubemap.store(encoder);
+        }
+        self.encode_pass(encoder, globals, timestamp_writes);
+        if let ComputeOutput::Packed(packed) = &self.output {
+            packed.store(encoder);
+        }
+        if let (Some(history), Some(output_texture)) = (&self.history, self.output_texture()) {
+            history.store(encoder, output_texture, globals.globals.frame);
+        }
+        if let Some(overlay) = &self.overlay {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                timestamp_writes: None,
+                label: Some("Overlay Pass"),
+            });
+            self.bind(
+                &mut compute_pass,
+                &overlay.pipeline,
+                Some(&overlay.bind_group),
+                globals,
+            );
+            let [workgroup_width, workgroup_height] = self.workgroup_size;
+            compute_pass.dispatch_workgroups(
+                self.width.div_ceil(workgroup_width),
+                self.height.div_ceil(workgroup_height),
+                1,
+            );
+        }
+    }
+
+    fn encode_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        globals: &GlobalsState,
+        timestamp_writes: Option<ComputePassTimestampWrites>,
+    ) {
+        // Variants selected with `next_entry_point` draw every tile
+        let tiles = self
+            .tiles
+            .as_deref()
+            .filter(|_| self.classify_tiles && self.active == 0);
+        if let Some(feedback) = &self.feedback {
+            encoder.clear_buffer(&feedback.buffer, 0, None);
+        }
+        if let Some(tiles) = tiles {
+            // Only the count, the other workgroup counts stay 1
+            encoder.clear_buffer(&tiles.tile_list, 0, Some(4));
+        }
+        if let Some(launches) = &self.launches {
+            encoder.clear_buffer(&launches.items, 0, Some(4));
+        }
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            timestamp_writes,
+            label: Some("Compute Pass"),
+        });
+        let [workgroup_width, workgroup_height] = self.workgroup_size;
+        let workgroups = [
+            self.width.div_ceil(workgroup_width),
+            self.height.div_ceil(workgroup_height),
+        ];
+
+        if let Some(predicate) = &self.predicate {
+            self.bind(
+                &mut compute_pass,
+                &predicate.pipeline,
+                Some(&predicate.bind_group),
+                globals,
+            );
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        if let Some(tiles) = tiles {
+            self.bind(
+                &mut compute_pass,
+                &tiles.classify_pipeline,
+                Some(&tiles.classify_bind_group),
+                globals,
+            );
+            compute_pass.dispatch_workgroups(workgroups[0], workgroups[1], 1);
+            self.bind(
+                &mut compute_pass,
+                &tiles.draw_pipeline,
+                Some(&tiles.draw_bind_group),
+                globals,
+            );
+            for _ in 0..self.steps_per_frame {
+                compute_pass.dispatch_workgroups_indirect(&tiles.tile_list, 0);
+            }
+            self.dispatch_launches(&mut compute_pass, globals);
+            return;
+        }
+
+        let pipeline = self.pipelines[self.active]
+            .as_ref()
+            .expect("selected pipelines are created");
+        self.bind(&mut compute_pass, pipeline, None, globals);
+        for _ in 0..self.steps_per_frame {
+            match &self.predicate {
+                // Zero workgroups when the predicate failed, the output keeps the previous image
+                Some(predicate) => compute_pass.dispatch_workgroups_indirect(&predicate.args, 0),
+                // Variants draw the whole image themselves
+                None if self.active != 0 => {
+                    compute_pass.dispatch_workgroups(workgroups[0], workgroups[1], 1)
+                }
+                None => {
+                    let [width, height] = globals.region().size;
+                    if width > 0 && height > 0 {
+                        compute_pass.dispatch_workgroups(
+                            width.div_ceil(workgroup_width),
+                            height.div_ceil(workgroup_height),
+                            1,
+                        );
+                    }
+                }
+            }
+        }
+        self.dispatch_launches(&mut compute_pass, globals);
+    }
+
+    /// Run `launch` for the items emitted while drawing, as many workgroups as the GPU counted
+    fn dispatch_launches<'a>(&'a self, compute_pass: &mut ComputePass<'a>, globals: &GlobalsState) {
+        let Some(launches) = &self.launches else {
+            return;
+        };
+        self.bind(
+            compute_pass,
+            &launches.args_pipeline,
+            Some(&launches.args_bind_group),
+            globals,
+        );
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        self.bind(compute_pass, &launches.pipeline, None, globals);
+        compute_pass.dispatch_workgroups_indirect(&launches.args, 0);
+    }
+
+    /// Set `pipeline` with the output image, the globals and optionally `extra` as group 2
+    fn bind<'a>(
+        &'a self,
+        compute_pass: &mut ComputePass<'a>,
+        pipeline: &'a ComputePipeline,
+        extra: Option<&'a BindGroup>,
+        globals: &GlobalsState,
+    ) {
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.globals_bind_group, &[]);
+        if let Some(extra) = extra {
+            compute_pass.set_bind_group(2, extra, &[]);
+        }
+        globals.set_compute_push_constants(compute_pass);
+    }
+}
+
+/// The drawing in 8 bits per channel, written where [`PACKED_FORMAT`] storage textures aren't reliable
+/// and copied into `texture` through `pixels` after every dispatch, see [`OutputStorage`]
+struct PackedOutput {
+    /// The `rgba8uint` image of [`OutputStorage::Uint`], `None` if the drawing writes `pixels` itself
+    storage: Option<(Texture, TextureView)>,
+    /// Rows padded to [`PACKED_ROW_ALIGNMENT`] pixels
+    pixels: Buffer,
+    texture: Texture,
+    view: TextureView,
+}
+
+impl PackedOutput {
+    fn new(device: &Device, storage: OutputStorage, width: u32, height: u32) -> Self {
+        let create_texture = |label, format, usage| {
+            device.create_texture(&TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let storage = (storage == OutputStorage::Uint).then(|| {
+            let texture = create_texture(
+                "Compute Uint Output Texture",
+                TextureFormat::Rgba8Uint,
+                TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC,
+            );
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        });
+        let pixels = device.create_buffer(&BufferDescriptor {
+            label: Some("Compute Output Pixels Buffer"),
+            size: u64::from(width.next_multiple_of(PACKED_ROW_ALIGNMENT)) * u64::from(height) * 4,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let texture = create_texture(
+            "Compute Output Texture",
+            PACKED_FORMAT,
+            TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::COPY_DST,
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Self {
+            storage,
+            pixels,
+            texture,
+            view,
+        }
+    }
+
+    /// Bound at `@group(0) @binding(0)` of the drawing
+    fn binding(&self) -> BindingResource<'_> {
+        match &self.storage {
+            Some((_, view)) => BindingResource::TextureView(view),
+            None => self.pixels.as_entire_binding(),
+        }
+    }
+
+    /// Copy the drawn pixels into `texture`, the bytes of `rgba8uint` and `pack4x8unorm` are those of `rgba8unorm`
+    fn store(&self, encoder: &mut CommandEncoder) {
+        let size = self.texture.size();
+        let pixels = ImageCopyBuffer {
+            buffer: &self.pixels,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width.next_multiple_of(PACKED_ROW_ALIGNMENT) * 4),
+                rows_per_image: None,
+            },
+        };
+        if let Some((storage, _)) = &self.storage {
+            encoder.copy_texture_to_buffer(storage.as_image_copy(), pixels, size);
+        }
+        encoder.copy_buffer_to_texture(pixels, self.texture.as_image_copy(), size);
+    }
+}
+
+/// The last frames of the drawing as layers of a texture array, read with `history` in the shader,
+/// see `src/shaders/history.wgsl`
+struct History {
+    texture: Texture,
+    view: TextureView,
+}
+
+impl History {
+    /// Frames are copied from the image, so they are kept in its `format`
+    fn new(device: &Device, format: TextureFormat, width: u32, height: u32, frames: u32) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("History Texture"),
             size: wgpu::Extent3d {
                 width,
                 height,
-                depth_or_array_layers: 1,
+                depth_or_array_layers: frames,
             },
             mip_level_count: 1,
             sample_count: 1,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8Unorm,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        // A single layer would be viewed as a plain 2D texture otherwise
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        Self { texture, view }
+    }
+
+    /// Copy the image drawn for `frame` into its layer, replacing the oldest frame
+    fn store(&self, encoder: &mut CommandEncoder, output_texture: &Texture, frame: u32) {
+        encoder.copy_texture_to_texture(
+            output_texture.as_image_copy(),
+            ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: frame % self.texture.depth_or_array_layers(),
+                },
+                aspect: TextureAspect::All,
+            },
+            output_texture.size(),
+        );
+    }
+}
+
+/// Edge length of the cubic workgroups of `fill_volume`
+const VOLUME_WORKGROUP_SIZE: u32 = 4;
+
+/// The voxels the `fill_volume` entry point appended for shaders defining `fn volume` writes,
+/// see `src/shaders/volume.wgsl`. It gets its own pass, after which the drawing samples it.
+/// Slices are layers of a texture array, the GL backend only writes the first slice of 3D storage textures
+struct Volume {
+    pipeline: ComputePipeline,
+    /// Takes the place of the drawing's bind group, which samples the texture being written
+    empty_bind_group: BindGroup,
+    bind_group: BindGroup,
+    view: TextureView,
+    sampler: Sampler,
+    size: u32,
+}
+
+impl Volume {
+    fn new(device: &Device, shaders: &Shaders, globals: &GlobalsState, size: u32) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Volume Texture"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                // The GL backend turns square textures with a multiple of 6 layers into cube maps,
+                // which can't be written as arrays
+                depth_or_array_layers: size + u32::from(size.is_multiple_of(6)),
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
             usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
-        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Volume Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
 
+        let (empty_layout, empty_bind_group) = empty_bind_group(device);
         let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some("Compute Bind Group Layout"),
+            label: Some("Volume Bind Group Layout"),
             entries: &[BindGroupLayoutEntry {
                 binding: 0,
                 visibility: ShaderStages::COMPUTE,
                 ty: BindingType::StorageTexture {
                     access: StorageTextureAccess::WriteOnly,
-                    format: TextureFormat::Rgba8Unorm,
+                    format: OUTPUT_FORMAT,
+                    view_dimension: TextureViewDimension::D2Array,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Volume Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&view),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Volume Pipeline Layout"),
+            bind_group_layouts: &[
+                &empty_layout,
+                &globals.bind_group_layout,
+                &bind_group_layout,
+            ],
+            push_constant_ranges: &globals.push_constant_ranges(ShaderStages::COMPUTE),
+        });
+        let pipeline = create_pipeline(
+            device,
+            &layout,
+            &shaders.drawing,
+            "fill_volume",
+            shaders.pipeline_cache.as_deref(),
+        );
+
+        Self {
+            pipeline,
+            empty_bind_group,
+            bind_group,
+            view,
+            sampler,
+            size,
+        }
+    }
+}
+
+/// The faces the `draw_cubemap` entry point appended for shaders defining `fn cubemap` draws,
+/// all six in one dispatch, see `src/shaders/cubemap.wgsl`.
+/// They are drawn side by side and copied into the layers of the cube texture the drawing samples,
+/// the GL backend can't write the layers of cube textures directly
+struct Cubemap {
+    pipeline: ComputePipeline,
+    /// Takes the place of the drawing's bind group, which samples the cube texture
+    empty_bind_group: BindGroup,
+    bind_group: BindGroup,
+    /// Three faces wide and two high
+    faces: Texture,
+    cube: Texture,
+    view: TextureView,
+    sampler: Sampler,
+    size: u32,
+}
+
+impl Cubemap {
+    fn new(device: &Device, shaders: &Shaders, globals: &GlobalsState, size: u32) -> Self {
+        let faces = device.create_texture(&TextureDescriptor {
+            label: Some("Cubemap Faces Texture"),
+            size: wgpu::Extent3d {
+                width: size * 3,
+                height: size * 2,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let cube = device.create_texture(&TextureDescriptor {
+            label: Some("Cubemap Texture"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = cube.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Cubemap Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let (empty_layout, empty_bind_group) = empty_bind_group(device);
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Cubemap Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::WriteOnly,
+                    format: OUTPUT_FORMAT,
                     view_dimension: TextureViewDimension::D2,
                 },
                 count: None,
             }],
         });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Cubemap Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(
+                    &faces.create_view(&TextureViewDescriptor::default()),
+                ),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Cubemap Pipeline Layout"),
+            bind_group_layouts: &[
+                &empty_layout,
+                &globals.bind_group_layout,
+                &bind_group_layout,
+            ],
+            push_constant_ranges: &globals.push_constant_ranges(ShaderStages::COMPUTE),
+        });
+        let pipeline = create_pipeline(
+            device,
+            &layout,
+            &shaders.drawing,
+            "draw_cubemap",
+            shaders.pipeline_cache.as_deref(),
+        );
 
+        Self {
+            pipeline,
+            empty_bind_group,
+            bind_group,
+            faces,
+            cube,
+            view,
+            sampler,
+            size,
+        }
+    }
+
+    /// Copy the faces drawn side by side into the layers of the cube texture
+    fn store(&self, encoder: &mut CommandEncoder) {
+        for face in 0..6 {
+            encoder.copy_texture_to_texture(
+                ImageCopyTexture {
+                    texture: &self.faces,
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: face % 3 * self.size,
+                        y: face / 3 * self.size,
+                        z: 0,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                ImageCopyTexture {
+                    texture: &self.cube,
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: face,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width: self.size,
+                    height: self.size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+}
+
+/// The `draw_overlay` entry point appended for shaders defining `fn overlay`, see `src/shaders/overlay.wgsl`.
+/// It writes its own texture, which only the render pass reads
+struct Overlay {
+    pipeline: ComputePipeline,
+    bind_group: BindGroup,
+    view: TextureView,
+    /// The [`Pointer`] uniform
+    pointer: Buffer,
+}
+
+impl Overlay {
+    fn new(
+        device: &Device,
+        shaders: &Shaders,
+        bind_group_layouts: [&BindGroupLayout; 2],
+        globals: &GlobalsState,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Overlay Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let pointer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Pointer Buffer"),
+            contents: bytemuck::bytes_of(&Pointer::default()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Overlay Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: OUTPUT_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
         let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Compute Bind Group"),
+            label: Some("Overlay Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: pointer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let [drawing_layout, globals_layout] = bind_group_layouts;
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[drawing_layout, globals_layout, &bind_group_layout],
+            push_constant_ranges: &globals.push_constant_ranges(ShaderStages::COMPUTE),
+        });
+        let pipeline = create_pipeline(
+            device,
+            &layout,
+            &shaders.drawing,
+            "draw_overlay",
+            shaders.pipeline_cache.as_deref(),
+        );
+
+        Self {
+            pipeline,
+            bind_group,
+            view,
+            pointer,
+        }
+    }
+}
+
+/// The `classify_tiles` and `draw_tiles` entry points appended for shaders defining `fn classify`,
+/// dispatched instead of `main`
+struct TileClassification {
+    classify_pipeline: ComputePipeline,
+    classify_bind_group: BindGroup,
+    draw_pipeline: ComputePipeline,
+    draw_bind_group: BindGroup,
+    /// Indirect arguments of `draw_tiles`, followed by the accepted tiles
+    tile_list: Buffer,
+}
+
+impl TileClassification {
+    fn new(
+        device: &Device,
+        shaders: &Shaders,
+        bind_group_layouts: [&BindGroupLayout; 2],
+        globals: &GlobalsState,
+        tile_count: u32,
+    ) -> Self {
+        let mut contents = vec![0u32; 3 + tile_count as usize];
+        contents[1..3].fill(1);
+        let tile_list = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Tile List Buffer"),
+            contents: bytemuck::cast_slice(&contents),
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+        });
+
+        // `classify_tiles` writes the list at binding 0, `draw_tiles` reads it at binding 1
+        let [drawing_layout, globals_layout] = bind_group_layouts;
+        let [
+            (classify_pipeline, classify_bind_group),
+            (draw_pipeline, draw_bind_group),
+        ] = [("classify_tiles", 0, false), ("draw_tiles", 1, true)].map(
+            |(entry_point, binding, read_only)| {
+                let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("Tile List Bind Group Layout"),
+                    entries: &[BindGroupLayoutEntry {
+                        binding,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+                let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("Tile List Bind Group"),
+                    layout: &layout,
+                    entries: &[BindGroupEntry {
+                        binding,
+                        resource: tile_list.as_entire_binding(),
+                    }],
+                });
+                let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Tile Classification Pipeline Layout"),
+                    bind_group_layouts: &[drawing_layout, globals_layout, &layout],
+                    push_constant_ranges: &globals.push_constant_ranges(ShaderStages::COMPUTE),
+                });
+                let pipeline = create_pipeline(
+                    device,
+                    &pipeline_layout,
+                    &shaders.drawing,
+                    entry_point,
+                    shaders.pipeline_cache.as_deref(),
+                );
+                (pipeline, bind_group)
+            },
+        );
+
+        Self {
+            classify_pipeline,
+            classify_bind_group,
+            draw_pipeline,
+            draw_bind_group,
+            tile_list,
+        }
+    }
+}
+
+/// The `write_dispatch_args` entry point appended for shaders defining `fn predicate() -> bool`,
+/// and the indirect arguments of the drawing dispatch it writes
+struct Predicate {
+    pipeline: ComputePipeline,
+    bind_group: BindGroup,
+    args: Buffer,
+}
+
+impl Predicate {
+    fn new(
+        device: &Device,
+        shaders: &Shaders,
+        bind_group_layouts: [&BindGroupLayout; 2],
+        globals: &GlobalsState,
+    ) -> Self {
+        let args = device.create_buffer(&BufferDescriptor {
+            label: Some("Dispatch Args Buffer"),
+            size: std::mem::size_of::<[u32; 3]>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Predicate Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Predicate Bind Group"),
             layout: &bind_group_layout,
             entries: &[BindGroupEntry {
                 binding: 0,
-                resource: BindingResource::TextureView(&output_view),
+                resource: args.as_entire_binding(),
             }],
         });
 
-        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
-            compilation_options: Default::default(),
-            label: Some("Compute Pipeline"),
-            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
-                label: Some("Compute Pipeline Layout"),
-                bind_group_layouts: &[&bind_group_layout],
-                push_constant_ranges: &[],
-            })),
-            module: &shaders.compute,
-            entry_point: "main",
+        let [drawing_layout, globals_layout] = bind_group_layouts;
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Predicate Pipeline Layout"),
+            bind_group_layouts: &[drawing_layout, globals_layout, &bind_group_layout],
+            push_constant_ranges: &globals.push_constant_ranges(ShaderStages::COMPUTE),
         });
+        let pipeline = create_pipeline(
+            device,
+            &layout,
+            &shaders.drawing,
+            "write_dispatch_args",
+            shaders.pipeline_cache.as_deref(),
+        );
 
         Self {
             pipeline,
             bind_group,
-            output_view,
+            args,
         }
     }
+}
+
+/// The items the drawing shader emitted for `fn launch(item: u32)`, the `write_launch_args` entry point
+/// turning their count into the indirect arguments of the `launch_items` dispatch, and those arguments
+struct Launches {
+    /// Bound after `feedback`
+    items: Buffer,
+    args_pipeline: ComputePipeline,
+    args_bind_group: BindGroup,
+    args: Buffer,
+    pipeline: ComputePipeline,
+}
+
+impl Launches {
+    fn create_items_buffer(device: &Device) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Launched Items Buffer"),
+            size: (u64::from(MAX_LAUNCHES) + 1) * 4,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
 
-    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, width: u32, height: u32) {
-        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-            timestamp_writes: None,
-            label: Some("Compute Pass"),
+    fn new(
+        device: &Device,
+        shaders: &Shaders,
+        pipeline_layout: &PipelineLayout,
+        bind_group_layouts: [&BindGroupLayout; 2],
+        globals: &GlobalsState,
+        items: Buffer,
+    ) -> Self {
+        let args = device.create_buffer(&BufferDescriptor {
+            label: Some("Launch Args Buffer"),
+            size: std::mem::size_of::<[u32; 3]>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Launch Args Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let args_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Launch Args Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: args.as_entire_binding(),
+            }],
         });
 
-        compute_pass.set_pipeline(&self.pipeline);
-        compute_pass.set_bind_group(0, &self.bind_group, &[]);
-        compute_pass.dispatch_workgroups(width / 8, height / 8, 1);
+        let [drawing_layout, globals_layout] = bind_group_layouts;
+        let args_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Launch Args Pipeline Layout"),
+            bind_group_layouts: &[drawing_layout, globals_layout, &bind_group_layout],
+            push_constant_ranges: &globals.push_constant_ranges(ShaderStages::COMPUTE),
+        });
+
+        Self {
+            items,
+            args_pipeline: create_pipeline(
+                device,
+                &args_layout,
+                &shaders.drawing,
+                "write_launch_args",
+                shaders.pipeline_cache.as_deref(),
+            ),
+            args_bind_group,
+            args,
+            pipeline: create_pipeline(
+                device,
+                pipeline_layout,
+                &shaders.drawing,
+                "launch_items",
+                shaders.pipeline_cache.as_deref(),
+            ),
+        }
+    }
+}
+
+/// Builds a [`ComputeState`] with user data bound next to the output image
+pub struct ComputeStateBuilder<'a> {
+    device: &'a Device,
+    shaders: &'a Shaders,
+    globals: &'a GlobalsState,
+    width: u32,
+    height: u32,
+    storage_buffers: Vec<(&'a Buffer, bool)>,
+    entry_point: Option<&'a str>,
+    output_buffer: Option<u64>,
+    steps_per_frame: u32,
+}
+
+impl<'a> ComputeStateBuilder<'a> {
+    /// Bind `buffer` as `var<storage, read>`, or `var<storage, read_write>` unless `read_only`.
+    /// Buffers are bound in the order they're added, starting at `@group(0) @binding(1)`.
+    /// The bind group keeps them alive, keep a reference to update them with `Queue::write_buffer`
+    pub fn storage_buffer(mut self, buffer: &'a Buffer, read_only: bool) -> Self {
+        self.storage_buffers.push((buffer, read_only));
+        self
+    }
+
+    /// Dispatch this entry point of the drawing shader instead of `main`
+    pub fn entry_point(mut self, name: &'a str) -> Self {
+        self.entry_point = Some(name);
+        self
     }
+
+    /// Bind a zeroed `var<storage, read_write>` buffer of `size` bytes at binding 0 instead of the output image,
+    /// for kernels built with [`Shaders::kernel`] whose results aren't an image.
+    /// The dispatch still covers `width` × `height` invocations, read the results with [`ComputeState::run_to_vec`]
+    pub fn output_buffer(mut self, size: u64) -> Self {
+        self.output_buffer = Some(size);
+        self
+    }
+
+    /// Dispatch the drawing shader `steps` times per frame, for simulations advancing their `scratch` state.
+    /// Every step sees the same globals and runs in the frame's compute pass
+    pub fn steps_per_frame(mut self, steps: u32) -> Self {
+        self.steps_per_frame = steps;
+        self
+    }
+
+    pub fn build(self) -> ComputeState {
+        let Self {
+            device,
+            shaders,
+            globals,
+            width,
+            height,
+            storage_buffers,
+            entry_point,
+            output_buffer,
+            steps_per_frame,
+        } = self;
+
+        let output = match output_buffer {
+            Some(size) => ComputeOutput::Buffer(device.create_buffer(&BufferDescriptor {
+                label: Some("Compute Output Buffer"),
+                size,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })),
+            None if shaders.output_storage != OutputStorage::Float => {
+                ComputeOutput::Packed(Box::new(PackedOutput::new(
+                    device,
+                    shaders.output_storage,
+                    width,
+                    height,
+                )))
+            }
+            None => {
+                let texture = device.create_texture(&TextureDescriptor {
+                    label: Some("Compute Output Texture"),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: OUTPUT_FORMAT,
+                    usage: TextureUsages::STORAGE_BINDING
+                        | TextureUsages::TEXTURE_BINDING
+                        | TextureUsages::COPY_SRC,
+                    view_formats: &[],
+                });
+                let view = texture.create_view(&TextureViewDescriptor::default());
+                ComputeOutput::Texture(texture, view)
+            }
+        };
+
+        let scratch = shaders.scratch_size.map(|size| {
+            Box::new(device.create_buffer(&BufferDescriptor {
+                label: Some("Scratch Buffer"),
+                size,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }))
+        });
+        let feedback = shaders
+            .feedback
+            .then(|| Box::new(FeedbackBuffer::new(device)));
+        let launched_items = shaders
+            .launch
+            .then(|| Launches::create_items_buffer(device));
+        let mut storage_buffers = storage_buffers;
+        storage_buffers.extend(scratch.as_deref().map(|buffer| (buffer, false)));
+        storage_buffers.extend(
+            feedback
+                .as_deref()
+                .map(|feedback| (&feedback.buffer, false)),
+        );
+        storage_buffers.extend(launched_items.as_ref().map(|buffer| (buffer, false)));
+
+        let output_ty = match &output {
+            ComputeOutput::Texture(..) => BindingType::StorageTexture {
+                access: StorageTextureAccess::WriteOnly,
+                format: OUTPUT_FORMAT,
+                view_dimension: TextureViewDimension::D2,
+            },
+            ComputeOutput::Packed(packed) if packed.storage.is_some() => {
+                BindingType::StorageTexture {
+                    access: StorageTextureAccess::WriteOnly,
+                    format: TextureFormat::Rgba8Uint,
+                    view_dimension: TextureViewDimension::D2,
+                }
+            }
+            ComputeOutput::Packed(_) | ComputeOutput::Buffer(_) => BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+        };
+        // Kernels writing a buffer have no frames to keep
+        let history = shaders
+            .history
+            .filter(|_| !matches!(output, ComputeOutput::Buffer(_)))
+            .map(|frames| {
+                Box::new(History::new(
+                    device,
+                    shaders.output_storage.format(),
+                    width,
+                    height,
+                    frames,
+                ))
+            });
+        let history_binding = storage_buffers.len() as u32 + 1;
+        let volume = shaders
+            .volume
+            .filter(|_| !matches!(output, ComputeOutput::Buffer(_)))
+            .map(|size| Box::new(Volume::new(device, shaders, globals, size)));
+        let volume_binding = history_binding + u32::from(history.is_some());
+        let cubemap = shaders
+            .cubemap
+            .filter(|_| !matches!(output, ComputeOutput::Buffer(_)))
+            .map(|size| Box::new(Cubemap::new(device, shaders, globals, size)));
+        let cubemap_binding = volume_binding + 2 * u32::from(volume.is_some());
+
+        let mut layout_entries = vec![BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::COMPUTE,
+            ty: output_ty,
+            count: None,
+        }];
+        layout_entries.extend(
+            storage_buffers
+                .iter()
+                .zip(1..)
+                .map(|(&(_, read_only), binding)| BindGroupLayoutEntry {
+                    binding,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }),
+        );
+        layout_entries.extend(history.as_ref().map(|_| BindGroupLayoutEntry {
+            binding: history_binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension: TextureViewDimension::D2Array,
+                multisampled: false,
+            },
+            count: None,
+        }));
+        if volume.is_some() {
+            layout_entries.extend([
+                BindGroupLayoutEntry {
+                    binding: volume_binding,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: volume_binding + 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ]);
+        }
+        if cubemap.is_some() {
+            layout_entries.extend([
+                BindGroupLayoutEntry {
+                    binding: cubemap_binding,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: cubemap_binding + 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ]);
+        }
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Compute Bind Group Layout"),
+            entries: &layout_entries,
+        });
+
+        let mut entries = vec![BindGroupEntry {
+            binding: 0,
+            resource: match &output {
+                ComputeOutput::Texture(_, view) => BindingResource::TextureView(view),
+                ComputeOutput::Packed(packed) => packed.binding(),
+                ComputeOutput::Buffer(buffer) => buffer.as_entire_binding(),
+            },
+        }];
+        entries.extend(
+            storage_buffers
+                .iter()
+                .zip(1..)
+                .map(|(&(buffer, _), binding)| BindGroupEntry {
+                    binding,
+                    resource: buffer.as_entire_binding(),
+                }),
+        );
+        entries.extend(history.as_ref().map(|history| BindGroupEntry {
+            binding: history_binding,
+            resource: BindingResource::TextureView(&history.view),
+        }));
+        if let Some(volume) = &volume {
+            entries.extend([
+                BindGroupEntry {
+                    binding: volume_binding,
+                    resource: BindingResource::TextureView(&volume.view),
+                },
+                BindGroupEntry {
+                    binding: volume_binding + 1,
+                    resource: BindingResource::Sampler(&volume.sampler),
+                },
+            ]);
+        }
+        if let Some(cubemap) = &cubemap {
+            entries.extend([
+                BindGroupEntry {
+                    binding: cubemap_binding,
+                    resource: BindingResource::TextureView(&cubemap.view),
+                },
+                BindGroupEntry {
+                    binding: cubemap_binding + 1,
+                    resource: BindingResource::Sampler(&cubemap.sampler),
+                },
+            ]);
+        }
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Compute Bind Group"),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Compute Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, &globals.bind_group_layout],
+            push_constant_ranges: &globals.push_constant_ranges(ShaderStages::COMPUTE),
+        });
+        let pipeline = create_pipeline(
+            device,
+            &pipeline_layout,
+            &shaders.drawing,
+            &shaders.entry_points[0],
+            shaders.pipeline_cache.as_deref(),
+        );
+        let predicate = shaders.predicate.then(|| {
+            Box::new(Predicate::new(
+                device,
+                shaders,
+                [&bind_group_layout, &globals.bind_group_layout],
+                globals,
+            ))
+        });
+        let launches = launched_items.map(|items| {
+            Box::new(Launches::new(
+                device,
+                shaders,
+                &pipeline_layout,
+                [&bind_group_layout, &globals.bind_group_layout],
+                globals,
+                items,
+            ))
+        });
+        let [workgroup_width, workgroup_height] = shaders.workgroup_size;
+        let tile_count = width.div_ceil(workgroup_width) * height.div_ceil(workgroup_height);
+        let max_workgroups = device.limits().max_compute_workgroups_per_dimension;
+        let tiles = match shaders.tile_classification {
+            true if tile_count > max_workgroups => {
+                log::warn!(
+                    "{tile_count} tiles exceed the {max_workgroups} workgroups of one dispatch, drawing all of them"
+                );
+                None
+            }
+            true => Some(Box::new(TileClassification::new(
+                device,
+                shaders,
+                [&bind_group_layout, &globals.bind_group_layout],
+                globals,
+                tile_count,
+            ))),
+            false => None,
+        };
+        let overlay = shaders.overlay.then(|| {
+            Box::new(Overlay::new(
+                device,
+                shaders,
+                [&bind_group_layout, &globals.bind_group_layout],
+                globals,
+                width,
+                height,
+            ))
+        });
+        let mut pipelines: Vec<_> = shaders.entry_points.iter().map(|_| None).collect();
+        pipelines[0] = Some(pipeline);
+
+        let mut compute_state = ComputeState {
+            pipelines,
+            entry_points: shaders.entry_points.clone(),
+            active: 0,
+            module: shaders.drawing.clone(),
+            pipeline_cache: shaders.pipeline_cache.clone(),
+            pipeline_layout,
+            bind_group,
+            globals_bind_group: globals.create_bind_group(device),
+            output,
+            predicate,
+            tiles,
+            classify_tiles: true,
+            scratch,
+            feedback,
+            launches,
+            history,
+            overlay,
+            volume,
+            cubemap,
+            steps_per_frame,
+            workgroup_size: shaders.workgroup_size,
+            width,
+            height,
+        };
+        if let Some(name) = entry_point
+            && let Err(err) = compute_state.select_entry_point(device, name)
+        {
+            log::warn!("{err}, dispatching {}", compute_state.entry_point());
+        }
+        compute_state
+    }
+}
+
+/// For passes that don't bind the drawing's resources
+fn empty_bind_group(device: &Device) -> (BindGroupLayout, BindGroup) {
+    let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Empty Bind Group Layout"),
+        entries: &[],
+    });
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Empty Bind Group"),
+        layout: &layout,
+        entries: &[],
+    });
+    (layout, bind_group)
+}
+
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    module: &ShaderModule,
+    entry_point: &str,
+    cache: Option<&PipelineCache>,
+) -> ComputePipeline {
+    device.create_compute_pipeline(&ComputePipelineDescriptor {
+        compilation_options: Default::default(),
+        label: Some("Compute Pipeline"),
+        layout: Some(layout),
+        module,
+        entry_point,
+        cache,
+    })
 }
@@ -0,0 +1,43 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    attract::AttractSettings, convolution::Convolution, crop::Crop, feedback::Trigger,
+    gamepad::GamepadBindings, output_window::WindowConfig, render::Quad,
+};
+
+/// Settings for installations, read from the TOML file passed with `--config`
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Enables the attract mode when present
+    pub attract: Option<AttractSettings>,
+    /// Named regions of the image to export, see [`CropControls`](crate::crop::CropControls)
+    #[serde(default)]
+    pub crops: BTreeMap<String, Crop>,
+    /// Actions taken on what a compute drawing reports through its `feedback` buffer
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    /// Nodes of the scene graph and images drawn over the image, see [`Quad`]
+    #[serde(default)]
+    pub quads: Vec<Quad>,
+    /// More windows showing the image, see [`OutputWindow`](crate::output_window::OutputWindow)
+    #[serde(default)]
+    pub windows: Vec<WindowConfig>,
+    /// Convolves the image when `--convolve` isn't given
+    pub convolution: Option<Convolution>,
+    /// Actions of the buttons of `--gamepad`s, instead of the [default ones](crate::gamepad::default_bindings)
+    pub gamepad: Option<GamepadBindings>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let config_file: Self = toml::from_str(&contents).map_err(|err| err.to_string())?;
+        if let Some(attract) = &config_file.attract {
+            attract.validate()?;
+        }
+        Ok(config_file)
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use crate::{cli::Options, headless::Headless, recording};
+
+/// Pixels between the cells and around the sheet
+const GAP: u32 = 8;
+/// Size of a pixel of the label font
+const FONT_SCALE: u32 = 2;
+const LABEL_HEIGHT: u32 = (GLYPH_HEIGHT + 2) * FONT_SCALE;
+const BACKGROUND: [u8; 4] = [24, 24, 24, 255];
+const TEXT: [u8; 4] = [220, 220, 220, 255];
+
+/// Grid of cells and the ranges of `globals.params` swept across it
+pub struct Sweep {
+    pub columns: u32,
+    pub rows: u32,
+    /// `params.x` of the first and last column
+    pub x: [f32; 2],
+    /// `params.y` of the first and last row, `None` to keep it at its initial value
+    pub y: Option<[f32; 2]>,
+    /// Size of a cell in pixels
+    pub cell: [u32; 2],
+    /// `globals.time` in seconds
+    pub time: f32,
+}
+
+impl Sweep {
+    /// Parameters drawn in a cell
+    fn params(&self, column: u32, row: u32, initial: [f32; 2]) -> [f32; 2] {
+        let lerp = |[from, to]: [f32; 2], index: u32, count: u32| {
+            from + (to - from) * index as f32 / (count - 1).max(1) as f32
+        };
+        [
+            lerp(self.x, column, self.columns),
+            self.y.map_or(initial[1], |y| lerp(y, row, self.rows)),
+        ]
+    }
+}
+
+/// Draw the `--shader` or `--example` once per cell of `sweep`
+/// and write the labeled images side by side to `output`
+pub fn run(options: &Options, sweep: &Sweep, output: &Path) -> Result<(), String> {
+    let [width, height] = sweep.cell;
+    let mut headless = Headless::new(options, width, height)?;
+    let initial = headless.initial_params;
+
+    let mut sheet = Sheet::new(sweep);
+    for row in 0..sweep.rows {
+        for column in 0..sweep.columns {
+            let params = sweep.params(column, row, initial);
+            headless.globals.globals.params = params;
+            headless.globals.globals.frame = 1;
+            headless.globals.globals.time = sweep.time;
+
+            let label = match sweep.y {
+                Some(_) => format!("{:.3}, {:.3}", params[0], params[1]),
+                None => format!("{:.3}", params[0]),
+            };
+            sheet.add(column, row, &headless.draw()?, &label);
+        }
+    }
+
+    recording::write_png(output, sheet.width, sheet.height, &sheet.pixels)
+        .map_err(|err| format!("Failed to write {}: {err}", output.display()))
+}
+
+/// The composited image, 8-bit RGBA
+struct Sheet {
+    width: u32,
+    height: u32,
+    cell: [u32; 2],
+    pixels: Vec<u8>,
+}
+
+impl Sheet {
+    fn new(sweep: &Sweep) -> Self {
+        let [cell_width, cell_height] = sweep.cell;
+        let width = sweep.columns * (cell_width + GAP) + GAP;
+        let height = sweep.rows * (cell_height + LABEL_HEIGHT + GAP) + GAP;
+        Self {
+            width,
+            height,
+            cell: sweep.cell,
+            pixels: BACKGROUND.repeat((width * height) as usize),
+        }
+    }
+
+    /// Copy a cell in and write its label below it
+    fn add(&mut self, column: u32, row: u32, image: &[u8], label: &str) {
+        let [cell_width, cell_height] = self.cell;
+        let left = GAP + column * (cell_width + GAP);
+        let top = GAP + row * (cell_height + LABEL_HEIGHT + GAP);
+
+        let row_size = (cell_width * 4) as usize;
+        for (y, source) in image.chunks_exact(row_size).enumerate() {
+            let start = (((top + y as u32) * self.width + left) * 4) as usize;
+            self.pixels[start..start + row_size].copy_from_slice(source);
+        }
+
+        let mut x = left;
+        let y = top + cell_height + FONT_SCALE;
+        for character in label.chars() {
+            if x + GLYPH_WIDTH * FONT_SCALE > left + cell_width {
+                break;
+            }
+            self.glyph(character, x, y);
+            x += (GLYPH_WIDTH + 1) * FONT_SCALE;
+        }
+    }
+
+    fn glyph(&mut self, character: char, left: u32, top: u32) {
+        let bits = glyph(character);
+        for y in 0..GLYPH_HEIGHT * FONT_SCALE {
+            for x in 0..GLYPH_WIDTH * FONT_SCALE {
+                let bit = (y / FONT_SCALE) * GLYPH_WIDTH + x / FONT_SCALE;
+                if bits & (1 << (GLYPH_WIDTH * GLYPH_HEIGHT - 1 - bit)) != 0 {
+                    let start = (((top + y) * self.width + left + x) * 4) as usize;
+                    self.pixels[start..start + 4].copy_from_slice(&TEXT);
+                }
+            }
+        }
+    }
+}
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// 3×5 pixel glyphs for the characters of numbers, rows from top to bottom
+fn glyph(character: char) -> u16 {
+    match character {
+        '0' => 0b111_101_101_101_111,
+        '1' => 0b010_110_010_010_111,
+        '2' => 0b111_001_111_100_111,
+        '3' => 0b111_001_111_001_111,
+        '4' => 0b101_101_111_001_001,
+        '5' => 0b111_100_111_001_111,
+        '6' => 0b111_100_111_101_111,
+        '7' => 0b111_001_010_010_010,
+        '8' => 0b111_101_111_101_111,
+        '9' => 0b111_101_111_001_111,
+        '.' => 0b000_000_000_000_010,
+        ',' => 0b000_000_000_010_100,
+        '-' => 0b000_000_111_000_000,
+        _ => 0,
+    }
+}
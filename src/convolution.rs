@@ -0,0 +1,377 @@
+use std::{path::PathBuf, str::FromStr};
+
+use serde::Deserialize;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{compute::OUTPUT_FORMAT, shaders::Shaders};
+
+/// Longest side of a kernel, which weights this many squared pixels per pixel unless it is separable
+pub const MAX_KERNEL_SIZE: u32 = 31;
+
+/// How far apart the weights may be from the product of a row and a column for a kernel to be separable,
+/// relative to the largest weight
+const SEPARABLE_TOLERANCE: f32 = 1e-5;
+
+/// Kernels named instead of listing weights
+const PRESETS: [(&str, &[f32]); 4] = [
+    // Binomial 5 × 5, separable
+    (
+        "blur",
+        &[
+            1.0, 4.0, 6.0, 4.0, 1.0, 4.0, 16.0, 24.0, 16.0, 4.0, 6.0, 24.0, 36.0, 24.0, 6.0, 4.0,
+            16.0, 24.0, 16.0, 4.0, 1.0, 4.0, 6.0, 4.0, 1.0,
+        ],
+    ),
+    (
+        "sharpen",
+        &[0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0],
+    ),
+    (
+        "edges",
+        &[-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0],
+    ),
+    ("emboss", &[-2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0]),
+];
+
+/// Square weights of a convolution, row by row with an odd number of rows.
+///
+/// Parsed from the name of a preset, `blur`, `sharpen`, `edges` or `emboss`,
+/// or from comma-separated weights, which are used as they are.
+/// The weights of `blur` are divided by their sum so it keeps the brightness
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Kernel {
+    size: u32,
+    weights: Vec<f32>,
+}
+
+impl Kernel {
+    /// `weights` has to hold an odd square number of them, at most [`MAX_KERNEL_SIZE`] squared
+    pub fn new(weights: Vec<f32>) -> Result<Self, String> {
+        let size = (weights.len() as f64).sqrt().round() as u32;
+        if (size * size) as usize != weights.len() || size.is_multiple_of(2) {
+            return Err(format!(
+                "a kernel needs an odd square number of weights like 9 or 25, got {}",
+                weights.len()
+            ));
+        }
+        if size > MAX_KERNEL_SIZE {
+            return Err(format!(
+                "kernels are at most {MAX_KERNEL_SIZE}x{MAX_KERNEL_SIZE}, got {size}x{size}"
+            ));
+        }
+        Ok(Self { size, weights })
+    }
+
+    /// Number of rows and columns
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// The column and the row whose product is the kernel, `None` unless it is separable into them
+    pub fn factors(&self) -> Option<(Vec<f32>, Vec<f32>)> {
+        let size = self.size as usize;
+        let (largest, &pivot) = self
+            .weights
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))?;
+        if size == 1 || pivot == 0.0 {
+            return None;
+        }
+        let (pivot_row, pivot_column) = (largest / size, largest % size);
+        let column: Vec<_> = (0..size)
+            .map(|row| self.weights[row * size + pivot_column])
+            .collect();
+        let row: Vec<_> = self.weights[pivot_row * size..][..size]
+            .iter()
+            .map(|weight| weight / pivot)
+            .collect();
+        let tolerance = pivot.abs() * SEPARABLE_TOLERANCE;
+        let separable = self.weights.iter().enumerate().all(|(index, weight)| {
+            (column[index / size] * row[index % size] - weight).abs() <= tolerance
+        });
+        separable.then_some((column, row))
+    }
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, String> {
+        let arg = arg.trim();
+        if let Some((_, weights)) = PRESETS.iter().find(|(name, _)| *name == arg) {
+            let mut kernel = Self::new(weights.to_vec())?;
+            if arg == "blur" {
+                let sum: f32 = kernel.weights.iter().sum();
+                kernel.weights.iter_mut().for_each(|weight| *weight /= sum);
+            }
+            return Ok(kernel);
+        }
+        let weights = arg
+            .split(',')
+            .map(|weight| {
+                weight.trim().parse::<f32>().map_err(|_| {
+                    format!(
+                        "{weight:?} is neither a weight nor one of blur, sharpen, edges and emboss"
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Self::new(weights)
+    }
+}
+
+impl TryFrom<String> for Kernel {
+    type Error = String;
+
+    fn try_from(arg: String) -> Result<Self, String> {
+        arg.parse()
+    }
+}
+
+/// A convolution of the image, from `--convolve` or the `[convolution]` table of the config file
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Convolution {
+    pub kernel: Kernel,
+    /// PNG convolved instead of the drawing, stretched to the size of the image
+    pub input: Option<PathBuf>,
+}
+
+/// Uniforms of `convolution.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ConvolutionParams {
+    size: u32,
+    _padding: [u32; 3],
+}
+
+/// One dispatch of the convolution
+struct Pass {
+    pipeline: ComputePipeline,
+    bind_group: BindGroup,
+}
+
+/// Blur, sharpening or edge detection of the image with a [`Kernel`], by a compute pass after bloom.
+///
+/// Separable kernels run as a pass over the rows into an intermediate texture and one over its columns,
+/// weighting 2N rather than N² pixels per pixel for an N × N kernel
+pub struct ConvolutionState {
+    passes: Vec<Pass>,
+    pub output_texture: Texture,
+    pub output_view: TextureView,
+    width: u32,
+    height: u32,
+    workgroup_size: [u32; 2],
+}
+
+impl ConvolutionState {
+    /// `width` × `height` is the size of the convolved image, `source_view` is stretched to it
+    pub fn new(
+        device: &Device,
+        shaders: &Shaders,
+        source_view: &TextureView,
+        width: u32,
+        height: u32,
+        kernel: &Kernel,
+    ) -> Self {
+        let module = shaders
+            .convolution
+            .as_ref()
+            .expect("Convolution shader was not created");
+
+        let create_texture = |label| {
+            device.create_texture(&TextureDescriptor {
+                label: Some(label),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: OUTPUT_FORMAT,
+                usage: TextureUsages::STORAGE_BINDING
+                    | TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        };
+        let output_texture = create_texture("Convolution Output Texture");
+        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Convolution Params Buffer"),
+            contents: bytemuck::bytes_of(&ConvolutionParams {
+                size: kernel.size,
+                _padding: [0; 3],
+            }),
+            usage: BufferUsages::UNIFORM,
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Convolution Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Convolution Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: OUTPUT_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Convolution Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pass = |entry_point, weights: &[f32], input: &TextureView, output: &TextureView| {
+            let weights_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Convolution Weights Buffer"),
+                contents: bytemuck::cast_slice(weights),
+                usage: BufferUsages::STORAGE,
+            });
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Convolution Bind Group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(input),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(output),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: weights_buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+            let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+                compilation_options: Default::default(),
+                label: Some("Convolution Pipeline"),
+                layout: Some(&pipeline_layout),
+                module,
+                entry_point,
+                cache: shaders.pipeline_cache.as_deref(),
+            });
+            Pass {
+                pipeline,
+                bind_group,
+            }
+        };
+
+        let size = kernel.size();
+        let passes = match kernel.factors() {
+            Some((column, row)) => {
+                log::debug!("Convolving with a separable {size}x{size} kernel");
+                let intermediate = create_texture("Convolution Intermediate Texture")
+                    .create_view(&TextureViewDescriptor::default());
+                vec![
+                    pass("convolve_rows", &row, source_view, &intermediate),
+                    pass("convolve_columns", &column, &intermediate, &output_view),
+                ]
+            }
+            None => {
+                log::debug!("Convolving with a {size}x{size} kernel");
+                vec![pass(
+                    "convolve",
+                    kernel.weights(),
+                    source_view,
+                    &output_view,
+                )]
+            }
+        };
+
+        Self {
+            passes,
+            output_texture,
+            output_view,
+            width,
+            height,
+            workgroup_size: shaders.workgroup_size,
+        }
+    }
+
+    pub fn dispatch(&self, encoder: &mut CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Convolution Pass"),
+        });
+
+        let [workgroup_width, workgroup_height] = self.workgroup_size;
+        for pass in &self.passes {
+            compute_pass.set_pipeline(&pass.pipeline);
+            compute_pass.set_bind_group(0, &pass.bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                self.width.div_ceil(workgroup_width),
+                self.height.div_ceil(workgroup_height),
+                1,
+            );
+        }
+    }
+}
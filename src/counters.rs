@@ -0,0 +1,46 @@
+use wgpu::*;
+
+/// Counters, append lists and free lists in `scratch`, included by shaders with `#include <counters.wgsl>`
+pub const WGSL: &str = include_str!("./shaders/counters.wgsl");
+
+/// Read `buffer` back as `T`s, waiting for the GPU. The buffer needs [`BufferUsages::COPY_SRC`]
+pub fn read_buffer<T: bytemuck::Pod>(device: &Device, queue: &Queue, buffer: &Buffer) -> Vec<T> {
+    let staging = device.create_buffer(&BufferDescriptor {
+        label: Some("Buffer Readback Buffer"),
+        size: buffer.size(),
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Buffer Readback Encoder"),
+    });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+    queue.submit(Some(encoder.finish()));
+
+    let slice = staging.slice(..);
+    slice.map_async(MapMode::Read, |_| {});
+    device.poll(Maintain::Wait);
+    // Copies out of the mapping, which needn't be aligned for `T`
+    let data = slice.get_mapped_range();
+    data.chunks_exact(std::mem::size_of::<T>())
+        .map(bytemuck::pod_read_unaligned)
+        .collect()
+}
+
+/// The counter at `counter` of a scratch buffer read back with
+/// [`Headless::read_scratch`](crate::Headless::read_scratch), 0 if it is out of range
+pub fn counter(scratch: &[u32], counter: u32) -> u32 {
+    scratch.get(counter as usize).copied().unwrap_or(0)
+}
+
+/// Items of the append list at `list` holding up to `capacity` of them, in the order they were appended
+pub fn list_items(scratch: &[u32], list: u32, capacity: u32) -> &[u32] {
+    let length = counter(scratch, list).min(capacity) as usize;
+    let start = list as usize + 1;
+    scratch.get(start..start + length).unwrap_or(&[])
+}
+
+/// Number of slots of the free list at `list` that are allocated and not released
+pub fn slots_in_use(scratch: &[u32], list: u32) -> u32 {
+    counter(scratch, list + 1).saturating_sub(counter(scratch, list))
+}
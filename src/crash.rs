@@ -0,0 +1,122 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::gpu::GpuState;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::logger;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{panic, path::Path};
+
+/// Directory crash reports are written to
+static REPORT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+static CONTEXT: Mutex<Context> = Mutex::new(Context {
+    device: None,
+    scene: None,
+});
+
+/// What was running when things went wrong, filled in as the app starts up
+struct Context {
+    device: Option<String>,
+    scene: Option<String>,
+}
+
+/// Write a crash report on every panic, next to the log file if there is one
+#[cfg(not(target_arch = "wasm32"))]
+pub fn install(log_file: Option<&Path>) {
+    let report_dir = log_file
+        .and_then(Path::parent)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map_or_else(std::env::temp_dir, Path::to_path_buf);
+    REPORT_DIR.get_or_init(|| report_dir);
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        write_report(&info.to_string());
+    }));
+}
+
+pub fn set_device(gpu_state: &GpuState) {
+    let mut description = String::new();
+    let _ = writeln!(description, "adapter: {:#?}", gpu_state.adapter.get_info());
+    let _ = writeln!(description, "draw mode: {:?}", gpu_state.draw_mode);
+    let _ = writeln!(
+        description,
+        "surface format: {:?}",
+        gpu_state.surface_format
+    );
+    let _ = writeln!(description, "limits: {:#?}", gpu_state.device.limits());
+
+    CONTEXT.lock().unwrap().device = Some(description);
+}
+
+/// Record the passes of the scene and which shader it was built from
+pub fn set_scene(passes: &[&str], shader: &str, shader_src: &str) {
+    let mut hasher = DefaultHasher::new();
+    shader_src.hash(&mut hasher);
+
+    CONTEXT.lock().unwrap().scene = Some(format!(
+        "passes: {}\nshader: {shader} (hash {:016x})\n",
+        passes.join(" -> "),
+        hasher.finish()
+    ));
+}
+
+/// Write everything known about the running app to a new report file
+pub fn write_report(reason: &str) {
+    let Some(report_dir) = REPORT_DIR.get() else {
+        return;
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "{} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(
+        report,
+        "{} {}, at {timestamp}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(report, "\n{reason}\n");
+
+    // The panic may have happened while the context was locked
+    match CONTEXT.try_lock() {
+        Ok(context) => {
+            report += context.device.as_deref().unwrap_or("no device yet\n");
+            report += "\n";
+            report += context.scene.as_deref().unwrap_or("no scene yet\n");
+        }
+        Err(_) => report += "context unavailable\n",
+    }
+
+    // The web build logs to the console instead
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        report += "\nrecent log lines:\n";
+        for line in logger::recent_lines() {
+            report += &line;
+            report += "\n";
+        }
+    }
+
+    let path = report_dir.join(format!("crash-{timestamp}.txt"));
+    match fs::write(&path, report) {
+        Ok(()) => eprintln!("Crash report written to {}", path.display()),
+        Err(err) => eprintln!("Failed to write crash report to {}: {err}", path.display()),
+    }
+}
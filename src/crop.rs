@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    readback::FrameLayout,
+    recording::{self, ImageFormat},
+};
+
+/// Name of the crop dragged with the right mouse button
+const DRAGGED: &str = "selection";
+
+/// A rectangle of the image, in fractions of its size so it fits any resolution
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Crop {
+    /// Left edge, 0 is the left and 1 the right side of the image
+    pub x: f32,
+    /// Top edge, 0 is the top and 1 the bottom of the image
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Resolution multiplier of the export, the drawing is redrawn that much larger
+    #[serde(default = "default_scale")]
+    pub scale: u32,
+}
+
+fn default_scale() -> u32 {
+    1
+}
+
+impl Crop {
+    /// All of the image
+    pub const FULL: Self = Self {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+        scale: 1,
+    };
+
+    /// Rectangle spanned by two corners
+    fn from_corners(a: [f32; 2], b: [f32; 2]) -> Self {
+        let [x0, y0] = [a[0].min(b[0]), a[1].min(b[1])].map(|v| v.clamp(0.0, 1.0));
+        let [x1, y1] = [a[0].max(b[0]), a[1].max(b[1])].map(|v| v.clamp(0.0, 1.0));
+        Self {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+            scale: 1,
+        }
+    }
+
+    /// Left, top, width and height in pixels of an image of `size`, at least one pixel large
+    pub fn pixels(&self, (width, height): (u32, u32)) -> [u32; 4] {
+        let left = ((self.x.clamp(0.0, 1.0) * width as f32) as u32).min(width - 1);
+        let top = ((self.y.clamp(0.0, 1.0) * height as f32) as u32).min(height - 1);
+        let right = (((self.x + self.width) * width as f32).round() as u32).clamp(left + 1, width);
+        let bottom =
+            (((self.y + self.height) * height as f32).round() as u32).clamp(top + 1, height);
+        [left, top, right - left, bottom - top]
+    }
+
+    /// Corners as `[left, top, right, bottom]` for highlighting in the render pass
+    pub fn corners(&self) -> [f32; 4] {
+        [self.x, self.y, self.x + self.width, self.y + self.height]
+    }
+}
+
+/// A crop being read back, written to a PNG file once it arrives
+pub struct CropExport {
+    pub name: String,
+    pub path: PathBuf,
+    /// Left, top, width and height in pixels of the texture read back
+    pub rect: [u32; 4],
+    /// Whether the drawing outputs display-ready colors, which then aren't sRGB encoded
+    pub srgb_output: bool,
+    pub format: ImageFormat,
+}
+
+impl CropExport {
+    /// Cut the crop out of the read back texture and write it
+    pub fn write(&self, layout: FrameLayout, data: &[u8]) {
+        let image = layout.texels(data);
+        let [left, top, width, height] = self.rect;
+        let texels: Vec<[f32; 4]> = image
+            .chunks_exact(layout.width as usize)
+            .skip(top as usize)
+            .take(height as usize)
+            .flat_map(|row| &row[left as usize..(left + width) as usize])
+            .copied()
+            .collect();
+        let written = recording::write_image(
+            &self.path,
+            self.format,
+            width,
+            height,
+            &texels,
+            self.srgb_output,
+        );
+        match written {
+            Ok(()) => log::info!("Exported crop {:?} to {}", self.name, self.path.display()),
+            Err(err) => log::error!("Failed to export crop {:?}: {err}", self.name),
+        }
+    }
+}
+
+/// Named crop rectangles from the `[crops]` table of the config file,
+/// plus one dragged with the right mouse button.
+/// `C` cycles through them and `E` exports the selected one
+pub struct CropControls {
+    crops: BTreeMap<String, Crop>,
+    selected: Option<String>,
+    /// Cursor position in fractions of the window
+    cursor: [f32; 2],
+    /// Where the right mouse button was pressed
+    drag_start: Option<[f32; 2]>,
+}
+
+impl CropControls {
+    pub fn new(crops: BTreeMap<String, Crop>) -> Self {
+        Self {
+            crops,
+            selected: None,
+            cursor: [0.0; 2],
+            drag_start: None,
+        }
+    }
+
+    /// The selected crop and its name, or the one being dragged
+    pub fn selected(&self) -> Option<(&str, Crop)> {
+        if let Some(start) = self.drag_start {
+            return Some((DRAGGED, Crop::from_corners(start, self.cursor)));
+        }
+        let name = self.selected.as_deref()?;
+        Some((name, self.crops[name]))
+    }
+
+    /// Select the next crop, or none after the last one
+    pub fn select_next(&mut self) {
+        let mut names = self.crops.keys();
+        self.selected = match &self.selected {
+            None => names.next().cloned(),
+            Some(selected) => names.skip_while(|name| *name != selected).nth(1).cloned(),
+        };
+        match self.selected() {
+            Some((name, crop)) => log::info!("Selected crop {name:?}: {crop:?}"),
+            None => log::info!("No crop selected"),
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag_start.is_some()
+    }
+
+    pub fn cursor_moved(&mut self, position: [f32; 2]) {
+        self.cursor = position;
+    }
+
+    pub fn drag_started(&mut self) {
+        self.drag_start = Some(self.cursor);
+    }
+
+    /// Keep the dragged rectangle as crop named `selection` and select it
+    pub fn drag_ended(&mut self) {
+        let Some(start) = self.drag_start.take() else {
+            return;
+        };
+        let crop = Crop::from_corners(start, self.cursor);
+        if crop.width <= 0.0 || crop.height <= 0.0 {
+            return;
+        }
+        self.crops.insert(DRAGGED.to_owned(), crop);
+        self.selected = Some(DRAGGED.to_owned());
+        log::info!("Selected crop {DRAGGED:?}: {crop:?}, press E to export it");
+    }
+}
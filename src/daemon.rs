@@ -0,0 +1,52 @@
+use std::{
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Signals a daemon reacts to: SIGHUP reloads, SIGTERM and SIGINT shut down cleanly.
+/// They only set flags, which the event loop polls once per frame
+pub struct Signals {
+    reload: Arc<AtomicBool>,
+    terminate: Arc<AtomicBool>,
+}
+
+impl Signals {
+    #[cfg(unix)]
+    pub fn register() -> io::Result<Self> {
+        use signal_hook::{
+            consts::{SIGHUP, SIGINT, SIGTERM},
+            flag,
+        };
+
+        let signals = Self::unregistered();
+        flag::register(SIGHUP, Arc::clone(&signals.reload))?;
+        flag::register(SIGTERM, Arc::clone(&signals.terminate))?;
+        flag::register(SIGINT, Arc::clone(&signals.terminate))?;
+        Ok(signals)
+    }
+
+    /// There are no such signals outside of unix, daemons are stopped by closing the window
+    #[cfg(not(unix))]
+    pub fn register() -> io::Result<Self> {
+        Ok(Self::unregistered())
+    }
+
+    fn unregistered() -> Self {
+        Self {
+            reload: Arc::new(AtomicBool::new(false)),
+            terminate: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Whether a reload was requested since the last call
+    pub fn take_reload(&self) -> bool {
+        self.reload.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn terminate_requested(&self) -> bool {
+        self.terminate.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,61 @@
+use crate::globals::Region;
+
+/// Collects the parts of the drawn image that changed since the last frame for `--dirty-radius`,
+/// so [`GlobalsState::set_region`](crate::globals::GlobalsState::set_region) can restrict the next one to them
+pub struct DirtyRegions {
+    /// Pixels around the cursor drawn again as it moves
+    radius: f32,
+    /// Everything marked since the last frame
+    pending: Option<Region>,
+    /// In pixels of the drawn image
+    cursor: Option<[f32; 2]>,
+}
+
+impl DirtyRegions {
+    /// Starts out with the whole image to draw
+    pub fn new(radius: u32) -> Self {
+        let mut dirty_regions = Self {
+            radius: radius as f32,
+            pending: None,
+            cursor: None,
+        };
+        dirty_regions.mark_all();
+        dirty_regions
+    }
+
+    /// Draw `region` again with the next frame
+    pub fn mark(&mut self, region: Region) {
+        self.pending = Some(match self.pending {
+            Some(pending) => pending.union(region),
+            None => region,
+        });
+    }
+
+    /// Draw the whole image again with the next frame, clamped to its size when dispatched
+    pub fn mark_all(&mut self) {
+        self.mark(Region {
+            origin: [0, 0],
+            size: [u32::MAX; 2],
+        });
+    }
+
+    /// Mark the pixels within the radius of the way from the last position of the cursor to `position`,
+    /// in pixels of the drawn image
+    pub fn cursor_moved(&mut self, position: [f32; 2]) {
+        let last = self.cursor.replace(position).unwrap_or(position);
+        let min = [0, 1].map(|axis| (last[axis].min(position[axis]) - self.radius).max(0.0));
+        let max = [0, 1].map(|axis| (last[axis].max(position[axis]) + self.radius).max(0.0));
+        self.mark(Region {
+            origin: min.map(|v| v.floor() as u32),
+            size: [0, 1].map(|axis| (max[axis].ceil() - min[axis].floor()) as u32 + 1),
+        });
+    }
+
+    /// The region to draw this frame, empty if nothing changed
+    pub fn take(&mut self) -> Region {
+        self.pending.take().unwrap_or(Region {
+            origin: [0, 0],
+            size: [0, 0],
+        })
+    }
+}
@@ -0,0 +1,130 @@
+use wgpu::*;
+
+use crate::{compute::OUTPUT_FORMAT, shaders::Shaders};
+
+/// Box filter from the supersampled image down to the presented resolution,
+/// antialiasing the edges of procedurally drawn shapes
+pub struct DownsampleState {
+    pipeline: ComputePipeline,
+    bind_group: BindGroup,
+    pub output_texture: Texture,
+    pub output_view: TextureView,
+    workgroup_size: [u32; 2],
+    /// Size of the downsampled image
+    width: u32,
+    height: u32,
+}
+
+impl DownsampleState {
+    /// `width` × `height` is the size of the downsampled image
+    pub fn new(
+        device: &Device,
+        shaders: &Shaders,
+        source_view: &TextureView,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let module = shaders
+            .downsample
+            .as_ref()
+            .expect("Downsample shader was not created");
+
+        let output_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Downsample Output Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::STORAGE_BINDING
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Downsample Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: OUTPUT_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Downsample Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(source_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&output_view),
+                },
+            ],
+        });
+
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            compilation_options: Default::default(),
+            label: Some("Downsample Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Downsample Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            })),
+            module,
+            entry_point: "main",
+            cache: shaders.pipeline_cache.as_deref(),
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            output_texture,
+            output_view,
+            workgroup_size: shaders.workgroup_size,
+            width,
+            height,
+        }
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Downsample Pass"),
+        });
+
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        let [workgroup_width, workgroup_height] = self.workgroup_size;
+        compute_pass.dispatch_workgroups(
+            self.width.div_ceil(workgroup_width),
+            self.height.div_ceil(workgroup_height),
+            1,
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use std::{collections::HashMap, fs, io::Write};
+
+use crate::{cli::Options, headless::Headless, recording};
+
+/// File listing every image of the edition, in `--edition-dir`
+const MANIFEST: &str = "manifest.csv";
+
+/// Draw `count` images of the `--shader` or `--example`, each with the next `globals.seed`
+/// starting at `--seed-start`, and write them with a manifest to `--edition-dir`
+pub fn generate(options: &Options, count: u32) -> Result<(), String> {
+    let dir = &options.edition_dir;
+    fs::create_dir_all(dir).map_err(|err| format!("Failed to create {}: {err}", dir.display()))?;
+    let manifest_path = dir.join(MANIFEST);
+    let mut manifest = fs::File::create(&manifest_path)
+        .map_err(|err| format!("Failed to create {}: {err}", manifest_path.display()))?;
+    let write_err = |err| format!("Failed to write {}: {err}", manifest_path.display());
+    writeln!(manifest, "number,file,seed,param_x,param_y,blake3").map_err(write_err)?;
+
+    let (width, height) = options.resolution();
+    let mut headless = Headless::new(options, width, height)?;
+    let [param_x, param_y] = headless.initial_params;
+    let digits = count.to_string().len().max(4);
+    // Seed of the first image with each hash, to point out shaders ignoring the seed
+    let mut seen = HashMap::new();
+
+    for number in 1..=count {
+        let seed = options
+            .seed_start
+            .checked_add(number - 1)
+            .ok_or("--seed-start is too large for this many images")?;
+        headless.globals.globals.seed = seed;
+        headless.globals.globals.frame = 1;
+        headless.globals.globals.time = 0.0;
+        let pixels = headless.draw()?;
+
+        let file = format!("{number:0digits$}.png");
+        let path = dir.join(&file);
+        recording::write_png(&path, width, height, &pixels)
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+
+        let hash = blake3::hash(&pixels);
+        if let Some(first) = seen.get(&hash) {
+            log::warn!(
+                "Seed {seed} draws the same image as seed {first}, does the shader read globals.seed?"
+            );
+        } else {
+            seen.insert(hash, seed);
+        }
+        writeln!(
+            manifest,
+            "{number},{file},{seed},{param_x},{param_y},{hash}"
+        )
+        .map_err(write_err)?;
+    }
+    Ok(())
+}
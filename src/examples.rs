@@ -0,0 +1,85 @@
+use clap::ValueEnum;
+
+/// Drawing shaders bundled with the binary, to see something without writing WGSL
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Example {
+    Gradient,
+    Clock,
+    Plasma,
+    Raymarch,
+    Life,
+    Spectrum,
+    Clouds,
+    Mandelbrot,
+    Voronoi,
+}
+
+impl Example {
+    /// Source of the `draw` function
+    pub fn source(self) -> &'static str {
+        match self {
+            Example::Gradient => include_str!("./shaders/examples/gradient.wgsl"),
+            Example::Clock => include_str!("./shaders/examples/clock.wgsl"),
+            Example::Plasma => include_str!("./shaders/examples/plasma.wgsl"),
+            Example::Raymarch => include_str!("./shaders/examples/raymarch.wgsl"),
+            Example::Life => include_str!("./shaders/examples/life.wgsl"),
+            Example::Spectrum => include_str!("./shaders/examples/spectrum.wgsl"),
+            Example::Clouds => include_str!("./shaders/examples/clouds.wgsl"),
+            Example::Mandelbrot => include_str!("./shaders/examples/mandelbrot.wgsl"),
+            Example::Voronoi => include_str!("./shaders/examples/voronoi.wgsl"),
+        }
+    }
+
+    /// `globals.params` the example looks best with, `None` for the usual 0.5 and 0.5
+    pub fn params(self) -> Option<[f32; 2]> {
+        match self {
+            Example::Mandelbrot => Some([0.25, 0.6]),
+            Example::Voronoi => Some([0.3, 0.15]),
+            _ => None,
+        }
+    }
+
+    /// The example selected by pressing number key `key`, in the order of `--list-examples`
+    pub fn for_key(key: &str) -> Option<Self> {
+        let number: usize = key.parse().ok()?;
+        number
+            .checked_sub(1)
+            .and_then(|index| Example::value_variants().get(index))
+            .copied()
+    }
+
+    /// Name of the source file under `src/shaders/examples`, for error messages
+    pub fn file_name(self) -> String {
+        format!(
+            "examples/{}.wgsl",
+            self.to_possible_value().unwrap().get_name()
+        )
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Example::Gradient => "a gradient slowly rotating through hue",
+            Example::Clock => "an analog clock counting the time since start",
+            Example::Plasma => "classic demoscene plasma",
+            Example::Raymarch => "a sphere above a checkered floor, sphere traced",
+            Example::Life => "gliders from Conway's Game of Life",
+            Example::Spectrum => "spectrum bars and waveform of the --audio input",
+            Example::Clouds => "drifting clouds filled into a volume and raymarched",
+            Example::Mandelbrot => "zooming into the Mandelbrot set and back out",
+            Example::Voronoi => "drifting Voronoi cells",
+        }
+    }
+
+    /// Print every example with its number key and description, for `--list-examples`
+    pub fn list() {
+        for (index, example) in Example::value_variants().iter().enumerate() {
+            let name = example.to_possible_value().unwrap();
+            println!(
+                "{} {:<10} {}",
+                index + 1,
+                name.get_name(),
+                example.description()
+            );
+        }
+    }
+}
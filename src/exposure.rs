@@ -0,0 +1,216 @@
+use std::sync::{Arc, OnceLock};
+
+use web_time::Instant;
+use wgpu::*;
+
+/// Luminance the average of the image is exposed to
+const MIDDLE_GRAY: f32 = 0.18;
+/// Seconds for the exposure to get about two thirds of the way to a new target, like eyes adapting
+const ADAPTATION_TIME: f32 = 0.5;
+/// The exposure stays within this many stops of 1
+const MAX_STOPS: f32 = 10.0;
+/// Size of `result`, the sum of log2 luminance and the number of pixels summed
+const RESULT_SIZE: BufferAddress = 8;
+
+/// Auto exposure: a compute pass reduces the presented image to its average log2 luminance,
+/// which is read back asynchronously and moves the exposure smoothly over time,
+/// so the average ends up at [`MIDDLE_GRAY`]
+pub struct AutoExposure {
+    reduce_pipeline: ComputePipeline,
+    finish_pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+    /// One sum per workgroup of `reduce`, sized for the image analyzed last
+    partials: Option<Buffer>,
+    result: Buffer,
+    staging: Buffer,
+    /// Set once the staging buffer is mapped, `None` while nothing is being read back
+    mapped: Option<Arc<OnceLock<bool>>>,
+    /// In stops
+    stops: f32,
+    /// When the exposure last moved, `None` until the first average arrives
+    updated: Option<Instant>,
+}
+
+impl AutoExposure {
+    pub fn new(device: &Device, pipeline_cache: Option<&PipelineCache>) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Luminance Shader"),
+            source: ShaderSource::Wgsl(include_str!("./shaders/luminance.wgsl").into()),
+        });
+        let storage_buffer = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Luminance Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage_buffer(1),
+                storage_buffer(2),
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Luminance Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                compilation_options: Default::default(),
+                label: Some("Luminance Pipeline"),
+                layout: Some(&layout),
+                module: &module,
+                entry_point,
+                cache: pipeline_cache,
+            })
+        };
+
+        Self {
+            reduce_pipeline: create_pipeline("reduce"),
+            finish_pipeline: create_pipeline("finish"),
+            bind_group_layout,
+            partials: None,
+            result: device.create_buffer(&BufferDescriptor {
+                label: Some("Luminance Result Buffer"),
+                size: RESULT_SIZE,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            staging: device.create_buffer(&BufferDescriptor {
+                label: Some("Luminance Readback Buffer"),
+                size: RESULT_SIZE,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            mapped: None,
+            stops: 0.0,
+            updated: None,
+        }
+    }
+
+    /// Average the luminance of `texture`, unless the last average is still being read back
+    pub fn analyze(&mut self, device: &Device, queue: &Queue, texture: &Texture) {
+        if self.mapped.is_some() {
+            return;
+        }
+        let workgroups = [texture.width().div_ceil(16), texture.height().div_ceil(16)];
+        let partials_size = BufferAddress::from(workgroups[0] * workgroups[1]) * 8;
+        let partials = match &self.partials {
+            Some(partials) if partials.size() == partials_size => partials,
+            _ => self
+                .partials
+                .insert(device.create_buffer(&BufferDescriptor {
+                    label: Some("Luminance Partials Buffer"),
+                    size: partials_size,
+                    usage: BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                })),
+        };
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Luminance Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: partials.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.result.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Luminance Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                timestamp_writes: None,
+                label: Some("Luminance Pass"),
+            });
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.set_pipeline(&self.reduce_pipeline);
+            compute_pass.dispatch_workgroups(workgroups[0], workgroups[1], 1);
+            compute_pass.set_pipeline(&self.finish_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.result, 0, &self.staging, 0, RESULT_SIZE);
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = Arc::new(OnceLock::new());
+        let mapped_callback = Arc::clone(&mapped);
+        self.staging
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                if let Err(err) = &result {
+                    log::error!("Failed to read back the average luminance: {err}");
+                }
+                let _ = mapped_callback.set(result.is_ok());
+            });
+        self.mapped = Some(mapped);
+    }
+
+    /// Move the exposure towards the average that arrived, without waiting for the GPU.
+    /// Returns whether it did
+    pub fn poll(&mut self, device: &Device) -> bool {
+        if self.mapped.is_none() {
+            return false;
+        }
+        device.poll(Maintain::Poll);
+        let Some(&mapped) = self.mapped.as_ref().and_then(|mapped| mapped.get()) else {
+            return false;
+        };
+        self.mapped = None;
+        if !mapped {
+            return false;
+        }
+        let [sum, pixels]: [f32; 2] =
+            bytemuck::cast_slice(&self.staging.slice(..).get_mapped_range())[..2]
+                .try_into()
+                .unwrap();
+        self.staging.unmap();
+        // Nothing but black
+        if pixels < 1.0 {
+            return false;
+        }
+
+        let target = (MIDDLE_GRAY.log2() - sum / pixels).clamp(-MAX_STOPS, MAX_STOPS);
+        let now = Instant::now();
+        self.stops = match self.updated.replace(now) {
+            Some(updated) => {
+                let elapsed = now.duration_since(updated).as_secs_f32();
+                let adaptation = 1.0 - (-elapsed / ADAPTATION_TIME).exp();
+                self.stops + (target - self.stops) * adaptation
+            }
+            // Start out adapted
+            None => target,
+        };
+        true
+    }
+
+    /// Factor the colors are multiplied by
+    pub fn exposure(&self) -> f32 {
+        self.stops.exp2()
+    }
+}
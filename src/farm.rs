@@ -0,0 +1,388 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    ops::Range,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{cli::Options, headless::Headless, recording};
+
+/// File listing every frame written so far with the hash of its pixels, in the output directory
+const MANIFEST: &str = "manifest.csv";
+/// How often the coordinator checks for new workers and whether all frames arrived
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a worker may take to send the next frame or receive a message,
+/// before it counts as lost and its chunk is handed to another worker
+const WORKER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// An image sequence split across the workers of a render farm
+#[derive(Clone, Copy)]
+pub struct Sequence {
+    /// Frames are numbered from 1 to `frames`, like recorded ones
+    pub frames: u32,
+    /// Frames handed to a worker at once
+    pub chunk_frames: u32,
+    /// `globals.time` of a frame is its number divided by this
+    pub framerate: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Sequence {
+    fn frame_size(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+}
+
+/// Progress of the coordinator, shared by the connections to the workers
+struct Farm {
+    /// Frames nobody works on yet
+    pending: VecDeque<Range<u32>>,
+    /// Hashes of the frames written so far
+    done: BTreeMap<u32, blake3::Hash>,
+    manifest: File,
+}
+
+/// Hand out the frames of `sequence` not yet in `output` to workers connecting to `listen`,
+/// and write the frames they send back as PNGs. Returns the number of frames drawn.
+/// Frames listed in the manifest of an earlier run are checked against their hashes and only drawn again if they differ
+pub fn coordinate(output: &Path, sequence: Sequence, listen: SocketAddr) -> Result<u32, String> {
+    fs::create_dir_all(output)
+        .map_err(|err| format!("Failed to create {}: {err}", output.display()))?;
+    let manifest_path = output.join(MANIFEST);
+    let done = verify_manifest(output, &sequence);
+    if !done.is_empty() {
+        println!("{} frames of an earlier run are complete", done.len());
+    }
+    let manifest = write_manifest(&manifest_path, &done)
+        .map_err(|err| format!("Failed to write {}: {err}", manifest_path.display()))?;
+
+    let missing: Vec<u32> = (1..=sequence.frames)
+        .filter(|frame| !done.contains_key(frame))
+        .collect();
+    let drawn = missing.len() as u32;
+    let farm = Arc::new(Mutex::new(Farm {
+        pending: chunks(&missing, sequence.chunk_frames),
+        done,
+        manifest,
+    }));
+
+    let listener = TcpListener::bind(listen)
+        .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+        .map_err(|err| format!("Failed to listen on {listen}: {err}"))?;
+    println!("Waiting for workers on {listen}, {drawn} frames to draw");
+    let mut workers = Vec::new();
+    while farm.lock().unwrap().done.len() < sequence.frames as usize {
+        match listener.accept() {
+            Ok((stream, worker)) => {
+                log::info!("Worker {worker} connected");
+                let farm = Arc::clone(&farm);
+                let output = output.to_owned();
+                workers.push(thread::spawn(move || {
+                    serve_worker(stream, worker, &farm, &output, sequence)
+                }));
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(err) => log::error!("Failed to accept a worker: {err}"),
+        }
+    }
+
+    // Let the workers still connected know they are done before exiting
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    // Frames arrive out of order, list them in order
+    write_manifest(&manifest_path, &farm.lock().unwrap().done)
+        .map_err(|err| format!("Failed to write {}: {err}", manifest_path.display()))?;
+    Ok(drawn)
+}
+
+/// Write the manifest listing `done` and keep it open for appending more frames
+fn write_manifest(path: &Path, done: &BTreeMap<u32, blake3::Hash>) -> io::Result<File> {
+    let mut lines = String::from("frame,file,blake3\n");
+    for (frame, hash) in done {
+        lines += &format!("{frame},{},{hash}\n", frame_file(*frame));
+    }
+    let mut manifest = File::create(path)?;
+    manifest.write_all(lines.as_bytes())?;
+    Ok(manifest)
+}
+
+/// Frames of the manifest in `output` whose files still hash the same
+fn verify_manifest(output: &Path, sequence: &Sequence) -> BTreeMap<u32, blake3::Hash> {
+    let Ok(manifest) = fs::read_to_string(output.join(MANIFEST)) else {
+        return BTreeMap::new();
+    };
+    let mut done = BTreeMap::new();
+    for line in manifest.lines().skip(1) {
+        let mut fields = line.split(',');
+        let (Some(frame), Some(file), Some(hash)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let (Ok(frame), Ok(hash)) = (frame.parse::<u32>(), blake3::Hash::from_hex(hash)) else {
+            continue;
+        };
+        if frame == 0 || frame > sequence.frames {
+            continue;
+        }
+        match read_png(&output.join(file)) {
+            Ok((width, height, pixels))
+                if (width, height) == (sequence.width, sequence.height)
+                    && blake3::hash(&pixels) == hash =>
+            {
+                done.insert(frame, hash);
+            }
+            Ok(_) => log::warn!("{file} doesn't match the manifest, drawing it again"),
+            Err(err) => log::warn!("Failed to read {file}, drawing it again: {err}"),
+        }
+    }
+    done
+}
+
+/// Runs of consecutive `frames`, at most `chunk_frames` long
+fn chunks(frames: &[u32], chunk_frames: u32) -> VecDeque<Range<u32>> {
+    let mut chunks = VecDeque::new();
+    for &frame in frames {
+        match chunks.back_mut() {
+            Some(Range { start, end }) if *end == frame && frame - *start < chunk_frames => {
+                *end += 1;
+            }
+            _ => chunks.push_back(frame..frame + 1),
+        }
+    }
+    chunks
+}
+
+fn frame_file(frame: u32) -> String {
+    format!("frame-{frame:06}.png")
+}
+
+/// Hand chunks to one worker until every frame is done, letting it wait while other workers finish theirs.
+/// The frames of a chunk it didn't finish, because it disconnected or timed out,
+/// are handed to the next worker asking
+fn serve_worker(
+    stream: TcpStream,
+    worker: SocketAddr,
+    farm: &Mutex<Farm>,
+    output: &Path,
+    sequence: Sequence,
+) {
+    let mut next_frame = None;
+    let result = (|| {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(WORKER_TIMEOUT))?;
+        stream.set_write_timeout(Some(WORKER_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        loop {
+            let chunk = {
+                let mut farm = farm.lock().unwrap();
+                let chunk = farm.pending.pop_front();
+                if chunk.is_none() && farm.done.len() == sequence.frames as usize {
+                    return writeln!(writer, "DONE");
+                }
+                chunk
+            };
+            // Chunks of other workers may still come back if they are lost
+            let Some(chunk) = chunk else {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+            let Sequence {
+                width,
+                height,
+                framerate,
+                ..
+            } = sequence;
+            next_frame = Some(chunk.clone());
+            writeln!(
+                writer,
+                "CHUNK {} {} {width} {height} {framerate}",
+                chunk.start, chunk.end
+            )?;
+            for frame in chunk.clone() {
+                next_frame = Some(frame..chunk.end);
+                receive_frame(&mut reader, frame, farm, output, &sequence)?;
+            }
+            next_frame = None;
+        }
+    })();
+
+    if let Err(err) = result {
+        log::error!("Lost worker {worker}: {err}");
+        if let Some(unfinished) = next_frame {
+            farm.lock().unwrap().pending.push_front(unfinished);
+        }
+    }
+}
+
+/// Read `frame` from a worker, check its hash and write it
+fn receive_frame(
+    reader: &mut impl BufRead,
+    frame: u32,
+    farm: &Mutex<Farm>,
+    output: &Path,
+    sequence: &Sequence,
+) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+    let hash = match header.split_whitespace().collect::<Vec<_>>()[..] {
+        ["FRAME", number, hash] if number.parse() == Ok(frame) => {
+            blake3::Hash::from_hex(hash).map_err(|err| invalid(err.to_string()))?
+        }
+        _ => return Err(invalid(format!("expected frame {frame}, got {header:?}"))),
+    };
+    let mut pixels = vec![0; sequence.frame_size()];
+    reader.read_exact(&mut pixels)?;
+    if blake3::hash(&pixels) != hash {
+        return Err(invalid(format!("frame {frame} was corrupted in transfer")));
+    }
+
+    let file = frame_file(frame);
+    recording::write_png(
+        &output.join(&file),
+        sequence.width,
+        sequence.height,
+        &pixels,
+    )?;
+    let mut farm = farm.lock().unwrap();
+    writeln!(farm.manifest, "{frame},{file},{hash}")?;
+    farm.done.insert(frame, hash);
+    Ok(())
+}
+
+/// Draw the chunks `coordinator` hands out with the `--shader` or `--example` until it is done.
+/// Returns the number of frames drawn
+pub fn work(options: &Options, coordinator: &str) -> Result<u32, String> {
+    let stream = TcpStream::connect(coordinator)
+        .map_err(|err| format!("Failed to connect to {coordinator}: {err}"))?;
+    let lost = |err: io::Error| format!("Lost the coordinator: {err}");
+    let mut reader = BufReader::new(stream.try_clone().map_err(lost)?);
+    let mut writer = BufWriter::new(stream);
+    let mut headless: Option<Headless> = None;
+    let mut drawn = 0;
+
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(lost)?;
+        let fields: Vec<u32> = match line.trim().split_once(' ') {
+            Some(("CHUNK", fields)) => fields
+                .split(' ')
+                .map(|field| field.parse().map_err(|_| format!("Invalid chunk {line:?}")))
+                .collect::<Result<_, _>>()?,
+            None if line.trim() == "DONE" => return Ok(drawn),
+            _ => return Err(format!("Unexpected message from the coordinator: {line:?}")),
+        };
+        let [start, end, width, height, framerate] = fields[..] else {
+            return Err(format!("Invalid chunk {line:?}"));
+        };
+        if start >= end || width == 0 || height == 0 || framerate == 0 {
+            return Err(format!("Invalid chunk {line:?}"));
+        }
+
+        if headless
+            .as_ref()
+            .is_none_or(|headless| headless.size() != (width, height))
+        {
+            headless = Some(Headless::new(options, width, height)?);
+        }
+        let headless = headless.as_mut().unwrap();
+        log::info!("Drawing frames {start} to {}", end - 1);
+        for frame in start..end {
+            let globals = &mut headless.globals.globals;
+            globals.seed = options.seed;
+            globals.frame = frame;
+            globals.time = frame as f32 / framerate as f32;
+            let pixels = headless.draw()?;
+            writeln!(writer, "FRAME {frame} {}", blake3::hash(&pixels)).map_err(lost)?;
+            writer.write_all(&pixels).map_err(lost)?;
+            drawn += 1;
+        }
+        writer.flush().map_err(lost)?;
+    }
+}
+
+/// Width, height and 8-bit RGBA pixels of a PNG written by [`recording::write_png`]
+fn read_png(path: &Path) -> io::Result<(u32, u32, Vec<u8>)> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(io::Error::other)?;
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not 8-bit RGBA"));
+    }
+    pixels.truncate(info.buffer_size());
+    Ok((info.width, info.height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for the files of a test
+    fn dir(test: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("farm-{}-{test}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn chunks_split_at_gaps_and_the_limit() {
+        assert_eq!(chunks(&[], 3), VecDeque::new());
+        assert_eq!(
+            chunks(&[1, 2, 3, 4, 5, 6, 7], 3),
+            VecDeque::from([1..4, 4..7, 7..8])
+        );
+        assert_eq!(
+            chunks(&[1, 2, 4, 5, 6, 9], 5),
+            VecDeque::from([1..3, 4..7, 9..10])
+        );
+        assert_eq!(chunks(&[3, 4], 1), VecDeque::from([3..4, 4..5]));
+    }
+
+    #[test]
+    fn manifest_keeps_only_matching_frames() {
+        let output = dir("manifest");
+        let sequence = Sequence {
+            frames: 3,
+            chunk_frames: 1,
+            framerate: 60,
+            width: 2,
+            height: 1,
+        };
+        let pixels = [255; 8];
+        for frame in [1, 2, 4] {
+            recording::write_png(&output.join(frame_file(frame)), 2, 1, &pixels).unwrap();
+        }
+        let hash = blake3::hash(&pixels);
+        let other = blake3::hash(&[0; 8]);
+        fs::write(
+            output.join(MANIFEST),
+            format!(
+                "frame,file,blake3\n\
+                 1,{},{hash}\n\
+                 2,{},{other}\n\
+                 4,{},{hash}\n\
+                 0,{},{hash}\n\
+                 3,frame-missing.png,{hash}\n",
+                frame_file(1),
+                frame_file(2),
+                frame_file(4),
+                frame_file(1),
+            ),
+        )
+        .unwrap();
+
+        let done = verify_manifest(&output, &sequence);
+        assert_eq!(done.into_iter().collect::<Vec<_>>(), [(1, hash)]);
+        fs::remove_dir_all(&output).unwrap();
+    }
+}
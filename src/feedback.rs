@@ -0,0 +1,257 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Write},
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use serde::Deserialize;
+use wgpu::*;
+
+use crate::osc::OscArg;
+
+/// Atomic counters and plain values in the `feedback` buffer
+pub const COUNTER_COUNT: usize = 4;
+pub const VALUE_COUNT: usize = 4;
+/// Size of the `feedback` buffer, matching `Feedback` in `feedback.wgsl`
+const FEEDBACK_SIZE: BufferAddress = ((COUNTER_COUNT + VALUE_COUNT) * 4) as BufferAddress;
+/// Readbacks in flight, the feedback of further frames is skipped until one arrives
+const MAX_PENDING_READBACKS: usize = 3;
+
+/// What the drawing shader reported about a frame through its `feedback` buffer
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Feedback {
+    pub frame: u32,
+    pub counters: [u32; COUNTER_COUNT],
+    pub values: [f32; VALUE_COUNT],
+}
+
+impl Feedback {
+    /// The frame number and counters as integers, followed by the values
+    pub fn osc_args(&self) -> Vec<OscArg> {
+        std::iter::once(self.frame)
+            .chain(self.counters)
+            .map(|int| OscArg::Int(int as i32))
+            .chain(self.values.map(OscArg::Float))
+            .collect()
+    }
+}
+
+/// The `feedback` buffer of a compute drawing, zeroed before every frame
+/// and read back asynchronously afterwards
+pub struct FeedbackBuffer {
+    pub buffer: Buffer,
+    /// Staging buffers with the frame copied into them, set once mapping finished
+    pending: VecDeque<(Buffer, u32, Arc<OnceLock<bool>>)>,
+    free_buffers: Vec<Buffer>,
+}
+
+impl FeedbackBuffer {
+    pub fn new(device: &Device) -> Self {
+        Self {
+            buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("Feedback Buffer"),
+                size: FEEDBACK_SIZE,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            pending: VecDeque::new(),
+            free_buffers: Vec::new(),
+        }
+    }
+
+    /// Start copying the feedback of `frame` back, after the frame was submitted
+    pub fn read_back(&mut self, device: &Device, queue: &Queue, frame: u32) {
+        if self.pending.len() >= MAX_PENDING_READBACKS {
+            return;
+        }
+        let staging = self.free_buffers.pop().unwrap_or_else(|| {
+            device.create_buffer(&BufferDescriptor {
+                label: Some("Feedback Readback Buffer"),
+                size: FEEDBACK_SIZE,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Feedback Readback Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &staging, 0, FEEDBACK_SIZE);
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = Arc::new(OnceLock::new());
+        let mapped_callback = Arc::clone(&mapped);
+        staging.slice(..).map_async(MapMode::Read, move |result| {
+            if let Err(err) = &result {
+                log::error!("Failed to read back feedback: {err}");
+            }
+            let _ = mapped_callback.set(result.is_ok());
+        });
+        self.pending.push_back((staging, frame, mapped));
+    }
+
+    /// The feedback of the latest frame that arrived since the last call, without waiting for the GPU
+    pub fn poll(&mut self, device: &Device) -> Option<Feedback> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        device.poll(Maintain::Poll);
+
+        let mut latest = None;
+        while let Some((_, _, mapped)) = self.pending.front()
+            && let Some(&mapped) = mapped.get()
+        {
+            let (staging, frame, _) = self.pending.pop_front().unwrap();
+            if mapped {
+                let data = staging.slice(..).get_mapped_range();
+                let words: &[u32] = bytemuck::cast_slice(&data);
+                let (counters, values) = words.split_at(COUNTER_COUNT);
+                latest = Some(Feedback {
+                    frame,
+                    counters: counters.try_into().unwrap(),
+                    values: bytemuck::cast_slice(values).try_into().unwrap(),
+                });
+                drop(data);
+                staging.unmap();
+            }
+            self.free_buffers.push(staging);
+        }
+        latest
+    }
+}
+
+/// Writes the feedback of every frame read back as a line of frame number, counters and values,
+/// for scripts to follow
+pub struct FeedbackLog {
+    output: Box<dyn Write>,
+}
+
+impl FeedbackLog {
+    /// Write to `path`, or to stdout for `-`
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let output: Box<dyn Write> = if path == Path::new("-") {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        Ok(Self { output })
+    }
+
+    pub fn write(&mut self, feedback: &Feedback) {
+        let Feedback {
+            frame,
+            counters,
+            values,
+        } = feedback;
+        let counters = counters.map(|counter| counter.to_string()).join(" ");
+        let values = values.map(|value| value.to_string()).join(" ");
+        if let Err(err) = writeln!(self.output, "{frame} {counters} {values}") {
+            log::error!("Failed to write feedback: {err}");
+        }
+    }
+}
+
+/// `[[triggers]]` of the config file: an action taken when the drawing shader
+/// reports a counter or value above or below a threshold
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Trigger {
+    /// Index into `feedback.counters`
+    pub counter: Option<usize>,
+    /// Index into `feedback.values`, instead of a counter
+    pub value: Option<usize>,
+    pub above: Option<f32>,
+    pub below: Option<f32>,
+    pub action: TriggerAction,
+}
+
+/// Written as `action = "reset"`, or as table for the actions taking arguments,
+/// e.g. `action = { scene = "plasma" }`
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TriggerAction {
+    /// Start over, like pressing `R`
+    Reset,
+    /// Dispatch the next entry point of the drawing shader, like pressing Tab
+    NextEntryPoint,
+    /// Show the next shader of the `--playlist`, or the next entry of the attract mode playlist,
+    /// leaving the interactive scene for it
+    NextScene,
+    /// Stop or continue the clock and the frames, like pressing Space
+    Pause,
+    /// Draw the next preset with its own shader
+    NextPreset,
+    /// Quit, e.g. once a simulation converged
+    Exit,
+    /// Write the presented image to `screenshot-<frame>.png`
+    Screenshot,
+    /// Switch to a preset, example, shader file or library bundle, like an attract mode playlist entry
+    Scene(String),
+    /// Send the frame number, counters and values as OSC message to `address` on `target`,
+    /// e.g. `{ osc = { target = "127.0.0.1:9000", address = "/drawing/converged" } }`
+    Osc { target: SocketAddr, address: String },
+}
+
+impl Trigger {
+    fn reading(&self, feedback: &Feedback) -> f32 {
+        match (self.counter, self.value) {
+            (Some(counter), _) => feedback.counters[counter] as f32,
+            (None, Some(value)) => feedback.values[value],
+            (None, None) => unreachable!("checked by Triggers::new"),
+        }
+    }
+
+    fn met(&self, feedback: &Feedback) -> bool {
+        let reading = self.reading(feedback);
+        self.above.is_some_and(|above| reading > above)
+            || self.below.is_some_and(|below| reading < below)
+    }
+}
+
+/// Fires each trigger once when its reading crosses the threshold,
+/// it fires again only after the reading went back
+pub struct Triggers {
+    triggers: Vec<Trigger>,
+    armed: Vec<bool>,
+}
+
+impl Triggers {
+    pub fn new(triggers: Vec<Trigger>) -> Result<Self, String> {
+        for trigger in &triggers {
+            match (trigger.counter, trigger.value) {
+                (Some(_), Some(_)) | (None, None) => {
+                    return Err("a trigger needs either a counter or a value".to_owned());
+                }
+                (Some(index), None) if index >= COUNTER_COUNT => {
+                    return Err(format!("there are only {COUNTER_COUNT} counters"));
+                }
+                (None, Some(index)) if index >= VALUE_COUNT => {
+                    return Err(format!("there are only {VALUE_COUNT} values"));
+                }
+                _ => {}
+            }
+            if trigger.above.is_none() && trigger.below.is_none() {
+                return Err("a trigger needs a threshold it is `above` or `below`".to_owned());
+            }
+        }
+        Ok(Self {
+            armed: vec![true; triggers.len()],
+            triggers,
+        })
+    }
+
+    /// Actions of the triggers `feedback` fires
+    pub fn check(&mut self, feedback: &Feedback) -> Vec<TriggerAction> {
+        let mut actions = Vec::new();
+        for (trigger, armed) in self.triggers.iter().zip(&mut self.armed) {
+            let met = trigger.met(feedback);
+            if met && *armed {
+                actions.push(trigger.action.clone());
+            }
+            *armed = !met;
+        }
+        actions
+    }
+}
@@ -0,0 +1,131 @@
+use winit::event::MouseScrollDelta;
+
+use crate::{camera::PIXELS_PER_LINE, globals::FractalView};
+
+/// Height of the plane shown at zoom 1, enough for the whole Mandelbrot set
+const VIEW_HEIGHT: f64 = 3.0;
+/// Factor the zoom changes by per line scrolled
+const WHEEL_FACTOR: f64 = 1.25;
+/// Deeper than this even the split floats run out of precision
+const MAX_ZOOM: f64 = 1e11;
+/// Where the Mandelbrot set is centered at the start
+const START_CENTER: [f64; 2] = [-0.6, 0.0];
+/// Iterations at zoom 1, deeper views take more to tell points apart
+const BASE_ITERATIONS: f64 = 256.0;
+/// Keeps deep views from stalling the GPU
+const MAX_ITERATIONS: u32 = 4096;
+
+/// Mouse controls of the [`FractalView`]: dragging with the left mouse button pans,
+/// the wheel zooms around the cursor, J switches to the Julia set of the point in the center
+/// and back, Home goes back to the start
+pub struct FractalControls {
+    center: [f64; 2],
+    zoom: f64,
+    /// Constant of the Julia set shown, `None` while showing the Mandelbrot set
+    julia: Option<[f64; 2]>,
+    dragging: bool,
+    /// In pixels of the drawn image
+    cursor: Option<[f64; 2]>,
+}
+
+impl FractalControls {
+    pub fn new() -> Self {
+        Self {
+            center: START_CENTER,
+            zoom: 1.0,
+            julia: None,
+            dragging: false,
+            cursor: None,
+        }
+    }
+
+    /// Back to the whole set
+    pub fn reset(&mut self) {
+        self.center = if self.julia.is_some() {
+            [0.0; 2]
+        } else {
+            START_CENTER
+        };
+        self.zoom = 1.0;
+    }
+
+    pub fn toggle_julia(&mut self) {
+        self.julia = match self.julia {
+            Some(_) => None,
+            None => Some(self.center),
+        };
+        if let Some([re, im]) = self.julia {
+            log::info!("Julia set of {re} + {im}i");
+        }
+        self.reset();
+    }
+
+    pub fn handle_button(&mut self, pressed: bool) {
+        self.dragging = pressed;
+    }
+
+    /// `position` and `resolution` in pixels of the drawn image
+    pub fn handle_cursor(&mut self, position: [f32; 2], resolution: [f32; 2]) {
+        let position = position.map(f64::from);
+        if let Some(last) = self.cursor.replace(position)
+            && self.dragging
+        {
+            let scale = self.scale(resolution);
+            self.center[0] -= (position[0] - last[0]) * scale;
+            // The image is drawn top down, the imaginary axis points up
+            self.center[1] += (position[1] - last[1]) * scale;
+        }
+    }
+
+    /// Zoom in or out, keeping the point under the cursor in place
+    pub fn handle_wheel(&mut self, delta: MouseScrollDelta, resolution: [f32; 2]) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => f64::from(y),
+            MouseScrollDelta::PixelDelta(position) => position.y / f64::from(PIXELS_PER_LINE),
+        };
+        let [width, height] = resolution.map(f64::from);
+        let [x, y] = self.cursor.unwrap_or([width / 2.0, height / 2.0]);
+        let offset = [x - width / 2.0, height / 2.0 - y];
+        let before = self.scale(resolution);
+        self.zoom = (self.zoom * WHEEL_FACTOR.powf(lines)).clamp(0.5, MAX_ZOOM);
+        let after = self.scale(resolution);
+        for (center, offset) in self.center.iter_mut().zip(offset) {
+            *center += offset * (before - after);
+        }
+    }
+
+    /// The view drawn at `resolution`
+    pub fn view(&self, resolution: [f32; 2]) -> FractalView {
+        let (center_hi, center_lo) = split(self.center);
+        let (julia_hi, julia_lo) = split(self.julia.unwrap_or_default());
+        let iterations = BASE_ITERATIONS * (1.0 + self.zoom.max(1.0).log2() / 4.0);
+        FractalView {
+            center_hi,
+            center_lo,
+            julia_hi,
+            julia_lo,
+            scale: self.scale(resolution) as f32,
+            julia: u32::from(self.julia.is_some()),
+            max_iterations: (iterations as u32).min(MAX_ITERATIONS),
+            one: 1.0,
+        }
+    }
+
+    /// Plane units per pixel
+    fn scale(&self, [_, height]: [f32; 2]) -> f64 {
+        VIEW_HEIGHT / (self.zoom * f64::from(height.max(1.0)))
+    }
+}
+
+impl Default for FractalControls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `point` as the nearest f32s and what they are off by
+fn split(point: [f64; 2]) -> ([f32; 2], [f32; 2]) {
+    let hi = point.map(|v| v as f32);
+    let lo = [0, 1].map(|axis| (point[axis] - f64::from(hi[axis])) as f32);
+    (hi, lo)
+}
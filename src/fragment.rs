@@ -0,0 +1,103 @@
+use wgpu::*;
+
+use crate::{globals::GlobalsState, shaders::Shaders};
+
+/// Downlevel replacement for [`ComputeState`](crate::compute::ComputeState).
+///
+/// Evaluates the drawing shader in a fullscreen fragment pass
+/// and renders into an offscreen texture, which is then presented like the compute output.
+pub struct FragmentDrawState {
+    pub pipeline: RenderPipeline,
+    pub globals_bind_group: BindGroup,
+    pub output_texture: Texture,
+    pub output_view: TextureView,
+}
+
+impl FragmentDrawState {
+    /// Format of the offscreen texture the drawing shader renders into
+    pub const FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+    pub fn new(
+        device: &Device,
+        shaders: &Shaders,
+        globals: &GlobalsState,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let output_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Fragment Output Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Fragment Drawing Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Fragment Drawing Pipeline Layout"),
+                bind_group_layouts: &[&globals.bind_group_layout],
+                push_constant_ranges: &globals.push_constant_ranges(ShaderStages::FRAGMENT),
+            })),
+            vertex: VertexState {
+                compilation_options: Default::default(),
+                module: &shaders.drawing,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shaders.drawing,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: Self::FORMAT,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: shaders.pipeline_cache.as_deref(),
+        });
+
+        Self {
+            pipeline,
+            globals_bind_group: globals.create_bind_group(device),
+            output_texture,
+            output_view,
+        }
+    }
+
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, globals: &GlobalsState) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Fragment Drawing Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.globals_bind_group, &[]);
+        globals.set_render_push_constants(&mut render_pass);
+        render_pass.draw(0..3, 0..1);
+    }
+}
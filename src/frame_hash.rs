@@ -0,0 +1,92 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use wgpu::*;
+
+/// Hashes the pixels of every drawn frame, to compare runs across machines or code changes.
+/// Each frame is read back before the next one is drawn, so this is slow
+pub struct FrameHasher {
+    output: Box<dyn Write>,
+}
+
+impl FrameHasher {
+    /// Write one line per frame to `path`, or to stdout for `-`
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let output: Box<dyn Write> = if path == Path::new("-") {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        Ok(Self { output })
+    }
+
+    /// Read back `texture` and write its BLAKE3 hash for `frame`
+    pub fn hash_frame(&mut self, device: &Device, queue: &Queue, texture: &Texture, frame: u32) {
+        let hash = blake3::hash(&read_texture(device, queue, texture));
+        if let Err(err) = writeln!(self.output, "{frame} {hash}") {
+            log::error!("Failed to write frame hash: {err}");
+        }
+    }
+}
+
+/// Copy `texture` back to the CPU, rows are tightly packed
+pub fn read_texture(device: &Device, queue: &Queue, texture: &Texture) -> Vec<u8> {
+    let bytes_per_texel = texture
+        .format()
+        .block_copy_size(None)
+        .expect("Frame texture has no fixed texel size");
+    let row_size = texture.width() * bytes_per_texel;
+    // Copies need rows aligned, the padding is dropped afterwards
+    let padded_row_size = row_size.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Frame Readback Buffer"),
+        size: (padded_row_size * texture.height()) as BufferAddress,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Frame Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_size),
+                rows_per_image: None,
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(MapMode::Read, |_| {});
+    device.poll(Maintain::Wait);
+
+    slice
+        .get_mapped_range()
+        .chunks_exact(padded_row_size as usize)
+        .flat_map(|row| &row[..row_size as usize])
+        .copied()
+        .collect()
+}
+
+/// Decode a half precision float, as stored in [`OUTPUT_FORMAT`](crate::compute::OUTPUT_FORMAT) textures
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((bits >> 10) & 0x1f);
+    let mantissa = f32::from(bits & 0x3ff);
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
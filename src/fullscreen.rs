@@ -0,0 +1,42 @@
+use clap::ValueEnum;
+use winit::window::{Fullscreen, Window};
+
+/// How the window covers the screen with `--fullscreen`, F11 or Alt+Enter
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FullscreenMode {
+    /// A window without decorations covering the monitor, switches instantly
+    #[default]
+    Borderless,
+    /// Takes over the monitor at its largest video mode, which may skip the compositor
+    Exclusive,
+}
+
+impl FullscreenMode {
+    /// Fullscreen on the monitor the window is on.
+    /// Falls back to borderless where exclusive mode isn't available, like on Wayland
+    pub fn fullscreen(self, window: &Window) -> Fullscreen {
+        let monitor = window.current_monitor();
+        let video_mode = match (self, &monitor) {
+            (FullscreenMode::Exclusive, Some(monitor)) => {
+                monitor.video_modes().max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate_millihertz())
+                })
+            }
+            _ => None,
+        };
+        match video_mode {
+            Some(video_mode) => Fullscreen::Exclusive(video_mode),
+            None => Fullscreen::Borderless(monitor),
+        }
+    }
+
+    /// Leave fullscreen, or enter it if the window is windowed
+    pub fn toggle(self, window: &Window) {
+        if window.fullscreen().is_some() {
+            window.set_fullscreen(None);
+        } else {
+            window.set_fullscreen(Some(self.fullscreen(window)));
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::feedback::TriggerAction;
+#[cfg(not(feature = "gamepad"))]
+use crate::globals::{Gamepad, MAX_GAMEPADS};
+
+/// Buttons of a gamepad, named by where they are: `south` is A on an Xbox controller and the cross on a PlayStation one.
+/// Bit `n` of [`Gamepad::buttons`](crate::globals::Gamepad::buttons) is set while the `n`th of them is held
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    /// The button with the logo of the console in the middle
+    Mode,
+    /// Pressing the left stick
+    LeftThumb,
+    RightThumb,
+    DpadUp,
+    DpadDown,
+    DpadLeft,
+    DpadRight,
+}
+
+impl GamepadButton {
+    /// The bit of the button in [`Gamepad::buttons`](crate::globals::Gamepad::buttons)
+    #[cfg(feature = "gamepad")]
+    pub fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// `[gamepad]` of the config file: the action taken when a button is pressed, e.g. `start = "pause"`
+pub type GamepadBindings = BTreeMap<GamepadButton, TriggerAction>;
+
+/// Used without a `[gamepad]` table: start pauses, select takes a screenshot
+/// and the right bumper switches to the next preset
+pub fn default_bindings() -> GamepadBindings {
+    BTreeMap::from([
+        (GamepadButton::Start, TriggerAction::Pause),
+        (GamepadButton::Select, TriggerAction::Screenshot),
+        (GamepadButton::RightBumper, TriggerAction::NextPreset),
+    ])
+}
+
+#[cfg(feature = "gamepad")]
+pub use input::GamepadInput;
+
+/// Stand-in for builds without the `gamepad` feature, which never opens
+#[cfg(not(feature = "gamepad"))]
+pub struct GamepadInput;
+
+#[cfg(not(feature = "gamepad"))]
+impl GamepadInput {
+    pub fn open(_bindings: GamepadBindings) -> Result<Self, String> {
+        Err("built without the `gamepad` feature".to_owned())
+    }
+
+    pub fn poll(&mut self) -> bool {
+        false
+    }
+
+    pub fn take_actions(&mut self) -> Vec<TriggerAction> {
+        Vec::new()
+    }
+
+    pub fn gamepads(&self) -> [Gamepad; MAX_GAMEPADS] {
+        [Gamepad::default(); MAX_GAMEPADS]
+    }
+}
+
+#[cfg(feature = "gamepad")]
+mod input {
+    use std::mem;
+
+    use gilrs::{Axis, Button, EventType, Gilrs};
+
+    use super::{GamepadBindings, GamepadButton};
+    use crate::{
+        feedback::TriggerAction,
+        globals::{Gamepad, MAX_GAMEPADS},
+    };
+
+    /// The buttons of gilrs, which calls the bumpers triggers and the triggers `LeftTrigger2` and `RightTrigger2`
+    const BUTTONS: [(GamepadButton, Button); 17] = [
+        (GamepadButton::South, Button::South),
+        (GamepadButton::East, Button::East),
+        (GamepadButton::North, Button::North),
+        (GamepadButton::West, Button::West),
+        (GamepadButton::LeftBumper, Button::LeftTrigger),
+        (GamepadButton::RightBumper, Button::RightTrigger),
+        (GamepadButton::LeftTrigger, Button::LeftTrigger2),
+        (GamepadButton::RightTrigger, Button::RightTrigger2),
+        (GamepadButton::Select, Button::Select),
+        (GamepadButton::Start, Button::Start),
+        (GamepadButton::Mode, Button::Mode),
+        (GamepadButton::LeftThumb, Button::LeftThumb),
+        (GamepadButton::RightThumb, Button::RightThumb),
+        (GamepadButton::DpadUp, Button::DPadUp),
+        (GamepadButton::DpadDown, Button::DPadDown),
+        (GamepadButton::DpadLeft, Button::DPadLeft),
+        (GamepadButton::DpadRight, Button::DPadRight),
+    ];
+
+    /// Follows the gamepads connected now or later, and the actions of the buttons pressed on them
+    pub struct GamepadInput {
+        gilrs: Gilrs,
+        bindings: GamepadBindings,
+        actions: Vec<TriggerAction>,
+    }
+
+    impl GamepadInput {
+        pub fn open(bindings: GamepadBindings) -> Result<Self, String> {
+            let gilrs = Gilrs::new().map_err(|err| err.to_string())?;
+            for (_, gamepad) in gilrs.gamepads() {
+                log::info!("Gamepad connected: {}", gamepad.name());
+            }
+            Ok(Self {
+                gilrs,
+                bindings,
+                actions: Vec::new(),
+            })
+        }
+
+        /// Take in what happened since the last call, returns whether there was any input
+        pub fn poll(&mut self) -> bool {
+            let mut input = false;
+            while let Some(event) = self.gilrs.next_event() {
+                match event.event {
+                    EventType::ButtonPressed(pressed, _) => {
+                        input = true;
+                        let action = BUTTONS
+                            .iter()
+                            .find(|(_, button)| *button == pressed)
+                            .and_then(|(button, _)| self.bindings.get(button));
+                        if let Some(action) = action {
+                            self.actions.push(action.clone());
+                        }
+                    }
+                    EventType::Connected => {
+                        log::info!("Gamepad connected: {}", self.gilrs.gamepad(event.id).name());
+                    }
+                    EventType::Disconnected => {
+                        log::info!(
+                            "Gamepad disconnected: {}",
+                            self.gilrs.gamepad(event.id).name()
+                        );
+                    }
+                    _ => input = true,
+                }
+            }
+            input
+        }
+
+        /// Actions of the buttons pressed since the last call
+        pub fn take_actions(&mut self) -> Vec<TriggerAction> {
+            mem::take(&mut self.actions)
+        }
+
+        /// The connected gamepads, each in the place of the order it was first connected in
+        pub fn gamepads(&self) -> [Gamepad; MAX_GAMEPADS] {
+            let mut gamepads = [Gamepad::default(); MAX_GAMEPADS];
+            for (id, gamepad) in self.gilrs.gamepads() {
+                let Some(slot) = gamepads.get_mut(usize::from(id)) else {
+                    continue;
+                };
+                let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+                *slot = Gamepad {
+                    left_stick: [
+                        gamepad.value(Axis::LeftStickX),
+                        gamepad.value(Axis::LeftStickY),
+                    ],
+                    right_stick: [
+                        gamepad.value(Axis::RightStickX),
+                        gamepad.value(Axis::RightStickY),
+                    ],
+                    triggers: [
+                        trigger(Button::LeftTrigger2),
+                        trigger(Button::RightTrigger2),
+                    ],
+                    buttons: BUTTONS
+                        .iter()
+                        .filter(|(_, button)| gamepad.is_pressed(*button))
+                        .fold(0, |bits, (button, _)| bits | button.bit()),
+                    connected: 1,
+                };
+            }
+            gamepads
+        }
+    }
+}
@@ -0,0 +1,611 @@
+use web_time::Instant;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{audio::AudioTexture, blue_noise::BlueNoiseTexture, fractal::FractalControls};
+
+/// Values available to every drawing shader as `globals`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Globals {
+    /// Size of the drawn image in pixels
+    pub resolution: [f32; 2],
+    /// Free parameters in `0..1` for the shader to interpret, adjusted by touch controls
+    pub params: [f32; 2],
+    /// Number of frames drawn so far
+    pub frame: u32,
+    /// Seconds since start
+    pub time: f32,
+    /// Position of the cursor in pixels of the drawn image
+    pub mouse: [f32; 2],
+    /// Seed of the piece of a `--generate`d edition, or `--seed`
+    pub seed: u32,
+    /// Random value drawn from `seed` and `frame`, the same on every run and machine.
+    /// Filled in when the globals are handed to the GPU
+    pub random: u32,
+    /// Pixel of the whole image the drawn part starts at, moved tile by tile by [`tiled`](crate::tiled) renders
+    pub offset: [u32; 2],
+    /// Pixel of the drawn image the compute pass of `draw` starts at.
+    /// Filled in from [`GlobalsState::set_region`] when the globals are handed to the GPU
+    pub region_offset: [u32; 2],
+    /// Pixels from `region_offset` on the compute pass of `draw` covers, the whole image unless set otherwise
+    pub region_size: [u32; 2],
+}
+
+/// Rectangle of pixels of the drawn image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub origin: [u32; 2],
+    pub size: [u32; 2],
+}
+
+impl Region {
+    /// The smallest region covering both
+    pub fn union(self, other: Region) -> Region {
+        let origin = [0, 1].map(|axis| self.origin[axis].min(other.origin[axis]));
+        let end = [0, 1].map(|axis| {
+            let end = |region: Region| region.origin[axis].saturating_add(region.size[axis]);
+            end(self).max(end(other))
+        });
+        Region {
+            origin,
+            size: [end[0] - origin[0], end[1] - origin[1]],
+        }
+    }
+}
+
+/// Where the scene is viewed from, available to drawing shaders as `camera`
+/// and moved by the [`CameraControls`](crate::camera::CameraControls).
+/// Bound as uniform buffer next to the globals, laid out like the WGSL struct with `vec3` aligned to 16 bytes
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, serde::Serialize)]
+pub struct Camera {
+    pub position: [f32; 3],
+    /// Vertical field of view in radians
+    pub fov: f32,
+    /// Unit vector the camera looks along
+    pub forward: [f32; 3],
+    #[serde(skip)]
+    pub _padding0: u32,
+    /// Unit vector pointing to the right of the image
+    pub right: [f32; 3],
+    #[serde(skip)]
+    pub _padding1: u32,
+    /// Unit vector pointing to the top of the image
+    pub up: [f32; 3],
+    #[serde(skip)]
+    pub _padding2: u32,
+}
+
+impl Camera {
+    /// A camera at `position` looking along `forward` with the world's y axis up
+    pub fn new(position: [f32; 3], forward: [f32; 3], fov: f32) -> Self {
+        let right = normalize(cross(forward, [0.0, 1.0, 0.0]));
+        Self {
+            position,
+            fov,
+            forward,
+            _padding0: 0,
+            right,
+            _padding1: 0,
+            up: cross(right, forward),
+            _padding2: 0,
+        }
+    }
+}
+
+impl Default for Camera {
+    /// Looking down at the origin from slightly above
+    fn default() -> Self {
+        Self::new([0.0, 1.2, 4.0], normalize([0.0, -0.3, -1.0]), 0.8)
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = v.iter().map(|c| c * c).sum::<f32>().sqrt();
+    v.map(|c| c / length)
+}
+
+/// The part of the complex plane the `fractal` preset shows, available to drawing shaders as `fractal_view`
+/// and moved by the [`FractalControls`].
+///
+/// f32 runs out of precision beyond a zoom of about 1e5, so points are split into a rounded `hi`
+/// and the `lo` remainder, which the shader adds up in double-single arithmetic.
+/// Laid out like the WGSL struct in `src/shaders/globals.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FractalView {
+    pub center_hi: [f32; 2],
+    pub center_lo: [f32; 2],
+    /// Constant of the Julia set
+    pub julia_hi: [f32; 2],
+    pub julia_lo: [f32; 2],
+    /// Plane units per pixel of the drawn image
+    pub scale: f32,
+    /// 1 to draw the Julia set of `julia_hi` + `julia_lo` instead of the Mandelbrot set
+    pub julia: u32,
+    pub max_iterations: u32,
+    /// Always 1, see `src/shaders/fractal.wgsl`
+    pub one: f32,
+}
+
+/// Fingers handed to drawing shaders as `touches`, at most this many
+pub const MAX_TOUCHES: usize = 10;
+
+/// [`TouchPoint::phase`] of a finger that went down since the previous frame
+pub const TOUCH_STARTED: u32 = 0;
+/// [`TouchPoint::phase`] of a finger held on the screen
+pub const TOUCH_MOVED: u32 = 1;
+/// [`TouchPoint::phase`] of a finger lifted since the previous frame, left out from the next one
+pub const TOUCH_ENDED: u32 = 2;
+/// [`TouchPoint::phase`] of a touch the platform took away, e.g. for a system gesture
+pub const TOUCH_CANCELLED: u32 = 3;
+
+/// A finger on the screen, see [`Touches`]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TouchPoint {
+    /// Position in pixels of the drawn image, like [`Globals::mouse`]
+    pub position: [f32; 2],
+    /// Stays the same while the finger is down, the lower bits of the platform's id
+    pub id: u32,
+    /// [`TOUCH_STARTED`], [`TOUCH_MOVED`], [`TOUCH_ENDED`] or [`TOUCH_CANCELLED`]
+    pub phase: u32,
+}
+
+/// Fingers on the screen and the view moved by two finger gestures, available to drawing shaders as `touches`
+/// and tracked by the [`TouchInput`](crate::touch::TouchInput).
+///
+/// Pinching and twisting scales and turns the image around the fingers, `touch_view(pixel)` in
+/// `src/shaders/globals.wgsl` is the point of the image shown at `pixel` that way.
+/// Laid out like the WGSL struct there
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Touches {
+    /// The first [`Touches::count`] are the fingers, in the order they went down
+    pub points: [TouchPoint; MAX_TOUCHES],
+    /// Where the pixel at the origin of the image is moved, in pixels
+    pub pan: [f32; 2],
+    /// Scale from pinching, 1 before
+    pub zoom: f32,
+    /// Radians turned by twisting two fingers, clockwise on screen as y points down
+    pub rotation: f32,
+    pub count: u32,
+    pub _padding: u32,
+}
+
+impl Default for Touches {
+    fn default() -> Self {
+        Self {
+            points: [TouchPoint::default(); MAX_TOUCHES],
+            pan: [0.0, 0.0],
+            zoom: 1.0,
+            rotation: 0.0,
+            count: 0,
+            _padding: 0,
+        }
+    }
+}
+
+/// Gamepads handed to drawing shaders as `gamepads`, at most this many
+pub const MAX_GAMEPADS: usize = 4;
+
+/// A gamepad, available to drawing shaders as one of the `gamepads` with `--gamepad`.
+/// All zero while nothing is connected in its place.
+/// Laid out like the WGSL struct in `src/shaders/globals.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Gamepad {
+    /// In `-1..1`, with y pointing up
+    pub left_stick: [f32; 2],
+    pub right_stick: [f32; 2],
+    /// How far the left and right trigger are pulled, in `0..1`
+    pub triggers: [f32; 2],
+    /// A bit per held [`GamepadButton`](crate::gamepad::GamepadButton), in the order they are declared
+    pub buttons: u32,
+    /// 1 while connected
+    pub connected: u32,
+}
+
+/// The pointer over the window, handed to the `overlay` of compute drawing shaders as `pointer`,
+/// see `src/shaders/overlay.wgsl`. Kept out of the [`Globals`], so the drawn image doesn't depend on it
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Pointer {
+    /// Position of the cursor in pixels of the drawn image, like [`Globals::mouse`]
+    pub position: [f32; 2],
+    /// Bit 0 is set while the left button is held, bit 1 for the right and bit 2 for the middle one
+    pub buttons: u32,
+    /// 1 while the window has focus, the overlay is only drawn then
+    pub focused: u32,
+}
+
+/// Size of the push constant range holding the [`Globals`]
+pub const PUSH_CONSTANT_SIZE: u32 = std::mem::size_of::<Globals>() as u32;
+
+/// Whether to request [`Features::PUSH_CONSTANTS`] for the [`Globals`] on `adapter`.
+/// The GL backend only emulates them with uniforms, so it sticks to the uniform buffer
+pub fn push_constants_available(adapter: &Adapter) -> bool {
+    adapter.get_info().backend != Backend::Gl
+        && adapter.features().contains(Features::PUSH_CONSTANTS)
+        && adapter.limits().max_push_constant_size >= PUSH_CONSTANT_SIZE
+}
+
+/// Whether `device` was created with room for the [`Globals`] in push constants
+pub fn push_constants_enabled(device: &Device) -> bool {
+    device.features().contains(Features::PUSH_CONSTANTS)
+        && device.limits().max_push_constant_size >= PUSH_CONSTANT_SIZE
+}
+
+/// The [`Globals`] handed to the drawing pipelines every frame.
+///
+/// Where the device supports push constants, they are set with each pass
+/// instead of being written to the uniform buffer.
+/// The uniform bind group stays part of the pipeline layouts either way, so bind group indices don't change
+pub struct GlobalsState {
+    pub globals: Globals,
+    pub buffer: Buffer,
+    pub bind_group_layout: BindGroupLayout,
+    /// Whether the drawing shaders declare `globals` as `var<push_constant>`, see [`Shaders::new`](crate::shaders::Shaders::new)
+    pub push_constants: bool,
+    /// Bound next to the uniform buffer
+    pub audio: AudioTexture,
+    /// Bound after `camera`
+    pub blue_noise: BlueNoiseTexture,
+    /// Bound after `audio`, set with [`GlobalsState::set_camera`]
+    pub camera: Camera,
+    camera_buffer: Buffer,
+    /// Bound after `blue_noise`, set with [`GlobalsState::set_fractal_view`]
+    pub fractal_view: FractalView,
+    fractal_view_buffer: Buffer,
+    /// Bound after `fractal_view`, set with [`GlobalsState::set_touches`]
+    pub touches: Touches,
+    touches_buffer: Buffer,
+    /// Bound after `touches`, set with [`GlobalsState::set_gamepads`]
+    pub gamepads: [Gamepad; MAX_GAMEPADS],
+    gamepads_buffer: Buffer,
+    /// Reference point of [`Globals::time`]
+    pub start: Instant,
+    /// Advance [`Globals::time`] by this many seconds per frame instead of following the clock,
+    /// for reproducible frames
+    pub fixed_timestep: Option<f32>,
+    /// Size of the drawn image
+    size: [u32; 2],
+    /// Part of the image `draw` is dispatched over, see [`GlobalsState::set_region`]
+    region: Option<Region>,
+}
+
+impl GlobalsState {
+    pub fn new(device: &Device, queue: &Queue, width: u32, height: u32) -> Self {
+        let globals = Globals {
+            resolution: [width as f32, height as f32],
+            params: [0.5, 0.5],
+            frame: 0,
+            time: 0.0,
+            mouse: [0.0, 0.0],
+            seed: 0,
+            random: 0,
+            offset: [0, 0],
+            region_offset: [0, 0],
+            region_size: [width, height],
+        };
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Globals Buffer"),
+            contents: bytemuck::bytes_of(&globals),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Globals Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let camera = Camera::default();
+        let camera_buffer = create_uniform_buffer(device, "Camera Buffer", &camera);
+        let fractal_view = FractalControls::new().view([width as f32, height as f32]);
+        let fractal_view_buffer =
+            create_uniform_buffer(device, "Fractal View Buffer", &fractal_view);
+        let touches = Touches::default();
+        let touches_buffer = create_uniform_buffer(device, "Touches Buffer", &touches);
+        let gamepads = [Gamepad::default(); MAX_GAMEPADS];
+        let gamepads_buffer = create_uniform_buffer(device, "Gamepads Buffer", &gamepads);
+
+        Self {
+            globals,
+            buffer,
+            bind_group_layout,
+            push_constants: push_constants_enabled(device),
+            audio: AudioTexture::new(device),
+            blue_noise: BlueNoiseTexture::new(device, queue),
+            camera,
+            camera_buffer,
+            fractal_view,
+            fractal_view_buffer,
+            touches,
+            touches_buffer,
+            gamepads,
+            gamepads_buffer,
+            start: Instant::now(),
+            fixed_timestep: None,
+            size: [width, height],
+            region: None,
+        }
+    }
+
+    /// A new state for `device` drawing `width`×`height` pixels, carrying over the values and the clock
+    pub fn recreate(&self, device: &Device, queue: &Queue, width: u32, height: u32) -> Self {
+        let mut globals = Self::new(device, queue, width, height);
+        globals.globals = Globals {
+            resolution: [width as f32, height as f32],
+            ..self.globals
+        };
+        globals.start = self.start;
+        globals.fixed_timestep = self.fixed_timestep;
+        globals.set_camera(queue, self.camera);
+        globals.set_touches(queue, self.touches);
+        globals.set_gamepads(queue, self.gamepads);
+        globals
+    }
+
+    /// Bind group for pipelines which include [`GlobalsState::bind_group_layout`]
+    pub fn create_bind_group(&self, device: &Device) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Globals Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&self.audio.view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.camera_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&self.blue_noise.view),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: self.fractal_view_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: self.touches_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 6,
+                    resource: self.gamepads_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Push constant ranges for pipelines drawing with the globals in `stages`
+    pub fn push_constant_ranges(&self, stages: ShaderStages) -> Vec<PushConstantRange> {
+        if self.push_constants {
+            vec![PushConstantRange {
+                stages,
+                range: 0..PUSH_CONSTANT_SIZE,
+            }]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Hand the globals to a compute pass, when they aren't read from the uniform buffer
+    pub fn set_compute_push_constants(&self, compute_pass: &mut ComputePass) {
+        if self.push_constants {
+            compute_pass.set_push_constants(0, bytemuck::bytes_of(&self.uploaded()));
+        }
+    }
+
+    /// Hand the globals to the fragment stage of a render pass,
+    /// when they aren't read from the uniform buffer
+    pub fn set_render_push_constants(&self, render_pass: &mut RenderPass) {
+        if self.push_constants {
+            render_pass.set_push_constants(
+                ShaderStages::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&self.uploaded()),
+            );
+        }
+    }
+
+    /// Advance to the next frame, uploading the values unless they go through push constants
+    pub fn next_frame(&mut self, queue: &Queue) {
+        self.globals.frame += 1;
+        self.globals.time = match self.fixed_timestep {
+            Some(timestep) => self.globals.frame as f32 * timestep,
+            None => self.start.elapsed().as_secs_f32(),
+        };
+        self.write(queue);
+    }
+
+    /// Move [`Globals::time`] `offset` fixed timesteps past the current frame, for sub-frame samples.
+    /// Does nothing while time follows the clock
+    pub fn set_subframe(&mut self, queue: &Queue, offset: f32) {
+        if let Some(timestep) = self.fixed_timestep {
+            self.globals.time = (self.globals.frame as f32 + offset) * timestep;
+            self.write(queue);
+        }
+    }
+
+    /// Jump to `frame` at `time` instead of advancing, e.g. to the one a sync leader draws
+    pub fn set_frame(&mut self, queue: &Queue, frame: u32, time: f32) {
+        self.globals.frame = frame;
+        self.globals.time = time;
+        self.write(queue);
+    }
+
+    /// Move the camera, takes effect with the next frame
+    pub fn set_camera(&mut self, queue: &Queue, camera: Camera) {
+        self.camera = camera;
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&camera));
+    }
+
+    /// Show another part of the complex plane, takes effect with the next frame
+    pub fn set_fractal_view(&mut self, queue: &Queue, view: FractalView) {
+        self.fractal_view = view;
+        queue.write_buffer(&self.fractal_view_buffer, 0, bytemuck::bytes_of(&view));
+    }
+
+    /// Hand the fingers on the screen to the shaders, takes effect with the next frame
+    pub fn set_touches(&mut self, queue: &Queue, touches: Touches) {
+        self.touches = touches;
+        queue.write_buffer(&self.touches_buffer, 0, bytemuck::bytes_of(&touches));
+    }
+
+    /// Hand the state of the gamepads to the shaders, takes effect with the next frame
+    pub fn set_gamepads(&mut self, queue: &Queue, gamepads: [Gamepad; MAX_GAMEPADS]) {
+        self.gamepads = gamepads;
+        queue.write_buffer(&self.gamepads_buffer, 0, bytemuck::cast_slice(&gamepads));
+    }
+
+    /// Dispatch the compute pass of `draw` over `region` of the image only, `None` for all of it.
+    /// The pixels outside keep what was drawn before, for incremental updates of images that only change
+    /// in places. With a predicate the workgroups of the whole image are dispatched to draw the region,
+    /// tile classification and variants selected with Tab draw the whole image.
+    /// Takes effect once the globals are written
+    pub fn set_region(&mut self, region: Option<Region>) {
+        self.region = region;
+    }
+
+    /// The part of the image `draw` is dispatched over, clamped to it
+    pub fn region(&self) -> Region {
+        let [width, height] = self.size;
+        self.region.map_or(
+            Region {
+                origin: [0, 0],
+                size: self.size,
+            },
+            |Region { origin, size }| {
+                let origin = [origin[0].min(width), origin[1].min(height)];
+                Region {
+                    origin,
+                    size: [
+                        size[0].min(width - origin[0]),
+                        size[1].min(height - origin[1]),
+                    ],
+                }
+            },
+        )
+    }
+
+    /// Upload the globals, a no-op with push constants which are set in every pass
+    pub fn write(&self, queue: &Queue) {
+        if !self.push_constants {
+            queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.uploaded()));
+        }
+    }
+
+    /// The globals as shaders see them, with [`Globals::random`] and the region filled in
+    fn uploaded(&self) -> Globals {
+        let region = self.region();
+        Globals {
+            random: frame_random(self.globals.seed, self.globals.frame),
+            region_offset: region.origin,
+            region_size: region.size,
+            ..self.globals
+        }
+    }
+}
+
+/// Hash of `seed` and `frame`, Chris Wellons' lowbias32 of both combined
+pub fn frame_random(seed: u32, frame: u32) -> u32 {
+    let mut h = frame.wrapping_add(seed.wrapping_mul(0x9e37_79b9));
+    h = (h ^ (h >> 16)).wrapping_mul(0x7feb_352d);
+    h = (h ^ (h >> 15)).wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+fn create_uniform_buffer<T: bytemuck::Pod>(device: &Device, label: &str, value: &T) -> Buffer {
+    device.create_buffer_init(&BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::bytes_of(value),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    })
+}
@@ -0,0 +1,69 @@
+use naga::{
+    ShaderStage,
+    back::wgsl::{self, WriterFlags},
+    front::glsl::{Frontend, Options},
+    valid::{Capabilities, ValidationFlags, Validator},
+};
+
+/// Whether `extension` marks a GLSL drawing shader, translated with [`to_wgsl`]
+pub fn is_glsl(extension: &str) -> bool {
+    extension.eq_ignore_ascii_case("glsl") || extension.eq_ignore_ascii_case("frag")
+}
+
+/// Globals the GLSL sees, each filled from the field of `globals` of the same name
+const GLOBALS: &[(&str, &str)] = &[
+    ("vec2", "resolution"),
+    ("vec2", "params"),
+    ("uint", "frame"),
+    ("float", "time"),
+    ("vec2", "mouse"),
+    ("uint", "seed"),
+];
+
+/// Translate a drawing shader written in GLSL into WGSL, with naga's GLSL frontend.
+///
+/// Like in WGSL it defines `vec4 draw(uvec2 pixel)` and has no `main`,
+/// the [`GLOBALS`] are plain global variables set before each call of `draw`
+pub fn to_wgsl(text: &str) -> Result<String, String> {
+    let globals: Vec<String> = GLOBALS
+        .iter()
+        .map(|(ty, name)| format!("{ty} {name};"))
+        .collect();
+    // Declared in front of the first line that isn't a directive like `#version`,
+    // so errors point at the lines of the file
+    let mut lines: Vec<&str> = text.lines().collect();
+    let first = lines
+        .iter()
+        .position(|line| !line.trim_start().starts_with('#'))
+        .unwrap_or(lines.len());
+    let declarations = format!("{} {}", globals.join(" "), lines.get(first).unwrap_or(&""));
+    lines.splice(first..(first + 1).min(lines.len()), [declarations.as_str()]);
+    let source = format!("{}\nvoid main() {{}}\n", lines.join("\n"));
+
+    let mut module = Frontend::default()
+        .parse(&Options::from(ShaderStage::Fragment), &source)
+        .map_err(|err| err.emit_to_string(&source))?;
+    // The `main` added above is dropped, the drawing shader brings its own entry points
+    module.entry_points.clear();
+    // `draw` calls the GLSL one after setting the globals
+    for (_, function) in module.functions.iter_mut() {
+        match function.name.as_deref() {
+            Some("main") => function.name = Some("glsl_main".to_owned()),
+            Some("draw") => function.name = Some("glsl_draw".to_owned()),
+            _ => {}
+        }
+    }
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|err| err.emit_to_string(&source))?;
+    let wgsl = wgsl::write_string(&module, &info, WriterFlags::empty())
+        .map_err(|err| format!("Failed to translate GLSL: {err}"))?;
+
+    let assignments: String = GLOBALS
+        .iter()
+        .map(|(_, name)| format!("    {name} = globals.{name};\n"))
+        .collect();
+    Ok(format!(
+        "{wgsl}\nfn draw(pixel: vec2<u32>) -> vec4<f32> {{\n{assignments}    return glsl_draw(pixel);\n}}\n"
+    ))
+}
@@ -1,40 +1,161 @@
-use std::sync::Arc;
-use wgpu::{Device, Queue, Surface, SurfaceConfiguration, TextureFormat};
+use std::{fmt, sync::Arc};
+use wgpu::{Adapter, Device, Instance, Queue, Surface, SurfaceConfiguration, TextureFormat};
 use winit::window::Window;
 
+use crate::{
+    adapter::AdapterSelection, cli::Options, compute::ComputeState, fragment::FragmentDrawState,
+    globals, pipeline_cache, recovery::DeviceHealth,
+};
+
+/// How the drawing shader gets evaluated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawMode {
+    /// Compute shader writing into a storage texture, or a storage buffer copied into one,
+    /// see [`OutputStorage`](crate::compute::OutputStorage)
+    Compute,
+    /// Fragment shader rendering into an offscreen target,
+    /// for WebGL2/GLES class devices without compute shaders
+    Fragment,
+    /// Fragment shader evaluated by the render pass straight onto the surface,
+    /// picked automatically when the adapter can't render into the offscreen target either
+    Direct,
+}
+
+impl DrawMode {
+    /// Choose how to draw based on what the adapter supports
+    fn select(adapter: &wgpu::Adapter, downlevel: bool) -> Self {
+        let compute_supported = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+        // Without storage textures in any of the image's formats the drawing writes a storage buffer
+        let output_writable = ComputeState::output_storage(adapter).is_some();
+
+        // WebGL2/GLES class devices still render into the offscreen target of the fragment drawing
+        let offscreen_renderable = adapter
+            .get_texture_format_features(FragmentDrawState::FORMAT)
+            .allowed_usages
+            .contains(
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            );
+
+        let fallback = if !compute_supported {
+            "The adapter can't run compute shaders"
+        } else if !output_writable {
+            "The adapter can't write storage textures or buffers from compute shaders"
+        } else if downlevel {
+            return DrawMode::Fragment;
+        } else {
+            return DrawMode::Compute;
+        };
+        if offscreen_renderable {
+            log::warn!("{fallback}, drawing with fragment shaders");
+            DrawMode::Fragment
+        } else {
+            log::warn!("{fallback}, drawing straight to the window");
+            DrawMode::Direct
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GpuError {
+    Surface(wgpu::CreateSurfaceError),
+    NoAdapter,
+    Device(wgpu::RequestDeviceError),
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpuError::Surface(err) => write!(f, "Failed to create surface: {err}"),
+            GpuError::NoAdapter => write!(f, "Failed to find adapter, see --list-adapters"),
+            GpuError::Device(err) => write!(f, "Failed to create device: {err}"),
+        }
+    }
+}
+
 pub struct GpuState {
+    /// Creates the surfaces of more windows, see [`OutputWindow`](crate::output_window::OutputWindow)
+    pub instance: Instance,
+    pub adapter: Adapter,
     pub device: Device,
     pub queue: Queue,
     pub surface: Surface<'static>,
     pub surface_format: TextureFormat,
     pub surface_config: SurfaceConfiguration,
+    pub draw_mode: DrawMode,
+    pub health: DeviceHealth,
+    /// Compiled pipelines kept between runs, see [`pipeline_cache`]
+    pub pipeline_cache: Option<Arc<wgpu::PipelineCache>>,
 }
 
 impl GpuState {
-    pub async fn new(window: &Arc<Window>, width: u32, height: u32) -> Self {
-        let instance = wgpu::Instance::default();
-        let surface = instance.create_surface(Arc::clone(window)).unwrap();
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                compatible_surface: Some(&surface),
-                ..Default::default()
-            })
+    pub async fn new(
+        window: &Arc<Window>,
+        width: u32,
+        height: u32,
+        options: &Options,
+    ) -> Result<Self, GpuError> {
+        let profile = options.profile;
+        let adapter_selection = AdapterSelection::new(options);
+        let instance = adapter_selection.create_instance();
+        let surface = instance
+            .create_surface(Arc::clone(window))
+            .map_err(GpuError::Surface)?;
+
+        let adapter = adapter_selection
+            .select(&instance, Some(&surface))
             .await
-            .expect("Failed to find adapter");
+            .ok_or(GpuError::NoAdapter)?;
+        log::info!("Using adapter {:?}", adapter.get_info().name);
+
+        let draw_mode = DrawMode::select(&adapter, options.downlevel);
+        let mut required_limits = match draw_mode {
+            DrawMode::Compute => profile.limits(&adapter),
+            DrawMode::Fragment | DrawMode::Direct => {
+                wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+            }
+        };
+        // Globals go through push constants where available, see `GlobalsState`
+        let mut required_features = wgpu::Features::empty();
+        if globals::push_constants_available(&adapter) {
+            required_features |= wgpu::Features::PUSH_CONSTANTS;
+            required_limits.max_push_constant_size = globals::PUSH_CONSTANT_SIZE;
+        }
+        // Sample counts other than 4 are adapter specific, see `GpuState::msaa_sample_count`
+        let msaa_features = wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+        if options.msaa > 1 && adapter.features().contains(msaa_features) {
+            required_features |= msaa_features;
+        }
+        required_features |= pipeline_cache::features(&adapter);
 
         let (device, queue) = adapter
-            .request_device(&Default::default(), None)
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_features,
+                    required_limits,
+                    ..Default::default()
+                },
+                None,
+            )
             .await
-            .expect("Failed to create device");
+            .map_err(GpuError::Device)?;
 
-        let surface_format = surface.get_capabilities(&adapter).formats[0];
+        // Formats are listed in the adapter's order of preference
+        let formats = surface.get_capabilities(&adapter).formats;
+        let prefer_srgb = options.color_space.prefers_srgb_surface(draw_mode);
+        let surface_format = formats
+            .iter()
+            .copied()
+            .find(|format| format.is_srgb() == prefer_srgb)
+            .unwrap_or(formats[0]);
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width,
             height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: profile.present_mode(),
             alpha_mode: wgpu::CompositeAlphaMode::Opaque,
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
@@ -42,13 +163,39 @@ impl GpuState {
 
         surface.configure(&device, &surface_config);
 
-        Self {
+        let health = DeviceHealth::watch(&device);
+        let pipeline_cache = pipeline_cache::load(&device, &adapter);
+
+        Ok(Self {
+            instance,
+            adapter,
             device,
             queue,
             surface,
             surface_format,
             surface_config,
-        }
+            draw_mode,
+            health,
+            pipeline_cache,
+        })
+    }
+
+    /// The highest sample count up to `requested` the surface format can be multisampled with
+    pub fn msaa_sample_count(&self, requested: u32) -> u32 {
+        let features = self.device.features();
+        let format_features =
+            if features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+                self.adapter
+                    .get_texture_format_features(self.surface_format)
+            } else {
+                self.surface_format.guaranteed_format_features(features)
+            };
+        [16, 8, 4, 2]
+            .into_iter()
+            .find(|&count| {
+                count <= requested && format_features.flags.sample_count_supported(count)
+            })
+            .unwrap_or(1)
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -60,4 +207,88 @@ impl GpuState {
     pub fn reconfigure_surface(&mut self) {
         self.surface.configure(&self.device, &self.surface_config);
     }
+
+    /// Create the surface of `window` again, after the system took it away while the app was suspended
+    pub fn recreate_surface(&mut self, window: &Arc<Window>) -> Result<(), GpuError> {
+        self.surface = self
+            .instance
+            .create_surface(Arc::clone(window))
+            .map_err(GpuError::Surface)?;
+        self.reconfigure_surface();
+        Ok(())
+    }
+}
+
+/// The first of `candidates` shaders on `adapter` can write to as storage texture.
+/// GL and mobile GPUs support fewer formats than WebGPU guarantees, e.g. GL has no `Rg32Float`.
+/// Formats WGSL has no name for are skipped
+pub fn storage_format(adapter: &Adapter, candidates: &[TextureFormat]) -> Option<TextureFormat> {
+    candidates.iter().copied().find(|&format| {
+        wgsl_storage_format(format).is_some()
+            && adapter
+                .get_texture_format_features(format)
+                .allowed_usages
+                .contains(wgpu::TextureUsages::STORAGE_BINDING)
+    })
+}
+
+/// Name of `format` in the declaration of a WGSL storage texture, `None` if it can't be one
+pub fn wgsl_storage_format(format: TextureFormat) -> Option<&'static str> {
+    let name = match format {
+        TextureFormat::Rgba8Unorm => "rgba8unorm",
+        TextureFormat::Rgba8Snorm => "rgba8snorm",
+        TextureFormat::Rgba8Uint => "rgba8uint",
+        TextureFormat::Rgba8Sint => "rgba8sint",
+        TextureFormat::Bgra8Unorm => "bgra8unorm",
+        TextureFormat::Rgba16Uint => "rgba16uint",
+        TextureFormat::Rgba16Sint => "rgba16sint",
+        TextureFormat::Rgba16Float => "rgba16float",
+        TextureFormat::R32Uint => "r32uint",
+        TextureFormat::R32Sint => "r32sint",
+        TextureFormat::R32Float => "r32float",
+        TextureFormat::Rg32Uint => "rg32uint",
+        TextureFormat::Rg32Sint => "rg32sint",
+        TextureFormat::Rg32Float => "rg32float",
+        TextureFormat::Rgba32Uint => "rgba32uint",
+        TextureFormat::Rgba32Sint => "rgba32sint",
+        TextureFormat::Rgba32Float => "rgba32float",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Wait for `future`. The browser's thread can't block, so on the web this only returns
+/// what is ready right away, which is everything with WebGL but little with WebGPU
+pub fn block_on<F: Future>(future: F) -> Option<F::Output> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return Some(pollster::block_on(future));
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+        match std::pin::pin!(future).poll(&mut context) {
+            std::task::Poll::Ready(output) => Some(output),
+            std::task::Poll::Pending => None,
+        }
+    }
+}
+
+/// Pop the innermost error scope and return its error.
+/// Errors arriving later on the web are only logged
+pub fn pop_error_scope(device: &Device) -> Option<wgpu::Error> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return pollster::block_on(device.pop_error_scope());
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let mut future = Box::pin(device.pop_error_scope());
+        block_on(future.as_mut()).unwrap_or_else(|| {
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Some(error) = future.await {
+                    log::error!("{error}");
+                }
+            });
+            None
+        })
+    }
 }
@@ -0,0 +1,177 @@
+use wgpu::{CommandEncoder, Queue, Texture, TextureView};
+
+use crate::{
+    accumulation::AccumulationState, bloom::BloomState, convolution::ConvolutionState,
+    downsample::DownsampleState, globals::GlobalsState, mipmap::MipmapState,
+    post_effect::PostEffect, render::LoadedImage, scene::Drawing,
+};
+
+/// Largest factor a node may scale its resolution by, see [`scaled`]
+pub const MAX_SCALE: f32 = 4.0;
+
+/// `size` scaled by `scale`, at least one pixel
+pub fn scaled(size: [u32; 2], scale: f32) -> [u32; 2] {
+    size.map(|size| ((size as f32 * scale).round() as u32).max(1))
+}
+
+/// What a node of the [`RenderGraph`] runs
+pub enum Pass {
+    /// An image from a file, which runs nothing
+    Image(LoadedImage),
+    Drawing(Drawing),
+    Accumulation(AccumulationState),
+    Downsample(DownsampleState),
+    Bloom(BloomState),
+    Convolution(ConvolutionState),
+    PostEffect(PostEffect),
+    Mipmap(MipmapState),
+}
+
+impl Pass {
+    /// The texture the pass writes
+    fn output(&self) -> (&Texture, &TextureView) {
+        match self {
+            Pass::Image(image) => (&image.texture, &image.view),
+            Pass::Drawing(drawing) => (drawing.output_texture(), drawing.output_view()),
+            Pass::Accumulation(accumulation) => {
+                (&accumulation.output_texture, &accumulation.output_view)
+            }
+            Pass::Downsample(downsample) => (&downsample.output_texture, &downsample.output_view),
+            Pass::Bloom(bloom) => (&bloom.output_texture, &bloom.output_view),
+            Pass::Convolution(convolution) => {
+                (&convolution.output_texture, &convolution.output_view)
+            }
+            Pass::PostEffect(effect) => (&effect.output_texture, &effect.output_view),
+            Pass::Mipmap(mipmap) => (&mipmap.texture, &mipmap.view),
+        }
+    }
+
+    /// `inputs` are the textures of the nodes named as inputs, in order
+    fn encode(
+        &mut self,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        globals: &GlobalsState,
+        inputs: &[&Texture],
+    ) {
+        match self {
+            Pass::Image(_) => {}
+            Pass::Drawing(drawing) => drawing.encode(queue, encoder, globals),
+            Pass::Accumulation(accumulation) => accumulation.dispatch(queue, encoder),
+            Pass::Downsample(downsample) => downsample.dispatch(encoder),
+            Pass::Bloom(bloom) => bloom.dispatch(encoder),
+            Pass::Convolution(convolution) => convolution.dispatch(encoder),
+            Pass::PostEffect(effect) => effect.dispatch(encoder, globals),
+            // The only pass copying its input rather than sampling it through a bind group
+            Pass::Mipmap(mipmap) => mipmap.dispatch(encoder, inputs[0]),
+        }
+    }
+}
+
+struct Node {
+    name: String,
+    /// Indices of the nodes whose outputs the pass reads
+    inputs: Vec<usize>,
+    pass: Pass,
+}
+
+/// The passes producing the presented image.
+///
+/// Every node is named and writes one texture, which later nodes read by that name:
+/// [`RenderGraph::view`] hands out the texture to bind when creating a pass,
+/// and [`RenderGraph::add`] records the dependency.
+/// Nodes can only read outputs that already exist, so they are encoded in the order they were added,
+/// which always runs producers before their readers.
+/// The graph owns every pass and so every texture, which live until it is replaced
+#[derive(Default)]
+pub struct RenderGraph {
+    nodes: Vec<Node>,
+}
+
+impl RenderGraph {
+    fn index(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    /// The texture written by the node `name`, to bind when creating a pass reading it
+    pub fn view(&self, name: &str) -> Option<&TextureView> {
+        self.index(name)
+            .map(|index| self.nodes[index].pass.output().1)
+    }
+
+    pub fn texture(&self, name: &str) -> Option<&Texture> {
+        self.index(name)
+            .map(|index| self.nodes[index].pass.output().0)
+    }
+
+    /// Name of the last node, whose output is presented
+    pub fn last(&self) -> Option<&str> {
+        self.nodes.last().map(|node| node.name.as_str())
+    }
+
+    /// Add `pass` as node `name`, reading the outputs of the nodes named in `inputs`
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        inputs: &[&str],
+        pass: Pass,
+    ) -> Result<(), String> {
+        let name = name.into();
+        if self.index(&name).is_some() {
+            return Err(format!(
+                "The render graph already has a node named {name:?}"
+            ));
+        }
+        let inputs = inputs
+            .iter()
+            .map(|input| {
+                self.index(input).ok_or_else(|| {
+                    format!("{name:?} reads {input:?}, which isn't in the render graph")
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        self.nodes.push(Node { name, inputs, pass });
+        Ok(())
+    }
+
+    /// Add the pass `create`d from the output of the last node, reading it.
+    /// Does nothing while the graph is empty
+    pub fn add_after_last(
+        &mut self,
+        name: &str,
+        create: impl FnOnce(&TextureView) -> Pass,
+    ) -> Result<(), String> {
+        let Some(input) = self.last().map(str::to_owned) else {
+            return Ok(());
+        };
+        let pass = create(self.view(&input).unwrap());
+        self.add(name, &[&input], pass)
+    }
+
+    /// Names of the nodes, in the order they run
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().map(|node| node.name.as_str())
+    }
+
+    pub fn passes(&self) -> impl Iterator<Item = &Pass> {
+        self.nodes.iter().map(|node| &node.pass)
+    }
+
+    pub fn passes_mut(&mut self) -> impl Iterator<Item = &mut Pass> {
+        self.nodes.iter_mut().map(|node| &mut node.pass)
+    }
+
+    /// Encode every pass after the ones it reads from
+    pub fn encode(&mut self, queue: &Queue, encoder: &mut CommandEncoder, globals: &GlobalsState) {
+        for index in 0..self.nodes.len() {
+            let (earlier, rest) = self.nodes.split_at_mut(index);
+            let node = &mut rest[0];
+            let inputs: Vec<&Texture> = node
+                .inputs
+                .iter()
+                .map(|&input| earlier[input].pass.output().0)
+                .collect();
+            node.pass.encode(queue, encoder, globals, &inputs);
+        }
+    }
+}
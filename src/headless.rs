@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use wgpu::*;
+
+use crate::{
+    adapter::AdapterSelection,
+    cli::Options,
+    compute::ComputeState,
+    frame_hash,
+    globals::{self, GlobalsState},
+    gpu::DrawMode,
+    pipeline_cache, recording,
+    scene::SceneConfig,
+    shaders::Shaders,
+    tonemap::ColorSpace,
+};
+
+/// The `--shader` or `--example` drawn by a compute pass without a window,
+/// for commands writing images like `contact-sheet` and `--generate`
+pub struct Headless {
+    adapter: Adapter,
+    device: Device,
+    queue: Queue,
+    /// Set before each [`Headless::draw`]
+    pub globals: GlobalsState,
+    /// `globals.params` from the bundle of the shader, or the defaults
+    pub initial_params: [f32; 2],
+    compute_state: ComputeState,
+    /// Saved when dropped, for the next command to start faster
+    pipeline_cache: Option<Arc<PipelineCache>>,
+    srgb_output: bool,
+    /// Bound by `compute_state`
+    _storage_buffers: Vec<Buffer>,
+}
+
+impl Headless {
+    pub fn new(options: &Options, width: u32, height: u32) -> Result<Self, String> {
+        Self::create(options, width, height, None)
+    }
+
+    /// Run the `--shader` as a kernel over `width` × `height` invocations, writing `output_size` bytes
+    /// read back with [`Headless::run_to_vec`] instead of drawing images. A kernel brings its own `@compute`
+    /// entry points, the first of which is dispatched, and declares its output as `@group(0) @binding(0)`
+    pub fn kernel(
+        options: &Options,
+        width: u32,
+        height: u32,
+        output_size: u64,
+    ) -> Result<Self, String> {
+        Self::create(options, width, height, Some(output_size))
+    }
+
+    fn create(
+        options: &Options,
+        width: u32,
+        height: u32,
+        output_size: Option<u64>,
+    ) -> Result<Self, String> {
+        let (adapter, device, queue) = pollster::block_on(create_device(options))
+            .ok_or("No adapter with compute shader support found, see --list-adapters")?;
+
+        let mut config = SceneConfig::headless(options, width, height);
+        config.pipeline_cache = pipeline_cache::load(&device, &adapter);
+        if output_size.is_none() {
+            config.output_storage = ComputeState::output_storage(&adapter).ok_or(
+                "The adapter can't write storage textures or buffers from compute shaders",
+            )?;
+        }
+        let draw_src = config
+            .draw_source()
+            .map_err(|err| format!("Failed to read shader: {err}"))?;
+        let storage_buffers = config
+            .create_storage_buffers(&device)
+            .map_err(|err| format!("Failed to read storage buffer: {err}"))?;
+
+        device.push_error_scope(ErrorFilter::Validation);
+        let mut globals = GlobalsState::new(&device, &queue, width, height);
+        let initial_params = config.initial_params.unwrap_or(globals.globals.params);
+        globals.globals.params = initial_params;
+        let shaders = match output_size {
+            Some(_) => Shaders::kernel(&device, &draw_src, &config)?,
+            None => Shaders::new(&device, DrawMode::Compute, &draw_src, &config)?,
+        };
+        let mut builder = ComputeState::builder(&device, &shaders, &globals, width, height);
+        if let Some(size) = output_size {
+            builder = builder.output_buffer(size);
+        }
+        for buffer in &storage_buffers {
+            builder = builder.storage_buffer(buffer, true);
+        }
+        if let Some(entry_point) = &config.entry_point {
+            builder = builder.entry_point(entry_point);
+        }
+        let compute_state = builder.build();
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(error.to_string());
+        }
+
+        Ok(Self {
+            adapter,
+            device,
+            queue,
+            globals,
+            initial_params,
+            compute_state,
+            pipeline_cache: config.pipeline_cache,
+            srgb_output: options.color_space == ColorSpace::Srgb,
+            _storage_buffers: storage_buffers,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.compute_state.size()
+    }
+
+    /// Fill the output buffer of a [`Headless::kernel`] before it runs, starting at `offset` bytes
+    pub fn write_output<T: bytemuck::Pod>(&self, offset: u64, data: &[T]) {
+        if let Some(buffer) = self.compute_state.output_buffer() {
+            self.queue
+                .write_buffer(buffer, offset, bytemuck::cast_slice(data));
+        }
+    }
+
+    /// Run a [`Headless::kernel`] once with the current globals and read back its output buffer as `T`s.
+    /// The output keeps what the kernel wrote for the next run, the scratch buffer starts out zeroed
+    pub fn run_to_vec<T: bytemuck::Pod>(&self) -> Result<Vec<T>, String> {
+        self.device.push_error_scope(ErrorFilter::Validation);
+        self.compute_state.clear_scratch(&self.device, &self.queue);
+        self.globals.write(&self.queue);
+        let output = self
+            .compute_state
+            .run_to_vec(&self.device, &self.queue, &self.globals)
+            .ok_or("Only kernels write an output buffer")?;
+        if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(error.to_string());
+        }
+        Ok(output)
+    }
+
+    /// Read back the scratch buffer as it was left by the last run or image, see [`counters`](crate::counters)
+    pub fn read_scratch(&self) -> Result<Vec<u32>, String> {
+        self.compute_state
+            .read_scratch(&self.device, &self.queue)
+            .ok_or_else(|| "No scratch buffer, see --scratch-size".to_owned())
+    }
+
+    /// Draw once with the current globals and read back the image as 8-bit sRGB RGBA.
+    /// The scratch buffer starts out zeroed for every image
+    pub fn draw(&self) -> Result<Vec<u8>, String> {
+        self.device.push_error_scope(ErrorFilter::Validation);
+        self.compute_state.clear_scratch(&self.device, &self.queue);
+        self.globals.write(&self.queue);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Headless Encoder"),
+            });
+        self.compute_state.dispatch(&mut encoder, &self.globals);
+        self.queue.submit(Some(encoder.finish()));
+
+        let texture = self
+            .compute_state
+            .output_texture()
+            .ok_or("Kernels write a buffer, see Headless::run_to_vec")?;
+        let data = frame_hash::read_texture(&self.device, &self.queue, texture);
+        if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(error.to_string());
+        }
+        Ok(recording::texture_to_rgba8(
+            texture,
+            &data,
+            self.srgb_output,
+        ))
+    }
+}
+
+impl Drop for Headless {
+    fn drop(&mut self) {
+        if let Some(cache) = &self.pipeline_cache {
+            pipeline_cache::save(cache, &self.adapter);
+        }
+    }
+}
+
+/// A device of the `--adapter` for drawing without a window, `None` if it can't run compute shaders
+pub async fn create_device(options: &Options) -> Option<(Adapter, Device, Queue)> {
+    let adapter_selection = AdapterSelection::new(options);
+    let instance = adapter_selection.create_instance();
+    let adapter = adapter_selection.select(&instance, None).await?;
+    if !adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(DownlevelFlags::COMPUTE_SHADERS)
+    {
+        return None;
+    }
+
+    let mut required_features = pipeline_cache::features(&adapter);
+    if globals::push_constants_available(&adapter) {
+        required_features |= Features::PUSH_CONSTANTS;
+    }
+    adapter
+        .request_device(
+            &DeviceDescriptor {
+                required_features,
+                required_limits: adapter.limits(),
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .ok()
+        .map(|(device, queue)| (adapter, device, queue))
+}
@@ -0,0 +1,177 @@
+use std::sync::{Arc, OnceLock};
+
+use wgpu::*;
+
+/// Bins of the histogram, four per stop from 2⁻¹⁰ to 2⁶, see `src/shaders/histogram.wgsl`
+pub const BIN_COUNT: usize = 64;
+/// The image is analyzed every this many frames, and only once the last histogram arrived
+const ANALYSIS_INTERVAL: u32 = 4;
+/// Size of `bins`
+const BINS_SIZE: BufferAddress = (BIN_COUNT * 4) as BufferAddress;
+
+/// Luminance histogram of the presented image, counted by a compute pass into a storage buffer
+/// with atomics and read back asynchronously every few frames.
+/// Drawn over the image by the render pass while `shown`
+pub struct Histogram {
+    pub shown: bool,
+    pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+    bins: Buffer,
+    staging: Buffer,
+    /// Set once the staging buffer is mapped, `None` while nothing is being read back
+    mapped: Option<Arc<OnceLock<bool>>>,
+    /// Pixels per bin of the latest histogram that arrived
+    counts: [u32; BIN_COUNT],
+}
+
+impl Histogram {
+    pub fn new(device: &Device, pipeline_cache: Option<&PipelineCache>, shown: bool) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Histogram Shader"),
+            source: ShaderSource::Wgsl(include_str!("./shaders/histogram.wgsl").into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Histogram Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            compilation_options: Default::default(),
+            label: Some("Histogram Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Histogram Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            })),
+            module: &module,
+            entry_point: "main",
+            cache: pipeline_cache,
+        });
+
+        Self {
+            shown,
+            pipeline,
+            bind_group_layout,
+            bins: device.create_buffer(&BufferDescriptor {
+                label: Some("Histogram Bins Buffer"),
+                size: BINS_SIZE,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            staging: device.create_buffer(&BufferDescriptor {
+                label: Some("Histogram Readback Buffer"),
+                size: BINS_SIZE,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            mapped: None,
+            counts: [0; BIN_COUNT],
+        }
+    }
+
+    /// Count the pixels of `texture` after `frame` was drawn, every [`ANALYSIS_INTERVAL`] frames
+    pub fn analyze(&mut self, device: &Device, queue: &Queue, texture: &Texture, frame: u32) {
+        if !frame.is_multiple_of(ANALYSIS_INTERVAL) || self.mapped.is_some() {
+            return;
+        }
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Histogram Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.bins.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Histogram Encoder"),
+        });
+        encoder.clear_buffer(&self.bins, 0, None);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                timestamp_writes: None,
+                label: Some("Histogram Pass"),
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                texture.width().div_ceil(16),
+                texture.height().div_ceil(16),
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&self.bins, 0, &self.staging, 0, BINS_SIZE);
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = Arc::new(OnceLock::new());
+        let mapped_callback = Arc::clone(&mapped);
+        self.staging
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                if let Err(err) = &result {
+                    log::error!("Failed to read back the histogram: {err}");
+                }
+                let _ = mapped_callback.set(result.is_ok());
+            });
+        self.mapped = Some(mapped);
+    }
+
+    /// Take the histogram if it arrived, without waiting for the GPU.
+    /// Returns whether it did, and the bars changed
+    pub fn poll(&mut self, device: &Device) -> bool {
+        if self.mapped.is_none() {
+            return false;
+        }
+        device.poll(Maintain::Poll);
+        let Some(&mapped) = self.mapped.as_ref().and_then(|mapped| mapped.get()) else {
+            return false;
+        };
+        self.mapped = None;
+        if !mapped {
+            return false;
+        }
+        self.counts.copy_from_slice(bytemuck::cast_slice(
+            &self.staging.slice(..).get_mapped_range(),
+        ));
+        self.staging.unmap();
+        true
+    }
+
+    /// Heights of the bars from 0 to 1, four bins per element as the render shader reads them
+    pub fn bars(&self) -> [[f32; 4]; BIN_COUNT / 4] {
+        // Black backgrounds would flatten everything else
+        let highest = self.counts[1..].iter().copied().max().unwrap_or(0).max(1) as f32;
+        let mut bars = [[0.0; 4]; BIN_COUNT / 4];
+        for (bin, &count) in self.counts.iter().enumerate() {
+            bars[bin / 4][bin % 4] = (count as f32 / highest).min(1.0);
+        }
+        bars
+    }
+}
@@ -0,0 +1,120 @@
+//! Drawing images with wgpu compute shaders, as used by the `show-gpu-compute-image` app.
+//!
+//! Besides the app, the crate can be embedded:
+//!
+//! - [`Headless`] draws the `--shader` or `--example` of some [`Options`] without a window
+//!   and reads the images back, to be saved with [`write_png`], see `examples/headless_render.rs` and `examples/readback.rs`,
+//!   or runs kernels writing a buffer instead, see `examples/prefix_sum.rs` and `examples/stream_compaction.rs`
+//! - [`run_app`] runs the app in a winit window created by the caller, see `examples/embed_in_winit.rs`
+//! - [`post_effect`] passes add WGSL effects to the image, see `examples/custom_pass.rs`,
+//!   and [`process`] runs them over image files
+//!
+//! Everything configurable goes through [`Options`], parsed from command line style arguments.
+
+mod accumulation;
+mod adapter;
+mod app;
+mod attract;
+mod audio;
+#[cfg(not(target_arch = "wasm32"))]
+mod bench;
+mod bloom;
+mod blue_noise;
+mod camera;
+pub mod cli;
+mod command_line;
+mod compute;
+mod config_file;
+#[cfg(not(target_arch = "wasm32"))]
+mod contact_sheet;
+mod convolution;
+pub mod counters;
+mod crash;
+mod crop;
+mod daemon;
+mod dirty;
+mod downsample;
+#[cfg(not(target_arch = "wasm32"))]
+mod edition;
+mod examples;
+mod exposure;
+#[cfg(not(target_arch = "wasm32"))]
+mod farm;
+mod feedback;
+mod fractal;
+mod fragment;
+mod frame_hash;
+mod fullscreen;
+mod gamepad;
+mod globals;
+mod glsl;
+mod gpu;
+mod graph;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+mod histogram;
+mod library;
+mod live;
+#[cfg(not(target_arch = "wasm32"))]
+mod logger;
+mod mipmap;
+mod ndi;
+mod osc;
+mod output_window;
+mod pacing;
+mod paint;
+mod pipeline_cache;
+mod playlist;
+pub mod post_effect;
+mod power;
+mod preprocessor;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod process;
+mod profile;
+mod project;
+mod reaction_diffusion;
+mod readback;
+mod recording;
+mod recovery;
+mod render;
+mod sandbox;
+#[cfg(not(target_arch = "wasm32"))]
+mod scan;
+mod scene;
+mod screensaver;
+#[cfg(not(target_arch = "wasm32"))]
+mod self_test;
+mod shaders;
+mod sidecar;
+mod snapshots;
+#[cfg(not(target_arch = "wasm32"))]
+mod sort;
+mod soundtrack;
+mod telemetry;
+#[cfg(test)]
+mod test_gpu;
+#[cfg(not(target_arch = "wasm32"))]
+mod tiled;
+mod tonemap;
+mod touch;
+#[cfg(not(target_arch = "wasm32"))]
+mod transcode;
+mod undo;
+#[cfg(not(target_arch = "wasm32"))]
+mod video;
+mod wall;
+#[cfg(not(target_arch = "wasm32"))]
+mod wallpaper;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+pub use app::run_app;
+pub use cli::Options;
+pub use command_line::run as run_command_line;
+#[cfg(not(target_arch = "wasm32"))]
+pub use headless::Headless;
+pub use recording::write_png;
+#[cfg(not(target_arch = "wasm32"))]
+pub use scan::{GpuScan, ScanKind};
+#[cfg(not(target_arch = "wasm32"))]
+pub use sort::GpuSort;
@@ -0,0 +1,264 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{io, path::Component, process::Command};
+
+/// Entry point of a bundle, providing the `draw` function
+const BUNDLE_SHADER: &str = "draw.wgsl";
+/// Optional file of a bundle with the initial `globals.params`, e.g. `0.3 0.7`
+const BUNDLE_PARAMS: &str = "params";
+
+/// Where fetched shader bundles are kept, one directory per bundle
+pub fn library_dir() -> Option<PathBuf> {
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(data_dir.join(env!("CARGO_PKG_NAME")).join("shaders"))
+}
+
+/// Download a bundle into the library, replacing an earlier version of it.
+///
+/// Git repositories get cloned and have to contain a `draw.wgsl`. Other URLs are rejected,
+/// a single file would leave out the includes and `params` of the bundle.
+/// The earlier version is only replaced once the new one is complete.
+/// Returns the name the bundle can be loaded by
+#[cfg(not(target_arch = "wasm32"))]
+pub fn fetch(source: &str, name: Option<&str>) -> io::Result<String> {
+    if !is_git_url(source) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{source} is not a git repository. Bundles are fetched with their includes and params, \
+                 which takes a repository, e.g. one ending in .git"
+            ),
+        ));
+    }
+    let name = match name {
+        Some(name) => name.to_owned(),
+        None => default_name(source)?,
+    };
+    if !is_valid_name(&name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{name:?} is not a valid bundle name, pick one with --name"),
+        ));
+    }
+
+    let library_dir = library_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "No data directory, set XDG_DATA_HOME",
+        )
+    })?;
+    fs::create_dir_all(&library_dir)?;
+    // Fetched next to the bundle first, an earlier version stays until the new one is complete.
+    // `~` can't be part of a bundle name, so these don't clash with other bundles
+    let bundle_dir = library_dir.join(&name);
+    let fetch_dir = library_dir.join(format!("{name}~fetching"));
+    let old_dir = library_dir.join(format!("{name}~old"));
+    for dir in [&fetch_dir, &old_dir] {
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+    }
+
+    let result = run(Command::new("git")
+        .args(["clone", "--depth", "1", "--"])
+        .arg(source)
+        .arg(&fetch_dir))
+    .and_then(|()| {
+        if fetch_dir.join(BUNDLE_SHADER).is_file() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{source} contains no {BUNDLE_SHADER}"),
+            ))
+        }
+    })
+    .and_then(|()| swap_in(&fetch_dir, &bundle_dir, &old_dir));
+    if result.is_err() && fetch_dir.exists() {
+        let _ = fs::remove_dir_all(&fetch_dir);
+    }
+    result.map(|()| name)
+}
+
+/// Move the bundle fetched to `fetch_dir` to `bundle_dir`, by way of moving an earlier version to `old_dir`.
+/// The earlier version is moved back if that fails and only removed once the new one is in place
+#[cfg(not(target_arch = "wasm32"))]
+fn swap_in(fetch_dir: &Path, bundle_dir: &Path, old_dir: &Path) -> io::Result<()> {
+    let replacing = bundle_dir.exists();
+    if replacing {
+        fs::rename(bundle_dir, old_dir)?;
+    }
+    if let Err(err) = fs::rename(fetch_dir, bundle_dir) {
+        if replacing && let Err(restore_err) = fs::rename(old_dir, bundle_dir) {
+            log::error!(
+                "Failed to restore {} from {}: {restore_err}",
+                bundle_dir.display(),
+                old_dir.display()
+            );
+        }
+        return Err(err);
+    }
+    if replacing && let Err(err) = fs::remove_dir_all(old_dir) {
+        log::warn!("Failed to remove {}: {err}", old_dir.display());
+    }
+    Ok(())
+}
+
+/// `path` itself if it exists, otherwise the shader of the bundle named like it
+pub fn resolve_shader(path: &Path) -> PathBuf {
+    if path.exists() {
+        return path.to_owned();
+    }
+
+    library_dir()
+        .map(|library_dir| library_dir.join(path).join(BUNDLE_SHADER))
+        .filter(|shader| shader.is_file())
+        .unwrap_or_else(|| path.to_owned())
+}
+
+/// Whether `shader` belongs to a bundle in the library
+pub fn contains(shader: &Path) -> bool {
+    library_dir().is_some_and(|library_dir| shader.starts_with(library_dir))
+}
+
+/// Initial `globals.params` of the bundle `shader` belongs to, if it has any
+pub fn read_params(shader: &Path) -> Option<[f32; 2]> {
+    let params = fs::read_to_string(shader.parent()?.join(BUNDLE_PARAMS)).ok()?;
+    let mut values = params.split_whitespace().map(str::parse::<f32>);
+    match (values.next(), values.next()) {
+        (Some(Ok(x)), Some(Ok(y))) => Some([x, y]),
+        _ => {
+            log::warn!(
+                "Ignoring malformed {BUNDLE_PARAMS} next to {}",
+                shader.display()
+            );
+            None
+        }
+    }
+}
+
+/// Whether `name` can name a directory of the library.
+/// It has to be a single plain component, `.` or `..` would replace the library or the directory it's in
+#[cfg(not(target_arch = "wasm32"))]
+fn is_valid_name(name: &str) -> bool {
+    matches!(
+        Path::new(name).components().collect::<Vec<_>>()[..],
+        [Component::Normal(_)]
+    ) && name
+        .chars()
+        .all(|c| c.is_alphanumeric() || "-_.".contains(c))
+}
+
+/// Whether `source` names a repository rather than a file, like `https://github.com/user/repo`
+#[cfg(not(target_arch = "wasm32"))]
+fn is_git_url(source: &str) -> bool {
+    if source.ends_with(".git") || source.starts_with("git@") || source.starts_with("git://") {
+        return true;
+    }
+    // Web pages of repositories have no extension, files to download do
+    let Some(path) = ["https://", "http://"]
+        .iter()
+        .find_map(|scheme| source.strip_prefix(scheme))
+    else {
+        return false;
+    };
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let last_segment = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    path.contains('/') && !last_segment.contains('.')
+}
+
+/// Last path segment of the URL, without extensions
+#[cfg(not(target_arch = "wasm32"))]
+fn default_name(source: &str) -> io::Result<String> {
+    source
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .and_then(|segment| segment.split('.').next())
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Can't name a bundle after {source:?}, pick a name with --name"),
+            )
+        })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run(command: &mut Command) -> io::Result<()> {
+    let status = command.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{command:?} failed with {status}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_stay_inside_the_library() {
+        assert!(is_valid_name("sketch"));
+        assert!(is_valid_name("wgsl-sketch.v2"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("."));
+        assert!(!is_valid_name(".."));
+        assert!(!is_valid_name("a/b"));
+        assert!(!is_valid_name("sketch~fetching"));
+    }
+
+    #[test]
+    fn failed_swap_keeps_the_installed_bundle() {
+        let library = std::env::temp_dir().join(format!("library-swap-{}", std::process::id()));
+        let bundle_dir = library.join("sketch");
+        fs::create_dir_all(&bundle_dir).unwrap();
+        fs::write(bundle_dir.join(BUNDLE_SHADER), "installed").unwrap();
+
+        // Nothing was fetched, so moving it in fails
+        let fetch_dir = library.join("sketch~fetching");
+        let old_dir = library.join("sketch~old");
+        assert!(swap_in(&fetch_dir, &bundle_dir, &old_dir).is_err());
+        assert_eq!(
+            fs::read_to_string(bundle_dir.join(BUNDLE_SHADER)).unwrap(),
+            "installed"
+        );
+        assert!(!old_dir.exists());
+
+        fs::create_dir(&fetch_dir).unwrap();
+        fs::write(fetch_dir.join(BUNDLE_SHADER), "fetched").unwrap();
+        swap_in(&fetch_dir, &bundle_dir, &old_dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(bundle_dir.join(BUNDLE_SHADER)).unwrap(),
+            "fetched"
+        );
+        assert!(!old_dir.exists() && !fetch_dir.exists());
+        fs::remove_dir_all(&library).unwrap();
+    }
+
+    #[test]
+    fn repository_pages_are_cloned() {
+        assert!(is_git_url("https://github.com/someone/sketch"));
+        assert!(is_git_url("https://github.com/someone/sketch/"));
+        assert!(is_git_url("https://example.com/sketch.git"));
+        assert!(is_git_url("git@github.com:someone/sketch.git"));
+        assert!(!is_git_url("https://example.com/shaders/tunnel.wgsl"));
+        assert!(!is_git_url("https://example.com/tunnel.wgsl?raw=true"));
+        assert!(!is_git_url("https://example.com"));
+        assert!(!is_git_url("-oProxyCommand=x"));
+    }
+}
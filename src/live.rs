@@ -0,0 +1,171 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use web_time::Instant;
+use wgpu::naga::{
+    front::wgsl,
+    valid::{Capabilities, ValidationFlags, Validator},
+};
+
+use crate::{
+    gpu::DrawMode, preprocessor::ShaderSource, project, scene::SceneConfig, shaders::DrawingSource,
+};
+
+/// How often the shader directory is checked for edits, short enough to feel immediate on stage
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the status strip stays green after a swap
+const SUCCEEDED_DURATION: Duration = Duration::from_secs(1);
+
+const FAILED_COLOR: [f32; 4] = [0.9, 0.1, 0.1, 1.0];
+const SUCCEEDED_COLOR: [f32; 4] = [0.1, 0.8, 0.2, 1.0];
+
+/// Live coding with `--live`: edits to the files next to the shader are preprocessed and validated
+/// on a background thread while the current scene keeps running.
+/// Only edits that validate are handed to the app, which swaps them in between frames
+pub struct LiveCoding {
+    /// Outcome of validating the latest edit
+    edits: Receiver<Result<(), String>>,
+    /// The scene the edits are validated for, kept current by [`LiveCoding::poll`]
+    config: Arc<Mutex<SceneConfig>>,
+}
+
+/// Strip along the bottom of the image confirming live coding swaps and dropped files:
+/// green for a moment after a success, red after a failure until the next success
+pub struct StatusStrip {
+    status: Status,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Idle,
+    Succeeded(Instant),
+    Failed,
+}
+
+impl LiveCoding {
+    pub fn new(
+        shader: &Path,
+        draw_mode: DrawMode,
+        push_constants: bool,
+        config: &SceneConfig,
+    ) -> Self {
+        let (sender, edits) = mpsc::channel();
+        let config = Arc::new(Mutex::new(config.clone()));
+        let watcher = Watcher {
+            shader: shader.to_owned(),
+            dir: shader
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+                .to_owned(),
+            draw_mode,
+            push_constants,
+            config: Arc::clone(&config),
+        };
+        thread::spawn(move || watcher.run(&sender));
+
+        Self { edits, config }
+    }
+
+    /// The outcome of validating the latest edit, if one was made since the last call.
+    /// `config` is the scene edits are validated for from now on
+    pub fn poll(&mut self, config: &SceneConfig) -> Option<Result<(), String>> {
+        config.clone_into(&mut self.config.lock().unwrap());
+        // Older edits were superseded
+        self.edits.try_iter().last()
+    }
+}
+
+impl StatusStrip {
+    pub fn new() -> Self {
+        Self {
+            status: Status::Idle,
+        }
+    }
+
+    /// Show that something was swapped in
+    pub fn succeeded(&mut self) {
+        self.status = Status::Succeeded(Instant::now());
+    }
+
+    /// Show that the latest attempt failed, until one succeeds
+    pub fn failed(&mut self) {
+        self.status = Status::Failed;
+    }
+
+    /// Hide the strip once a success was shown long enough, returns whether the color changed
+    pub fn expire(&mut self) -> bool {
+        if let Status::Succeeded(since) = self.status
+            && since.elapsed() >= SUCCEEDED_DURATION
+        {
+            self.status = Status::Idle;
+            return true;
+        }
+        false
+    }
+
+    /// Transparent while there's nothing to show
+    pub fn color(&self) -> [f32; 4] {
+        match self.status {
+            Status::Idle => [0.0; 4],
+            Status::Succeeded(_) => SUCCEEDED_COLOR,
+            Status::Failed => FAILED_COLOR,
+        }
+    }
+}
+
+impl Default for StatusStrip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs on the background thread
+struct Watcher {
+    shader: PathBuf,
+    dir: PathBuf,
+    draw_mode: DrawMode,
+    push_constants: bool,
+    config: Arc<Mutex<SceneConfig>>,
+}
+
+impl Watcher {
+    /// Validate every edit until the app stops listening
+    fn run(&self, sender: &Sender<Result<(), String>>) {
+        let mut modified: Option<SystemTime> = project::modified(&self.dir);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let latest = project::modified(&self.dir);
+            if latest == modified {
+                continue;
+            }
+            modified = latest;
+            if sender.send(self.validate()).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Whether the shader builds, with errors pointing at the lines of the files they are in
+    fn validate(&self) -> Result<(), String> {
+        let source = ShaderSource::read(&self.shader)
+            .map_err(|err| format!("Failed to read {}: {err}", self.shader.display()))?;
+        let config = self.config.lock().unwrap().clone();
+        let drawing = DrawingSource::new(self.draw_mode, self.push_constants, &source, &config)?;
+        let text = &drawing.source.text;
+        let module = wgsl::parse_str(text)
+            .map_err(|err| drawing.source.remap_error(&err.emit_to_string(text)))?;
+        // The device checks its own capabilities once the shader is swapped in
+        Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .map_err(|err| drawing.source.remap_error(&err.emit_to_string(text)))?;
+        Ok(())
+    }
+}
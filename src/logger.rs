@@ -0,0 +1,139 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use web_time::Instant;
+
+/// Number of lines kept around for crash reports
+const RECENT_LINE_COUNT: usize = 100;
+
+static RECENT_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// The latest log lines, oldest first.
+/// Gives up instead of blocking, as this is called while panicking
+pub fn recent_lines() -> Vec<String> {
+    match RECENT_LINES.try_lock() {
+        Ok(recent) => recent.iter().cloned().collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Writes log lines to stderr, or appends them to a file.
+///
+/// Under systemd stderr ends up in the journal, so when stderr is the journal or the app runs as a daemon,
+/// lines are prefixed with their syslog priority for journald to pick up.
+pub struct Logger {
+    start: Instant,
+    output: Mutex<Output>,
+}
+
+enum Output {
+    /// Lines carry their syslog priority if `priority`
+    Stderr {
+        priority: bool,
+    },
+    File(File),
+}
+
+impl Logger {
+    /// Install the logger, appending to `log_file` if given.
+    /// A `daemon` always prefixes priorities, as its stderr is expected to end up in the journal
+    pub fn init(log_file: Option<&Path>, daemon: bool) -> io::Result<()> {
+        let output = match log_file {
+            Some(path) => Output::File(OpenOptions::new().create(true).append(true).open(path)?),
+            None => Output::Stderr {
+                priority: daemon || stderr_is_journal(),
+            },
+        };
+
+        let logger = Logger {
+            start: Instant::now(),
+            output: Mutex::new(output),
+        };
+
+        log::set_boxed_logger(Box::new(logger)).expect("Logger was already initialized");
+        log::set_max_level(LevelFilter::Info);
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // wgpu is very chatty on info level
+        metadata.level() <= Level::Warn || metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let line = format!("[{elapsed:>10.3}s] {:<5} {}", record.level(), record.args());
+
+        let mut output = self.output.lock().unwrap();
+        // Logging must never take the app down, failed writes are dropped
+        let _ = match &mut *output {
+            Output::Stderr { priority: true } => {
+                writeln!(io::stderr(), "<{}>{line}", syslog_priority(record.level()))
+            }
+            Output::Stderr { priority: false } => writeln!(io::stderr(), "{line}"),
+            Output::File(file) => writeln!(file, "{line}"),
+        };
+        drop(output);
+
+        let mut recent = RECENT_LINES.lock().unwrap();
+        if recent.len() == RECENT_LINE_COUNT {
+            recent.pop_front();
+        }
+        recent.push_back(line);
+    }
+
+    fn flush(&self) {
+        if let Output::File(file) = &mut *self.output.lock().unwrap() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Whether stderr is the stream systemd connected to the journal,
+/// whose device and inode it passes as `JOURNAL_STREAM=dev:inode`
+fn stderr_is_journal() -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::{fd::AsFd, unix::fs::MetadataExt};
+
+        let Some(stream) = std::env::var_os("JOURNAL_STREAM") else {
+            return false;
+        };
+        let Some((dev, ino)) = stream.to_str().and_then(|stream| stream.split_once(':')) else {
+            return false;
+        };
+        let Ok(metadata) = io::stderr()
+            .as_fd()
+            .try_clone_to_owned()
+            .map(File::from)
+            .and_then(|stderr| stderr.metadata())
+        else {
+            return false;
+        };
+        dev.parse() == Ok(metadata.dev()) && ino.parse() == Ok(metadata.ino())
+    }
+
+    #[cfg(not(unix))]
+    false
+}
+
+fn syslog_priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
@@ -0,0 +1,145 @@
+use wgpu::*;
+
+use crate::{compute::OUTPUT_FORMAT, shaders::Shaders};
+
+/// Mip chain of the presented image, regenerated every frame by halving it level by level.
+/// The render pass samples it, so images shown smaller than their resolution don't shimmer
+pub struct MipmapState {
+    pipeline: ComputePipeline,
+    /// Reading one level and writing the next, for every level but the first
+    bind_groups: Vec<BindGroup>,
+    pub texture: Texture,
+    /// All levels, for sampling
+    pub view: TextureView,
+    workgroup_size: [u32; 2],
+}
+
+impl MipmapState {
+    /// `width` × `height` is the size of the presented image
+    pub fn new(device: &Device, shaders: &Shaders, width: u32, height: u32) -> Self {
+        let module = shaders
+            .mipmap
+            .as_ref()
+            .expect("Mipmap shader was not created");
+
+        let mip_level_count = u32::BITS - width.max(height).leading_zeros();
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Mipmap Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::STORAGE_BINDING
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Mipmap Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: OUTPUT_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let level_view = |level| {
+            texture.create_view(&TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        };
+        let bind_groups = (1..mip_level_count)
+            .map(|level| {
+                device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("Mipmap Bind Group"),
+                    layout: &bind_group_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(&level_view(level - 1)),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(&level_view(level)),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            compilation_options: Default::default(),
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Mipmap Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            })),
+            module,
+            entry_point: "main",
+            cache: shaders.pipeline_cache.as_deref(),
+        });
+
+        Self {
+            pipeline,
+            bind_groups,
+            texture,
+            view,
+            workgroup_size: shaders.workgroup_size,
+        }
+    }
+
+    /// Copy `source` into the first level and fill the others from it
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, source: &Texture) {
+        encoder.copy_texture_to_texture(
+            source.as_image_copy(),
+            self.texture.as_image_copy(),
+            source.size(),
+        );
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Mipmap Pass"),
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        let [workgroup_width, workgroup_height] = self.workgroup_size;
+        for (level, bind_group) in (1..).zip(&self.bind_groups) {
+            let size = self
+                .texture
+                .size()
+                .mip_level_size(level, TextureDimension::D2);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                size.width.div_ceil(workgroup_width),
+                size.height.div_ceil(workgroup_height),
+                1,
+            );
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use wgpu::{Device, Queue, Texture};
+
+use crate::readback::ReadbackRing;
+
+/// Frames read back at once, newer ones are dropped while the network falls behind
+const MAX_PENDING_FRAMES: usize = 3;
+
+/// Publishes the presented frames as an NDI source on the network, for `--ndi`,
+/// so VJ software like Resolume, OBS or TouchDesigner can take them as live video.
+///
+/// Frames are read back without waiting for the GPU and sent as 8-bit sRGB RGBA
+pub struct NdiOutput {
+    sender: Sender,
+    readbacks: ReadbackRing<()>,
+    srgb_output: bool,
+}
+
+impl NdiOutput {
+    /// Announce a source called `name`, drawings are in linear light unless `srgb_output`
+    pub fn new(name: &str, srgb_output: bool) -> Result<Self, String> {
+        Ok(Self {
+            sender: Sender::new(name)?,
+            readbacks: ReadbackRing::new(MAX_PENDING_FRAMES),
+            srgb_output,
+        })
+    }
+
+    /// Read back `texture` to send it once it arrives, unless too many frames are on their way
+    pub fn capture(&mut self, device: &Device, queue: &Queue, texture: &Texture) {
+        self.poll(device);
+        if !self.readbacks.is_full() {
+            self.readbacks.start(device, queue, texture, ());
+        }
+    }
+
+    /// Send the frames read back so far
+    pub fn poll(&mut self, device: &Device) {
+        let (sender, srgb_output) = (&self.sender, self.srgb_output);
+        self.readbacks.collect(device, false, |(), layout, data| {
+            sender.send(
+                layout.width,
+                layout.height,
+                &layout.rgba8(data, srgb_output),
+            );
+            true
+        });
+    }
+
+    /// Forget the frames read back from a lost device
+    pub fn device_lost(&mut self) {
+        self.readbacks.clear();
+    }
+}
+
+#[cfg(feature = "ndi")]
+use sender::Sender;
+
+/// Stand-in for builds without the `ndi` feature, which never connects
+#[cfg(not(feature = "ndi"))]
+struct Sender;
+
+#[cfg(not(feature = "ndi"))]
+impl Sender {
+    fn new(_name: &str) -> Result<Self, String> {
+        Err("built without the `ndi` feature".to_owned())
+    }
+
+    fn send(&self, _width: u32, _height: u32, _pixels: &[u8]) {}
+}
+
+/// Bindings to the few functions of the NDI SDK needed for sending video.
+/// The runtime is loaded when the sender is created, so building doesn't need the SDK
+#[cfg(feature = "ndi")]
+mod sender {
+    use std::{
+        env,
+        ffi::{CString, c_char, c_int, c_void},
+        path::PathBuf,
+        ptr,
+    };
+
+    use libloading::Library;
+
+    const FOURCC_RGBA: u32 = u32::from_le_bytes(*b"RGBA");
+    const FRAME_FORMAT_PROGRESSIVE: c_int = 1;
+    /// Lets the runtime fill in the timecode
+    const TIMECODE_SYNTHESIZE: i64 = i64::MAX;
+
+    /// Names of the runtime library, newest version first
+    #[cfg(target_os = "windows")]
+    const LIBRARY_NAMES: &[&str] = &["Processing.NDI.Lib.x64.dll"];
+    #[cfg(target_os = "macos")]
+    const LIBRARY_NAMES: &[&str] = &["libndi.dylib"];
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    const LIBRARY_NAMES: &[&str] = &["libndi.so.6", "libndi.so.5", "libndi.so"];
+    /// Variables the runtime installers set to the directory they installed to
+    const RUNTIME_DIR_VARIABLES: [&str; 2] = ["NDI_RUNTIME_DIR_V6", "NDI_RUNTIME_DIR_V5"];
+
+    type Initialize = unsafe extern "C" fn() -> bool;
+    type Destroy = unsafe extern "C" fn();
+    type SendCreate = unsafe extern "C" fn(*const SendSettings) -> *mut c_void;
+    type SendVideo = unsafe extern "C" fn(*mut c_void, *const VideoFrame);
+    type SendDestroy = unsafe extern "C" fn(*mut c_void);
+
+    /// Mirrors `NDIlib_send_create_t`
+    #[repr(C)]
+    struct SendSettings {
+        name: *const c_char,
+        groups: *const c_char,
+        clock_video: bool,
+        clock_audio: bool,
+    }
+
+    /// Mirrors `NDIlib_video_frame_v2_t`
+    #[repr(C)]
+    struct VideoFrame {
+        width: c_int,
+        height: c_int,
+        fourcc: u32,
+        frame_rate_numerator: c_int,
+        frame_rate_denominator: c_int,
+        picture_aspect_ratio: f32,
+        frame_format: c_int,
+        timecode: i64,
+        data: *const u8,
+        line_stride: c_int,
+        metadata: *const c_char,
+        timestamp: i64,
+    }
+
+    pub struct Sender {
+        instance: *mut c_void,
+        send_video: SendVideo,
+        send_destroy: SendDestroy,
+        destroy: Destroy,
+        /// Keeps the functions above loaded
+        _library: Library,
+    }
+
+    impl Sender {
+        pub fn new(name: &str) -> Result<Self, String> {
+            let library = load_library()?;
+            let name = CString::new(name).map_err(|err| err.to_string())?;
+            // SAFETY: The signatures match the headers of the SDK,
+            // and the functions stay loaded as long as the sender keeps the library
+            unsafe {
+                let initialize: Initialize = symbol(&library, b"NDIlib_initialize\0")?;
+                let destroy: Destroy = symbol(&library, b"NDIlib_destroy\0")?;
+                let send_create: SendCreate = symbol(&library, b"NDIlib_send_create\0")?;
+                let send_video: SendVideo = symbol(&library, b"NDIlib_send_send_video_v2\0")?;
+                let send_destroy: SendDestroy = symbol(&library, b"NDIlib_send_destroy\0")?;
+
+                if !initialize() {
+                    return Err("NDI doesn't support this CPU".to_owned());
+                }
+                // Frames are sent as they are drawn rather than paced by the runtime
+                let settings = SendSettings {
+                    name: name.as_ptr(),
+                    groups: ptr::null(),
+                    clock_video: false,
+                    clock_audio: false,
+                };
+                let instance = send_create(&settings);
+                if instance.is_null() {
+                    destroy();
+                    return Err("Failed to create the NDI sender".to_owned());
+                }
+                Ok(Self {
+                    instance,
+                    send_video,
+                    send_destroy,
+                    destroy,
+                    _library: library,
+                })
+            }
+        }
+
+        /// Send a frame of tightly packed 8-bit RGBA rows
+        pub fn send(&self, width: u32, height: u32, pixels: &[u8]) {
+            let frame = VideoFrame {
+                width: width as c_int,
+                height: height as c_int,
+                fourcc: FOURCC_RGBA,
+                frame_rate_numerator: 60,
+                frame_rate_denominator: 1,
+                picture_aspect_ratio: width as f32 / height as f32,
+                frame_format: FRAME_FORMAT_PROGRESSIVE,
+                timecode: TIMECODE_SYNTHESIZE,
+                data: pixels.as_ptr(),
+                line_stride: (width * 4) as c_int,
+                metadata: ptr::null(),
+                timestamp: 0,
+            };
+            // SAFETY: Sending is synchronous, the runtime is done with `pixels` when it returns
+            unsafe { (self.send_video)(self.instance, &frame) }
+        }
+    }
+
+    impl Drop for Sender {
+        fn drop(&mut self) {
+            // SAFETY: The instance was created by this runtime and isn't used afterwards
+            unsafe {
+                (self.send_destroy)(self.instance);
+                (self.destroy)();
+            }
+        }
+    }
+
+    /// # Safety
+    /// `T` has to be the type of the function called `name`
+    unsafe fn symbol<T: Copy>(library: &Library, name: &[u8]) -> Result<T, String> {
+        // SAFETY: Upheld by the caller
+        unsafe { library.get::<T>(name) }
+            .map(|symbol| *symbol)
+            .map_err(|err| format!("The NDI runtime lacks a function: {err}"))
+    }
+
+    /// The runtime from the directory of its installer, or wherever the system looks for libraries
+    fn load_library() -> Result<Library, String> {
+        let candidates = RUNTIME_DIR_VARIABLES
+            .iter()
+            .filter_map(env::var_os)
+            .flat_map(|dir| {
+                LIBRARY_NAMES
+                    .iter()
+                    .map(move |name| PathBuf::from(&dir).join(name))
+            })
+            .chain(LIBRARY_NAMES.iter().map(PathBuf::from));
+        for path in candidates {
+            // SAFETY: Loading the NDI runtime only initializes it
+            if let Ok(library) = unsafe { Library::new(&path) } {
+                return Ok(library);
+            }
+        }
+        Err(format!(
+            "No NDI runtime found, install it from ndi.video or set {}",
+            RUNTIME_DIR_VARIABLES[0]
+        ))
+    }
+}
@@ -0,0 +1,46 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+/// An argument of an OSC message
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+}
+
+/// Send an OSC 1.0 message to `address` on `target` over UDP, e.g. to cue a lighting desk or a sound engine
+pub fn send(target: SocketAddr, address: &str, args: &[OscArg]) -> io::Result<()> {
+    let local = match target {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    UdpSocket::bind(local)?.send_to(&encode(address, args), target)?;
+    Ok(())
+}
+
+fn encode(address: &str, args: &[OscArg]) -> Vec<u8> {
+    let mut message = Vec::new();
+    push_string(&mut message, address);
+    let type_tags: String = std::iter::once(',')
+        .chain(args.iter().map(|arg| match arg {
+            OscArg::Int(_) => 'i',
+            OscArg::Float(_) => 'f',
+        }))
+        .collect();
+    push_string(&mut message, &type_tags);
+    for arg in args {
+        match arg {
+            OscArg::Int(value) => message.extend(value.to_be_bytes()),
+            OscArg::Float(value) => message.extend(value.to_be_bytes()),
+        }
+    }
+    message
+}
+
+/// Strings are null-terminated and padded to a multiple of 4 bytes
+fn push_string(message: &mut Vec<u8>, string: &str) {
+    message.extend(string.as_bytes());
+    message.push(0);
+    message.resize(message.len().next_multiple_of(4), 0);
+}
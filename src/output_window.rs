@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+use wgpu::{Surface, SurfaceConfiguration};
+use winit::{
+    dpi::LogicalSize,
+    event_loop::ActiveEventLoop,
+    window::{Fullscreen, Window, WindowId},
+};
+
+use crate::{
+    globals::GlobalsState,
+    gpu::GpuState,
+    recovery::SurfaceRecovery,
+    render::RenderState,
+    scene::{Scene, SceneConfig},
+    tonemap::{ColorSpace, TonemapParams, Transfer},
+};
+
+/// `[[windows]]` table of the config file, another window showing the image, e.g. on a projector
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WindowConfig {
+    pub title: String,
+    /// Node of the scene graph shown, like the `source` of a [`Quad`](crate::render::Quad)
+    #[serde(default = "default_source")]
+    pub source: String,
+    /// Index of the monitor covered borderless, windowed when left out
+    pub monitor: Option<usize>,
+    /// Inner size in logical pixels while windowed
+    #[serde(default = "default_size")]
+    pub size: [u32; 2],
+}
+
+fn default_source() -> String {
+    "output".to_owned()
+}
+
+fn default_size() -> [u32; 2] {
+    [crate::app::WIDTH, crate::app::HEIGHT]
+}
+
+/// A window next to the main one, presenting a node of the same scene with its own surface and render pass.
+/// It shows the image only, without quads, crop selection or status strip
+pub struct OutputWindow {
+    window: Arc<Window>,
+    surface: Surface<'static>,
+    surface_config: SurfaceConfiguration,
+    transfer: Transfer,
+    source: String,
+    /// [`Scene::id`] of the scene `render_state` was built for, it is rebuilt once the scene changes
+    scene_id: Option<u64>,
+    /// `None` if the scene has no node called `source`
+    render_state: Option<RenderState>,
+}
+
+impl OutputWindow {
+    pub fn open(
+        event_loop: &ActiveEventLoop,
+        gpu_state: &GpuState,
+        color_space: ColorSpace,
+        config: &WindowConfig,
+    ) -> Result<Self, String> {
+        let [width, height] = config.size;
+        let mut attributes = Window::default_attributes()
+            .with_title(&config.title)
+            .with_inner_size(LogicalSize::new(width, height));
+        if let Some(index) = config.monitor {
+            let monitor = event_loop.available_monitors().nth(index).ok_or_else(|| {
+                let count = event_loop.available_monitors().count();
+                format!("There is no monitor {index}, only {count}")
+            })?;
+            attributes = attributes.with_fullscreen(Some(Fullscreen::Borderless(Some(monitor))));
+        }
+        let window = Arc::new(
+            event_loop
+                .create_window(attributes)
+                .map_err(|err| err.to_string())?,
+        );
+        let (surface, surface_config) = create_surface(gpu_state, &window)?;
+
+        Ok(Self {
+            window,
+            transfer: color_space.transfer(surface_config.format),
+            surface,
+            surface_config,
+            source: config.source.clone(),
+            scene_id: None,
+            render_state: None,
+        })
+    }
+
+    /// Create the surface again for the device of `gpu_state`, after the previous one was lost
+    pub fn reconnect(&mut self, gpu_state: &GpuState) -> Result<(), String> {
+        (self.surface, self.surface_config) = create_surface(gpu_state, &self.window)?;
+        self.scene_id = None;
+        Ok(())
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn resize(&mut self, gpu_state: &GpuState, width: u32, height: u32) {
+        self.surface_config.width = width.max(1);
+        self.surface_config.height = height.max(1);
+        self.surface
+            .configure(&gpu_state.device, &self.surface_config);
+    }
+
+    /// Present the [`WindowConfig::source`] of `scene`, tonemapped like the main window
+    pub fn render(
+        &mut self,
+        gpu_state: &GpuState,
+        scene: &Scene,
+        config: &SceneConfig,
+        globals: &GlobalsState,
+        tonemap_params: TonemapParams,
+    ) {
+        let tonemap_params = TonemapParams {
+            transfer: self.transfer as u32,
+            selection: [0.0; 4],
+            status: [0.0; 4],
+            histogram_shown: 0,
+            ..tonemap_params
+        };
+        if self.scene_id != Some(scene.id()) {
+            self.scene_id = Some(scene.id());
+            self.render_state = scene.window_render_state(
+                &gpu_state.device,
+                &self.source,
+                self.surface_config.format,
+                tonemap_params,
+                config,
+            );
+            if self.render_state.is_none() {
+                log::error!(
+                    "Window {:?} shows {:?}, which isn't a node of the scene",
+                    self.window.title(),
+                    self.source
+                );
+            }
+        }
+        let Some(render_state) = &mut self.render_state else {
+            return;
+        };
+
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(err) => {
+                match SurfaceRecovery::for_error(&err) {
+                    SurfaceRecovery::Reconfigure => self
+                        .surface
+                        .configure(&gpu_state.device, &self.surface_config),
+                    SurfaceRecovery::SkipFrame => log::warn!("Skipping frame: {err}"),
+                    SurfaceRecovery::RecreateDevice => {
+                        log::error!("{err}");
+                        gpu_state.health.mark_lost();
+                    }
+                }
+                return;
+            }
+        };
+        render_state.set_tonemapping(&gpu_state.queue, tonemap_params);
+        render_state.fit(
+            &gpu_state.queue,
+            (frame.texture.width(), frame.texture.height()),
+        );
+        let mut encoder =
+            gpu_state
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Window Render Encoder"),
+                });
+        render_state.render(
+            &gpu_state.device,
+            &mut encoder,
+            &frame.texture,
+            globals,
+            true,
+        );
+        gpu_state.queue.submit(Some(encoder.finish()));
+        frame.present();
+    }
+}
+
+/// A surface on `window` in the format of the main window where possible
+fn create_surface(
+    gpu_state: &GpuState,
+    window: &Arc<Window>,
+) -> Result<(Surface<'static>, SurfaceConfiguration), String> {
+    let surface = gpu_state
+        .instance
+        .create_surface(Arc::clone(window))
+        .map_err(|err| err.to_string())?;
+    let formats = surface.get_capabilities(&gpu_state.adapter).formats;
+    let format = formats
+        .iter()
+        .copied()
+        .find(|&format| format == gpu_state.surface_format)
+        .or(formats.first().copied())
+        .ok_or("The surface is incompatible with the adapter")?;
+    let size = window.inner_size();
+    let surface_config = SurfaceConfiguration {
+        format,
+        width: size.width.max(1),
+        height: size.height.max(1),
+        ..gpu_state.surface_config.clone()
+    };
+    surface.configure(&gpu_state.device, &surface_config);
+    Ok((surface, surface_config))
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+/// How often the event loop still wakes up while no frames are drawn,
+/// to check file watchers, signals and sockets
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Schedules frames: at most `--max-fps` of them, none while the window can't be seen,
+/// and with `--on-demand` only after something changed
+pub struct FramePacer {
+    interval: Option<Duration>,
+    on_demand: bool,
+    last_frame: Instant,
+    /// Something changed since the last frame
+    changed: bool,
+    occluded: bool,
+    minimized: bool,
+    /// Only draw after something changed, like with `--on-demand`
+    paused: bool,
+}
+
+impl FramePacer {
+    pub fn new(max_fps: Option<u32>, on_demand: bool) -> Self {
+        Self {
+            interval: max_fps.map(|fps| Duration::from_secs(1) / fps),
+            on_demand,
+            last_frame: Instant::now(),
+            changed: true,
+            occluded: false,
+            minimized: false,
+            paused: false,
+        }
+    }
+
+    /// Draw again with `--on-demand`
+    pub fn invalidate(&mut self) {
+        self.changed = true;
+    }
+
+    /// Windows are occluded when completely covered or on another workspace
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+        self.invalidate();
+    }
+
+    /// Minimized windows are resized to nothing on some platforms
+    pub fn resized(&mut self, width: u32, height: u32) {
+        self.minimized = width == 0 || height == 0;
+        self.invalidate();
+    }
+
+    /// While paused the last frame is only shown again after something changed
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.invalidate();
+    }
+
+    /// Whether the window can't be seen
+    pub fn hidden(&self) -> bool {
+        self.occluded || self.minimized
+    }
+
+    /// When the next frame is due, `None` if it can be drawn right away.
+    /// Pauses while the window is hidden if `may_pause`
+    pub fn next_frame_at(&self, may_pause: bool) -> Option<Instant> {
+        if may_pause && self.hidden() || (self.on_demand || self.paused) && !self.changed {
+            return Some(Instant::now() + IDLE_CHECK_INTERVAL);
+        }
+        let due = self.last_frame + self.interval?;
+        (Instant::now() < due).then_some(due)
+    }
+
+    pub fn frame_drawn(&mut self) {
+        self.last_frame = Instant::now();
+        self.changed = false;
+    }
+}
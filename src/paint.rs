@@ -0,0 +1,554 @@
+use clap::ValueEnum;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+use winit::event::MouseScrollDelta;
+
+use crate::{
+    camera::PIXELS_PER_LINE,
+    compute::{ComputeState, OUTPUT_FORMAT, OutputStorage},
+    gpu::{DrawMode, GpuState},
+    shaders::Shaders,
+    snapshots::SnapshotRing,
+    undo::TileHistory,
+};
+
+/// Most segments splatted per frame, those of quicker strokes are splatted over the following frames
+const MAX_SEGMENTS: usize = 256;
+/// Factor the brush radius changes by per line scrolled
+const WHEEL_FACTOR: f32 = 1.15;
+/// Colors X cycles the brush through, the last one is that of the paper and erases
+const PALETTE: [[f32; 3]; 8] = [
+    [0.02, 0.02, 0.03],
+    [0.75, 0.05, 0.04],
+    [0.95, 0.55, 0.02],
+    [0.9, 0.8, 0.05],
+    [0.05, 0.45, 0.12],
+    [0.02, 0.2, 0.65],
+    [0.35, 0.05, 0.5],
+    [0.9, 0.86, 0.74],
+];
+
+/// What the paint preset keeps for undo and redo
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UndoHistory {
+    /// The tiles each stroke changed, compressed on the GPU, see `src/shaders/undo.wgsl`
+    Tiles,
+    /// A copy of the whole canvas before every stroke
+    Snapshots,
+}
+
+/// A piece of a stroke, as the paint shader reads it from its storage buffer
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Segment {
+    /// In pixels of the drawn image
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+    /// Linear RGB, `a` is unused
+    pub color: [f32; 4],
+    /// In pixels
+    pub radius: f32,
+    /// Part of the radius painted fully before the brush fades out
+    pub hardness: f32,
+    pub _padding: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PaintParams {
+    segment_count: u32,
+    /// 1 to start from blank paper rather than the canvas
+    clear: u32,
+    _padding: [u32; 2],
+}
+
+/// Mouse controls of the paint preset: dragging with the left mouse button paints,
+/// the wheel sizes the brush, Shift and the wheel set its hardness and X picks the next color
+pub struct PaintControls {
+    radius: f32,
+    hardness: f32,
+    /// Index into [`PALETTE`]
+    color: usize,
+    painting: bool,
+    /// In pixels of the drawn image
+    cursor: Option<[f32; 2]>,
+    /// Painted since the last frame
+    segments: Vec<Segment>,
+    /// Whether a stroke started since the last frame, to snapshot the canvas for undo
+    stroke_started: bool,
+}
+
+impl PaintControls {
+    pub fn new() -> Self {
+        Self {
+            radius: 12.0,
+            hardness: 0.5,
+            color: 0,
+            painting: false,
+            cursor: None,
+            segments: Vec::new(),
+            stroke_started: false,
+        }
+    }
+
+    /// Start a stroke with a dot under the cursor, or end it
+    pub fn handle_button(&mut self, pressed: bool) {
+        self.painting = pressed;
+        if pressed && let Some(cursor) = self.cursor {
+            self.stroke_started = true;
+            self.paint(cursor, cursor);
+        }
+    }
+
+    /// `position` in pixels of the drawn image
+    pub fn handle_cursor(&mut self, position: [f32; 2]) {
+        if let Some(last) = self.cursor.replace(position)
+            && self.painting
+        {
+            self.paint(last, position);
+        }
+    }
+
+    /// Size the brush, or set its hardness while `shift` is held
+    pub fn handle_wheel(&mut self, delta: MouseScrollDelta, shift: bool) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+        };
+        if shift {
+            self.hardness = (self.hardness + 0.1 * lines).clamp(0.0, 1.0);
+            log::info!("Brush hardness: {:.1}", self.hardness);
+        } else {
+            self.radius = (self.radius * WHEEL_FACTOR.powf(lines)).clamp(1.0, 256.0);
+            log::info!("Brush radius: {:.0} px", self.radius);
+        }
+    }
+
+    pub fn next_color(&mut self) {
+        self.color = (self.color + 1) % PALETTE.len();
+        if self.color == PALETTE.len() - 1 {
+            log::info!("Brush color: eraser");
+        } else {
+            log::info!("Brush color: {}", self.color + 1);
+        }
+    }
+
+    /// Whether a stroke started since the last call
+    pub fn take_stroke_start(&mut self) -> bool {
+        std::mem::take(&mut self.stroke_started)
+    }
+
+    /// The segments painted since the last call
+    pub fn take_segments(&mut self) -> Vec<Segment> {
+        std::mem::take(&mut self.segments)
+    }
+
+    fn paint(&mut self, start: [f32; 2], end: [f32; 2]) {
+        let [r, g, b] = PALETTE[self.color];
+        self.segments.push(Segment {
+            start,
+            end,
+            color: [r, g, b, 1.0],
+            radius: self.radius,
+            hardness: self.hardness,
+            _padding: [0.0; 2],
+        });
+    }
+}
+
+impl Default for PaintControls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Paint preset: strokes are splatted into a persistent canvas.
+///
+/// The canvas lives in two textures which are ping-ponged, every frame with new segments reads one
+/// and writes the other and `output_view`. When the next stroke starts, the tiles the last one changed
+/// are kept in the [`TileHistory`], whose steps undo and redo apply to the canvas,
+/// or with [`UndoHistory::Snapshots`] the canvas is copied into the [`SnapshotRing`] before it
+pub struct PaintState {
+    pub pipeline: ComputePipeline,
+    /// `bind_groups[i]` reads from canvas `i` and writes to the other one
+    pub bind_groups: [BindGroup; 2],
+    pub segments_buffer: Buffer,
+    pub params_buffer: Buffer,
+    pub output_texture: Texture,
+    pub output_view: TextureView,
+    canvases: [Texture; 2],
+    history: History,
+    /// Whether segments were splatted since the tile history last recorded the canvas
+    painted: bool,
+    /// Segments waiting to be splatted
+    pending: Vec<Segment>,
+    /// Start the next splat from blank paper
+    clear: bool,
+    /// Index of the canvas holding the latest strokes
+    current: usize,
+    workgroup_size: [u32; 2],
+    width: u32,
+    height: u32,
+}
+
+impl PaintState {
+    /// Whether the device can paint, it takes compute shaders writing the canvas in [`OUTPUT_FORMAT`]
+    pub fn supported(gpu_state: &GpuState) -> bool {
+        gpu_state.draw_mode == DrawMode::Compute
+            && ComputeState::output_storage(&gpu_state.adapter) == Some(OutputStorage::Float)
+    }
+
+    /// The `undo_history` kept takes up to `undo_memory` bytes
+    pub fn new(
+        device: &Device,
+        shaders: &Shaders,
+        width: u32,
+        height: u32,
+        undo_history: UndoHistory,
+        undo_memory: u64,
+    ) -> Self {
+        let module = shaders
+            .paint
+            .as_ref()
+            .expect("Paint shader was not created");
+
+        let canvases = [0, 1].map(|_| create_canvas(device, "Paint Canvas Texture", width, height));
+        let canvas_views = canvases
+            .each_ref()
+            .map(|texture| texture.create_view(&TextureViewDescriptor::default()));
+
+        let output_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Paint Output Texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::STORAGE_BINDING
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+
+        let segments_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Paint Segments Buffer"),
+            size: (MAX_SEGMENTS * std::mem::size_of::<Segment>()) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Paint Params Buffer"),
+            contents: bytemuck::bytes_of(&PaintParams {
+                segment_count: 0,
+                clear: 1,
+                _padding: [0; 2],
+            }),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let storage_texture = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::StorageTexture {
+                access: StorageTextureAccess::WriteOnly,
+                format: OUTPUT_FORMAT,
+                view_dimension: TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Paint Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage_texture(1),
+                storage_texture(2),
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Paint Bind Group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&canvas_views[i]),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(&canvas_views[1 - i]),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::TextureView(&output_view),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: segments_buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            compilation_options: Default::default(),
+            label: Some("Paint Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Paint Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            })),
+            module,
+            entry_point: "splat",
+            cache: shaders.pipeline_cache.as_deref(),
+        });
+
+        Self {
+            pipeline,
+            bind_groups,
+            segments_buffer,
+            params_buffer,
+            output_texture,
+            output_view,
+            canvases,
+            history: match undo_history {
+                UndoHistory::Tiles => History::Tiles(Box::new(TileHistory::new(
+                    device,
+                    shaders.undo.as_ref().expect("Undo shader was not created"),
+                    shaders.pipeline_cache.as_deref(),
+                    [width, height],
+                    undo_memory,
+                ))),
+                UndoHistory::Snapshots => History::Snapshots(SnapshotRing::new(
+                    device,
+                    OUTPUT_FORMAT,
+                    width,
+                    height,
+                    undo_memory,
+                )),
+            },
+            painted: false,
+            pending: Vec::new(),
+            clear: true,
+            current: 0,
+            workgroup_size: shaders.workgroup_size,
+            width,
+            height,
+        }
+    }
+
+    /// Splat `segments` with the next frames
+    pub fn add_segments(&mut self, segments: Vec<Segment>) {
+        self.pending.extend(segments);
+    }
+
+    /// Keep what the strokes so far changed, before the segments added next, for [`PaintState::undo`]
+    pub fn snapshot(&mut self, device: &Device, queue: &Queue) {
+        // The paper and the segments of earlier strokes still pending belong to the canvas before it
+        let mut encoder = create_encoder(device);
+        self.dispatch(queue, &mut encoder);
+        match &mut self.history {
+            History::Tiles(history) => {
+                queue.submit(Some(encoder.finish()));
+                if std::mem::take(&mut self.painted) {
+                    history.record(device, queue, &self.canvases[self.current]);
+                }
+            }
+            History::Snapshots(history) => {
+                history.push(&mut encoder, &self.canvases[self.current]);
+                queue.submit(Some(encoder.finish()));
+            }
+        }
+    }
+
+    /// Take in the sizes of the tile history's steps that came back from the GPU, once per frame
+    pub fn collect_history(&mut self, device: &Device) {
+        if let History::Tiles(history) = &mut self.history {
+            history.collect(device, false);
+        }
+    }
+
+    /// Take back the last stroke, returns false if there is none
+    pub fn undo(&mut self, device: &Device, queue: &Queue) -> bool {
+        // Segments of the stroke not splatted yet are taken back with it
+        self.pending.clear();
+        self.restore(device, queue, false)
+    }
+
+    /// Paint the stroke taken back last again, returns false if there is none
+    pub fn redo(&mut self, device: &Device, queue: &Queue) -> bool {
+        self.restore(device, queue, true)
+    }
+
+    /// Start over on blank paper, the strokes so far can't be taken back
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        match &mut self.history {
+            History::Tiles(history) => history.clear(),
+            History::Snapshots(history) => history.clear(),
+        }
+        self.clear = true;
+    }
+
+    /// Undo, or `redo`, a step of the history in the canvas and copy it into the output.
+    /// The tile history first keeps the stroke painted since it last recorded as a step of its own
+    fn restore(&mut self, device: &Device, queue: &Queue, redo: bool) -> bool {
+        let mut encoder = create_encoder(device);
+        let restored = match &mut self.history {
+            History::Tiles(history) => {
+                if std::mem::take(&mut self.painted) {
+                    history.record(device, queue, &self.canvases[self.current]);
+                }
+                // The step is applied to the other canvas, which then holds the latest strokes
+                let [canvas, target] = [self.current, 1 - self.current].map(|i| &self.canvases[i]);
+                let step = if redo {
+                    TileHistory::redo
+                } else {
+                    TileHistory::undo
+                };
+                let restored = step(history, device, queue, &mut encoder, canvas, target);
+                if restored {
+                    self.current = 1 - self.current;
+                }
+                restored
+            }
+            History::Snapshots(history) => {
+                let step = if redo {
+                    SnapshotRing::redo
+                } else {
+                    SnapshotRing::undo
+                };
+                step(history, &mut encoder, &self.canvases[self.current])
+            }
+        };
+        if !restored {
+            return false;
+        }
+        self.copy(
+            &mut encoder,
+            &self.canvases[self.current],
+            &self.output_texture,
+        );
+        queue.submit(Some(encoder.finish()));
+        true
+    }
+
+    /// Splat the pending segments into the canvas
+    pub fn dispatch(&mut self, queue: &Queue, encoder: &mut CommandEncoder) {
+        if self.pending.is_empty() && !self.clear {
+            return;
+        }
+
+        let count = self.pending.len().min(MAX_SEGMENTS);
+        let segments: Vec<Segment> = self.pending.drain(..count).collect();
+        if !segments.is_empty() {
+            queue.write_buffer(&self.segments_buffer, 0, bytemuck::cast_slice(&segments));
+        }
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::bytes_of(&PaintParams {
+                segment_count: count as u32,
+                clear: u32::from(std::mem::take(&mut self.clear)),
+                _padding: [0; 2],
+            }),
+        );
+        self.painted = true;
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Paint Pass"),
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_groups[self.current], &[]);
+        compute_pass.dispatch_workgroups(
+            self.width.div_ceil(self.workgroup_size[0]),
+            self.height.div_ceil(self.workgroup_size[1]),
+            1,
+        );
+        self.current = 1 - self.current;
+    }
+
+    fn copy(&self, encoder: &mut CommandEncoder, source: &Texture, destination: &Texture) {
+        encoder.copy_texture_to_texture(
+            source.as_image_copy(),
+            destination.as_image_copy(),
+            Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// What [`PaintState`] keeps for undo, picked by [`UndoHistory`]
+enum History {
+    Tiles(Box<TileHistory>),
+    Snapshots(SnapshotRing),
+}
+
+fn create_encoder(device: &Device) -> CommandEncoder {
+    device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Paint Snapshot Encoder"),
+    })
+}
+
+fn create_canvas(device: &Device, label: &str, width: u32, height: u32) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: OUTPUT_FORMAT,
+        usage: TextureUsages::STORAGE_BINDING
+            | TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_SRC
+            | TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
@@ -0,0 +1,67 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use wgpu::{Adapter, Device, Features, PipelineCache, PipelineCacheDescriptor};
+
+/// Features to request for keeping compiled pipelines between runs, if the adapter can.
+/// So far only Vulkan drivers can, elsewhere pipelines are compiled on every start
+pub fn features(adapter: &Adapter) -> Features {
+    adapter.features() & Features::PIPELINE_CACHE
+}
+
+/// The pipelines compiled by earlier runs on `adapter`, read from the platform cache directory.
+/// `None` unless `device` was created with the [`features`]
+pub fn load(device: &Device, adapter: &Adapter) -> Option<Arc<PipelineCache>> {
+    if !device.features().contains(Features::PIPELINE_CACHE) {
+        return None;
+    }
+    let data = path(adapter).and_then(|path| fs::read(path).ok());
+    // SAFETY: the data was written by `save` from `PipelineCache::get_data`, into a file named after
+    // the cache key of the adapter. With `fallback` an outdated or foreign cache is replaced by an empty one
+    let cache = unsafe {
+        device.create_pipeline_cache(&PipelineCacheDescriptor {
+            label: Some("Pipeline Cache"),
+            data: data.as_deref(),
+            fallback: true,
+        })
+    };
+    Some(Arc::new(cache))
+}
+
+/// Write the pipelines compiled so far, for the next run to start faster
+pub fn save(cache: &PipelineCache, adapter: &Adapter) {
+    let (Some(data), Some(path)) = (cache.get_data(), path(adapter)) else {
+        return;
+    };
+    // Written next to it first, so a crash can't leave half a cache behind
+    let temp_path = path.with_extension("temp");
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&temp_path, data))
+        .and_then(|()| fs::rename(&temp_path, &path));
+    if let Err(err) = result {
+        log::warn!(
+            "Failed to save the pipeline cache {}: {err}",
+            path.display()
+        );
+    }
+}
+
+/// File the pipelines of `adapter` are kept in, `None` if it can't keep them
+fn path(adapter: &Adapter) -> Option<PathBuf> {
+    let key = wgpu::util::pipeline_cache_key(&adapter.get_info())?;
+    let home_cache = if cfg!(target_os = "macos") {
+        "Library/Caches"
+    } else {
+        ".cache"
+    };
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(home_cache)))
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(cache_dir.join(env!("CARGO_PKG_NAME")).join(key))
+}
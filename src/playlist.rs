@@ -0,0 +1,113 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use web_time::Instant;
+
+/// The `.wgsl` files of a `--playlist` directory, cycled through on a timer or with Page Up and Page Down
+pub struct Playlist {
+    /// Sorted by name
+    shaders: Vec<PathBuf>,
+    /// Index of the shader shown
+    index: usize,
+    /// `None` when only the keys switch
+    interval: Option<Duration>,
+    shown_at: Instant,
+}
+
+impl Playlist {
+    /// Every `.wgsl` file in `directory`, `interval` is `None` to only switch with the keys
+    pub fn load(directory: &Path, interval: Option<Duration>) -> io::Result<Self> {
+        let mut shaders: Vec<PathBuf> = fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<_>>()?;
+        shaders.retain(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "wgsl")
+        });
+        shaders.sort();
+        if shaders.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No .wgsl files in {}", directory.display()),
+            ));
+        }
+        Ok(Self {
+            shaders,
+            index: 0,
+            interval,
+            shown_at: Instant::now(),
+        })
+    }
+
+    pub fn shaders(&self) -> &[PathBuf] {
+        &self.shaders
+    }
+
+    /// Index of the shader shown
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Drop the shader at `index` from the playlist, for ones that fail to build.
+    /// The one shown can't be removed
+    pub fn remove(&mut self, index: usize) {
+        if index == self.index {
+            return;
+        }
+        self.shaders.remove(index);
+        if index < self.index {
+            self.index -= 1;
+        }
+    }
+
+    /// Move `steps` entries on, backwards if negative, and start timing the new one. Returns its index
+    pub fn step(&mut self, steps: isize) -> usize {
+        let len = self.shaders.len() as isize;
+        self.index = (self.index as isize + steps).rem_euclid(len) as usize;
+        self.shown_at = Instant::now();
+        self.index
+    }
+
+    /// Whether the shader shown has been up for the interval
+    pub fn due(&self) -> bool {
+        self.shaders.len() > 1
+            && self
+                .interval
+                .is_some_and(|interval| self.shown_at.elapsed() >= interval)
+    }
+}
+
+/// How the new scene replaces the previous one during a [`Crossfade`], applied by the render pass
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transition {
+    /// Blend the whole image at once
+    Fade,
+    /// Sweep a soft edge from left to right
+    Wipe,
+    /// Switch pixel by pixel in random order
+    Dissolve,
+}
+
+/// A switch between two scenes, both keep running while the new one is drawn over the previous one
+pub struct Crossfade {
+    started: Instant,
+    duration: Duration,
+}
+
+impl Crossfade {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            started: Instant::now(),
+            duration,
+        }
+    }
+
+    /// From 0 when the crossfade starts to 1 once it is over
+    pub fn progress(&self) -> f32 {
+        (self.started.elapsed().as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+}
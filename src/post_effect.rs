@@ -0,0 +1,370 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{
+    compute::OUTPUT_FORMAT,
+    globals::GlobalsState,
+    graph::{MAX_SCALE, Pass, RenderGraph, scaled},
+    preprocessor::ShaderSource,
+    project::{PROJECT_FILE, Project},
+    scene::{Drawing, SceneConfig},
+    shaders::Shaders,
+};
+
+/// File in the post-effects directory listing the passes
+const MANIFEST: &str = "effects.toml";
+
+/// `effects.toml`, the passes of a post-effect stack in the order they are applied
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default, rename = "pass")]
+    passes: Vec<PassConfig>,
+}
+
+/// A `[[pass]]` of `effects.toml` or of a project
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PassConfig {
+    /// WGSL file providing the `effect` function, relative to the manifest
+    shader: PathBuf,
+    /// Up to four numbers handed to the shader as `params`, zero if left out
+    #[serde(default)]
+    params: Vec<f32>,
+    /// Factor of the presented resolution the pass runs at, like 0.5 for half the width and height,
+    /// up to [`MAX_SCALE`]. Its input, and the image after the last pass, are resampled when the sizes differ
+    #[serde(default = "full_scale")]
+    scale: f32,
+}
+
+fn full_scale() -> f32 {
+    1.0
+}
+
+impl PassConfig {
+    pub fn new(shader: PathBuf, params: Vec<f32>) -> Self {
+        Self {
+            shader,
+            params,
+            scale: full_scale(),
+        }
+    }
+}
+
+/// A fullscreen compute pass of a user-supplied shader, run on the presented image.
+/// The shader provides `fn effect(pixel: vec2<u32>) -> vec4<f32>` reading the previous pass,
+/// see `src/shaders/post_effect.wgsl`
+pub struct PostEffect {
+    pipeline: ComputePipeline,
+    bind_group: BindGroup,
+    globals_bind_group: BindGroup,
+    pub output_texture: Texture,
+    pub output_view: TextureView,
+    workgroup_size: [u32; 2],
+}
+
+impl PostEffect {
+    /// Add the passes listed in the manifest of `dir` to the `graph`,
+    /// each reading the output of the node before it
+    pub fn add_stack(
+        device: &Device,
+        dir: &Path,
+        graph: &mut RenderGraph,
+        globals: &GlobalsState,
+        config: &SceneConfig,
+    ) -> Result<(), String> {
+        let mut manifest_path = dir.join(MANIFEST);
+        // A project directory lists its passes in the project file
+        let passes = if !manifest_path.is_file() && dir.join(PROJECT_FILE).is_file() {
+            manifest_path = dir.join(PROJECT_FILE);
+            Project::load(dir)?.passes
+        } else {
+            fs::read_to_string(&manifest_path)
+                .map_err(|err| err.to_string())
+                .and_then(|contents| {
+                    toml::from_str::<Manifest>(&contents).map_err(|err| err.to_string())
+                })
+                .map_err(|err| format!("{}: {err}", manifest_path.display()))?
+                .passes
+        };
+        Self::add_passes(device, dir, &passes, graph, globals, config)
+            .map_err(|err| format!("{}: {err}", manifest_path.display()))
+    }
+
+    /// Add `passes` with shaders relative to `dir` to the `graph`,
+    /// each reading the output of the node before it, resampled to its own size if that differs
+    pub fn add_passes(
+        device: &Device,
+        dir: &Path,
+        passes: &[PassConfig],
+        graph: &mut RenderGraph,
+        globals: &GlobalsState,
+        config: &SceneConfig,
+    ) -> Result<(), String> {
+        // Effects run on the presented image, after supersampling is averaged down
+        let presented = config.presented_size;
+        for pass in passes {
+            if pass.params.len() > 4 {
+                return Err(format!("{} has more than 4 params", pass.shader.display()));
+            }
+            if !(pass.scale > 0.0 && pass.scale <= MAX_SCALE) {
+                return Err(format!(
+                    "{} has to run at a scale above 0 and up to {MAX_SCALE}",
+                    pass.shader.display()
+                ));
+            }
+            let mut params = [0.0; 4];
+            params[..pass.params.len()].copy_from_slice(&pass.params);
+
+            let source = ShaderSource::read(&dir.join(&pass.shader))
+                .map_err(|err| format!("Failed to read {}: {err}", pass.shader.display()))?;
+            let size = scaled(presented, pass.scale);
+            Self::resample(device, size, graph, globals, config)?;
+            let input = graph
+                .last()
+                .ok_or("Post effects need a drawn image")?
+                .to_owned();
+            let input_view = graph.view(&input).unwrap();
+            let scratch = scratch(graph);
+            let module = Shaders::create_post_effect_shader(
+                device,
+                &source,
+                config,
+                scratch.map(Buffer::size),
+            )?;
+            let effect = Self::new(
+                device, &module, input_view, scratch, params, size, globals, config,
+            );
+            let number = numbered(graph, "post effect");
+            graph.add(number, &[&input], Pass::PostEffect(effect))?;
+        }
+        if passes.is_empty() {
+            return Ok(());
+        }
+        Self::resample(device, presented, graph, globals, config)
+    }
+
+    /// Add a node scaling the output of the last one to `size`, unless it already has that size
+    pub fn resample(
+        device: &Device,
+        [width, height]: [u32; 2],
+        graph: &mut RenderGraph,
+        globals: &GlobalsState,
+        config: &SceneConfig,
+    ) -> Result<(), String> {
+        let Some(input) = graph.last().map(str::to_owned) else {
+            return Err("Nothing to resample in an empty render graph".to_owned());
+        };
+        let max_size = device.limits().max_texture_dimension_2d;
+        if width.max(height) > max_size {
+            return Err(format!(
+                "Resampling to {width}x{height} exceeds the maximum texture size of {max_size}"
+            ));
+        }
+        let texture = graph.texture(&input).unwrap();
+        if [texture.width(), texture.height()] == [width, height] {
+            return Ok(());
+        }
+        let source =
+            ShaderSource::builtin("resample.wgsl", include_str!("./shaders/resample.wgsl"));
+        let module = Shaders::create_post_effect_shader(device, &source, config, None)?;
+        let input_view = graph.view(&input).unwrap();
+        let resample = Self::new(
+            device,
+            &module,
+            input_view,
+            None,
+            [0.0; 4],
+            [width, height],
+            globals,
+            config,
+        );
+        let number = numbered(graph, "resample");
+        graph.add(number, &[&input], Pass::PostEffect(resample))
+    }
+
+    /// A pass running `module`, which declares `scratch` if it is given
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &Device,
+        module: &ShaderModule,
+        input_view: &TextureView,
+        scratch: Option<&Buffer>,
+        params: [f32; 4],
+        [width, height]: [u32; 2],
+        globals: &GlobalsState,
+        config: &SceneConfig,
+    ) -> Self {
+        let output_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Post Effect Output Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::STORAGE_BINDING
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Post Effect Params Buffer"),
+            contents: bytemuck::cast_slice(&params),
+            usage: BufferUsages::UNIFORM,
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Post Effect Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mut layout_entries = vec![
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::WriteOnly,
+                    format: OUTPUT_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+        let mut entries = vec![
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(input_view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(&sampler),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::TextureView(&output_view),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: params_buffer.as_entire_binding(),
+            },
+        ];
+        if let Some(scratch) = scratch {
+            layout_entries.push(BindGroupLayoutEntry {
+                binding: 4,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+            entries.push(BindGroupEntry {
+                binding: 4,
+                resource: scratch.as_entire_binding(),
+            });
+        }
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Post Effect Bind Group Layout"),
+            entries: &layout_entries,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Post Effect Bind Group"),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            compilation_options: Default::default(),
+            label: Some("Post Effect Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Post Effect Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout, &globals.bind_group_layout],
+                push_constant_ranges: &globals.push_constant_ranges(ShaderStages::COMPUTE),
+            })),
+            module,
+            entry_point: "main",
+            cache: config.pipeline_cache.as_deref(),
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            globals_bind_group: globals.create_bind_group(device),
+            output_texture,
+            output_view,
+            workgroup_size: config.workgroup_size,
+        }
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, globals: &GlobalsState) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Post Effect Pass"),
+        });
+
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.globals_bind_group, &[]);
+        globals.set_compute_push_constants(&mut compute_pass);
+        let [workgroup_width, workgroup_height] = self.workgroup_size;
+        compute_pass.dispatch_workgroups(
+            self.output_texture.width().div_ceil(workgroup_width),
+            self.output_texture.height().div_ceil(workgroup_height),
+            1,
+        );
+    }
+}
+
+/// The scratch buffer of the compute drawing in `graph`, shared by the post effects after it
+fn scratch(graph: &RenderGraph) -> Option<&Buffer> {
+    graph.passes().find_map(|pass| match pass {
+        Pass::Drawing(Drawing::Compute(compute_state)) => compute_state.scratch(),
+        _ => None,
+    })
+}
+
+/// `name` numbered on from the nodes of that name already in the `graph`
+fn numbered(graph: &RenderGraph, name: &str) -> String {
+    let number = graph.names().filter(|node| node.starts_with(name)).count() + 1;
+    format!("{name} {number}")
+}
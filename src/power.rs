@@ -0,0 +1,167 @@
+use std::{fs, time::Duration};
+
+use web_time::Instant;
+
+/// Highest frame rate while throttled
+const THROTTLED_FPS: u32 = 30;
+/// Fraction of the image width and height drawn while throttled
+const THROTTLED_RESOLUTION_SCALE: f32 = 0.5;
+/// How often battery and temperatures are read
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Throttling starts when a thermal zone reaches the first temperature in °C,
+/// and ends once all of them cooled down below the second
+const HOT: f32 = 85.0;
+const COOLED_DOWN: f32 = 75.0;
+/// Throttling lasts at least this long,
+/// so plugging a laptop in and out doesn't rebuild the scene every time
+const MIN_THROTTLE_DURATION: Duration = Duration::from_secs(30);
+/// Screensavers throttle after running this long, when nobody is looking closely anymore
+const SCREENSAVER_IDLE: Duration = Duration::from_secs(10 * 60);
+
+/// Lowers frame rate and resolution while running on battery or when the system runs hot,
+/// for `--power-saving`.
+///
+/// Reads the Linux `/sys/class/power_supply` and `/sys/class/thermal` interfaces,
+/// elsewhere nothing is throttled
+pub struct PowerSaving {
+    /// Frame rate even while not throttled
+    frame_rate_cap: Option<u32>,
+    /// Throttle once running this long
+    idle_after: Option<Duration>,
+    started: Instant,
+    throttled: bool,
+    throttled_since: Instant,
+    last_check: Option<Instant>,
+    last_frame: Instant,
+}
+
+impl Default for PowerSaving {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PowerSaving {
+    pub fn new() -> Self {
+        Self {
+            frame_rate_cap: None,
+            idle_after: None,
+            started: Instant::now(),
+            throttled: false,
+            throttled_since: Instant::now(),
+            last_check: None,
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// For `--wallpaper`, which draws behind everything all day: always capped at 30 fps,
+    /// and at half the resolution too while on battery or running hot
+    pub fn wallpaper() -> Self {
+        Self {
+            frame_rate_cap: Some(THROTTLED_FPS),
+            ..Self::new()
+        }
+    }
+
+    /// For screensavers: always capped at `fps`, and throttled for good after ten minutes
+    pub fn screensaver(fps: u32) -> Self {
+        Self {
+            frame_rate_cap: Some(fps),
+            idle_after: Some(SCREENSAVER_IDLE),
+            ..Self::new()
+        }
+    }
+
+    /// Check battery and temperatures if it's time to,
+    /// returns whether to throttle when that changed
+    pub fn poll(&mut self) -> Option<bool> {
+        if self
+            .last_check
+            .is_some_and(|last_check| last_check.elapsed() < CHECK_INTERVAL)
+        {
+            return None;
+        }
+        self.last_check = Some(Instant::now());
+
+        let on_battery = on_battery();
+        let temperature = hottest_temperature();
+        let hot = temperature.is_some_and(|temperature| {
+            temperature >= if self.throttled { COOLED_DOWN } else { HOT }
+        });
+        let idle = self
+            .idle_after
+            .is_some_and(|idle_after| self.started.elapsed() >= idle_after);
+        let throttle = on_battery || hot || idle;
+        if throttle == self.throttled
+            || (!throttle && self.throttled_since.elapsed() < MIN_THROTTLE_DURATION)
+        {
+            return None;
+        }
+
+        self.throttled = throttle;
+        match temperature {
+            _ if !throttle => log::info!("Back to full frame rate and resolution"),
+            _ if on_battery => log::info!("Running on battery, saving power"),
+            _ if idle => log::info!("Running for a while, saving power"),
+            Some(temperature) => log::info!("Running hot at {temperature:.0}°C, saving power"),
+            None => {}
+        }
+        if throttle {
+            self.throttled_since = Instant::now();
+        }
+        Some(throttle)
+    }
+
+    /// When the next frame is due, `None` if it can be drawn right away
+    pub fn next_frame_at(&mut self) -> Option<Instant> {
+        let fps = match (self.throttled, self.frame_rate_cap) {
+            (false, None) => return None,
+            (false, Some(cap)) => cap,
+            (true, cap) => cap.map_or(THROTTLED_FPS, |cap| cap.min(THROTTLED_FPS)),
+        };
+
+        let due = self.last_frame + Duration::from_secs(1) / fps;
+        if Instant::now() < due {
+            return Some(due);
+        }
+        self.last_frame = Instant::now();
+        None
+    }
+
+    /// Size of the drawn image, scaled down while throttled
+    pub fn image_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        if !self.throttled {
+            return (width, height);
+        }
+        let scale = |size: u32| ((size as f32 * THROTTLED_RESOLUTION_SCALE) as u32).max(1);
+        (scale(width), scale(height))
+    }
+}
+
+/// Whether a battery is discharging
+fn on_battery() -> bool {
+    let Ok(supplies) = fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    supplies.flatten().any(|supply| {
+        let path = supply.path();
+        let read = |file| fs::read_to_string(path.join(file)).unwrap_or_default();
+        read("type").trim() == "Battery" && read("status").trim() == "Discharging"
+    })
+}
+
+/// Temperature of the hottest thermal zone in °C
+fn hottest_temperature() -> Option<f32> {
+    fs::read_dir("/sys/class/thermal")
+        .ok()?
+        .flatten()
+        .filter(|zone| {
+            zone.file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+        })
+        .filter_map(|zone| fs::read_to_string(zone.path().join("temp")).ok())
+        .filter_map(|temp| temp.trim().parse::<f32>().ok())
+        .map(|millidegrees| millidegrees / 1000.0)
+        .max_by(f32::total_cmp)
+}
@@ -0,0 +1,452 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{counters, glsl};
+
+/// Includes nested deeper than this are assumed to be cyclic
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Snippets built into the binary by name, included with `#include <name>`
+pub const SNIPPETS: &[(&str, &str)] = &[("counters.wgsl", counters::WGSL)];
+
+/// A drawing shader as written, before [`preprocess`]ing
+pub struct ShaderSource {
+    /// Shown in error messages
+    pub name: String,
+    pub text: String,
+    /// File the text was read from, `#include`s are relative to its directory
+    pub path: Option<PathBuf>,
+}
+
+impl ShaderSource {
+    /// Read a WGSL file, or a GLSL one ending in `.glsl` or `.frag` [translated](glsl::to_wgsl) to WGSL
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let mut text = fs::read_to_string(path)?;
+        if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(glsl::is_glsl)
+        {
+            text = glsl::to_wgsl(&text).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {err}", path.display()),
+                )
+            })?;
+        }
+        Ok(Self {
+            name: path.display().to_string(),
+            text,
+            path: Some(path.to_owned()),
+        })
+    }
+
+    /// A shader built into the binary, it can only include files relative to the working directory
+    pub fn builtin(name: &str, text: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            text: text.to_owned(),
+            path: None,
+        }
+    }
+}
+
+/// WGSL ready for module creation, remembering which file and line every line came from
+#[derive(Default)]
+pub struct Preprocessed {
+    pub text: String,
+    files: Vec<String>,
+    /// Index into `files` and line number of every line of `text`
+    origins: Vec<(usize, usize)>,
+}
+
+impl Preprocessed {
+    /// Append `text` without preprocessing it, e.g. generated declarations or built-in entry points
+    pub fn push_inline(&mut self, name: &str, text: &str) {
+        let file = self.file_index(name);
+        for (index, line) in text.lines().enumerate() {
+            self.push_line(line, file, index + 1);
+        }
+    }
+
+    pub fn push(&mut self, other: &Preprocessed) {
+        for (line, &(file, number)) in other.text.lines().zip(&other.origins) {
+            let file = self.file_index(&other.files[file]);
+            self.push_line(line, file, number);
+        }
+    }
+
+    fn file_index(&mut self, name: &str) -> usize {
+        match self.files.iter().position(|file| file == name) {
+            Some(index) => index,
+            None => {
+                self.files.push(name.to_owned());
+                self.files.len() - 1
+            }
+        }
+    }
+
+    fn push_line(&mut self, line: &str, file: usize, number: usize) {
+        self.text += line;
+        self.text += "\n";
+        self.origins.push((file, number));
+    }
+
+    /// File and line number the line at `index` of `text` was written at, like `draw.wgsl:12`
+    pub fn location(&self, index: usize) -> String {
+        self.origins
+            .get(index)
+            .map_or_else(String::new, |&(file, number)| {
+                format!("{}:{number}", self.files[file])
+            })
+    }
+
+    /// Point the `wgsl:LINE:COLUMN` locations and line number gutters of a shader error
+    /// at the file and line the code was written in
+    pub fn remap_error(&self, message: &str) -> String {
+        let origin = |line: &str| {
+            let (file, number) = *self
+                .origins
+                .get(line.parse::<usize>().ok()?.checked_sub(1)?)?;
+            Some((&self.files[file], number))
+        };
+
+        let mut remapped = String::with_capacity(message.len());
+        for line in message.lines() {
+            if let Some((prefix, location)) = line.split_once("┌─ ")
+                && let [column, number, _] = location.rsplitn(3, ':').collect::<Vec<_>>()[..]
+                && let Some((file, number)) = origin(number)
+            {
+                remapped += &format!("{prefix}┌─ {file}:{number}:{column}");
+            } else if let Some((gutter, code)) = line.split_once(" │")
+                && let Some((_, number)) = origin(gutter.trim())
+            {
+                let width = gutter.len();
+                remapped += &format!("{number:>width$} │{code}");
+            } else {
+                remapped += line;
+            }
+            remapped += "\n";
+        }
+        remapped
+    }
+}
+
+/// Resolve the directives of `source`:
+///
+/// - `#include "file.wgsl"` inserts a file, relative to the including one
+/// - `#include <counters.wgsl>` inserts a snippet built into the binary, see [`SNIPPETS`]
+/// - `#define NAME` or `#define NAME VALUE`, replacing `NAME` with `VALUE` in the following lines
+/// - `#undef NAME`
+/// - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` keep or drop the lines between them
+///
+/// `defines` are defined up front, e.g. `COMPUTE` by [`Shaders`](crate::shaders::Shaders).
+/// Integer values of `#define`s above `max_define_value` are lowered to it
+pub fn preprocess(
+    source: &ShaderSource,
+    defines: &[&str],
+    max_define_value: Option<u32>,
+) -> Result<Preprocessed, String> {
+    let mut preprocessor = Preprocessor {
+        defines: defines
+            .iter()
+            .map(|&name| (name.to_owned(), String::new()))
+            .collect(),
+        max_define_value,
+        output: Preprocessed::default(),
+    };
+    preprocessor.run(&source.name, &source.text, source.path.as_deref(), 0)?;
+    Ok(preprocessor.output)
+}
+
+struct Preprocessor {
+    defines: HashMap<String, String>,
+    max_define_value: Option<u32>,
+    output: Preprocessed,
+}
+
+/// An `#ifdef` or `#ifndef` and where it stands
+struct Conditional {
+    line: usize,
+    /// Whether the lines of the current branch are kept, disregarding enclosing conditionals
+    active: bool,
+    in_else: bool,
+}
+
+impl Preprocessor {
+    fn run(
+        &mut self,
+        name: &str,
+        text: &str,
+        path: Option<&Path>,
+        depth: usize,
+    ) -> Result<(), String> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(format!("{name}: includes nested too deeply"));
+        }
+
+        let file = self.output.file_index(name);
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let error = |message: &str| format!("{name}:{number}: {message}");
+            let active = conditionals.iter().all(|conditional| conditional.active);
+
+            let Some(directive) = line.trim().strip_prefix('#') else {
+                if active {
+                    let line = self.substitute(line);
+                    self.output.push_line(&line, file, number);
+                }
+                continue;
+            };
+            let (keyword, argument) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(keyword, argument)| {
+                    (keyword, argument.trim())
+                });
+            match keyword {
+                "ifdef" | "ifndef" => conditionals.push(Conditional {
+                    line: number,
+                    active: self.defines.contains_key(argument) == (keyword == "ifdef"),
+                    in_else: false,
+                }),
+                "else" => match conditionals.last_mut() {
+                    Some(conditional) if !conditional.in_else => {
+                        conditional.active = !conditional.active;
+                        conditional.in_else = true;
+                    }
+                    Some(_) => return Err(error("#else after #else")),
+                    None => return Err(error("#else without #ifdef")),
+                },
+                "endif" => {
+                    conditionals
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef"))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) = argument
+                        .split_once(char::is_whitespace)
+                        .map_or((argument, ""), |(define, value)| (define, value.trim()));
+                    if define.is_empty() {
+                        return Err(error("#define without a name"));
+                    }
+                    let value = self.limit(define, value);
+                    self.defines.insert(define.to_owned(), value);
+                }
+                "undef" => {
+                    self.defines.remove(argument);
+                }
+                "include" if argument.starts_with('<') => {
+                    let name = argument.trim_start_matches('<').trim_end_matches('>');
+                    let (_, snippet) = SNIPPETS
+                        .iter()
+                        .find(|(snippet, _)| *snippet == name)
+                        .ok_or_else(|| error(&format!("Unknown snippet <{name}>")))?;
+                    self.run(&format!("<{name}>"), snippet, None, depth + 1)?;
+                }
+                "include" => {
+                    let include = argument.trim_matches('"');
+                    let dir = path.and_then(Path::parent).unwrap_or(Path::new("."));
+                    let include_path = dir.join(include);
+                    let include_text = fs::read_to_string(&include_path)
+                        .map_err(|err| error(&format!("Failed to include {include}: {err}")))?;
+                    self.run(
+                        &include_path.display().to_string(),
+                        &include_text,
+                        Some(&include_path),
+                        depth + 1,
+                    )?;
+                }
+                _ => return Err(error(&format!("Unknown directive #{keyword}"))),
+            }
+        }
+
+        match conditionals.last() {
+            Some(conditional) => Err(format!(
+                "{name}:{}: #ifdef without #endif",
+                conditional.line
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// `value` lowered to `max_define_value` if it is a larger integer, keeping its suffix
+    fn limit(&self, define: &str, value: &str) -> String {
+        let digits = value.trim_end_matches(['u', 'i']);
+        match (self.max_define_value, digits.parse::<u64>()) {
+            (Some(max), Ok(number)) if number > u64::from(max) => {
+                log::warn!("Lowering {define} from {digits} to {max} in the sandbox");
+                format!("{max}{}", &value[digits.len()..])
+            }
+            _ => value.to_owned(),
+        }
+    }
+
+    /// `line` with every identifier `#define`d to a value replaced by it
+    fn substitute(&self, line: &str) -> String {
+        if self.defines.values().all(String::is_empty) {
+            return line.to_owned();
+        }
+
+        let mut substituted = String::with_capacity(line.len());
+        let mut identifier = String::new();
+        for c in line.chars().chain(Some('\n')) {
+            if c.is_alphanumeric() || c == '_' {
+                identifier.push(c);
+                continue;
+            }
+            match self.defines.get(&identifier) {
+                Some(value) if !value.is_empty() => substituted += value,
+                _ => substituted += &identifier,
+            }
+            identifier.clear();
+            if c != '\n' {
+                substituted.push(c);
+            }
+        }
+        substituted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for the files of a test
+    fn dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("preprocessor-{}-{test}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, name: &str, text: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn run(text: &str, defines: &[&str]) -> Result<String, String> {
+        preprocess(&ShaderSource::builtin("test.wgsl", text), defines, None)
+            .map(|preprocessed| preprocessed.text)
+    }
+
+    #[test]
+    fn nested_includes() {
+        let dir = dir("nested");
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        write(&dir, "lib/inner.wgsl", "inner");
+        write(
+            &dir,
+            "lib/outer.wgsl",
+            "outer before\n#include \"inner.wgsl\"\nouter after",
+        );
+        let main = write(
+            &dir,
+            "main.wgsl",
+            "main before\n#include \"lib/outer.wgsl\"\nmain after",
+        );
+
+        let source = ShaderSource::read(&main).unwrap();
+        let preprocessed = preprocess(&source, &[], None).unwrap();
+        assert_eq!(
+            preprocessed.text,
+            "main before\nouter before\ninner\nouter after\nmain after\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_fail() {
+        let dir = dir("cycle");
+        write(&dir, "a.wgsl", "#include \"b.wgsl\"");
+        let b = write(&dir, "b.wgsl", "#include \"a.wgsl\"");
+
+        let source = ShaderSource::read(&b).unwrap();
+        let error = preprocess(&source, &[], None).err().unwrap();
+        assert!(error.contains("nested too deeply"), "{error}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let text = "#ifdef A\n\
+                    a\n\
+                    #ifdef B\n\
+                    ab\n\
+                    #else\n\
+                    a not b\n\
+                    #endif\n\
+                    #else\n\
+                    not a\n\
+                    #ifndef B\n\
+                    neither\n\
+                    #endif\n\
+                    #endif\n\
+                    always";
+        assert_eq!(run(text, &["A", "B"]).unwrap(), "a\nab\nalways\n");
+        assert_eq!(run(text, &["A"]).unwrap(), "a\na not b\nalways\n");
+        assert_eq!(run(text, &["B"]).unwrap(), "not a\nalways\n");
+        assert_eq!(run(text, &[]).unwrap(), "not a\nneither\nalways\n");
+    }
+
+    #[test]
+    fn dropped_lines_define_nothing() {
+        let text = "#ifdef A\n#define SIZE 4\n#endif\nSIZE";
+        assert_eq!(run(text, &[]).unwrap(), "SIZE\n");
+        assert_eq!(run(text, &["A"]).unwrap(), "4\n");
+    }
+
+    #[test]
+    fn unterminated_conditionals_fail() {
+        assert_eq!(
+            run("a\n#ifdef A\nb\n#ifdef B\n#endif", &[]),
+            Err("test.wgsl:2: #ifdef without #endif".to_owned())
+        );
+        assert_eq!(
+            run("#endif", &[]),
+            Err("test.wgsl:1: #endif without #ifdef".to_owned())
+        );
+        assert_eq!(
+            run("#ifdef A\n#else\n#else\n#endif", &[]),
+            Err("test.wgsl:3: #else after #else".to_owned())
+        );
+    }
+
+    #[test]
+    fn errors_point_at_included_files() {
+        let dir = dir("remap");
+        let include = write(&dir, "noise.wgsl", "fn noise() {}\nfn broken() { oops }");
+        let main = write(
+            &dir,
+            "main.wgsl",
+            "// main\n#include \"noise.wgsl\"\nfn draw() {}",
+        );
+
+        let mut preprocessed = Preprocessed::default();
+        preprocessed.push_inline("prelude", "const A: u32 = 1u;");
+        preprocessed.push(&preprocess(&ShaderSource::read(&main).unwrap(), &[], None).unwrap());
+        // Line 4 of the whole shader is line 2 of the included file
+        let message = "error: no definition in scope for identifier: `oops`\n  \
+                       ┌─ wgsl:4:15\n  \
+                       │\n\
+                       4 │ fn broken() { oops }\n  \
+                       │               ^^^^ unknown identifier";
+        let remapped = preprocessed.remap_error(message);
+        let lines: Vec<&str> = remapped.lines().collect();
+        assert_eq!(lines[1], format!("  ┌─ {}:2:15", include.display()));
+        assert_eq!(lines[3], "2 │ fn broken() { oops }");
+        assert_eq!(lines[4], "  │               ^^^^ unknown identifier");
+
+        // Lines of the including file keep their numbers
+        let message = "  ┌─ wgsl:5:1";
+        assert_eq!(
+            preprocessed.remap_error(message),
+            format!("  ┌─ {}:3:1\n", main.display())
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use wgpu::*;
+
+use crate::{
+    cli::Options,
+    frame_hash,
+    globals::GlobalsState,
+    graph::{Pass, RenderGraph},
+    headless, pipeline_cache,
+    post_effect::{PassConfig, PostEffect},
+    recording,
+    render::{LoadedImage, load_image, upload_image},
+    scene::SceneConfig,
+    video::{AudioTrack, VideoDecoder, VideoEncoder},
+};
+
+/// Name of the node holding the image or video frame being processed
+const INPUT: &str = "input";
+
+/// Run the `effects` and then the `--post-effects` over every PNG and every frame of the videos of `inputs`
+/// on the GPU and write the results to the directory `output` under the names of the inputs.
+/// Returns how many files were written
+pub fn run(
+    options: &Options,
+    inputs: &[PathBuf],
+    effects: &[PathBuf],
+    output: &Path,
+) -> Result<usize, String> {
+    if effects.is_empty() && options.post_effects.is_none() {
+        return Err("Nothing to run, pass --effect or --post-effects".to_owned());
+    }
+    let (adapter, device, queue) = pollster::block_on(headless::create_device(options))
+        .ok_or("No adapter with compute shader support found, see --list-adapters")?;
+    let pipeline_cache = pipeline_cache::load(&device, &adapter);
+    fs::create_dir_all(output)
+        .map_err(|err| format!("Failed to create {}: {err}", output.display()))?;
+
+    // `--params` are the first two params of every `--effect`
+    let params = options.params.map_or(Vec::new(), |params| params.to_vec());
+    let effects: Vec<_> = effects
+        .iter()
+        .map(|shader| PassConfig::new(shader.clone(), params.clone()))
+        .collect();
+
+    let result = inputs.iter().try_for_each(|input| {
+        let name = input
+            .file_name()
+            .ok_or_else(|| format!("{} isn't a file", input.display()))?;
+        let path = output.join(name);
+        if let (Ok(path), Ok(input)) = (fs::canonicalize(&path), fs::canonicalize(input))
+            && path == input
+        {
+            return Err(format!(
+                "Refusing to overwrite the input {}",
+                input.display()
+            ));
+        }
+        let is_png = input
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        let cache = pipeline_cache.as_ref();
+        let result = if is_png {
+            process_image(options, &device, &queue, cache, input, &path, &effects)
+        } else {
+            process_video(options, &device, &queue, cache, input, &path, &effects)
+        };
+        result.map_err(|err| format!("{}: {err}", input.display()))?;
+        log::info!("Wrote {}", path.display());
+        Ok(())
+    });
+    // Also what was compiled before a failure
+    if let Some(cache) = &pipeline_cache {
+        pipeline_cache::save(cache, &adapter);
+    }
+    result.map(|()| inputs.len())
+}
+
+fn process_image(
+    options: &Options,
+    device: &Device,
+    queue: &Queue,
+    pipeline_cache: Option<&Arc<PipelineCache>>,
+    input: &Path,
+    output: &Path,
+    effects: &[PassConfig],
+) -> Result<(), String> {
+    let image = load_image(device, queue, input).map_err(|err| err.to_string())?;
+    let (width, height) = (image.texture.width(), image.texture.height());
+    let mut processor = Processor::new(options, device, queue, pipeline_cache, image, effects)?;
+    let pixels = processor.run(device, queue)?;
+    recording::write_png(output, width, height, &pixels)
+        .map_err(|err| format!("Failed to write {}: {err}", output.display()))
+}
+
+/// Run the passes over every frame of the video at `input`, with `globals.time` and `globals.frame`
+/// counting the frames, and encode the results along with its audio
+fn process_video(
+    options: &Options,
+    device: &Device,
+    queue: &Queue,
+    pipeline_cache: Option<&Arc<PipelineCache>>,
+    input: &Path,
+    output: &Path,
+    effects: &[PassConfig],
+) -> Result<(), String> {
+    let mut decoder = VideoDecoder::new(input).map_err(|err| err.to_string())?;
+    let info = decoder.info();
+    let first = decoder
+        .read_frame()
+        .map_err(|err| err.to_string())?
+        .ok_or("The video has no frames")?;
+    let image = upload_image(device, queue, info.width, info.height, &first);
+    let mut processor = Processor::new(options, device, queue, pipeline_cache, image, effects)?;
+    let mut encoder = VideoEncoder::new(output, info, Some(AudioTrack::CopiedFrom(input)))
+        .map_err(|err| err.to_string())?;
+
+    let mut frame = Some(first);
+    let mut index = 0;
+    while let Some(pixels) = frame {
+        processor.set_input(queue, &pixels);
+        processor.globals.globals.frame = index;
+        processor.globals.globals.time = info.time(index) as f32;
+        let processed = processor.run(device, queue)?;
+        encoder
+            .write_frame(&processed)
+            .map_err(|err| format!("Failed to encode frame {index}: {err}"))?;
+        frame = decoder.read_frame().map_err(|err| err.to_string())?;
+        index += 1;
+    }
+    encoder.finish().map_err(|err| err.to_string())?;
+    decoder.finish().map_err(|err| err.to_string())?;
+    log::info!("Processed {index} frames");
+    Ok(())
+}
+
+/// The passes for images of one size, reading the node [`INPUT`]
+struct Processor {
+    graph: RenderGraph,
+    globals: GlobalsState,
+}
+
+impl Processor {
+    fn new(
+        options: &Options,
+        device: &Device,
+        queue: &Queue,
+        pipeline_cache: Option<&Arc<PipelineCache>>,
+        image: LoadedImage,
+        effects: &[PassConfig],
+    ) -> Result<Self, String> {
+        let (width, height) = (image.texture.width(), image.texture.height());
+        // Shaders are compiled for the size of the images, which is a constant in them
+        let config = SceneConfig {
+            pipeline_cache: pipeline_cache.cloned(),
+            ..SceneConfig::headless(options, width, height)
+        };
+        let globals = GlobalsState::new(device, queue, width, height);
+
+        device.push_error_scope(ErrorFilter::Validation);
+        let mut graph = RenderGraph::default();
+        graph.add(INPUT, &[], Pass::Image(image))?;
+        PostEffect::add_passes(
+            device,
+            Path::new(""),
+            effects,
+            &mut graph,
+            &globals,
+            &config,
+        )?;
+        if let Some(dir) = &options.post_effects {
+            PostEffect::add_stack(device, dir, &mut graph, &globals, &config)?;
+        }
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(error.to_string());
+        }
+        Ok(Self { graph, globals })
+    }
+
+    /// Replace the input with 8-bit sRGB RGBA pixels of the same size
+    fn set_input(&self, queue: &Queue, rgba: &[u8]) {
+        let texture = self.graph.texture(INPUT).unwrap();
+        queue.write_texture(
+            texture.as_image_copy(),
+            rgba,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(texture.width() * 4),
+                rows_per_image: None,
+            },
+            texture.size(),
+        );
+    }
+
+    /// The input after the passes, as 8-bit sRGB RGBA
+    fn run(&mut self, device: &Device, queue: &Queue) -> Result<Vec<u8>, String> {
+        device.push_error_scope(ErrorFilter::Validation);
+        self.globals.write(queue);
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Process Encoder"),
+        });
+        self.graph.encode(queue, &mut encoder, &self.globals);
+        queue.submit(Some(encoder.finish()));
+
+        let texture = self.graph.texture(self.graph.last().unwrap()).unwrap();
+        let data = frame_hash::read_texture(device, queue, texture);
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(error.to_string());
+        }
+        // The input was decoded from sRGB, so the effects wrote linear colors
+        Ok(recording::texture_to_rgba8(texture, &data, false))
+    }
+}
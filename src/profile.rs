@@ -0,0 +1,66 @@
+use clap::ValueEnum;
+
+use crate::app::{HEIGHT, WIDTH};
+
+/// Hardware profile, bundling defaults tuned for a class of devices
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Desktop and laptop GPUs
+    Default,
+    /// Raspberry Pi class hardware (V3D): low resolution, small workgroups,
+    /// and falling back to GLES where Vulkan is missing
+    Pi,
+}
+
+impl Profile {
+    /// Resolution of the drawn image
+    pub fn resolution(self) -> (u32, u32) {
+        match self {
+            Profile::Default => (WIDTH, HEIGHT),
+            Profile::Pi => (WIDTH / 2, HEIGHT / 2),
+        }
+    }
+
+    /// Workgroup size of every compute shader.
+    /// V3D executes 16 invocations at a time, larger groups only add register pressure
+    pub fn workgroup_size(self) -> [u32; 2] {
+        match self {
+            Profile::Default => [8, 8],
+            Profile::Pi => [4, 4],
+        }
+    }
+
+    pub fn backends(self) -> wgpu::Backends {
+        match self {
+            Profile::Default => preferred_backends(),
+            // Vulkan where the v3dv driver is installed, GLES otherwise
+            Profile::Pi => wgpu::Backends::VULKAN | wgpu::Backends::GL,
+        }
+    }
+
+    /// Limits requested for the compute path.
+    /// V3D falls short of the WebGPU defaults, so the Pi settles for the GLES 3.1 level ones
+    pub fn limits(self, adapter: &wgpu::Adapter) -> wgpu::Limits {
+        match self {
+            Profile::Default => wgpu::Limits::default(),
+            Profile::Pi => wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        }
+    }
+
+    pub fn present_mode(self) -> wgpu::PresentMode {
+        // Anything but Fifo tears or spins the CPU on the Pi,
+        // and desktops are fine with vsync as well
+        wgpu::PresentMode::Fifo
+    }
+}
+
+/// Metal is the only backend worth trying on iOS
+#[cfg(target_os = "ios")]
+fn preferred_backends() -> wgpu::Backends {
+    wgpu::Backends::METAL
+}
+
+#[cfg(not(target_os = "ios"))]
+fn preferred_backends() -> wgpu::Backends {
+    wgpu::Backends::all()
+}
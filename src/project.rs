@@ -0,0 +1,237 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use web_time::Instant;
+
+use crate::{
+    cli::{Options, Preset},
+    compute::MAX_HISTORY,
+    examples::Example,
+    post_effect::PassConfig,
+    tonemap::Tonemapper,
+};
+
+/// File describing a project, in the directory passed with `--project`
+pub const PROJECT_FILE: &str = "project.toml";
+
+/// How often the project directory is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A complete effect as a folder: the drawing shader, its resources, settings and post-effect passes,
+/// read from `project.toml`. Paths are relative to the project directory.
+/// Settings left out, or given on the command line, keep the values of the command line
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Project {
+    /// Preset or example name, like `--preset` and `--example`
+    preset: Option<String>,
+    example: Option<String>,
+    /// WGSL file providing the `draw` function
+    shader: Option<PathBuf>,
+    entry_point: Option<String>,
+    /// Initial `globals.params`
+    params: Option<[f32; 2]>,
+    resolution: Option<[u32; 2]>,
+    supersample: Option<u32>,
+    workgroup_size: Option<[u32; 2]>,
+    accumulate: Option<bool>,
+    /// Files bound as read-only storage buffers, in binding order
+    #[serde(default)]
+    storage_buffers: Vec<PathBuf>,
+    /// Size in bytes of the `scratch` buffer
+    scratch_size: Option<u64>,
+    /// Number of previous frames kept for `history`
+    history: Option<u32>,
+    mipmaps: Option<bool>,
+    bloom: Option<f32>,
+    bloom_threshold: Option<f32>,
+    tonemap: Option<String>,
+    exposure: Option<f32>,
+    /// Post-effect passes, as in an `effects.toml`
+    #[serde(default, rename = "pass")]
+    pub passes: Vec<PassConfig>,
+}
+
+impl Project {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let path = dir.join(PROJECT_FILE);
+        fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| toml::from_str(&contents).map_err(|err| err.to_string()))
+            .map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    /// `options` with the settings of the project in `dir`.
+    /// Flags given on the command line win over the project, which wins over the defaults
+    pub fn apply(&self, dir: &Path, options: &Options) -> Result<Options, String> {
+        let mut options = options.clone();
+        if let Some(name) = &self.preset {
+            let preset = Preset::from_str(name, true)
+                .map_err(|_| format!("Unknown preset {name:?} in {PROJECT_FILE}"))?;
+            if !options.given("preset") {
+                options.preset = preset;
+            }
+        }
+        let drawing_given = options.shader.is_some() || options.example.is_some();
+        if let Some(name) = &self.example {
+            let example = Example::from_str(name, true)
+                .map_err(|_| format!("Unknown example {name:?} in {PROJECT_FILE}"))?;
+            if !drawing_given {
+                options.example = Some(example);
+            }
+        }
+        if let Some(shader) = &self.shader
+            && !drawing_given
+        {
+            options.shader = Some(dir.join(shader));
+            options.example = None;
+        }
+        if options.entry_point.is_none() {
+            options.entry_point.clone_from(&self.entry_point);
+        }
+        if options.params.is_none() {
+            options.params = self.params;
+        }
+        if let Some(resolution) = self.resolution {
+            if resolution.contains(&0) {
+                return Err(format!("The resolution in {PROJECT_FILE} is empty"));
+            }
+            options.resolution.get_or_insert(resolution);
+        }
+        if let Some(supersample) = self.supersample {
+            if !(1..=4).contains(&supersample) {
+                return Err(format!("Supersampling in {PROJECT_FILE} has to be 1 to 4"));
+            }
+            if !options.given("supersample") {
+                options.supersample = supersample;
+            }
+        }
+        if options.workgroup_size.is_none() {
+            options.workgroup_size = self.workgroup_size;
+        }
+        if let Some(accumulate) = self.accumulate
+            && !options.given("accumulate")
+        {
+            options.accumulate = accumulate;
+        }
+        if options.storage_buffers.is_empty() {
+            options.storage_buffers = self
+                .storage_buffers
+                .iter()
+                .map(|path| dir.join(path))
+                .collect();
+        }
+        if options.scratch_size.is_none() {
+            options.scratch_size = self.scratch_size;
+        }
+        if let Some(history) = self.history {
+            if !(1..=MAX_HISTORY).contains(&history) {
+                return Err(format!(
+                    "The history in {PROJECT_FILE} has to be 1 to {MAX_HISTORY} frames"
+                ));
+            }
+            options.history.get_or_insert(history);
+        }
+        if let Some(mipmaps) = self.mipmaps
+            && !options.given("mipmaps")
+        {
+            options.mipmaps = mipmaps;
+        }
+        if let Some(bloom) = self.bloom
+            && !options.given("bloom")
+        {
+            options.bloom = bloom;
+        }
+        if let Some(threshold) = self.bloom_threshold
+            && !options.given("bloom_threshold")
+        {
+            options.bloom_threshold = threshold;
+        }
+        if let Some(name) = &self.tonemap {
+            let tonemapper = Tonemapper::from_str(name, true)
+                .map_err(|_| format!("Unknown tonemapper {name:?} in {PROJECT_FILE}"))?;
+            options.tonemap.get_or_insert(tonemapper);
+        }
+        if options.exposure.is_none() {
+            options.exposure = self.exposure;
+        }
+        if !self.passes.is_empty() && options.post_effects.is_none() {
+            options.post_effects = Some(dir.to_owned());
+        }
+        Ok(options)
+    }
+}
+
+/// `options` with the project of `--project` applied, if there is one
+pub fn apply(options: &Options) -> Result<Options, String> {
+    match &options.project {
+        Some(dir) => Project::load(dir)?.apply(dir, options),
+        None => Ok(options.clone()),
+    }
+}
+
+/// Notices when a file in the project directory was saved, for the app to apply the project again
+pub struct ProjectWatcher {
+    /// Options from the command line, the project is applied to them
+    pub command_line: Options,
+    dir: PathBuf,
+    /// Latest modification time of the files in `dir`
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl ProjectWatcher {
+    pub fn new(dir: &Path, command_line: Options) -> Self {
+        Self {
+            command_line,
+            modified: modified(dir),
+            dir: dir.to_owned(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Whether a project file changed since the last call, checked at most once a second
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let modified = modified(&self.dir);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+/// Latest modification time of the files in `dir`
+pub fn modified(dir: &Path) -> Option<SystemTime> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.modified().ok())
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn flags_given_on_the_command_line_win() {
+        let project: Project =
+            toml::from_str("bloom = 0.5\nsupersample = 2\nmipmaps = true").unwrap();
+        let options = Options::parse_from(["test", "--bloom", "0", "--supersample", "1"]);
+        let options = project.apply(Path::new("."), &options).unwrap();
+        assert_eq!(options.bloom, 0.0);
+        assert_eq!(options.supersample, 1);
+        assert!(options.mipmaps);
+    }
+}
@@ -0,0 +1,339 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{
+    compute::{ComputeState, OUTPUT_FORMAT, OutputStorage},
+    gpu::{self, DrawMode, GpuState},
+    scene::SceneConfig,
+    shaders::Shaders,
+};
+
+/// Simulation steps dispatched per rendered frame unless `--steps-per-frame` says otherwise
+pub const DEFAULT_STEPS_PER_FRAME: u32 = 12;
+
+/// Formats of the state in order of preference. Two channels are enough,
+/// but GL can't write `Rg32Float` storage textures and gets four
+pub const STATE_FORMATS: [TextureFormat; 2] =
+    [TextureFormat::Rg32Float, TextureFormat::Rgba32Float];
+
+/// Uniforms of the reaction-diffusion shader
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ReactionDiffusionParams {
+    pub feed: f32,
+    pub kill: f32,
+    pub diffuse_a: f32,
+    pub diffuse_b: f32,
+}
+
+impl ReactionDiffusionParams {
+    pub fn new(feed: f32, kill: f32) -> Self {
+        Self {
+            feed,
+            kill,
+            diffuse_a: 1.0,
+            diffuse_b: 0.5,
+        }
+    }
+
+    /// Map normalized control values onto the interesting range of feed and kill rates
+    pub fn from_controls([x, y]: [f32; 2]) -> Self {
+        Self::new(0.01 + 0.09 * x, 0.045 + 0.025 * y)
+    }
+
+    /// Inverse of [`ReactionDiffusionParams::from_controls`]
+    pub fn controls(&self) -> [f32; 2] {
+        [(self.feed - 0.01) / 0.09, (self.kill - 0.045) / 0.025].map(|c| c.clamp(0.0, 1.0))
+    }
+}
+
+/// Gray-Scott reaction-diffusion preset.
+///
+/// The state lives in two textures of one of the [`STATE_FORMATS`] which are ping-ponged between steps,
+/// the colorized result is written to `output_view`.
+pub struct ReactionDiffusionState {
+    pub step_pipeline: ComputePipeline,
+    pub colorize_pipeline: ComputePipeline,
+    /// Step bind groups, `step_bind_groups[i]` reads from state `i` and writes to the other one
+    pub step_bind_groups: [BindGroup; 2],
+    /// `colorize_bind_groups[i]` reads from state `i`
+    pub colorize_bind_groups: [BindGroup; 2],
+    pub params_buffer: Buffer,
+    pub output_texture: Texture,
+    pub output_view: TextureView,
+    /// Index of the state texture holding the latest simulation step
+    current: usize,
+    steps_per_frame: u32,
+    workgroup_size: [u32; 2],
+    width: u32,
+    height: u32,
+}
+
+impl ReactionDiffusionState {
+    /// Format of the state on the adapter, `None` if the device can't run the simulation
+    pub fn state_format(gpu_state: &GpuState) -> Option<TextureFormat> {
+        // The image is written in `OUTPUT_FORMAT` along with the state
+        if gpu_state.draw_mode != DrawMode::Compute
+            || ComputeState::output_storage(&gpu_state.adapter) != Some(OutputStorage::Float)
+        {
+            return None;
+        }
+        gpu::storage_format(&gpu_state.adapter, &STATE_FORMATS)
+    }
+
+    /// The simulation of `config`, with its state in [`SceneConfig::state_format`]
+    pub fn new(device: &Device, queue: &Queue, shaders: &Shaders, config: &SceneConfig) -> Self {
+        let SceneConfig {
+            reaction_diffusion_params: params,
+            state_format,
+            width,
+            height,
+            ..
+        } = *config;
+        let steps_per_frame = config.steps_per_frame.unwrap_or(DEFAULT_STEPS_PER_FRAME);
+        let module = shaders
+            .reaction_diffusion
+            .as_ref()
+            .expect("Reaction-diffusion shader was not created");
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let state_textures = [0, 1].map(|_| {
+            device.create_texture(&TextureDescriptor {
+                label: Some("Reaction-Diffusion State Texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: state_format,
+                usage: TextureUsages::STORAGE_BINDING
+                    | TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST,
+                view_formats: &[],
+            })
+        });
+        // Channels past the two chemicals stay zero
+        let channels = state_format.components() as usize;
+        let initial_state: Vec<f32> = initial_state(width, height)
+            .into_iter()
+            .flat_map(|[a, b]| [a, b, 0.0, 0.0].into_iter().take(channels))
+            .collect();
+        queue.write_texture(
+            state_textures[0].as_image_copy(),
+            bytemuck::cast_slice(&initial_state),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * (channels * std::mem::size_of::<f32>()) as u32),
+                rows_per_image: None,
+            },
+            size,
+        );
+        let state_views =
+            state_textures.map(|texture| texture.create_view(&TextureViewDescriptor::default()));
+
+        let output_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Reaction-Diffusion Output Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::STORAGE_BINDING
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Reaction-Diffusion Params Buffer"),
+            contents: bytemuck::bytes_of(&params),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let state_in_entry = BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let step_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Reaction-Diffusion Step Bind Group Layout"),
+            entries: &[
+                state_in_entry,
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: state_format,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let colorize_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Reaction-Diffusion Colorize Bind Group Layout"),
+            entries: &[
+                state_in_entry,
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: OUTPUT_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let step_bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Reaction-Diffusion Step Bind Group"),
+                layout: &step_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&state_views[i]),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(&state_views[1 - i]),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+
+        let colorize_bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Reaction-Diffusion Colorize Bind Group"),
+                layout: &colorize_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&state_views[i]),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(&output_view),
+                    },
+                ],
+            })
+        });
+
+        let step_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            compilation_options: Default::default(),
+            label: Some("Reaction-Diffusion Step Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Reaction-Diffusion Step Pipeline Layout"),
+                bind_group_layouts: &[&step_layout],
+                push_constant_ranges: &[],
+            })),
+            module,
+            entry_point: "step",
+            cache: shaders.pipeline_cache.as_deref(),
+        });
+
+        let colorize_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            compilation_options: Default::default(),
+            label: Some("Reaction-Diffusion Colorize Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Reaction-Diffusion Colorize Pipeline Layout"),
+                bind_group_layouts: &[&colorize_layout],
+                push_constant_ranges: &[],
+            })),
+            module,
+            entry_point: "colorize",
+            cache: shaders.pipeline_cache.as_deref(),
+        });
+
+        Self {
+            step_pipeline,
+            colorize_pipeline,
+            step_bind_groups,
+            colorize_bind_groups,
+            params_buffer,
+            output_texture,
+            output_view,
+            current: 0,
+            steps_per_frame,
+            workgroup_size: shaders.workgroup_size,
+            width,
+            height,
+        }
+    }
+
+    /// Upload new rates, the simulation continues from its current state
+    pub fn set_params(&self, queue: &Queue, params: ReactionDiffusionParams) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    /// Advance the simulation by `steps_per_frame` steps and colorize the result
+    pub fn dispatch(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Reaction-Diffusion Pass"),
+        });
+
+        let workgroups_x = self.width.div_ceil(self.workgroup_size[0]);
+        let workgroups_y = self.height.div_ceil(self.workgroup_size[1]);
+
+        compute_pass.set_pipeline(&self.step_pipeline);
+        for _ in 0..self.steps_per_frame {
+            compute_pass.set_bind_group(0, &self.step_bind_groups[self.current], &[]);
+            compute_pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+            self.current = 1 - self.current;
+        }
+
+        compute_pass.set_pipeline(&self.colorize_pipeline);
+        compute_pass.set_bind_group(0, &self.colorize_bind_groups[self.current], &[]);
+        compute_pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+    }
+}
+
+/// Chemical A everywhere, with a few seeded patches of chemical B
+fn initial_state(width: u32, height: u32) -> Vec<[f32; 2]> {
+    let mut state = vec![[1.0, 0.0]; (width * height) as usize];
+
+    let seeds = [(0.5, 0.5), (0.3, 0.35), (0.7, 0.4), (0.45, 0.72)];
+    let radius = (width.min(height) / 20).max(1) as i64;
+
+    for (sx, sy) in seeds {
+        let cx = (sx * width as f32) as i64;
+        let cy = (sy * height as f32) as i64;
+        for y in (cy - radius).max(0)..(cy + radius).min(height as i64) {
+            for x in (cx - radius).max(0)..(cx + radius).min(width as i64) {
+                state[(y * width as i64 + x) as usize] = [0.5, 0.25];
+            }
+        }
+    }
+
+    state
+}
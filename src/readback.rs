@@ -0,0 +1,233 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, OnceLock},
+};
+
+use wgpu::*;
+
+/// How the pixels of a frame are laid out in a staging buffer
+#[derive(Clone, Copy)]
+pub struct FrameLayout {
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub padded_row_size: u32,
+}
+
+impl FrameLayout {
+    /// Layout of a `width` × `height` part of `texture`, with rows aligned for `copy_texture_to_buffer`
+    pub fn padded_region(texture: &Texture, width: u32, height: u32) -> Self {
+        let bytes_per_texel = texture
+            .format()
+            .block_copy_size(None)
+            .expect("Frame texture has no fixed texel size");
+        Self {
+            format: texture.format(),
+            width,
+            height,
+            padded_row_size: (width * bytes_per_texel)
+                .next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT),
+        }
+    }
+
+    /// Bytes of a row of texels, without the padding
+    pub fn row_size(&self) -> usize {
+        (self.width * self.format.block_copy_size(None).unwrap()) as usize
+    }
+
+    fn size(&self) -> BufferAddress {
+        (self.padded_row_size * self.height) as BufferAddress
+    }
+}
+
+/// Copies textures, or parts of buffers, into a ring of staging buffers, which are mapped asynchronously,
+/// so reading back frames never waits for the GPU.
+/// Each readback carries a `T` saying what to do with it once it arrives
+pub struct ReadbackRing<T> {
+    pending: VecDeque<PendingReadback<T>>,
+    /// Staging buffers of finished readbacks, for reuse
+    free_buffers: Vec<Buffer>,
+    capacity: usize,
+}
+
+/// A texture being copied into a staging buffer
+struct PendingReadback<T> {
+    buffer: Buffer,
+    /// Set once mapping finished, to whether it succeeded
+    mapped: Arc<OnceLock<bool>>,
+    layout: FrameLayout,
+    tag: T,
+}
+
+impl<T> ReadbackRing<T> {
+    /// At most `capacity` readbacks are in flight
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            free_buffers: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Whether another readback has to wait until one is collected
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Start copying `texture` back, it is handed out by [`ReadbackRing::collect`] once mapped.
+    /// Check [`ReadbackRing::is_full`] first, the ring grows past its capacity otherwise
+    pub fn start(&mut self, device: &Device, queue: &Queue, texture: &Texture, tag: T) {
+        let size = [texture.width(), texture.height()];
+        self.start_region(device, queue, texture, [0, 0], size, tag);
+    }
+
+    /// Like [`ReadbackRing::start`] for the `size` texels of `texture` from `origin` on
+    pub fn start_region(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        texture: &Texture,
+        origin: [u32; 2],
+        size: [u32; 2],
+        tag: T,
+    ) {
+        let layout = FrameLayout::padded_region(texture, size[0], size[1]);
+        self.start_copy(device, queue, layout, tag, |encoder, buffer| {
+            encoder.copy_texture_to_buffer(
+                ImageCopyTexture {
+                    origin: Origin3d {
+                        x: origin[0],
+                        y: origin[1],
+                        z: 0,
+                    },
+                    ..texture.as_image_copy()
+                },
+                ImageCopyBuffer {
+                    buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(layout.padded_row_size),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: size[0],
+                    height: size[1],
+                    depth_or_array_layers: 1,
+                },
+            );
+        });
+    }
+
+    /// Like [`ReadbackRing::start`] for `size` bytes of `buffer` from `offset` on,
+    /// handed out as a single row of [`TextureFormat::R32Uint`] texels
+    pub fn start_buffer(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        buffer: &Buffer,
+        offset: BufferAddress,
+        size: u32,
+        tag: T,
+    ) {
+        let layout = FrameLayout {
+            format: TextureFormat::R32Uint,
+            width: size / 4,
+            height: 1,
+            padded_row_size: size,
+        };
+        self.start_copy(device, queue, layout, tag, |encoder, staging| {
+            encoder.copy_buffer_to_buffer(buffer, offset, staging, 0, size.into());
+        });
+    }
+
+    /// Copy into a staging buffer for `layout` with `copy` and map it
+    fn start_copy(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        layout: FrameLayout,
+        tag: T,
+        copy: impl FnOnce(&mut CommandEncoder, &Buffer),
+    ) {
+        // Buffers of another size are left over from before a resize
+        self.free_buffers
+            .retain(|buffer| buffer.size() == layout.size());
+        let buffer = self.free_buffers.pop().unwrap_or_else(|| {
+            device.create_buffer(&BufferDescriptor {
+                label: Some("Readback Buffer"),
+                size: layout.size(),
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        copy(&mut encoder, &buffer);
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = Arc::new(OnceLock::new());
+        let mapped_callback = Arc::clone(&mapped);
+        buffer.slice(..).map_async(MapMode::Read, move |result| {
+            if let Err(err) = &result {
+                log::error!("Failed to read back a frame: {err}");
+            }
+            let _ = mapped_callback.set(result.is_ok());
+        });
+        self.pending.push_back(PendingReadback {
+            buffer,
+            mapped,
+            layout,
+            tag,
+        });
+    }
+
+    /// Hand the mapped readbacks to `take` in the order they were started,
+    /// with their padded rows. Stops at the first one `take` refuses, it is offered again next time.
+    /// Only waits for the GPU if `wait`, e.g. before exiting
+    pub fn collect(
+        &mut self,
+        device: &Device,
+        wait: bool,
+        mut take: impl FnMut(&T, FrameLayout, &[u8]) -> bool,
+    ) {
+        if self.pending.is_empty() {
+            return;
+        }
+        device.poll(if wait { Maintain::Wait } else { Maintain::Poll });
+
+        while let Some(readback) = self.pending.front()
+            && let Some(&mapped) = readback.mapped.get()
+        {
+            if mapped {
+                let taken = take(
+                    &readback.tag,
+                    readback.layout,
+                    &readback.buffer.slice(..).get_mapped_range(),
+                );
+                if !taken {
+                    return;
+                }
+                readback.buffer.unmap();
+            }
+            let readback = self.pending.pop_front().unwrap();
+            self.free_buffers.push(readback.buffer);
+        }
+    }
+
+    /// Drop the staging buffers of a lost device, readbacks in flight are lost with it
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.free_buffers.clear();
+    }
+}
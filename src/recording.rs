@@ -0,0 +1,519 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+    },
+    thread::{self, JoinHandle},
+};
+
+use clap::ValueEnum;
+use wgpu::*;
+
+use crate::{
+    frame_hash::f16_to_f32,
+    readback::{FrameLayout, ReadbackRing},
+};
+
+/// Readbacks in flight before the next frame waits for the oldest one
+const MAX_PENDING_READBACKS: usize = 3;
+/// Frames waiting for an encoder before frames stay in their staging buffers
+const ENCODE_QUEUE_LENGTH: usize = 8;
+/// Encoder threads at most, PNG compression is the bottleneck
+const MAX_WORKERS: usize = 4;
+
+/// Start of every raw frame file, followed by width and height as little endian `u32`,
+/// the index of the texture format in [`RAW_FORMATS`] and whether colors are sRGB encoded
+const RAW_MAGIC: &[u8] = b"GPGPURAW";
+const RAW_FORMATS: [TextureFormat; 2] = [TextureFormat::Rgba8Unorm, TextureFormat::Rgba16Float];
+/// Fastest zstd level, raw capture is about keeping up rather than small files
+#[cfg(not(target_arch = "wasm32"))]
+const RAW_COMPRESSION_LEVEL: i32 = 1;
+
+/// How `--record` stores frames
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Numbered image files in the `--format`, ready to use
+    Png,
+    /// The texels as drawn, compressed with zstd.
+    /// Much cheaper to write than PNG, convert them with the `transcode` command afterwards
+    Raw,
+}
+
+/// File format of screenshots, crops and frames recorded as images, set with `--format`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// 8-bit PNG, sRGB encoded and clamped to 0..1
+    Png8,
+    /// 16-bit PNG, sRGB encoded and clamped to 0..1, without the banding of 8 bits
+    Png16,
+    /// OpenEXR with 32-bit float channels in linear light, keeping values outside of 0..1
+    Exr,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png8 | ImageFormat::Png16 => "png",
+            ImageFormat::Exr => "exr",
+        }
+    }
+}
+
+/// Channels of EXR files in the alphabetical order they are stored in,
+/// with the index of their component in an RGBA texel
+const EXR_CHANNELS: [(&str, usize); 4] = [("A", 3), ("B", 2), ("G", 1), ("R", 0)];
+
+/// Extension of raw frame files
+pub const RAW_EXTENSION: &str = "raw.zst";
+
+/// Writes every drawn frame as numbered file to a directory, for `--record`.
+///
+/// Frames are copied into a ring of readback buffers, so drawing goes on while the GPU finishes the copies.
+/// A pool of worker threads converts and compresses them.
+/// If the GPU or the workers fall behind, [`Recorder::poll`] holds off the next frame
+/// instead of blocking the event loop or piling up frames in memory
+pub struct Recorder {
+    directory: PathBuf,
+    format: RecordFormat,
+    /// Format of the files of [`RecordFormat::Png`]
+    image_format: ImageFormat,
+    /// Whether the drawing outputs display-ready colors, which then aren't sRGB encoded
+    srgb_output: bool,
+    /// Frame numbers being read back
+    readbacks: ReadbackRing<u32>,
+    jobs: Option<SyncSender<EncodeJob>>,
+    /// Jobs sent but not yet taken by a worker
+    queued: Arc<AtomicUsize>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct EncodeJob {
+    path: PathBuf,
+    format: RecordFormat,
+    image_format: ImageFormat,
+    layout: FrameLayout,
+    srgb_output: bool,
+    data: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn new(
+        directory: &Path,
+        format: RecordFormat,
+        image_format: ImageFormat,
+        srgb_output: bool,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+
+        let (sender, receiver) = mpsc::sync_channel(ENCODE_QUEUE_LENGTH);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let queued = Arc::new(AtomicUsize::new(0));
+        let worker_count = thread::available_parallelism()
+            .map_or(1, |parallelism| parallelism.get())
+            .min(MAX_WORKERS);
+        let workers = (0..worker_count)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let queued = Arc::clone(&queued);
+                thread::spawn(move || encode_worker(&receiver, &queued))
+            })
+            .collect();
+
+        Ok(Self {
+            directory: directory.to_owned(),
+            format,
+            image_format,
+            srgb_output,
+            readbacks: ReadbackRing::new(MAX_PENDING_READBACKS),
+            jobs: Some(sender),
+            queued,
+            workers,
+        })
+    }
+
+    /// Hand finished frames to the encoders.
+    /// Returns whether the next frame can be captured, draw it only then to not lose it
+    pub fn poll(&mut self, device: &Device) -> bool {
+        self.send_mapped(device, false);
+        !self.readbacks.is_full()
+    }
+
+    /// Start reading back `texture`, it is encoded once the GPU finished copying it
+    pub fn capture(&mut self, device: &Device, queue: &Queue, texture: &Texture, frame: u32) {
+        self.readbacks.start(device, queue, texture, frame);
+        self.send_mapped(device, false);
+    }
+
+    /// Wait for all frames to be read back and encoded
+    pub fn finish(&mut self, device: &Device) {
+        self.send_mapped(device, true);
+
+        // Workers exit once the queue is closed and drained
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                log::error!("A recording encoder panicked");
+            }
+        }
+        log::info!("Recording written to {}", self.directory.display());
+    }
+
+    /// Drop the staging buffers of a lost device, frames still being read back are lost with it
+    pub fn device_lost(&mut self) {
+        if !self.readbacks.is_empty() {
+            log::warn!(
+                "Lost {} recorded frames with the device",
+                self.readbacks.len()
+            );
+        }
+        self.readbacks.clear();
+    }
+
+    /// Hand mapped frames to the encoders, in order.
+    /// Unless `wait`ing, frames stay in their staging buffers while the encode queue is full
+    fn send_mapped(&mut self, device: &Device, wait: bool) {
+        let Some(jobs) = &self.jobs else {
+            return;
+        };
+        self.readbacks
+            .collect(device, wait, |&frame, layout, data| {
+                if !wait && self.queued.load(Ordering::Acquire) >= ENCODE_QUEUE_LENGTH {
+                    return false;
+                }
+                let extension = match self.format {
+                    RecordFormat::Png => self.image_format.extension(),
+                    RecordFormat::Raw => RAW_EXTENSION,
+                };
+                let job = EncodeJob {
+                    path: self.directory.join(format!("frame-{frame:06}.{extension}")),
+                    format: self.format,
+                    image_format: self.image_format,
+                    layout,
+                    srgb_output: self.srgb_output,
+                    data: data.to_vec(),
+                };
+                self.queued.fetch_add(1, Ordering::AcqRel);
+                if jobs.send(job).is_err() {
+                    log::error!("Recording encoders stopped, dropping frame {frame}");
+                }
+                true
+            });
+    }
+}
+
+fn encode_worker(receiver: &Mutex<Receiver<EncodeJob>>, queued: &AtomicUsize) {
+    loop {
+        // The lock is released before encoding, so other workers can take the next job
+        let job = receiver.lock().unwrap().recv();
+        let Ok(job) = job else {
+            return;
+        };
+        queued.fetch_sub(1, Ordering::AcqRel);
+        let result = match job.format {
+            RecordFormat::Png => write_image(
+                &job.path,
+                job.image_format,
+                job.layout.width,
+                job.layout.height,
+                &job.layout.texels(&job.data),
+                job.srgb_output,
+            ),
+            RecordFormat::Raw => write_raw(&job),
+        };
+        if let Err(err) = result {
+            log::error!("Failed to write {}: {err}", job.path.display());
+        }
+    }
+}
+
+/// Write a raw frame: the header, followed by the tightly packed texels compressed with zstd
+fn write_raw(job: &EncodeJob) -> io::Result<()> {
+    let FrameLayout {
+        format,
+        width,
+        height,
+        padded_row_size,
+    } = job.layout;
+    let format_id = RAW_FORMATS
+        .iter()
+        .position(|&raw_format| raw_format == format)
+        .ok_or_else(|| io::Error::other(format!("Can't capture {format:?} frames")))?;
+
+    let mut file = BufWriter::new(File::create(&job.path)?);
+    file.write_all(RAW_MAGIC)?;
+    file.write_all(&width.to_le_bytes())?;
+    file.write_all(&height.to_le_bytes())?;
+    file.write_all(&[format_id as u8, job.srgb_output as u8])?;
+
+    let row_size = job.layout.row_size();
+    let rows = job.data.chunks_exact(padded_row_size as usize);
+    compress(file, rows.map(|row| &row[..row_size]))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn compress<'a>(file: impl Write, rows: impl Iterator<Item = &'a [u8]>) -> io::Result<()> {
+    let mut encoder = zstd::Encoder::new(file, RAW_COMPRESSION_LEVEL)?;
+    for row in rows {
+        encoder.write_all(row)?;
+    }
+    encoder.finish()?.flush()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn decompress(file: impl Read) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    zstd::Decoder::new(file)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// zstd is a C library, which doesn't build for the web
+#[cfg(target_arch = "wasm32")]
+fn compress<'a>(_file: impl Write, _rows: impl Iterator<Item = &'a [u8]>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "raw frames aren't supported on the web",
+    ))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn decompress(_file: impl Read) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "raw frames aren't supported on the web",
+    ))
+}
+
+/// A frame of a raw capture
+pub struct RawFrame {
+    pub width: u32,
+    pub height: u32,
+    /// 8-bit RGBA, sRGB encoded
+    pub pixels: Vec<u8>,
+}
+
+impl RawFrame {
+    /// Read a frame written by `--record-format raw`
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut header = [0; RAW_MAGIC.len() + 10];
+        file.read_exact(&mut header)?;
+        let (magic, header) = header.split_at(RAW_MAGIC.len());
+        if magic != RAW_MAGIC {
+            return Err(invalid("not a raw capture frame"));
+        }
+        let width = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let height = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let format = *RAW_FORMATS
+            .get(header[8] as usize)
+            .ok_or_else(|| invalid("unknown texture format"))?;
+        let srgb_output = header[9] != 0;
+
+        let too_large = || invalid("frame size is too large");
+        let padded_row_size = width
+            .checked_mul(format.block_copy_size(None).unwrap())
+            .ok_or_else(too_large)?;
+        let size = padded_row_size.checked_mul(height).ok_or_else(too_large)?;
+        let layout = FrameLayout {
+            format,
+            width,
+            height,
+            padded_row_size,
+        };
+        let data = decompress(file)?;
+        if data.len() != size as usize {
+            return Err(invalid("frame is truncated"));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels: layout.rgba8(&data, srgb_output),
+        })
+    }
+}
+
+/// Convert the tightly packed texels of `texture`, as read back for frame hashes,
+/// to sRGB encoded 8-bit RGBA
+pub fn texture_to_rgba8(texture: &Texture, data: &[u8], srgb_output: bool) -> Vec<u8> {
+    let layout = FrameLayout {
+        format: texture.format(),
+        width: texture.width(),
+        height: texture.height(),
+        padded_row_size: 0,
+    };
+    FrameLayout {
+        padded_row_size: layout.row_size() as u32,
+        ..layout
+    }
+    .rgba8(data, srgb_output)
+}
+
+impl FrameLayout {
+    /// The texels of a frame as they were drawn, row by row without padding
+    pub fn texels(&self, data: &[u8]) -> Vec<[f32; 4]> {
+        let row_size = self.row_size();
+        let mut values = Vec::with_capacity((self.width * self.height * 4) as usize);
+        for row in data.chunks_exact(self.padded_row_size as usize) {
+            let row = &row[..row_size];
+            match self.format {
+                TextureFormat::Rgba16Float => {
+                    let row: &[u16] = bytemuck::cast_slice(row);
+                    values.extend(row.iter().map(|&bits| f16_to_f32(bits)));
+                }
+                _ => values.extend(row.iter().map(|&byte| byte as f32 / 255.0)),
+            }
+        }
+        values
+            .chunks_exact(4)
+            .map(|texel| [texel[0], texel[1], texel[2], texel[3]])
+            .collect()
+    }
+
+    /// Convert a frame to 8-bit RGBA for image files.
+    /// Drawings are in linear light unless `srgb_output`, the result is always sRGB encoded
+    pub fn rgba8(&self, data: &[u8], srgb_output: bool) -> Vec<u8> {
+        self.texels(data)
+            .iter()
+            .flat_map(|&texel| encode(texel, srgb_output).map(|v| (v * 255.0).round() as u8))
+            .collect()
+    }
+}
+
+/// Clamp a texel to 0..1 and sRGB encode it, unless the drawing did already
+fn encode(texel: [f32; 4], srgb_output: bool) -> [f32; 4] {
+    let mut texel = texel.map(|v| v.clamp(0.0, 1.0));
+    if !srgb_output {
+        texel[..3].iter_mut().for_each(|v| *v = linear_to_srgb(*v));
+    }
+    texel
+}
+
+/// Write `texels` as drawn in `format`, they are in linear light unless `srgb_output`
+pub fn write_image(
+    path: &Path,
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    texels: &[[f32; 4]],
+    srgb_output: bool,
+) -> io::Result<()> {
+    match format {
+        ImageFormat::Png8 => {
+            let pixels: Vec<u8> = texels
+                .iter()
+                .flat_map(|&texel| encode(texel, srgb_output).map(|v| (v * 255.0).round() as u8))
+                .collect();
+            write_png(path, width, height, &pixels)
+        }
+        ImageFormat::Png16 => {
+            let pixels: Vec<u8> = texels
+                .iter()
+                .flat_map(|&texel| encode(texel, srgb_output))
+                .flat_map(|v| ((v * 65535.0).round() as u16).to_be_bytes())
+                .collect();
+            let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Sixteen);
+            encoder.write_header()?.write_image_data(&pixels)?;
+            Ok(())
+        }
+        ImageFormat::Exr => {
+            let linear: Vec<[f32; 4]> = match srgb_output {
+                true => texels
+                    .iter()
+                    .map(|&[r, g, b, a]| {
+                        [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+                    })
+                    .collect(),
+                false => texels.to_vec(),
+            };
+            write_exr(path, width, height, &linear)
+        }
+    }
+}
+
+/// Write `width` × `height` 8-bit RGBA `pixels` to a PNG file at `path`
+pub fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(pixels)?;
+    Ok(())
+}
+
+/// Write an uncompressed scanline OpenEXR file with 32-bit float RGBA channels.
+/// Every scanline is a block of its own, after the header and a table of their offsets
+fn write_exr(path: &Path, width: u32, height: u32, texels: &[[f32; 4]]) -> io::Result<()> {
+    // Magic number and version 2, single-part scanline
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+    let mut channels = Vec::new();
+    for (name, _) in EXR_CHANNELS {
+        channels.extend_from_slice(name.as_bytes());
+        // Name terminator, FLOAT pixel type, not perceptually linear, reserved, no subsampling
+        channels.extend_from_slice(&[0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+    }
+    channels.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    attribute("channels", "chlist", &channels);
+    attribute("compression", "compression", &[0]);
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&header)?;
+    // Each block is its y coordinate, its size and the row of every channel
+    let row_size = width as u64 * 4 * 4;
+    let block_size = 8 + row_size;
+    let first_block = header.len() as u64 + 8 * height as u64;
+    for y in 0..height as u64 {
+        file.write_all(&(first_block + y * block_size).to_le_bytes())?;
+    }
+    for (y, row) in texels.chunks_exact(width as usize).enumerate() {
+        file.write_all(&(y as i32).to_le_bytes())?;
+        file.write_all(&(row_size as i32).to_le_bytes())?;
+        for (_, channel) in EXR_CHANNELS {
+            for texel in row {
+                file.write_all(&texel[channel].to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
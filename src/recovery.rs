@@ -0,0 +1,77 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use wgpu::{Device, SurfaceError};
+
+use crate::crash;
+
+/// Whether the device is still usable, fed by wgpu's error callbacks.
+/// Once lost, the device and everything created from it have to be rebuilt
+#[derive(Clone)]
+pub struct DeviceHealth {
+    lost: Arc<AtomicBool>,
+}
+
+impl DeviceHealth {
+    /// Install error callbacks on `device`, instead of wgpu's default of panicking
+    pub fn watch(device: &Device) -> Self {
+        let health = Self {
+            lost: Arc::new(AtomicBool::new(false)),
+        };
+
+        let lost_health = health.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Also called when the device is dropped, after a rebuild or on shutdown
+            if !matches!(reason, wgpu::DeviceLostReason::Dropped) {
+                log::error!("Device lost ({reason:?}): {message}");
+                crash::write_report(&format!("Device lost ({reason:?}): {message}"));
+                lost_health.mark_lost();
+            }
+        });
+
+        let error_health = health.clone();
+        device.on_uncaptured_error(Box::new(move |error| match error {
+            wgpu::Error::OutOfMemory { .. } => {
+                log::error!("{error}");
+                error_health.mark_lost();
+            }
+            // A bug in a shader or in this app, not worth giving up the device for
+            wgpu::Error::Validation { .. } | wgpu::Error::Internal { .. } => {
+                log::error!("{error}")
+            }
+        }));
+
+        health
+    }
+
+    pub fn mark_lost(&self) {
+        self.lost.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+}
+
+/// How to get past a failure to acquire the next surface texture
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SurfaceRecovery {
+    /// The surface no longer matches the window, configure it again
+    Reconfigure,
+    /// Nothing is wrong with our state, try again with the next frame
+    SkipFrame,
+    /// The device can't be trusted anymore
+    RecreateDevice,
+}
+
+impl SurfaceRecovery {
+    pub fn for_error(error: &SurfaceError) -> Self {
+        match error {
+            SurfaceError::Lost | SurfaceError::Outdated => SurfaceRecovery::Reconfigure,
+            SurfaceError::Timeout => SurfaceRecovery::SkipFrame,
+            SurfaceError::OutOfMemory => SurfaceRecovery::RecreateDevice,
+        }
+    }
+}
@@ -1,34 +1,242 @@
-use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use std::{cell::Cell, fs::File, io, path::Path, path::PathBuf};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use wgpu::util::{BufferInitDescriptor, DeviceExt, TextureDataOrder};
 use wgpu::*;
 
-use crate::compute::ComputeState;
-use crate::shaders::Shaders;
+use crate::{
+    compute::ComputeState,
+    globals::{Camera, GlobalsState},
+    scene::SceneConfig,
+    shaders::Shaders,
+    tonemap::TonemapParams,
+};
+
+/// How the image is fitted into a window of another aspect ratio
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scaling {
+    /// Show all of the image, with black bars on the sides that are too long
+    Fit,
+    /// Cover the whole window, cutting off what doesn't fit
+    Fill,
+    /// Cover the whole window, distorting the image
+    Stretch,
+}
+
+impl Scaling {
+    /// Quad showing an image of `image` size on a target of `target` size, as triangle strip of
+    /// clip space positions and texture coordinates
+    fn quad(
+        self,
+        (image_width, image_height): (u32, u32),
+        (width, height): (u32, u32),
+    ) -> [f32; 16] {
+        // > 1 when the image is wider than the target
+        let aspect = (image_width as f32 / image_height as f32)
+            / (width.max(1) as f32 / height.max(1) as f32);
+        let (position, uv) = match self {
+            Scaling::Stretch => ([1.0, 1.0], [1.0, 1.0]),
+            Scaling::Fit if aspect > 1.0 => ([1.0, 1.0 / aspect], [1.0, 1.0]),
+            Scaling::Fit => ([aspect, 1.0], [1.0, 1.0]),
+            Scaling::Fill if aspect > 1.0 => ([1.0, 1.0], [1.0 / aspect, 1.0]),
+            Scaling::Fill => ([1.0, 1.0], [1.0, aspect]),
+        };
+        let [x, y] = position;
+        let [u0, v0] = uv.map(|extent| 0.5 - 0.5 * extent);
+        let [u1, v1] = uv.map(|extent| 0.5 + 0.5 * extent);
+        [
+            // pos    // uv
+            -x, -y, u0, v1, //
+            x, -y, u1, v1, //
+            -x, y, u0, v0, //
+            x, y, u1, v0,
+        ]
+    }
+}
+
+/// How the image is sampled when it is scaled to the window, toggled with F
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// Sharp pixels, for pixel art viewed at integer scales
+    Nearest,
+    /// Smooth interpolation between pixels
+    Linear,
+}
+
+impl Filter {
+    fn other(self) -> Self {
+        match self {
+            Filter::Nearest => Filter::Linear,
+            Filter::Linear => Filter::Nearest,
+        }
+    }
+
+    fn mode(self) -> FilterMode {
+        match self {
+            Filter::Nearest => FilterMode::Nearest,
+            Filter::Linear => FilterMode::Linear,
+        }
+    }
+}
+
+/// `[[quads]]` of the config file: a node of the scene graph or an image, drawn over the image on screen.
+/// Quads are drawn in the order they are listed, for picture-in-picture or side-by-side comparisons
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Quad {
+    pub name: String,
+    /// Node of the scene graph shown, e.g. `drawing` or `bloom`, or `output` for the presented image
+    pub source: Option<String>,
+    /// PNG shown instead of a node, as is without tonemapping
+    pub image: Option<PathBuf>,
+    /// Center in fractions of the window, from its top left corner
+    #[serde(default = "default_position")]
+    pub position: [f32; 2],
+    /// Width and height in fractions of the window
+    #[serde(default = "default_scale")]
+    pub scale: [f32; 2],
+    /// Degrees turned clockwise around the center
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+fn default_position() -> [f32; 2] {
+    [0.5, 0.5]
+}
+
+fn default_scale() -> [f32; 2] {
+    [1.0, 1.0]
+}
+
+/// Placement of a [`Quad`] on the target, mirrors `QuadTransform` in src/shaders/render_shader.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct QuadTransform {
+    /// In clip space
+    center: [f32; 2],
+    half_size: [f32; 2],
+    /// Radians clockwise
+    rotation: f32,
+    /// Width over height of the target, so rotating doesn't shear
+    aspect: f32,
+    tonemapped: u32,
+    _padding: u32,
+}
+
+impl QuadTransform {
+    fn new(quad: &Quad, tonemapped: bool, (width, height): (u32, u32)) -> Self {
+        let [x, y] = quad.position;
+        Self {
+            center: [x * 2.0 - 1.0, 1.0 - y * 2.0],
+            half_size: quad.scale,
+            rotation: quad.rotation.to_radians(),
+            aspect: width.max(1) as f32 / height.max(1) as f32,
+            tonemapped: tonemapped.into(),
+            _padding: 0,
+        }
+    }
+}
+
+/// A [`Quad`] ready to draw
+struct QuadLayer {
+    quad: Quad,
+    /// Whether the source is a node of the scene graph, images aren't tonemapped
+    tonemapped: bool,
+    transform_buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+/// Pipeline drawing the [`Quad`]s over the image
+struct QuadLayers {
+    pipeline: RenderPipeline,
+    layers: Vec<QuadLayer>,
+}
+
+/// Perspective view into the cubemap of the drawing shader, shown in place of the image when enabled.
+/// Mirrors `Preview` in src/shaders/render_shader.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Preview {
+    camera: Camera,
+    enabled: u32,
+    _padding: [u32; 3],
+}
+
+impl Preview {
+    fn new(camera: Option<Camera>) -> Self {
+        Self {
+            camera: camera.unwrap_or_default(),
+            enabled: camera.is_some().into(),
+            _padding: [0; 3],
+        }
+    }
+}
 
 pub struct RenderState {
     pub pipeline: RenderPipeline,
     pub bind_group: BindGroup,
-    pub vertex_buffer: Buffer,
+    /// Bind group sampling with the other [`Filter`], swapped in by [`RenderState::toggle_filter`].
+    /// `None` in direct mode, where nothing gets sampled
+    other_bind_group: Option<BindGroup>,
+    filter: Filter,
+    /// `None` when the drawing shader is evaluated directly by [`RenderState::new_direct`],
+    /// which covers the window with a single triangle instead
+    pub vertex_buffer: Option<Buffer>,
+    /// Tonemapping uniforms, `None` in direct mode where nothing gets tone-mapped
+    pub tonemap_buffer: Option<Buffer>,
+    /// [`Preview`] uniforms, `None` in direct mode
+    preview_buffer: Option<Buffer>,
+    /// Drawn over the image, `None` without `[[quads]]` in the config file
+    quads: Option<QuadLayers>,
+    scaling: Scaling,
+    image_size: (u32, u32),
+    /// Size of the target the vertex buffer was last fitted to
+    target_size: Cell<Option<(u32, u32)>>,
+    /// Samples per pixel, the pass renders into `msaa_target` and resolves into the target if above 1
+    sample_count: u32,
+    target_format: TextureFormat,
+    /// Multisampled color texture, recreated when the target size changes
+    msaa_target: Option<(TextureView, (u32, u32))>,
 }
 
 impl RenderState {
     pub fn new(
         device: &wgpu::Device,
         shaders: &Shaders,
-        compute_state: &ComputeState,
+        source_view: &TextureView,
+        compute_state: Option<&ComputeState>,
         surface_format: wgpu::TextureFormat,
+        tonemap_params: TonemapParams,
+        config: &SceneConfig,
     ) -> Self {
-        let sampler = device.create_sampler(&SamplerDescriptor::default());
-
+        let image_size = (config.width, config.height);
+        let SceneConfig {
+            scaling,
+            filter,
+            msaa_samples,
+            ..
+        } = *config;
         // Vertex buffer with quad data (pos, uv)
-        // we can map our texture to it and render to the window
-        let vertices: &[f32] = &[
-            // pos      // uv
-            -1.0, -1.0, 0.0, 1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 0.0,
-        ];
+        // we can map our texture to it and render to the window.
+        // Covers the target until it is fitted to its size
+        let vertices = Scaling::Stretch.quad(image_size, image_size);
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(vertices),
-            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let tonemap_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Tonemap Buffer"),
+            contents: bytemuck::bytes_of(&tonemap_params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let preview_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Preview Buffer"),
+            contents: bytemuck::bytes_of(&Preview::new(None)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
         let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -50,24 +258,137 @@ impl RenderState {
                     ty: BindingType::Sampler(SamplerBindingType::Filtering),
                     count: None,
                 },
-            ],
-        });
-
-        let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Render Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&compute_state.output_view),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&sampler),
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
             ],
         });
 
+        let overlay_view = compute_state.and_then(ComputeState::overlay_view);
+        let cubemap_view = compute_state.and_then(ComputeState::cubemap_view);
+        // Transparent in place of the overlay of drawing shaders without one
+        let empty_overlay = overlay_view.is_none().then(|| {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: Some("Empty Overlay Texture"),
+                    size: Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8Unorm,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor::default())
+        });
+        let overlay_view = overlay_view.or(empty_overlay.as_ref()).unwrap();
+        // Never shown, the preview is only enabled for drawing shaders with a cubemap
+        let empty_cubemap = cubemap_view.is_none().then(|| {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: Some("Empty Cubemap Texture"),
+                    size: Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 6,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8Unorm,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::Cube),
+                    ..Default::default()
+                })
+        });
+        let cubemap_view = cubemap_view.or(empty_cubemap.as_ref()).unwrap();
+
+        let create_bind_group = |filter: Filter| {
+            let sampler = device.create_sampler(&SamplerDescriptor {
+                mag_filter: filter.mode(),
+                min_filter: filter.mode(),
+                // Blends mip levels when there is a chain, see `MipmapState`
+                mipmap_filter: FilterMode::Linear,
+                ..Default::default()
+            });
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Render Bind Group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(source_view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: tonemap_buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(overlay_view),
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: BindingResource::TextureView(cubemap_view),
+                    },
+                    BindGroupEntry {
+                        binding: 5,
+                        resource: preview_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let bind_group = create_bind_group(filter);
+        let other_bind_group = create_bind_group(filter.other());
+
         let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
             layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
@@ -111,26 +432,383 @@ impl RenderState {
                 ..Default::default()
             },
             depth_stencil: None,
-            multisample: MultisampleState::default(),
+            multisample: MultisampleState {
+                count: msaa_samples,
+                ..Default::default()
+            },
             multiview: None,
+            cache: shaders.pipeline_cache.as_deref(),
         });
 
         Self {
             pipeline,
             bind_group,
-            vertex_buffer,
+            other_bind_group: Some(other_bind_group),
+            filter,
+            vertex_buffer: Some(vertex_buffer),
+            tonemap_buffer: Some(tonemap_buffer),
+            preview_buffer: Some(preview_buffer),
+            quads: None,
+            scaling,
+            image_size,
+            target_size: Cell::new(None),
+            sample_count: msaa_samples,
+            target_format: surface_format,
+            msaa_target: None,
         }
     }
 
-    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target_view: &TextureView) {
+    /// Render-only mode, for adapters without compute support.
+    /// The fragment entry points of the drawing shader are rendered straight to the surface.
+    pub fn new_direct(
+        device: &wgpu::Device,
+        shaders: &Shaders,
+        globals: &GlobalsState,
+        surface_format: wgpu::TextureFormat,
+    ) -> Self {
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Direct Render Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Direct Render Pipeline Layout"),
+                bind_group_layouts: &[&globals.bind_group_layout],
+                push_constant_ranges: &globals.push_constant_ranges(ShaderStages::FRAGMENT),
+            })),
+            vertex: VertexState {
+                compilation_options: Default::default(),
+                module: &shaders.drawing,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shaders.drawing,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: shaders.pipeline_cache.as_deref(),
+        });
+
+        Self {
+            pipeline,
+            bind_group: globals.create_bind_group(device),
+            other_bind_group: None,
+            filter: Filter::Nearest,
+            vertex_buffer: None,
+            tonemap_buffer: None,
+            preview_buffer: None,
+            quads: None,
+            scaling: Scaling::Stretch,
+            image_size: (0, 0),
+            target_size: Cell::new(None),
+            // Every pixel is shaded anyway, there are no edges to antialias
+            sample_count: 1,
+            target_format: surface_format,
+            msaa_target: None,
+        }
+    }
+
+    /// Switch the tonemapping curve and exposure, takes effect with the next frame
+    pub fn set_tonemapping(&self, queue: &Queue, params: TonemapParams) {
+        if let Some(tonemap_buffer) = &self.tonemap_buffer {
+            queue.write_buffer(tonemap_buffer, 0, bytemuck::bytes_of(&params));
+        }
+    }
+
+    /// Draw `quads` over the image, their sources are looked up by name with `view`.
+    /// Quads whose source can't be found are left out
+    pub fn add_quads<'a>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        shaders: &Shaders,
+        quads: &[Quad],
+        view: impl Fn(&str) -> Option<&'a TextureView>,
+    ) {
+        let Some(tonemap_buffer) = &self.tonemap_buffer else {
+            log::warn!("Quads can't be shown with frames drawn straight to the window");
+            return;
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Quad Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let target_size = self.target_size.get().unwrap_or(self.image_size);
+
+        let mut layers = Vec::new();
+        for quad in quads {
+            let (node_view, image_view) = match (&quad.source, &quad.image) {
+                (Some(source), None) => match view(source) {
+                    Some(view) => (Some(view), None),
+                    None => {
+                        log::error!(
+                            "Quad {:?} shows {source:?}, which isn't a node of the scene",
+                            quad.name
+                        );
+                        continue;
+                    }
+                },
+                (None, Some(path)) => match load_image(device, queue, path) {
+                    Ok(image) => (None, Some(image.view)),
+                    Err(err) => {
+                        log::error!(
+                            "Failed to load the image {} of quad {:?}: {err}",
+                            path.display(),
+                            quad.name
+                        );
+                        continue;
+                    }
+                },
+                _ => {
+                    log::error!("Quad {:?} needs either a source or an image", quad.name);
+                    continue;
+                }
+            };
+            let tonemapped = node_view.is_some();
+            let texture_view = node_view.or(image_view.as_ref()).unwrap();
+            let transform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Quad Transform Buffer"),
+                contents: bytemuck::bytes_of(&QuadTransform::new(quad, tonemapped, target_size)),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Quad Bind Group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(texture_view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: tonemap_buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 6,
+                        resource: transform_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+            layers.push(QuadLayer {
+                quad: quad.clone(),
+                tonemapped,
+                transform_buffer,
+                bind_group,
+            });
+        }
+        if layers.is_empty() {
+            return;
+        }
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Quad Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Quad Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            })),
+            vertex: VertexState {
+                compilation_options: Default::default(),
+                module: &shaders.render,
+                entry_point: "vs_quad",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shaders.render,
+                entry_point: "fs_quad",
+                targets: &[Some(ColorTargetState {
+                    format: self.target_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: self.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: shaders.pipeline_cache.as_deref(),
+        });
+        self.quads = Some(QuadLayers { pipeline, layers });
+    }
+
+    /// Whether the [`Quad`] called `name` is drawn, it's left out if its source or image is missing
+    pub fn has_quad(&self, name: &str) -> bool {
+        self.quads
+            .as_ref()
+            .is_some_and(|quads| quads.layers.iter().any(|layer| layer.quad.name == name))
+    }
+
+    /// Show the cubemap of the drawing shader as seen by `camera` instead of the image,
+    /// or the image again with `None`
+    pub fn set_preview(&self, queue: &Queue, camera: Option<Camera>) {
+        if let Some(preview_buffer) = &self.preview_buffer {
+            queue.write_buffer(preview_buffer, 0, bytemuck::bytes_of(&Preview::new(camera)));
+        }
+    }
+
+    /// Switch between nearest and linear filtering, returns the new filter.
+    /// `None` in direct mode, where nothing gets sampled
+    pub fn toggle_filter(&mut self) -> Option<Filter> {
+        let other_bind_group = self.other_bind_group.as_mut()?;
+        std::mem::swap(&mut self.bind_group, other_bind_group);
+        self.filter = self.filter.other();
+        Some(self.filter)
+    }
+
+    /// Place the image on a target of `target_size` according to the [`Scaling`],
+    /// only writes the vertex buffer when the size changed
+    pub fn fit(&self, queue: &Queue, target_size: (u32, u32)) {
+        let Some(vertex_buffer) = &self.vertex_buffer else {
+            return;
+        };
+        if self.target_size.replace(Some(target_size)) == Some(target_size) {
+            return;
+        }
+        let vertices = self.scaling.quad(self.image_size, target_size);
+        queue.write_buffer(vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        for layer in self.quads.iter().flat_map(|quads| &quads.layers) {
+            let transform = QuadTransform::new(&layer.quad, layer.tonemapped, target_size);
+            queue.write_buffer(&layer.transform_buffer, 0, bytemuck::bytes_of(&transform));
+        }
+    }
+
+    /// Position in fractions of the image for one in fractions of the target,
+    /// outside of `0..1` on letterbox bars
+    pub fn image_position(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let Some(target_size) = self.target_size.get() else {
+            return [x, y];
+        };
+        let quad = self.scaling.quad(self.image_size, target_size);
+        // Top left and bottom right corners of the quad, in clip space and texture coordinates
+        let ([left, top], [u0, v0]) = ([quad[8], quad[9]], [quad[10], quad[11]]);
+        let ([right, bottom], [u1, v1]) = ([quad[4], quad[5]], [quad[6], quad[7]]);
+        let [clip_x, clip_y] = [x * 2.0 - 1.0, 1.0 - y * 2.0];
+        [
+            u0 + (u1 - u0) * (clip_x - left) / (right - left),
+            v0 + (v1 - v0) * (clip_y - top) / (bottom - top),
+        ]
+    }
+
+    /// Create the multisampled color texture for `target` if its size changed
+    fn update_msaa_target(&mut self, device: &Device, target: &Texture) {
+        let size = (target.width(), target.height());
+        if self
+            .msaa_target
+            .as_ref()
+            .is_none_or(|(_, msaa_size)| *msaa_size != size)
+        {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some("MSAA Color Texture"),
+                size: target.size(),
+                mip_level_count: 1,
+                sample_count: self.sample_count,
+                dimension: TextureDimension::D2,
+                format: self.target_format,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            self.msaa_target = Some((view, size));
+        }
+    }
+
+    /// Render into `target`, through a multisampled texture resolved into it when multisampling.
+    /// `globals` are only read in direct mode. Without `clear` the image is blended over what `target` holds,
+    /// which only works without multisampling
+    pub fn render(
+        &mut self,
+        device: &Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &Texture,
+        globals: &GlobalsState,
+        clear: bool,
+    ) {
+        if self.sample_count > 1 {
+            self.update_msaa_target(device, target);
+        }
+        let target_view = target.create_view(&TextureViewDescriptor::default());
+        let (view, resolve_target) = match &self.msaa_target {
+            Some((msaa_view, _)) if self.sample_count > 1 => (msaa_view, Some(&target_view)),
+            _ => (&target_view, None),
+        };
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: target_view,
-                resolve_target: None,
+                view,
+                resolve_target,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                    store: wgpu::StoreOp::Store,
+                    load: if clear {
+                        wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    // Only the resolved samples are needed afterwards
+                    store: match resolve_target {
+                        Some(_) => wgpu::StoreOp::Discard,
+                        None => wgpu::StoreOp::Store,
+                    },
                 },
             })],
             depth_stencil_attachment: None,
@@ -139,7 +817,93 @@ impl RenderState {
 
         render_pass.set_pipeline(&self.pipeline);
         render_pass.set_bind_group(0, &self.bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.draw(0..4, 0..1);
+        match &self.vertex_buffer {
+            Some(vertex_buffer) => {
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.draw(0..4, 0..1);
+                if let Some(quads) = &self.quads {
+                    render_pass.set_pipeline(&quads.pipeline);
+                    for layer in &quads.layers {
+                        render_pass.set_bind_group(0, &layer.bind_group, &[]);
+                        render_pass.draw(0..4, 0..1);
+                    }
+                }
+            }
+            // Fullscreen triangle, generated in the vertex shader
+            None => {
+                globals.set_render_push_constants(&mut render_pass);
+                render_pass.draw(0..3, 0..1);
+            }
+        }
     }
 }
+
+/// A PNG uploaded by [`load_image`], the input node of a [`RenderGraph`](crate::graph::RenderGraph)
+/// processing images
+pub struct LoadedImage {
+    pub texture: Texture,
+    pub view: TextureView,
+}
+
+/// Upload the PNG at `path` as sRGB texture, any color type is expanded to 8-bit RGBA
+pub fn load_image(device: &Device, queue: &Queue, path: &Path) -> io::Result<LoadedImage> {
+    let mut decoder = png::Decoder::new(io::BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(io::Error::other)?;
+    pixels.truncate(info.buffer_size());
+    let rgba: Vec<u8> = match info.color_type {
+        png::ColorType::Rgba => pixels,
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|gray| [gray[0], gray[0], gray[0], gray[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels
+            .iter()
+            .flat_map(|&gray| [gray, gray, gray, 255])
+            .collect(),
+        png::ColorType::Indexed => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "palette wasn't expanded",
+            ));
+        }
+    };
+    Ok(upload_image(device, queue, info.width, info.height, &rgba))
+}
+
+/// Upload `width` × `height` pixels of 8-bit sRGB RGBA as texture, which can be overwritten with new pixels
+pub fn upload_image(
+    device: &Device,
+    queue: &Queue,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> LoadedImage {
+    let texture = device.create_texture_with_data(
+        queue,
+        &TextureDescriptor {
+            label: Some("Image Texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        TextureDataOrder::LayerMajor,
+        rgba,
+    );
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    LoadedImage { texture, view }
+}
@@ -0,0 +1,80 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use web_time::Instant;
+
+use crate::{cli::Options, library};
+
+/// Longest side of the drawn image
+pub const MAX_IMAGE_SIZE: u32 = 2048;
+/// Size in bytes of the largest `scratch` buffer
+pub const MAX_SCRATCH_SIZE: u64 = 16 << 20;
+/// Integer `#define`s above this, usually loop counts, are lowered to it
+pub const MAX_DEFINE_VALUE: u32 = 1024;
+/// GPU time a frame may take
+const FRAME_BUDGET: Duration = Duration::from_millis(200);
+/// Frames in a row over the budget before the shader is stopped
+const OVER_BUDGET_FRAMES: u32 = 3;
+
+/// Whether the `--shader` gets the limits of the sandbox:
+/// with `--sandbox`, or when it was fetched into the library unless it is trusted with `--trust`
+pub fn applies(options: &Options) -> bool {
+    let fetched = options
+        .shader
+        .as_deref()
+        .map(library::resolve_shader)
+        .is_some_and(|shader| library::contains(&shader));
+    options.sandbox || (fetched && !options.trust)
+}
+
+/// Measures how long the GPU takes for the frames of a sandboxed shader
+pub struct Watchdog {
+    /// GPU time of the last submitted frame, once it is done
+    last_frame: Arc<Mutex<Option<Duration>>>,
+    over_budget: u32,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        Self {
+            last_frame: Arc::new(Mutex::new(None)),
+            over_budget: 0,
+        }
+    }
+
+    /// Start timing the work just submitted to `queue`
+    pub fn submitted(&self, queue: &wgpu::Queue) {
+        let submitted_at = Instant::now();
+        let last_frame = Arc::clone(&self.last_frame);
+        queue.on_submitted_work_done(move || {
+            *last_frame.lock().unwrap() = Some(submitted_at.elapsed());
+        });
+    }
+
+    /// Returns whether the shader exceeded its budget for too many frames in a row
+    pub fn poll(&mut self, device: &wgpu::Device) -> bool {
+        device.poll(wgpu::Maintain::Poll);
+        let Some(duration) = self.last_frame.lock().unwrap().take() else {
+            return false;
+        };
+        if duration > FRAME_BUDGET {
+            self.over_budget += 1;
+            log::warn!(
+                "Frame took {} ms on the GPU, over the sandbox budget of {} ms",
+                duration.as_millis(),
+                FRAME_BUDGET.as_millis()
+            );
+        } else {
+            self.over_budget = 0;
+        }
+        self.over_budget >= OVER_BUDGET_FRAMES
+    }
+}
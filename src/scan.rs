@@ -0,0 +1,261 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+/// Values summed per workgroup by `src/shaders/scan.wgsl`
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Whether a value's prefix sum includes the value itself
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScanKind {
+    /// `[1, 2, 3]` becomes `[1, 3, 6]`
+    Inclusive,
+    /// `[1, 2, 3]` becomes `[0, 1, 3]`, e.g. where compacted items are written to
+    Exclusive,
+}
+
+/// See `Level` in `src/shaders/scan.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Level {
+    len: u32,
+    exclusive: u32,
+    blocks: u32,
+    _padding: u32,
+}
+
+/// Prefix sums of `u32`s in storage buffers on the GPU, e.g. for compaction or histogram equalization.
+///
+/// Every workgroup scans a block of values, the totals of the blocks are scanned the same way
+/// one level up until a single block is left, then each level adds the totals of the blocks before
+pub struct GpuScan {
+    scan_pipeline: ComputePipeline,
+    add_pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+}
+
+impl GpuScan {
+    pub fn new(device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Scan Shader"),
+            source: ShaderSource::Wgsl(include_str!("./shaders/scan.wgsl").into()),
+        });
+        let buffer = |binding, ty| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = BufferBindingType::Storage { read_only: false };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Scan Bind Group Layout"),
+            entries: &[
+                buffer(0, storage),
+                buffer(1, storage),
+                buffer(2, BufferBindingType::Uniform),
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Scan Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                compilation_options: Default::default(),
+                label: Some("Scan Pipeline"),
+                layout: Some(&layout),
+                module: &module,
+                entry_point,
+                cache: None,
+            })
+        };
+
+        Self {
+            scan_pipeline: create_pipeline("scan_blocks"),
+            add_pipeline: create_pipeline("add_offsets"),
+            bind_group_layout,
+        }
+    }
+
+    /// Record replacing the first `len` `u32`s of `buffer` by their prefix sums, wrapping on overflow.
+    /// `buffer` needs [`BufferUsages::STORAGE`]
+    pub fn scan(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        buffer: &Buffer,
+        len: u32,
+        kind: ScanKind,
+    ) {
+        if len == 0 {
+            return;
+        }
+        // The block totals of every level, the last one holds the total of all values
+        let mut lens = vec![len];
+        let mut sums = Vec::new();
+        loop {
+            let blocks = lens[lens.len() - 1].div_ceil(WORKGROUP_SIZE);
+            sums.push(device.create_buffer(&BufferDescriptor {
+                label: Some("Scan Block Sums Buffer"),
+                size: u64::from(blocks) * 4,
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            }));
+            if blocks == 1 {
+                break;
+            }
+            lens.push(blocks);
+        }
+        let bind_groups: Vec<_> = lens
+            .iter()
+            .enumerate()
+            .map(|(depth, &len)| {
+                let level = device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Scan Level Buffer"),
+                    contents: bytemuck::bytes_of(&Level {
+                        len,
+                        exclusive: u32::from(depth == 0 && kind == ScanKind::Exclusive),
+                        blocks: len.div_ceil(WORKGROUP_SIZE),
+                        _padding: 0,
+                    }),
+                    usage: BufferUsages::UNIFORM,
+                });
+                let data = if depth == 0 { buffer } else { &sums[depth - 1] };
+                device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("Scan Bind Group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: data.as_entire_binding(),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: sums[depth].as_entire_binding(),
+                        },
+                        BindGroupEntry {
+                            binding: 2,
+                            resource: level.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        let max_workgroups = device.limits().max_compute_workgroups_per_dimension;
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Scan Pass"),
+        });
+        compute_pass.set_pipeline(&self.scan_pipeline);
+        for (bind_group, &len) in bind_groups.iter().zip(&lens) {
+            let (x, y) = workgroups(len, max_workgroups);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(x, y, 1);
+        }
+        // The single block at the top needs no offsets
+        compute_pass.set_pipeline(&self.add_pipeline);
+        for (bind_group, &len) in bind_groups.iter().zip(&lens).rev().skip(1) {
+            let (x, y) = workgroups(len, max_workgroups);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(x, y, 1);
+        }
+    }
+}
+
+/// Workgroups covering the blocks of `len` values, in rows of at most `max_workgroups`.
+/// The last row may overhang the blocks, those workgroups do nothing
+fn workgroups(len: u32, max_workgroups: u32) -> (u32, u32) {
+    let blocks = len.div_ceil(WORKGROUP_SIZE);
+    let x = blocks.min(max_workgroups);
+    (x, blocks.div_ceil(x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_gpu;
+
+    #[test]
+    fn workgroups_fit_in_one_row() {
+        assert_eq!(workgroups(1, 65_535), (1, 1));
+        assert_eq!(workgroups(256, 65_535), (1, 1));
+        assert_eq!(workgroups(257, 65_535), (2, 1));
+        assert_eq!(workgroups(70_000, 65_535), (274, 1));
+    }
+
+    #[test]
+    fn workgroups_split_into_rows() {
+        // 274 blocks in rows of 20, the last row overhangs by 6
+        assert_eq!(workgroups(70_000, 20), (20, 14));
+        assert_eq!(workgroups(256 * 40, 20), (20, 2));
+        let blocks = 65_536 * 2 + 1;
+        assert_eq!(workgroups(blocks * WORKGROUP_SIZE, 65_535), (65_535, 3));
+    }
+
+    /// Scan `values` on the GPU, `None` without an adapter
+    fn gpu_scan(values: &[u32], kind: ScanKind, max_workgroups: u32) -> Option<Vec<u32>> {
+        let limits = Limits {
+            max_compute_workgroups_per_dimension: max_workgroups,
+            ..Limits::downlevel_defaults()
+        };
+        let (device, queue) = test_gpu::device(limits)?;
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Test Scan Buffer"),
+            contents: bytemuck::cast_slice(values),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        });
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        GpuScan::new(&device).scan(&device, &mut encoder, &buffer, values.len() as u32, kind);
+        queue.submit(Some(encoder.finish()));
+        Some(test_gpu::read_u32s(&device, &queue, &buffer, values.len()))
+    }
+
+    fn cpu_scan(values: &[u32], kind: ScanKind) -> Vec<u32> {
+        let mut sum = 0u32;
+        values
+            .iter()
+            .map(|&value| {
+                let before = sum;
+                sum = sum.wrapping_add(value);
+                match kind {
+                    ScanKind::Inclusive => sum,
+                    ScanKind::Exclusive => before,
+                }
+            })
+            .collect()
+    }
+
+    fn check(len: u32, max_workgroups: u32) {
+        let values: Vec<u32> = (0..len)
+            .map(|i| i.wrapping_mul(2_654_435_761) % 1000)
+            .collect();
+        for kind in [ScanKind::Inclusive, ScanKind::Exclusive] {
+            let Some(sums) = gpu_scan(&values, kind, max_workgroups) else {
+                eprintln!("No adapter with compute shaders, skipping");
+                return;
+            };
+            assert_eq!(
+                sums,
+                cpu_scan(&values, kind),
+                "{kind:?} scan of {len} values"
+            );
+        }
+    }
+
+    #[test]
+    fn scan_matches_cpu() {
+        for len in [1, 255, 256, 257, 70_000] {
+            check(len, 65_535);
+        }
+    }
+
+    #[test]
+    fn scan_matches_cpu_in_rows_of_workgroups() {
+        check(70_000, 20);
+    }
+}
@@ -0,0 +1,916 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::{
+    accumulation::AccumulationState,
+    bloom::{BloomParams, BloomState},
+    cli::{Options, Preset},
+    compute::{ComputeState, OutputStorage, VolumeView},
+    convolution::{Convolution, ConvolutionState},
+    crash,
+    downsample::DownsampleState,
+    examples::Example,
+    fragment::FragmentDrawState,
+    globals::GlobalsState,
+    gpu::{DrawMode, GpuState},
+    graph::{Pass, RenderGraph, scaled},
+    library,
+    mipmap::MipmapState,
+    paint::{PaintState, UndoHistory},
+    post_effect::PostEffect,
+    preprocessor::ShaderSource,
+    reaction_diffusion::{ReactionDiffusionParams, ReactionDiffusionState, STATE_FORMATS},
+    render::{Filter, Quad, RenderState, Scaling, load_image},
+    sandbox,
+    shaders::Shaders,
+    tonemap::TonemapParams,
+    wall::WallTile,
+};
+
+/// Everything needed to (re)build a [`Scene`], resolved against what the device supports
+#[derive(Clone)]
+pub struct SceneConfig {
+    pub preset: Preset,
+    pub accumulate: bool,
+    /// Replaces the `draw` function of the preset, re-read on every reload
+    pub shader_path: Option<PathBuf>,
+    /// Initial `globals.params` from the bundle of the shader
+    pub initial_params: Option<[f32; 2]>,
+    /// Replaces the `draw` function of the preset, unless there is a `shader_path`
+    pub example: Option<Example>,
+    /// Files bound as storage buffers of the compute drawing, see [`SceneConfig::create_storage_buffers`]
+    pub storage_buffers: Vec<PathBuf>,
+    /// Size in bytes of the `scratch` buffer of the compute drawing, a multiple of 4
+    pub scratch_size: Option<u64>,
+    /// Number of previous frames the compute drawing reads, see `src/shaders/history.wgsl`
+    pub history: Option<u32>,
+    /// Edge length of the volume of shaders defining `volume`, see `src/shaders/volume.wgsl`
+    pub volume_size: u32,
+    pub volume_view: VolumeView,
+    /// Edge length of the faces of shaders defining `cubemap`, see `src/shaders/cubemap.wgsl`
+    pub cubemap_size: u32,
+    pub reaction_diffusion_params: ReactionDiffusionParams,
+    /// Format of the reaction-diffusion state, the first of [`STATE_FORMATS`] the adapter can write
+    pub state_format: wgpu::TextureFormat,
+    /// Where the compute drawing writes its image, negotiated with the adapter, see [`ComputeState::output_storage`]
+    pub output_storage: OutputStorage,
+    /// Compiled pipelines kept between runs, `None` where the device can't keep them
+    pub pipeline_cache: Option<Arc<wgpu::PipelineCache>>,
+    /// Steps the compute drawing or simulation advances per frame, the preset's default if `None`
+    pub steps_per_frame: Option<u32>,
+    /// What the paint preset keeps for undo
+    pub undo_history: UndoHistory,
+    /// Bytes the paint preset may keep for undo
+    pub undo_memory: u64,
+    pub workgroup_size: [u32; 2],
+    /// Size of the drawn image, `supersample` times the presented one scaled by the [`scale`](Self::scale) of the drawing
+    pub width: u32,
+    pub height: u32,
+    /// Size of the image handed to the render pass
+    pub presented_size: [u32; 2],
+    /// The image is drawn at this many times the resolution in each direction and averaged down
+    pub supersample: u32,
+    /// Factors of the resolution of nodes of the render graph by name, from `--scale`
+    pub scales: BTreeMap<String, f32>,
+    /// The part of a video wall drawn here, `None` when drawing all of the image
+    pub wall: Option<WallTile>,
+    /// Samples per pixel of the render pass, 1 unless multisampling
+    pub msaa_samples: u32,
+    /// Whether the presented image gets a mip chain every frame
+    pub mipmaps: bool,
+    /// Glow added around bright colors, `None` without bloom
+    pub bloom: Option<BloomParams>,
+    /// Kernel the image is convolved with after bloom, from `--convolve` or the config file
+    pub convolution: Option<Convolution>,
+    /// Directory with the manifest of the post effects, see [`PostEffect::add_stack`]
+    pub post_effects: Option<PathBuf>,
+    /// How the image is fitted into the window
+    pub scaling: Scaling,
+    /// How the image is sampled when scaled to the window
+    pub filter: Filter,
+    /// `@compute` entry point of the drawing shader to dispatch instead of `main`
+    pub entry_point: Option<String>,
+    /// Whether the shader is limited by the sandbox, see [`sandbox::applies`]
+    pub sandboxed: bool,
+    /// Drawn over the image on screen, from the `[[quads]]` of the config file
+    pub quads: Vec<Quad>,
+}
+
+impl SceneConfig {
+    /// The `--shader` or `--example` drawn at `width` × `height` without a window,
+    /// by the compute pass alone
+    pub fn headless(options: &Options, width: u32, height: u32) -> Self {
+        let shader_path = options.shader.as_deref().map(library::resolve_shader);
+        Self {
+            preset: Preset::Drawing,
+            accumulate: false,
+            initial_params: options
+                .params
+                .or_else(|| shader_path.as_deref().and_then(library::read_params)),
+            shader_path,
+            example: options.example,
+            storage_buffers: options.storage_buffers.clone(),
+            scratch_size: options.scratch_size(),
+            history: options.history,
+            volume_size: options.volume_size,
+            volume_view: options.volume_view,
+            cubemap_size: options.cubemap_size,
+            reaction_diffusion_params: ReactionDiffusionParams::new(options.feed, options.kill),
+            state_format: STATE_FORMATS[0],
+            output_storage: OutputStorage::Float,
+            pipeline_cache: None,
+            steps_per_frame: options.steps_per_frame,
+            undo_history: options.undo_history,
+            undo_memory: 0,
+            workgroup_size: options
+                .workgroup_size
+                .unwrap_or(options.profile.workgroup_size()),
+            width,
+            height,
+            presented_size: [width, height],
+            supersample: 1,
+            scales: BTreeMap::new(),
+            wall: None,
+            msaa_samples: 1,
+            mipmaps: false,
+            bloom: None,
+            convolution: None,
+            post_effects: None,
+            scaling: options.scaling,
+            filter: options.filter,
+            entry_point: options.entry_point.clone(),
+            quads: Vec::new(),
+            sandboxed: sandbox::applies(options),
+        }
+    }
+
+    pub fn new(options: &Options, gpu_state: &GpuState) -> Self {
+        let mut preset = options.preset;
+        let state_format = ReactionDiffusionState::state_format(gpu_state);
+        if preset == Preset::ReactionDiffusion && state_format.is_none() {
+            log::warn!(
+                "The reaction-diffusion preset requires compute shaders and float storage textures, showing the drawing instead"
+            );
+            preset = Preset::Drawing;
+        }
+        if preset == Preset::Paint && !PaintState::supported(gpu_state) {
+            log::warn!(
+                "The paint preset requires compute shaders and float storage textures, showing the drawing instead"
+            );
+            preset = Preset::Drawing;
+        }
+
+        let mut accumulate =
+            options.accumulate || preset == Preset::PathTracer || options.motion_blur.is_some();
+        if accumulate && gpu_state.draw_mode != DrawMode::Compute {
+            log::warn!(
+                "Accumulating frames and motion blur require compute shaders, showing single frames instead"
+            );
+            accumulate = false;
+        }
+        let output_storage =
+            ComputeState::output_storage(&gpu_state.adapter).unwrap_or(OutputStorage::Float);
+        // The passes after the drawing write float images
+        let float_passes = output_storage == OutputStorage::Float;
+        if accumulate && !float_passes {
+            log::warn!(
+                "Accumulating frames and motion blur require float storage textures, showing single frames instead"
+            );
+            accumulate = false;
+        }
+
+        let sandboxed = sandbox::applies(options);
+        let mut supersample = options.supersample;
+        if supersample > 1 && sandboxed {
+            log::warn!("Supersampling is turned off in the sandbox");
+            supersample = 1;
+        }
+        if supersample > 1 && (gpu_state.draw_mode != DrawMode::Compute || !float_passes) {
+            log::warn!(
+                "Supersampling requires compute shaders and float storage textures, drawing at the presented resolution"
+            );
+            supersample = 1;
+        }
+
+        let mut scales: BTreeMap<String, f32> = options.scales.iter().cloned().collect();
+        if sandboxed && scales.values().any(|&scale| scale > 1.0) {
+            log::warn!("Scaling nodes up is turned off in the sandbox");
+            scales.retain(|_, scale| *scale <= 1.0);
+        }
+        // Nodes read inputs of other sizes through a resampling compute pass
+        if !scales.is_empty() && (gpu_state.draw_mode != DrawMode::Compute || !float_passes) {
+            log::warn!(
+                "Scaling nodes requires compute shaders and float storage textures, running them at the presented resolution"
+            );
+            scales.clear();
+        }
+
+        let mut mipmaps = options.mipmaps;
+        if mipmaps && (gpu_state.draw_mode != DrawMode::Compute || !float_passes) {
+            log::warn!(
+                "Generating mipmaps requires compute shaders and float storage textures, presenting without them"
+            );
+            mipmaps = false;
+        }
+
+        let mut bloom =
+            (options.bloom > 0.0).then(|| BloomParams::new(options.bloom_threshold, options.bloom));
+        if bloom.is_some() && (gpu_state.draw_mode != DrawMode::Compute || !float_passes) {
+            log::warn!(
+                "Bloom requires compute shaders and float storage textures, presenting without it"
+            );
+            bloom = None;
+        }
+
+        let mut convolution = options.convolve.clone().map(|kernel| Convolution {
+            kernel,
+            input: options.convolve_input.clone(),
+        });
+        if convolution.is_some() && (gpu_state.draw_mode != DrawMode::Compute || !float_passes) {
+            log::warn!(
+                "Convolutions require compute shaders and float storage textures, presenting without them"
+            );
+            convolution = None;
+        }
+
+        let mut post_effects = options.post_effects.clone();
+        if post_effects.is_some() && (gpu_state.draw_mode != DrawMode::Compute || !float_passes) {
+            log::warn!(
+                "Post effects require compute shaders and float storage textures, presenting without them"
+            );
+            post_effects = None;
+        }
+
+        let msaa_samples = gpu_state.msaa_sample_count(options.msaa);
+        if msaa_samples < options.msaa {
+            log::warn!(
+                "{} samples per pixel are unsupported for {:?}, multisampling with {msaa_samples}",
+                options.msaa,
+                gpu_state.surface_format
+            );
+        }
+
+        let shader_path = options.shader.as_deref().map(library::resolve_shader);
+
+        let default_workgroup_size = options.profile.workgroup_size();
+        let mut workgroup_size = options.workgroup_size.unwrap_or(default_workgroup_size);
+        if !workgroup_size_supported(workgroup_size, &gpu_state.device.limits()) {
+            log::warn!(
+                "Workgroup size {workgroup_size:?} exceeds the limits of the device, using {default_workgroup_size:?}"
+            );
+            workgroup_size = default_workgroup_size;
+        }
+
+        if !options.storage_buffers.is_empty() && gpu_state.draw_mode != DrawMode::Compute {
+            log::warn!("Storage buffers require compute shaders, they aren't bound");
+        }
+
+        let mut scratch_size = options.scratch_size();
+        if scratch_size.is_some() && gpu_state.draw_mode != DrawMode::Compute {
+            log::warn!("The scratch buffer requires compute shaders, it isn't bound");
+            scratch_size = None;
+        }
+        let max_size = u64::from(gpu_state.device.limits().max_storage_buffer_binding_size) & !3;
+        if let Some(size) = scratch_size.filter(|&size| size > max_size) {
+            log::warn!(
+                "A scratch buffer of {size} bytes exceeds the limits of the device, using {max_size}"
+            );
+            scratch_size = Some(max_size);
+        }
+        if let Some(size) =
+            scratch_size.filter(|&size| sandboxed && size > sandbox::MAX_SCRATCH_SIZE)
+        {
+            log::warn!(
+                "A scratch buffer of {size} bytes exceeds the sandbox, using {}",
+                sandbox::MAX_SCRATCH_SIZE
+            );
+            scratch_size = Some(sandbox::MAX_SCRATCH_SIZE);
+        }
+
+        let mut history = options.history;
+        if history.is_some() && gpu_state.draw_mode != DrawMode::Compute {
+            log::warn!("Reading previous frames requires compute shaders, they aren't kept");
+            history = None;
+        }
+
+        let limits = gpu_state.device.limits();
+        let max_volume_size = limits
+            .max_texture_array_layers
+            .min(limits.max_texture_dimension_2d);
+        let mut volume_size = options.volume_size;
+        if volume_size > max_volume_size {
+            log::warn!(
+                "A volume of {volume_size} voxels exceeds the limits of the device, using {max_volume_size}"
+            );
+            volume_size = max_volume_size;
+        }
+        // The faces are drawn three wide
+        let max_cubemap_size = limits.max_texture_dimension_2d / 3;
+        let mut cubemap_size = options.cubemap_size;
+        if cubemap_size > max_cubemap_size {
+            log::warn!(
+                "Cubemap faces of {cubemap_size} pixels exceed the limits of the device, using {max_cubemap_size}"
+            );
+            cubemap_size = max_cubemap_size;
+        }
+
+        let mut config = Self {
+            preset,
+            accumulate,
+            initial_params: options
+                .params
+                .or_else(|| shader_path.as_deref().and_then(library::read_params))
+                .or_else(|| {
+                    options
+                        .example
+                        .filter(|_| shader_path.is_none())
+                        .and_then(Example::params)
+                }),
+            shader_path,
+            example: options.example,
+            storage_buffers: options.storage_buffers.clone(),
+            scratch_size,
+            history,
+            volume_size,
+            volume_view: options.volume_view,
+            cubemap_size,
+            reaction_diffusion_params: ReactionDiffusionParams::new(options.feed, options.kill),
+            state_format: state_format.unwrap_or(STATE_FORMATS[0]),
+            output_storage,
+            pipeline_cache: gpu_state.pipeline_cache.clone(),
+            steps_per_frame: options.steps_per_frame,
+            undo_history: options.undo_history,
+            undo_memory: options.undo_memory << 20,
+            workgroup_size,
+            width: 0,
+            height: 0,
+            presented_size: [0, 0],
+            supersample,
+            scales,
+            // Checked when the app starts
+            wall: options.wall().ok().flatten(),
+            msaa_samples,
+            mipmaps,
+            bloom,
+            convolution,
+            post_effects,
+            scaling: options.scaling,
+            filter: options.filter,
+            entry_point: options.entry_point.clone(),
+            sandboxed,
+            quads: Vec::new(),
+        };
+        config.set_image_size(options.resolution(), &gpu_state.device);
+        config
+    }
+
+    /// Present images of `width` × `height`, drawn at `supersample` times that size scaled by the [`scale`](Self::scale) of the drawing.
+    /// Supersampling is lowered if the drawn image would exceed the textures the device supports,
+    /// sandboxed shaders draw at most [`sandbox::MAX_IMAGE_SIZE`] on the longer side
+    pub fn set_image_size(&mut self, (mut width, mut height): (u32, u32), device: &wgpu::Device) {
+        let longer_side = width.max(height);
+        if self.sandboxed && longer_side > sandbox::MAX_IMAGE_SIZE {
+            width = (width * sandbox::MAX_IMAGE_SIZE / longer_side).max(1);
+            height = (height * sandbox::MAX_IMAGE_SIZE / longer_side).max(1);
+            log::warn!("Drawing at {width}x{height} in the sandbox");
+        }
+        self.presented_size = [width, height];
+        let scale = self.scale(DRAWING);
+        let drawn = |supersample: u32| scaled([width * supersample, height * supersample], scale);
+        let max_size = device.limits().max_texture_dimension_2d;
+        let longer_drawn_side = |supersample: u32| drawn(supersample).into_iter().max().unwrap();
+        while self.supersample > 1 && longer_drawn_side(self.supersample) > max_size {
+            self.supersample -= 1;
+            log::warn!(
+                "Supersampling {width}x{height} exceeds the maximum texture size of {max_size}, lowering it to {}x",
+                self.supersample
+            );
+        }
+        let [mut drawn_width, mut drawn_height] = drawn(self.supersample);
+        let longer_side = drawn_width.max(drawn_height);
+        if longer_side > max_size {
+            drawn_width = (u64::from(drawn_width) * u64::from(max_size) / u64::from(longer_side))
+                .max(1) as u32;
+            drawn_height = (u64::from(drawn_height) * u64::from(max_size) / u64::from(longer_side))
+                .max(1) as u32;
+            log::warn!(
+                "Scaling the drawing by {scale} exceeds the maximum texture size of {max_size}, drawing at {drawn_width}x{drawn_height}"
+            );
+        }
+        self.width = drawn_width;
+        self.height = drawn_height;
+    }
+
+    /// Factor of the usual resolution of the node `name` of the render graph, 1 unless set with `--scale`
+    pub fn scale(&self, name: &str) -> f32 {
+        self.scales.get(name).copied().unwrap_or(1.0)
+    }
+
+    /// Top left pixel of the part of the video wall drawn here, added to the pixels handed to `draw`
+    pub fn image_offset(&self) -> [u32; 2] {
+        self.wall
+            .map_or([0, 0], |wall| wall.offset(self.width, self.height))
+    }
+
+    /// Size of the whole image in pixels, larger than the drawn one on a video wall
+    pub fn resolution(&self) -> [f32; 2] {
+        let [columns, rows] = self.wall.map_or([1, 1], |wall| wall.grid);
+        [(self.width * columns) as f32, (self.height * rows) as f32]
+    }
+
+    /// The drawing as `--preset`, `--example` or `--shader` would name it,
+    /// for sync followers to load the same one
+    pub fn scene_name(&self) -> String {
+        match (&self.shader_path, self.example) {
+            (Some(path), _) => path.display().to_string(),
+            (None, Some(example)) => possible_value_name(example),
+            (None, None) => possible_value_name(self.preset),
+        }
+    }
+
+    /// Initial `globals.params`: the rates of the reaction-diffusion preset, or those of the bundle
+    pub fn start_params(&self) -> Option<[f32; 2]> {
+        match self.preset {
+            Preset::ReactionDiffusion => Some(self.reaction_diffusion_params.controls()),
+            Preset::Drawing
+            | Preset::PathTracer
+            | Preset::Sdf
+            | Preset::Fractal
+            | Preset::Paint => self.initial_params,
+        }
+    }
+
+    pub fn shader_name(&self) -> String {
+        match (&self.shader_path, self.example) {
+            (Some(path), _) => path.display().to_string(),
+            (None, Some(example)) => format!("the {example:?} example"),
+            (None, None) => format!("built into the {:?} preset", self.preset),
+        }
+    }
+
+    pub fn draw_source(&self) -> io::Result<ShaderSource> {
+        match (&self.shader_path, self.example) {
+            (Some(path), _) => ShaderSource::read(path),
+            (None, Some(example)) => Ok(ShaderSource::builtin(
+                &example.file_name(),
+                example.source(),
+            )),
+            (None, None) => Ok(Shaders::builtin_draw_source(self.preset)),
+        }
+    }
+
+    /// Read-only storage buffers holding the contents of the `storage_buffers` files,
+    /// zero-padded to whole `u32`s
+    pub fn create_storage_buffers(&self, device: &wgpu::Device) -> io::Result<Vec<wgpu::Buffer>> {
+        self.storage_buffers
+            .iter()
+            .map(|path| {
+                let mut contents = fs::read(path)?;
+                if contents.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Storage buffer {} is empty", path.display()),
+                    ));
+                }
+                contents.resize(contents.len().next_multiple_of(4), 0);
+
+                Ok(device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("User Storage Buffer"),
+                    contents: &contents,
+                    usage: wgpu::BufferUsages::STORAGE,
+                }))
+            })
+            .collect()
+    }
+}
+
+fn possible_value_name(value: impl clap::ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .expect("no variants are skipped")
+        .get_name()
+        .to_owned()
+}
+
+fn workgroup_size_supported([x, y]: [u32; 2], limits: &wgpu::Limits) -> bool {
+    x <= limits.max_compute_workgroup_size_x
+        && y <= limits.max_compute_workgroup_size_y
+        && x * y <= limits.max_compute_invocations_per_workgroup
+}
+
+/// Node of the drawing in the [`RenderGraph`] of a [`Scene`]
+const DRAWING: &str = "drawing";
+const ACCUMULATION: &str = "accumulation";
+const DOWNSAMPLING: &str = "downsampling";
+const BLOOM: &str = "bloom";
+const CONVOLUTION: &str = "convolution";
+/// Node generating the mip chain, the last one when present
+const MIPMAPS: &str = "mipmaps";
+
+/// Nodes `--scale` can run at another resolution. The drawing is scaled relative to the supersampled size,
+/// the others relative to the presented one
+pub const SCALED_NODES: [&str; 5] = [DRAWING, ACCUMULATION, DOWNSAMPLING, BLOOM, CONVOLUTION];
+
+/// Source of [`Scene::id`]
+static NEXT_SCENE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The pipelines producing and presenting the image
+pub struct Scene {
+    /// Drawing, accumulation, downsampling, bloom, convolution, post effects and mipmaps, as far as they are enabled
+    pub graph: RenderGraph,
+    pub render_state: RenderState,
+    /// Kept for [`Scene::window_render_state`]
+    shaders: Shaders,
+    id: u64,
+}
+
+impl Scene {
+    pub fn new(
+        gpu_state: &GpuState,
+        globals: &GlobalsState,
+        config: &SceneConfig,
+        tonemap_params: TonemapParams,
+    ) -> io::Result<Self> {
+        let SceneConfig {
+            preset,
+            accumulate,
+            width,
+            height,
+            ..
+        } = *config;
+
+        let draw_src = config.draw_source()?;
+        let shaders = Shaders::new(&gpu_state.device, gpu_state.draw_mode, &draw_src, config)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let graph_error = |err: String| io::Error::new(io::ErrorKind::InvalidData, err);
+
+        let drawing = match gpu_state.draw_mode {
+            DrawMode::Compute if preset == Preset::ReactionDiffusion => Some(
+                Drawing::ReactionDiffusion(Box::new(ReactionDiffusionState::new(
+                    &gpu_state.device,
+                    &gpu_state.queue,
+                    &shaders,
+                    config,
+                ))),
+            ),
+            DrawMode::Compute if preset == Preset::Paint => {
+                Some(Drawing::Paint(Box::new(PaintState::new(
+                    &gpu_state.device,
+                    &shaders,
+                    width,
+                    height,
+                    config.undo_history,
+                    config.undo_memory,
+                ))))
+            }
+            DrawMode::Compute => {
+                let mut builder =
+                    ComputeState::builder(&gpu_state.device, &shaders, globals, width, height);
+                let storage_buffers = config.create_storage_buffers(&gpu_state.device)?;
+                for buffer in &storage_buffers {
+                    builder = builder.storage_buffer(buffer, true);
+                }
+                if let Some(entry_point) = &config.entry_point {
+                    builder = builder.entry_point(entry_point);
+                }
+                if let Some(steps) = config.steps_per_frame {
+                    builder = builder.steps_per_frame(steps);
+                }
+                Some(Drawing::Compute(Box::new(builder.build())))
+            }
+            DrawMode::Fragment => Some(Drawing::Fragment(Box::new(FragmentDrawState::new(
+                &gpu_state.device,
+                &shaders,
+                globals,
+                width,
+                height,
+            )))),
+            DrawMode::Direct => None,
+        };
+
+        // Every pass reads the output of the one before it
+        let mut graph = RenderGraph::default();
+        if let Some(drawing) = drawing {
+            graph
+                .add(DRAWING, &[], Pass::Drawing(drawing))
+                .map_err(graph_error)?;
+        }
+        // Every scaled node reads its input resampled to its own size
+        let resample = |graph: &mut RenderGraph, size: [u32; 2]| {
+            if graph.last().is_none() {
+                return Ok(());
+            }
+            PostEffect::resample(&gpu_state.device, size, graph, globals, config)
+                .map_err(graph_error)
+        };
+        let presented = config.presented_size;
+        if accumulate {
+            let [width, height] = scaled([width, height], config.scale(ACCUMULATION));
+            resample(&mut graph, [width, height])?;
+            graph
+                .add_after_last(ACCUMULATION, |view| {
+                    Pass::Accumulation(AccumulationState::new(
+                        &gpu_state.device,
+                        &shaders,
+                        view,
+                        width,
+                        height,
+                    ))
+                })
+                .map_err(graph_error)?;
+        }
+        if config.supersample > 1 {
+            let [width, height] = scaled(presented, config.scale(DOWNSAMPLING));
+            // Averages blocks of `supersample` × `supersample` pixels
+            resample(
+                &mut graph,
+                [width * config.supersample, height * config.supersample],
+            )?;
+            graph
+                .add_after_last(DOWNSAMPLING, |view| {
+                    Pass::Downsample(DownsampleState::new(
+                        &gpu_state.device,
+                        &shaders,
+                        view,
+                        width,
+                        height,
+                    ))
+                })
+                .map_err(graph_error)?;
+        }
+        if let Some(params) = config.bloom {
+            let [width, height] = scaled(presented, config.scale(BLOOM));
+            resample(&mut graph, [width, height])?;
+            graph
+                .add_after_last(BLOOM, |view| {
+                    Pass::Bloom(BloomState::new(
+                        &gpu_state.device,
+                        &shaders,
+                        view,
+                        width,
+                        height,
+                        params,
+                    ))
+                })
+                .map_err(graph_error)?;
+        }
+        if let Some(convolution) = &config.convolution {
+            // Convolved instead of the drawing when given
+            let image = convolution
+                .input
+                .as_ref()
+                .map(|path| {
+                    load_image(&gpu_state.device, &gpu_state.queue, path).map_err(|err| {
+                        io::Error::new(err.kind(), format!("{}: {err}", path.display()))
+                    })
+                })
+                .transpose()?;
+            let [width, height] = scaled(presented, config.scale(CONVOLUTION));
+            if image.is_none() {
+                resample(&mut graph, [width, height])?;
+            }
+            graph
+                .add_after_last(CONVOLUTION, |view| {
+                    Pass::Convolution(ConvolutionState::new(
+                        &gpu_state.device,
+                        &shaders,
+                        image.as_ref().map_or(view, |image| &image.view),
+                        width,
+                        height,
+                        &convolution.kernel,
+                    ))
+                })
+                .map_err(graph_error)?;
+        }
+        if let Some(dir) = &config.post_effects
+            && graph.last().is_some()
+        {
+            PostEffect::add_stack(&gpu_state.device, dir, &mut graph, globals, config)
+                .map_err(graph_error)?;
+        }
+        resample(&mut graph, presented)?;
+        if config.mipmaps {
+            // Generated from the image rather than sampling it, see `Pass::Mipmap`
+            let [width, height] = presented;
+            graph
+                .add_after_last(MIPMAPS, |_| {
+                    Pass::Mipmap(MipmapState::new(&gpu_state.device, &shaders, width, height))
+                })
+                .map_err(graph_error)?;
+        }
+
+        let compute_state = graph.passes().find_map(|pass| match pass {
+            Pass::Drawing(Drawing::Compute(compute_state)) => Some(&**compute_state),
+            _ => None,
+        });
+        let mut render_state = match graph.last().and_then(|last| graph.view(last)) {
+            Some(view) => RenderState::new(
+                &gpu_state.device,
+                &shaders,
+                view,
+                compute_state,
+                gpu_state.surface_format,
+                tonemap_params,
+                config,
+            ),
+            None => RenderState::new_direct(
+                &gpu_state.device,
+                &shaders,
+                globals,
+                gpu_state.surface_format,
+            ),
+        };
+        if !config.quads.is_empty() {
+            render_state.add_quads(
+                &gpu_state.device,
+                &gpu_state.queue,
+                &shaders,
+                &config.quads,
+                |source| source_view(&graph, source),
+            );
+        }
+
+        let scene = Self {
+            graph,
+            render_state,
+            shaders,
+            id: NEXT_SCENE_ID.fetch_add(1, Ordering::Relaxed),
+        };
+        crash::set_scene(&scene.passes(), &config.shader_name(), &draw_src.text);
+        Ok(scene)
+    }
+
+    /// Different for every scene built, to tell when things made from one are outdated
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Another render pass presenting the node `source` of the graph, or `output` for the presented image,
+    /// on a surface of `surface_format`. `None` if there is no such node, as in [`DrawMode::Direct`]
+    pub fn window_render_state(
+        &self,
+        device: &wgpu::Device,
+        source: &str,
+        surface_format: wgpu::TextureFormat,
+        tonemap_params: TonemapParams,
+        config: &SceneConfig,
+    ) -> Option<RenderState> {
+        Some(RenderState::new(
+            device,
+            &self.shaders,
+            source_view(&self.graph, source)?,
+            self.compute_state(),
+            surface_format,
+            tonemap_params,
+            config,
+        ))
+    }
+
+    /// The image handed to the render pass, `None` in [`DrawMode::Direct`].
+    /// Mipmaps are a filtered copy of it, so recordings and hashes take the image they are made from
+    pub fn presented_texture(&self) -> Option<&wgpu::Texture> {
+        let presented = self.graph.names().filter(|&name| name != MIPMAPS).last()?;
+        self.graph.texture(presented)
+    }
+
+    pub fn drawing(&self) -> Option<&Drawing> {
+        self.graph.passes().find_map(|pass| match pass {
+            Pass::Drawing(drawing) => Some(drawing),
+            _ => None,
+        })
+    }
+
+    pub fn drawing_mut(&mut self) -> Option<&mut Drawing> {
+        self.graph.passes_mut().find_map(|pass| match pass {
+            Pass::Drawing(drawing) => Some(drawing),
+            _ => None,
+        })
+    }
+
+    /// The compute drawing, `None` for other kinds of drawings
+    pub fn compute_state(&self) -> Option<&ComputeState> {
+        match self.drawing() {
+            Some(Drawing::Compute(compute_state)) => Some(compute_state),
+            _ => None,
+        }
+    }
+
+    pub fn compute_state_mut(&mut self) -> Option<&mut ComputeState> {
+        match self.drawing_mut() {
+            Some(Drawing::Compute(compute_state)) => Some(compute_state),
+            _ => None,
+        }
+    }
+
+    pub fn accumulation_mut(&mut self) -> Option<&mut AccumulationState> {
+        self.graph.passes_mut().find_map(|pass| match pass {
+            Pass::Accumulation(accumulation) => Some(accumulation),
+            _ => None,
+        })
+    }
+
+    pub fn bloom(&self) -> Option<&BloomState> {
+        self.graph.passes().find_map(|pass| match pass {
+            Pass::Bloom(bloom) => Some(bloom),
+            _ => None,
+        })
+    }
+
+    /// Names of the passes making up a frame, in order
+    fn passes(&self) -> Vec<&str> {
+        let mut passes: Vec<&str> = self
+            .graph
+            .names()
+            .map(|name| match (name, self.drawing()) {
+                (DRAWING, Some(Drawing::Compute(_))) => "compute drawing",
+                (DRAWING, Some(Drawing::Fragment(_))) => "fragment drawing",
+                (DRAWING, Some(Drawing::ReactionDiffusion(_))) => "reaction-diffusion",
+                (DRAWING, Some(Drawing::Paint(_))) => "paint",
+                (name, _) => name,
+            })
+            .collect();
+        passes.push(match self.drawing() {
+            Some(_) => "tonemapped render",
+            None => "direct render",
+        });
+        passes
+    }
+
+    /// Draw the next image, returns false in [`DrawMode::Direct`] where there is nothing to encode
+    pub fn encode(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        globals: &GlobalsState,
+    ) -> bool {
+        if self.drawing().is_none() {
+            return false;
+        }
+        self.graph.encode(queue, encoder, globals);
+        true
+    }
+}
+
+/// Whatever produces the image that gets presented.
+/// Absent in [`DrawMode::Direct`], where the render pass draws by itself
+pub enum Drawing {
+    Compute(Box<ComputeState>),
+    Fragment(Box<FragmentDrawState>),
+    ReactionDiffusion(Box<ReactionDiffusionState>),
+    Paint(Box<PaintState>),
+}
+
+impl Drawing {
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        match self {
+            Drawing::Compute(compute_state) => compute_state
+                .output_view()
+                .expect("scenes draw into textures"),
+            Drawing::Fragment(fragment_state) => &fragment_state.output_view,
+            Drawing::ReactionDiffusion(simulation) => &simulation.output_view,
+            Drawing::Paint(paint) => &paint.output_view,
+        }
+    }
+
+    pub fn output_texture(&self) -> &wgpu::Texture {
+        match self {
+            Drawing::Compute(compute_state) => compute_state
+                .output_texture()
+                .expect("scenes draw into textures"),
+            Drawing::Fragment(fragment_state) => &fragment_state.output_texture,
+            Drawing::ReactionDiffusion(simulation) => &simulation.output_texture,
+            Drawing::Paint(paint) => &paint.output_texture,
+        }
+    }
+
+    pub fn encode(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        globals: &GlobalsState,
+    ) {
+        match self {
+            Drawing::Compute(compute_state) => compute_state.dispatch(encoder, globals),
+            Drawing::Fragment(fragment_state) => fragment_state.draw(encoder, globals),
+            Drawing::ReactionDiffusion(simulation) => simulation.dispatch(encoder),
+            Drawing::Paint(paint) => paint.dispatch(queue, encoder),
+        }
+    }
+}
+
+/// The view of the node `source` of `graph`, or of its last node for `output`
+fn source_view<'a>(graph: &'a RenderGraph, source: &str) -> Option<&'a wgpu::TextureView> {
+    match source {
+        "output" => graph.last().and_then(|last| graph.view(last)),
+        name => graph.view(name),
+    }
+}
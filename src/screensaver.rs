@@ -0,0 +1,265 @@
+use std::{env, fs, io, path::PathBuf};
+
+use clap::Parser;
+use winit::{
+    dpi::PhysicalPosition,
+    event::{ElementState, KeyEvent, WindowEvent},
+    window::{Fullscreen, Window, WindowAttributes},
+};
+
+use crate::cli::Options;
+
+/// Frame rate of `--screensaver`
+pub const FPS: u32 = 30;
+/// Frame rate of `--screensaver-preview`, which is only a few hundred pixels wide
+pub const PREVIEW_FPS: u32 = 15;
+/// Pixels the cursor may move before that ends the screensaver, as some mice jitter
+const CURSOR_TOLERANCE: f64 = 8.0;
+
+/// How Windows starts a screensaver, from the arguments of the `.scr` file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Invocation {
+    /// `/s`: run fullscreen until there is input
+    Run,
+    /// `/p HWND`: draw into the preview of the screensaver settings
+    Preview(isize),
+    /// `/c`, or no arguments: show the settings
+    Configure,
+}
+
+impl Invocation {
+    /// How this executable was started, if it is a screensaver, which on Windows are executables renamed to `.scr`
+    pub fn detect() -> Option<Result<Self, String>> {
+        let exe = env::current_exe().ok()?;
+        exe.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("scr"))
+            .then(|| Self::parse(env::args().skip(1)))
+    }
+
+    /// Parse the arguments after the executable, like `/s`, `/p 1234`, `/P:1234` or `-c`
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let Some(first) = args.next() else {
+            return Ok(Self::Configure);
+        };
+        let first = first.trim_start_matches(['/', '-']).to_ascii_lowercase();
+        let (flag, handle) = match first.split_once(':') {
+            Some((flag, handle)) => (flag, Some(handle.to_owned())),
+            None => (first.as_str(), None),
+        };
+        match flag {
+            "s" => Ok(Self::Run),
+            "p" => {
+                let handle = handle
+                    .or_else(|| args.next())
+                    .ok_or("Missing the window handle of the preview")?;
+                handle
+                    .parse()
+                    .map(Self::Preview)
+                    .map_err(|err| format!("Invalid window handle {handle:?}: {err}"))
+            }
+            "c" => Ok(Self::Configure),
+            _ => Err(format!("Unknown screensaver argument {first:?}")),
+        }
+    }
+
+    /// The options in the file at [`options_path`] with the flags for running this way.
+    /// Also changes the working directory to the one of the screensaver,
+    /// as Windows starts them in the system directory, so relative paths in the options are relative to it
+    pub fn options(self) -> Result<Options, String> {
+        let path = options_path()?;
+        if let Some(dir) = path.parent() {
+            env::set_current_dir(dir)
+                .map_err(|err| format!("Failed to enter {}: {err}", dir.display()))?;
+        }
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(format!("Failed to read {}: {err}", path.display())),
+        };
+
+        let mut args = vec!["screensaver".to_owned()];
+        args.extend(
+            text.lines()
+                .filter(|line| !line.trim_start().starts_with('#'))
+                .flat_map(str::split_whitespace)
+                .map(str::to_owned),
+        );
+        match self {
+            Self::Run => args.push("--screensaver".to_owned()),
+            Self::Preview(parent) => {
+                args.extend(["--screensaver-preview".to_owned(), parent.to_string()])
+            }
+            Self::Configure => {}
+        }
+        Options::try_parse_from(args).map_err(|err| format!("{}: {err}", path.display()))
+    }
+}
+
+/// The file with the options of the screensaver: next to it, with the extension `.args`
+pub fn options_path() -> Result<PathBuf, String> {
+    env::current_exe()
+        .map(|exe| exe.with_extension("args"))
+        .map_err(|err| format!("Failed to find the screensaver: {err}"))
+}
+
+/// What `/c` shows: where the options are, as there is no settings dialog
+pub fn configure() {
+    let message = match options_path() {
+        Ok(path) => format!(
+            "Edit {} to change what this screensaver draws.\n\
+             It takes the command line options, like --example plasma --bloom 0.5",
+            path.display()
+        ),
+        Err(err) => err,
+    };
+    show_message(&message);
+}
+
+/// Show `message` in a message box on Windows, where screensavers have no console, elsewhere print it
+pub fn show_message(message: &str) {
+    #[cfg(target_os = "windows")]
+    win32::message_box(message);
+    #[cfg(not(target_os = "windows"))]
+    println!("{message}");
+}
+
+/// Window settings for `--screensaver` and `--screensaver-preview`
+pub fn window_attributes(attributes: WindowAttributes, options: &Options) -> WindowAttributes {
+    if options.screensaver {
+        return attributes
+            .with_decorations(false)
+            .with_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
+    let Some(parent) = options.screensaver_preview else {
+        return attributes;
+    };
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::num::NonZeroIsize;
+        use winit::{
+            dpi::PhysicalSize,
+            raw_window_handle::{RawWindowHandle, Win32WindowHandle},
+        };
+        let Some(hwnd) = NonZeroIsize::new(parent) else {
+            log::warn!("The preview window handle is null, opening a normal window");
+            return attributes;
+        };
+        let [width, height] = win32::client_size(parent);
+        let handle = RawWindowHandle::Win32(Win32WindowHandle::new(hwnd));
+        // SAFETY: the settings dialog keeps its preview window alive while the preview runs
+        unsafe { attributes.with_parent_window(Some(handle)) }
+            .with_decorations(false)
+            .with_inner_size(PhysicalSize::new(width.max(1), height.max(1)))
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        log::warn!(
+            "Drawing into window {parent} is only supported on Windows, opening a normal window"
+        );
+        attributes
+    }
+}
+
+/// Settings of the created window that can't be made with its attributes
+pub fn set_up(window: &Window, options: &Options) {
+    if options.screensaver {
+        window.set_cursor_visible(false);
+    }
+}
+
+/// Ends `--screensaver` on input, and both screensaver modes when their window goes away
+pub struct Screensaver {
+    preview: bool,
+    cursor_start: Option<PhysicalPosition<f64>>,
+}
+
+impl Screensaver {
+    pub fn new(preview: bool) -> Self {
+        Self {
+            preview,
+            cursor_start: None,
+        }
+    }
+
+    /// Whether `event` ends the screensaver: pressing keys and buttons, touching, scrolling,
+    /// or moving the mouse a few pixels. Previews only end when their window closes
+    pub fn ends_on(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CloseRequested | WindowEvent::Destroyed => true,
+            _ if self.preview => false,
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            }
+            | WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                ..
+            }
+            | WindowEvent::MouseWheel { .. }
+            | WindowEvent::Touch(_) => true,
+            // The first position is where the cursor was when the screensaver started
+            WindowEvent::CursorMoved { position, .. } => {
+                let start = *self.cursor_start.get_or_insert(*position);
+                (position.x - start.x).hypot(position.y - start.y) > CURSOR_TOLERANCE
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    type Hwnd = isize;
+
+    const MB_ICONINFORMATION: u32 = 0x40;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Rect {
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    }
+
+    #[link(name = "user32")]
+    unsafe extern "system" {
+        fn MessageBoxW(window: Hwnd, text: *const u16, caption: *const u16, kind: u32) -> i32;
+        fn GetClientRect(window: Hwnd, rect: *mut Rect) -> i32;
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain([0]).collect()
+    }
+
+    pub fn message_box(message: &str) {
+        let caption = wide(crate::app::TITLE);
+        // SAFETY: both strings are NUL-terminated and outlive the call, no owner window is passed
+        unsafe {
+            MessageBoxW(
+                0,
+                wide(message).as_ptr(),
+                caption.as_ptr(),
+                MB_ICONINFORMATION,
+            );
+        }
+    }
+
+    /// Width and height of the inside of `window` in pixels
+    pub fn client_size(window: Hwnd) -> [u32; 2] {
+        let mut rect = Rect::default();
+        // SAFETY: `rect` is a valid `RECT` for the call to fill, an invalid `window` only makes it fail
+        if unsafe { GetClientRect(window, &mut rect) } == 0 {
+            return [0; 2];
+        }
+        [
+            (rect.right - rect.left).max(0) as u32,
+            (rect.bottom - rect.top).max(0) as u32,
+        ]
+    }
+}
@@ -0,0 +1,479 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{
+    adapter::AdapterSelection,
+    cli::Options,
+    scan::{GpuScan, ScanKind},
+    sort::GpuSort,
+};
+
+/// Side length of the textures written by the checks.
+/// 64 texels of 4 or 16 bytes keep rows aligned for copies
+const SIZE: u32 = 64;
+
+/// Result of a single check
+enum Outcome {
+    Pass,
+    Fail(String),
+    Skipped(&'static str),
+}
+
+struct Check {
+    name: &'static str,
+    run: fn(&TestDevice) -> Outcome,
+}
+
+const CHECKS: &[Check] = &[
+    Check {
+        name: "storage texture write",
+        run: storage_texture_write,
+    },
+    Check {
+        name: "buffer atomics",
+        run: buffer_atomics,
+    },
+    Check {
+        name: "float texture",
+        run: float_texture,
+    },
+    Check {
+        name: "readback integrity",
+        run: readback_integrity,
+    },
+    Check {
+        name: "gpu sort",
+        run: gpu_sort,
+    },
+    Check {
+        name: "gpu scan",
+        run: gpu_scan,
+    },
+];
+
+/// Run small known-good compute and readback operations on the selected adapter
+/// and print which of them work. Returns whether all of them passed
+pub fn run(options: &Options) -> bool {
+    let Some(device) = pollster::block_on(TestDevice::new(options)) else {
+        println!("No adapter found, see --list-adapters");
+        return false;
+    };
+
+    let info = device.adapter.get_info();
+    println!("{} ({:?}, {})", info.name, info.backend, info.driver);
+
+    let mut passed = true;
+    for check in CHECKS {
+        device.device.push_error_scope(ErrorFilter::Validation);
+        let outcome = (check.run)(&device);
+        let outcome = match pollster::block_on(device.device.pop_error_scope()) {
+            Some(error) => Outcome::Fail(error.to_string()),
+            None => outcome,
+        };
+
+        match outcome {
+            Outcome::Pass => println!("{:<24} pass", check.name),
+            Outcome::Fail(reason) => {
+                passed = false;
+                println!("{:<24} FAIL  {reason}", check.name);
+            }
+            Outcome::Skipped(reason) => println!("{:<24} skip  {reason}", check.name),
+        }
+    }
+
+    passed
+}
+
+struct TestDevice {
+    adapter: Adapter,
+    device: Device,
+    queue: Queue,
+    module: ShaderModule,
+    compute_supported: bool,
+}
+
+impl TestDevice {
+    async fn new(options: &Options) -> Option<Self> {
+        let adapter_selection = AdapterSelection::new(options);
+        let instance = adapter_selection.create_instance();
+        let adapter = adapter_selection.select(&instance, None).await?;
+
+        let compute_supported = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::COMPUTE_SHADERS);
+        let required_limits = if compute_supported {
+            Limits::downlevel_defaults()
+        } else {
+            Limits::downlevel_webgl2_defaults()
+        };
+
+        let (device, queue) = adapter
+            .request_device(
+                &DeviceDescriptor {
+                    required_limits: required_limits.using_resolution(adapter.limits()),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .ok()?;
+
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Self-Test Shader"),
+            source: ShaderSource::Wgsl(include_str!("./shaders/self_test.wgsl").into()),
+        });
+
+        Some(Self {
+            adapter,
+            device,
+            queue,
+            module,
+            compute_supported,
+        })
+    }
+
+    /// Run `entry_point` once per texel of a `SIZE`² grid, with `resource` bound to `binding`
+    fn dispatch(&self, entry_point: &str, binding: u32, resource: BindingResource) {
+        let pipeline = self
+            .device
+            .create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some("Self-Test Pipeline"),
+                layout: None,
+                module: &self.module,
+                entry_point,
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Self-Test Bind Group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[BindGroupEntry { binding, resource }],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor::default());
+            compute_pass.set_pipeline(&pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(SIZE / 8, SIZE / 8, 1);
+        }
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    fn create_storage_texture(&self, format: TextureFormat) -> Texture {
+        self.device.create_texture(&TextureDescriptor {
+            label: Some("Self-Test Texture"),
+            size: Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    fn read_texture(&self, texture: &Texture, bytes_per_texel: u32) -> Vec<u8> {
+        let bytes_per_row = SIZE * bytes_per_texel;
+        let buffer = self.create_readback_buffer((bytes_per_row * SIZE) as BufferAddress);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        self.read_buffer(&buffer)
+    }
+
+    fn create_readback_buffer(&self, size: BufferAddress) -> Buffer {
+        self.device.create_buffer(&BufferDescriptor {
+            label: Some("Self-Test Readback Buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Wait for the GPU and map `buffer`, empty if mapping fails
+    fn read_buffer(&self, buffer: &Buffer) -> Vec<u8> {
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(Maintain::Wait);
+
+        match receiver.recv() {
+            Ok(Ok(())) => {
+                let data = slice.get_mapped_range().to_vec();
+                buffer.unmap();
+                data
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn storage_texture_write(device: &TestDevice) -> Outcome {
+    if !device.compute_supported {
+        return Outcome::Skipped("no compute shaders");
+    }
+    // Guaranteed by WebGPU, but not by every GLES driver of mobile GPUs
+    let format_usages = device
+        .adapter
+        .get_texture_format_features(TextureFormat::Rgba8Unorm)
+        .allowed_usages;
+    if !format_usages.contains(TextureUsages::STORAGE_BINDING) {
+        return Outcome::Skipped("no Rgba8Unorm storage textures");
+    }
+
+    let texture = device.create_storage_texture(TextureFormat::Rgba8Unorm);
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    device.dispatch("write_unorm", 0, BindingResource::TextureView(&view));
+
+    let texels = device.read_texture(&texture, 4);
+    let expected = (0..SIZE).flat_map(|y| (0..SIZE).flat_map(move |x| [x, y, (x + y) % 256, 255]));
+    compare(texels.iter().map(|&byte| byte as u32), expected)
+}
+
+fn float_texture(device: &TestDevice) -> Outcome {
+    if !device.compute_supported {
+        return Outcome::Skipped("no compute shaders");
+    }
+    let format_usages = device
+        .adapter
+        .get_texture_format_features(TextureFormat::Rgba32Float)
+        .allowed_usages;
+    if !format_usages.contains(TextureUsages::STORAGE_BINDING) {
+        return Outcome::Skipped("no Rgba32Float storage textures");
+    }
+
+    let texture = device.create_storage_texture(TextureFormat::Rgba32Float);
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    device.dispatch("write_float", 1, BindingResource::TextureView(&view));
+
+    let texels = device.read_texture(&texture, 16);
+    let expected = (0..SIZE).flat_map(|y| {
+        (0..SIZE).flat_map(move |x| [x as f32 + 0.25, y as f32 * 1000.5, -(x as f32), 1e-3])
+    });
+    compare(
+        texels
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())),
+        expected,
+    )
+}
+
+fn buffer_atomics(device: &TestDevice) -> Outcome {
+    if !device.compute_supported {
+        return Outcome::Skipped("no compute shaders");
+    }
+
+    let counter = device.device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Self-Test Counter"),
+        contents: &0u32.to_le_bytes(),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+    });
+    device.dispatch("count", 2, counter.as_entire_binding());
+
+    let readback = device.create_readback_buffer(4);
+    let mut encoder = device
+        .device
+        .create_command_encoder(&CommandEncoderDescriptor::default());
+    encoder.copy_buffer_to_buffer(&counter, 0, &readback, 0, 4);
+    device.queue.submit(Some(encoder.finish()));
+
+    let count = device.read_buffer(&readback);
+    compare(
+        count
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap())),
+        [SIZE * SIZE],
+    )
+}
+
+/// Upload a pattern, copy it between buffers and read it back
+fn readback_integrity(device: &TestDevice) -> Outcome {
+    let pattern: Vec<u8> = (0..SIZE * SIZE * 4)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+
+    let source = device.device.create_buffer(&BufferDescriptor {
+        label: Some("Self-Test Upload Buffer"),
+        size: pattern.len() as BufferAddress,
+        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    device.queue.write_buffer(&source, 0, &pattern);
+
+    let readback = device.create_readback_buffer(pattern.len() as BufferAddress);
+    let mut encoder = device
+        .device
+        .create_command_encoder(&CommandEncoderDescriptor::default());
+    encoder.copy_buffer_to_buffer(&source, 0, &readback, 0, pattern.len() as BufferAddress);
+    device.queue.submit(Some(encoder.finish()));
+
+    compare(device.read_buffer(&readback), pattern)
+}
+
+/// Sort keys with duplicates, alone and with their indices as values, against sorting on the CPU.
+/// The length isn't a power of two, so the keys past it must stay where they are
+fn gpu_sort(device: &TestDevice) -> Outcome {
+    if !device.compute_supported {
+        return Outcome::Skipped("no compute shaders");
+    }
+
+    let len = SIZE * SIZE - 37;
+    let keys: Vec<u32> = (0..SIZE * SIZE)
+        .map(|i| i.wrapping_mul(2_654_435_761) % 1000)
+        .collect();
+    let create_buffer = |contents: &[u32]| {
+        device.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Self-Test Sort Buffer"),
+            contents: bytemuck::cast_slice(contents),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        })
+    };
+    let key_buffer = create_buffer(&keys);
+    let value_buffer = create_buffer(&(0..SIZE * SIZE).collect::<Vec<_>>());
+    let lone_key_buffer = create_buffer(&keys);
+
+    let size = BufferAddress::from(SIZE * SIZE * 4);
+    let key_readback = device.create_readback_buffer(size);
+    let value_readback = device.create_readback_buffer(size);
+    let lone_key_readback = device.create_readback_buffer(size);
+    let mut encoder = device
+        .device
+        .create_command_encoder(&CommandEncoderDescriptor::default());
+    let sort = GpuSort::new(&device.device);
+    sort.sort_keys(&device.device, &mut encoder, &lone_key_buffer, len);
+    sort.sort_pairs(
+        &device.device,
+        &mut encoder,
+        &key_buffer,
+        &value_buffer,
+        len,
+    );
+    encoder.copy_buffer_to_buffer(&key_buffer, 0, &key_readback, 0, size);
+    encoder.copy_buffer_to_buffer(&value_buffer, 0, &value_readback, 0, size);
+    encoder.copy_buffer_to_buffer(&lone_key_buffer, 0, &lone_key_readback, 0, size);
+    device.queue.submit(Some(encoder.finish()));
+
+    let read_u32s = |buffer| -> Vec<u32> {
+        device
+            .read_buffer(buffer)
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    };
+    let sorted_keys = read_u32s(&key_readback);
+    let values = read_u32s(&value_readback);
+    let mut expected = keys[..len as usize].to_vec();
+    expected.sort_unstable();
+    expected.extend_from_slice(&keys[len as usize..]);
+    if let Outcome::Fail(reason) = compare(read_u32s(&lone_key_readback), expected.clone()) {
+        return Outcome::Fail(format!("lone key {reason}"));
+    }
+    if let Outcome::Fail(reason) = compare(sorted_keys.iter().copied(), expected) {
+        return Outcome::Fail(format!("key {reason}"));
+    }
+    // Every value still belongs to its key, wherever equal keys ended up
+    compare(
+        values
+            .iter()
+            .map(|&value| keys.get(value as usize).copied()),
+        sorted_keys.into_iter().map(Some),
+    )
+}
+
+/// Inclusive and exclusive prefix sums against summing on the CPU.
+/// Enough values for three levels of block sums, and a length that isn't a multiple of a block
+fn gpu_scan(device: &TestDevice) -> Outcome {
+    if !device.compute_supported {
+        return Outcome::Skipped("no compute shaders");
+    }
+
+    let len: u32 = 70_000;
+    let values: Vec<u32> = (0..len)
+        .map(|i| i.wrapping_mul(2_654_435_761) % 1000)
+        .collect();
+    let size = BufferAddress::from(len * 4);
+    let scan = GpuScan::new(&device.device);
+    let mut encoder = device
+        .device
+        .create_command_encoder(&CommandEncoderDescriptor::default());
+    let readbacks = [ScanKind::Inclusive, ScanKind::Exclusive].map(|kind| {
+        let buffer = device.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Self-Test Scan Buffer"),
+            contents: bytemuck::cast_slice(&values),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        });
+        scan.scan(&device.device, &mut encoder, &buffer, len, kind);
+        let readback = device.create_readback_buffer(size);
+        encoder.copy_buffer_to_buffer(&buffer, 0, &readback, 0, size);
+        readback
+    });
+    device.queue.submit(Some(encoder.finish()));
+
+    let mut sum = 0;
+    let inclusive: Vec<u32> = values
+        .iter()
+        .map(|value| {
+            sum += value;
+            sum
+        })
+        .collect();
+    let exclusive: Vec<u32> = values
+        .iter()
+        .zip(&inclusive)
+        .map(|(value, sum)| sum - value)
+        .collect();
+    for (readback, expected) in readbacks.iter().zip([inclusive, exclusive]) {
+        let sums = device.read_buffer(readback);
+        let sums = sums
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+        if let Outcome::Fail(reason) = compare(sums, expected) {
+            return Outcome::Fail(reason);
+        }
+    }
+    Outcome::Pass
+}
+
+/// Pass if both sequences are equal, otherwise describe the first difference
+fn compare<T: PartialEq + std::fmt::Debug>(
+    actual: impl IntoIterator<Item = T>,
+    expected: impl IntoIterator<Item = T>,
+) -> Outcome {
+    let mut actual = actual.into_iter();
+    for (index, expected) in expected.into_iter().enumerate() {
+        match actual.next() {
+            Some(value) if value == expected => {}
+            Some(value) => {
+                return Outcome::Fail(format!("value {index} is {value:?}, expected {expected:?}"));
+            }
+            None => return Outcome::Fail(format!("read back only {index} values")),
+        }
+    }
+    Outcome::Pass
+}
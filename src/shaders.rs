@@ -1,27 +1,623 @@
-use wgpu::{Device, ShaderModule};
+use std::{rc::Rc, sync::Arc};
+
+use wgpu::{Device, PipelineCache, ShaderModule, TextureFormat};
+
+use crate::{
+    cli::Preset,
+    compute::{MAX_LAUNCHES, OUTPUT_FORMAT, OutputStorage, VolumeView},
+    globals,
+    gpu::{self, DrawMode},
+    preprocessor::{self, Preprocessed, ShaderSource},
+    sandbox,
+    scene::SceneConfig,
+};
 
 pub struct Shaders {
-    pub compute: ShaderModule,
+    /// Drawing shader, with either compute or fragment entry points depending on the [`DrawMode`],
+    /// shared with the [`ComputeState`](crate::compute::ComputeState) creating pipelines from it later on
+    pub drawing: Rc<ShaderModule>,
+    /// `@compute` entry points of `drawing`, the built-in `main` first, see [`Shaders::compute_entry_points`]
+    pub entry_points: Vec<String>,
+    /// Whether the compute drawing shader defines `fn predicate() -> bool`, deciding on the GPU
+    /// whether the drawing is dispatched, see `src/shaders/predicate.wgsl`
+    pub predicate: bool,
+    /// Whether the compute drawing shader defines `fn classify(tile: vec2<u32>) -> bool`,
+    /// restricting `draw` to the tiles it accepts, see `src/shaders/tiles.wgsl`
+    pub tile_classification: bool,
+    /// Whether the compute drawing shader defines `fn launch(item: u32)`, dispatched indirectly
+    /// once per item `draw` emitted, see `src/shaders/launch.wgsl`
+    pub launch: bool,
+    /// Whether the compute drawing shader defines `fn overlay(pixel: vec2<u32>) -> vec4<f32>`,
+    /// drawing affordances shown on screen only, see `src/shaders/overlay.wgsl`
+    pub overlay: bool,
+    /// Where the compute drawing writes its image, see [`SceneConfig::output_storage`]
+    pub output_storage: OutputStorage,
+    /// Passed to every pipeline created from these shaders, see [`SceneConfig::pipeline_cache`]
+    pub pipeline_cache: Option<Arc<PipelineCache>>,
     pub render: ShaderModule,
+    /// Only created for [`Preset::ReactionDiffusion`]
+    pub reaction_diffusion: Option<ShaderModule>,
+    /// Only created for [`Preset::Paint`]
+    pub paint: Option<ShaderModule>,
+    /// Finds the tiles a stroke changed, only created for [`Preset::Paint`]
+    pub undo: Option<ShaderModule>,
+    /// Only created when accumulating frames
+    pub accumulate: Option<ShaderModule>,
+    /// Only created when supersampling
+    pub downsample: Option<ShaderModule>,
+    pub mipmap: Option<ShaderModule>,
+    pub bloom: Option<ShaderModule>,
+    pub convolution: Option<ShaderModule>,
+    /// `@workgroup_size` of every compute entry point, see [`Shaders::compute_prelude`]
+    pub workgroup_size: [u32; 2],
+    /// Size in bytes of the `scratch` buffer the compute drawing declares
+    pub scratch_size: Option<u64>,
+    /// Whether the compute drawing uses the `feedback` buffer, which is then bound after `scratch`
+    pub feedback: bool,
+    /// Number of previous frames the compute drawing reads, bound after `feedback`
+    pub history: Option<u32>,
+    /// Edge length of the cube of voxels filled by `fn volume(voxel: vec3<u32>) -> vec4<f32>`
+    /// if the compute drawing shader defines it, bound after `history`, see `src/shaders/volume.wgsl`
+    pub volume: Option<u32>,
+    /// Edge length of the faces drawn by `fn cubemap(direction: vec3<f32>) -> vec4<f32>`
+    /// if the compute drawing shader defines it, bound after the volume, see `src/shaders/cubemap.wgsl`
+    pub cubemap: Option<u32>,
+}
+
+/// The drawing shader put together from the `draw` function and the built-in entry points,
+/// not yet compiled
+pub struct DrawingSource {
+    pub source: Preprocessed,
+    pub label: &'static str,
+    /// See [`Shaders::entry_points`]
+    pub entry_points: Vec<String>,
+    pub predicate: bool,
+    pub tile_classification: bool,
+    pub launch: bool,
+    pub overlay: bool,
+    pub scratch_size: Option<u64>,
+    pub feedback: bool,
+    pub history: Option<u32>,
+    pub volume: Option<u32>,
+    pub cubemap: Option<u32>,
+}
+
+impl DrawingSource {
+    /// `draw_src` provides the `draw` function evaluated for every pixel,
+    /// see [`Shaders::builtin_draw_source`].
+    /// It is [`preprocess`](preprocessor::preprocess)ed with `COMPUTE` or `FRAGMENT` defined depending on `draw_mode`.
+    /// `globals` are declared as push constants if `push_constants` are enabled on the device
+    pub fn new(
+        draw_mode: DrawMode,
+        push_constants: bool,
+        draw_src: &ShaderSource,
+        config: &SceneConfig,
+    ) -> Result<Self, String> {
+        let define = match draw_mode {
+            DrawMode::Compute => "COMPUTE",
+            DrawMode::Fragment | DrawMode::Direct => "FRAGMENT",
+        };
+        let max_define_value = config.sandboxed.then_some(sandbox::MAX_DEFINE_VALUE);
+        let draw_src = preprocessor::preprocess(draw_src, &[define], max_define_value)?;
+        let code = strip_comments(&draw_src.text);
+        let compute = draw_mode == DrawMode::Compute;
+        let predicate = compute && declares_function(&code, "predicate");
+        let tile_classification = compute && declares_function(&code, "classify");
+        if tile_classification && !declares_function(&code, "fill") {
+            return Err("`classify` needs a `fill` function for the tiles it rejects".to_owned());
+        }
+        if tile_classification && predicate {
+            return Err("`predicate` and `classify` can't be combined".to_owned());
+        }
+        let launch = compute && declares_function(&code, "launch");
+        let overlay = compute && declares_function(&code, "overlay");
+        let scratch_size = config.scratch_size.filter(|_| compute);
+        let feedback = compute && mentions(&code, "feedback");
+        let history = config.history.filter(|_| compute);
+        let volume = (compute && declares_function(&code, "volume")).then_some(config.volume_size);
+        let cubemap =
+            (compute && declares_function(&code, "cubemap")).then_some(config.cubemap_size);
+        if config.output_storage != OutputStorage::Float
+            && (overlay || volume.is_some() || cubemap.is_some())
+        {
+            return Err(format!(
+                "`overlay`, `volume` and `cubemap` need {OUTPUT_FORMAT:?} storage textures, which the adapter can't write"
+            ));
+        }
+        let draw = declares_function(&code, "draw");
+        // Shaders only filling a volume or cubemap are shown with the built-in views
+        let volume_view = (volume.is_some() && cubemap.is_none() && !draw).then(|| {
+            format!(
+                "const VOLUME_SLICE: bool = {};\n{}",
+                config.volume_view == VolumeView::Slice,
+                include_str!("./shaders/volume_view.wgsl")
+            )
+        });
+        let (label, source) = match draw_mode {
+            DrawMode::Compute => {
+                let globals = Shaders::compute_globals(
+                    push_constants,
+                    config,
+                    feedback,
+                    launch,
+                    history,
+                    volume,
+                    cubemap,
+                );
+                let mut extensions = Vec::new();
+                if predicate {
+                    extensions.push(("predicate.wgsl", include_str!("./shaders/predicate.wgsl")));
+                } else if tile_classification {
+                    extensions.push(("tiles.wgsl", include_str!("./shaders/tiles.wgsl")));
+                }
+                if launch {
+                    extensions.push(("launch.wgsl", include_str!("./shaders/launch.wgsl")));
+                }
+                if overlay {
+                    extensions.push(("overlay.wgsl", include_str!("./shaders/overlay.wgsl")));
+                }
+                if volume.is_some() {
+                    extensions.push(("volume.wgsl", include_str!("./shaders/volume.wgsl")));
+                }
+                if let Some(volume_view) = &volume_view {
+                    extensions.push(("volume_view.wgsl", volume_view));
+                }
+                if cubemap.is_some() {
+                    extensions.push(("cubemap.wgsl", include_str!("./shaders/cubemap.wgsl")));
+                    if !draw {
+                        extensions.push((
+                            "cubemap_view.wgsl",
+                            include_str!("./shaders/cubemap_view.wgsl"),
+                        ));
+                    }
+                }
+                let prelude = Shaders::compute_prelude(config);
+                (
+                    "Compute Shader",
+                    Self::compute_source(
+                        &prelude,
+                        &globals,
+                        config.output_storage,
+                        &draw_src,
+                        &extensions,
+                    )?,
+                )
+            }
+            DrawMode::Fragment | DrawMode::Direct => {
+                let globals = Shaders::globals_declaration(push_constants, 0, config);
+                (
+                    "Fragment Drawing Shader",
+                    Self::fragment_source(&globals, &draw_src),
+                )
+            }
+        };
+        let mut entry_points = vec!["main".to_owned()];
+        if draw_mode == DrawMode::Compute {
+            entry_points.extend(Shaders::compute_entry_points(&draw_src.text));
+        }
+
+        Ok(Self {
+            source,
+            label,
+            entry_points,
+            predicate,
+            tile_classification,
+            launch,
+            overlay,
+            scratch_size,
+            feedback,
+            history,
+            volume,
+            cubemap,
+        })
+    }
+
+    /// A kernel for [`ComputeStateBuilder::output_buffer`](crate::compute::ComputeStateBuilder::output_buffer):
+    /// `src` is [`preprocess`](preprocessor::preprocess)ed with `KERNEL` defined and brings its own `@compute` entry points,
+    /// the first of which is dispatched. It declares what it writes as `@group(0) @binding(0) var<storage, read_write>`,
+    /// the storage buffers, `scratch` and `feedback` follow as for drawing shaders
+    pub fn kernel(
+        push_constants: bool,
+        src: &ShaderSource,
+        config: &SceneConfig,
+    ) -> Result<Self, String> {
+        let max_define_value = config.sandboxed.then_some(sandbox::MAX_DEFINE_VALUE);
+        let src = preprocessor::preprocess(src, &["KERNEL"], max_define_value)?;
+        let entry_points = Shaders::compute_entry_points(&src.text);
+        if entry_points.is_empty() {
+            return Err("Kernels need a `@compute` entry point".to_owned());
+        }
+        let feedback = mentions(&strip_comments(&src.text), "feedback");
+        let globals =
+            Shaders::compute_globals(push_constants, config, feedback, false, None, None, None);
+        let mut source = Preprocessed::default();
+        source.push_inline("prelude", &Shaders::compute_prelude(config));
+        source.push_inline("globals", &globals);
+        source.push(&src);
+
+        Ok(Self {
+            source,
+            label: "Kernel Shader",
+            entry_points,
+            predicate: false,
+            tile_classification: false,
+            launch: false,
+            overlay: false,
+            scratch_size: config.scratch_size,
+            feedback,
+            history: None,
+            volume: None,
+            cubemap: None,
+        })
+    }
+
+    /// `extensions` are appended after the built-in entry point, as names for errors and sources.
+    /// `out_image` is declared for `output_storage`, where colors are converted before storing them
+    /// `textureStore(out_image, ...)` calls `store_out_image` instead, see [`store_out_image_calls`]
+    fn compute_source(
+        prelude: &str,
+        globals: &str,
+        output_storage: OutputStorage,
+        draw_src: &Preprocessed,
+        extensions: &[(&str, &str)],
+    ) -> Result<Preprocessed, String> {
+        let mut body = Preprocessed::default();
+        body.push(draw_src);
+        body.push_inline(
+            "drawing_compute.wgsl",
+            include_str!("./shaders/drawing_compute.wgsl"),
+        );
+        for (name, extension) in extensions {
+            body.push_inline(name, extension);
+        }
+        if output_storage != OutputStorage::Float {
+            body.text = store_out_image_calls(&body, output_storage)?;
+        }
+        let mut shader_src = Preprocessed::default();
+        shader_src.push_inline("prelude", prelude);
+        shader_src.push_inline("globals", globals);
+        shader_src.push_inline("out_image", &output_storage.wgsl_declaration());
+        shader_src.push(&body);
+        Ok(shader_src)
+    }
+
+    fn fragment_source(globals: &str, draw_src: &Preprocessed) -> Preprocessed {
+        let mut shader_src = Preprocessed::default();
+        shader_src.push_inline("globals", globals);
+        shader_src.push(draw_src);
+        shader_src.push_inline(
+            "drawing_fragment.wgsl",
+            include_str!("./shaders/drawing_fragment.wgsl"),
+        );
+        shader_src
+    }
 }
 
 impl Shaders {
-    pub fn new(device: &Device) -> Self {
-        let compute = Self::create_compute_shader(device);
-        let render = Self::create_render_shader(device);
+    /// Compile the [`DrawingSource`] of `draw_src` along with the built-in passes the `config` needs.
+    /// Fails if it doesn't preprocess or compile, with errors pointing at the lines of the files they are in
+    pub fn new(
+        device: &Device,
+        draw_mode: DrawMode,
+        draw_src: &ShaderSource,
+        config: &SceneConfig,
+    ) -> Result<Self, String> {
+        let drawing = DrawingSource::new(
+            draw_mode,
+            globals::push_constants_enabled(device),
+            draw_src,
+            config,
+        )?;
+        Self::compile(device, drawing, config)
+    }
 
-        Self { compute, render }
+    /// Compile the [`DrawingSource::kernel`] of `src`, the passes drawing images aren't created
+    pub fn kernel(
+        device: &Device,
+        src: &ShaderSource,
+        config: &SceneConfig,
+    ) -> Result<Self, String> {
+        let kernel = DrawingSource::kernel(globals::push_constants_enabled(device), src, config)?;
+        let config = SceneConfig {
+            preset: Preset::Drawing,
+            accumulate: false,
+            supersample: 1,
+            mipmaps: false,
+            bloom: None,
+            convolution: None,
+            ..config.clone()
+        };
+        Self::compile(device, kernel, &config)
     }
 
-    fn create_compute_shader(device: &Device) -> ShaderModule {
-        let shader_src = include_str!("./shaders/drawing.wgsl");
+    fn compile(
+        device: &Device,
+        drawing: DrawingSource,
+        config: &SceneConfig,
+    ) -> Result<Self, String> {
+        let prelude = Self::compute_prelude(config);
+        let DrawingSource {
+            source,
+            label,
+            entry_points,
+            predicate,
+            tile_classification,
+            launch,
+            overlay,
+            scratch_size,
+            feedback,
+            history,
+            volume,
+            cubemap,
+        } = drawing;
+        let drawing = Self::create_drawing_shader(device, label, &source)?;
+        let render = Self::create_render_shader(device);
+        let reaction_diffusion = (config.preset == Preset::ReactionDiffusion)
+            .then(|| Self::create_reaction_diffusion_shader(device, &prelude, config.state_format));
+        let paint =
+            (config.preset == Preset::Paint).then(|| Self::create_paint_shader(device, &prelude));
+        let undo = (config.preset == Preset::Paint).then(|| Self::create_undo_shader(device));
+        let accumulate = config
+            .accumulate
+            .then(|| Self::create_accumulate_shader(device, &prelude));
+        let downsample = (config.supersample > 1)
+            .then(|| Self::create_downsample_shader(device, &prelude, config.supersample));
+        let mipmap = config
+            .mipmaps
+            .then(|| Self::create_mipmap_shader(device, &prelude));
+        let bloom = config
+            .bloom
+            .is_some()
+            .then(|| Self::create_bloom_shader(device, &prelude));
+        let convolution = config
+            .convolution
+            .is_some()
+            .then(|| Self::create_convolution_shader(device, &prelude));
 
-        device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Compute Shader"),
-            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        Ok(Self {
+            drawing: Rc::new(drawing),
+            entry_points,
+            predicate,
+            tile_classification,
+            launch,
+            overlay,
+            output_storage: config.output_storage,
+            pipeline_cache: config.pipeline_cache.clone(),
+            render,
+            reaction_diffusion,
+            paint,
+            undo,
+            accumulate,
+            downsample,
+            mipmap,
+            bloom,
+            convolution,
+            workgroup_size: config.workgroup_size,
+            scratch_size,
+            feedback,
+            history,
+            volume,
+            cubemap,
         })
     }
 
+    /// The `draw` function shipped with a preset
+    pub fn builtin_draw_source(preset: Preset) -> ShaderSource {
+        match preset {
+            Preset::PathTracer => ShaderSource::builtin(
+                "path_tracer.wgsl",
+                include_str!("./shaders/path_tracer.wgsl"),
+            ),
+            Preset::Sdf => ShaderSource::builtin("sdf.wgsl", include_str!("./shaders/sdf.wgsl")),
+            Preset::Fractal => {
+                ShaderSource::builtin("fractal.wgsl", include_str!("./shaders/fractal.wgsl"))
+            }
+            Preset::Drawing | Preset::ReactionDiffusion | Preset::Paint => {
+                ShaderSource::builtin("drawing.wgsl", include_str!("./shaders/drawing.wgsl"))
+            }
+        }
+    }
+
+    /// Names of the functions marked `@compute` in `src`, besides `draw` it may define
+    /// further entry points writing to `out_image`, e.g. variants of an algorithm to compare
+    fn compute_entry_points(src: &str) -> Vec<String> {
+        strip_comments(src)
+            .split("@compute")
+            .skip(1)
+            .filter_map(|attributes| {
+                let (_, signature) = attributes.split_once("fn")?;
+                let name: String = signature
+                    .trim_start()
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_')
+                    .collect();
+                (!name.is_empty()).then_some(name)
+            })
+            .collect()
+    }
+
+    /// Constants prepended to every compute shader.
+    /// Dispatches round up to whole workgroups, so entry points skip invocations
+    /// outside of `IMAGE_WIDTH` × `IMAGE_HEIGHT`
+    fn compute_prelude(config: &SceneConfig) -> String {
+        let [x, y] = config.workgroup_size;
+        format!(
+            "const WORKGROUP_SIZE_X: u32 = {x}u;\n\
+             const WORKGROUP_SIZE_Y: u32 = {y}u;\n\
+             const IMAGE_WIDTH: u32 = {}u;\n\
+             const IMAGE_HEIGHT: u32 = {}u;\n",
+            config.width, config.height
+        )
+    }
+
+    /// Declares `globals` as push constants, or as uniform buffer in bind group `group`,
+    /// and the `audio` texture, `camera`, `blue_noise` texture, `fractal_view`, `touches` and `gamepads` next to it, matching [`GlobalsState`](crate::globals::GlobalsState),
+    /// followed by the hashes and noise of `src/shaders/noise.wgsl`.
+    /// `IMAGE_OFFSET` is the pixel of the whole image the drawn part starts at, see [`SceneConfig::image_offset`],
+    /// and `image_offset()` adds the offset of the tile drawn by [`tiled`](crate::tiled) renders
+    fn globals_declaration(push_constants: bool, group: u32, config: &SceneConfig) -> String {
+        let declaration = if push_constants {
+            "var<push_constant> globals: Globals;".to_owned()
+        } else {
+            format!("@group({group}) @binding(0)\nvar<uniform> globals: Globals;")
+        };
+        let audio = format!("@group({group}) @binding(1)\nvar audio: texture_2d<f32>;");
+        let camera = format!("@group({group}) @binding(2)\nvar<uniform> camera: Camera;");
+        let blue_noise = format!("@group({group}) @binding(3)\nvar blue_noise: texture_2d<f32>;");
+        let fractal_view =
+            format!("@group({group}) @binding(4)\nvar<uniform> fractal_view: FractalView;");
+        let touches = format!("@group({group}) @binding(5)\nvar<uniform> touches: Touches;");
+        let gamepads = format!(
+            "@group({group}) @binding(6)\nvar<uniform> gamepads: array<Gamepad, {}>;",
+            globals::MAX_GAMEPADS
+        );
+        let [x, y] = config.image_offset();
+        let offset = format!(
+            "const IMAGE_OFFSET: vec2<u32> = vec2<u32>({x}u, {y}u);\n\
+             fn image_offset() -> vec2<u32> {{\n    return IMAGE_OFFSET + globals.offset;\n}}"
+        );
+        [
+            include_str!("./shaders/globals.wgsl"),
+            &declaration,
+            &audio,
+            &camera,
+            &blue_noise,
+            &fractal_view,
+            &touches,
+            &gamepads,
+            &offset,
+            include_str!("./shaders/noise.wgsl"),
+        ]
+        .join("\n")
+    }
+
+    /// Globals of compute shaders, followed by the `scratch`, `feedback` and `launches` buffers after the storage buffers,
+    /// the `history` of previous frames, and the `volume_texture` and `cubemap_texture` with their samplers
+    fn compute_globals(
+        push_constants: bool,
+        config: &SceneConfig,
+        feedback: bool,
+        launch: bool,
+        history: Option<u32>,
+        volume: Option<u32>,
+        cubemap: Option<u32>,
+    ) -> String {
+        let mut globals = Shaders::globals_declaration(push_constants, 1, config);
+        let mut binding = config.storage_buffers.len() + 1;
+        if let Some(size) = config.scratch_size {
+            globals += &Shaders::scratch_declaration(size, binding);
+            binding += 1;
+        }
+        if feedback {
+            globals += &format!(
+                "\n{}@group(0) @binding({binding})\n\
+                 var<storage, read_write> feedback: Feedback;\n",
+                include_str!("./shaders/feedback.wgsl")
+            );
+            binding += 1;
+        }
+        if launch {
+            globals += &format!(
+                "\n@group(0) @binding({binding})\n\
+                 var<storage, read_write> launches: Launches;\n\
+                 const MAX_LAUNCHES: u32 = {MAX_LAUNCHES}u;\n"
+            );
+            binding += 1;
+        }
+        if let Some(frames) = history {
+            globals += &format!(
+                "\n@group(0) @binding({binding})\n\
+                 var history_frames: texture_2d_array<f32>;\n\
+                 const HISTORY_LENGTH: u32 = {frames}u;\n{}",
+                include_str!("./shaders/history.wgsl")
+            );
+            binding += 1;
+        }
+        if let Some(size) = volume {
+            globals += &format!(
+                "\n@group(0) @binding({binding})\n\
+                 var volume_texture: texture_2d_array<f32>;\n\
+                 @group(0) @binding({})\n\
+                 var volume_sampler: sampler;\n\
+                 const VOLUME_SIZE: u32 = {size}u;\n",
+                binding + 1
+            );
+            binding += 2;
+        }
+        if let Some(size) = cubemap {
+            globals += &format!(
+                "\n@group(0) @binding({binding})\n\
+                 var cubemap_texture: texture_cube<f32>;\n\
+                 @group(0) @binding({})\n\
+                 var cubemap_sampler: sampler;\n\
+                 const CUBEMAP_SIZE: u32 = {size}u;\n",
+                binding + 1
+            );
+        }
+        globals
+    }
+
+    /// Declares the `scratch` buffer at `binding` of bind group 0, after the storage buffers,
+    /// and its length in `u32`s as `SCRATCH_LENGTH`. A constant rather than a uniform,
+    /// as changing the size means building new pipelines anyway. Compute drawings, kernels and post effects declare it
+    fn scratch_declaration(size: u64, binding: usize) -> String {
+        format!(
+            "\n@group(0) @binding({binding})\n\
+             var<storage, read_write> scratch: array<atomic<u32>>;\n\
+             const SCRATCH_LENGTH: u32 = {}u;\n",
+            size / 4
+        )
+    }
+
+    /// Compile the drawing shader, remapping the lines of errors to the files they are in
+    fn create_drawing_shader(
+        device: &Device,
+        label: &str,
+        shader_src: &Preprocessed,
+    ) -> Result<ShaderModule, String> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(shader_src.text.as_str().into()),
+        });
+        match gpu::pop_error_scope(device) {
+            Some(error) => Err(shader_src.remap_error(&error.to_string())),
+            None => Ok(module),
+        }
+    }
+
+    /// Compute shader of a [`PostEffect`](crate::post_effect::PostEffect) pass,
+    /// `source` provides its `effect` function and is preprocessed like drawing shaders.
+    /// With a `scratch_size`, the scratch buffer of the drawing is declared as for drawing shaders
+    pub fn create_post_effect_shader(
+        device: &Device,
+        source: &ShaderSource,
+        config: &SceneConfig,
+        scratch_size: Option<u64>,
+    ) -> Result<ShaderModule, String> {
+        let [x, y] = config.workgroup_size;
+        let prelude = format!(
+            "const WORKGROUP_SIZE_X: u32 = {x}u;\n\
+             const WORKGROUP_SIZE_Y: u32 = {y}u;\n"
+        );
+        let mut globals =
+            Self::globals_declaration(globals::push_constants_enabled(device), 1, config);
+        if let Some(size) = scratch_size {
+            // After the bindings of `src/shaders/post_effect.wgsl`
+            globals += &Self::scratch_declaration(size, 4);
+        }
+        let max_define_value = config.sandboxed.then_some(sandbox::MAX_DEFINE_VALUE);
+        let mut shader_src = Preprocessed::default();
+        shader_src.push_inline("prelude", &prelude);
+        shader_src.push_inline("globals", &globals);
+        shader_src.push(&preprocessor::preprocess(source, &[], max_define_value)?);
+        shader_src.push_inline(
+            "post_effect.wgsl",
+            include_str!("./shaders/post_effect.wgsl"),
+        );
+        Self::create_drawing_shader(device, "Post Effect Shader", &shader_src)
+    }
+
     fn create_render_shader(device: &Device) -> ShaderModule {
         let shader_src = include_str!("./shaders/render_shader.wgsl");
         device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -29,4 +625,280 @@ impl Shaders {
             source: wgpu::ShaderSource::Wgsl(shader_src.into()),
         })
     }
+
+    /// The reaction-diffusion shader, with `state_out` declared in the negotiated `state_format`
+    fn create_reaction_diffusion_shader(
+        device: &Device,
+        prelude: &str,
+        state_format: TextureFormat,
+    ) -> ShaderModule {
+        let state_out = format!(
+            "@group(0) @binding(1)\nvar state_out: texture_storage_2d<{}, write>;",
+            gpu::wgsl_storage_format(state_format).expect("state formats are storage formats")
+        );
+        let shader_src = [
+            prelude,
+            &state_out,
+            include_str!("./shaders/reaction_diffusion.wgsl"),
+        ]
+        .join("\n");
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Reaction-Diffusion Shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        })
+    }
+
+    fn create_paint_shader(device: &Device, prelude: &str) -> ShaderModule {
+        let shader_src = [prelude, include_str!("./shaders/paint.wgsl")].join("\n");
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Paint Shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        })
+    }
+
+    fn create_undo_shader(device: &Device) -> ShaderModule {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Undo Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("./shaders/undo.wgsl").into()),
+        })
+    }
+
+    fn create_accumulate_shader(device: &Device, prelude: &str) -> ShaderModule {
+        let shader_src = [prelude, include_str!("./shaders/accumulate.wgsl")].join("\n");
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Accumulation Shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        })
+    }
+
+    fn create_downsample_shader(device: &Device, prelude: &str, supersample: u32) -> ShaderModule {
+        let shader_src = [
+            prelude,
+            &format!("const SUPERSAMPLE: u32 = {supersample}u;"),
+            include_str!("./shaders/downsample.wgsl"),
+        ]
+        .join("\n");
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Downsample Shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        })
+    }
+
+    fn create_mipmap_shader(device: &Device, prelude: &str) -> ShaderModule {
+        let shader_src = [prelude, include_str!("./shaders/mipmap.wgsl")].join("\n");
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap Shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        })
+    }
+
+    fn create_bloom_shader(device: &Device, prelude: &str) -> ShaderModule {
+        let shader_src = [prelude, include_str!("./shaders/bloom.wgsl")].join("\n");
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Bloom Shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        })
+    }
+
+    fn create_convolution_shader(device: &Device, prelude: &str) -> ShaderModule {
+        let shader_src = [prelude, include_str!("./shaders/convolution.wgsl")].join("\n");
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Convolution Shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        })
+    }
+}
+
+/// `src` without `//` comments
+fn strip_comments(src: &str) -> String {
+    src.lines()
+        .map(|line| line.split_once("//").map_or(line, |(code, _)| code))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `code` with its `textureStore(out_image, ...)` calls turned into `store_out_image(...)`,
+/// however they are spaced and broken into lines, for `output_storage`s other than a float storage texture.
+/// The lines are kept, so errors still point at the right ones.
+/// Any other use of `out_image` is an error, as it isn't a texture `textureStore` can write colors to
+fn store_out_image_calls(
+    code: &Preprocessed,
+    output_storage: OutputStorage,
+) -> Result<String, String> {
+    let text = &code.text;
+    let tokens = spanned_tokens(text);
+    let mut rewritten = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut index = 0;
+    while let Some(&(start, token)) = tokens.get(index) {
+        let call = tokens
+            .get(index..index + 4)
+            .map(|call| call.iter().map(|&(_, token)| token).collect::<Vec<_>>());
+        if call.as_deref() == Some(&["textureStore", "(", "out_image", ","][..]) {
+            let (comma, _) = tokens[index + 3];
+            rewritten += &text[copied..start];
+            rewritten += "store_out_image(";
+            rewritten.extend(text[start..comma].matches('\n'));
+            copied = comma + 1;
+            index += 4;
+        } else if token == "out_image" {
+            let storage = if output_storage == OutputStorage::Buffer {
+                "a storage buffer"
+            } else {
+                "an `rgba8uint` texture"
+            };
+            return Err(format!(
+                "{}: `out_image` is {storage} on this adapter, \
+                 it can only be written with `textureStore(out_image, pixel, color)`",
+                code.location(text[..start].matches('\n').count())
+            ));
+        } else {
+            index += 1;
+        }
+    }
+    rewritten += &text[copied..];
+    Ok(rewritten)
+}
+
+/// Identifiers, numbers and punctuation of `code` outside of comments, with the offsets they start at
+fn spanned_tokens(code: &str) -> Vec<(usize, &str)> {
+    let word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut tokens = Vec::new();
+    let mut chars = code.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, c)| c);
+        if c == '/' && next == Some('/') {
+            while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+        } else if c == '/' && next == Some('*') {
+            // Block comments nest in WGSL
+            chars.next();
+            let mut depth = 1;
+            while depth > 0
+                && let Some((_, c)) = chars.next()
+            {
+                let next = chars.peek().map(|&(_, c)| c);
+                if c == '/' && next == Some('*') {
+                    chars.next();
+                    depth += 1;
+                } else if c == '*' && next == Some('/') {
+                    chars.next();
+                    depth -= 1;
+                }
+            }
+        } else if word(c) {
+            let mut end = start + c.len_utf8();
+            while let Some((index, c)) = chars.next_if(|&(_, c)| word(c)) {
+                end = index + c.len_utf8();
+            }
+            tokens.push((start, &code[start..end]));
+        } else if !c.is_whitespace() {
+            tokens.push((start, &code[start..start + c.len_utf8()]));
+        }
+    }
+    tokens
+}
+
+/// Identifiers, keywords and numbers of `code`
+fn tokens(code: &str) -> Vec<&str> {
+    code.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+/// Whether `code` declares a function called `name`
+fn declares_function(code: &str, name: &str) -> bool {
+    tokens(code).windows(2).any(|pair| pair == ["fn", name])
+}
+
+/// Whether `code` refers to `name` anywhere
+fn mentions(code: &str, name: &str) -> bool {
+    tokens(code).contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocessor::{ShaderSource, preprocess};
+
+    const PRELUDE: &str = "const WORKGROUP_SIZE_X: u32 = 8u;\n\
+                           const WORKGROUP_SIZE_Y: u32 = 8u;\n\
+                           const IMAGE_WIDTH: u32 = 64u;\n\
+                           const IMAGE_HEIGHT: u32 = 64u;\n";
+    const GLOBALS: &str = "struct Globals { region_offset: vec2<u32>, region_size: vec2<u32> };\n\
+                           @group(1) @binding(0)\n\
+                           var<uniform> globals: Globals;\n\
+                           fn image_offset() -> vec2<u32> { return vec2<u32>(0u); }\n";
+
+    /// `draw` as compiled for `output_storage`, validated by naga
+    fn compile(output_storage: OutputStorage, draw: &str) -> Result<Preprocessed, String> {
+        let draw_src = preprocess(&ShaderSource::builtin("draw.wgsl", draw), &[], None)?;
+        let source =
+            DrawingSource::compute_source(PRELUDE, GLOBALS, output_storage, &draw_src, &[])?;
+        let module = naga::front::wgsl::parse_str(&source.text)
+            .map_err(|err| err.emit_to_string(&source.text))?;
+        naga::valid::Validator::new(Default::default(), naga::valid::Capabilities::all())
+            .validate(&module)
+            .map_err(|err| format!("{err:?}"))?;
+        Ok(source)
+    }
+
+    /// Stores spelled and spaced in ways a plain text replacement misses
+    const SPACED_STORES: &str = "fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    return vec4<f32>(1.0);
+}
+
+@compute @workgroup_size(8, 8)
+fn sparkle(@builtin(global_invocation_id) gid: vec3<u32>) {
+    // textureStore(out_image, ...) in a comment stays as it is
+    textureStore( out_image , vec2<i32>(gid.xy), vec4<f32>(0.5));
+    textureStore(
+        out_image,
+        vec2<i32>(gid.xy),
+        vec4<f32>(0.25)
+    );
+    textureStore(/* the image */ out_image, vec2<i32>(gid.xy), vec4<f32>(0.0));
+}
+";
+
+    #[test]
+    fn float_storage_is_written_directly() {
+        let source = compile(OutputStorage::Float, SPACED_STORES).unwrap();
+        assert!(!source.text.contains("store_out_image"));
+    }
+
+    #[test]
+    fn uint_storage_stores_through_the_conversion() {
+        let source = compile(OutputStorage::Uint, SPACED_STORES).unwrap();
+        assert!(
+            source
+                .text
+                .contains("store_out_image( vec2<i32>(gid.xy), vec4<f32>(0.5))")
+        );
+        // Lines are kept, so the store broken over several lines now starts with an empty one
+        assert!(
+            source
+                .text
+                .contains("store_out_image(\n\n        vec2<i32>(gid.xy),")
+        );
+    }
+
+    #[test]
+    fn buffer_storage_stores_through_the_conversion() {
+        compile(OutputStorage::Buffer, SPACED_STORES).unwrap();
+    }
+
+    #[test]
+    fn other_uses_of_packed_images_are_reported() {
+        let draw = "fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let size = textureDimensions(out_image);
+    return vec4<f32>(1.0);
+}
+";
+        for output_storage in [OutputStorage::Uint, OutputStorage::Buffer] {
+            let Err(err) = compile(output_storage, draw) else {
+                panic!("{output_storage:?} accepted `textureDimensions(out_image)`");
+            };
+            assert!(err.starts_with("draw.wgsl:2: `out_image` is"), "{err}");
+        }
+    }
 }
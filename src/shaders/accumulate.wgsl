@@ -0,0 +1,33 @@
+// Progressive accumulation.
+// Sums up the samples of successive frames and writes their average to the output texture.
+
+struct Accumulation {
+    sample_count: u32,
+};
+
+@group(0) @binding(0)
+var sample_image: texture_2d<f32>;
+@group(0) @binding(1)
+var accumulated_in: texture_2d<f32>;
+@group(0) @binding(2)
+var accumulated_out: texture_storage_2d<rgba32float, write>;
+@group(0) @binding(3)
+var average_out: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(4)
+var<uniform> accumulation: Accumulation;
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if gid.x >= IMAGE_WIDTH || gid.y >= IMAGE_HEIGHT {
+        return;
+    }
+
+    let p = vec2<i32>(gid.xy);
+    var sum = textureLoad(sample_image, p, 0);
+    if accumulation.sample_count > 1u {
+        sum += textureLoad(accumulated_in, p, 0);
+    }
+    textureStore(accumulated_out, p, sum);
+
+    textureStore(average_out, p, sum / f32(accumulation.sample_count));
+}
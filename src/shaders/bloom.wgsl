@@ -0,0 +1,94 @@
+// Bloom.
+// `prefilter` keeps what is brighter than the threshold at half the resolution, `downsample` blurs it into ever smaller levels,
+// `upsample` adds each level to the blurred smaller one on the way back up and `composite` adds the result to the image.
+
+// Mirrors `BloomParams` in src/bloom.rs
+struct BloomParams {
+    threshold: f32,
+    intensity: f32,
+    levels: u32,
+}
+
+// Blurred into `output`
+@group(0) @binding(0)
+var input: texture_2d<f32>;
+// Added to the blurred `input` by `upsample` and `composite`
+@group(0) @binding(1)
+var base: texture_2d<f32>;
+@group(0) @binding(2)
+var output: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3)
+var linear: sampler;
+@group(0) @binding(4)
+var<uniform> params: BloomParams;
+
+// 4 × 4 tent filter of `input` around the pixels covered by `pixel` of the half as large `output`
+fn downsampled(pixel: vec2<u32>) -> vec4<f32> {
+    let last = vec2<i32>(textureDimensions(input)) - 1;
+    var weights = array<f32, 4>(1.0, 3.0, 3.0, 1.0);
+    var sum = vec4<f32>(0.0);
+    for (var y = 0; y < 4; y += 1) {
+        for (var x = 0; x < 4; x += 1) {
+            let coord = clamp(vec2<i32>(pixel) * 2 + vec2<i32>(x - 1, y - 1), vec2<i32>(0), last);
+            sum += weights[x] * weights[y] * textureLoad(input, coord, 0);
+        }
+    }
+    return sum / 64.0;
+}
+
+// 3 × 3 tent filter of the smaller `input` at `pixel` of `output`
+fn upsampled(pixel: vec2<u32>) -> vec4<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(textureDimensions(output));
+    let texel = 1.0 / vec2<f32>(textureDimensions(input));
+    var sum = vec4<f32>(0.0);
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let weight = f32((2 - abs(x)) * (2 - abs(y)));
+            sum += weight * textureSampleLevel(input, linear, uv + vec2<f32>(f32(x), f32(y)) * texel, 0.0);
+        }
+    }
+    return sum / 16.0;
+}
+
+fn outside(pixel: vec2<u32>) -> bool {
+    let size = textureDimensions(output);
+    return pixel.x >= size.x || pixel.y >= size.y;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn prefilter(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if outside(gid.xy) {
+        return;
+    }
+    let color = downsampled(gid.xy).rgb;
+    textureStore(output, vec2<i32>(gid.xy), vec4<f32>(max(color - params.threshold, vec3<f32>(0.0)), 1.0));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn downsample(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if outside(gid.xy) {
+        return;
+    }
+    textureStore(output, vec2<i32>(gid.xy), downsampled(gid.xy));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn upsample(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if outside(gid.xy) {
+        return;
+    }
+    let pixel = vec2<i32>(gid.xy);
+    textureStore(output, pixel, textureLoad(base, pixel, 0) + upsampled(gid.xy));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn composite(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if outside(gid.xy) {
+        return;
+    }
+    let pixel = vec2<i32>(gid.xy);
+    let color = textureLoad(base, pixel, 0);
+    // Every level added its share on the way up
+    let glow = upsampled(gid.xy).rgb * params.intensity / f32(params.levels);
+    textureStore(output, pixel, vec4<f32>(color.rgb + glow, color.a));
+}
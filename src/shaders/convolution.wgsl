@@ -0,0 +1,78 @@
+// Convolution with a user-supplied kernel, see src/convolution.rs.
+// `convolve` weights the `size` × `size` pixels around each pixel with `weights` row by row.
+// Separable kernels run `convolve_rows` and then `convolve_columns` instead, each weighting `size` pixels.
+// `input` is sampled at the pixels of `output`, so an image of another size is stretched to it,
+// and clamped at the edges. The alpha of the pixel itself is kept.
+
+// Mirrors `ConvolutionParams` in src/convolution.rs
+struct ConvolutionParams {
+    size: u32,
+}
+
+@group(0) @binding(0)
+var input: texture_2d<f32>;
+@group(0) @binding(1)
+var output: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(2)
+var linear: sampler;
+@group(0) @binding(3)
+var<storage, read> weights: array<f32>;
+@group(0) @binding(4)
+var<uniform> params: ConvolutionParams;
+
+// `input` at `offset` pixels of `output` from `pixel`
+fn tap(pixel: vec2<u32>, offset: vec2<i32>) -> vec4<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5 + vec2<f32>(offset)) / vec2<f32>(textureDimensions(output));
+    return textureSampleLevel(input, linear, uv, 0.0);
+}
+
+// `size` pixels along `step` centered on `pixel`, weighted with the first `size` weights
+fn convolve_line(pixel: vec2<u32>, step: vec2<i32>) -> vec3<f32> {
+    let radius = i32(params.size / 2u);
+    var sum = vec3<f32>(0.0);
+    for (var i = 0; i < i32(params.size); i += 1) {
+        sum += weights[i] * tap(pixel, (i - radius) * step).rgb;
+    }
+    return sum;
+}
+
+fn outside(pixel: vec2<u32>) -> bool {
+    let size = textureDimensions(output);
+    return pixel.x >= size.x || pixel.y >= size.y;
+}
+
+fn store(pixel: vec2<u32>, color: vec3<f32>) {
+    textureStore(output, vec2<i32>(pixel), vec4<f32>(color, tap(pixel, vec2<i32>(0)).a));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn convolve(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if outside(gid.xy) {
+        return;
+    }
+    let radius = i32(params.size / 2u);
+    var sum = vec3<f32>(0.0);
+    for (var y = 0; y < i32(params.size); y += 1) {
+        for (var x = 0; x < i32(params.size); x += 1) {
+            let weight = weights[u32(y) * params.size + u32(x)];
+            sum += weight * tap(gid.xy, vec2<i32>(x - radius, y - radius)).rgb;
+        }
+    }
+    store(gid.xy, sum);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn convolve_rows(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if outside(gid.xy) {
+        return;
+    }
+    store(gid.xy, convolve_line(gid.xy, vec2<i32>(1, 0)));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn convolve_columns(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if outside(gid.xy) {
+        return;
+    }
+    store(gid.xy, convolve_line(gid.xy, vec2<i32>(0, 1)));
+}
@@ -0,0 +1,65 @@
+// Counters, append lists and free lists kept in `scratch`, see src/counters.rs.
+// Included with `#include <counters.wgsl>` by shaders declaring a scratch buffer with `--scratch-size`.
+// Each structure lives at an offset into `scratch` in `u32`s the shader picks, scratch starts out zeroed,
+// so they start out empty. Read them back with the helpers in src/counters.rs at the same offsets.
+
+// Add `value` to the counter at `counter`, returns its value before
+fn counter_add(counter: u32, value: u32) -> u32 {
+    return atomicAdd(&scratch[counter], value);
+}
+
+fn counter_load(counter: u32) -> u32 {
+    return atomicLoad(&scratch[counter]);
+}
+
+// An append list at `list` takes `capacity + 1` elements: the number of appends, then the items.
+// Append from any invocation, e.g. the indices passing a test for stream compaction.
+// Returns false if the list was full and `item` was dropped
+fn append(list: u32, capacity: u32, item: u32) -> bool {
+    let index = atomicAdd(&scratch[list], 1u);
+    if index >= capacity {
+        return false;
+    }
+    atomicStore(&scratch[list + 1u + index], item);
+    return true;
+}
+
+// Items appended so far, read them in a later dispatch than the one appending
+fn list_length(list: u32, capacity: u32) -> u32 {
+    return min(atomicLoad(&scratch[list]), capacity);
+}
+
+fn list_item(list: u32, index: u32) -> u32 {
+    return atomicLoad(&scratch[list + 1u + index]);
+}
+
+// Empty `list` again, from a single invocation
+fn list_clear(list: u32) {
+    atomicStore(&scratch[list], 0u);
+}
+
+const NO_SLOT: u32 = 0xffffffffu;
+
+// A free list at `list` hands out the slots `0` to `capacity - 1`, e.g. of particles, and takes
+// `capacity + 2` elements: the number of released slots, the number of slots ever handed out,
+// then the released slots. Slots are allocated and released in different dispatches.
+// Returns `NO_SLOT` once all of them are in use
+fn allocate(list: u32, capacity: u32) -> u32 {
+    let released = atomicSub(&scratch[list], 1u);
+    // Wrapped around when other invocations emptied it meanwhile
+    if released > 0u && released <= capacity {
+        return atomicLoad(&scratch[list + 1u + released]);
+    }
+    atomicAdd(&scratch[list], 1u);
+    let fresh = atomicAdd(&scratch[list + 1u], 1u);
+    if fresh < capacity {
+        return fresh;
+    }
+    atomicSub(&scratch[list + 1u], 1u);
+    return NO_SLOT;
+}
+
+fn release(list: u32, slot: u32) {
+    let index = atomicAdd(&scratch[list], 1u);
+    atomicStore(&scratch[list + 2u + index], slot);
+}
@@ -0,0 +1,64 @@
+// Cubemaps for shaders defining `fn cubemap(direction: vec3<f32>) -> vec4<f32>`, e.g. procedural skyboxes.
+// `draw_cubemap` evaluates it for every pixel of the six CUBEMAP_SIZE² faces in a pass of its own,
+// after which `draw` can look up directions with `sample_cubemap` or show all of it with `cubemap_equirect`.
+// The cubemap pass binds nothing in group 0, so `cubemap` reads `globals`, `camera` and `audio` only.
+
+// The faces side by side, +X, -X and +Y above -Y, +Z and -Z
+@group(2) @binding(0)
+var cubemap_faces: texture_storage_2d<rgba16float, write>;
+
+// Direction through the center of `pixel` on `face`, oriented like the layers of a cube texture
+fn cubemap_direction(face: u32, pixel: vec2<u32>) -> vec3<f32> {
+    let st = (vec2<f32>(pixel) + 0.5) / f32(CUBEMAP_SIZE) * 2.0 - 1.0;
+    switch face {
+        case 0u: {
+            return normalize(vec3<f32>(1.0, -st.y, -st.x));
+        }
+        case 1u: {
+            return normalize(vec3<f32>(-1.0, -st.y, st.x));
+        }
+        case 2u: {
+            return normalize(vec3<f32>(st.x, 1.0, st.y));
+        }
+        case 3u: {
+            return normalize(vec3<f32>(st.x, -1.0, -st.y));
+        }
+        case 4u: {
+            return normalize(vec3<f32>(st.x, -st.y, 1.0));
+        }
+        default: {
+            return normalize(vec3<f32>(-st.x, -st.y, -1.0));
+        }
+    }
+}
+
+// One layer of workgroups per face
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn draw_cubemap(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if gid.x >= CUBEMAP_SIZE || gid.y >= CUBEMAP_SIZE {
+        return;
+    }
+    let origin = vec2<u32>(gid.z % 3u, gid.z / 3u) * CUBEMAP_SIZE;
+    textureStore(cubemap_faces, origin + gid.xy, cubemap(cubemap_direction(gid.z, gid.xy)));
+}
+
+fn sample_cubemap(direction: vec3<f32>) -> vec4<f32> {
+    return textureSampleLevel(cubemap_texture, cubemap_sampler, direction, 0.0);
+}
+
+// Longitude across and latitude down the image, its middle looks along -z like the default camera
+fn equirect_direction(pixel: vec2<u32>) -> vec3<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5) / globals.resolution;
+    let longitude = (uv.x - 0.5) * 6.2831853;
+    let latitude = (0.5 - uv.y) * 3.1415927;
+    return vec3<f32>(
+        cos(latitude) * sin(longitude),
+        sin(latitude),
+        -cos(latitude) * cos(longitude),
+    );
+}
+
+// All directions of the cubemap in an equirectangular projection, twice as wide as high
+fn cubemap_equirect(pixel: vec2<u32>) -> vec4<f32> {
+    return sample_cubemap(equirect_direction(pixel));
+}
@@ -0,0 +1,5 @@
+// `draw` of shaders only defining `cubemap`, so the drawn, recorded and exported image is the equirectangular projection
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    return cubemap_equirect(pixel);
+}
@@ -0,0 +1,22 @@
+// Supersampling.
+// Averages each SUPERSAMPLE × SUPERSAMPLE block of the drawn image into a pixel of the presented one.
+
+@group(0) @binding(0)
+var supersampled: texture_2d<f32>;
+@group(0) @binding(1)
+var downsampled: texture_storage_2d<rgba16float, write>;
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if gid.x >= IMAGE_WIDTH / SUPERSAMPLE || gid.y >= IMAGE_HEIGHT / SUPERSAMPLE {
+        return;
+    }
+
+    var sum = vec4<f32>(0.0);
+    for (var y = 0u; y < SUPERSAMPLE; y += 1u) {
+        for (var x = 0u; x < SUPERSAMPLE; x += 1u) {
+            sum += textureLoad(supersampled, vec2<i32>(gid.xy * SUPERSAMPLE + vec2<u32>(x, y)), 0);
+        }
+    }
+    textureStore(downsampled, vec2<i32>(gid.xy), sum / f32(SUPERSAMPLE * SUPERSAMPLE));
+}
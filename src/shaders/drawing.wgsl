@@ -1,13 +1,10 @@
 // Main shader responsible for visual output
-// This was intently written as a compute shader.
+// This was intently written as a compute shader,
+// the entry points live in drawing_compute.wgsl and drawing_fragment.wgsl
 
-@group(0) @binding(0)
-var out_image: texture_storage_2d<rgba8unorm, write>;
-
-@compute @workgroup_size(8, 8)
-fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
-    let x = f32(gid.x) / 512.0;
-    let y = f32(gid.y) / 512.0;
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let x = f32(pixel.x) / globals.resolution.x;
+    let y = f32(pixel.y) / globals.resolution.y;
     let d = sqrt(x*x + y*y);
 
     let phase = sin(d*15.0);
@@ -20,5 +17,5 @@ fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
         phase*phase,
         1.0
     );
-    textureStore(out_image, vec2<i32>(gid.xy), color);
+    return color;
 }
@@ -0,0 +1,12 @@
+// Compute entry point, writes the output of `draw` into a storage texture,
+// `out_image` is declared in the format negotiated with the adapter, see `Shaders::compute_source`
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    // The last workgroups overhang regions that aren't a multiple of the workgroup size
+    if gid.x >= globals.region_size.x || gid.y >= globals.region_size.y {
+        return;
+    }
+    let pixel = gid.xy + globals.region_offset;
+    textureStore(out_image, vec2<i32>(pixel), draw(pixel + image_offset()));
+}
@@ -0,0 +1,13 @@
+// Fragment entry points, used on downlevel devices without storage textures.
+// A single triangle covers the whole target, `draw` is evaluated per pixel.
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return draw(vec2<u32>(position.xy) + image_offset());
+}
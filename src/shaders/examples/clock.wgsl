@@ -0,0 +1,46 @@
+// Example: an analog clock counting the time since start
+
+fn segment_distance(p: vec2<f32>, end: vec2<f32>) -> f32 {
+    let t = clamp(dot(p, end) / dot(end, end), 0.0, 1.0);
+    return length(p - end * t);
+}
+
+// Direction of a hand pointing at `turns` of a full revolution, clockwise from 12
+fn hand(turns: f32) -> vec2<f32> {
+    let angle = 6.28318 * turns;
+    return vec2<f32>(sin(angle), -cos(angle));
+}
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let size = min(globals.resolution.x, globals.resolution.y);
+    let p = (vec2<f32>(pixel) - 0.5 * globals.resolution) / (0.5 * size);
+    let r = length(p);
+
+    var color = vec3<f32>(0.05, 0.06, 0.08);
+    if r < 0.9 {
+        color = vec3<f32>(0.92, 0.9, 0.85);
+    }
+
+    // Hour ticks
+    let angle = atan2(p.x, -p.y) / 6.28318 + 1.0;
+    let tick = abs(fract(angle * 12.0 + 0.5) - 0.5) / 12.0 * 6.28318 * r;
+    if r > 0.75 && r < 0.85 && tick < 0.015 {
+        color = vec3<f32>(0.1);
+    }
+
+    let seconds = globals.time;
+    var hands = array<vec3<f32>, 3>(
+        // turns, length, width
+        vec3<f32>(seconds / 43200.0, 0.45, 0.03),
+        vec3<f32>(seconds / 3600.0, 0.7, 0.02),
+        vec3<f32>(floor(seconds) / 60.0, 0.8, 0.008),
+    );
+    for (var i = 0; i < 3; i++) {
+        let h = hands[i];
+        if segment_distance(p, hand(h.x) * h.y) < h.z {
+            color = select(vec3<f32>(0.1), vec3<f32>(0.8, 0.1, 0.1), i == 2);
+        }
+    }
+
+    return vec4<f32>(color, 1.0);
+}
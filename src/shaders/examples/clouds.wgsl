@@ -0,0 +1,23 @@
+// Drifting clouds of value noise from `noise_fbm`, filled into a volume and raymarched through the camera.
+// `globals.params.x` sets the coverage and `globals.params.y` the density
+
+#ifdef COMPUTE
+fn volume(voxel: vec3<u32>) -> vec4<f32> {
+    let p = (vec3<f32>(voxel) + 0.5) / f32(VOLUME_SIZE) * 2.0 - 1.0;
+    let drift = vec3<f32>(globals.time * 0.1, 0.0, globals.time * 0.05);
+    // Fade out towards the faces of the cube
+    let edge = 1.0 - smoothstep(0.7, 1.0, max(abs(p.x), max(abs(p.y), abs(p.z))));
+    let coverage = 0.35 + 0.3 * globals.params.x;
+    let density = max(noise_fbm(p * 2.5 + drift, 5u) - (1.0 - coverage), 0.0) * edge;
+    // Lighter at the top, as if lit from above
+    let shade = mix(vec3<f32>(0.55, 0.6, 0.7), vec3<f32>(1.0, 0.97, 0.92), p.y * 0.5 + 0.5);
+    return vec4<f32>(shade, density * 40.0 * (0.25 + globals.params.y));
+}
+#else
+// Volumes require compute shaders, show a single layer of the clouds instead
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let uv = vec2<f32>(pixel) / globals.resolution;
+    let clouds = noise_fbm(vec3<f32>(uv * 4.0, globals.time * 0.1), 5u);
+    return vec4<f32>(mix(vec3<f32>(0.35, 0.5, 0.8), vec3<f32>(1.0), smoothstep(0.4, 0.7, clouds)), 1.0);
+}
+#endif
@@ -0,0 +1,8 @@
+// Example: a gradient slowly rotating through hue
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let uv = vec2<f32>(pixel) / globals.resolution;
+    let hue = uv.x + 0.1 * globals.time;
+    let color = 0.5 + 0.5 * cos(6.28318 * (hue + vec3<f32>(0.0, 0.33, 0.67)));
+    return vec4<f32>(color * (1.0 - 0.5 * uv.y), 1.0);
+}
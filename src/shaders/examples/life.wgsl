@@ -0,0 +1,34 @@
+// Example: Conway's Game of Life, a fleet of gliders.
+// Drawing shaders keep no state between frames, so instead of simulating the board
+// every glider is placed in closed form: it repeats its 4 phases while moving one cell diagonally.
+
+const CELL_SIZE: u32 = 8u;
+// Gliders sit on a lattice with this spacing in cells, far enough apart to never interact
+const TILE_SIZE: u32 = 12u;
+const FRAMES_PER_GENERATION: u32 = 6u;
+
+// The 4 phases of a glider as 4×4 bitmasks, bit `y * 4 + x`
+const PHASES = array<u32, 4>(1858u, 9808u, 25920u, 27680u);
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let cell = pixel / CELL_SIZE;
+    let generation = globals.frame / FRAMES_PER_GENERATION;
+    let offset = generation / 4u;
+    let local = (cell % TILE_SIZE + TILE_SIZE - offset % TILE_SIZE) % TILE_SIZE;
+
+    // Constant arrays can't be indexed dynamically
+    var phases = PHASES;
+    var alive = false;
+    if local.x < 4u && local.y < 4u {
+        alive = (phases[generation % 4u] >> (local.y * 4u + local.x) & 1u) == 1u;
+    }
+
+    let in_cell = pixel % CELL_SIZE;
+    let border = in_cell.x == 0u || in_cell.y == 0u;
+    if alive {
+        return vec4<f32>(0.95, 0.85, 0.3, 1.0);
+    } else if border {
+        return vec4<f32>(0.12, 0.12, 0.15, 1.0);
+    }
+    return vec4<f32>(0.05, 0.05, 0.07, 1.0);
+}
@@ -0,0 +1,28 @@
+// Example: the Mandelbrot set, zooming into Seahorse Valley and back out.
+// `globals.params.x` sets the zoom speed and `globals.params.y` shifts the palette
+
+const CENTER: vec2<f32> = vec2<f32>(-0.743643, 0.131825);
+const MAX_ITERATIONS: u32 = 384u;
+// Doublings of the zoom, deeper ones run out of f32 precision
+const MAX_DEPTH: f32 = 12.0;
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5 - 0.5 * globals.resolution) / globals.resolution.y;
+    let speed = 0.02 + 0.2 * globals.params.x;
+    let depth = MAX_DEPTH * (0.5 - 0.5 * cos(globals.time * speed));
+    let c = CENTER + vec2<f32>(uv.x, -uv.y) * 3.0 / exp2(depth);
+
+    var z = vec2<f32>(0.0);
+    var i = 0u;
+    while i < MAX_ITERATIONS && dot(z, z) <= 256.0 {
+        z = vec2<f32>(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        i++;
+    }
+    if i == MAX_ITERATIONS {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    // Continuous iteration count, against bands
+    let n = f32(i) - log2(log2(dot(z, z))) + 4.0;
+    let color = 0.5 + 0.5 * cos(0.15 * n + 6.28318 * (globals.params.y + vec3<f32>(0.0, 0.1, 0.2)));
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,15 @@
+// Example: classic demoscene plasma, a sum of sine waves
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let p = vec2<f32>(pixel) / globals.resolution.y * 8.0;
+    let t = globals.time;
+
+    var v = sin(p.x + t);
+    v += sin(0.5 * (p.y + t));
+    v += sin(0.5 * (p.x + p.y + t));
+    let c = p + vec2<f32>(sin(t / 3.0), cos(t / 2.0)) * 4.0;
+    v += sin(sqrt(dot(c, c) + 1.0) + t);
+
+    let color = 0.5 + 0.5 * cos(3.14159 * v + vec3<f32>(0.0, 2.0, 4.0));
+    return vec4<f32>(color, 1.0);
+}
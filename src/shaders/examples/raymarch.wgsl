@@ -0,0 +1,56 @@
+// Example: a sphere above a checkered floor, rendered by sphere tracing a distance field
+
+fn scene_distance(p: vec3<f32>) -> f32 {
+    let sphere = length(p - vec3<f32>(0.0, 1.0, 0.0)) - 1.0;
+    let floor = p.y;
+    return min(sphere, floor);
+}
+
+fn scene_normal(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2<f32>(0.001, 0.0);
+    return normalize(vec3<f32>(
+        scene_distance(p + e.xyy) - scene_distance(p - e.xyy),
+        scene_distance(p + e.yxy) - scene_distance(p - e.yxy),
+        scene_distance(p + e.yyx) - scene_distance(p - e.yyx),
+    ));
+}
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let uv = (vec2<f32>(pixel) - 0.5 * globals.resolution) / globals.resolution.y;
+
+    // Camera circling the sphere
+    let angle = 0.3 * globals.time;
+    let origin = vec3<f32>(4.0 * sin(angle), 2.0, 4.0 * cos(angle));
+    let forward = normalize(vec3<f32>(0.0, 0.8, 0.0) - origin);
+    let right = normalize(cross(forward, vec3<f32>(0.0, 1.0, 0.0)));
+    let up = cross(right, forward);
+    let direction = normalize(forward + uv.x * right - uv.y * up);
+
+    var t = 0.0;
+    for (var i = 0; i < 96; i++) {
+        let d = scene_distance(origin + direction * t);
+        if d < 0.001 || t > 50.0 {
+            break;
+        }
+        t += d;
+    }
+
+    let sky = mix(vec3<f32>(0.7, 0.8, 1.0), vec3<f32>(0.3, 0.45, 0.8), clamp(-direction.y * 2.0, 0.0, 1.0));
+    if t > 50.0 {
+        return vec4<f32>(sky, 1.0);
+    }
+
+    let p = origin + direction * t;
+    let n = scene_normal(p);
+    let light = normalize(vec3<f32>(0.6, 0.8, 0.3));
+    let diffuse = max(dot(n, light), 0.0);
+
+    var albedo = vec3<f32>(0.9, 0.3, 0.2);
+    if p.y < 0.01 {
+        let checker = (i32(floor(p.x)) + i32(floor(p.z))) & 1;
+        albedo = select(vec3<f32>(0.8), vec3<f32>(0.3), checker == 1);
+    }
+
+    let fog = exp(-0.04 * t);
+    return vec4<f32>(mix(sky, albedo * (0.15 + 0.85 * diffuse), fog), 1.0);
+}
@@ -0,0 +1,27 @@
+// Example: spectrum bars and waveform of the `--audio` input
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let uv = vec2<f32>(pixel) / globals.resolution;
+    let height = 1.0 - uv.y;
+
+    // 64 bars, each the loudest of its 8 spectrum bins
+    let bar = u32(uv.x * 64.0);
+    var level = 0.0;
+    for (var i = 0u; i < 8u; i++) {
+        level = max(level, textureLoad(audio, vec2<u32>(bar * 8u + i, 0u), 0).r);
+    }
+    let in_bar = fract(uv.x * 64.0) < 0.8;
+
+    var color = vec3<f32>(0.02, 0.02, 0.05);
+    if in_bar && height < level {
+        color = mix(vec3<f32>(0.1, 0.4, 1.0), vec3<f32>(1.0, 0.2, 0.4), height);
+    }
+
+    // Waveform drawn across the upper half
+    let sample = textureLoad(audio, vec2<u32>(u32(uv.x * 511.0), 1u), 0).r;
+    if abs(height - (0.5 + 0.5 * sample)) < 0.004 {
+        color = vec3<f32>(0.9);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,32 @@
+// Example: drifting Voronoi cells with dark borders, hashed with `rng_pcg3d`.
+// `globals.params.x` sets the number of cells and `globals.params.y` the border width
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let p = vec2<f32>(pixel) / globals.resolution.y * mix(3.0, 20.0, globals.params.x);
+    let cell = floor(p);
+
+    // Distances to the nearest and second nearest point, each cell has one
+    var nearest = 8.0;
+    var second = 8.0;
+    var nearest_hash = 0u;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbor = cell + vec2<f32>(f32(x), f32(y));
+            let hash = rng_pcg3d(bitcast<vec3<u32>>(vec3<i32>(vec2<i32>(neighbor), 0)));
+            let phase = vec2<f32>(rng_float(hash.x), rng_float(hash.y));
+            let point = neighbor + 0.5 + 0.4 * sin(0.7 * globals.time + 6.28318 * phase);
+            let d = distance(p, point);
+            if d < nearest {
+                second = nearest;
+                nearest = d;
+                nearest_hash = hash.z;
+            } else if d < second {
+                second = d;
+            }
+        }
+    }
+
+    let color = 0.5 + 0.5 * cos(6.28318 * (rng_float(nearest_hash) + vec3<f32>(0.0, 0.33, 0.67)));
+    let border = smoothstep(0.0, 0.02 + 0.2 * globals.params.y, second - nearest);
+    return vec4<f32>(color * border, 1.0);
+}
@@ -0,0 +1,6 @@
+// Reported by the drawing shader about a frame and read back by the app, zeroed before every frame.
+// Count with `atomicAdd(&feedback.counters[0], 1u)`, write `values` from a single invocation
+struct Feedback {
+    counters: array<atomic<u32>, 4>,
+    values: array<f32, 4>,
+}
@@ -0,0 +1,127 @@
+// Mandelbrot and Julia set explorer, showing the part of the complex plane in `fractal_view`
+// the app moves with the mouse: drag to pan, scroll to zoom and J for the Julia set of the center.
+// `globals.params.x` sets how fast the colors cycle and `globals.params.y` shifts the palette.
+//
+// Once a pixel is smaller than f32 can tell apart, the iteration switches to double-single
+// arithmetic: every number is the unevaluated sum of two f32s, `hi` and the error `lo`,
+// which carries about 48 bits of mantissa.
+
+// Pixels smaller than this are drawn in double-single arithmetic
+const DOUBLE_SINGLE_SCALE: f32 = 1e-5;
+// Large so the smooth iteration count is accurate
+const ESCAPE_RADIUS_SQUARED: f32 = 256.0;
+
+// Compilers may simplify float expressions as if they were exact, `(a + b) - a` to `b`,
+// which would throw away the rounding errors kept in `lo`. Multiplying by `fractal_view.one`,
+// which they can't know is 1, keeps the intermediate results as written.
+
+// `a + b` as hi and lo parts, exact for any two floats (Knuth)
+fn ds_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let one = fractal_view.one;
+    let s = (a + b) * one;
+    let v = (s - a) * one;
+    return vec2<f32>(s, (a - (s - v) * one) + (b - v));
+}
+
+// `a + b` as hi and lo parts, exact when `abs(a) >= abs(b)`
+fn ds_quick_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let one = fractal_view.one;
+    let s = (a + b) * one;
+    return vec2<f32>(s, b - (s - a) * one);
+}
+
+// `a` split into two halves of 12 bits each, whose products are exact (Dekker)
+fn ds_split(a: f32) -> vec2<f32> {
+    let one = fractal_view.one;
+    let t = (4097.0 * a) * one;
+    let hi = (t - (t - a) * one) * one;
+    return vec2<f32>(hi, a - hi);
+}
+
+// `a * b` as hi and lo parts, exact
+fn ds_two_prod(a: f32, b: f32) -> vec2<f32> {
+    let p = (a * b) * fractal_view.one;
+    let x = ds_split(a);
+    let y = ds_split(b);
+    return vec2<f32>(p, ((x.x * y.x - p) + x.x * y.y + x.y * y.x) + x.y * y.y);
+}
+
+fn ds_add(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let s = ds_two_sum(a.x, b.x);
+    let t = ds_two_sum(a.y, b.y);
+    let u = ds_quick_two_sum(s.x, s.y + t.x);
+    return ds_quick_two_sum(u.x, u.y + t.y);
+}
+
+fn ds_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let p = ds_two_prod(a.x, b.x);
+    return ds_quick_two_sum(p.x, p.y + (a.x * b.y + a.y * b.x));
+}
+
+// Iterations until `z` escapes, or `max_iterations`, and `dot(z, z)` at that point
+struct Orbit {
+    iterations: u32,
+    length_squared: f32,
+};
+
+fn orbit_single(start: vec2<f32>, c: vec2<f32>) -> Orbit {
+    var z = start;
+    var i = 0u;
+    while i < fractal_view.max_iterations && dot(z, z) <= ESCAPE_RADIUS_SQUARED {
+        z = vec2<f32>(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        i++;
+    }
+    return Orbit(i, dot(z, z));
+}
+
+// Like `orbit_single` with the real and imaginary parts each as double-single
+fn orbit_double(start_re: vec2<f32>, start_im: vec2<f32>, c_re: vec2<f32>, c_im: vec2<f32>) -> Orbit {
+    var re = start_re;
+    var im = start_im;
+    var i = 0u;
+    while i < fractal_view.max_iterations && re.x * re.x + im.x * im.x <= ESCAPE_RADIUS_SQUARED {
+        let re_squared = ds_mul(re, re);
+        let im_squared = ds_mul(im, im);
+        let product = ds_mul(re, im);
+        re = ds_add(ds_add(re_squared, -im_squared), c_re);
+        im = ds_add(ds_add(product, product), c_im);
+        i++;
+    }
+    return Orbit(i, re.x * re.x + im.x * im.x);
+}
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    // Offset from the center of the view, the imaginary axis points up
+    let uv = vec2<f32>(pixel) + 0.5 - 0.5 * globals.resolution;
+    let offset = vec2<f32>(uv.x, -uv.y) * fractal_view.scale;
+    let julia = fractal_view.julia != 0u;
+
+    var orbit: Orbit;
+    if fractal_view.scale < DOUBLE_SINGLE_SCALE {
+        let re = ds_add(vec2<f32>(fractal_view.center_hi.x, fractal_view.center_lo.x), vec2<f32>(offset.x, 0.0));
+        let im = ds_add(vec2<f32>(fractal_view.center_hi.y, fractal_view.center_lo.y), vec2<f32>(offset.y, 0.0));
+        if julia {
+            let c_re = vec2<f32>(fractal_view.julia_hi.x, fractal_view.julia_lo.x);
+            let c_im = vec2<f32>(fractal_view.julia_hi.y, fractal_view.julia_lo.y);
+            orbit = orbit_double(re, im, c_re, c_im);
+        } else {
+            orbit = orbit_double(vec2<f32>(0.0), vec2<f32>(0.0), re, im);
+        }
+    } else {
+        let point = fractal_view.center_hi + offset;
+        if julia {
+            orbit = orbit_single(point, fractal_view.julia_hi);
+        } else {
+            orbit = orbit_single(vec2<f32>(0.0), point);
+        }
+    }
+
+    if orbit.iterations == fractal_view.max_iterations {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    // Continuous iteration count, against bands
+    let n = f32(orbit.iterations) - log2(log2(orbit.length_squared)) + 4.0;
+    let density = 0.05 + 0.3 * globals.params.x;
+    let color = 0.5 + 0.5 * cos(density * n + 6.28318 * (globals.params.y + vec3<f32>(0.0, 0.1, 0.2)));
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,122 @@
+// Mirrors `Globals` in src/globals.rs, available to drawing shaders as `globals`
+
+struct Globals {
+    // Size of the drawn image in pixels
+    resolution: vec2<f32>,
+    // Free parameters in 0..1, adjusted by touch controls
+    params: vec2<f32>,
+    // Number of frames drawn so far
+    frame: u32,
+    // Seconds since start
+    time: f32,
+    // Position of the cursor in pixels of the drawn image
+    mouse: vec2<f32>,
+    // Seed of the piece of a --generate'd edition, or --seed
+    seed: u32,
+    // Random value drawn from `seed` and `frame`, the same on every run and machine
+    random: u32,
+    // Pixel of the whole image the drawn part starts at in tiled renders, see `image_offset`
+    offset: vec2<u32>,
+    // Part of the drawn image the compute pass of `draw` covers, all of it unless only some pixels changed
+    region_offset: vec2<u32>,
+    region_size: vec2<u32>,
+};
+
+// Mirrors `Camera` in src/globals.rs, available to drawing shaders as `camera`
+struct Camera {
+    position: vec3<f32>,
+    // Vertical field of view in radians
+    fov: f32,
+    forward: vec3<f32>,
+    right: vec3<f32>,
+    up: vec3<f32>,
+};
+
+// Mirrors `FractalView` in src/globals.rs, available to drawing shaders as `fractal_view`.
+// Points are split into the nearest f32 `hi` and the remainder `lo` for double-single arithmetic
+struct FractalView {
+    center_hi: vec2<f32>,
+    center_lo: vec2<f32>,
+    julia_hi: vec2<f32>,
+    julia_lo: vec2<f32>,
+    // Units of the complex plane per pixel
+    scale: f32,
+    // 1 to draw the Julia set of `julia_hi` + `julia_lo` instead of the Mandelbrot set
+    julia: u32,
+    max_iterations: u32,
+    // Always 1, see src/shaders/fractal.wgsl
+    one: f32,
+};
+
+// Phases of a `TouchPoint`, like the `TOUCH_*` constants in src/globals.rs.
+// Lifted fingers are kept with `TOUCH_ENDED` for one frame
+const TOUCH_STARTED: u32 = 0u;
+const TOUCH_MOVED: u32 = 1u;
+const TOUCH_ENDED: u32 = 2u;
+const TOUCH_CANCELLED: u32 = 3u;
+
+struct TouchPoint {
+    // Position in pixels of the drawn image, like `globals.mouse`
+    position: vec2<f32>,
+    // Stays the same while the finger is down
+    id: u32,
+    phase: u32,
+};
+
+// Mirrors `Touches` in src/globals.rs, available to drawing shaders as `touches`.
+// The first `count` points are the fingers on the screen, `pan`, `zoom` and `rotation` follow two finger gestures
+struct Touches {
+    points: array<TouchPoint, 10>,
+    pan: vec2<f32>,
+    zoom: f32,
+    rotation: f32,
+    count: u32,
+};
+
+// Bits of `Gamepad.buttons`, in the order of `GamepadButton` in src/gamepad.rs
+const GAMEPAD_SOUTH: u32 = 1u;
+const GAMEPAD_EAST: u32 = 2u;
+const GAMEPAD_NORTH: u32 = 4u;
+const GAMEPAD_WEST: u32 = 8u;
+const GAMEPAD_LEFT_BUMPER: u32 = 16u;
+const GAMEPAD_RIGHT_BUMPER: u32 = 32u;
+const GAMEPAD_LEFT_TRIGGER: u32 = 64u;
+const GAMEPAD_RIGHT_TRIGGER: u32 = 128u;
+const GAMEPAD_SELECT: u32 = 256u;
+const GAMEPAD_START: u32 = 512u;
+const GAMEPAD_MODE: u32 = 1024u;
+const GAMEPAD_LEFT_THUMB: u32 = 2048u;
+const GAMEPAD_RIGHT_THUMB: u32 = 4096u;
+const GAMEPAD_DPAD_UP: u32 = 8192u;
+const GAMEPAD_DPAD_DOWN: u32 = 16384u;
+const GAMEPAD_DPAD_LEFT: u32 = 32768u;
+const GAMEPAD_DPAD_RIGHT: u32 = 65536u;
+
+// Mirrors `Gamepad` in src/globals.rs, available to drawing shaders as the `gamepads` array with `--gamepad`.
+// All zero where nothing is connected
+struct Gamepad {
+    // In -1..1, with y pointing up
+    left_stick: vec2<f32>,
+    right_stick: vec2<f32>,
+    // How far the left and right trigger are pulled, in 0..1
+    triggers: vec2<f32>,
+    // A bit per held button, test them with the `GAMEPAD_*` constants
+    buttons: u32,
+    // 1 while connected
+    connected: u32,
+};
+
+// The point of the image shown at `pixel` once it was pinched, twisted and dragged with two fingers
+fn touch_view(pixel: vec2<f32>) -> vec2<f32> {
+    let c = cos(touches.rotation);
+    let s = sin(touches.rotation);
+    let p = (pixel - touches.pan) / touches.zoom;
+    return vec2<f32>(c * p.x + s * p.y, c * p.y - s * p.x);
+}
+
+// Direction of the ray from `camera.position` through the center of `pixel`
+fn camera_ray(pixel: vec2<u32>) -> vec3<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5 - 0.5 * globals.resolution) / globals.resolution.y;
+    let scale = 2.0 * tan(camera.fov * 0.5);
+    return normalize(camera.forward + (uv.x * camera.right - uv.y * camera.up) * scale);
+}
@@ -0,0 +1,49 @@
+// Luminance histogram of the presented image, see src/histogram.rs.
+// Counts the pixels per bin of log2 luminance, in a workgroup first and then in `bins`.
+
+// Mirrors `BIN_COUNT` in src/histogram.rs
+const BIN_COUNT: u32 = 64u;
+const MIN_LOG_LUMINANCE: f32 = -10.0;
+const BINS_PER_STOP: f32 = 4.0;
+
+@group(0) @binding(0)
+var image: texture_2d<f32>;
+@group(0) @binding(1)
+var<storage, read_write> bins: array<atomic<u32>, BIN_COUNT>;
+
+var<workgroup> local_bins: array<atomic<u32>, BIN_COUNT>;
+
+fn bin(color: vec3<f32>) -> u32 {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    // Black and anything darker than the lowest bin is counted in bin 0
+    if luminance <= 0.0 {
+        return 0u;
+    }
+    let position = (log2(luminance) - MIN_LOG_LUMINANCE) * BINS_PER_STOP;
+    return u32(clamp(position, 0.0, f32(BIN_COUNT - 1u)));
+}
+
+@compute @workgroup_size(16, 16)
+fn main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    if index < BIN_COUNT {
+        atomicStore(&local_bins[index], 0u);
+    }
+    workgroupBarrier();
+
+    let size = textureDimensions(image);
+    if gid.x < size.x && gid.y < size.y {
+        let color = textureLoad(image, vec2<i32>(gid.xy), 0);
+        atomicAdd(&local_bins[bin(color.rgb)], 1u);
+    }
+    workgroupBarrier();
+
+    if index < BIN_COUNT {
+        let count = atomicLoad(&local_bins[index]);
+        if count > 0u {
+            atomicAdd(&bins[index], count);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+// Previous frames of the compute drawing, kept with `--history FRAMES`.
+// After each frame the image is copied into layer `globals.frame % HISTORY_LENGTH` of `history_frames`
+
+// The image at `pixel` as drawn `frames_ago` frames before the current one, 1 to `HISTORY_LENGTH`.
+// Black until that many frames were drawn
+fn history(pixel: vec2<u32>, frames_ago: u32) -> vec4<f32> {
+    let age = clamp(frames_ago, 1u, HISTORY_LENGTH);
+    let layer = (globals.frame + HISTORY_LENGTH - age) % HISTORY_LENGTH;
+    // `pixel` is in the whole image, the history only holds the drawn part
+    return textureLoad(history_frames, pixel - image_offset(), layer, 0);
+}
@@ -0,0 +1,40 @@
+// Appended to compute shaders defining `fn launch(item: u32)`.
+// `draw` calls `emit(item)` for work to be done after it, e.g. the particles an emitter spawned
+// or the objects a culling pass kept. `write_launch_args` turns the number of items into the
+// workgroup counts of the indirect `launch_items` dispatch, which calls `launch` once per item
+
+const LAUNCH_WORKGROUP_SIZE: u32 = 64u;
+
+// `count` is reset every frame, items past `MAX_LAUNCHES` are dropped
+struct Launches {
+    count: atomic<u32>,
+    items: array<u32, MAX_LAUNCHES>,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> launch_args: array<u32, 3>;
+
+fn emit(item: u32) {
+    let index = atomicAdd(&launches.count, 1u);
+    if index < MAX_LAUNCHES {
+        launches.items[index] = item;
+    }
+}
+
+fn launch_count() -> u32 {
+    return min(atomicLoad(&launches.count), MAX_LAUNCHES);
+}
+
+@compute @workgroup_size(1)
+fn write_launch_args() {
+    launch_args[0] = (launch_count() + LAUNCH_WORKGROUP_SIZE - 1u) / LAUNCH_WORKGROUP_SIZE;
+    launch_args[1] = 1u;
+    launch_args[2] = 1u;
+}
+
+@compute @workgroup_size(LAUNCH_WORKGROUP_SIZE)
+fn launch_items(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if gid.x < launch_count() {
+        launch(launches.items[gid.x]);
+    }
+}
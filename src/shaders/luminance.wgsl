@@ -0,0 +1,67 @@
+// Average log2 luminance of the presented image, see src/exposure.rs.
+// `reduce` sums each 16 × 16 block of pixels in workgroup memory into `partials`,
+// `finish` sums the partials in a single workgroup into `result`.
+
+const WORKGROUP_SIZE: u32 = 256u;
+// Darker pixels are left out, mostly black backgrounds which would pull the average down without end
+const MIN_LUMINANCE: f32 = 0.001;
+
+@group(0) @binding(0)
+var image: texture_2d<f32>;
+// Sum of log2 luminance and number of pixels per block
+@group(0) @binding(1)
+var<storage, read_write> partials: array<vec2<f32>>;
+@group(0) @binding(2)
+var<storage, read_write> result: vec2<f32>;
+
+var<workgroup> sums: array<vec2<f32>, WORKGROUP_SIZE>;
+
+// Tree reduction of `sums` into `sums[0]`, halving the invocations adding up every step
+fn reduce_workgroup(index: u32) {
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride >>= 1u) {
+        if index < stride {
+            sums[index] += sums[index + stride];
+        }
+        workgroupBarrier();
+    }
+}
+
+@compute @workgroup_size(16, 16)
+fn reduce(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+    @builtin(workgroup_id) workgroup: vec3<u32>,
+) {
+    let size = textureDimensions(image);
+    var value = vec2<f32>(0.0);
+    if gid.x < size.x && gid.y < size.y {
+        let color = textureLoad(image, vec2<i32>(gid.xy), 0).rgb;
+        let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+        if luminance > MIN_LUMINANCE {
+            value = vec2<f32>(log2(luminance), 1.0);
+        }
+    }
+    sums[index] = value;
+    workgroupBarrier();
+    reduce_workgroup(index);
+
+    if index == 0u {
+        let columns = (size.x + 15u) / 16u;
+        partials[workgroup.y * columns + workgroup.x] = sums[0];
+    }
+}
+
+@compute @workgroup_size(256)
+fn finish(@builtin(local_invocation_index) index: u32) {
+    var value = vec2<f32>(0.0);
+    for (var i = index; i < arrayLength(&partials); i += WORKGROUP_SIZE) {
+        value += partials[i];
+    }
+    sums[index] = value;
+    workgroupBarrier();
+    reduce_workgroup(index);
+
+    if index == 0u {
+        result = sums[0];
+    }
+}
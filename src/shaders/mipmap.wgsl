@@ -0,0 +1,23 @@
+// Mipmap generation.
+// Averages each 2 × 2 block of a mip level into a pixel of the next, clamping at the edges of odd sizes.
+
+@group(0) @binding(0)
+var larger: texture_2d<f32>;
+@group(0) @binding(1)
+var smaller: texture_storage_2d<rgba16float, write>;
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let size = textureDimensions(smaller);
+    if gid.x >= size.x || gid.y >= size.y {
+        return;
+    }
+
+    let last = vec2<i32>(textureDimensions(larger)) - 1;
+    let corner = vec2<i32>(gid.xy * 2u);
+    let sum = textureLoad(larger, corner, 0)
+        + textureLoad(larger, min(corner + vec2<i32>(1, 0), last), 0)
+        + textureLoad(larger, min(corner + vec2<i32>(0, 1), last), 0)
+        + textureLoad(larger, min(corner + vec2<i32>(1, 1), last), 0);
+    textureStore(smaller, vec2<i32>(gid.xy), sum / 4.0);
+}
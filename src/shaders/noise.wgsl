@@ -0,0 +1,84 @@
+// Hashes and noise available to every drawing shader, next to `globals`.
+// Names start with `rng_` or `noise_` to stay clear of the functions of the shader itself
+
+// Mirrors `BLUE_NOISE_SIZE` in src/blue_noise.rs
+const BLUE_NOISE_SIZE: u32 = 64u;
+
+// PCG hash of a single value, from Jarzynski and Olano's "Hash Functions for GPU Rendering"
+fn rng_pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Three hashes of three values at once, from the same paper
+fn rng_pcg3d(v: vec3<u32>) -> vec3<u32> {
+    var p = v * 1664525u + 1013904223u;
+    p.x += p.y * p.z;
+    p.y += p.z * p.x;
+    p.z += p.x * p.y;
+    p ^= p >> vec3<u32>(16u);
+    p.x += p.y * p.z;
+    p.y += p.z * p.x;
+    p.z += p.x * p.y;
+    return p;
+}
+
+// A hash turned into a float in [0, 1)
+fn rng_float(hash: u32) -> f32 {
+    return f32(hash >> 8u) / 16777216.0;
+}
+
+// White noise in [0, 1) for `pixel`, different every frame and the same on every run, see `globals.random`
+fn rng_pixel(pixel: vec2<u32>) -> vec3<f32> {
+    let hash = rng_pcg3d(vec3<u32>(pixel, globals.random));
+    return vec3<f32>(f32(hash.x >> 8u), f32(hash.y >> 8u), f32(hash.z >> 8u)) / 16777216.0;
+}
+
+// Random value in [0, 1) at the integer coordinates `cell`
+fn noise_lattice(cell: vec3<f32>) -> f32 {
+    return rng_float(rng_pcg3d(bitcast<vec3<u32>>(vec3<i32>(cell))).x);
+}
+
+// Value noise in [0, 1), smoothly interpolated between random values at integer coordinates
+fn noise_value(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(
+            mix(noise_lattice(i), noise_lattice(i + vec3<f32>(1.0, 0.0, 0.0)), u.x),
+            mix(noise_lattice(i + vec3<f32>(0.0, 1.0, 0.0)), noise_lattice(i + vec3<f32>(1.0, 1.0, 0.0)), u.x),
+            u.y,
+        ),
+        mix(
+            mix(noise_lattice(i + vec3<f32>(0.0, 0.0, 1.0)), noise_lattice(i + vec3<f32>(1.0, 0.0, 1.0)), u.x),
+            mix(noise_lattice(i + vec3<f32>(0.0, 1.0, 1.0)), noise_lattice(i + vec3<f32>(1.0, 1.0, 1.0)), u.x),
+            u.y,
+        ),
+        u.z,
+    );
+}
+
+// `octaves` of value noise, each twice as fine and half as strong as the one before, in [0, 1)
+fn noise_fbm(p: vec3<f32>, octaves: u32) -> f32 {
+    var sum = 0.0;
+    var total = 0.0;
+    var amplitude = 0.5;
+    var q = p;
+    for (var octave = 0u; octave < octaves; octave++) {
+        sum += amplitude * noise_value(q);
+        total += amplitude;
+        q *= 2.03;
+        amplitude *= 0.5;
+    }
+    return sum / max(total, 1e-6);
+}
+
+// Blue noise in [0, 1) for `pixel`, tiling every 64 pixels. Evenly spread unlike white noise,
+// so dithering and jittered samples look smooth. It steps by the golden ratio every frame,
+// which keeps it blue over time too, for shaders averaging successive frames
+fn noise_blue(pixel: vec2<u32>) -> f32 {
+    let value = textureLoad(blue_noise, pixel % BLUE_NOISE_SIZE, 0).r;
+    return fract(value + 0.618034 * f32(globals.frame % 4096u));
+}
@@ -0,0 +1,16 @@
+// The image of drawings on adapters that can't write storage textures, see `OutputStorage::Buffer`.
+// Calls to `textureStore(out_image, ...)` in the drawing are turned into calls to `store_out_image`
+
+@group(0) @binding(0)
+var<storage, read_write> out_pixels: array<u32>;
+
+// Rows are padded for copying them into a texture, `OUT_ROW_ALIGNMENT` is prepended
+const OUT_ROW_PIXELS: u32 = (IMAGE_WIDTH + OUT_ROW_ALIGNMENT - 1u) / OUT_ROW_ALIGNMENT * OUT_ROW_ALIGNMENT;
+
+// Stores `color` with the bytes of an `rgba8unorm` texel
+fn store_out_image(pixel: vec2<i32>, color: vec4<f32>) {
+    if any(pixel < vec2<i32>(0)) || any(vec2<u32>(pixel) >= vec2<u32>(IMAGE_WIDTH, IMAGE_HEIGHT)) {
+        return;
+    }
+    out_pixels[u32(pixel.y) * OUT_ROW_PIXELS + u32(pixel.x)] = pack4x8unorm(color);
+}
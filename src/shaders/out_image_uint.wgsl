@@ -0,0 +1,10 @@
+// `out_image` of drawings with 8 bits per channel, see `OutputStorage::Uint`.
+// Calls to `textureStore(out_image, ...)` in the drawing are turned into calls to `store_out_image`
+
+@group(0) @binding(0)
+var out_image: texture_storage_2d<rgba8uint, write>;
+
+// Stores `color` like an `rgba8unorm` texture would
+fn store_out_image(pixel: vec2<i32>, color: vec4<f32>) {
+    textureStore(out_image, pixel, vec4<u32>(round(saturate(color) * 255.0)));
+}
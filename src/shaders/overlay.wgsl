@@ -0,0 +1,55 @@
+// Appended to compute drawing shaders defining `fn overlay(pixel: vec2<u32>) -> vec4<f32>`,
+// which draws interaction affordances such as brush previews and guides.
+// The overlay is composited over the tone-mapped image on screen only, recordings and exports never contain it.
+// It returns premultiplied colors, and is only evaluated while the window has focus
+
+// Mirrors `Pointer` in src/globals.rs
+struct Pointer {
+    // Position of the cursor in pixels of the image, like `globals.mouse`
+    position: vec2<f32>,
+    // Bit 0 is set while the left button is held, bit 1 for the right and bit 2 for the middle one
+    buttons: u32,
+    focused: u32,
+};
+
+@group(2) @binding(0)
+var overlay_image: texture_storage_2d<rgba16float, write>;
+@group(2) @binding(1)
+var<uniform> pointer: Pointer;
+
+// Whether the mouse `button` is held, 0 for left, 1 for right and 2 for middle
+fn pointer_pressed(button: u32) -> bool {
+    return (pointer.buttons & (1u << button)) != 0u;
+}
+
+// Coverage of a circle of `radius` pixels around the pointer, outlined `width` pixels wide
+fn pointer_ring(pixel: vec2<u32>, radius: f32, width: f32) -> f32 {
+    let distance = length(vec2<f32>(pixel) + 0.5 - pointer.position);
+    return clamp(width * 0.5 + 0.5 - abs(distance - radius), 0.0, 1.0);
+}
+
+// Coverage of a crosshair through the pointer, `size` pixels from the center to each end
+fn pointer_crosshair(pixel: vec2<u32>, size: f32, width: f32) -> f32 {
+    let offset = abs(vec2<f32>(pixel) + 0.5 - pointer.position);
+    let horizontal = offset.x <= size && offset.y <= width * 0.5;
+    let vertical = offset.y <= size && offset.x <= width * 0.5;
+    return select(0.0, 1.0, horizontal || vertical);
+}
+
+// Coverage of the horizontal and vertical lines through `point`, across the whole image
+fn guides(pixel: vec2<u32>, point: vec2<f32>, width: f32) -> f32 {
+    let offset = abs(vec2<f32>(pixel) + 0.5 - point);
+    return select(0.0, 1.0, any(offset <= vec2<f32>(width * 0.5)));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn draw_overlay(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if gid.x >= IMAGE_WIDTH || gid.y >= IMAGE_HEIGHT {
+        return;
+    }
+    var color = vec4<f32>(0.0);
+    if pointer.focused != 0u {
+        color = overlay(gid.xy + image_offset());
+    }
+    textureStore(overlay_image, vec2<i32>(gid.xy), color);
+}
@@ -0,0 +1,60 @@
+// Paint preset: splats the segments of brush strokes into the canvas.
+// `splat` reads the canvas, paints every segment over it in order and writes the result
+// to the other canvas and the output image.
+
+struct Segment {
+    start: vec2<f32>,
+    end: vec2<f32>,
+    color: vec4<f32>,
+    radius: f32,
+    hardness: f32,
+};
+
+struct Params {
+    segment_count: u32,
+    clear: u32,
+};
+
+@group(0) @binding(0)
+var canvas_in: texture_2d<f32>;
+@group(0) @binding(1)
+var canvas_out: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(2)
+var out_image: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3)
+var<storage, read> segments: array<Segment>;
+@group(0) @binding(4)
+var<uniform> params: Params;
+
+// Mirrors the last color of `PALETTE` in src/paint.rs, which erases
+const PAPER: vec4<f32> = vec4<f32>(0.9, 0.86, 0.74, 1.0);
+
+fn distance_to_segment(p: vec2<f32>, a: vec2<f32>, b: vec2<f32>) -> f32 {
+    let ab = b - a;
+    let t = clamp(dot(p - a, ab) / max(dot(ab, ab), 1e-6), 0.0, 1.0);
+    return length(p - a - ab * t);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn splat(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let size = textureDimensions(canvas_in);
+    if gid.x >= size.x || gid.y >= size.y {
+        return;
+    }
+
+    var color = PAPER;
+    if params.clear == 0u {
+        color = textureLoad(canvas_in, vec2<i32>(gid.xy), 0);
+    }
+    let p = vec2<f32>(gid.xy) + 0.5;
+    for (var i = 0u; i < params.segment_count; i++) {
+        let segment = segments[i];
+        let distance = distance_to_segment(p, segment.start, segment.end);
+        // Hard brushes still get a pixel of antialiasing
+        let inner = min(segment.radius * segment.hardness, segment.radius - 1.0);
+        let coverage = 1.0 - smoothstep(inner, segment.radius, distance);
+        color = mix(color, vec4<f32>(segment.color.rgb, 1.0), coverage);
+    }
+    textureStore(canvas_out, vec2<i32>(gid.xy), color);
+    textureStore(out_image, vec2<i32>(gid.xy), color);
+}
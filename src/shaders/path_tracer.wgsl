@@ -0,0 +1,104 @@
+// Minimal path tracer, meant to be used with progressive accumulation.
+// Every frame traces one jittered sample per pixel, seeded by `globals.frame`.
+
+struct Ray {
+    origin: vec3<f32>,
+    direction: vec3<f32>,
+};
+
+struct Hit {
+    distance: f32,
+    normal: vec3<f32>,
+    albedo: vec3<f32>,
+    emission: vec3<f32>,
+};
+
+var<private> rng_state: u32;
+
+// PCG hash
+fn random() -> f32 {
+    rng_state = rng_state * 747796405u + 2891336453u;
+    var word = ((rng_state >> ((rng_state >> 28u) + 4u)) ^ rng_state) * 277803737u;
+    word = (word >> 22u) ^ word;
+    return f32(word) / 4294967295.0;
+}
+
+fn random_unit_vector() -> vec3<f32> {
+    let z = random() * 2.0 - 1.0;
+    let angle = random() * 6.2831853;
+    let r = sqrt(max(0.0, 1.0 - z * z));
+    return vec3<f32>(r * cos(angle), r * sin(angle), z);
+}
+
+fn hit_sphere(ray: Ray, center: vec3<f32>, radius: f32, closest: ptr<function, Hit>) -> bool {
+    let oc = ray.origin - center;
+    let b = dot(oc, ray.direction);
+    let c = dot(oc, oc) - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return false;
+    }
+    let t = -b - sqrt(discriminant);
+    if t < 0.001 || t > (*closest).distance {
+        return false;
+    }
+    (*closest).distance = t;
+    (*closest).normal = normalize(ray.origin + t * ray.direction - center);
+    return true;
+}
+
+fn trace_scene(ray: Ray) -> Hit {
+    var hit: Hit;
+    hit.distance = 1e9;
+    hit.emission = vec3<f32>(0.0);
+
+    if hit_sphere(ray, vec3<f32>(0.0, -1000.5, 0.0), 1000.0, &hit) {
+        hit.albedo = vec3<f32>(0.75, 0.72, 0.68);
+    }
+    if hit_sphere(ray, vec3<f32>(-0.6, 0.0, 0.0), 0.5, &hit) {
+        hit.albedo = vec3<f32>(0.85, 0.25, 0.2);
+    }
+    if hit_sphere(ray, vec3<f32>(0.55, -0.15, -0.4), 0.35, &hit) {
+        hit.albedo = vec3<f32>(0.2, 0.45, 0.85);
+    }
+    if hit_sphere(ray, vec3<f32>(0.3, 1.4, 0.6), 0.3, &hit) {
+        hit.albedo = vec3<f32>(0.0);
+        hit.emission = vec3<f32>(12.0, 10.0, 8.0);
+    }
+    return hit;
+}
+
+fn sky(direction: vec3<f32>) -> vec3<f32> {
+    let t = 0.5 * (direction.y + 1.0);
+    return mix(vec3<f32>(0.9, 0.9, 0.95), vec3<f32>(0.35, 0.5, 0.8), t) * 0.6;
+}
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    rng_state = (pixel.x * 1973u + pixel.y * 9277u + globals.frame * 26699u) | 1u;
+
+    // Jitter within the pixel for antialiasing
+    let jitter = vec2<f32>(random(), random());
+    let uv = (vec2<f32>(pixel) + jitter) / globals.resolution * 2.0 - 1.0;
+    let aspect = globals.resolution.x / globals.resolution.y;
+
+    var ray: Ray;
+    ray.origin = vec3<f32>(0.0, 0.3, 2.5);
+    ray.direction = normalize(vec3<f32>(uv.x * aspect, -uv.y, -1.8));
+
+    var throughput = vec3<f32>(1.0);
+    var radiance = vec3<f32>(0.0);
+    for (var bounce = 0; bounce < 4; bounce++) {
+        let hit = trace_scene(ray);
+        if hit.distance >= 1e9 {
+            radiance += throughput * sky(ray.direction);
+            break;
+        }
+        radiance += throughput * hit.emission;
+        throughput *= hit.albedo;
+
+        ray.origin = ray.origin + hit.distance * ray.direction;
+        ray.direction = normalize(hit.normal + random_unit_vector());
+    }
+
+    return vec4<f32>(radiance, 1.0);
+}
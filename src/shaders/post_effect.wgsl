@@ -0,0 +1,33 @@
+// Compute entry point of a post-effect pass, writes `effect` of every pixel into a storage texture.
+// The pass reads the output of the previous one, or the drawn image if it is the first
+
+@group(0) @binding(0)
+var input: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+@group(0) @binding(2)
+var output: texture_storage_2d<rgba16float, write>;
+// `params` of the pass in the manifest
+@group(0) @binding(3)
+var<uniform> params: vec4<f32>;
+// Binding 4 is the `scratch` buffer of a compute drawing, if it has one
+
+// Bilinearly filtered input at `uv`, clamped to the edges
+fn sample_input(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(input, input_sampler, uv, 0.0);
+}
+
+// Input at `pixel`, clamped to the edges
+fn load_input(pixel: vec2<i32>) -> vec4<f32> {
+    let last = vec2<i32>(textureDimensions(input)) - 1;
+    return textureLoad(input, clamp(pixel, vec2<i32>(0), last), 0);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let size = textureDimensions(output);
+    if gid.x >= size.x || gid.y >= size.y {
+        return;
+    }
+    textureStore(output, vec2<i32>(gid.xy), effect(gid.xy));
+}
@@ -0,0 +1,13 @@
+// Appended to compute shaders defining `fn predicate() -> bool`.
+// Writes the workgroup counts of the indirect drawing dispatch, zero when `predicate` returns false
+
+@group(2) @binding(0)
+var<storage, read_write> dispatch_args: array<u32, 3>;
+
+@compute @workgroup_size(1)
+fn write_dispatch_args() {
+    let run = predicate();
+    dispatch_args[0] = select(0u, (IMAGE_WIDTH + WORKGROUP_SIZE_X - 1u) / WORKGROUP_SIZE_X, run);
+    dispatch_args[1] = (IMAGE_HEIGHT + WORKGROUP_SIZE_Y - 1u) / WORKGROUP_SIZE_Y;
+    dispatch_args[2] = 1u;
+}
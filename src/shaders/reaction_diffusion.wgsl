@@ -0,0 +1,67 @@
+// Gray-Scott reaction-diffusion.
+// The state holds the concentration of chemical A in `x` and chemical B in `y`,
+// `step` advances the simulation once, `colorize` turns the state into a visible image.
+// `state_out` is declared in the format of the state the adapter supports, see src/reaction_diffusion.rs
+
+struct Params {
+    feed: f32,
+    kill: f32,
+    diffuse_a: f32,
+    diffuse_b: f32,
+};
+
+@group(0) @binding(0)
+var state_in: texture_2d<f32>;
+@group(0) @binding(2)
+var<uniform> params: Params;
+@group(0) @binding(3)
+var out_image: texture_storage_2d<rgba16float, write>;
+
+// Wraps around the edges, so the pattern tiles
+fn load(position: vec2<i32>) -> vec2<f32> {
+    let size = vec2<i32>(textureDimensions(state_in));
+    return textureLoad(state_in, (position + size) % size, 0).xy;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn step(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let size = textureDimensions(state_in);
+    if gid.x >= size.x || gid.y >= size.y {
+        return;
+    }
+
+    let p = vec2<i32>(gid.xy);
+    let center = load(p);
+
+    var laplacian = -center;
+    laplacian += 0.2 * (load(p + vec2<i32>(1, 0)) + load(p - vec2<i32>(1, 0))
+        + load(p + vec2<i32>(0, 1)) + load(p - vec2<i32>(0, 1)));
+    laplacian += 0.05 * (load(p + vec2<i32>(1, 1)) + load(p - vec2<i32>(1, 1))
+        + load(p + vec2<i32>(1, -1)) + load(p - vec2<i32>(1, -1)));
+
+    let a = center.x;
+    let b = center.y;
+    let reaction = a * b * b;
+
+    let next = vec2<f32>(
+        a + params.diffuse_a * laplacian.x - reaction + params.feed * (1.0 - a),
+        b + params.diffuse_b * laplacian.y + reaction - (params.kill + params.feed) * b,
+    );
+    textureStore(state_out, p, vec4<f32>(clamp(next, vec2<f32>(0.0), vec2<f32>(1.0)), 0.0, 0.0));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn colorize(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let size = textureDimensions(state_in);
+    if gid.x >= size.x || gid.y >= size.y {
+        return;
+    }
+
+    let state = textureLoad(state_in, vec2<i32>(gid.xy), 0).xy;
+    let t = smoothstep(0.05, 0.35, state.y);
+
+    let background = vec3<f32>(0.96, 0.93, 0.86);
+    let foreground = vec3<f32>(0.08, 0.18, 0.32);
+    let color = mix(background, foreground, t);
+    textureStore(out_image, vec2<i32>(gid.xy), vec4<f32>(color, 1.0));
+}
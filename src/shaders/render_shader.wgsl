@@ -3,6 +3,52 @@ struct VertexOutput {
     @location(0) uv: vec2<f32>
 };
 
+// Mirrors `TonemapParams` in src/tonemap.rs
+struct Tonemapping {
+    curve: u32,
+    exposure: f32,
+    transfer: u32,
+    progress: f32,
+    selection: vec4<f32>,
+    status: vec4<f32>,
+    transition: u32,
+    histogram_shown: u32,
+    // Four bins per element, see `Histogram::bars`
+    histogram: array<vec4<f32>, 16>,
+};
+
+// Mirrors `Camera` in src/globals.rs
+struct Camera {
+    position: vec3<f32>,
+    fov: f32,
+    forward: vec3<f32>,
+    right: vec3<f32>,
+    up: vec3<f32>,
+};
+
+// Mirrors `Preview` in src/render.rs
+struct Preview {
+    camera: Camera,
+    enabled: u32,
+};
+
+// Mirrors `QuadTransform` in src/render.rs
+struct QuadTransform {
+    center: vec2<f32>,
+    half_size: vec2<f32>,
+    rotation: f32,
+    aspect: f32,
+    tonemapped: u32,
+};
+
+// Height of the status strip, in texture coordinates
+const STATUS_STRIP_HEIGHT: f32 = 0.006;
+// Width of the soft edge of the wipe transition, in texture coordinates
+const WIPE_EDGE: f32 = 0.05;
+// Corners of the histogram, in texture coordinates
+const HISTOGRAM_MIN: vec2<f32> = vec2<f32>(0.02, 0.74);
+const HISTOGRAM_MAX: vec2<f32> = vec2<f32>(0.34, 0.96);
+
 @vertex
 fn vs_main(@location(0) pos: vec2<f32>, @location(1) uv: vec2<f32>) -> VertexOutput {
     var out: VertexOutput;
@@ -15,8 +61,174 @@ fn vs_main(@location(0) pos: vec2<f32>, @location(1) uv: vec2<f32>) -> VertexOut
 var my_tex: texture_2d<f32>;
 @group(0) @binding(1)
 var my_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> tonemapping: Tonemapping;
+// Affordances drawn by the `overlay` of the drawing shader, premultiplied and shown on screen only
+@group(0) @binding(3)
+var overlay: texture_2d<f32>;
+// Faces drawn by the `cubemap` of the drawing shader, looked at through `preview.camera` when enabled
+@group(0) @binding(4)
+var cubemap: texture_cube<f32>;
+@group(0) @binding(5)
+var<uniform> preview: Preview;
+// Placement of the quad drawn by `vs_quad` and `fs_quad`, which only bind it along with bindings 0 to 2
+@group(0) @binding(6)
+var<uniform> quad: QuadTransform;
+
+// Like `camera_ray` of drawing shaders, with the image size of the source texture
+fn preview_direction(uv: vec2<f32>) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(my_tex));
+    let centered = (uv - 0.5) * size / size.y;
+    let camera = preview.camera;
+    let scale = 2.0 * tan(camera.fov * 0.5);
+    return normalize(camera.forward + (centered.x * camera.right - centered.y * camera.up) * scale);
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return (color * (a * color + b)) / (color * (c * color + d) + e);
+}
+
+fn exponential(color: vec3<f32>) -> vec3<f32> {
+    return 1.0 - exp(-color);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+// Match the encoding of the surface, see `ColorSpace::transfer`
+fn transfer(color: vec3<f32>) -> vec3<f32> {
+    switch tonemapping.transfer {
+        case 1u: {
+            return linear_to_srgb(color);
+        }
+        case 2u: {
+            return srgb_to_linear(color);
+        }
+        default: {
+            return color;
+        }
+    }
+}
+
+// Random value in [0, 1) for a pixel, the order pixels switch in while dissolving
+fn dissolve_noise(pixel: vec2<f32>) -> f32 {
+    var h = u32(pixel.x) * 1973u + u32(pixel.y) * 9277u;
+    h = (h ^ (h >> 15u)) * 0x2c1b3c6du;
+    h = (h ^ (h >> 12u)) * 0x297a2d39u;
+    h ^= h >> 15u;
+    return f32(h >> 8u) / 16777216.0;
+}
+
+// How much of the image covers the previous scene at `uv`, see `Transition` in src/playlist.rs
+fn transition_alpha(uv: vec2<f32>, pixel: vec2<f32>) -> f32 {
+    let progress = tonemapping.progress;
+    switch tonemapping.transition {
+        case 1u: {
+            return clamp((progress * (1.0 + WIPE_EDGE) - uv.x) / WIPE_EDGE, 0.0, 1.0);
+        }
+        case 2u: {
+            return select(0.0, 1.0, dissolve_noise(pixel) < progress);
+        }
+        default: {
+            return progress;
+        }
+    }
+}
+
+// The histogram as bars over a dimmed background, `mapped` outside of it
+fn draw_histogram(uv: vec2<f32>, mapped: vec3<f32>) -> vec3<f32> {
+    if any(uv < HISTOGRAM_MIN) || any(uv >= HISTOGRAM_MAX) {
+        return mapped;
+    }
+    let position = (uv - HISTOGRAM_MIN) / (HISTOGRAM_MAX - HISTOGRAM_MIN);
+    let bin = u32(position.x * 64.0);
+    let height = tonemapping.histogram[bin / 4u][bin % 4u];
+    if 1.0 - position.y < height {
+        return vec3<f32>(0.85);
+    }
+    return mapped * 0.25;
+}
+
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    let exposed = max(color * tonemapping.exposure, vec3<f32>(0.0));
+    var mapped: vec3<f32>;
+    switch tonemapping.curve {
+        case 1u: {
+            mapped = reinhard(exposed);
+        }
+        case 2u: {
+            mapped = aces(exposed);
+        }
+        case 3u: {
+            mapped = exponential(exposed);
+        }
+        default: {
+            mapped = exposed;
+        }
+    }
+    return clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(my_tex, my_sampler, in.uv);
+    // Both sampled in uniform control flow, selected afterwards
+    let image_color = textureSample(my_tex, my_sampler, in.uv);
+    let preview_color = textureSample(cubemap, my_sampler, preview_direction(in.uv));
+    let color = select(image_color, preview_color, preview.enabled != 0u);
+    let overlay_color = textureSample(overlay, my_sampler, in.uv);
+    var mapped = tonemap(color.rgb);
+    mapped = mapped * (1.0 - overlay_color.a) + overlay_color.rgb;
+    let selection = tonemapping.selection;
+    let selected = all(in.uv >= selection.xy) && all(in.uv <= selection.zw);
+    if selection.z > selection.x && !selected {
+        mapped *= 0.4;
+    }
+    if tonemapping.histogram_shown != 0u {
+        mapped = draw_histogram(in.uv, mapped);
+    }
+    let status = tonemapping.status;
+    if in.uv.y > 1.0 - STATUS_STRIP_HEIGHT {
+        mapped = mix(mapped, status.rgb, status.a);
+    }
+    return vec4<f32>(transfer(mapped), color.a * transition_alpha(in.uv, in.position.xy));
+}
+
+// Corners of a triangle strip, turned in window pixels rather than clip space so rotating doesn't shear
+@vertex
+fn vs_quad(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u)) * 2.0 - 1.0;
+    let local = corner * quad.half_size * vec2<f32>(quad.aspect, 1.0);
+    let c = cos(quad.rotation);
+    let s = sin(quad.rotation);
+    let rotated = vec2<f32>(local.x * c + local.y * s, local.y * c - local.x * s);
+    var out: VertexOutput;
+    out.position = vec4<f32>(quad.center + rotated / vec2<f32>(quad.aspect, 1.0), 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x * 0.5 + 0.5, 0.5 - corner.y * 0.5);
+    return out;
+}
+
+@fragment
+fn fs_quad(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(my_tex, my_sampler, in.uv);
+    let mapped = select(color.rgb, tonemap(color.rgb), quad.tonemapped != 0u);
+    return vec4<f32>(transfer(mapped), color.a);
 }
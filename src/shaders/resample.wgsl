@@ -0,0 +1,7 @@
+// Post effect the render graph inserts between passes running at different resolutions,
+// bilinearly filtering the previous pass to the size of the next one
+
+fn effect(pixel: vec2<u32>) -> vec4<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(textureDimensions(output));
+    return sample_input(uv);
+}
@@ -0,0 +1,79 @@
+// Prefix sums of `data` in place, see src/scan.rs.
+// `scan_blocks` scans every block of `WORKGROUP_SIZE` values in workgroup memory and writes the
+// total of each block to `sums`, which is scanned the same way one level up. `add_offsets` then
+// adds the scanned totals of the blocks before each block to its values.
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+struct Level {
+    len: u32,
+    // Whether each value becomes the sum of the values before it, without itself
+    exclusive: u32,
+    // Blocks of `len`, the last row of workgroups overhangs them when they don't fit in one row
+    blocks: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read_write> data: array<u32>;
+@group(0) @binding(1)
+var<storage, read_write> sums: array<u32>;
+@group(0) @binding(2)
+var<uniform> level: Level;
+
+var<workgroup> partial: array<u32, WORKGROUP_SIZE>;
+
+fn block_index(workgroup: vec3<u32>, workgroups: vec3<u32>) -> u32 {
+    return workgroup.y * workgroups.x + workgroup.x;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn scan_blocks(
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) workgroup: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+) {
+    let block = block_index(workgroup, workgroups);
+    if block >= level.blocks {
+        return;
+    }
+    let index = block * WORKGROUP_SIZE + local;
+    var value = 0u;
+    if index < level.len {
+        value = data[index];
+    }
+    partial[local] = value;
+    workgroupBarrier();
+    for (var offset = 1u; offset < WORKGROUP_SIZE; offset *= 2u) {
+        var sum = partial[local];
+        if local >= offset {
+            sum += partial[local - offset];
+        }
+        workgroupBarrier();
+        partial[local] = sum;
+        workgroupBarrier();
+    }
+
+    if index < level.len {
+        data[index] = select(partial[local], partial[local] - value, level.exclusive != 0u);
+    }
+    if local == WORKGROUP_SIZE - 1u {
+        sums[block] = partial[local];
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn add_offsets(
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) workgroup: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+) {
+    let block = block_index(workgroup, workgroups);
+    if block >= level.blocks {
+        return;
+    }
+    let index = block * WORKGROUP_SIZE + local;
+    if block > 0u && index < level.len {
+        data[index] += sums[block - 1u];
+    }
+}
@@ -0,0 +1,130 @@
+// Sphere tracer of a signed distance field, viewed through the `camera`
+// the app moves with the mouse and W, A, S and D.
+// Replace `scene` to march a different shape, it returns the distance to the closest surface
+// and the index of its material.
+
+const MAX_STEPS: i32 = 128;
+const MAX_DISTANCE: f32 = 50.0;
+const SURFACE_DISTANCE: f32 = 0.001;
+const LIGHT_DIRECTION: vec3<f32> = vec3<f32>(0.577, 0.577, 0.577);
+
+fn sd_sphere(p: vec3<f32>, radius: f32) -> f32 {
+    return length(p) - radius;
+}
+
+fn sd_box(p: vec3<f32>, size: vec3<f32>) -> f32 {
+    let q = abs(p) - size;
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0);
+}
+
+fn sd_torus(p: vec3<f32>, radii: vec2<f32>) -> f32 {
+    let q = vec2<f32>(length(p.xz) - radii.x, p.y);
+    return length(q) - radii.y;
+}
+
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    let h = clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
+    return mix(b, a, h) - k * h * (1.0 - h);
+}
+
+// Distance and material: 0 for the floor, 1 for the blob, 2 for the torus
+fn scene(p: vec3<f32>) -> vec2<f32> {
+    let floor_distance = p.y + 0.5;
+    let bob = 0.15 * sin(globals.time * 1.5);
+    let blob = smooth_min(
+        sd_sphere(p - vec3<f32>(-0.6, bob, 0.0), 0.5),
+        sd_box(p - vec3<f32>(-0.6, -0.3, 0.0), vec3<f32>(0.35, 0.2, 0.35)) - 0.05,
+        0.2 + 0.3 * globals.params.x,
+    );
+    let angle = globals.time * 0.5;
+    let rotation = mat2x2<f32>(cos(angle), -sin(angle), sin(angle), cos(angle));
+    let torus_p = p - vec3<f32>(0.8, 0.1, -0.2);
+    let spun = vec3<f32>(torus_p.x, rotation * torus_p.yz);
+    let torus = sd_torus(spun, vec2<f32>(0.4, 0.12 + 0.1 * globals.params.y));
+
+    var closest = vec2<f32>(floor_distance, 0.0);
+    if blob < closest.x {
+        closest = vec2<f32>(blob, 1.0);
+    }
+    if torus < closest.x {
+        closest = vec2<f32>(torus, 2.0);
+    }
+    return closest;
+}
+
+fn normal(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2<f32>(0.001, 0.0);
+    return normalize(vec3<f32>(
+        scene(p + e.xyy).x - scene(p - e.xyy).x,
+        scene(p + e.yxy).x - scene(p - e.yxy).x,
+        scene(p + e.yyx).x - scene(p - e.yyx).x,
+    ));
+}
+
+// Penumbra from how closely the ray towards the light passes other surfaces
+fn soft_shadow(origin: vec3<f32>, direction: vec3<f32>) -> f32 {
+    var shadow = 1.0;
+    var t = 0.02;
+    for (var i = 0; i < 48 && t < 10.0; i++) {
+        let distance = scene(origin + direction * t).x;
+        if distance < SURFACE_DISTANCE {
+            return 0.0;
+        }
+        shadow = min(shadow, 8.0 * distance / t);
+        t += clamp(distance, 0.02, 0.5);
+    }
+    return clamp(shadow, 0.0, 1.0);
+}
+
+fn ambient_occlusion(p: vec3<f32>, n: vec3<f32>) -> f32 {
+    var occlusion = 0.0;
+    var weight = 1.0;
+    for (var i = 1; i <= 5; i++) {
+        let offset = 0.03 * f32(i);
+        occlusion += weight * (offset - scene(p + n * offset).x);
+        weight *= 0.7;
+    }
+    return clamp(1.0 - 3.0 * occlusion, 0.0, 1.0);
+}
+
+fn albedo(material: f32, p: vec3<f32>) -> vec3<f32> {
+    if material < 0.5 {
+        let checker = (i32(floor(p.x)) + i32(floor(p.z))) & 1;
+        return select(vec3<f32>(0.45), vec3<f32>(0.7), checker == 0);
+    }
+    if material < 1.5 {
+        return vec3<f32>(0.85, 0.3, 0.2);
+    }
+    return vec3<f32>(0.2, 0.45, 0.85);
+}
+
+fn sky(direction: vec3<f32>) -> vec3<f32> {
+    let t = 0.5 * (direction.y + 1.0);
+    return mix(vec3<f32>(0.9, 0.9, 0.95), vec3<f32>(0.35, 0.5, 0.8), t);
+}
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    let direction = camera_ray(pixel);
+    var t = 0.0;
+    var hit = vec2<f32>(MAX_DISTANCE, -1.0);
+    for (var i = 0; i < MAX_STEPS && t < MAX_DISTANCE; i++) {
+        let step = scene(camera.position + direction * t);
+        if step.x < SURFACE_DISTANCE * t {
+            hit = vec2<f32>(t, step.y);
+            break;
+        }
+        t += step.x;
+    }
+    if hit.y < 0.0 {
+        return vec4<f32>(sky(direction), 1.0);
+    }
+
+    let p = camera.position + direction * hit.x;
+    let n = normal(p);
+    let diffuse = max(dot(n, LIGHT_DIRECTION), 0.0) * soft_shadow(p + n * 0.002, LIGHT_DIRECTION);
+    let ambient = (0.5 + 0.5 * n.y) * ambient_occlusion(p, n);
+    var color = albedo(hit.y, p) * (vec3<f32>(2.0, 1.9, 1.7) * diffuse + 0.3 * sky(n) * ambient);
+    // Fade distant surfaces into the sky
+    color = mix(color, sky(direction), 1.0 - exp(-0.002 * hit.x * hit.x));
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,26 @@
+// Known-good kernels for `--self-test`, each checked against the pattern in src/self_test.rs
+
+@group(0) @binding(0)
+var unorm_out: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(1)
+var float_out: texture_storage_2d<rgba32float, write>;
+@group(0) @binding(2)
+var<storage, read_write> counter: atomic<u32>;
+
+@compute @workgroup_size(8, 8)
+fn write_unorm(@builtin(global_invocation_id) id: vec3<u32>) {
+    let color = vec4<f32>(f32(id.x), f32(id.y), f32((id.x + id.y) % 256u), 255.0) / 255.0;
+    textureStore(unorm_out, vec2<i32>(id.xy), color);
+}
+
+@compute @workgroup_size(8, 8)
+fn write_float(@builtin(global_invocation_id) id: vec3<u32>) {
+    let x = f32(id.x);
+    let y = f32(id.y);
+    textureStore(float_out, vec2<i32>(id.xy), vec4<f32>(x + 0.25, y * 1000.5, -x, 1e-3));
+}
+
+@compute @workgroup_size(8, 8)
+fn count(@builtin(global_invocation_id) id: vec3<u32>) {
+    atomicAdd(&counter, 1u);
+}
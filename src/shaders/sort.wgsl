@@ -0,0 +1,72 @@
+// Bitonic sort of `keys`, and of `values` along with them, see src/sort.rs.
+// Every dispatch is one step of the sorting network, comparing pairs `distance` apart and swapping
+// them so the smaller key comes first. The first step of every merge compares mirrored pairs instead,
+// so all comparisons go the same way and the keys past `len` can be treated as larger than any other.
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+struct Step {
+    distance: u32,
+    // Whether the pairs are mirrored around the middle of blocks of `2 * distance` keys
+    mirror: u32,
+    len: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read_write> keys: array<u32>;
+@group(0) @binding(1)
+var<storage, read_write> values: array<u32>;
+@group(0) @binding(2)
+var<uniform> step: Step;
+
+// Indices of the pair compared by invocation `index`, the lower one first
+fn pair(index: u32) -> vec2<u32> {
+    let block = index / step.distance * 2u * step.distance;
+    let offset = index % step.distance;
+    if step.mirror != 0u {
+        return vec2<u32>(block + offset, block + 2u * step.distance - 1u - offset);
+    }
+    return vec2<u32>(block + offset, block + offset + step.distance);
+}
+
+fn invocation(gid: vec3<u32>, workgroups: vec3<u32>) -> u32 {
+    return gid.y * workgroups.x * WORKGROUP_SIZE + gid.x;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn sort_keys(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+) {
+    let indices = pair(invocation(gid, workgroups));
+    if indices.y >= step.len {
+        return;
+    }
+    let low = keys[indices.x];
+    let high = keys[indices.y];
+    if low > high {
+        keys[indices.x] = high;
+        keys[indices.y] = low;
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn sort_pairs(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) workgroups: vec3<u32>,
+) {
+    let indices = pair(invocation(gid, workgroups));
+    if indices.y >= step.len {
+        return;
+    }
+    let low = keys[indices.x];
+    let high = keys[indices.y];
+    if low > high {
+        keys[indices.x] = high;
+        keys[indices.y] = low;
+        let value = values[indices.x];
+        values[indices.x] = values[indices.y];
+        values[indices.y] = value;
+    }
+}
@@ -0,0 +1,58 @@
+// Appended to compute shaders defining `fn classify(tile: vec2<u32>) -> bool` and `fn fill(pixel: vec2<u32>) -> vec4<f32>`.
+// Tiles are the areas covered by one workgroup. `classify_tiles` fills those `classify` rejects
+// and lists the others, `draw_tiles` then runs `draw` on the listed ones only
+
+struct TileList {
+    // Workgroup counts of the indirect `draw_tiles` dispatch, `count` is reset every frame
+    count: atomic<u32>,
+    y: u32,
+    z: u32,
+    // Listed tiles as `x | y << 16`
+    tiles: array<u32>,
+}
+
+// `TileList` as read by `draw_tiles`
+struct DrawnTiles {
+    count: u32,
+    y: u32,
+    z: u32,
+    tiles: array<u32>,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> tile_list: TileList;
+
+@group(2) @binding(1)
+var<storage, read> drawn_tiles: DrawnTiles;
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn classify_tiles(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(workgroup_id) tile: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    if classify(tile.xy) {
+        if index == 0u {
+            let slot = atomicAdd(&tile_list.count, 1u);
+            tile_list.tiles[slot] = tile.x | (tile.y << 16u);
+        }
+        return;
+    }
+    if gid.x < IMAGE_WIDTH && gid.y < IMAGE_HEIGHT {
+        textureStore(out_image, vec2<i32>(gid.xy), fill(gid.xy + image_offset()));
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y)
+fn draw_tiles(
+    @builtin(workgroup_id) slot: vec3<u32>,
+    @builtin(local_invocation_id) local: vec3<u32>,
+) {
+    let encoded = drawn_tiles.tiles[slot.x];
+    let tile = vec2<u32>(encoded & 0xffffu, encoded >> 16u);
+    let pixel = tile * vec2<u32>(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y) + local.xy;
+    if pixel.x >= IMAGE_WIDTH || pixel.y >= IMAGE_HEIGHT {
+        return;
+    }
+    textureStore(out_image, vec2<i32>(pixel), draw(pixel + image_offset()));
+}
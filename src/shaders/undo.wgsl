@@ -0,0 +1,255 @@
+// Paint preset: keeps what a stroke changed as compressed tile deltas, so undo takes little memory.
+// `compress` XORs the bits of every 32×32 tile of the canvas with its copy from before the stroke.
+// A tile that differs is stored as a mask with a bit per word of the delta, followed by the words
+// that aren't zero, so the pixels the stroke left alone take no room.
+// `apply` XORs the deltas of a step back into the canvas, which undoes it, and doing so again redoes it.
+//
+// The steps follow each other in `pool`, used as a ring of words: a step is the number of tiles it stored,
+// the index and data offset of each of them, then their data. `begin` and `finish` run on a single invocation
+// around `compress` to place the step after the last one.
+
+// Mirrors `TILE_SIZE` in src/undo.rs
+const TILE_SIZE: u32 = 32u;
+// Mirrors `MAX_STEPS` in src/undo.rs
+const MAX_STEPS: u32 = 1024u;
+// Mirrors `MASK_WORDS` in src/undo.rs, one bit for each of the two words of a pixel of the tile
+const MASK_WORDS: u32 = 64u;
+const NO_STEP: u32 = 0xffffffffu;
+const WORKGROUP_SIZE: u32 = 256u;
+const PIXELS_PER_INVOCATION: u32 = TILE_SIZE * TILE_SIZE / WORKGROUP_SIZE;
+const WORDS_PER_INVOCATION: u32 = 2u * PIXELS_PER_INVOCATION;
+
+struct State {
+    // Where the step recorded last starts in the pool, and what it stored, read back by src/undo.rs
+    start: u32,
+    tiles: atomic<u32>,
+    words: atomic<u32>,
+    overflow: atomic<u32>,
+    // Where the next step starts
+    head: u32,
+    // Set when a step didn't fit, the steps are useless until src/undo.rs starts over
+    broken: u32,
+    _padding: vec2<u32>,
+    // Start of every step, by its slot
+    starts: array<u32, MAX_STEPS>,
+}
+
+struct Params {
+    // Slot of the step recorded or applied
+    step: u32,
+    // Slot of the first step dropped before recording, as they could be redone, or `NO_STEP`
+    rewind: u32,
+}
+
+// The canvas as it was after the last step, only read by `compress`
+@group(0) @binding(0)
+var base: texture_2d<f32>;
+@group(0) @binding(1)
+var canvas: texture_2d<f32>;
+@group(0) @binding(2)
+var<storage, read_write> pool: array<u32>;
+@group(0) @binding(3)
+var<storage, read_write> state: State;
+@group(0) @binding(4)
+var<uniform> params: Params;
+// The canvas with a step applied, only written by `apply`
+@group(0) @binding(5)
+var applied: texture_storage_2d<rgba16float, write>;
+
+// Set bits of the delta words of the tile
+var<workgroup> mask: array<atomic<u32>, MASK_WORDS>;
+// Set bits in the mask words before each one, where the stored words of its bits start
+var<workgroup> ranks: array<u32, MASK_WORDS>;
+var<workgroup> stored_words: u32;
+// Pool index the data of the tile starts at, or `NO_STEP` if it isn't stored
+var<workgroup> data_start: u32;
+// Index of the tile applied and where its data starts, `x` is `NO_STEP` for workgroups without one
+var<workgroup> entry: vec2<u32>;
+
+fn tiles_x() -> u32 {
+    return (textureDimensions(canvas).x + TILE_SIZE - 1u) / TILE_SIZE;
+}
+
+fn tile_count() -> u32 {
+    let size = textureDimensions(canvas);
+    return tiles_x() * ((size.y + TILE_SIZE - 1u) / TILE_SIZE);
+}
+
+// Words before the data of a step: its tile count, then an index and offset for every tile it could store
+fn index_words() -> u32 {
+    return 1u + 2u * tile_count();
+}
+
+fn wrap(index: u32) -> u32 {
+    return index % arrayLength(&pool);
+}
+
+// Pixel `index` of the tile, row by row
+fn tile_pixel(tile: u32, index: u32) -> vec2<u32> {
+    let corner = vec2(tile % tiles_x(), tile / tiles_x()) * TILE_SIZE;
+    return corner + vec2(index % TILE_SIZE, index / TILE_SIZE);
+}
+
+fn pixel_bits(color: vec4<f32>) -> vec2<u32> {
+    return vec2(pack2x16float(color.rg), pack2x16float(color.ba));
+}
+
+// Whether delta word `word` of a tile isn't zero, `mask_word` is the word of the mask holding its bit
+fn has_word(word: u32, mask_word: u32) -> bool {
+    return ((mask_word >> (word % 32u)) & 1u) != 0u;
+}
+
+// Where delta word `word` of a tile is stored, counted from the end of the mask
+fn word_rank(word: u32, mask_word: u32) -> u32 {
+    return ranks[word / 32u] + countOneBits(mask_word & ((1u << (word % 32u)) - 1u));
+}
+
+@compute @workgroup_size(1)
+fn begin() {
+    if params.rewind != NO_STEP {
+        state.head = state.starts[params.rewind];
+    }
+    state.start = state.head;
+    state.starts[params.step] = state.head;
+    atomicStore(&state.tiles, 0u);
+    atomicStore(&state.words, 0u);
+    atomicStore(&state.overflow, 0u);
+}
+
+// One workgroup per tile
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn compress(
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    let size = textureDimensions(canvas);
+    let tile = wid.y * tiles_x() + wid.x;
+    if local < MASK_WORDS {
+        atomicStore(&mask[local], 0u);
+    }
+    workgroupBarrier();
+
+    var delta: array<u32, WORDS_PER_INVOCATION>;
+    for (var i = 0u; i < PIXELS_PER_INVOCATION; i++) {
+        let index = local * PIXELS_PER_INVOCATION + i;
+        let pixel = tile_pixel(tile, index);
+        var bits = vec2(0u);
+        if all(pixel < size) {
+            bits = pixel_bits(textureLoad(base, pixel, 0)) ^ pixel_bits(textureLoad(canvas, pixel, 0));
+        }
+        for (var part = 0u; part < 2u; part++) {
+            let word = 2u * index + part;
+            delta[2u * i + part] = bits[part];
+            if bits[part] != 0u {
+                atomicOr(&mask[word / 32u], 1u << (word % 32u));
+            }
+        }
+    }
+    workgroupBarrier();
+
+    if local < MASK_WORDS {
+        var rank = 0u;
+        for (var j = 0u; j < local; j++) {
+            rank += countOneBits(atomicLoad(&mask[j]));
+        }
+        ranks[local] = rank;
+        if local == MASK_WORDS - 1u {
+            stored_words = rank + countOneBits(atomicLoad(&mask[local]));
+        }
+    }
+    let stored = workgroupUniformLoad(&stored_words);
+    if stored == 0u {
+        return;
+    }
+
+    if local == 0u {
+        let words = MASK_WORDS + stored;
+        let offset = atomicAdd(&state.words, words);
+        if index_words() + offset + words > arrayLength(&pool) {
+            atomicStore(&state.overflow, 1u);
+            data_start = NO_STEP;
+        } else {
+            let slot = atomicAdd(&state.tiles, 1u);
+            pool[wrap(state.start + 1u + 2u * slot)] = tile;
+            pool[wrap(state.start + 2u + 2u * slot)] = offset;
+            data_start = state.start + index_words() + offset;
+        }
+    }
+    let start = workgroupUniformLoad(&data_start);
+    if start == NO_STEP {
+        return;
+    }
+
+    if local < MASK_WORDS {
+        pool[wrap(start + local)] = atomicLoad(&mask[local]);
+    }
+    for (var i = 0u; i < WORDS_PER_INVOCATION; i++) {
+        let word = local * WORDS_PER_INVOCATION + i;
+        let mask_word = atomicLoad(&mask[word / 32u]);
+        if has_word(word, mask_word) {
+            pool[wrap(start + MASK_WORDS + word_rank(word, mask_word))] = delta[i];
+        }
+    }
+}
+
+@compute @workgroup_size(1)
+fn finish() {
+    pool[wrap(state.start)] = atomicLoad(&state.tiles);
+    state.head = wrap(state.start + index_words() + atomicLoad(&state.words));
+    if atomicLoad(&state.overflow) != 0u {
+        state.broken = 1u;
+    }
+}
+
+// One workgroup per tile the step could have stored, those past the ones it did return right away.
+// `applied` has to hold a copy of `canvas` already, the tiles not stored are left as they are
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn apply(
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    if local == 0u {
+        let start = state.starts[params.step];
+        let slot = wid.y * tiles_x() + wid.x;
+        entry = vec2(NO_STEP, 0u);
+        if state.broken == 0u && slot < pool[wrap(start)] {
+            entry = vec2(
+                pool[wrap(start + 1u + 2u * slot)],
+                start + index_words() + pool[wrap(start + 2u + 2u * slot)],
+            );
+        }
+    }
+    let tile_entry = workgroupUniformLoad(&entry);
+    if tile_entry.x == NO_STEP {
+        return;
+    }
+    let tile = tile_entry.x;
+    let start = tile_entry.y;
+
+    if local < MASK_WORDS {
+        var rank = 0u;
+        for (var j = 0u; j < local; j++) {
+            rank += countOneBits(pool[wrap(start + j)]);
+        }
+        ranks[local] = rank;
+    }
+    workgroupBarrier();
+
+    let size = textureDimensions(canvas);
+    for (var i = 0u; i < PIXELS_PER_INVOCATION; i++) {
+        let index = local * PIXELS_PER_INVOCATION + i;
+        let pixel = tile_pixel(tile, index);
+        if any(pixel >= size) {
+            continue;
+        }
+        var bits = pixel_bits(textureLoad(canvas, pixel, 0));
+        for (var part = 0u; part < 2u; part++) {
+            let word = 2u * index + part;
+            let mask_word = pool[wrap(start + word / 32u)];
+            if has_word(word, mask_word) {
+                bits[part] ^= pool[wrap(start + MASK_WORDS + word_rank(word, mask_word))];
+            }
+        }
+        textureStore(applied, pixel, vec4(unpack2x16float(bits.x), unpack2x16float(bits.y)));
+    }
+}
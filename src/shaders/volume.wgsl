@@ -0,0 +1,72 @@
+// Volumes for shaders defining `fn volume(voxel: vec3<u32>) -> vec4<f32>`.
+// `fill_volume` evaluates it for every voxel of a VOLUME_SIZE³ cube in its own pass, one texture layer per slice,
+// after which `draw` can look at the result with `sample_volume`, `volume_slice` or `volume_raymarch`.
+// The color goes in `rgb` and the density per unit length in `a`.
+// The volume pass binds nothing in group 0, so `volume` reads `globals`, `camera` and `audio` only.
+
+@group(2) @binding(0)
+var volume_image: texture_storage_2d_array<rgba16float, write>;
+
+@compute @workgroup_size(4, 4, 4)
+fn fill_volume(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if any(gid >= vec3<u32>(VOLUME_SIZE)) {
+        return;
+    }
+    textureStore(volume_image, gid.xy, gid.z, volume(gid));
+}
+
+// The volume filtered between voxels, at `uvw` from 0 to 1.
+// Layers are filtered within, and blended in between by hand
+fn sample_volume_uvw(uvw: vec3<f32>) -> vec4<f32> {
+    let layer = clamp(uvw.z * f32(VOLUME_SIZE) - 0.5, 0.0, f32(VOLUME_SIZE - 1u));
+    let below = u32(layer);
+    let above = min(below + 1u, VOLUME_SIZE - 1u);
+    return mix(
+        textureSampleLevel(volume_texture, volume_sampler, uvw.xy, below, 0.0),
+        textureSampleLevel(volume_texture, volume_sampler, uvw.xy, above, 0.0),
+        fract(layer),
+    );
+}
+
+// The volume filtered between voxels, at `position` within the cube from -1 to 1
+fn sample_volume(position: vec3<f32>) -> vec4<f32> {
+    return sample_volume_uvw(position * 0.5 + 0.5);
+}
+
+// The slice at `depth` from 0 to 1, fitted into the middle of the image
+fn volume_slice(pixel: vec2<u32>, depth: f32) -> vec4<f32> {
+    let size = min(globals.resolution.x, globals.resolution.y);
+    let uv = (vec2<f32>(pixel) + 0.5 - 0.5 * globals.resolution) / size + 0.5;
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return vec4<f32>(0.0);
+    }
+    return sample_volume_uvw(vec3<f32>(uv, depth));
+}
+
+// Composited front to back along the ray of the `camera` through `pixel`, one voxel per step.
+// Returns the premultiplied color and the opacity
+fn volume_raymarch(pixel: vec2<u32>) -> vec4<f32> {
+    let direction = camera_ray(pixel);
+    // Keeps the reciprocal finite for rays along the axes
+    let safe = select(direction, vec3<f32>(1e-6), abs(direction) < vec3<f32>(1e-6));
+    let t0 = (vec3<f32>(-1.0) - camera.position) / safe;
+    let t1 = (vec3<f32>(1.0) - camera.position) / safe;
+    let near_planes = min(t0, t1);
+    let far_planes = max(t0, t1);
+    let near = max(max(near_planes.x, max(near_planes.y, near_planes.z)), 0.0);
+    let far = min(far_planes.x, min(far_planes.y, far_planes.z));
+    if near >= far {
+        return vec4<f32>(0.0);
+    }
+
+    let step = 2.0 / f32(VOLUME_SIZE);
+    var color = vec3<f32>(0.0);
+    var transmittance = 1.0;
+    for (var t = near + 0.5 * step; t < far && transmittance > 0.01; t += step) {
+        let sample = sample_volume(camera.position + direction * t);
+        let opacity = 1.0 - exp(-max(sample.a, 0.0) * step);
+        color += transmittance * opacity * sample.rgb;
+        transmittance *= 1.0 - opacity;
+    }
+    return vec4<f32>(color, 1.0 - transmittance);
+}
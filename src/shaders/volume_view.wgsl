@@ -0,0 +1,13 @@
+// `draw` of shaders only defining `volume`, showing it as `--volume-view` says.
+// Slices show the color scaled by the density, the depth follows `globals.params.y`
+
+const VOLUME_BACKGROUND: vec3<f32> = vec3<f32>(0.02, 0.02, 0.03);
+
+fn draw(pixel: vec2<u32>) -> vec4<f32> {
+    if VOLUME_SLICE {
+        let sample = volume_slice(pixel, globals.params.y);
+        return vec4<f32>(VOLUME_BACKGROUND + sample.rgb * clamp(sample.a, 0.0, 1.0), 1.0);
+    }
+    let composited = volume_raymarch(pixel);
+    return vec4<f32>(composited.rgb + (1.0 - composited.a) * VOLUME_BACKGROUND, 1.0);
+}
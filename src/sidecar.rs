@@ -0,0 +1,74 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{camera::CameraView, tonemap::Tonemapper};
+
+/// Where a shader was left off, saved next to it when the app closes and restored when it's opened again.
+/// `draw.wgsl` keeps its settings in `draw.settings.toml`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ShaderSettings {
+    pub params: [f32; 2],
+    /// `globals.time`, the clock continues from there
+    pub time: f32,
+    /// Name of the [`Tonemapper`], like `--tonemap`
+    tonemap: String,
+    pub exposure: f32,
+    /// Only for drawings viewed with the [`CameraControls`](crate::camera::CameraControls)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraView>,
+}
+
+impl ShaderSettings {
+    pub fn new(
+        params: [f32; 2],
+        time: f32,
+        tonemapper: Tonemapper,
+        exposure: f32,
+        camera: Option<CameraView>,
+    ) -> Self {
+        Self {
+            params,
+            time,
+            tonemap: tonemapper
+                .to_possible_value()
+                .expect("tonemappers have names")
+                .get_name()
+                .to_owned(),
+            exposure,
+            camera,
+        }
+    }
+
+    /// The settings saved for `shader`, if there are any
+    pub fn load(shader: &Path) -> Option<Self> {
+        let path = path(shader);
+        let contents = fs::read_to_string(&path).ok()?;
+        match toml::from_str(&contents) {
+            Ok(settings) => Some(settings),
+            Err(err) => {
+                log::warn!("Ignoring malformed {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, shader: &Path) -> io::Result<()> {
+        let contents = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path(shader), contents)
+    }
+
+    pub fn tonemapper(&self) -> Option<Tonemapper> {
+        Tonemapper::from_str(&self.tonemap, true).ok()
+    }
+}
+
+/// The sidecar file of `shader`
+fn path(shader: &Path) -> PathBuf {
+    shader.with_extension("settings.toml")
+}
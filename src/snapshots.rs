@@ -0,0 +1,160 @@
+use wgpu::*;
+
+/// Copies of a canvas texture kept on the GPU for undo and redo.
+///
+/// The copies are the layers of one texture array, used as a ring: once it is full the oldest
+/// snapshot is overwritten. Slots before `position` are the states undo goes back to,
+/// the slot at `position` holds the canvas as it is if it was saved and the ones after it can be redone
+pub struct SnapshotRing {
+    /// `None` when not even one snapshot fits into the budget
+    texture: Option<Texture>,
+    capacity: u32,
+    /// Layer of the oldest snapshot
+    start: u32,
+    /// Snapshots kept, counted from `start`
+    len: u32,
+    /// Steps that can be undone
+    position: u32,
+    width: u32,
+    height: u32,
+}
+
+impl SnapshotRing {
+    /// As many snapshots of a `width` × `height` canvas in `format` as fit into `budget` bytes
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        budget: u64,
+    ) -> Self {
+        let snapshot_size = u64::from(width)
+            * u64::from(height)
+            * u64::from(format.block_copy_size(None).unwrap_or(4));
+        let capacity = (budget / snapshot_size.max(1))
+            .min(u64::from(device.limits().max_texture_array_layers)) as u32;
+        if capacity == 0 {
+            log::warn!(
+                "A snapshot takes {} MB, more than the undo memory, nothing can be undone",
+                snapshot_size.div_ceil(1 << 20)
+            );
+        }
+        let texture = (capacity > 0).then(|| {
+            device.create_texture(&TextureDescriptor {
+                label: Some("Snapshot Texture"),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: capacity,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::COPY_SRC | TextureUsages::COPY_DST,
+                view_formats: &[],
+            })
+        });
+        Self {
+            texture,
+            capacity,
+            start: 0,
+            len: 0,
+            position: 0,
+            width,
+            height,
+        }
+    }
+
+    /// Save `canvas` before it is changed, the steps that could be redone are dropped
+    pub fn push(&mut self, encoder: &mut CommandEncoder, canvas: &Texture) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.position == self.capacity {
+            self.drop_oldest();
+        }
+        self.save(encoder, canvas, self.position);
+        self.position += 1;
+        self.len = self.position;
+    }
+
+    /// Copy the previous snapshot into `canvas`, returns false if there is none.
+    /// The canvas is saved first so it can be redone, unless the ring is full
+    pub fn undo(&mut self, encoder: &mut CommandEncoder, canvas: &Texture) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        if self.len == self.position && self.position < self.capacity {
+            self.save(encoder, canvas, self.position);
+            self.len += 1;
+        }
+        self.position -= 1;
+        self.restore(encoder, canvas, self.position);
+        true
+    }
+
+    /// Copy the snapshot undone last back into `canvas`, returns false if there is none
+    pub fn redo(&mut self, encoder: &mut CommandEncoder, canvas: &Texture) -> bool {
+        if self.position + 1 >= self.len {
+            return false;
+        }
+        self.position += 1;
+        self.restore(encoder, canvas, self.position);
+        true
+    }
+
+    /// Forget all snapshots
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+        self.position = 0;
+    }
+
+    fn drop_oldest(&mut self) {
+        self.start = (self.start + 1) % self.capacity;
+        self.len -= 1;
+        self.position -= 1;
+    }
+
+    fn save(&self, encoder: &mut CommandEncoder, canvas: &Texture, slot: u32) {
+        if let Some(texture) = &self.texture {
+            encoder.copy_texture_to_texture(
+                canvas.as_image_copy(),
+                self.layer(texture, slot),
+                self.extent(),
+            );
+        }
+    }
+
+    fn restore(&self, encoder: &mut CommandEncoder, canvas: &Texture, slot: u32) {
+        if let Some(texture) = &self.texture {
+            encoder.copy_texture_to_texture(
+                self.layer(texture, slot),
+                canvas.as_image_copy(),
+                self.extent(),
+            );
+        }
+    }
+
+    fn layer<'a>(&self, texture: &'a Texture, slot: u32) -> ImageCopyTexture<'a> {
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d {
+                x: 0,
+                y: 0,
+                z: (self.start + slot) % self.capacity,
+            },
+            aspect: TextureAspect::All,
+        }
+    }
+
+    fn extent(&self) -> Extent3d {
+        Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
+        }
+    }
+}
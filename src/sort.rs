@@ -0,0 +1,332 @@
+use std::num::NonZeroU64;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+/// Invocations per workgroup of `src/shaders/sort.wgsl`, each compares one pair
+const WORKGROUP_SIZE: u32 = 256;
+
+/// One step of the sorting network, see `Step` in `src/shaders/sort.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Step {
+    distance: u32,
+    mirror: u32,
+    len: u32,
+    _padding: u32,
+}
+
+/// Sorts `u32` keys in storage buffers on the GPU, optionally moving `u32` values along with them,
+/// e.g. particle indices by depth. Non-negative `f32`s sort by their bits, so depths can be keys as they are.
+///
+/// A bitonic sorting network with one dispatch per step, log₂ n · (log₂ n + 1) / 2 of them
+/// for n keys rounded up to a power of two, all recorded into one compute pass. The order of equal keys isn't kept
+pub struct GpuSort {
+    keys_pipeline: ComputePipeline,
+    pairs_pipeline: ComputePipeline,
+    keys_layout: BindGroupLayout,
+    pairs_layout: BindGroupLayout,
+}
+
+impl GpuSort {
+    pub fn new(device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Sort Shader"),
+            source: ShaderSource::Wgsl(include_str!("./shaders/sort.wgsl").into()),
+        });
+        let storage_buffer = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let step = BindGroupLayoutEntry {
+            binding: 2,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: NonZeroU64::new(std::mem::size_of::<Step>() as u64),
+            },
+            count: None,
+        };
+        let keys_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Sort Keys Bind Group Layout"),
+            entries: &[storage_buffer(0), step],
+        });
+        let pairs_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Sort Pairs Bind Group Layout"),
+            entries: &[storage_buffer(0), storage_buffer(1), step],
+        });
+        let create_pipeline = |layout: &BindGroupLayout, entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                compilation_options: Default::default(),
+                label: Some("Sort Pipeline"),
+                layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Sort Pipeline Layout"),
+                    bind_group_layouts: &[layout],
+                    push_constant_ranges: &[],
+                })),
+                module: &module,
+                entry_point,
+                cache: None,
+            })
+        };
+
+        Self {
+            keys_pipeline: create_pipeline(&keys_layout, "sort_keys"),
+            pairs_pipeline: create_pipeline(&pairs_layout, "sort_pairs"),
+            keys_layout,
+            pairs_layout,
+        }
+    }
+
+    /// Record sorting the first `len` keys of `keys` ascending.
+    /// `keys` is a buffer of `u32`s with [`BufferUsages::STORAGE`]
+    pub fn sort_keys(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        keys: &Buffer,
+        len: u32,
+    ) {
+        self.encode(
+            device,
+            encoder,
+            &self.keys_pipeline,
+            &self.keys_layout,
+            &[keys],
+            len,
+        );
+    }
+
+    /// Record sorting the first `len` keys of `keys` ascending and moving the `u32`s of `values` along with them
+    pub fn sort_pairs(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        keys: &Buffer,
+        values: &Buffer,
+        len: u32,
+    ) {
+        self.encode(
+            device,
+            encoder,
+            &self.pairs_pipeline,
+            &self.pairs_layout,
+            &[keys, values],
+            len,
+        );
+    }
+
+    fn encode(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        pipeline: &ComputePipeline,
+        layout: &BindGroupLayout,
+        buffers: &[&Buffer],
+        len: u32,
+    ) {
+        let steps = steps(len);
+        if steps.is_empty() {
+            return;
+        }
+        // Every dispatch reads its own step, so the steps can't be written with `Queue::write_buffer`
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as usize;
+        let mut contents = vec![0; steps.len() * alignment];
+        for (step, chunk) in steps.iter().zip(contents.chunks_exact_mut(alignment)) {
+            chunk[..std::mem::size_of::<Step>()].copy_from_slice(bytemuck::bytes_of(step));
+        }
+        let steps_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sort Steps Buffer"),
+            contents: &contents,
+            usage: BufferUsages::UNIFORM,
+        });
+        let mut entries: Vec<_> = buffers
+            .iter()
+            .zip(0..)
+            .map(|(buffer, binding)| BindGroupEntry {
+                binding,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        entries.push(BindGroupEntry {
+            binding: 2,
+            resource: BindingResource::Buffer(BufferBinding {
+                buffer: &steps_buffer,
+                offset: 0,
+                size: NonZeroU64::new(std::mem::size_of::<Step>() as u64),
+            }),
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Sort Bind Group"),
+            layout,
+            entries: &entries,
+        });
+
+        // One invocation per pair of the keys rounded up to a power of two
+        let workgroups = (len.next_power_of_two() / 2).div_ceil(WORKGROUP_SIZE);
+        let workgroups_x = workgroups.min(device.limits().max_compute_workgroups_per_dimension);
+        let workgroups_y = workgroups.div_ceil(workgroups_x);
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            timestamp_writes: None,
+            label: Some("Sort Pass"),
+        });
+        compute_pass.set_pipeline(pipeline);
+        for index in 0..steps.len() {
+            compute_pass.set_bind_group(0, &bind_group, &[(index * alignment) as u32]);
+            compute_pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+        }
+    }
+}
+
+/// The steps sorting `len` keys: merging blocks of 2, 4 and so on up to all keys,
+/// each merge starting with the mirrored comparisons and halving the distance down to 1
+fn steps(len: u32) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let mut block = 2;
+    while len > 1 && block <= len.next_power_of_two() {
+        let mut distance = block / 2;
+        let mut mirror = 1;
+        while distance > 0 {
+            steps.push(Step {
+                distance,
+                mirror,
+                len,
+                _padding: 0,
+            });
+            distance /= 2;
+            mirror = 0;
+        }
+        block *= 2;
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_gpu;
+
+    #[test]
+    fn nothing_to_sort() {
+        assert!(steps(0).is_empty());
+        assert!(steps(1).is_empty());
+    }
+
+    #[test]
+    fn steps_per_length() {
+        // log₂ n · (log₂ n + 1) / 2 steps for n rounded up to a power of two
+        for (len, count) in [
+            (2, 1),
+            (3, 3),
+            (4, 3),
+            (255, 36),
+            (256, 36),
+            (257, 45),
+            (70_000, 153),
+        ] {
+            let steps = steps(len);
+            assert_eq!(steps.len(), count, "steps for {len} keys");
+            assert!(steps.iter().all(|step| step.len == len));
+        }
+    }
+
+    #[test]
+    fn first_step_of_each_block_mirrors() {
+        let steps = steps(257);
+        let distances: Vec<u32> = steps
+            .iter()
+            .filter(|step| step.mirror == 1)
+            .map(|step| step.distance)
+            .collect();
+        // Blocks of 2 to 512, the mirror compares across the whole block
+        assert_eq!(distances, [1, 2, 4, 8, 16, 32, 64, 128, 256]);
+        for pair in steps.windows(2) {
+            if pair[1].mirror == 0 {
+                assert_eq!(pair[1].distance * 2, pair[0].distance);
+            } else {
+                assert_eq!(pair[0].distance, 1);
+            }
+        }
+    }
+
+    fn keys(len: u32) -> Vec<u32> {
+        // Duplicates included
+        (0..len)
+            .map(|i| i.wrapping_mul(2_654_435_761) % (len / 2 + 1))
+            .collect()
+    }
+
+    #[test]
+    fn sort_keys_matches_cpu() {
+        let Some((device, queue)) = test_gpu::device(Limits::downlevel_defaults()) else {
+            eprintln!("No adapter with compute shaders, skipping");
+            return;
+        };
+        let sort = GpuSort::new(&device);
+        for len in [1, 255, 256, 257, 70_000] {
+            let keys = keys(len);
+            let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Test Sort Keys Buffer"),
+                contents: bytemuck::cast_slice(&keys),
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            });
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+            sort.sort_keys(&device, &mut encoder, &buffer, len);
+            queue.submit(Some(encoder.finish()));
+
+            let mut expected = keys;
+            expected.sort_unstable();
+            let sorted = test_gpu::read_u32s(&device, &queue, &buffer, len as usize);
+            assert_eq!(sorted, expected, "{len} keys");
+        }
+    }
+
+    #[test]
+    fn sort_pairs_moves_values_along() {
+        let Some((device, queue)) = test_gpu::device(Limits::downlevel_defaults()) else {
+            eprintln!("No adapter with compute shaders, skipping");
+            return;
+        };
+        let sort = GpuSort::new(&device);
+        for len in [1, 255, 256, 257, 70_000] {
+            let keys = keys(len);
+            let indices: Vec<u32> = (0..len).collect();
+            let create_buffer = |contents: &[u32]| {
+                device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Test Sort Buffer"),
+                    contents: bytemuck::cast_slice(contents),
+                    usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+                })
+            };
+            let (key_buffer, value_buffer) = (create_buffer(&keys), create_buffer(&indices));
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+            sort.sort_pairs(&device, &mut encoder, &key_buffer, &value_buffer, len);
+            queue.submit(Some(encoder.finish()));
+
+            let sorted = test_gpu::read_u32s(&device, &queue, &key_buffer, len as usize);
+            let values = test_gpu::read_u32s(&device, &queue, &value_buffer, len as usize);
+            let mut expected = keys.clone();
+            expected.sort_unstable();
+            assert_eq!(sorted, expected, "{len} keys");
+            // Every index once, each next to the key it came with
+            let mut moved = values.clone();
+            moved.sort_unstable();
+            assert_eq!(moved, indices, "values of {len} keys");
+            assert!(
+                values
+                    .iter()
+                    .zip(&sorted)
+                    .all(|(&index, &key)| keys[index as usize] == key),
+                "values of {len} keys"
+            );
+        }
+    }
+}
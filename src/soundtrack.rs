@@ -0,0 +1,101 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Read;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// File the soundtrack of a recording is written to, next to its frames
+pub const SOUNDTRACK_FILE: &str = "audio.wav";
+
+/// Writes the live audio input heard while recording as mono 16 bit WAV, in step with the frames.
+///
+/// The frames advance by a fixed 1/60 s each, or 1/`--fps`, however long drawing them takes,
+/// so each frame gets exactly its share of samples: the latest ones that arrived
+/// since the previous frame, padded with silence when drawing ran ahead of real time.
+/// That way the sound stays in sync with what the shader reacted to
+pub struct Soundtrack {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    frame_rate: u32,
+    frames: u64,
+    samples: u64,
+}
+
+impl Soundtrack {
+    pub fn new(directory: &Path, sample_rate: u32, frame_rate: u32) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(directory.join(SOUNDTRACK_FILE))?);
+        // The sizes are filled in by `finish`
+        write_header(&mut file, sample_rate, 0)?;
+        Ok(Self {
+            file,
+            sample_rate,
+            frame_rate,
+            frames: 0,
+            samples: 0,
+        })
+    }
+
+    /// Append the audio of the next frame from the `samples` recorded since the previous one
+    pub fn push_frame(&mut self, samples: &[f32]) -> io::Result<()> {
+        self.frames += 1;
+        // Rounding the total instead of each frame keeps 44.1 kHz from drifting
+        let total = self.frames * u64::from(self.sample_rate) / u64::from(self.frame_rate);
+        let needed = (total - self.samples) as usize;
+
+        let latest = &samples[samples.len().saturating_sub(needed)..];
+        for _ in latest.len()..needed {
+            self.file.write_all(&0i16.to_le_bytes())?;
+        }
+        for sample in latest {
+            let sample = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples = total;
+        Ok(())
+    }
+
+    /// Fill in the sizes in the header
+    pub fn finish(mut self) -> io::Result<()> {
+        let data_size = u32::try_from(self.samples * 2)
+            .map_err(|_| io::Error::other("Soundtrack exceeds the 4 GiB a WAV file can hold"))?;
+        self.file.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.file, self.sample_rate, data_size)?;
+        self.file.flush()
+    }
+}
+
+/// Frame rate the soundtrack at `path` was recorded for, given the number of `frames` recorded with it
+#[cfg(not(target_arch = "wasm32"))]
+pub fn frame_rate(path: &Path, frames: usize) -> io::Result<u32> {
+    let mut header = [0; 44];
+    File::open(path)?.read_exact(&mut header)?;
+    let sample_rate = u32::from_le_bytes(header[24..28].try_into().unwrap());
+    let samples = u32::from_le_bytes(header[40..44].try_into().unwrap()) / 2;
+    if samples == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} holds no audio", path.display()),
+        ));
+    }
+    Ok((frames as f64 * f64::from(sample_rate) / f64::from(samples)).round() as u32)
+}
+
+/// Header of a mono 16 bit PCM WAV file with `data_size` bytes of samples
+fn write_header(file: &mut impl Write, sample_rate: u32, data_size: u32) -> io::Result<()> {
+    let block_align: u16 = 2;
+    file.write_all(b"RIFF")?;
+    file.write_all(&(36 + data_size).to_le_bytes())?;
+    file.write_all(b"WAVEfmt ")?;
+    file.write_all(&16u32.to_le_bytes())?;
+    // PCM, one channel
+    file.write_all(&1u16.to_le_bytes())?;
+    file.write_all(&1u16.to_le_bytes())?;
+    file.write_all(&sample_rate.to_le_bytes())?;
+    file.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+    file.write_all(&block_align.to_le_bytes())?;
+    file.write_all(&16u16.to_le_bytes())?;
+    file.write_all(b"data")?;
+    file.write_all(&data_size.to_le_bytes())
+}
@@ -0,0 +1,100 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+use web_time::Instant;
+
+use crate::globals::Camera;
+
+/// File the telemetry of a recording is written to, next to its frames
+pub const TELEMETRY_FILE: &str = "telemetry.jsonl";
+
+/// The state a recorded frame was drawn with, one line of [`TELEMETRY_FILE`]
+#[derive(Serialize)]
+pub struct FrameTelemetry {
+    /// Number of the frame, as in its file name
+    pub frame: u32,
+    pub time: f32,
+    pub seed: u32,
+    pub params: [f32; 2],
+    /// Only with camera controls
+    pub camera: Option<Camera>,
+    /// Milliseconds from submitting the frame until the GPU finished it,
+    /// `null` if the GPU never reported back
+    pub gpu_ms: Option<f64>,
+}
+
+/// Writes the [`FrameTelemetry`] of every recorded frame as JSON lines, for `--telemetry`.
+///
+/// Lines wait until the GPU finished their frame, so they are written in order with its timing
+pub struct Telemetry {
+    output: BufWriter<File>,
+    /// Frames submitted but not written yet, oldest first, with the GPU time once it arrives
+    pending: VecDeque<(FrameTelemetry, Arc<Mutex<Option<Duration>>>)>,
+}
+
+impl Telemetry {
+    pub fn new(directory: &Path) -> io::Result<Self> {
+        Ok(Self {
+            output: BufWriter::new(File::create(directory.join(TELEMETRY_FILE))?),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Time the frame just submitted to `queue`, it is written once the GPU finished it
+    pub fn submitted(&mut self, queue: &wgpu::Queue, telemetry: FrameTelemetry) {
+        let submitted_at = Instant::now();
+        let gpu_time = Arc::new(Mutex::new(None));
+        let done = Arc::clone(&gpu_time);
+        queue.on_submitted_work_done(move || {
+            *done.lock().unwrap() = Some(submitted_at.elapsed());
+        });
+        self.pending.push_back((telemetry, gpu_time));
+    }
+
+    /// Write the frames the GPU finished
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        device.poll(wgpu::Maintain::Poll);
+        while let Some((_, gpu_time)) = self.pending.front()
+            && gpu_time.lock().unwrap().is_some()
+        {
+            self.write_oldest();
+        }
+    }
+
+    /// Wait for the GPU and write all remaining frames
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        device.poll(wgpu::Maintain::Wait);
+        while !self.pending.is_empty() {
+            self.write_oldest();
+        }
+        if let Err(err) = self.output.flush() {
+            log::error!("Failed to write the telemetry: {err}");
+        }
+    }
+
+    fn write_oldest(&mut self) {
+        let Some((mut telemetry, gpu_time)) = self.pending.pop_front() else {
+            return;
+        };
+        telemetry.gpu_ms = gpu_time
+            .lock()
+            .unwrap()
+            .map(|duration| duration.as_secs_f64() * 1000.0);
+        let result = serde_json::to_writer(&mut self.output, &telemetry)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(self.output));
+        if let Err(err) = result {
+            log::error!(
+                "Failed to write the telemetry of frame {}: {err}",
+                telemetry.frame
+            );
+        }
+    }
+}
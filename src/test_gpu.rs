@@ -0,0 +1,48 @@
+//! Devices for the tests of the GPU algorithms, which skip when there is no adapter
+
+use wgpu::*;
+
+/// A device with `limits` on the default adapter, `None` if there is none or it can't run compute shaders
+pub fn device(limits: Limits) -> Option<(Device, Queue)> {
+    let instance = Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions::default()))?;
+    if !adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(DownlevelFlags::COMPUTE_SHADERS)
+    {
+        return None;
+    }
+    pollster::block_on(adapter.request_device(
+        &DeviceDescriptor {
+            required_limits: limits.using_resolution(adapter.limits()),
+            ..Default::default()
+        },
+        None,
+    ))
+    .ok()
+}
+
+/// The first `len` `u32`s of `buffer`, which needs [`BufferUsages::COPY_SRC`]
+pub fn read_u32s(device: &Device, queue: &Queue, buffer: &Buffer, len: usize) -> Vec<u32> {
+    let size = (len * 4) as BufferAddress;
+    let readback = device.create_buffer(&BufferDescriptor {
+        label: Some("Test Readback Buffer"),
+        size,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+    encoder.copy_buffer_to_buffer(buffer, 0, &readback, 0, size);
+    queue.submit(Some(encoder.finish()));
+
+    let slice = readback.slice(..);
+    slice.map_async(MapMode::Read, |result| {
+        result.expect("Failed to map readback")
+    });
+    device.poll(Maintain::Wait);
+    let data = slice.get_mapped_range();
+    data.chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
@@ -0,0 +1,68 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{cli::Options, headless::Headless};
+
+/// Image drawn tile by tile, for exports larger than the device can draw at once
+pub struct Poster {
+    /// Size of the whole image in pixels
+    pub size: [u32; 2],
+    /// Size of the tiles drawn at once, the last column and row are cut off at the edges
+    pub tile: [u32; 2],
+    /// `globals.time` in seconds
+    pub time: f32,
+}
+
+/// Draw the `--shader` or `--example` tile by tile, each with `globals.offset` at its top left pixel,
+/// and stream the rows of tiles into the PNG at `output` as they are read back.
+/// Only one row of tiles is held in memory, so the image may be far larger than it
+pub fn run(options: &Options, poster: &Poster, output: &Path) -> Result<(), String> {
+    let [width, height] = poster.size;
+    let [tile_width, tile_height] = [poster.tile[0].min(width), poster.tile[1].min(height)];
+    let mut headless = Headless::new(options, tile_width, tile_height)?;
+    headless.globals.globals.resolution = [width as f32, height as f32];
+    headless.globals.globals.frame = 1;
+    headless.globals.globals.time = poster.time;
+
+    let write_err =
+        |err: &dyn std::fmt::Display| format!("Failed to write {}: {err}", output.display());
+    let file = File::create(output).map_err(|err| write_err(&err))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|err| write_err(&err))?;
+    let mut stream = writer.stream_writer().map_err(|err| write_err(&err))?;
+
+    let columns = width.div_ceil(tile_width);
+    let rows = height.div_ceil(tile_height);
+    let row_size = width as usize * 4;
+    for row in 0..rows {
+        let top = row * tile_height;
+        let rows_drawn = tile_height.min(height - top) as usize;
+        let mut strip = vec![0; row_size * rows_drawn];
+        for column in 0..columns {
+            let left = column * tile_width;
+            headless.globals.globals.offset = [left, top];
+            let pixels = headless.draw()?;
+            let columns_drawn = tile_width.min(width - left) as usize * 4;
+            for (y, tile_row) in pixels
+                .chunks_exact(tile_width as usize * 4)
+                .take(rows_drawn)
+                .enumerate()
+            {
+                let start = y * row_size + left as usize * 4;
+                strip[start..start + columns_drawn].copy_from_slice(&tile_row[..columns_drawn]);
+            }
+            log::info!(
+                "Drew tile {} of {}",
+                row * columns + column + 1,
+                rows * columns
+            );
+        }
+        stream.write_all(&strip).map_err(|err| write_err(&err))?;
+    }
+    stream.finish().map_err(|err| write_err(&err))
+}
@@ -0,0 +1,108 @@
+use clap::ValueEnum;
+use wgpu::TextureFormat;
+
+use crate::{gpu::DrawMode, histogram::BIN_COUNT, playlist::Transition};
+
+/// Curve mapping HDR colors into the displayable range, applied by the render pass
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tonemapper {
+    /// No tonemapping, values above 1.0 are clipped
+    Clamp,
+    Reinhard,
+    /// Filmic curve, fitted to the ACES reference transform
+    Aces,
+    /// `1 - exp(-color)`, approaching white smoothly
+    Exposure,
+}
+
+impl Tonemapper {
+    /// The curve after this one, for cycling through them at runtime
+    pub fn next(self) -> Self {
+        match self {
+            Tonemapper::Clamp => Tonemapper::Reinhard,
+            Tonemapper::Reinhard => Tonemapper::Aces,
+            Tonemapper::Aces => Tonemapper::Exposure,
+            Tonemapper::Exposure => Tonemapper::Clamp,
+        }
+    }
+}
+
+/// How the colors written by the drawing shader are meant
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Linear light, encoded to sRGB for display
+    Linear,
+    /// Already sRGB encoded, shown as they are
+    Srgb,
+}
+
+impl ColorSpace {
+    /// Whether a surface format with hardware sRGB encoding should be preferred.
+    /// In [`DrawMode::Direct`] no tonemapping pass runs,
+    /// so the surface format alone has to match how colors are meant
+    pub fn prefers_srgb_surface(self, draw_mode: DrawMode) -> bool {
+        self == ColorSpace::Linear || draw_mode != DrawMode::Direct
+    }
+
+    /// Conversion the tonemapping pass applies for presenting on `surface_format`
+    pub fn transfer(self, surface_format: TextureFormat) -> Transfer {
+        match (self, surface_format.is_srgb()) {
+            (ColorSpace::Linear, false) => Transfer::EncodeSrgb,
+            // The surface encodes on write, so sRGB colors have to be decoded first
+            (ColorSpace::Srgb, true) => Transfer::DecodeSrgb,
+            (ColorSpace::Linear, true) | (ColorSpace::Srgb, false) => Transfer::None,
+        }
+    }
+}
+
+/// Transfer function applied after tonemapping
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transfer {
+    None,
+    EncodeSrgb,
+    DecodeSrgb,
+}
+
+/// Uniforms of the tonemapping step in `render_shader.wgsl`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TonemapParams {
+    /// Index of the [`Tonemapper`] variant
+    pub curve: u32,
+    /// Colors get multiplied by this before the curve is applied
+    pub exposure: f32,
+    /// Index of the [`Transfer`] variant
+    pub transfer: u32,
+    /// Progress of the [`Crossfade`](crate::playlist::Crossfade) over the previous scene, 1 when there is none
+    pub progress: f32,
+    /// Selected crop as `[left, top, right, bottom]` in texture coordinates,
+    /// everything outside is dimmed. Empty when nothing is selected
+    pub selection: [f32; 4],
+    /// Color of the status strip along the bottom edge, see
+    /// [`StatusStrip::color`](crate::live::StatusStrip::color). Transparent when hidden
+    pub status: [f32; 4],
+    /// Index of the [`Transition`] variant
+    pub transition: u32,
+    /// 1 to draw `histogram` over the bottom left of the image
+    pub histogram_shown: u32,
+    pub _padding: [u32; 2],
+    /// Bar heights of the [`Histogram`](crate::histogram::Histogram), four bins per element
+    pub histogram: [[f32; 4]; BIN_COUNT / 4],
+}
+
+impl TonemapParams {
+    pub fn new(tonemapper: Tonemapper, exposure: f32, transfer: Transfer) -> Self {
+        Self {
+            curve: tonemapper as u32,
+            exposure,
+            transfer: transfer as u32,
+            progress: 1.0,
+            selection: [0.0; 4],
+            status: [0.0; 4],
+            transition: Transition::Fade as u32,
+            histogram_shown: 0,
+            _padding: [0; 2],
+            histogram: [[0.0; 4]; BIN_COUNT / 4],
+        }
+    }
+}
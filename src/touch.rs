@@ -0,0 +1,234 @@
+use std::{collections::HashMap, f32::consts::PI, time::Duration};
+use web_time::Instant;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{Touch, TouchPhase},
+};
+
+use crate::globals::{
+    MAX_TOUCHES, TOUCH_CANCELLED, TOUCH_ENDED, TOUCH_MOVED, TOUCH_STARTED, TouchPoint, Touches,
+};
+
+/// A second tap within this interval counts as a double tap
+const DOUBLE_TAP_INTERVAL: Duration = Duration::from_millis(300);
+/// Touches travelling less than this many pixels count as taps
+const TAP_SLOP: f64 = 12.0;
+
+pub enum TouchAction {
+    /// The parameters were dragged to new values
+    SetParams([f32; 2]),
+    /// Double tap, start over
+    Reset,
+}
+
+/// Touch-first parameter controls, so sketches can be tweaked on tablets without a keyboard.
+///
+/// Dragging a single finger moves the two shader parameters,
+/// horizontally for the first and vertically for the second.
+/// Dragging across the whole screen covers the full `0..1` range.
+pub struct TouchControls {
+    params: [f32; 2],
+    /// Position where each active touch started, and where it was last seen
+    touches: HashMap<u64, (PhysicalPosition<f64>, PhysicalPosition<f64>)>,
+    last_tap: Option<Instant>,
+    /// The first finger down, which also acts as the mouse
+    primary: Option<u64>,
+}
+
+impl TouchControls {
+    pub fn new(params: [f32; 2]) -> Self {
+        Self {
+            params,
+            touches: HashMap::new(),
+            last_tap: None,
+            primary: None,
+        }
+    }
+
+    /// Track which finger acts as the mouse, returns whether `touch` is of it
+    pub fn primary(&mut self, touch: &Touch) -> bool {
+        match touch.phase {
+            TouchPhase::Started if self.primary.is_none() => {
+                self.primary = Some(touch.id);
+                true
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled if self.primary == Some(touch.id) => {
+                self.primary = None;
+                true
+            }
+            _ => self.primary == Some(touch.id),
+        }
+    }
+
+    pub fn handle(&mut self, touch: &Touch, window_size: PhysicalSize<u32>) -> Option<TouchAction> {
+        match touch.phase {
+            TouchPhase::Started => {
+                self.touches
+                    .insert(touch.id, (touch.location, touch.location));
+                None
+            }
+            TouchPhase::Moved => {
+                let single_touch = self.touches.len() == 1;
+                let (_, last) = self.touches.get_mut(&touch.id)?;
+                let dx = (touch.location.x - last.x) / window_size.width.max(1) as f64;
+                let dy = (last.y - touch.location.y) / window_size.height.max(1) as f64;
+                *last = touch.location;
+
+                // Multi finger gestures are left to the platform
+                if !single_touch {
+                    return None;
+                }
+
+                self.params[0] = (self.params[0] + dx as f32).clamp(0.0, 1.0);
+                self.params[1] = (self.params[1] + dy as f32).clamp(0.0, 1.0);
+                Some(TouchAction::SetParams(self.params))
+            }
+            TouchPhase::Ended => {
+                let (start, _) = self.touches.remove(&touch.id)?;
+                let travelled = (touch.location.x - start.x).hypot(touch.location.y - start.y);
+                if travelled > TAP_SLOP {
+                    return None;
+                }
+
+                let now = Instant::now();
+                match self.last_tap.replace(now) {
+                    Some(previous) if now - previous < DOUBLE_TAP_INTERVAL => {
+                        self.last_tap = None;
+                        Some(TouchAction::Reset)
+                    }
+                    _ => None,
+                }
+            }
+            TouchPhase::Cancelled => {
+                self.touches.remove(&touch.id);
+                None
+            }
+        }
+    }
+}
+
+/// The fingers on the screen for drawing shaders, handed to them as `touches`.
+///
+/// Two fingers pinch, twist and drag the view of [`Touches`] around them,
+/// so the point under each finger stays under it
+pub struct TouchInput {
+    touches: Touches,
+}
+
+impl TouchInput {
+    pub fn new() -> Self {
+        Self {
+            touches: Touches::default(),
+        }
+    }
+
+    /// Track `touch`, at `position` in pixels of the drawn image
+    pub fn handle(&mut self, touch: &Touch, position: [f32; 2]) {
+        let id = touch.id as u32;
+        let count = self.touches.count as usize;
+        let Some(index) = self.touches.points[..count]
+            .iter()
+            .position(|point| point.id == id && point.phase < TOUCH_ENDED)
+        else {
+            if touch.phase == TouchPhase::Started && count < MAX_TOUCHES {
+                self.touches.points[count] = TouchPoint {
+                    position,
+                    id,
+                    phase: TOUCH_STARTED,
+                };
+                self.touches.count += 1;
+            }
+            return;
+        };
+
+        let held = self.held();
+        let before = self.touches.points[index].position;
+        let point = &mut self.touches.points[index];
+        point.position = position;
+        point.phase = match touch.phase {
+            TouchPhase::Started | TouchPhase::Moved => point.phase,
+            TouchPhase::Ended => TOUCH_ENDED,
+            TouchPhase::Cancelled => TOUCH_CANCELLED,
+        };
+        if touch.phase == TouchPhase::Moved
+            && let [first, second] = held[..]
+            && (first == index || second == index)
+        {
+            let other = self.touches.points[first + second - index].position;
+            self.gesture([before, other], [position, other]);
+        }
+    }
+
+    /// The touches to draw the next frame with.
+    /// Lifted fingers are handed over once, and fingers that went down are held from then on
+    pub fn next_frame(&mut self) -> Touches {
+        let touches = self.touches;
+        let count = touches.count as usize;
+        let mut held = 0;
+        for index in 0..count {
+            let mut point = touches.points[index];
+            if point.phase < TOUCH_ENDED {
+                point.phase = TOUCH_MOVED;
+                self.touches.points[held] = point;
+                held += 1;
+            }
+        }
+        self.touches.count = held as u32;
+        touches
+    }
+
+    /// Put the view back to where it was before any gestures
+    pub fn reset_view(&mut self) {
+        self.touches = Touches {
+            points: self.touches.points,
+            count: self.touches.count,
+            ..Touches::default()
+        };
+    }
+
+    /// Indices of the fingers still on the screen
+    fn held(&self) -> Vec<usize> {
+        (0..self.touches.count as usize)
+            .filter(|&index| self.touches.points[index].phase < TOUCH_ENDED)
+            .collect()
+    }
+
+    /// Move the view with two fingers going from `before` to `after`: their center drags it,
+    /// their distance zooms and their angle turns it around the center
+    fn gesture(&mut self, before: [[f32; 2]; 2], after: [[f32; 2]; 2]) {
+        let [old_center, new_center] =
+            [before, after].map(|[a, b]| [0.5 * (a[0] + b[0]), 0.5 * (a[1] + b[1])]);
+        let [old_span, new_span] = [before, after].map(|[a, b]| [b[0] - a[0], b[1] - a[1]]);
+        let old_length = old_span[0].hypot(old_span[1]);
+        let new_length = new_span[0].hypot(new_span[1]);
+        if old_length < 1.0 || new_length < 1.0 {
+            return;
+        }
+        let zoom = new_length / old_length;
+        let mut angle = new_span[1].atan2(new_span[0]) - old_span[1].atan2(old_span[0]);
+        if angle > PI {
+            angle -= 2.0 * PI;
+        } else if angle < -PI {
+            angle += 2.0 * PI;
+        }
+
+        // Scale and turn the panned origin around the old center, then move it along with the center
+        let (sin, cos) = angle.sin_cos();
+        let [x, y] = [
+            self.touches.pan[0] - old_center[0],
+            self.touches.pan[1] - old_center[1],
+        ];
+        self.touches.pan = [
+            new_center[0] + zoom * (cos * x - sin * y),
+            new_center[1] + zoom * (sin * x + cos * y),
+        ];
+        self.touches.zoom *= zoom;
+        self.touches.rotation += angle;
+    }
+}
+
+impl Default for TouchInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
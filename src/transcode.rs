@@ -0,0 +1,136 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    recording::{self, RAW_EXTENSION, RawFrame},
+    soundtrack::{self, SOUNDTRACK_FILE},
+    video::{AudioTrack, VideoEncoder, VideoInfo},
+};
+
+/// Convert the raw frames in `input` to numbered PNGs in the `output` directory,
+/// or to a video if `output` has an extension, returns the number of frames.
+/// The video gets the `audio` file, or the soundtrack recorded along with the frames,
+/// starting `audio_offset` seconds in
+pub fn run(
+    input: &Path,
+    output: &Path,
+    framerate: u32,
+    audio: Option<&Path>,
+    audio_offset: f64,
+) -> io::Result<usize> {
+    let frames = raw_frames(input)?;
+    if frames.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No *.{RAW_EXTENSION} frames in {}", input.display()),
+        ));
+    }
+
+    if output.extension().is_some() {
+        let recorded = input.join(SOUNDTRACK_FILE);
+        let audio = match audio {
+            Some(audio) => Some(audio),
+            None if recorded.is_file() => {
+                let recorded_framerate = soundtrack::frame_rate(&recorded, frames.len())?;
+                if framerate != recorded_framerate {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "The audio in {} was recorded for {recorded_framerate} fps, \
+                             it would be out of sync at {framerate} fps",
+                            recorded.display()
+                        ),
+                    ));
+                }
+                Some(recorded.as_path())
+            }
+            None => None,
+        };
+        encode_video(&frames, output, framerate, audio, audio_offset)?;
+    } else {
+        if audio.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Audio can only be added to a video, not to PNGs",
+            ));
+        }
+        fs::create_dir_all(output)?;
+        for (index, path) in frames.iter().enumerate() {
+            let frame = read_frame(path)?;
+            recording::write_png(
+                &output.join(format!("frame-{:06}.png", index + 1)),
+                frame.width,
+                frame.height,
+                &frame.pixels,
+            )?;
+        }
+    }
+    Ok(frames.len())
+}
+
+/// Pipe the frames to ffmpeg, which picks the codecs from the extension of `output`
+fn encode_video(
+    frames: &[PathBuf],
+    output: &Path,
+    framerate: u32,
+    audio: Option<&Path>,
+    audio_offset: f64,
+) -> io::Result<()> {
+    let first = read_frame(&frames[0])?;
+    let info = VideoInfo {
+        width: first.width,
+        height: first.height,
+        frame_rate: [framerate, 1],
+    };
+    let audio = audio.map(|path| AudioTrack::Padded {
+        path,
+        offset: audio_offset,
+    });
+    let mut encoder = VideoEncoder::new(output, info, audio)?;
+
+    let written = (|| {
+        encoder.write_frame(&first.pixels)?;
+        for path in &frames[1..] {
+            let frame = read_frame(path)?;
+            if (frame.width, frame.height) != (first.width, first.height) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} is {}x{}, but the video is {}x{}, the window was resized while recording",
+                        path.display(),
+                        frame.width,
+                        frame.height,
+                        first.width,
+                        first.height
+                    ),
+                ));
+            }
+            encoder.write_frame(&frame.pixels)?;
+        }
+        Ok(())
+    })();
+    // ffmpeg finishes the file even if a frame failed to be read
+    let finished = encoder.finish();
+    written.and(finished)
+}
+
+/// Raw frame files in `directory`, in recording order
+fn raw_frames(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut frames: Vec<PathBuf> = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    frames.retain(|path| {
+        path.file_name()
+            .is_some_and(|name| name.to_string_lossy().ends_with(RAW_EXTENSION))
+    });
+    // Frame numbers are zero padded, so they sort by name
+    frames.sort();
+    Ok(frames)
+}
+
+fn read_frame(path: &Path) -> io::Result<RawFrame> {
+    RawFrame::read(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))
+}
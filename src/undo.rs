@@ -0,0 +1,782 @@
+use std::collections::VecDeque;
+
+use wgpu::*;
+
+use crate::{compute::OUTPUT_FORMAT, readback::ReadbackRing};
+
+/// Width and height of the tiles changes are kept in, mirrored in `src/shaders/undo.wgsl`
+const TILE_SIZE: u32 = 32;
+/// Most steps kept, mirrored in `src/shaders/undo.wgsl`
+const MAX_STEPS: usize = 1024;
+/// Words of the mask in front of a stored tile, a bit for each of the two words of its pixels,
+/// mirrored in `src/shaders/undo.wgsl`
+const MASK_WORDS: u32 = TILE_SIZE * TILE_SIZE * 2 / 32;
+const NO_STEP: u32 = u32::MAX;
+/// Bytes of the `State` of `src/shaders/undo.wgsl` in front of the start of every step
+const STATE_HEADER: u64 = 32;
+/// Offset of `head` in the `State`, followed by `broken`
+const HEAD_OFFSET: u64 = 16;
+
+/// Undo and redo for a canvas in [`OUTPUT_FORMAT`], keeping only compressed deltas of the tiles each step changed.
+///
+/// `base` is a copy of the canvas as it was after the last recorded step. Recording a step XORs the canvas
+/// with it on the GPU and keeps the tiles that differ in the `pool`, a ring of words: per tile a mask of the
+/// words of the delta that aren't zero, followed by those words. Once it is full the oldest steps are dropped.
+/// XORing a step back into the canvas undoes it, doing so again redoes it.
+///
+/// Nothing waits for the GPU: how much of the pool a step takes comes back through a [`ReadbackRing`],
+/// until then it may take up to the `reserve`, and the oldest steps it could overwrite can't be undone
+pub struct TileHistory {
+    base: Texture,
+    base_view: TextureView,
+    /// Whether `base` has to be copied from the canvas before it can be diffed against
+    base_stale: bool,
+    /// `None` when not even a step without tiles fits into the budget
+    pool: Option<Buffer>,
+    /// Words of the pool
+    capacity: u32,
+    /// Words a step may take before its size is known
+    reserve: u32,
+    /// Words taken by the steps whose size is known, from the start of the oldest one
+    used: u32,
+    steps: VecDeque<Step>,
+    /// Steps that can be undone, the ones after can be redone
+    position: usize,
+    /// Id of the next step, its slot in the `starts` of the shader is the id modulo [`MAX_STEPS`]
+    next_id: u64,
+    /// The `State` of the shader
+    state_buffer: Buffer,
+    params_buffer: Buffer,
+    record_pipelines: [ComputePipeline; 3],
+    apply_pipeline: ComputePipeline,
+    record_layout: BindGroupLayout,
+    apply_layout: BindGroupLayout,
+    /// Tiles and words each step stored, by its id
+    sizes: ReadbackRing<u64>,
+    width: u32,
+    height: u32,
+}
+
+struct Step {
+    id: u64,
+    /// Words it takes in the pool, once they came back from the GPU
+    size: Option<u32>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    step: u32,
+    /// Slot of the first step dropped before recording, or [`NO_STEP`]
+    rewind: u32,
+    _padding: [u32; 2],
+}
+
+impl TileHistory {
+    /// Deltas of a `width` × `height` canvas taking up to `budget` bytes
+    pub fn new(
+        device: &Device,
+        module: &ShaderModule,
+        pipeline_cache: Option<&PipelineCache>,
+        [width, height]: [u32; 2],
+        budget: u64,
+    ) -> Self {
+        let base = device.create_texture(&TextureDescriptor {
+            label: Some("Undo Base Texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let base_view = base.create_view(&TextureViewDescriptor::default());
+
+        let limits = device.limits();
+        let capacity = (budget
+            .min(u64::from(limits.max_storage_buffer_binding_size))
+            .min(limits.max_buffer_size)
+            / 4) as u32;
+        let tiles = width.div_ceil(TILE_SIZE) * height.div_ceil(TILE_SIZE);
+        let index_words = index_words(width, height);
+        if capacity < index_words {
+            log::warn!(
+                "The index of a step takes {} KB, more than the undo memory, nothing can be undone",
+                (index_words * 4).div_ceil(1 << 10)
+            );
+        }
+        let pool = (capacity >= index_words).then(|| {
+            device.create_buffer(&BufferDescriptor {
+                label: Some("Undo Pool Buffer"),
+                size: u64::from(capacity) * 4,
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            })
+        });
+        let largest_step = index_words + tiles * (MASK_WORDS + TILE_SIZE * TILE_SIZE * 2);
+
+        let state_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Undo State Buffer"),
+            size: STATE_HEADER + 4 * MAX_STEPS as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Undo Params Buffer"),
+            size: std::mem::size_of::<Params>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let entry = |binding, ty| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        };
+        let texture = BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable: false },
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        };
+        let storage = BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let uniform = BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let record_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Undo Record Bind Group Layout"),
+            entries: &[
+                entry(0, texture),
+                entry(1, texture),
+                entry(2, storage),
+                entry(3, storage),
+                entry(4, uniform),
+            ],
+        });
+        let apply_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Undo Apply Bind Group Layout"),
+            entries: &[
+                entry(1, texture),
+                entry(2, storage),
+                entry(3, storage),
+                entry(4, uniform),
+                entry(
+                    5,
+                    BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: OUTPUT_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                ),
+            ],
+        });
+
+        let pipeline = |layout: &BindGroupLayout, entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                compilation_options: Default::default(),
+                label: Some("Undo Pipeline"),
+                layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Undo Pipeline Layout"),
+                    bind_group_layouts: &[layout],
+                    push_constant_ranges: &[],
+                })),
+                module,
+                entry_point,
+                cache: pipeline_cache,
+            })
+        };
+        let record_pipelines = ["begin", "compress", "finish"]
+            .map(|entry_point| pipeline(&record_layout, entry_point));
+        let apply_pipeline = pipeline(&apply_layout, "apply");
+
+        Self {
+            base,
+            base_view,
+            base_stale: true,
+            pool,
+            capacity,
+            reserve: largest_step.min(capacity),
+            used: 0,
+            steps: VecDeque::new(),
+            position: 0,
+            next_id: 0,
+            state_buffer,
+            params_buffer,
+            record_pipelines,
+            apply_pipeline,
+            record_layout,
+            apply_layout,
+            sizes: ReadbackRing::new(4),
+            width,
+            height,
+        }
+    }
+
+    /// Keep what changed in `canvas` since the last step as a new one, the steps that could be redone are dropped.
+    /// Call it only if the canvas was painted on, a step without changes would be undone like any other
+    pub fn record(&mut self, device: &Device, queue: &Queue, canvas: &Texture) {
+        let mut encoder = create_encoder(device);
+        if std::mem::take(&mut self.base_stale) {
+            // The steps start over, and so does the ring
+            queue.write_buffer(
+                &self.state_buffer,
+                HEAD_OFFSET,
+                bytemuck::bytes_of(&[0u32; 2]),
+            );
+            self.copy_all(&mut encoder, canvas);
+            queue.submit(Some(encoder.finish()));
+            return;
+        }
+        if self.pool.is_none() {
+            return;
+        }
+
+        let rewind = self
+            .steps
+            .get(self.position)
+            .map_or(NO_STEP, |step| slot(step.id));
+        while self.steps.len() > self.position {
+            let step = self.steps.pop_back().unwrap();
+            self.used -= step.size.unwrap_or(0);
+        }
+        if self.steps.len() == MAX_STEPS {
+            self.drop_oldest();
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::bytes_of(&Params {
+                step: slot(id),
+                rewind,
+                _padding: [0; 2],
+            }),
+        );
+
+        let canvas_view = canvas.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Undo Record Bind Group"),
+            layout: &self.record_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&self.base_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&canvas_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.pool().as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: self.state_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: self.params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                timestamp_writes: None,
+                label: Some("Undo Record Pass"),
+            });
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            let [begin, compress, finish] = &self.record_pipelines;
+            compute_pass.set_pipeline(begin);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+            compute_pass.set_pipeline(compress);
+            compute_pass.dispatch_workgroups(
+                self.width.div_ceil(TILE_SIZE),
+                self.height.div_ceil(TILE_SIZE),
+                1,
+            );
+            compute_pass.set_pipeline(finish);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        // `base` catches up with the canvas
+        self.copy_all(&mut encoder, canvas);
+        queue.submit(Some(encoder.finish()));
+        self.sizes
+            .start_buffer(device, queue, &self.state_buffer, 0, 16, id);
+
+        self.steps.push_back(Step { id, size: None });
+        self.position = self.steps.len();
+    }
+
+    /// Take the sizes of the steps that came back from the GPU, dropping the oldest steps
+    /// once the pool is full. Only waits for the GPU if `wait`
+    pub fn collect(&mut self, device: &Device, wait: bool) {
+        let mut sizes = Vec::new();
+        self.sizes.collect(device, wait, |&id, _, bytes| {
+            sizes.push((id, *bytemuck::from_bytes::<[u32; 4]>(bytes)));
+            true
+        });
+        for (id, [_start, _tiles, words, overflow]) in sizes {
+            if overflow != 0 {
+                log::warn!(
+                    "The stroke changed more than the undo memory holds, it can't be taken back"
+                );
+                self.clear();
+                continue;
+            }
+            // Steps dropped since don't count
+            let size = index_words(self.width, self.height) + words;
+            if let Some(step) = self.steps.iter_mut().find(|step| step.id == id) {
+                step.size = Some(size);
+                self.used += size;
+            }
+            while self.used > self.capacity {
+                self.drop_oldest();
+            }
+        }
+    }
+
+    /// XOR the last step into `canvas`, writing the result to `target`, which `base` is copied from afterwards.
+    /// Returns false if there is none
+    pub fn undo(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        canvas: &Texture,
+        target: &Texture,
+    ) -> bool {
+        if self.position == 0 || !self.is_kept(self.position - 1) {
+            return false;
+        }
+        self.position -= 1;
+        self.apply(device, queue, encoder, [canvas, target], self.position);
+        true
+    }
+
+    /// XOR the step undone last into `canvas` like [`TileHistory::undo`], returns false if there is none
+    pub fn redo(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        canvas: &Texture,
+        target: &Texture,
+    ) -> bool {
+        if self.position == self.steps.len() {
+            return false;
+        }
+        self.apply(device, queue, encoder, [canvas, target], self.position);
+        self.position += 1;
+        true
+    }
+
+    /// Forget all steps, the canvas is copied anew before the next one is recorded
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.used = 0;
+        self.position = 0;
+        self.base_stale = true;
+    }
+
+    /// Whether step `index` is still in the pool for sure, and not overwritten by the steps after it
+    /// whose size isn't known yet
+    fn is_kept(&self, index: usize) -> bool {
+        let mut after = 0;
+        let mut unknown = 0;
+        for step in self.steps.range(index..) {
+            match step.size {
+                Some(size) => after += u64::from(size),
+                None => unknown += 1,
+            }
+        }
+        self.steps[index].size.is_none()
+            || after + unknown * u64::from(self.reserve) <= u64::from(self.capacity)
+    }
+
+    fn apply(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        [canvas, target]: [&Texture; 2],
+        index: usize,
+    ) {
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::bytes_of(&Params {
+                step: slot(self.steps[index].id),
+                rewind: NO_STEP,
+                _padding: [0; 2],
+            }),
+        );
+        let canvas_view = canvas.create_view(&TextureViewDescriptor::default());
+        let target_view = target.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Undo Apply Bind Group"),
+            layout: &self.apply_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&canvas_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.pool().as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: self.state_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: self.params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: BindingResource::TextureView(&target_view),
+                },
+            ],
+        });
+
+        // The tiles the step didn't store stay as they are
+        encoder.copy_texture_to_texture(
+            canvas.as_image_copy(),
+            target.as_image_copy(),
+            self.extent(),
+        );
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                timestamp_writes: None,
+                label: Some("Undo Apply Pass"),
+            });
+            compute_pass.set_pipeline(&self.apply_pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                self.width.div_ceil(TILE_SIZE),
+                self.height.div_ceil(TILE_SIZE),
+                1,
+            );
+        }
+        encoder.copy_texture_to_texture(
+            target.as_image_copy(),
+            self.base.as_image_copy(),
+            self.extent(),
+        );
+    }
+
+    /// Drop the oldest step, or all of them if it is one to redo, as the others were recorded on top of it
+    fn drop_oldest(&mut self) {
+        if self.position == 0 {
+            self.clear();
+            return;
+        }
+        let step = self.steps.pop_front().unwrap();
+        self.used -= step.size.unwrap_or(0);
+        self.position -= 1;
+    }
+
+    fn pool(&self) -> &Buffer {
+        self.pool.as_ref().expect("steps are only kept in a pool")
+    }
+
+    fn copy_all(&self, encoder: &mut CommandEncoder, canvas: &Texture) {
+        encoder.copy_texture_to_texture(
+            canvas.as_image_copy(),
+            self.base.as_image_copy(),
+            self.extent(),
+        );
+    }
+
+    fn extent(&self) -> Extent3d {
+        Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
+        }
+    }
+}
+
+/// Slot of step `id` in the `starts` of the shader
+fn slot(id: u64) -> u32 {
+    (id % MAX_STEPS as u64) as u32
+}
+
+/// Words in front of the data of a step: its tile count, and the index and offset of every tile it could store
+fn index_words(width: u32, height: u32) -> u32 {
+    1 + 2 * width.div_ceil(TILE_SIZE) * height.div_ceil(TILE_SIZE)
+}
+
+fn create_encoder(device: &Device) -> CommandEncoder {
+    device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Undo Encoder"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::{frame_hash, test_gpu};
+
+    const WIDTH: u32 = 70;
+    const HEIGHT: u32 = 40;
+    /// Words of a step changing one pixel of one tile: the index of the 6 tiles, the mask and the two words of the pixel
+    const PIXEL_STEP_WORDS: u64 = 1 + 2 * 6 + MASK_WORDS as u64 + 2;
+    /// Budget holding two steps changing a pixel, but not three
+    const TWO_STEPS: u64 = (2 * PIXEL_STEP_WORDS + 40) * 4;
+    /// 1.0 as `f16`, the alpha of painted pixels
+    const ONE: u16 = 0x3c00;
+
+    /// The two canvases the paint preset applies steps between
+    struct Canvas {
+        device: Device,
+        queue: Queue,
+        textures: [Texture; 2],
+        current: Cell<usize>,
+        /// Painted since the last record
+        painted: Cell<bool>,
+    }
+
+    impl Canvas {
+        fn new() -> Option<Self> {
+            let (device, queue) = test_gpu::device(Limits::downlevel_defaults())?;
+            let textures = [0, 1].map(|_| {
+                device.create_texture(&TextureDescriptor {
+                    label: None,
+                    size: Extent3d {
+                        width: WIDTH,
+                        height: HEIGHT,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: OUTPUT_FORMAT,
+                    usage: TextureUsages::STORAGE_BINDING
+                        | TextureUsages::TEXTURE_BINDING
+                        | TextureUsages::COPY_SRC
+                        | TextureUsages::COPY_DST,
+                    view_formats: &[],
+                })
+            });
+            Some(Self {
+                device,
+                queue,
+                textures,
+                current: Cell::new(0),
+                painted: Cell::new(false),
+            })
+        }
+
+        fn history(&self, budget: u64) -> TileHistory {
+            let module = self.device.create_shader_module(ShaderModuleDescriptor {
+                label: None,
+                source: ShaderSource::Wgsl(include_str!("./shaders/undo.wgsl").into()),
+            });
+            TileHistory::new(&self.device, &module, None, [WIDTH, HEIGHT], budget)
+        }
+
+        fn texture(&self) -> &Texture {
+            &self.textures[self.current.get()]
+        }
+
+        /// Set the red channel of a pixel to `red`, a `f16`
+        fn paint(&self, [x, y]: [u32; 2], red: u16) {
+            self.queue.write_texture(
+                ImageCopyTexture {
+                    origin: Origin3d { x, y, z: 0 },
+                    ..self.texture().as_image_copy()
+                },
+                bytemuck::cast_slice(&[red, 0, 0, ONE]),
+                ImageDataLayout::default(),
+                Extent3d::default(),
+            );
+            self.painted.set(true);
+        }
+
+        fn record(&self, history: &mut TileHistory) {
+            history.record(&self.device, &self.queue, self.texture());
+            self.painted.set(false);
+        }
+
+        /// Record, and wait for the size of the step
+        fn record_and_collect(&self, history: &mut TileHistory) {
+            self.record(history);
+            history.collect(&self.device, true);
+        }
+
+        /// Undo or redo with `step` after recording what was painted, like the paint preset does.
+        /// Returns whether there was something to do
+        fn step(
+            &self,
+            history: &mut TileHistory,
+            step: fn(
+                &mut TileHistory,
+                &Device,
+                &Queue,
+                &mut CommandEncoder,
+                &Texture,
+                &Texture,
+            ) -> bool,
+        ) -> bool {
+            if self.painted.get() {
+                self.record(history);
+            }
+            let mut encoder = create_encoder(&self.device);
+            let current = self.current.get();
+            let stepped = step(
+                history,
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &self.textures[current],
+                &self.textures[1 - current],
+            );
+            self.queue.submit(Some(encoder.finish()));
+            if stepped {
+                self.current.set(1 - current);
+            }
+            stepped
+        }
+
+        fn pixels(&self) -> Vec<u8> {
+            frame_hash::read_texture(&self.device, &self.queue, self.texture())
+        }
+    }
+
+    fn red(pixels: &[u8], [x, y]: [u32; 2]) -> u16 {
+        let index = ((y * WIDTH + x) * 8) as usize;
+        u16::from_le_bytes([pixels[index], pixels[index + 1]])
+    }
+
+    #[test]
+    fn undo_and_redo_apply_the_changed_tiles() {
+        let Some(canvas) = Canvas::new() else {
+            return;
+        };
+        let mut history = canvas.history(1 << 20);
+        canvas.record(&mut history);
+        let blank = canvas.pixels();
+        canvas.paint([5, 5], 0x3800);
+        canvas.paint([6, 5], 0x3400);
+        canvas.record(&mut history);
+        let first = canvas.pixels();
+        // In the cut off tile at the bottom right corner
+        canvas.paint([WIDTH - 1, HEIGHT - 1], 0x3a00);
+        let second = canvas.pixels();
+
+        // Without waiting for the sizes of the steps
+        assert!(canvas.step(&mut history, TileHistory::undo));
+        assert_eq!(canvas.pixels(), first);
+        assert!(canvas.step(&mut history, TileHistory::undo));
+        assert_eq!(canvas.pixels(), blank);
+        assert!(!canvas.step(&mut history, TileHistory::undo));
+
+        assert!(canvas.step(&mut history, TileHistory::redo));
+        assert_eq!(canvas.pixels(), first);
+        assert!(canvas.step(&mut history, TileHistory::redo));
+        assert_eq!(canvas.pixels(), second);
+        assert!(!canvas.step(&mut history, TileHistory::redo));
+    }
+
+    #[test]
+    fn painting_after_undo_drops_the_redo_steps() {
+        let Some(canvas) = Canvas::new() else {
+            return;
+        };
+        let mut history = canvas.history(1 << 20);
+        canvas.record(&mut history);
+        canvas.paint([5, 5], 0x3800);
+        canvas.record(&mut history);
+        let first = canvas.pixels();
+        canvas.paint([40, 5], 0x3a00);
+
+        assert!(canvas.step(&mut history, TileHistory::undo));
+        canvas.paint([40, 35], 0x3400);
+        let third = canvas.pixels();
+        assert!(canvas.step(&mut history, TileHistory::undo));
+        assert_eq!(canvas.pixels(), first);
+        assert!(canvas.step(&mut history, TileHistory::redo));
+        assert_eq!(canvas.pixels(), third);
+        assert!(!canvas.step(&mut history, TileHistory::redo));
+    }
+
+    #[test]
+    fn full_pool_drops_the_oldest_steps() {
+        let Some(canvas) = Canvas::new() else {
+            return;
+        };
+        let mut history = canvas.history(TWO_STEPS);
+        canvas.record(&mut history);
+        for x in [5, 40, WIDTH - 1] {
+            canvas.paint([x, 5], 0x3800);
+            canvas.record_and_collect(&mut history);
+        }
+        let latest = canvas.pixels();
+        let oldest_kept = {
+            assert!(canvas.step(&mut history, TileHistory::undo));
+            assert!(canvas.step(&mut history, TileHistory::undo));
+            canvas.pixels()
+        };
+        assert!(!canvas.step(&mut history, TileHistory::undo));
+        assert_eq!(canvas.pixels(), oldest_kept);
+        // The first stroke can't be taken back anymore
+        assert_eq!(red(&oldest_kept, [5, 5]), 0x3800);
+        assert_eq!(red(&oldest_kept, [40, 5]), 0);
+        // The last step wrapped around the end of the pool
+        assert!(canvas.step(&mut history, TileHistory::redo));
+        assert!(canvas.step(&mut history, TileHistory::redo));
+        assert_eq!(canvas.pixels(), latest);
+
+        // A step larger than the whole pool can't be kept, and takes the others with it
+        for x in [5, 40, WIDTH - 1] {
+            canvas.paint([x, 30], 0x3a00);
+        }
+        let large = canvas.pixels();
+        canvas.record_and_collect(&mut history);
+        assert!(!canvas.step(&mut history, TileHistory::undo));
+        assert_eq!(canvas.pixels(), large);
+    }
+
+    #[test]
+    fn steps_a_pending_one_could_overwrite_wait_for_its_size() {
+        let Some(canvas) = Canvas::new() else {
+            return;
+        };
+        let mut history = canvas.history(TWO_STEPS);
+        canvas.record(&mut history);
+        for x in [5, 40] {
+            canvas.paint([x, 5], 0x3800);
+            canvas.record_and_collect(&mut history);
+        }
+        let second = canvas.pixels();
+        canvas.paint([WIDTH - 1, 5], 0x3800);
+
+        assert!(canvas.step(&mut history, TileHistory::undo));
+        assert_eq!(canvas.pixels(), second);
+        // The step just undone could have taken the whole pool
+        assert!(!canvas.step(&mut history, TileHistory::undo));
+        history.collect(&canvas.device, true);
+        assert!(canvas.step(&mut history, TileHistory::undo));
+        assert_eq!(red(&canvas.pixels(), [40, 5]), 0);
+        // Only the first step was dropped
+        assert!(!canvas.step(&mut history, TileHistory::undo));
+    }
+}
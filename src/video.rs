@@ -0,0 +1,202 @@
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+/// Size and frame rate of the first video stream of a file, read with ffprobe
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Frames per second as a fraction, like 30000/1001 for NTSC
+    pub frame_rate: [u32; 2],
+}
+
+impl VideoInfo {
+    pub fn probe(path: &Path) -> io::Result<Self> {
+        let output = Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "v:0"])
+            .args(["-show_entries", "stream=width,height,r_frame_rate"])
+            .args(["-of", "csv=p=0"])
+            .arg(path)
+            .output()
+            .map_err(|err| io::Error::new(err.kind(), format!("Failed to run ffprobe: {err}")))?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "ffprobe failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("No video stream found, ffprobe printed {:?}", stdout.trim()),
+            )
+        };
+        let fields: Vec<_> = stdout.trim().split(',').collect();
+        let [width, height, frame_rate] = fields[..] else {
+            return Err(invalid());
+        };
+        let (numerator, denominator) = frame_rate.split_once('/').unwrap_or((frame_rate, "1"));
+        let parse = |n: &str| n.trim().parse::<u32>().ok().filter(|&n| n > 0);
+        match (
+            parse(width),
+            parse(height),
+            parse(numerator),
+            parse(denominator),
+        ) {
+            (Some(width), Some(height), Some(numerator), Some(denominator)) => Ok(Self {
+                width,
+                height,
+                frame_rate: [numerator, denominator],
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Seconds from the start of the video to frame `index`, computed for every frame
+    /// so rates like 30000/1001 don't drift
+    pub fn time(&self, index: u32) -> f64 {
+        let [numerator, denominator] = self.frame_rate;
+        f64::from(index) * f64::from(denominator) / f64::from(numerator)
+    }
+
+    fn frame_rate_arg(&self) -> String {
+        let [numerator, denominator] = self.frame_rate;
+        format!("{numerator}/{denominator}")
+    }
+
+    fn frame_size(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+}
+
+/// Frames of a video decoded by ffmpeg, as 8-bit sRGB RGBA
+pub struct VideoDecoder {
+    info: VideoInfo,
+    ffmpeg: Child,
+    stdout: ChildStdout,
+}
+
+impl VideoDecoder {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let info = VideoInfo::probe(path)?;
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-i"])
+            .arg(path)
+            .args(["-map", "0:v:0", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            // Frames are dropped or repeated to the probed rate, so each one is 1 / rate seconds
+            .args(["-r", &info.frame_rate_arg(), "-"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| io::Error::new(err.kind(), format!("Failed to run ffmpeg: {err}")))?;
+        let stdout = ffmpeg.stdout.take().unwrap();
+        Ok(Self {
+            info,
+            ffmpeg,
+            stdout,
+        })
+    }
+
+    pub fn info(&self) -> VideoInfo {
+        self.info
+    }
+
+    /// The next frame, `None` after the last one
+    pub fn read_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut pixels = vec![0; self.info.frame_size()];
+        match self.stdout.read_exact(&mut pixels) {
+            Ok(()) => Ok(Some(pixels)),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Wait for ffmpeg after the last frame was read
+    pub fn finish(mut self) -> io::Result<()> {
+        let status = self.ffmpeg.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "ffmpeg failed decoding with {status}"
+            )))
+        }
+    }
+}
+
+/// Audio added to an encoded video
+#[derive(Clone, Copy, Debug)]
+pub enum AudioTrack<'a> {
+    /// The audio of another video, left out if it has none
+    CopiedFrom(&'a Path),
+    /// An audio file starting `offset` seconds into the video.
+    /// The video sets the length: shorter audio is padded with silence, longer audio is cut
+    Padded { path: &'a Path, offset: f64 },
+}
+
+/// Encodes 8-bit sRGB RGBA frames with ffmpeg, which picks the codecs from the extension of the output
+pub struct VideoEncoder {
+    ffmpeg: Child,
+    stdin: ChildStdin,
+}
+
+impl VideoEncoder {
+    /// Write frames of the size and rate of `info` to `output`, along with the `audio`
+    pub fn new(output: &Path, info: VideoInfo, audio: Option<AudioTrack>) -> io::Result<Self> {
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", info.width, info.height)])
+            .args(["-framerate", &info.frame_rate_arg(), "-i", "-"]);
+        match audio {
+            Some(AudioTrack::CopiedFrom(video)) => {
+                command
+                    .arg("-i")
+                    .arg(video)
+                    // The `?` leaves out the audio of videos without any
+                    .args(["-map", "0:v", "-map", "1:a?", "-c:a", "copy"]);
+            }
+            Some(AudioTrack::Padded { path, offset }) => {
+                command
+                    .args(["-itsoffset", &offset.to_string(), "-i"])
+                    .arg(path)
+                    .args(["-map", "0:v", "-map", "1:a", "-af", "apad", "-shortest"]);
+            }
+            None => {}
+        }
+        let mut ffmpeg = command
+            // Most players only handle 4:2:0 chroma subsampling, which needs even sizes
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+            .args(["-pix_fmt", "yuv420p"])
+            .arg(output)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| io::Error::new(err.kind(), format!("Failed to run ffmpeg: {err}")))?;
+        let stdin = ffmpeg.stdin.take().unwrap();
+        Ok(Self { ffmpeg, stdin })
+    }
+
+    pub fn write_frame(&mut self, pixels: &[u8]) -> io::Result<()> {
+        self.stdin.write_all(pixels)
+    }
+
+    /// Let ffmpeg finish the file after the last frame
+    pub fn finish(self) -> io::Result<()> {
+        let Self { mut ffmpeg, stdin } = self;
+        // Closing stdin ends the input
+        drop(stdin);
+        let status = ffmpeg.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "ffmpeg failed encoding with {status}"
+            )))
+        }
+    }
+}
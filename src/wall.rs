@@ -0,0 +1,211 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+/// Largest sync message, with room for long shader paths
+const MAX_MESSAGE_SIZE: usize = 4096;
+/// Messages up to this many frames older than the last one were reordered on the way and are dropped,
+/// messages even older come from a leader that started over
+const REORDER_WINDOW: u32 = 60;
+
+/// One screen of a video wall spanning several machines.
+/// Every screen draws its part of one image of the size of the whole wall
+#[derive(Clone, Copy, Debug)]
+pub struct WallTile {
+    /// Screens in each direction
+    pub grid: [u32; 2],
+    /// Column and row of this screen, counting from 1
+    pub tile: [u32; 2],
+}
+
+impl WallTile {
+    pub fn new(grid: [u32; 2], tile: [u32; 2]) -> Result<Self, String> {
+        if tile[0] > grid[0] || tile[1] > grid[1] {
+            return Err(format!(
+                "tile {}x{} is outside of a {}x{} wall",
+                tile[0], tile[1], grid[0], grid[1]
+            ));
+        }
+        Ok(Self { grid, tile })
+    }
+
+    /// Top left pixel of this screen's part, for screens of `width` × `height` pixels
+    pub fn offset(&self, width: u32, height: u32) -> [u32; 2] {
+        [(self.tile[0] - 1) * width, (self.tile[1] - 1) * height]
+    }
+}
+
+/// What followers take over from the leader, sent every frame
+#[derive(Debug, PartialEq)]
+pub struct SyncMessage {
+    pub frame: u32,
+    pub time: f32,
+    pub seed: u32,
+    pub params: [f32; 2],
+    /// Preset, example or shader file, see [`SceneConfig::scene_name`](crate::scene::SceneConfig::scene_name)
+    pub scene: String,
+}
+
+impl SyncMessage {
+    /// A line of space separated fields, the scene last as it may contain spaces
+    fn encode(&self) -> String {
+        let [param_x, param_y] = self.params;
+        format!(
+            "{} {} {} {param_x} {param_y} {}",
+            self.frame, self.time, self.seed, self.scene
+        )
+    }
+
+    fn decode(message: &str) -> Option<Self> {
+        let mut fields = message.splitn(6, ' ');
+        let mut next = || fields.next();
+        Some(Self {
+            frame: next()?.parse().ok()?,
+            time: next()?.parse().ok()?,
+            seed: next()?.parse().ok()?,
+            params: [next()?.parse().ok()?, next()?.parse().ok()?],
+            scene: next()?.to_owned(),
+        })
+    }
+}
+
+/// Broadcasts the state of every frame to the followers over UDP
+pub struct SyncLeader {
+    socket: UdpSocket,
+    target: SocketAddr,
+    /// Failures are only logged once, as they would repeat every frame
+    failed: bool,
+}
+
+impl SyncLeader {
+    /// Send to `target`, usually the broadcast address of the network
+    pub fn new(target: SocketAddr) -> io::Result<Self> {
+        let local = match target {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.set_broadcast(true)?;
+        Ok(Self {
+            socket,
+            target,
+            failed: false,
+        })
+    }
+
+    pub fn send(&mut self, message: &SyncMessage) {
+        match self
+            .socket
+            .send_to(message.encode().as_bytes(), self.target)
+        {
+            Ok(_) => self.failed = false,
+            Err(err) if !self.failed => {
+                log::error!("Failed to send sync message to {}: {err}", self.target);
+                self.failed = true;
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Receives the leader's state without blocking
+pub struct SyncFollower {
+    /// Listening on IPv6 and IPv4, a single dual-stack socket where the system binds `[::]` to both
+    sockets: Vec<UdpSocket>,
+    /// Frame of the newest message received
+    last_frame: Option<u32>,
+}
+
+impl SyncFollower {
+    /// Listen on `port` for leaders sending over IPv6 or IPv4
+    pub fn new(port: u16) -> io::Result<Self> {
+        // Binding IPv4 fails once `[::]` took both, it is only needed where `[::]` is IPv6 only
+        let v6 = UdpSocket::bind(("::", port));
+        let v4 = UdpSocket::bind(("0.0.0.0", port));
+        let sockets = match (v6, v4) {
+            (Err(err), Err(_)) => return Err(err),
+            (v6, v4) => [v6, v4].into_iter().flatten().collect::<Vec<_>>(),
+        };
+        for socket in &sockets {
+            socket.set_nonblocking(true)?;
+        }
+        Ok(Self {
+            sockets,
+            last_frame: None,
+        })
+    }
+
+    /// The newest message that arrived since the last call, skipping frames the follower fell behind on
+    /// and frames older than ones already received
+    pub fn receive(&mut self) -> Option<SyncMessage> {
+        let mut buffer = [0; MAX_MESSAGE_SIZE];
+        let mut latest = None;
+        for socket in &self.sockets {
+            loop {
+                match socket.recv(&mut buffer) {
+                    Ok(size) => match std::str::from_utf8(&buffer[..size])
+                        .ok()
+                        .and_then(SyncMessage::decode)
+                    {
+                        Some(message) if !is_reordered(message.frame, self.last_frame) => {
+                            self.last_frame = Some(message.frame);
+                            latest = Some(message);
+                        }
+                        Some(_) => {}
+                        None => log::warn!("Ignoring a malformed sync message"),
+                    },
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => {
+                        log::error!("Failed to receive sync messages: {err}");
+                        break;
+                    }
+                }
+            }
+        }
+        latest
+    }
+}
+
+/// Whether `frame` arrived after the newer `last_frame`
+fn is_reordered(frame: u32, last_frame: Option<u32>) -> bool {
+    last_frame.is_some_and(|last| frame < last && last - frame <= REORDER_WINDOW)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let message = SyncMessage {
+            frame: 1200,
+            time: 20.5,
+            seed: 7,
+            params: [0.25, 0.75],
+            scene: "shaders/my tunnel.wgsl".to_owned(),
+        };
+        assert_eq!(SyncMessage::decode(&message.encode()), Some(message));
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        assert_eq!(SyncMessage::decode(""), None);
+        assert_eq!(SyncMessage::decode("12 0.5 7 0.1"), None);
+        assert_eq!(SyncMessage::decode("twelve 0.5 7 0.1 0.2 drawing"), None);
+        assert_eq!(SyncMessage::decode("-1 0.5 7 0.1 0.2 drawing"), None);
+    }
+
+    #[test]
+    fn only_recent_older_frames_count_as_reordered() {
+        assert!(!is_reordered(100, None));
+        assert!(!is_reordered(100, Some(100)));
+        assert!(!is_reordered(101, Some(100)));
+        assert!(is_reordered(99, Some(100)));
+        assert!(is_reordered(100, Some(100 + REORDER_WINDOW)));
+        assert!(!is_reordered(100, Some(100 + REORDER_WINDOW + 1)));
+        // A leader starting over from frame 0 long after its last message
+        assert!(!is_reordered(0, Some(5000)));
+        assert!(is_reordered(0, Some(REORDER_WINDOW)));
+    }
+}
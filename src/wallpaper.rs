@@ -0,0 +1,148 @@
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event_loop::ActiveEventLoop,
+    window::{Window, WindowAttributes, WindowLevel},
+};
+
+/// Window settings for `--wallpaper`: no decorations, covering the primary monitor, below every other window.
+///
+/// On X11 the window becomes a desktop window, which window managers keep behind the desktop icons.
+/// Wayland has no way to do that without the layer-shell protocol, which winit doesn't speak,
+/// so there the window is only kept at the bottom
+pub fn window_attributes(
+    attributes: WindowAttributes,
+    event_loop: &ActiveEventLoop,
+) -> WindowAttributes {
+    let mut attributes = attributes
+        .with_decorations(false)
+        .with_window_level(WindowLevel::AlwaysOnBottom)
+        .with_active(false);
+    if let Some(monitor) = event_loop
+        .primary_monitor()
+        .or_else(|| event_loop.available_monitors().next())
+    {
+        let position: PhysicalPosition<i32> = monitor.position();
+        let size: PhysicalSize<u32> = monitor.size();
+        attributes = attributes.with_position(position).with_inner_size(size);
+    }
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    {
+        use winit::platform::{
+            wayland::ActiveEventLoopExtWayland,
+            x11::{WindowAttributesExtX11, WindowType},
+        };
+        if event_loop.is_wayland() {
+            log::warn!(
+                "Wayland windows can't be put behind the desktop icons, the wallpaper is kept below other windows instead"
+            );
+        } else {
+            attributes = attributes.with_x11_window_type(vec![WindowType::Desktop]);
+        }
+    }
+    attributes
+}
+
+/// Move the created window behind the desktop icons where that needs the window itself
+pub fn attach(window: &Window) {
+    #[cfg(target_os = "windows")]
+    {
+        use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+        match window.window_handle().map(|handle| handle.as_raw()) {
+            Ok(RawWindowHandle::Win32(handle)) => {
+                if !win32::attach_to_desktop(handle.hwnd.get()) {
+                    log::warn!("The desktop window wasn't found, the wallpaper is a normal window");
+                }
+            }
+            _ => log::warn!("The window has no Win32 handle to put behind the desktop icons"),
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    let _ = window;
+}
+
+/// The undocumented dance of wallpaper engines: Progman, the window drawing the desktop, is asked
+/// to create a WorkerW window between the wallpaper and the icons, which the window is then parented to
+#[cfg(target_os = "windows")]
+mod win32 {
+    use std::ptr;
+
+    type Hwnd = isize;
+
+    /// Makes Progman create the WorkerW window behind the icons
+    const SPAWN_WORKERW: u32 = 0x052C;
+    const SMTO_NORMAL: u32 = 0;
+
+    #[link(name = "user32")]
+    unsafe extern "system" {
+        fn FindWindowW(class_name: *const u16, window_name: *const u16) -> Hwnd;
+        fn FindWindowExW(
+            parent: Hwnd,
+            child_after: Hwnd,
+            class_name: *const u16,
+            window_name: *const u16,
+        ) -> Hwnd;
+        fn SendMessageTimeoutW(
+            window: Hwnd,
+            message: u32,
+            wparam: usize,
+            lparam: isize,
+            flags: u32,
+            timeout: u32,
+            result: *mut usize,
+        ) -> isize;
+        fn EnumWindows(callback: extern "system" fn(Hwnd, isize) -> i32, lparam: isize) -> i32;
+        fn SetParent(child: Hwnd, parent: Hwnd) -> Hwnd;
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain([0]).collect()
+    }
+
+    /// Finds the WorkerW after the top-level window holding the icons, `SHELLDLL_DefView`
+    extern "system" fn find_workerw(window: Hwnd, workerw: isize) -> i32 {
+        let workerw = workerw as *mut Hwnd;
+        // SAFETY: `workerw` is the `&mut Hwnd` `attach_to_desktop` passed to `EnumWindows`,
+        // which only calls back before it returns, so the pointer is still valid and not aliased.
+        // The class names are NUL-terminated and outlive the calls
+        unsafe {
+            let icons = FindWindowExW(window, 0, wide("SHELLDLL_DefView").as_ptr(), ptr::null());
+            if icons != 0 {
+                *workerw = FindWindowExW(0, window, wide("WorkerW").as_ptr(), ptr::null());
+            }
+        }
+        1
+    }
+
+    /// Parent `window` to the WorkerW behind the desktop icons, returns whether it was found
+    pub fn attach_to_desktop(window: isize) -> bool {
+        // SAFETY: the strings are NUL-terminated and outlive the calls, `result` and `workerw` outlive them too.
+        // Window handles that went away in between only make the calls fail
+        unsafe {
+            let progman = FindWindowW(wide("Progman").as_ptr(), ptr::null());
+            if progman == 0 {
+                return false;
+            }
+            let mut result = 0;
+            SendMessageTimeoutW(progman, SPAWN_WORKERW, 0, 0, SMTO_NORMAL, 1000, &mut result);
+
+            let mut workerw: Hwnd = 0;
+            EnumWindows(find_workerw, &mut workerw as *mut Hwnd as isize);
+            if workerw == 0 {
+                // Windows 11 24H2 creates the WorkerW as a child of Progman instead
+                workerw = FindWindowExW(progman, 0, wide("WorkerW").as_ptr(), ptr::null());
+            }
+            if workerw == 0 {
+                return false;
+            }
+            SetParent(window, workerw);
+            true
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use clap::Parser;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlCanvasElement;
+use winit::{event_loop::EventLoop, platform::web::WindowAttributesExtWebSys, window::Window};
+
+use crate::{app, cli::Options};
+
+/// Id of the canvas to draw into, one is appended to the page if there is none
+const CANVAS_ID: &str = "gpgpu-canvas";
+
+/// Entry point in the browser, taking the options from the query string of the page
+pub fn main() {
+    console_error_panic_hook::set_once();
+    console_log::init_with_level(log::Level::Info).expect("Failed to set up logging");
+
+    let options = Options::try_parse_from(query_args()).unwrap_or_else(|err| {
+        log::error!("Ignoring the query string: {err}");
+        Options::parse_from([env!("CARGO_PKG_NAME")])
+    });
+
+    let canvas = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id(CANVAS_ID))
+        .and_then(|element| element.dyn_into::<HtmlCanvasElement>().ok());
+    let event_loop = EventLoop::with_user_event().build().unwrap();
+    app::run_app(
+        event_loop,
+        options,
+        Box::new(move |event_loop| {
+            let attributes = Window::default_attributes()
+                .with_title(app::TITLE)
+                .with_inner_size(winit::dpi::LogicalSize::new(app::WIDTH, app::HEIGHT))
+                .with_append(canvas.is_none())
+                .with_canvas(canvas);
+            event_loop.create_window(attributes).unwrap()
+        }),
+    );
+}
+
+/// Command line arguments from the query string, `?example=plasma&downlevel`
+/// is read like `--example plasma --downlevel`
+fn query_args() -> Vec<String> {
+    let search = web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .unwrap_or_default();
+    let decode = |component: &str| {
+        js_sys::decode_uri_component(&component.replace('+', " "))
+            .map(String::from)
+            .unwrap_or_else(|_| component.to_owned())
+    };
+
+    let mut args = vec![env!("CARGO_PKG_NAME").to_owned()];
+    for pair in search.trim_start_matches('?').split('&') {
+        match pair.split_once('=') {
+            Some((name, value)) => args.extend([format!("--{}", decode(name)), decode(value)]),
+            None if !pair.is_empty() => args.push(format!("--{}", decode(pair))),
+            None => {}
+        }
+    }
+    args
+}